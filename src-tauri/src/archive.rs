@@ -0,0 +1,90 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+/// A zip of many small files, uploaded once and expanded by the server.
+/// The file is removed when this is dropped.
+pub struct BatchArchive {
+    pub path: PathBuf,
+    pub file_count: usize,
+}
+
+impl Drop for BatchArchive {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to remove batch archive {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Zip `files` into a temporary archive. Each entry is `(absolute path, name
+/// inside the archive)`; names keep the folder-relative layout so the server
+/// can attribute expanded documents to their original location.
+pub fn build_archive(files: &[(PathBuf, String)]) -> Result<BatchArchive, String> {
+    let path = std::env::temp_dir().join(format!("exemem-batch-{}.zip", uuid::Uuid::new_v4()));
+    let archive = BatchArchive {
+        path,
+        file_count: files.len(),
+    };
+
+    let file = File::create(&archive.path)
+        .map_err(|e| format!("Failed to create batch archive: {}", e))?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for (source, name) in files {
+        add_file(&mut zip, source, name, options)?;
+    }
+
+    zip.finish()
+        .and_then(|mut writer| writer.flush().map_err(Into::into))
+        .map_err(|e| format!("Failed to finish batch archive: {}", e))?;
+    Ok(archive)
+}
+
+fn add_file<W: Write + std::io::Seek>(
+    zip: &mut ZipWriter<W>,
+    source: &Path,
+    name: &str,
+    options: SimpleFileOptions,
+) -> Result<(), String> {
+    let bytes = std::fs::read(source)
+        .map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    // Zip entry names always use forward slashes
+    zip.start_file(name.replace('\\', "/"), options)
+        .map_err(|e| format!("Failed to add {} to archive: {}", name, e))?;
+    zip.write_all(&bytes)
+        .map_err(|e| format!("Failed to add {} to archive: {}", name, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_contains_files_and_is_removed() {
+        let dir = std::env::temp_dir().join(format!("exemem-archive-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("chats")).unwrap();
+        std::fs::write(dir.join("chats/a.json"), b"{\"a\": 1}").unwrap();
+        std::fs::write(dir.join("b.txt"), b"hello").unwrap();
+
+        let archive = build_archive(&[
+            (dir.join("chats/a.json"), "chats/a.json".to_string()),
+            (dir.join("b.txt"), "b.txt".to_string()),
+        ])
+        .unwrap();
+        let path = archive.path.clone();
+
+        let mut zip = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(zip.len(), 2);
+        let mut contents = String::new();
+        std::io::Read::read_to_string(&mut zip.by_name("b.txt").unwrap(), &mut contents).unwrap();
+        assert_eq!(contents, "hello");
+
+        drop(archive);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,197 @@
+//! Many mutations from NDJSON, applied a few at a time, for loading existing
+//! datasets. One bad line doesn't stop the rest; the report says which
+//! lines went in and which didn't.
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::query::{self, QueryClient};
+use crate::retry::{self, RetryError, RetryPolicy};
+
+/// Most mutations one batch may have in flight
+pub const MAX_CONCURRENCY: usize = 32;
+
+/// How hard a batch pushes the server.
+#[derive(Debug, Clone, Copy)]
+pub struct BatchOptions {
+    /// Mutations in flight at once, up to `MAX_CONCURRENCY`
+    pub concurrency: usize,
+    /// Applied to each line; transient failures are retried
+    pub retry: RetryPolicy,
+}
+
+/// A line that couldn't be applied.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LineFailure {
+    /// 1-based line number in the input
+    pub line: usize,
+    pub error: String,
+    pub kind: &'static str,
+}
+
+/// Counts so far, for a progress line.
+#[derive(Debug, Clone, Copy, Default, Serialize, PartialEq)]
+pub struct BatchProgress {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct BatchReport {
+    /// Records read, not counting blank lines
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Line numbers applied, in order
+    pub succeeded_lines: Vec<usize>,
+    /// Lines that weren't, in order
+    pub failures: Vec<LineFailure>,
+}
+
+impl BatchReport {
+    fn progress(&self) -> BatchProgress {
+        BatchProgress {
+            succeeded: self.succeeded,
+            failed: self.failed,
+        }
+    }
+
+    fn fail(&mut self, line: usize, err: &Error) {
+        self.failed += 1;
+        self.failures.push(LineFailure {
+            line,
+            error: err.to_string(),
+            kind: err.kind(),
+        });
+    }
+}
+
+/// Apply one `operation` on `schema` per NDJSON record from `input`, with
+/// at most `options.concurrency` in flight. Transient failures are retried,
+/// reusing the line's idempotency key. Only failing to read `input` is an
+/// error; bad records are reported per line.
+pub async fn mutate_ndjson<R, F>(
+    client: &QueryClient,
+    config: &AppConfig,
+    schema: &str,
+    operation: &str,
+    input: R,
+    options: BatchOptions,
+    mut on_progress: F,
+) -> Result<BatchReport, Error>
+where
+    R: AsyncBufRead + Unpin,
+    F: FnMut(BatchProgress),
+{
+    let concurrency = options.concurrency.clamp(1, MAX_CONCURRENCY);
+    let policy = &options.retry;
+    let apply = |line: usize, record: Value| async move {
+        let key = uuid::Uuid::new_v4().to_string();
+        let result = retry::with_backoff(policy, query::transient, || {
+            client.mutate_once(config, schema, operation, record.clone(), &key)
+        })
+        .await
+        .map_err(|err| match err {
+            RetryError::Permanent(err) | RetryError::Exhausted { error: err, .. } => err,
+        })
+        .and_then(|resp| {
+            if resp.success {
+                return Ok(());
+            }
+            Err(Error::Server {
+                status: None,
+                message: resp.message.unwrap_or_else(|| "Mutation was not applied".to_string()),
+            })
+        });
+        (line, result)
+    };
+
+    let mut report = BatchReport::default();
+    let mut lines = input.lines();
+    let mut line = 0;
+    let mut in_flight = FuturesUnordered::new();
+    let mut read_all = false;
+    loop {
+        while !read_all && in_flight.len() < concurrency {
+            let Some(text) = lines
+                .next_line()
+                .await
+                .map_err(|e| Error::Io(format!("Failed to read records: {}", e)))?
+            else {
+                read_all = true;
+                break;
+            };
+            line += 1;
+            if text.trim().is_empty() {
+                continue;
+            }
+            report.total += 1;
+            match serde_json::from_str(&text) {
+                Ok(record) => in_flight.push(apply(line, record)),
+                Err(e) => {
+                    report.fail(line, &Error::Validation(format!("Invalid JSON: {}", e)));
+                    on_progress(report.progress());
+                }
+            }
+        }
+
+        let Some((line, result)) = in_flight.next().await else {
+            break;
+        };
+        match result {
+            Ok(()) => {
+                report.succeeded += 1;
+                report.succeeded_lines.push(line);
+            }
+            Err(e) => report.fail(line, &e),
+        }
+        on_progress(report.progress());
+    }
+
+    report.succeeded_lines.sort_unstable();
+    report.failures.sort_by_key(|f| f.line);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_bad_lines_are_reported_without_stopping() {
+        let input: &[u8] = b"{not json\n\n[1, 2]\n";
+        let options = BatchOptions {
+            concurrency: 4,
+            retry: RetryPolicy {
+                max_attempts: 1,
+                base_delay: Duration::from_millis(1),
+                max_busy_waits: 0,
+            },
+        };
+        let mut updates = 0;
+        let report = mutate_ndjson(
+            &QueryClient::new(),
+            &AppConfig::default(),
+            "notes",
+            "insert",
+            input,
+            options,
+            |_| updates += 1,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.total, 2);
+        assert_eq!(report.failed, 2);
+        assert!(report.succeeded_lines.is_empty());
+        let lines: Vec<usize> = report.failures.iter().map(|f| f.line).collect();
+        assert_eq!(lines, [1, 3]);
+        assert!(report.failures.iter().all(|f| f.kind == "validation"));
+        assert_eq!(updates, 2);
+    }
+}
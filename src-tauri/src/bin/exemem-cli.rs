@@ -1,107 +1,47 @@
-use clap::{Parser, Subcommand};
-use exemem_client_lib::query::QueryClient;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use clap::{Parser, Subcommand, ValueEnum};
+use exemem_client_lib::batch;
+use exemem_client_lib::cancel;
+use exemem_client_lib::config::{AppConfig, Environment};
+use exemem_client_lib::config_check;
+use exemem_client_lib::capabilities::Capabilities;
+use exemem_client_lib::dead_letter;
+use exemem_client_lib::doctor;
+use exemem_client_lib::env_config::EnvOverrides;
+use exemem_client_lib::error::{Error, EXIT_CONFIG, EXIT_PARTIAL};
+use exemem_client_lib::export::{self, ExportFormat};
+use exemem_client_lib::headless;
+use exemem_client_lib::http;
+use exemem_client_lib::ledger::{self, HistoryFilter};
+use exemem_client_lib::logs::{self, LogDirectives, LogFilter};
+use exemem_client_lib::paths;
+use exemem_client_lib::query::{QueryClient, SearchFilters};
+use exemem_client_lib::query_metrics::{self, QueryMetrics};
+use exemem_client_lib::redact::{self, redact};
+use exemem_client_lib::retry::RetryPolicy;
+use exemem_client_lib::saved_queries::SavedQueries;
+use exemem_client_lib::scanner::{self, ScanResult};
+use exemem_client_lib::search::{self, SearchHit};
+use exemem_client_lib::self_update;
+use exemem_client_lib::sessions;
+use exemem_client_lib::simulate::{self, SimulationConfig};
+use exemem_client_lib::stats;
+use exemem_client_lib::storage::{ExememApiStore, ExememAuth, ExememNamespacedStore};
+use exemem_client_lib::status;
+use exemem_client_lib::transcripts::{self, QueryHistoryFilter, TranscriptStore};
+use exemem_client_lib::upload_queue::UploadPriority;
+use exemem_client_lib::uploader::{is_success_status, UploadProgressFn, UploadStatus};
+use fold_db::storage::error::StorageError;
+use fold_db::storage::traits::{KvStore, NamespacedStore};
+use serde::Serialize;
 use serde_json::Value;
-
-// Re-use config from the library crate
-// Note: config is private in lib, so we replicate the load path here
-use directories::ProjectDirs;
-use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::sync::Arc;
 
-const DEV_API_URL: &str = "https://ygyu7ritx8.execute-api.us-west-2.amazonaws.com";
-const PROD_API_URL: &str = "https://jdsx4ixk2i.execute-api.us-east-1.amazonaws.com";
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-enum Environment {
-    Dev,
-    Prod,
-    Custom,
-}
-
-impl Default for Environment {
-    fn default() -> Self {
-        Self::Dev
-    }
-}
-
-fn default_true() -> bool {
-    true
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct CliConfig {
-    api_base_url: String,
-    api_key: String,
-    watched_folder: Option<PathBuf>,
-    auto_ingest: bool,
-    #[serde(default = "default_true")]
-    auto_approve_watched: bool,
-    #[serde(default)]
-    environment: Environment,
-    #[serde(default)]
-    session_token: Option<String>,
-    #[serde(default)]
-    user_hash: Option<String>,
-}
-
-impl Default for CliConfig {
-    fn default() -> Self {
-        Self {
-            api_base_url: String::new(),
-            api_key: String::new(),
-            watched_folder: None,
-            auto_ingest: true,
-            auto_approve_watched: true,
-            environment: Environment::default(),
-            session_token: None,
-            user_hash: None,
-        }
-    }
-}
-
-impl CliConfig {
-    fn config_path() -> Result<PathBuf, String> {
-        let dirs = ProjectDirs::from("ai", "exemem", "exemem-client")
-            .ok_or_else(|| "Could not determine config directory".to_string())?;
-        Ok(dirs.config_dir().join("config.json"))
-    }
-
-    fn load() -> Result<Self, String> {
-        let path = Self::config_path()?;
-        if !path.exists() {
-            return Ok(Self::default());
-        }
-        let data = std::fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read config: {}", e))?;
-        serde_json::from_str(&data)
-            .map_err(|e| format!("Failed to parse config: {}", e))
-    }
-
-    fn save(&self) -> Result<(), String> {
-        let path = Self::config_path()?;
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create config dir: {}", e))?;
-        }
-        let data = serde_json::to_string_pretty(self)
-            .map_err(|e| format!("Failed to serialize config: {}", e))?;
-        std::fs::write(&path, data)
-            .map_err(|e| format!("Failed to write config: {}", e))
-    }
-
-    fn api_url(&self) -> &str {
-        match self.environment {
-            Environment::Dev => DEV_API_URL,
-            Environment::Prod => PROD_API_URL,
-            Environment::Custom => &self.api_base_url,
-        }
-    }
-}
-
-/// Adapter to convert CliConfig into the library's AppConfig-compatible struct
-/// for QueryClient methods
+/// Adapter from the shared AppConfig to the query client's CLI config
 struct ConfigAdapter<'a> {
-    config: &'a CliConfig,
+    config: &'a AppConfig,
 }
 
 impl<'a> ConfigAdapter<'a> {
@@ -110,6 +50,9 @@ impl<'a> ConfigAdapter<'a> {
             api_url: self.config.api_url().to_string(),
             api_key: self.config.api_key.clone(),
             user_hash: self.config.user_hash.clone(),
+            sign_requests: self.config.signer().is_some(),
+            custom_categories: self.config.scanner.category_names(),
+            locale: self.config.locale.clone(),
         }
     }
 }
@@ -118,7 +61,52 @@ impl<'a> ConfigAdapter<'a> {
 #[command(name = "exemem-cli")]
 #[command(about = "Exemem CLI — Query, search, and mutate your Exemem data")]
 #[command(version)]
+#[command(after_help = "\
+Exit codes:
+  0    success
+  1    internal: anything unexpected
+  2    validation: bad arguments or input
+  3    auth: missing or rejected credentials
+  4    network, timeout: the backend couldn't be reached in time
+  5    server: the backend answered with an error
+  6    io: local files couldn't be read or written
+  7    config: the configuration is missing or unusable
+  8    partial: items of a batch failed; the report on stdout says which
+  130  cancelled
+
+Errors are printed on stderr as JSON:
+  {\"error\": \"<message>\", \"kind\": \"<kind>\", \"exit_code\": <code>, \"status\": <HTTP status, server errors only>}")]
 struct Cli {
+    /// Keep config and data next to the executable instead of the user
+    /// profile (EXEMEM_DATA_DIR takes precedence)
+    #[arg(long, global = true)]
+    portable: bool,
+    /// Give up on backend requests after this many seconds (default: the
+    /// configured query timeout)
+    #[arg(long, global = true)]
+    timeout: Option<u64>,
+    /// Use this saved profile's environment, API key and watched folder
+    /// instead of the current ones
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// How to print results: pretty JSON, one JSON value per line for jq,
+    /// aligned columns, CSV, or plain text
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Json)]
+    format: OutputFormat,
+    /// Log more on stderr: -v for info, -vv for debug, -vvv for trace
+    #[arg(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+    /// Log nothing on stderr
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Also append logs to this file, as JSON lines like the app's log
+    /// (at least info level)
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+    /// Log levels per module, such as "warn,exemem_client_lib::uploader=debug"
+    /// (default: the EXEMEM_LOG environment variable)
+    #[arg(long, global = true)]
+    log_filter: Option<String>,
     #[command(subcommand)]
     command: Commands,
 }
@@ -127,16 +115,55 @@ struct Cli {
 enum Commands {
     /// Run a natural language query against your data
     Query {
-        /// The query string
-        query: String,
+        /// The query string, or `-` to read it from stdin
+        #[arg(required_unless_present = "query_file", conflicts_with = "query_file")]
+        query: Option<String>,
+        /// Read the query from this file (`-` for stdin)
+        #[arg(long)]
+        query_file: Option<PathBuf>,
         /// Session ID for follow-up queries
         #[arg(long)]
         session_id: Option<String>,
+        /// Also write the results to this file (.json, .csv or .md)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
     },
     /// Search the native word index
     Search {
         /// The search term
         term: String,
+        /// Only records from files in this scanner category (e.g. personal_data)
+        #[arg(long)]
+        category: Option<String>,
+        /// Only records ingested on or after this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only records ingested before this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        until: Option<String>,
+        /// Only records from files whose name contains this text
+        #[arg(long)]
+        source_file: Option<String>,
+        /// Print one line per hit with matched terms highlighted instead of JSON
+        #[arg(long)]
+        highlight: bool,
+        /// Also write the results to this file (.json, .csv or .md)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    /// Find documents by meaning rather than exact words
+    Semantic {
+        /// Text to find similar documents for
+        text: String,
+        /// Maximum number of documents to return (default 20)
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Print one line per hit instead of JSON
+        #[arg(long)]
+        plain: bool,
+        /// Also write the results to this file (.json, .csv or .md)
+        #[arg(long, short = 'o')]
+        output: Option<PathBuf>,
     },
     /// Execute a mutation against a schema
     Mutate {
@@ -146,9 +173,33 @@ enum Commands {
         /// Operation type (insert, update, delete)
         #[arg(long)]
         operation: String,
-        /// JSON data for the mutation
+        /// JSON data for the mutation, or `-` to read it from stdin. Several
+        /// records, one JSON value per line, are applied one after another.
+        #[arg(long, required_unless_present = "data_file", conflicts_with = "data_file")]
+        data: Option<String>,
+        /// Read the JSON data from this file (`-` for stdin)
         #[arg(long)]
-        data: String,
+        data_file: Option<PathBuf>,
+    },
+    /// Apply one mutation per line of an NDJSON file, several at a time,
+    /// retrying transient failures. Reports which lines succeeded and which
+    /// failed, and exits with 8 if any failed.
+    MutateBatch {
+        /// Target schema name
+        #[arg(long)]
+        schema: String,
+        /// Operation type (insert, update, delete)
+        #[arg(long)]
+        operation: String,
+        /// NDJSON records, one per line (`-` for stdin)
+        #[arg(long)]
+        file: PathBuf,
+        /// Mutations in flight at once
+        #[arg(long, default_value_t = 4)]
+        concurrency: usize,
+        /// Times to retry a record after a network or server error
+        #[arg(long, default_value_t = 3)]
+        retries: u32,
     },
     /// Ask a follow-up question in an existing session
     Chat {
@@ -158,11 +209,182 @@ enum Commands {
         /// The follow-up question
         question: String,
     },
+    /// Inspect the schemas available for mutations
+    Schema {
+        #[command(subcommand)]
+        action: SchemaCommands,
+    },
+    /// Manage and run saved queries
+    Saved {
+        #[command(subcommand)]
+        action: SavedCommands,
+    },
+    /// List failed uploads, or retry them
+    Failed {
+        /// Retry failed uploads instead of listing them
+        #[arg(long)]
+        retry: bool,
+        /// Only retry the failed uploads with these ids (default: all)
+        #[arg(long)]
+        id: Vec<String>,
+    },
+    /// List, show or delete conversations, from this machine's transcripts
+    /// and the backend
+    Sessions {
+        #[command(subcommand)]
+        action: SessionsCommands,
+    },
+    /// Browse local history
+    History {
+        #[command(subcommand)]
+        what: HistoryCommands,
+    },
+    /// Show upload volume and throughput per day
+    Stats {
+        /// Number of days to include, counting today
+        #[arg(long, default_value_t = 30, global = true)]
+        days: usize,
+        #[command(subcommand)]
+        what: Option<StatsCommands>,
+    },
+    /// Print the features, importers and auth methods this build supports
+    Capabilities,
+    /// Replay recorded ingestion progress traces through the upload scheduler
+    /// in simulated time. Record traces by running the app with
+    /// EXEMEM_RECORD_TRACES=1.
+    Simulate {
+        /// Trace file (JSON lines); defaults to the app's recorded traces
+        #[arg(long)]
+        trace: Option<std::path::PathBuf>,
+        /// Concurrent upload slots
+        #[arg(long)]
+        slots: Option<usize>,
+        /// Simulated upload time per file, in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        upload_ms: u64,
+        /// Stretch server indexing time by this factor
+        #[arg(long, default_value_t = 1.0)]
+        slowdown: f64,
+    },
+    /// Show the desktop app's log
+    Logs {
+        /// Keep printing new entries as they are written
+        #[arg(long, short)]
+        follow: bool,
+        /// Minimum level to show (error, warn, info, debug, trace)
+        #[arg(long, default_value = "info")]
+        level: String,
+        /// Only show entries whose module path contains this
+        #[arg(long)]
+        module: Option<String>,
+        /// Number of existing entries to show first
+        #[arg(long, short = 'n', default_value_t = 50)]
+        lines: usize,
+    },
+    /// Scan a folder and show which files would be ingested. Approved files
+    /// are uploaded right away.
+    Scan {
+        /// Folder to scan (default: the watched folder)
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Same as `--format table`
+        #[arg(long, hide = true)]
+        table: bool,
+        /// Ingest every recommended file
+        #[arg(long)]
+        approve_all: bool,
+        /// Ingest every file in this category, including ones the scanner
+        /// skips; repeat for more categories
+        #[arg(long)]
+        approve_category: Vec<String>,
+        /// Ask about each recommended file the other flags didn't approve
+        #[arg(long, short)]
+        interactive: bool,
+    },
+    /// Scan once, upload new or changed recommended files, wait for their
+    /// ingestion and print a summary. Meant for cron or Task Scheduler.
+    SyncOnce {
+        /// Folder to sync (default: the watched folder)
+        #[arg(long)]
+        path: Option<PathBuf>,
+        /// Stop waiting for ingestion after this many seconds
+        #[arg(long, default_value_t = 600)]
+        ingest_timeout: u64,
+    },
+    /// Upload and ingest specific files, or the recommended files in a
+    /// folder, showing progress for each. Exits non-zero if any fails.
+    Ingest {
+        /// Files or folders to ingest
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Upload without triggering ingestion
+        #[arg(long)]
+        no_auto_ingest: bool,
+    },
+    /// Watch the configured folder and upload new files without the desktop
+    /// app, printing one JSON line per event. Stops after the current upload
+    /// on Ctrl-C or SIGTERM.
+    Watch {
+        /// Run under a service manager: no hints on stderr, just the event
+        /// lines on stdout
+        #[arg(long)]
+        daemon: bool,
+        /// Print a service definition that runs `watch --daemon`, then exit
+        #[arg(long, value_enum)]
+        print_service: Option<ServiceManager>,
+    },
+    /// Show whether a watcher is running, pending work, the last upload and
+    /// error, whether the API is reachable and the account's usage
+    Status {
+        /// Only read local state; don't contact the API
+        #[arg(long)]
+        offline: bool,
+    },
+    /// Live dashboard of uploads, the queue, recent errors and a query
+    /// prompt, for terminals without a desktop
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Also run the watcher in the dashboard
+        #[arg(long)]
+        watch: bool,
+    },
+    /// Check the config, API, API key, watched folder, free disk space, clock
+    /// skew and (on Linux) inotify limits, with a hint for each problem.
+    /// Exits with 7 if any check fails.
+    Doctor {
+        #[command(subcommand)]
+        target: Option<DoctorCommands>,
+    },
+    /// Read and write raw keys through the Storage API, for debugging
+    /// code built on ExememApiStore. Values are base64 in the output.
+    Storage {
+        #[command(subcommand)]
+        action: StorageCommands,
+    },
+    /// Storage API namespaces
+    Namespaces {
+        #[command(subcommand)]
+        action: NamespacesCommands,
+    },
+    /// Replace this binary with the latest release for this platform, once
+    /// its SHA-256 matches the published one
+    SelfUpdate {
+        /// Only report whether an update is available
+        #[arg(long)]
+        check: bool,
+        /// Reinstall the latest release even if this one is as new
+        #[arg(long)]
+        force: bool,
+    },
     /// View or update configuration
     Config {
         /// Show current configuration
         #[arg(long)]
         show: bool,
+        /// Show the configuration commands actually use, and where each
+        /// value comes from (environment, profile, file or default)
+        #[arg(long)]
+        effective: bool,
         /// Set environment (Dev, Prod, Custom)
         #[arg(long)]
         env: Option<String>,
@@ -175,95 +397,1616 @@ enum Commands {
     },
 }
 
-fn error_json(msg: &str) -> ! {
-    let err = serde_json::json!({ "error": msg });
-    eprintln!("{}", serde_json::to_string_pretty(&err).unwrap());
-    std::process::exit(1);
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum OutputFormat {
+    Json,
+    Ndjson,
+    Table,
+    Csv,
+    Plain,
+}
+
+#[derive(Subcommand)]
+enum SchemaCommands {
+    /// List schemas
+    List,
+    /// Show the fields of a schema
+    Describe { name: String },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ServiceManager {
+    /// A systemd user unit (~/.config/systemd/user/exemem-watch.service)
+    Systemd,
+    /// A launchd agent (~/Library/LaunchAgents/com.exemem.watch.plist)
+    Launchd,
+}
+
+#[derive(Subcommand)]
+enum DoctorCommands {
+    /// Check required settings and the watched folder, and try the API key
+    /// against the configured API. Exits with 7 if any problem is an error.
+    Config,
+}
+
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// Print the values of one or more keys, or write a single key's value
+    /// to --output
+    Get {
+        #[arg(long)]
+        namespace: String,
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// The keys are base64, for keys that aren't text
+        #[arg(long)]
+        key_base64: bool,
+        /// Write the raw value to this file instead of printing it
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Set a key's value from --file, --value or --value-base64
+    Put {
+        #[arg(long)]
+        namespace: String,
+        key: String,
+        #[arg(long)]
+        key_base64: bool,
+        /// Read the value from this file ("-" for stdin)
+        #[arg(long, required_unless_present_any = ["value", "value_base64"], conflicts_with_all = ["value", "value_base64"])]
+        file: Option<PathBuf>,
+        /// The value as text
+        #[arg(long, conflicts_with = "value_base64")]
+        value: Option<String>,
+        /// The value as base64
+        #[arg(long)]
+        value_base64: Option<String>,
+    },
+    /// Delete a key
+    Delete {
+        #[arg(long)]
+        namespace: String,
+        key: String,
+        #[arg(long)]
+        key_base64: bool,
+    },
+    /// List keys starting with a prefix, one page at a time
+    ScanPrefix {
+        #[arg(long)]
+        namespace: String,
+        /// Empty lists the whole namespace
+        #[arg(default_value = "")]
+        prefix: String,
+        #[arg(long)]
+        key_base64: bool,
+        /// Most items to return
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+        /// Continue from the `next_cursor` of an earlier scan
+        #[arg(long)]
+        cursor: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum NamespacesCommands {
+    /// List every namespace
+    List,
+}
+
+#[derive(Subcommand)]
+enum SavedCommands {
+    /// Save a query under a name; `{param}` placeholders are filled in at run time
+    Save { name: String, query: String },
+    /// List saved queries
+    List,
+    /// Run a saved query
+    Run {
+        name: String,
+        /// Placeholder value as name=value; repeat for each placeholder
+        #[arg(long = "param", short = 'p')]
+        params: Vec<String>,
+    },
+    /// Delete a saved query
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum SessionsCommands {
+    /// Conversations, most recently active first
+    List {
+        /// Maximum number of sessions to show
+        #[arg(long, default_value_t = 50)]
+        limit: usize,
+        /// Only sessions recorded on this machine; don't contact the API
+        #[arg(long)]
+        local: bool,
+    },
+    /// Every turn of a session, oldest first (Markdown with --format plain)
+    Show { session_id: String },
+    /// Delete sessions here and on the backend
+    Delete {
+        /// Sessions to delete
+        #[arg(required_unless_present = "before")]
+        session_ids: Vec<String>,
+        /// Also delete every session last active before this date
+        /// (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        before: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum StatsCommands {
+    /// Upload volume and throughput (the default)
+    Uploads,
+    /// Calls, latency, results and token usage of queries, follow-ups and searches
+    Queries,
+}
+
+#[derive(Subcommand)]
+enum HistoryCommands {
+    /// List past uploads, newest first
+    Uploads {
+        /// Only uploads on or after this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only uploads before this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        until: Option<String>,
+        /// Only uploads with this status (e.g. done, error, ingesting)
+        #[arg(long)]
+        status: Option<String>,
+        /// Only uploads in this scanner category
+        #[arg(long)]
+        category: Option<String>,
+        /// Maximum number of entries to show
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// List past questions and their (truncated) answers, newest first
+    Queries {
+        /// Only entries whose question or answer contains all these words
+        search: Option<String>,
+        /// Only questions asked on or after this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only questions asked before this date (YYYY-MM-DD or Unix seconds)
+        #[arg(long)]
+        until: Option<String>,
+        /// Maximum number of entries to show
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+fn query_client(config: &AppConfig, timeout_secs: Option<u64>) -> QueryClient {
+    let timeout = cancel::query_timeout(config.query_timeout_secs, timeout_secs);
+    let client = QueryClient::with_client(http::build_client(&config.proxy, &config.tls, &config.connection))
+        .with_timeout(timeout);
+    // Metrics are best effort; without a data dir there's nowhere to keep them
+    match QueryMetrics::load().or_else(|_| QueryMetrics::empty()) {
+        Ok(metrics) => client.with_metrics(metrics),
+        Err(_) => client,
+    }
+}
+
+/// Client, base URL and credentials for the Storage API.
+fn storage_api(config: &AppConfig) -> (Arc<reqwest::Client>, String, ExememAuth) {
+    let auth = ExememAuth::from_config(config).unwrap_or_else(|| {
+        fail_config(Error::Validation("No API key or user hash configured".to_string()))
+    });
+    let client = Arc::new(http::build_client(&config.proxy, &config.tls, &config.connection));
+    (client, config.api_url().to_string(), auth)
+}
+
+fn storage_store(config: &AppConfig, namespace: &str, timeout_secs: Option<u64>) -> ExememApiStore {
+    let (client, base_url, auth) = storage_api(config);
+    let store = ExememApiStore::new(client, base_url, namespace.to_string(), auth);
+    match timeout_secs {
+        Some(secs) => store.with_timeout(std::time::Duration::from_secs(secs)),
+        None => store,
+    }
+}
+
+/// Fail with a Storage API error: a refused operation exits with 2, anything
+/// else with 4.
+fn storage_failure(err: StorageError) -> ! {
+    match err {
+        StorageError::InvalidOperation(msg) => invalid(msg),
+        other => fail(Error::Network(other.to_string())),
+    }
+}
+
+/// A key as given on the command line, text or (with `base64`) base64.
+fn storage_key(key: &str, base64: bool) -> Vec<u8> {
+    if !base64 {
+        return key.as_bytes().to_vec();
+    }
+    BASE64
+        .decode(key)
+        .unwrap_or_else(|e| invalid(format!("Invalid base64 key {:?}: {}", key, e)))
+}
+
+/// A key and value for output. `key` is null when the key isn't UTF-8.
+fn storage_item(key: &[u8], value: &[u8]) -> Value {
+    serde_json::json!({
+        "key": std::str::from_utf8(key).ok(),
+        "key_base64": BASE64.encode(key),
+        "value_base64": BASE64.encode(value),
+        "bytes": value.len(),
+    })
+}
+
+/// Resolved settings, each with where its value came from.
+fn effective_config(config: &AppConfig, profile: Option<&str>) -> Result<Value, Error> {
+    const PROFILE_FIELDS: &[&str] = &["environment", "api_base_url", "api_key", "user_hash", "watched_folder"];
+    let file = AppConfig::load_file_json()?;
+    let overridden = EnvOverrides::from_env().fields();
+    let source = |field: &str| match overridden.iter().find(|(f, _)| *f == field) {
+        Some((_, var)) => format!("env:{}", var),
+        None if profile.is_some() && PROFILE_FIELDS.contains(&field) => {
+            format!("profile:{}", profile.unwrap_or_default())
+        }
+        None if file.get(field).is_some() => "file".to_string(),
+        None => "default".to_string(),
+    };
+    // Dev and Prod URLs are built in, so they follow the environment
+    let url_source = match config.environment {
+        Environment::Custom => source("api_base_url"),
+        _ => source("environment"),
+    };
+    Ok(serde_json::json!({
+        "environment": { "value": format!("{:?}", config.environment), "source": source("environment") },
+        "api_url": { "value": config.api_url(), "source": url_source },
+        "api_key_set": { "value": !config.api_key.is_empty(), "source": source("api_key") },
+        "user_hash": { "value": config.user_hash, "source": source("user_hash") },
+        "watched_folder": { "value": config.watched_folder, "source": source("watched_folder") },
+        "auto_ingest": { "value": config.auto_ingest, "source": source("auto_ingest") },
+        "auto_approve_watched": { "value": config.auto_approve_watched, "source": source("auto_approve_watched") },
+    }))
+}
+
+/// Install the logger for `-v`/`--quiet`, `--log-file` and `--log-filter`.
+fn init_logging(cli: &Cli) {
+    use log::LevelFilter;
+    let stderr_level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::Off,
+        (false, 0) => LevelFilter::Warn,
+        (false, 1) => LevelFilter::Info,
+        (false, 2) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    };
+    let spec = cli.log_filter.clone().or_else(|| std::env::var("EXEMEM_LOG").ok()).unwrap_or_default();
+    let directives = LogDirectives::parse(&spec, stderr_level).unwrap_or_else(invalid);
+    // The file is an audit trail, so it gets at least info even when quiet
+    let file_level = if cli.quiet { LevelFilter::Info } else { stderr_level.max(LevelFilter::Info) };
+    let file = cli
+        .log_file
+        .as_deref()
+        .map(|path| (path, directives.with_default(file_level)));
+    let stderr = if cli.quiet { LogDirectives::new(LevelFilter::Off) } else { directives.clone() };
+    logs::init_cli_logger(stderr, file).unwrap_or_else(|e| local_failure(&e));
+}
+
+/// The effective config for `profile`, exiting on failure. Its credentials
+/// are masked in anything printed from then on.
+fn load_config(profile: Option<&str>) -> AppConfig {
+    let config = AppConfig::load_with_profile(profile).unwrap_or_else(fail_config);
+    redact::remember_secrets(&config);
+    config
+}
+
+/// Print the error JSON on stderr and exit with `code`. The shape is stable:
+/// `error`, `kind` and `exit_code` always, `status` for server errors.
+fn exit_with(kind: &str, code: i32, message: &str, status: Option<u16>) -> ! {
+    let mut out = serde_json::json!({ "error": redact(message), "kind": kind, "exit_code": code });
+    if let Some(status) = status {
+        out["status"] = status.into();
+    }
+    eprintln!("{}", serde_json::to_string_pretty(&out).unwrap());
+    std::process::exit(code);
+}
+
+/// Exit with the code for `err`'s kind.
+fn fail(err: Error) -> ! {
+    let status = match &err {
+        Error::Server { status, .. } => *status,
+        _ => None,
+    };
+    exit_with(err.kind(), err.exit_code(), &err.to_string(), status)
+}
+
+/// Exit because the configuration can't be used, whatever went wrong
+/// reading it.
+fn fail_config(err: Error) -> ! {
+    exit_with("config", EXIT_CONFIG, &err.to_string(), None)
+}
+
+/// Exit because items of a batch failed, after the report was printed.
+fn partial_failure(message: String) -> ! {
+    exit_with("partial", EXIT_PARTIAL, &message, None)
+}
+
+/// Export format for `--output`, checked before sending the request.
+fn export_format(path: &std::path::Path) -> ExportFormat {
+    ExportFormat::from_path(path).unwrap_or_else(invalid)
+}
+
+fn export_results(path: &std::path::Path, results: &[Value], format: ExportFormat) {
+    export::write(path, results, format).unwrap_or_else(|e| fail(Error::Io(e)));
+    eprintln!("Wrote {} results to {}", results.len(), path.display());
+}
+
+/// Search hits as `score  source: snippet`, with matches in bold yellow
+/// when stdout is a terminal and NO_COLOR isn't set.
+fn print_hits(hits: &[SearchHit]) {
+    use std::io::IsTerminal;
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let (open, close) = if color { ("\x1b[1;33m", "\x1b[0m") } else { ("", "") };
+    for hit in hits {
+        let score = hit.score.map(|s| format!("{:.2}", s)).unwrap_or_else(|| "-".to_string());
+        let snippet = search::render_highlights(&hit.snippet, &hit.highlights, open, close);
+        match &hit.source_file {
+            Some(source) => println!("{:>6}  {}: {}", score, source, snippet),
+            None => println!("{:>6}  {}", score, snippet),
+        }
+    }
+}
+
+/// Left-aligned columns sized to their widest cell; the last column isn't
+/// padded.
+fn print_table(headers: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let line = |cells: Vec<&str>| {
+        let last = cells.len().saturating_sub(1);
+        let padded: Vec<String> = cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| if i == last { cell.to_string() } else { format!("{:<1$}", cell, widths[i]) })
+            .collect();
+        println!("{}", padded.join("  "));
+    };
+    line(headers.to_vec());
+    for row in rows {
+        line(row.iter().map(String::as_str).collect());
+    }
+}
+
+fn format_size(bytes: Option<u64>) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let Some(bytes) = bytes else {
+        return "-".to_string();
+    };
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn print_scan_table(scan: &ScanResult) {
+    let groups = [
+        ("ingest", &scan.recommended_files),
+        ("skip", &scan.skipped_files),
+        ("convert", &scan.needs_converter_files),
+    ];
+    let rows: Vec<Vec<String>> = groups
+        .iter()
+        .flat_map(|(action, files)| {
+            files.iter().map(move |f| {
+                vec![
+                    action.to_string(),
+                    f.category.clone(),
+                    format_size(f.size_bytes),
+                    f.path.clone(),
+                ]
+            })
+        })
+        .collect();
+    print_table(&["ACTION", "CATEGORY", "SIZE", "PATH"], &rows);
+    println!(
+        "\n{} files: {} recommended, {} skipped, {} need converting",
+        scan.total_files,
+        scan.recommended_files.len(),
+        scan.skipped_files.len(),
+        scan.needs_converter_files.len()
+    );
+}
+
+/// Print `value` in `format`. An array is a list of rows; anything else is
+/// a single record, shown one field per line by `table` and `plain`.
+fn print_output<T: Serialize>(format: OutputFormat, value: &T) {
+    let value = serde_json::to_value(value).expect("output serializes");
+    let rows = match &value {
+        Value::Array(items) => items.as_slice(),
+        other => std::slice::from_ref(other),
+    };
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        OutputFormat::Ndjson => {
+            for row in rows {
+                println!("{}", row);
+            }
+        }
+        OutputFormat::Csv => print!("{}", export::render(rows, ExportFormat::Csv)),
+        OutputFormat::Table | OutputFormat::Plain if !value.is_array() => {
+            let (columns, cells) = export::columns(rows);
+            let fields: Vec<Vec<String>> = columns
+                .into_iter()
+                .zip(cells.into_iter().next().unwrap_or_default())
+                .map(|(column, cell)| vec![column, cell])
+                .collect();
+            if format == OutputFormat::Table {
+                print_table(&["FIELD", "VALUE"], &fields);
+            } else {
+                for field in fields {
+                    println!("{}: {}", field[0], field[1]);
+                }
+            }
+        }
+        OutputFormat::Table => {
+            let (columns, cells) = export::columns(rows);
+            let headers: Vec<String> = columns.iter().map(|c| c.to_uppercase()).collect();
+            print_table(&headers.iter().map(String::as_str).collect::<Vec<_>>(), &cells);
+        }
+        OutputFormat::Plain => {
+            for row in rows {
+                match row {
+                    Value::String(text) => println!("{}", text),
+                    other => println!("{}", export::columns(std::slice::from_ref(other)).1[0].join("\t")),
+                }
+            }
+        }
+    }
+}
+
+/// Print search hits; `plain` is one highlighted line per hit.
+fn print_search<T: Serialize>(format: OutputFormat, response: &T, hits: &[SearchHit]) {
+    match format {
+        OutputFormat::Json => print_output(format, response),
+        OutputFormat::Plain => print_hits(hits),
+        OutputFormat::Table => {
+            let rows: Vec<Vec<String>> = hits
+                .iter()
+                .map(|hit| {
+                    vec![
+                        hit.score.map(|s| format!("{:.2}", s)).unwrap_or_else(|| "-".to_string()),
+                        hit.source_file.clone().unwrap_or_default(),
+                        hit.snippet.replace('\n', " "),
+                    ]
+                })
+                .collect();
+            print_table(&["SCORE", "SOURCE", "SNIPPET"], &rows);
+        }
+        OutputFormat::Ndjson | OutputFormat::Csv => print_output(format, &hits),
+    }
+}
+
+/// Print a scan; other than as JSON or a table, one row per file.
+fn print_scan(format: OutputFormat, scan: &ScanResult) {
+    match format {
+        OutputFormat::Json => print_output(format, scan),
+        OutputFormat::Table => print_scan_table(scan),
+        _ => {
+            let groups = [
+                ("ingest", &scan.recommended_files),
+                ("skip", &scan.skipped_files),
+                ("convert", &scan.needs_converter_files),
+            ];
+            let rows: Vec<Value> = groups
+                .iter()
+                .flat_map(|(action, files)| {
+                    files.iter().map(move |f| {
+                        serde_json::json!({
+                            "action": action,
+                            "category": f.category,
+                            "size_bytes": f.size_bytes,
+                            "path": f.path,
+                            "reason": f.reason,
+                        })
+                    })
+                })
+                .collect();
+            print_output(format, &rows);
+        }
+    }
+}
+
+/// Ask on the terminal whether to ingest each of `files`, returning the
+/// approved and declined paths. Answering `q` stops asking.
+fn prompt_approvals<'a>(
+    files: impl Iterator<Item = &'a scanner::FileRecommendation>,
+) -> (Vec<String>, Vec<String>) {
+    use std::io::{BufRead, IsTerminal, Write};
+    if !std::io::stdin().is_terminal() {
+        invalid("--interactive needs a terminal on stdin".to_string());
+    }
+    let mut approved = Vec::new();
+    let mut declined = Vec::new();
+    let mut lines = std::io::stdin().lock().lines();
+    for file in files {
+        eprint!("Ingest {} ({}, {})? [y/N/q] ", file.path, file.category, format_size(file.size_bytes));
+        let _ = std::io::stderr().flush();
+        let answer = match lines.next() {
+            Some(Ok(answer)) => answer.trim().to_lowercase(),
+            _ => break,
+        };
+        match answer.as_str() {
+            "y" | "yes" => approved.push(file.path.clone()),
+            "q" | "quit" => break,
+            _ => declined.push(file.path.clone()),
+        }
+    }
+    (approved, declined)
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM from a service manager.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Definition for `manager` that keeps `watch --daemon` running, using the
+/// current executable and `--profile`, if given.
+fn service_definition(manager: ServiceManager, profile: Option<&str>) -> String {
+    let exe = std::env::current_exe()
+        .map(|p| p.display().to_string())
+        .unwrap_or_else(|_| "exemem-cli".to_string());
+    let mut args = vec![exe, "watch".to_string(), "--daemon".to_string()];
+    if let Some(profile) = profile {
+        args.extend(["--profile".to_string(), profile.to_string()]);
+    }
+    match manager {
+        ServiceManager::Systemd => format!(
+            r#"[Unit]
+Description=Exemem folder sync
+After=network-online.target
+
+[Service]
+ExecStart={}
+Restart=on-failure
+RestartSec=10
+
+[Install]
+WantedBy=default.target
+"#,
+            args.join(" ")
+        ),
+        ServiceManager::Launchd => {
+            let program: String = args
+                .iter()
+                .map(|arg| format!("        <string>{}</string>\n", arg))
+                .collect();
+            format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>com.exemem.watch</string>
+    <key>ProgramArguments</key>
+    <array>
+{}    </array>
+    <key>RunAtLoad</key>
+    <true/>
+    <key>KeepAlive</key>
+    <dict>
+        <key>SuccessfulExit</key>
+        <false/>
+    </dict>
+</dict>
+</plist>
+"#,
+                program
+            )
+        }
+    }
+}
+
+/// Text given inline or in a file, where `-` for either means stdin.
+fn read_input(inline: Option<String>, file: Option<PathBuf>) -> String {
+    let path = match (inline, file) {
+        (Some(text), _) if text != "-" => return text,
+        (Some(_), _) => PathBuf::from("-"),
+        (None, Some(path)) => path,
+        (None, None) => invalid("No input given".to_string()),
+    };
+    let read = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(&path)
+    };
+    read.unwrap_or_else(|e| {
+        let source = if path.as_os_str() == "-" { "stdin".to_string() } else { path.display().to_string() };
+        fail(Error::Io(format!("Failed to read {}: {}", source, e)))
+    })
+}
+
+/// Mutation records in `text`: one JSON value, or one per line (NDJSON).
+fn parse_records(text: &str) -> Vec<Value> {
+    if let Ok(value) = serde_json::from_str(text) {
+        return vec![value];
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .unwrap_or_else(|e| invalid(format!("Invalid JSON data on line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+/// Redraw the progress line for `name` on stderr.
+fn draw_progress(name: &str, percent: f64, stage: &str) {
+    use std::io::Write;
+    const WIDTH: usize = 24;
+    let percent = percent.clamp(0.0, 100.0);
+    let filled = (percent / 100.0 * WIDTH as f64).round() as usize;
+    eprint!(
+        "\r\x1b[2K{} [{}{}] {:>3.0}% {}",
+        name,
+        "#".repeat(filled),
+        "-".repeat(WIDTH - filled),
+        percent,
+        stage
+    );
+    let _ = std::io::stderr().flush();
+}
+
+/// Fail reading or writing local state, such as the ledger or the log
+/// file (exit code 6).
+fn local_failure(msg: &str) -> ! {
+    fail(Error::Io(msg.to_string()))
+}
+
+/// Fail because of bad command-line input (exit code 2).
+fn invalid(msg: String) -> ! {
+    fail(Error::Validation(msg))
 }
 
 #[tokio::main]
 async fn main() {
     let cli = Cli::parse();
+    paths::init(cli.portable);
+    init_logging(&cli);
+    let timeout = cli.timeout;
+    let profile = cli.profile;
+    let format = cli.format;
 
     match cli.command {
-        Commands::Query { query, session_id } => {
-            let config = CliConfig::load().unwrap_or_else(|e| error_json(&e));
+        Commands::Query {
+            query,
+            query_file,
+            session_id,
+            output,
+        } => {
+            let query = read_input(query, query_file);
+            if query.trim().is_empty() {
+                invalid("The query is empty".to_string());
+            }
+            let export_as = output.as_deref().map(export_format);
+            let config = load_config(profile.as_deref());
             let adapter = ConfigAdapter { config: &config };
             let app_cfg = adapter.to_app_config();
-            let client = QueryClient::new();
+            let client = query_client(&config, timeout);
 
             match client
                 .run_query_with_adapter(&app_cfg, &query, session_id.as_deref())
                 .await
             {
                 Ok(resp) => {
-                    println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+                    if let (Some(path), Some(export_as)) = (&output, export_as) {
+                        export_results(path, &resp.raw_results, export_as);
+                    }
+                    match format {
+                        OutputFormat::Json => print_output(format, &resp),
+                        OutputFormat::Plain => {
+                            println!("{}", resp.ai_interpretation);
+                            print_output(format, &resp.raw_results);
+                        }
+                        _ => print_output(format, &resp.raw_results),
+                    }
+                }
+                Err(e) => fail(e),
+            }
+        }
+        Commands::Search {
+            term,
+            category,
+            since,
+            until,
+            source_file,
+            highlight,
+            output,
+        } => {
+            let export_as = output.as_deref().map(export_format);
+            let parse = |value: Option<String>| {
+                value.map(|v| ledger::parse_date(&v).unwrap_or_else(invalid))
+            };
+            let filters = SearchFilters {
+                category,
+                ingested_after: parse(since),
+                ingested_before: parse(until),
+                source_file,
+            };
+            let config = load_config(profile.as_deref());
+            let adapter = ConfigAdapter { config: &config };
+            let app_cfg = adapter.to_app_config();
+            let client = query_client(&config, timeout);
+
+            match client
+                .search_index_with_adapter(&app_cfg, &term, &filters)
+                .await {
+                Ok(resp) => {
+                    if let (Some(path), Some(export_as)) = (&output, export_as) {
+                        let raw: Vec<Value> = resp.results.iter().map(|h| h.raw.clone()).collect();
+                        export_results(path, &raw, export_as);
+                    }
+                    let format = if highlight { OutputFormat::Plain } else { format };
+                    print_search(format, &resp, &resp.results);
                 }
-                Err(e) => error_json(&e),
+                Err(e) => fail(e),
             }
         }
-        Commands::Search { term } => {
-            let config = CliConfig::load().unwrap_or_else(|e| error_json(&e));
+        Commands::Semantic {
+            text,
+            limit,
+            plain,
+            output,
+        } => {
+            let export_as = output.as_deref().map(export_format);
+            let config = load_config(profile.as_deref());
             let adapter = ConfigAdapter { config: &config };
             let app_cfg = adapter.to_app_config();
-            let client = QueryClient::new();
+            let client = query_client(&config, timeout);
 
-            match client.search_index_with_adapter(&app_cfg, &term).await {
+            match client
+                .semantic_search_with_adapter(&app_cfg, &text, limit)
+                .await
+            {
                 Ok(resp) => {
-                    println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+                    if let (Some(path), Some(export_as)) = (&output, export_as) {
+                        let raw: Vec<Value> = resp.results.iter().map(|h| h.raw.clone()).collect();
+                        export_results(path, &raw, export_as);
+                    }
+                    let format = if plain { OutputFormat::Plain } else { format };
+                    print_search(format, &resp, &resp.results);
                 }
-                Err(e) => error_json(&e),
+                Err(e) => fail(e),
             }
         }
         Commands::Mutate {
             schema,
             operation,
             data,
+            data_file,
         } => {
-            let config = CliConfig::load().unwrap_or_else(|e| error_json(&e));
+            let mut records = parse_records(&read_input(data, data_file));
+            if records.is_empty() {
+                invalid("No JSON data given".to_string());
+            }
+            let config = load_config(profile.as_deref());
             let adapter = ConfigAdapter { config: &config };
             let app_cfg = adapter.to_app_config();
-            let client = QueryClient::new();
+            let client = query_client(&config, timeout);
 
-            let data_value: Value = serde_json::from_str(&data)
-                .unwrap_or_else(|e| error_json(&format!("Invalid JSON data: {}", e)));
+            if records.len() == 1 {
+                match client
+                    .mutate_with_adapter(&app_cfg, &schema, &operation, records.remove(0))
+                    .await
+                {
+                    Ok(resp) => {
+                        print_output(format, &resp);
+                    }
+                    Err(e) => fail(e),
+                }
+                return;
+            }
 
-            match client
-                .mutate_with_adapter(&app_cfg, &schema, &operation, data_value)
-                .await
-            {
-                Ok(resp) => {
-                    println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+            let total = records.len();
+            let mut results = Vec::new();
+            let mut failed = 0;
+            for (i, record) in records.into_iter().enumerate() {
+                match client.mutate_with_adapter(&app_cfg, &schema, &operation, record).await {
+                    Ok(resp) => results.push(serde_json::json!({ "record": i + 1, "result": resp })),
+                    Err(e) => {
+                        failed += 1;
+                        eprintln!("Record {} of {} failed: {}", i + 1, total, redact(&e.to_string()));
+                        results.push(serde_json::json!({
+                            "record": i + 1,
+                            "error": redact(&e.to_string()),
+                            "kind": e.kind(),
+                        }));
+                    }
                 }
-                Err(e) => error_json(&e),
+            }
+            print_output(format, &results);
+            if failed > 0 {
+                partial_failure(format!("{} of {} mutations failed", failed, total));
+            }
+        }
+        Commands::MutateBatch {
+            schema,
+            operation,
+            file,
+            concurrency,
+            retries,
+        } => {
+            if !(1..=batch::MAX_CONCURRENCY).contains(&concurrency) {
+                invalid(format!("--concurrency must be between 1 and {}", batch::MAX_CONCURRENCY));
+            }
+            let input: Box<dyn tokio::io::AsyncBufRead + Unpin> = if file.as_os_str() == "-" {
+                Box::new(tokio::io::BufReader::new(tokio::io::stdin()))
+            } else {
+                match tokio::fs::File::open(&file).await {
+                    Ok(f) => Box::new(tokio::io::BufReader::new(f)),
+                    Err(e) => local_failure(&format!("Failed to read {}: {}", file.display(), e)),
+                }
+            };
+            let config = load_config(profile.as_deref());
+            let client = query_client(&config, timeout);
+            let options = batch::BatchOptions {
+                concurrency,
+                retry: RetryPolicy {
+                    max_attempts: retries + 1,
+                    base_delay: std::time::Duration::from_millis(500),
+                    max_busy_waits: 0,
+                },
+            };
+
+            use std::io::IsTerminal;
+            let live = std::io::stderr().is_terminal();
+            let report = batch::mutate_ndjson(&client, &config, &schema, &operation, input, options, |progress| {
+                if live {
+                    use std::io::Write;
+                    eprint!("\r\x1b[2K{} applied, {} failed", progress.succeeded, progress.failed);
+                    let _ = std::io::stderr().flush();
+                }
+            })
+            .await
+            .unwrap_or_else(fail);
+            if live {
+                eprintln!();
+            }
+            print_output(format, &report);
+            if report.failed > 0 {
+                partial_failure(format!("{} of {} records failed", report.failed, report.total));
             }
         }
         Commands::Chat {
             session_id,
             question,
         } => {
-            let config = CliConfig::load().unwrap_or_else(|e| error_json(&e));
+            let config = load_config(profile.as_deref());
             let adapter = ConfigAdapter { config: &config };
             let app_cfg = adapter.to_app_config();
-            let client = QueryClient::new();
+            let client = query_client(&config, timeout);
 
             match client
                 .chat_followup_with_adapter(&app_cfg, &session_id, &question)
                 .await
             {
-                Ok(resp) => {
-                    println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+                Ok(resp) if format == OutputFormat::Plain => println!("{}", resp.answer),
+                Ok(resp) => print_output(format, &resp),
+                Err(e) => fail(e),
+            }
+        }
+        Commands::Schema { action } => {
+            let config = load_config(profile.as_deref());
+            let adapter = ConfigAdapter { config: &config };
+            let app_cfg = adapter.to_app_config();
+            let client = query_client(&config, timeout);
+
+            let result = match action {
+                SchemaCommands::List => client
+                    .list_schemas_with_adapter(&app_cfg)
+                    .await
+                    .map(|schemas| serde_json::to_value(schemas).unwrap()),
+                SchemaCommands::Describe { name } => client
+                    .describe_schema_with_adapter(&app_cfg, &name)
+                    .await
+                    .map(|schema| serde_json::to_value(schema).unwrap()),
+            };
+            match result {
+                Ok(value) => print_output(format, &value),
+                Err(e) => fail(e),
+            }
+        }
+        Commands::Saved { action } => {
+            let mut saved = SavedQueries::load().unwrap_or_else(|e| fail(Error::Io(e)));
+            match action {
+                SavedCommands::Save { name, query } => {
+                    let query = saved.upsert(&name, &query).unwrap_or_else(invalid);
+                    print_output(format, &query);
+                }
+                SavedCommands::List => {
+                    print_output(format, &saved.list());
+                }
+                SavedCommands::Delete { name } => {
+                    let deleted = saved.delete(&name).unwrap_or_else(|e| fail(Error::Io(e)));
+                    print_output(format, &serde_json::json!({ "deleted": deleted }));
+                }
+                SavedCommands::Run { name, params } => {
+                    let values: std::collections::HashMap<String, String> = params
+                        .iter()
+                        .map(|param| match param.split_once('=') {
+                            Some((key, value)) => (key.trim().to_string(), value.to_string()),
+                            None => invalid(format!("Invalid --param {:?}: use name=value", param)),
+                        })
+                        .collect();
+                    let query = saved
+                        .get(&name)
+                        .unwrap_or_else(|| invalid(format!("No saved query named '{}'", name)))
+                        .render(&values)
+                        .unwrap_or_else(invalid);
+
+                    let config = load_config(profile.as_deref());
+                    let adapter = ConfigAdapter { config: &config };
+                    let app_cfg = adapter.to_app_config();
+                    let client = query_client(&config, timeout);
+                    match client.run_query_with_adapter(&app_cfg, &query, None).await {
+                        Ok(resp) => {
+                            print_output(format, &resp);
+                        }
+                        Err(e) => fail(e),
+                    }
                 }
-                Err(e) => error_json(&e),
             }
         }
+        Commands::Scan {
+            path,
+            table,
+            approve_all,
+            approve_category,
+            interactive,
+        } => {
+            let config = load_config(profile.as_deref());
+            let mut known = scanner::CATEGORIES.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            known.extend(config.scanner.category_names());
+            if let Some(unknown) = approve_category.iter().find(|c| !known.contains(c)) {
+                invalid(format!(
+                    "Unknown category '{}'; expected one of: {}",
+                    unknown,
+                    known.join(", ")
+                ));
+            }
+            let Some(folder) = path.or_else(|| config.watched_folder.clone()) else {
+                invalid("No folder given and no watched folder configured; pass --path".to_string());
+            };
+            let format = if table { OutputFormat::Table } else { format };
+            let scan = headless::scan(&folder, &config).unwrap_or_else(fail);
+            if format != OutputFormat::Json {
+                print_scan(format, &scan);
+            }
+
+            let mut approved: Vec<String> = scan
+                .recommended_files
+                .iter()
+                .chain(&scan.skipped_files)
+                .filter(|f| {
+                    (approve_all && f.should_ingest) || approve_category.contains(&f.category)
+                })
+                .map(|f| f.path.clone())
+                .collect();
+            let mut declined = Vec::new();
+            if interactive {
+                let undecided = scan.recommended_files.iter().filter(|f| !approved.contains(&f.path));
+                let (yes, no) = prompt_approvals(undecided);
+                approved.extend(yes);
+                declined = no;
+            }
+            let approving = approve_all || !approve_category.is_empty() || interactive;
+            if !approving {
+                if format == OutputFormat::Json {
+                    print_scan(format, &scan);
+                }
+                return;
+            }
+            if approved.is_empty() {
+                eprintln!("No files approved");
+            } else if config.api_key.is_empty() {
+                fail_config(Error::Validation("Set an API key before ingesting".to_string()));
+            }
+
+            let uploads = headless::ingest_approved(&config, &scan, &approved, &declined, |result| {
+                match &result.error {
+                    Some(error) => eprintln!("failed    {}: {}", result.filename, error),
+                    None => eprintln!("uploaded  {}", result.filename),
+                }
+            })
+            .await
+            .unwrap_or_else(fail);
+            if format == OutputFormat::Json {
+                print_output(format, &serde_json::json!({ "scan": scan, "uploads": uploads }));
+            }
+        }
+        Commands::SyncOnce {
+            path,
+            ingest_timeout,
+        } => {
+            let config = load_config(profile.as_deref());
+            if config.api_key.is_empty() {
+                fail_config(Error::Validation("Set an API key before syncing".to_string()));
+            }
+            let Some(folder) = path.or_else(|| config.watched_folder.clone()) else {
+                invalid("No folder given and no watched folder configured; pass --path".to_string());
+            };
+            let summary = headless::sync_once(
+                &config,
+                &folder,
+                std::time::Duration::from_secs(ingest_timeout),
+                |result| match &result.error {
+                    Some(error) => eprintln!("failed    {}: {}", result.filename, error),
+                    None => eprintln!("uploaded  {}", result.filename),
+                },
+            )
+            .await
+            .unwrap_or_else(fail);
+            print_output(format, &summary);
+            if !summary.ok() {
+                partial_failure(format!(
+                    "{} uploads and {} ingestions failed",
+                    summary.failed, summary.ingestion_failed
+                ));
+            }
+        }
+        Commands::Ingest {
+            paths,
+            no_auto_ingest,
+        } => {
+            use std::io::IsTerminal;
+            let mut config = load_config(profile.as_deref());
+            if no_auto_ingest {
+                config.auto_ingest = false;
+            }
+            if config.api_key.is_empty() {
+                fail_config(Error::Validation("Set an API key before ingesting".to_string()));
+            }
+            let files = headless::expand_paths(&paths, &config).unwrap_or_else(fail);
+            if files.is_empty() {
+                eprintln!("No files to ingest");
+                return;
+            }
+            let mut pipeline = headless::Pipeline::open(&config).unwrap_or_else(fail);
+            // Without a terminal, only the final line for each file is printed
+            let live = std::io::stderr().is_terminal();
+            let clear = if live { "\r\x1b[2K" } else { "" };
+
+            let mut outcomes = Vec::new();
+            let mut failed = 0;
+            for (path, context) in &files {
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| path.display().to_string());
+                let on_progress: Option<UploadProgressFn> = live.then(|| {
+                    let name = name.clone();
+                    Arc::new(move |sent: u64, total: u64| {
+                        let percent = if total == 0 { 100.0 } else { sent as f64 * 100.0 / total as f64 };
+                        draw_progress(&name, percent, "uploading");
+                    }) as UploadProgressFn
+                });
+                let result = pipeline
+                    .upload_with_progress(&config, path, context, UploadPriority::Manual, on_progress)
+                    .await;
+
+                let ingestion = match (&result.progress_id, &result.status) {
+                    (Some(progress_id), UploadStatus::Ingesting) => {
+                        let status = pipeline
+                            .wait_for_ingestion(&config, progress_id, |progress| {
+                                if live {
+                                    draw_progress(&name, progress.percent.unwrap_or(0.0), &progress.status);
+                                }
+                            })
+                            .await;
+                        Some(status.unwrap_or_else(|| "timed_out".to_string()))
+                    }
+                    _ => None,
+                };
+                let ok = result.status != UploadStatus::Error
+                    && ingestion.as_deref().map_or(true, is_success_status);
+                match (&result.error, &ingestion) {
+                    (Some(error), _) => eprintln!("{}failed    {}: {}", clear, name, error),
+                    (None, Some(status)) if !ok => eprintln!("{}failed    {}: ingestion {}", clear, name, status),
+                    (None, Some(_)) => eprintln!("{}ingested  {}", clear, name),
+                    (None, None) => eprintln!("{}uploaded  {}", clear, name),
+                }
+                if !ok {
+                    failed += 1;
+                }
+                outcomes.push(serde_json::json!({
+                    "path": path,
+                    "upload": result,
+                    "ingestion_status": ingestion,
+                }));
+            }
+            print_output(format, &outcomes);
+            if failed > 0 {
+                partial_failure(format!("{} of {} files failed", failed, files.len()));
+            }
+        }
+        Commands::Watch {
+            daemon,
+            print_service,
+        } => {
+            if let Some(manager) = print_service {
+                print!("{}", service_definition(manager, profile.as_deref()));
+                return;
+            }
+            let config = load_config(profile.as_deref());
+            if !daemon {
+                eprintln!("Watching for new files; press Ctrl-C to stop");
+            }
+            let result = headless::watch(&config, shutdown_signal(), |record| {
+                println!("{}", serde_json::to_string(&record).unwrap());
+            })
+            .await;
+            if let Err(e) = result {
+                fail(e);
+            }
+        }
+        Commands::Failed { retry, id } => {
+            if retry {
+                let ids = if id.is_empty() { None } else { Some(id.as_slice()) };
+                match dead_letter::retry_failed_uploads(ids).await {
+                    Ok(results) => {
+                        print_output(format, &results);
+                    }
+                    Err(e) => local_failure(&e),
+                }
+            } else {
+                match dead_letter::list_failed_uploads() {
+                    Ok(failed) => {
+                        print_output(format, &failed);
+                    }
+                    Err(e) => local_failure(&e),
+                }
+            }
+        }
+        Commands::History {
+            what:
+                HistoryCommands::Uploads {
+                    since,
+                    until,
+                    status,
+                    category,
+                    limit,
+                },
+        } => {
+            let parse = |value: Option<String>| {
+                value.map(|v| ledger::parse_date(&v).unwrap_or_else(invalid))
+            };
+            let filter = HistoryFilter {
+                since: parse(since),
+                until: parse(until),
+                status,
+                category,
+                limit,
+            };
+            match ledger::upload_history(&filter) {
+                Ok(entries) => {
+                    print_output(format, &entries);
+                }
+                Err(e) => local_failure(&e),
+            }
+        }
+        Commands::Sessions { action } => {
+            let config = load_config(profile.as_deref());
+            let client = query_client(&config, timeout);
+            let transcripts = TranscriptStore::load().unwrap_or_else(|e| fail(Error::Io(e)));
+            match action {
+                SessionsCommands::List { limit, local } => {
+                    let mut list = transcripts.sessions(limit).unwrap_or_else(|e| fail(Error::Io(e)));
+                    if !local {
+                        match client.list_sessions(&config).await {
+                            Ok(remote) => list = sessions::merge_remote(list, remote, limit),
+                            Err(e) => log::warn!("Couldn't list backend sessions: {}", e),
+                        }
+                    }
+                    print_output(format, &list);
+                }
+                SessionsCommands::Show { session_id } => {
+                    let mut turns = transcripts.session(&session_id).unwrap_or_else(|e| fail(Error::Io(e)));
+                    if turns.is_empty() {
+                        turns = client
+                            .get_session_transcript(&config, &session_id)
+                            .await
+                            .unwrap_or_else(fail);
+                    }
+                    match format {
+                        OutputFormat::Plain => print!("{}", transcripts::to_markdown(&turns)),
+                        _ => print_output(format, &turns),
+                    }
+                }
+                SessionsCommands::Delete {
+                    mut session_ids,
+                    before,
+                } => {
+                    if let Some(before) = before {
+                        let before = ledger::parse_date(&before).unwrap_or_else(invalid);
+                        let mut known = transcripts
+                            .sessions(i64::MAX as usize)
+                            .unwrap_or_else(|e| fail(Error::Io(e)));
+                        match client.list_sessions(&config).await {
+                            Ok(remote) => known = sessions::merge_remote(known, remote, usize::MAX),
+                            Err(e) => log::warn!("Couldn't list backend sessions: {}", e),
+                        }
+                        // Sessions the backend doesn't date are kept
+                        session_ids.extend(
+                            known
+                                .into_iter()
+                                .filter(|s| s.last_active_at > 0 && s.last_active_at < before)
+                                .map(|s| s.session_id),
+                        );
+                    }
+                    let mut seen = std::collections::HashSet::new();
+                    session_ids.retain(|id| seen.insert(id.clone()));
+
+                    let total = session_ids.len();
+                    let mut results = Vec::new();
+                    let mut failed = 0;
+                    for session_id in session_ids {
+                        let deleted = match sessions::forget_local(&transcripts, &session_id) {
+                            Ok(removed) => {
+                                sessions::forget_remote(&config, &client, &session_id, removed).await
+                            }
+                            Err(e) => Err(e),
+                        };
+                        match deleted {
+                            Ok(turns) => results.push(serde_json::json!({
+                                "session_id": session_id,
+                                "deleted": true,
+                                "turns": turns,
+                            })),
+                            Err(e) => {
+                                failed += 1;
+                                results.push(serde_json::json!({
+                                    "session_id": session_id,
+                                    "deleted": false,
+                                    "error": redact(&e.to_string()),
+                                    "kind": e.kind(),
+                                }));
+                            }
+                        }
+                    }
+                    print_output(format, &results);
+                    if failed > 0 {
+                        partial_failure(format!("{} of {} sessions couldn't be deleted", failed, total));
+                    }
+                }
+            }
+        }
+        Commands::History {
+            what:
+                HistoryCommands::Queries {
+                    search,
+                    since,
+                    until,
+                    limit,
+                },
+        } => {
+            let parse = |value: Option<String>| {
+                value.map(|v| ledger::parse_date(&v).unwrap_or_else(invalid))
+            };
+            let filter = QueryHistoryFilter {
+                since: parse(since),
+                until: parse(until),
+                search,
+                limit,
+            };
+            match TranscriptStore::load().and_then(|store| store.history(&filter)) {
+                Ok(entries) => {
+                    print_output(format, &entries);
+                }
+                Err(e) => fail(Error::Io(e)),
+            }
+        }
+        Commands::Stats {
+            days,
+            what: None | Some(StatsCommands::Uploads),
+        } => match stats::upload_stats(days) {
+            Ok(report) => {
+                print_output(format, &report);
+            }
+            Err(e) => local_failure(&e),
+        },
+        Commands::Stats {
+            days,
+            what: Some(StatsCommands::Queries),
+        } => match query_metrics::query_metrics(days) {
+            Ok(report) => {
+                print_output(format, &report);
+            }
+            Err(e) => local_failure(&e),
+        },
+        Commands::Capabilities => {
+            print_output(format, &Capabilities::current());
+        }
+        Commands::Simulate {
+            trace,
+            slots,
+            upload_ms,
+            slowdown,
+        } => {
+            let path = trace
+                .map(Ok)
+                .unwrap_or_else(simulate::traces_path)
+                .unwrap_or_else(|e| local_failure(&e));
+            let traces = simulate::load_traces(&path).unwrap_or_else(|e| local_failure(&e));
+            let defaults = SimulationConfig::default();
+            let config = SimulationConfig {
+                slots: slots.unwrap_or(defaults.slots),
+                upload_ms,
+                slowdown,
+            };
+            let report = simulate::simulate(&traces, &config);
+            print_output(format, &report);
+        }
+        Commands::Logs {
+            follow,
+            level,
+            module,
+            lines,
+        } => {
+            let level = level
+                .parse::<log::LevelFilter>()
+                .unwrap_or_else(|_| invalid(format!("Invalid level: {}", level)));
+            let filter = LogFilter { level, module };
+            let path = logs::log_file().unwrap_or_else(|e| local_failure(&e));
+            if !path.exists() {
+                local_failure(&format!(
+                    "No log file at {}; has the app been started?",
+                    path.display()
+                ));
+            }
+
+            for record in logs::tail(&path, lines, &filter).unwrap_or_else(|e| local_failure(&e)) {
+                println!("{}", record.display());
+            }
+            if follow {
+                let result = logs::follow(&path, &filter, |record| {
+                    println!("{}", record.display());
+                    true
+                });
+                if let Err(e) = result {
+                    local_failure(&e);
+                }
+            }
+        }
+        Commands::Status { offline } => {
+            let config = load_config(profile.as_deref());
+            let report = if offline {
+                status::local_status(&config)
+            } else {
+                status::status(&config, &query_client(&config, timeout)).await
+            };
+            print_output(format, &report);
+            if let Some(usage) = &report.usage {
+                let exhausted = usage.exhausted();
+                if !exhausted.is_empty() {
+                    eprintln!(
+                        "Quota used up: {}; uploads or queries will be refused until it resets or the plan changes",
+                        exhausted.join(", ")
+                    );
+                }
+            }
+        }
+        #[cfg(feature = "tui")]
+        Commands::Tui { watch } => {
+            let config = load_config(profile.as_deref());
+            let client = query_client(&config, timeout);
+            if let Err(e) = exemem_client_lib::tui::run(config, client, watch).await {
+                fail(e);
+            }
+        }
+        Commands::Doctor { target: None } => {
+            let config = load_config(profile.as_deref());
+            let report = doctor::diagnose(&config, &query_client(&config, timeout)).await;
+            match format {
+                OutputFormat::Json => print_output(format, &report),
+                _ => print_output(format, &report.checks),
+            }
+            if !report.ok {
+                std::process::exit(EXIT_CONFIG);
+            }
+        }
+        Commands::Doctor {
+            target: Some(DoctorCommands::Config),
+        } => {
+            let config = load_config(profile.as_deref());
+            let report = config_check::check_config(&config, &query_client(&config, timeout)).await;
+            print_output(format, &report);
+            if !report.ok {
+                std::process::exit(EXIT_CONFIG);
+            }
+        }
+        Commands::Storage { action } => {
+            let config = load_config(profile.as_deref());
+            match action {
+                StorageCommands::Get {
+                    namespace,
+                    keys,
+                    key_base64,
+                    output,
+                } => {
+                    if output.is_some() && keys.len() > 1 {
+                        invalid("--output takes a single key".to_string());
+                    }
+                    let keys: Vec<Vec<u8>> = keys.iter().map(|key| storage_key(key, key_base64)).collect();
+                    let store = storage_store(&config, &namespace, timeout);
+                    // One round trip per 25 keys rather than one per key
+                    let values = store.batch_get(&keys).await.unwrap_or_else(storage_failure);
+                    let mut items: Vec<Value> = keys
+                        .iter()
+                        .zip(values)
+                        .map(|(key, value)| {
+                            let Some(value) = value else {
+                                return serde_json::json!({ "key_base64": BASE64.encode(key), "found": false });
+                            };
+                            let mut item = storage_item(key, &value);
+                            if let Some(path) = &output {
+                                if let Err(e) = std::fs::write(path, &value) {
+                                    local_failure(&format!("Failed to write {}: {}", path.display(), e));
+                                }
+                                item.as_object_mut().unwrap().remove("value_base64");
+                                item["output"] = serde_json::json!(path);
+                            }
+                            item["found"] = true.into();
+                            item
+                        })
+                        .collect();
+                    // A single key prints as before, not as a list of one
+                    if items.len() == 1 {
+                        print_output(format, &items.remove(0));
+                    } else {
+                        print_output(format, &items);
+                    }
+                }
+                StorageCommands::Put {
+                    namespace,
+                    key,
+                    key_base64,
+                    file,
+                    value,
+                    value_base64,
+                } => {
+                    let key = storage_key(&key, key_base64);
+                    let value = match (file, value, value_base64) {
+                        (Some(path), _, _) if path.as_os_str() == "-" => {
+                            let mut bytes = Vec::new();
+                            std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)
+                                .unwrap_or_else(|e| local_failure(&format!("Failed to read stdin: {}", e)));
+                            bytes
+                        }
+                        (Some(path), _, _) => std::fs::read(&path).unwrap_or_else(|e| {
+                            local_failure(&format!("Failed to read {}: {}", path.display(), e))
+                        }),
+                        (None, Some(text), _) => text.into_bytes(),
+                        (None, None, Some(encoded)) => BASE64
+                            .decode(encoded.trim())
+                            .unwrap_or_else(|e| invalid(format!("Invalid --value-base64: {}", e))),
+                        (None, None, None) => invalid("No value given".to_string()),
+                    };
+                    let bytes = value.len();
+                    let store = storage_store(&config, &namespace, timeout);
+                    store.put(&key, value).await.unwrap_or_else(storage_failure);
+                    print_output(
+                        format,
+                        &serde_json::json!({ "key_base64": BASE64.encode(&key), "bytes": bytes, "stored": true }),
+                    );
+                }
+                StorageCommands::Delete {
+                    namespace,
+                    key,
+                    key_base64,
+                } => {
+                    let key = storage_key(&key, key_base64);
+                    let store = storage_store(&config, &namespace, timeout);
+                    let deleted = store.delete(&key).await.unwrap_or_else(storage_failure);
+                    print_output(format, &serde_json::json!({ "deleted": deleted }));
+                }
+                StorageCommands::ScanPrefix {
+                    namespace,
+                    prefix,
+                    key_base64,
+                    limit,
+                    cursor,
+                } => {
+                    if limit == 0 {
+                        invalid("--limit must be at least 1".to_string());
+                    }
+                    let prefix = storage_key(&prefix, key_base64);
+                    let store = storage_store(&config, &namespace, timeout);
+                    let page = store
+                        .scan_prefix_page(&prefix, cursor.as_deref(), limit)
+                        .await
+                        .unwrap_or_else(storage_failure);
+                    let items: Vec<Value> = page.items.iter().map(|(k, v)| storage_item(k, v)).collect();
+                    match format {
+                        OutputFormat::Json => print_output(
+                            format,
+                            &serde_json::json!({ "items": items, "next_cursor": page.next_cursor }),
+                        ),
+                        _ => {
+                            print_output(format, &items);
+                            if let Some(next) = page.next_cursor {
+                                eprintln!("More keys: rerun with --cursor {}", next);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Namespaces {
+            action: NamespacesCommands::List,
+        } => {
+            let config = load_config(profile.as_deref());
+            let (client, base_url, auth) = storage_api(&config);
+            let mut store = ExememNamespacedStore::with_client(client, base_url, auth);
+            if let Some(secs) = timeout {
+                store = store.with_timeout(std::time::Duration::from_secs(secs));
+            }
+            let names = store.list_namespaces().await.unwrap_or_else(storage_failure);
+            print_output(format, &names);
+        }
+        Commands::SelfUpdate { check, force } => {
+            let config = load_config(profile.as_deref());
+            let client = http::build_client(&config.proxy, &config.tls, &config.connection);
+            let update = self_update::check(&client, &config).await.unwrap_or_else(fail);
+            if check || !(update.update_available || force) {
+                print_output(format, &update);
+                return;
+            }
+            let exe = std::env::current_exe()
+                .unwrap_or_else(|e| local_failure(&format!("Can't find this binary: {}", e)));
+            self_update::install(&client, &update.release, &exe).await.unwrap_or_else(fail);
+            print_output(
+                format,
+                &serde_json::json!({
+                    "updated": true,
+                    "from": update.current_version,
+                    "to": update.latest_version,
+                    "path": exe,
+                }),
+            );
+        }
         Commands::Config {
             show,
+            effective,
             env,
             api_key,
             api_url,
         } => {
-            let mut config = CliConfig::load().unwrap_or_else(|e| error_json(&e));
+            let mut config = load_config(profile.as_deref());
+
+            if effective {
+                let output = effective_config(&config, profile.as_deref()).unwrap_or_else(fail);
+                print_output(format, &output);
+                return;
+            }
 
             if show && env.is_none() && api_key.is_none() && api_url.is_none() {
                 let output = serde_json::json!({
@@ -275,7 +2018,7 @@ async fn main() {
                     "auto_ingest": config.auto_ingest,
                     "auto_approve_watched": config.auto_approve_watched,
                 });
-                println!("{}", serde_json::to_string_pretty(&output).unwrap());
+                print_output(format, &output);
                 return;
             }
 
@@ -286,7 +2029,7 @@ async fn main() {
                     "Dev" | "dev" => Environment::Dev,
                     "Prod" | "prod" => Environment::Prod,
                     "Custom" | "custom" => Environment::Custom,
-                    _ => error_json(&format!("Invalid environment: {}. Use Dev, Prod, or Custom", env_str)),
+                    _ => invalid(format!("Invalid environment: {}. Use Dev, Prod, or Custom", env_str)),
                 };
                 changed = true;
             }
@@ -302,16 +2045,19 @@ async fn main() {
                 changed = true;
             }
 
+            if changed && profile.is_some() {
+                invalid("--profile can't be combined with config changes; switch to the profile first".to_string());
+            }
             if changed {
-                config.save().unwrap_or_else(|e| error_json(&e));
+                config.save().unwrap_or_else(fail);
                 let output = serde_json::json!({
                     "status": "saved",
                     "environment": format!("{:?}", config.environment),
                     "api_url": config.api_url(),
                 });
-                println!("{}", serde_json::to_string_pretty(&output).unwrap());
+                print_output(format, &output);
             } else {
-                error_json("No config changes specified. Use --show, --env, --api-key, or --api-url");
+                invalid("No config changes specified. Use --show, --effective, --env, --api-key, or --api-url".to_string());
             }
         }
     }
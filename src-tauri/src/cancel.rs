@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::error::Error;
+
+/// Commands that only touch local state
+pub const LOCAL_TIMEOUT: Duration = Duration::from_secs(30);
+/// Queries, chat and search against the backend
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(120);
+/// Folder scans, which can walk a very large tree
+pub const SCAN_TIMEOUT: Duration = Duration::from_secs(600);
+/// Commands that wait for the user in a file dialog before doing their work
+pub const DIALOG_TIMEOUT: Duration = Duration::from_secs(600);
+/// Upper bound for a configured or per-call query timeout
+pub const MAX_QUERY_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Timeout for one query: `override_secs` if given, else the configured
+/// default, capped at [`MAX_QUERY_TIMEOUT`].
+pub fn query_timeout(configured_secs: u64, override_secs: Option<u64>) -> Duration {
+    let secs = override_secs.unwrap_or(configured_secs).max(1);
+    Duration::from_secs(secs).min(MAX_QUERY_TIMEOUT)
+}
+
+/// Set once; wakes everything waiting on it.
+#[derive(Default)]
+pub struct CancelToken {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once `cancel` has been called.
+    pub async fn cancelled(&self) {
+        loop {
+            // Registered before the check, so a cancel in between isn't missed
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Running command invocations, keyed by the id the frontend sent with them.
+///
+/// A command takes an `invocation_id` and runs through here if it talks to
+/// the backend, derives a key from a passphrase, waits on a file dialog, or
+/// reads or writes files outside the app's data dir. Commands that only read
+/// or update local app state (getters, toggles, the small JSON stores in the
+/// data dir) finish in milliseconds and are exempt, as is `select_folder`,
+/// whose only wait is a dialog the user dismisses. Work a command spawns and
+/// returns from early, like uploads, reports through events instead.
+#[derive(Default)]
+pub struct CommandRegistry {
+    running: Mutex<HashMap<String, Arc<CancelToken>>>,
+}
+
+impl CommandRegistry {
+    /// Track an invocation until the returned handle is dropped. Invocations
+    /// without an id still get a token, they just can't be cancelled.
+    pub fn register(self: &Arc<Self>, invocation_id: Option<String>) -> Invocation {
+        let token = Arc::new(CancelToken::default());
+        if let Some(id) = &invocation_id {
+            self.running
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(id.clone(), token.clone());
+        }
+        Invocation {
+            id: invocation_id,
+            token,
+            registry: self.clone(),
+        }
+    }
+
+    /// Run a command body, failing with [`Error::Cancelled`] or
+    /// [`Error::Timeout`] instead of waiting for it. Dropping the body
+    /// releases any state locks it held.
+    pub async fn run<T>(
+        self: &Arc<Self>,
+        invocation_id: Option<String>,
+        timeout: Duration,
+        work: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        self.register(invocation_id).run(timeout, work).await
+    }
+
+    /// Cancel a running invocation. Returns false if it already finished.
+    pub fn cancel(&self, invocation_id: &str) -> bool {
+        let token = self
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(invocation_id)
+            .cloned();
+        match token {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn running(&self) -> usize {
+        self.running.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+/// A registered invocation; unregisters itself when dropped, so it can be
+/// moved into background work a command spawns.
+pub struct Invocation {
+    id: Option<String>,
+    token: Arc<CancelToken>,
+    registry: Arc<CommandRegistry>,
+}
+
+impl Invocation {
+    pub async fn run<T>(
+        &self,
+        timeout: Duration,
+        work: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        tokio::select! {
+            result = tokio::time::timeout(timeout, work) => result.unwrap_or_else(|_| {
+                Err(Error::Timeout(format!("Timed out after {}s", timeout.as_secs())))
+            }),
+            _ = self.token.cancelled() => Err(Error::Cancelled),
+        }
+    }
+
+    /// Run background work until it finishes (Some) or the invocation is
+    /// cancelled (None).
+    pub async fn until_cancelled<F: Future>(&self, work: F) -> Option<F::Output> {
+        tokio::select! {
+            output = work => Some(output),
+            _ = self.token.cancelled() => None,
+        }
+    }
+}
+
+impl Drop for Invocation {
+    fn drop(&mut self) {
+        let Some(id) = &self.id else {
+            return;
+        };
+        let mut running = self
+            .registry
+            .running
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        // A later invocation may have reused the id
+        if running
+            .get(id)
+            .is_some_and(|token| Arc::ptr_eq(token, &self.token))
+        {
+            running.remove(id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_timeout_prefers_override() {
+        assert_eq!(query_timeout(120, None), Duration::from_secs(120));
+        assert_eq!(query_timeout(120, Some(15)), Duration::from_secs(15));
+        assert_eq!(query_timeout(120, Some(0)), Duration::from_secs(1));
+        assert_eq!(query_timeout(120, Some(86_400)), MAX_QUERY_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn test_cancel_aborts_running_command() {
+        let registry = Arc::new(CommandRegistry::default());
+        let canceller = registry.clone();
+        tokio::spawn(async move {
+            while !canceller.cancel("inv-1") {
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let result: Result<(), Error> = registry
+            .run(Some("inv-1".to_string()), Duration::from_secs(60), async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        assert_eq!(result.unwrap_err(), Error::Cancelled);
+        assert_eq!(registry.running(), 0);
+        assert!(!registry.cancel("inv-1"));
+    }
+
+    #[tokio::test]
+    async fn test_timeout_and_completion() {
+        let registry = Arc::new(CommandRegistry::default());
+        let slow: Result<(), Error> = registry
+            .run(None, Duration::from_millis(10), async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(())
+            })
+            .await;
+        assert_eq!(slow.unwrap_err().kind(), "timeout");
+
+        let fast = registry
+            .run(Some("inv-2".to_string()), Duration::from_secs(1), async {
+                Ok(7)
+            })
+            .await;
+        assert_eq!(fast, Ok(7));
+        assert_eq!(registry.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_background_work_outlives_command() {
+        let registry = Arc::new(CommandRegistry::default());
+        let invocation = registry.register(Some("inv-3".to_string()));
+        assert_eq!(registry.running(), 1);
+
+        let work = tokio::spawn(async move {
+            invocation
+                .until_cancelled(tokio::time::sleep(Duration::from_secs(60)))
+                .await
+        });
+        while !registry.cancel("inv-3") {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(work.await.unwrap(), None);
+        assert_eq!(registry.running(), 0);
+    }
+}
@@ -0,0 +1,137 @@
+use serde::Serialize;
+
+/// Every command registered with the Tauri invoke handler. Keep in sync with
+/// `generate_handler!` in `lib.rs`.
+pub const COMMANDS: &[&str] = &[
+    "get_config",
+    "save_config",
+    "list_profiles",
+    "save_profile",
+    "switch_profile",
+    "validate_config",
+    "config_encryption_status",
+    "unlock_config",
+    "lock_config",
+    "set_config_passphrase",
+    "export_settings",
+    "import_settings",
+    "select_folder",
+    "get_sync_status",
+    "get_recent_activity",
+    "get_upload_history",
+    "get_upload_stats",
+    "delete_entry",
+    "restore_entry",
+    "get_deleted_entries",
+    "purge_deleted_entries",
+    "get_missed_events",
+    "get_startup_report",
+    "get_client_capabilities",
+    "cancel_command",
+    "cancel_query",
+    "get_failed_uploads",
+    "retry_failed_uploads",
+    "scan_folder",
+    "reset_classification_feedback",
+    "get_scan_trends",
+    "explain_file",
+    "approve_and_ingest",
+    "get_ingestion_progress",
+    "run_query",
+    "clear_query_cache",
+    "get_query_metrics",
+    "get_telemetry_preview",
+    "save_query",
+    "list_saved_queries",
+    "delete_saved_query",
+    "run_saved_query",
+    "get_full_results",
+    "get_more_results",
+    "export_results",
+    "read_payload",
+    "release_payload",
+    "chat_followup",
+    "search_transcripts",
+    "list_sessions",
+    "get_session_transcript",
+    "export_session_transcript",
+    "delete_session",
+    "get_query_history",
+    "delete_query_history",
+    "search_index",
+    "semantic_search",
+    "mutate",
+    "queue_request",
+    "get_offline_queue",
+    "remove_queued_request",
+    "list_schemas",
+    "describe_schema",
+    "start_watching",
+    "stop_watching",
+];
+
+/// What this build of the client can do, so the frontend and scripts can
+/// check before calling into optional subsystems.
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    /// Optional subsystems, e.g. "direct_s3" or "transcripts"
+    pub features: Vec<&'static str>,
+    /// Ways files get into Exemem
+    pub importers: Vec<&'static str>,
+    /// Transformations applied to files before upload
+    pub preprocessors: Vec<&'static str>,
+    pub auth_methods: Vec<&'static str>,
+    pub commands: &'static [&'static str],
+}
+
+impl Capabilities {
+    /// Capabilities of the running binary.
+    pub fn current() -> Self {
+        let mut features = vec![
+            "transcripts",
+            "hooks",
+            "post_ingest_actions",
+            "upload_schedule",
+            "dry_run",
+            "proxy",
+            "custom_tls",
+            "cancellation",
+            "query_cache",
+            "offline_queue",
+            "semantic_search",
+        ];
+        if crate::direct_s3::SUPPORTED {
+            features.push("direct_s3");
+        }
+
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            features,
+            importers: vec!["folder_scan", "folder_watch", "zip_archive"],
+            preprocessors: vec!["classification", "gzip", "zstd"],
+            auth_methods: vec!["api_key", "browser_login"],
+            commands: COMMANDS,
+        }
+    }
+
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_reflects_build() {
+        let caps = Capabilities::current();
+        assert_eq!(caps.has_feature("direct_s3"), cfg!(feature = "direct-s3"));
+        assert!(caps.commands.contains(&"get_client_capabilities"));
+
+        let json = serde_json::to_value(&caps).unwrap();
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert!(!json["auth_methods"].as_array().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+//! Text that arrives without a file, like a quick note or pasted clipboard
+//! contents, saved as a document so it can go through the upload pipeline.
+//! The documents are kept afterwards, as the originals the ledger points to.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::Error;
+use crate::naming::{Capture, CaptureNaming};
+use crate::paths;
+
+/// Where captured documents are saved.
+pub fn captures_dir() -> Result<PathBuf, Error> {
+    Ok(paths::data_dir().map_err(Error::Io)?.join("captures"))
+}
+
+/// Save `content` in `dir` as a Markdown document named by `naming`, with
+/// `title` as its heading when there is one. Returns the new file's path.
+pub fn save_text(
+    dir: &Path,
+    naming: &CaptureNaming,
+    kind: &str,
+    title: Option<&str>,
+    content: &str,
+    captured_at: u64,
+) -> Result<PathBuf, Error> {
+    let title = title.map(str::trim).filter(|t| !t.is_empty());
+    if content.trim().is_empty() && title.is_none() {
+        return Err(Error::Validation("Nothing to ingest: the text is empty".to_string()));
+    }
+    let io = |what: &str, path: &Path, e: std::io::Error| {
+        Error::Io(format!("Failed to {} {}: {}", what, path.display(), e))
+    };
+    std::fs::create_dir_all(dir).map_err(|e| io("create", dir, e))?;
+
+    let stem = naming.file_stem(&Capture {
+        kind,
+        text: Some(title.unwrap_or(content)),
+        captured_at,
+        counter: captures_on_day(dir, naming, captured_at),
+    });
+    let document = match title {
+        Some(title) => format!("# {}\n\n{}\n", title, content.trim_end()),
+        None => format!("{}\n", content.trim_end()),
+    };
+
+    // Never overwrite an earlier capture that got the same name
+    for n in 1.. {
+        let name = match n {
+            1 => format!("{}.md", stem),
+            n => format!("{}-{}.md", stem, n),
+        };
+        let path = dir.join(name);
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                use std::io::Write;
+                file.write_all(document.as_bytes()).map_err(|e| io("write", &path, e))?;
+                return Ok(path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(io("create", &path, e)),
+        }
+    }
+    unreachable!("some numbered name is free")
+}
+
+/// How many captures in `dir` were saved on the same local day as
+/// `captured_at`, for the `{counter}` placeholder.
+fn captures_on_day(dir: &Path, naming: &CaptureNaming, captured_at: u64) -> u32 {
+    let offset = i64::from(naming.utc_offset_minutes) * 60;
+    let day = |secs: u64| (secs as i64 + offset).div_euclid(86_400);
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .filter_map(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .filter(|t| day(t.as_secs()) == day(captured_at))
+        .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_text_names_and_never_overwrites() {
+        let dir = std::env::temp_dir().join(format!("exemem-captures-{}", uuid::Uuid::new_v4()));
+        let naming = CaptureNaming {
+            template: "{kind}-{title}".to_string(),
+            ..Default::default()
+        };
+        let now = crate::ledger::now_secs();
+
+        let first = save_text(&dir, &naming, "note", Some("Call Sam"), "about the lease\n\n", now).unwrap();
+        assert_eq!(first.file_name().unwrap(), "note-call-sam.md");
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "# Call Sam\n\nabout the lease\n");
+
+        let second = save_text(&dir, &naming, "note", Some("Call Sam"), "again", now).unwrap();
+        assert_eq!(second.file_name().unwrap(), "note-call-sam-2.md");
+
+        let pasted = save_text(&dir, &naming, "clipboard", None, "  \nhttps://example.com", now).unwrap();
+        assert_eq!(pasted.file_name().unwrap(), "clipboard-https-example-com.md");
+
+        assert!(save_text(&dir, &naming, "note", Some(" "), "\n", now).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::Path;
+
+/// Text-like formats that compress well enough to be worth the CPU.
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &["json", "csv", "log", "txt"];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionAlgorithm {
+    Gzip,
+    Zstd,
+}
+
+impl Default for CompressionAlgorithm {
+    fn default() -> Self {
+        Self::Gzip
+    }
+}
+
+impl CompressionAlgorithm {
+    /// Value used for the Content-Encoding header and presigned URL request.
+    pub fn content_encoding(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+fn default_min_size_bytes() -> u64 {
+    1024 * 1024
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompressionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub algorithm: CompressionAlgorithm,
+    /// Files smaller than this are uploaded as-is
+    #[serde(default = "default_min_size_bytes")]
+    pub min_size_bytes: u64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            algorithm: CompressionAlgorithm::default(),
+            min_size_bytes: default_min_size_bytes(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    /// Algorithm to use for this file, or None to upload it uncompressed.
+    pub fn algorithm_for(&self, path: &Path, size: u64) -> Option<CompressionAlgorithm> {
+        if self.enabled && size >= self.min_size_bytes && is_compressible(path) {
+            Some(self.algorithm)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn is_compressible(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| COMPRESSIBLE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+pub fn compress(bytes: &[u8], algorithm: CompressionAlgorithm) -> Result<Vec<u8>, String> {
+    match algorithm {
+        CompressionAlgorithm::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder
+                .write_all(bytes)
+                .and_then(|_| encoder.finish())
+                .map_err(|e| format!("Gzip compression failed: {}", e))
+        }
+        CompressionAlgorithm::Zstd => zstd::encode_all(bytes, 0)
+            .map_err(|e| format!("Zstd compression failed: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_gzip_roundtrip() {
+        let data = b"{\"a\": 1}\n".repeat(1000);
+        let compressed = compress(&data, CompressionAlgorithm::Gzip).unwrap();
+        assert!(compressed.len() < data.len());
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_zstd_roundtrip() {
+        let data = b"timestamp,level,message\n".repeat(1000);
+        let compressed = compress(&data, CompressionAlgorithm::Zstd).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(zstd::decode_all(&compressed[..]).unwrap(), data);
+    }
+
+    #[test]
+    fn test_algorithm_for_respects_threshold_and_type() {
+        let config = CompressionConfig {
+            enabled: true,
+            algorithm: CompressionAlgorithm::Zstd,
+            min_size_bytes: 100,
+        };
+        assert_eq!(
+            config.algorithm_for(Path::new("a.csv"), 200),
+            Some(CompressionAlgorithm::Zstd)
+        );
+        assert_eq!(config.algorithm_for(Path::new("a.csv"), 50), None);
+        assert_eq!(config.algorithm_for(Path::new("a.png"), 200), None);
+        assert_eq!(
+            CompressionConfig::default().algorithm_for(Path::new("a.csv"), 1 << 30),
+            None
+        );
+    }
+}
@@ -1,14 +1,46 @@
-use directories::ProjectDirs;
+use crate::compression::CompressionConfig;
+use crate::config_crypto::{self, ConfigKey};
+use crate::config_migrations::{self, CONFIG_VERSION};
+use crate::direct_s3::DirectS3Config;
+use crate::env_config::{EnvOverrides, CONFIG_PASSPHRASE_VAR};
+use crate::error::Error;
+use crate::file_actions::PostIngestAction;
+use crate::hooks::HookConfig;
+use crate::http::{ConnectionConfig, ProxyConfig, TlsConfig};
+use crate::naming::CaptureNaming;
+use crate::paths;
+use crate::profiles::Profiles;
+use crate::query_cache::QueryCacheConfig;
+use crate::scanner::ScannerConfig;
+use crate::schedule::UploadSchedule;
+use crate::signing::RequestSigner;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::path::PathBuf;
 
-const DEV_API_URL: &str = "https://ygyu7ritx8.execute-api.us-west-2.amazonaws.com";
-const PROD_API_URL: &str = "https://jdsx4ixk2i.execute-api.us-east-1.amazonaws.com";
+pub(crate) const DEV_API_URL: &str = "https://ygyu7ritx8.execute-api.us-west-2.amazonaws.com";
+pub(crate) const PROD_API_URL: &str = "https://jdsx4ixk2i.execute-api.us-east-1.amazonaws.com";
 
 fn default_true() -> bool {
     true
 }
 
+fn default_deleted_retention_days() -> u64 {
+    30
+}
+
+fn default_config_version() -> u32 {
+    CONFIG_VERSION
+}
+
+fn default_query_timeout_secs() -> u64 {
+    crate::cancel::QUERY_TIMEOUT.as_secs()
+}
+
+fn default_quick_query_shortcut() -> String {
+    "CommandOrControl+Shift+Space".to_string()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Environment {
     Dev,
@@ -22,6 +54,21 @@ impl Default for Environment {
     }
 }
 
+/// Whether `config.json` is encrypted with a passphrase, and if so whether
+/// it has been unlocked in this process.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ConfigEncryption {
+    pub encrypted: bool,
+    pub locked: bool,
+}
+
+pub(crate) fn locked_error() -> Error {
+    Error::Auth(format!(
+        "config.json is encrypted; unlock it with its passphrase or set {}",
+        CONFIG_PASSPHRASE_VAR
+    ))
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
     pub api_base_url: String,
@@ -36,6 +83,75 @@ pub struct AppConfig {
     pub session_token: Option<String>,
     #[serde(default)]
     pub user_hash: Option<String>,
+    /// Language for user-facing messages (e.g. "es"); defaults to the system locale
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// Tags attached to the metadata of every ingested file
+    #[serde(default)]
+    pub default_tags: Vec<String>,
+    /// Hidden activity/ledger entries are purged after this many days
+    #[serde(default = "default_deleted_retention_days")]
+    pub deleted_retention_days: u64,
+    /// Run the upload pipeline without any network calls, logging what
+    /// would have been uploaded
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Applied to new HTTP clients, so changes take effect after a restart
+    #[serde(default)]
+    pub proxy: ProxyConfig,
+    /// Extra CA certificates and client certificate; also applied at startup
+    #[serde(default)]
+    pub tls: TlsConfig,
+    /// Pooling, keep-alive and HTTP/2 for the shared client; also applied
+    /// at startup
+    #[serde(default)]
+    pub connection: ConnectionConfig,
+    /// Quiet hours for watcher-originated uploads; manual ingests ignore it
+    #[serde(default)]
+    pub upload_schedule: UploadSchedule,
+    /// File names for documents created from captures rather than files
+    #[serde(default)]
+    pub capture_naming: CaptureNaming,
+    /// Command and/or webhook run when a file finishes ingestion
+    #[serde(default)]
+    pub hooks: HookConfig,
+    /// Upload to the user's own S3 bucket instead of Exemem's
+    #[serde(default)]
+    pub direct_s3: DirectS3Config,
+    /// What to do with a local file once its ingestion is confirmed
+    #[serde(default)]
+    pub post_ingest_action: PostIngestAction,
+    /// Reuse responses to repeated queries and searches
+    #[serde(default)]
+    pub query_cache: QueryCacheConfig,
+    /// How long a query, follow-up or search may run before it's abandoned,
+    /// unless the call asks for a different limit
+    #[serde(default = "default_query_timeout_secs")]
+    pub query_timeout_secs: u64,
+    /// Environments whose API requests are HMAC-signed instead of carrying
+    /// the API key, for deployments that verify signatures
+    #[serde(default)]
+    pub request_signing: Vec<Environment>,
+    /// Scan limits, skipped directories and custom categories
+    #[serde(default)]
+    pub scanner: ScannerConfig,
+    /// Send anonymous usage events (features used, durations, error kinds).
+    /// Off unless the user opts in
+    #[serde(default)]
+    pub telemetry_enabled: bool,
+    /// Start the app in the tray when the user logs in, so syncing doesn't
+    /// depend on remembering to open it
+    #[serde(default)]
+    pub launch_at_login: bool,
+    /// Global hotkey that shows or hides the quick-query window, e.g.
+    /// "CommandOrControl+Shift+Space"; empty turns it off
+    #[serde(default = "default_quick_query_shortcut")]
+    pub quick_query_shortcut: String,
+    /// Format version of the file; older files are migrated when loaded
+    #[serde(default = "default_config_version")]
+    pub config_version: u32,
 }
 
 impl Default for AppConfig {
@@ -49,38 +165,234 @@ impl Default for AppConfig {
             environment: Environment::default(),
             session_token: None,
             user_hash: None,
+            locale: None,
+            compression: CompressionConfig::default(),
+            default_tags: Vec::new(),
+            deleted_retention_days: default_deleted_retention_days(),
+            dry_run: false,
+            proxy: ProxyConfig::default(),
+            tls: TlsConfig::default(),
+            connection: ConnectionConfig::default(),
+            upload_schedule: UploadSchedule::default(),
+            capture_naming: CaptureNaming::default(),
+            hooks: HookConfig::default(),
+            direct_s3: DirectS3Config::default(),
+            post_ingest_action: PostIngestAction::default(),
+            query_cache: QueryCacheConfig::default(),
+            query_timeout_secs: default_query_timeout_secs(),
+            request_signing: Vec::new(),
+            scanner: ScannerConfig::default(),
+            telemetry_enabled: false,
+            launch_at_login: false,
+            quick_query_shortcut: default_quick_query_shortcut(),
+            config_version: CONFIG_VERSION,
         }
     }
 }
 
 impl AppConfig {
-    fn config_path() -> Result<PathBuf, String> {
-        let dirs = ProjectDirs::from("ai", "exemem", "exemem-client")
-            .ok_or_else(|| "Could not determine config directory".to_string())?;
-        Ok(dirs.config_dir().join("config.json"))
+    fn config_path() -> Result<PathBuf, Error> {
+        Ok(paths::config_dir().map_err(Error::Io)?.join("config.json"))
+    }
+
+    /// The config file with any `EXEMEM_*` environment overrides applied;
+    /// see `env_config` for the precedence.
+    pub fn load() -> Result<Self, Error> {
+        Self::load_with_profile(None)
+    }
+
+    /// The effective config: defaults, then the file, then `profile`'s
+    /// account settings when given, then environment overrides.
+    pub fn load_with_profile(profile: Option<&str>) -> Result<Self, Error> {
+        let mut value = Self::file_json_with_defaults()?;
+        if let Some(name) = profile {
+            let profiles = Profiles::load()?;
+            let profile = profiles
+                .get(name)
+                .ok_or_else(|| Error::Validation(format!("No profile named '{}'", name)))?;
+            profile.apply_json(&mut value).map_err(Error::Internal)?;
+        }
+        EnvOverrides::from_env()
+            .apply_json(&mut value)
+            .map_err(Error::Validation)?;
+        serde_json::from_value(value)
+            .map_err(|e| Error::Validation(format!("Failed to parse config: {}", e)))
+    }
+
+    /// The config file as JSON, as saved (and decrypted); an empty object
+    /// if there isn't one yet.
+    ///
+    /// A file from an older version is migrated first: the original is
+    /// kept as `config.v<N>.json.bak` and the upgraded file written in its
+    /// place. A file from a newer version is an error.
+    pub fn load_file_json() -> Result<Value, Error> {
+        let path = Self::config_path()?;
+        let Some(file) = Self::read_file()? else {
+            return Ok(Value::Object(Default::default()));
+        };
+        let mut value = if config_crypto::is_encrypted(&file) {
+            Self::open_encrypted(&file)?
+        } else {
+            file
+        };
+        let from = config_migrations::migrate(&mut value).map_err(Error::Validation)?;
+        if from < CONFIG_VERSION {
+            let backup = path.with_extension(format!("v{}.json.bak", from));
+            std::fs::copy(&path, &backup)
+                .map_err(|e| Error::Io(format!("Failed to back up config: {}", e)))?;
+            Self::write_file(&value, config_crypto::unlocked().as_ref())?;
+            log::info!(
+                "Migrated config from version {} to {}; the original is at {:?}",
+                from, CONFIG_VERSION, backup
+            );
+        }
+        Ok(value)
     }
 
-    pub fn load() -> Result<Self, String> {
+    /// The file exactly as stored, encrypted or not.
+    fn read_file() -> Result<Option<Value>, Error> {
         let path = Self::config_path()?;
         if !path.exists() {
-            return Ok(Self::default());
+            return Ok(None);
         }
         let data = std::fs::read_to_string(&path)
-            .map_err(|e| format!("Failed to read config: {}", e))?;
+            .map_err(|e| Error::Io(format!("Failed to read config: {}", e)))?;
         serde_json::from_str(&data)
-            .map_err(|e| format!("Failed to parse config: {}", e))
+            .map(Some)
+            .map_err(|e| Error::Validation(format!("Failed to parse config: {}", e)))
     }
 
-    pub fn save(&self) -> Result<(), String> {
+    /// Decrypt with the unlocked key. The CLI can't prompt, so when nothing
+    /// is unlocked yet the passphrase may come from the environment.
+    pub(crate) fn open_encrypted(file: &Value) -> Result<Value, Error> {
+        if let Some(key) = config_crypto::unlocked() {
+            return config_crypto::decrypt_with(file, &key).map_err(Error::Auth);
+        }
+        let passphrase = std::env::var(CONFIG_PASSPHRASE_VAR)
+            .ok()
+            .filter(|p| !p.is_empty())
+            .ok_or_else(locked_error)?;
+        let (value, key) = config_crypto::decrypt(file, &passphrase).map_err(Error::Auth)?;
+        config_crypto::set_unlocked(Some(key));
+        Ok(value)
+    }
+
+    /// Write `value` as the config file, sealed with `key` if given.
+    fn write_file(value: &Value, key: Option<&ConfigKey>) -> Result<(), Error> {
         let path = Self::config_path()?;
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)
-                .map_err(|e| format!("Failed to create config dir: {}", e))?;
+                .map_err(|e| Error::Io(format!("Failed to create config dir: {}", e)))?;
         }
-        let data = serde_json::to_string_pretty(self)
-            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        let value = match key {
+            Some(key) => config_crypto::encrypt(key, value).map_err(Error::Internal)?,
+            None => value.clone(),
+        };
+        let data = serde_json::to_string_pretty(&value)
+            .map_err(|e| Error::Internal(format!("Failed to serialize config: {}", e)))?;
         std::fs::write(&path, data)
-            .map_err(|e| format!("Failed to write config: {}", e))
+            .map_err(|e| Error::Io(format!("Failed to write config: {}", e)))
+    }
+
+    pub fn encryption() -> Result<ConfigEncryption, Error> {
+        let encrypted = Self::read_file()?.is_some_and(|file| config_crypto::is_encrypted(&file));
+        Ok(ConfigEncryption {
+            encrypted,
+            locked: encrypted && config_crypto::unlocked().is_none(),
+        })
+    }
+
+    /// Unlock an encrypted config file for this process and load it.
+    pub fn unlock(passphrase: &str) -> Result<Self, Error> {
+        if let Some(file) = Self::read_file()?.filter(config_crypto::is_encrypted) {
+            let (_, key) = config_crypto::decrypt(&file, passphrase).map_err(Error::Auth)?;
+            config_crypto::set_unlocked(Some(key));
+            // Profiles saved before they were sealed with the config
+            if let Err(e) = Profiles::seal() {
+                log::warn!("Failed to encrypt profiles: {}", e);
+            }
+        }
+        Self::load()
+    }
+
+    /// Forget the unlocked key; loading and saving fail until it's unlocked
+    /// again.
+    pub fn lock() {
+        config_crypto::set_unlocked(None);
+    }
+
+    /// Save this config encrypted with `passphrase`, or unencrypted with
+    /// None. Only possible while unlocked, so a locked file can't be
+    /// replaced by whatever config happens to be in memory.
+    pub fn set_passphrase(&self, passphrase: Option<&str>) -> Result<(), Error> {
+        if Self::encryption()?.locked {
+            return Err(locked_error());
+        }
+        let key = passphrase
+            .map(ConfigKey::new)
+            .transpose()
+            .map_err(Error::Validation)?;
+        // Read with the old key, written back with the new one
+        let profiles = Profiles::load()?;
+        self.save_with_key(key.as_ref())?;
+        config_crypto::set_unlocked(key);
+        profiles.save()
+    }
+
+    fn file_json_with_defaults() -> Result<Value, Error> {
+        let mut value = serde_json::to_value(Self::default())
+            .map_err(|e| Error::Internal(format!("Failed to serialize config: {}", e)))?;
+        if let (Value::Object(fields), Value::Object(file)) = (&mut value, Self::load_file_json()?) {
+            fields.extend(file);
+        }
+        Ok(value)
+    }
+
+    /// Write the config, keeping the file's own values for any fields that
+    /// are currently overridden from the environment. An encrypted file
+    /// stays encrypted, and can't be saved over while locked.
+    pub fn save(&self) -> Result<(), Error> {
+        let key = config_crypto::unlocked();
+        if key.is_none() && Self::encryption()?.encrypted {
+            return Err(locked_error());
+        }
+        self.save_with_key(key.as_ref())
+    }
+
+    fn save_with_key(&self, key: Option<&ConfigKey>) -> Result<(), Error> {
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| Error::Internal(format!("Failed to serialize config: {}", e)))?;
+        let overrides = EnvOverrides::from_env();
+        if !overrides.is_empty() {
+            overrides.restore_json(&mut value, &Self::file_json_with_defaults()?);
+        }
+        Self::write_file(&value, key)
+    }
+
+    /// Check every section that has its own validation.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.proxy.validate().map_err(Error::Validation)?;
+        self.tls.validate().map_err(Error::Validation)?;
+        self.connection.validate().map_err(Error::Validation)?;
+        self.upload_schedule.validate().map_err(Error::Validation)?;
+        self.capture_naming.validate().map_err(Error::Validation)?;
+        self.hooks.validate().map_err(Error::Validation)?;
+        self.direct_s3.validate().map_err(Error::Validation)?;
+        self.scanner.validate().map_err(Error::Validation)?;
+        let shortcut = self.quick_query_shortcut.trim();
+        if !shortcut.is_empty() {
+            shortcut
+                .parse::<tauri_plugin_global_shortcut::Shortcut>()
+                .map_err(|e| Error::Validation(format!("Invalid quick-query shortcut {:?}: {}", shortcut, e)))?;
+        }
+        let max = crate::cancel::MAX_QUERY_TIMEOUT.as_secs();
+        if !(1..=max).contains(&self.query_timeout_secs) {
+            return Err(Error::Validation(format!(
+                "Query timeout must be between 1 and {} seconds",
+                max
+            )));
+        }
+        Ok(())
     }
 
     pub fn api_url(&self) -> &str {
@@ -91,6 +403,12 @@ impl AppConfig {
         }
     }
 
+    /// Signer for API requests, if signing is on for the current environment.
+    pub fn signer(&self) -> Option<RequestSigner> {
+        (self.request_signing.contains(&self.environment) && !self.api_key.is_empty())
+            .then(|| RequestSigner::from_api_key(&self.api_key))
+    }
+
     pub fn is_configured(&self) -> bool {
         !self.api_url().is_empty()
             && !self.api_key.is_empty()
@@ -0,0 +1,187 @@
+use crate::config::{AppConfig, Environment};
+use crate::error::Error;
+use crate::query::QueryClient;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Uploads or queries will fail until it's fixed
+    Error,
+    /// Works, but probably not as intended
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ConfigProblem {
+    /// Config field the problem is about, e.g. "watched_folder"
+    pub field: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Everything found wrong with a config, so it can be fixed up front rather
+/// than discovered on the first failed upload.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigReport {
+    /// No errors (warnings are allowed)
+    pub ok: bool,
+    pub api_url: String,
+    /// Whether the API answered at all; None when it wasn't checked
+    pub api_reachable: Option<bool>,
+    /// Whether the API accepted the API key; None when it wasn't checked
+    pub api_key_accepted: Option<bool>,
+    pub problems: Vec<ConfigProblem>,
+}
+
+impl ConfigReport {
+    fn new(config: &AppConfig, problems: Vec<ConfigProblem>) -> Self {
+        Self {
+            ok: !problems.iter().any(|p| p.severity == Severity::Error),
+            api_url: config.api_url().to_string(),
+            api_reachable: None,
+            api_key_accepted: None,
+            problems,
+        }
+    }
+
+    fn push(&mut self, field: &'static str, severity: Severity, message: String) {
+        if severity == Severity::Error {
+            self.ok = false;
+        }
+        self.problems.push(ConfigProblem {
+            field,
+            severity,
+            message,
+        });
+    }
+}
+
+/// Problems visible without the network: missing fields, invalid sections,
+/// and a watched folder that can't be read.
+pub fn check_fields(config: &AppConfig) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    let mut error = |field, message: String| {
+        problems.push(ConfigProblem {
+            field,
+            severity: Severity::Error,
+            message,
+        })
+    };
+
+    if config.api_key.trim().is_empty() {
+        error("api_key", "No API key is set".to_string());
+    }
+    if config.environment == Environment::Custom {
+        match url::Url::parse(config.api_base_url.trim()) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            _ => error(
+                "api_base_url",
+                format!("Custom API URL {:?} is not an http(s) URL", config.api_base_url),
+            ),
+        }
+    }
+    match &config.watched_folder {
+        None => error("watched_folder", "No watched folder is set".to_string()),
+        Some(folder) => {
+            if let Err(message) = check_folder(folder) {
+                error("watched_folder", message);
+            }
+        }
+    }
+    if let Err(Error::Validation(message)) = config.validate() {
+        error("config", message);
+    }
+
+    if config.dry_run {
+        problems.push(ConfigProblem {
+            field: "dry_run",
+            severity: Severity::Warning,
+            message: "Dry run is on, so nothing will be uploaded".to_string(),
+        });
+    }
+    problems
+}
+
+fn check_folder(folder: &Path) -> Result<(), String> {
+    let metadata = std::fs::metadata(folder)
+        .map_err(|e| format!("Watched folder {} can't be opened: {}", folder.display(), e))?;
+    if !metadata.is_dir() {
+        return Err(format!("Watched folder {} is not a folder", folder.display()));
+    }
+    std::fs::read_dir(folder)
+        .map(|_| ())
+        .map_err(|e| format!("Watched folder {} can't be read: {}", folder.display(), e))
+}
+
+/// `check_fields`, plus an authenticated request to the configured API.
+pub async fn check_config(config: &AppConfig, client: &QueryClient) -> ConfigReport {
+    let mut report = ConfigReport::new(config, check_fields(config));
+    if config.api_url().is_empty() || config.api_key.trim().is_empty() {
+        return report;
+    }
+
+    match client.list_schemas(config).await {
+        Ok(_) => {
+            report.api_reachable = Some(true);
+            report.api_key_accepted = Some(true);
+        }
+        Err(Error::Auth(message)) => {
+            report.api_reachable = Some(true);
+            report.api_key_accepted = Some(false);
+            report.push("api_key", Severity::Error, format!("The API rejected the key: {}", message));
+        }
+        Err(e @ (Error::Network(_) | Error::Timeout(_))) => {
+            report.api_reachable = Some(false);
+            report.push("api_base_url", Severity::Error, format!("Can't reach {}: {}", config.api_url(), e));
+        }
+        Err(e) => {
+            // It answered, just not successfully; the key may still be fine
+            report.api_reachable = Some(true);
+            report.push("api_base_url", Severity::Warning, format!("The API answered with an error: {}", e));
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(problems: &[ConfigProblem]) -> Vec<&'static str> {
+        problems.iter().map(|p| p.field).collect()
+    }
+
+    #[test]
+    fn test_empty_config_reports_required_fields() {
+        let problems = check_fields(&AppConfig::default());
+        assert_eq!(fields(&problems), vec!["api_key", "watched_folder"]);
+        assert!(problems.iter().all(|p| p.severity == Severity::Error));
+    }
+
+    #[test]
+    fn test_folder_and_custom_url_checks() {
+        let dir = std::env::temp_dir();
+        let config = AppConfig {
+            api_key: "key".to_string(),
+            environment: Environment::Custom,
+            api_base_url: "localhost:9000".to_string(),
+            watched_folder: Some(dir.join(format!("missing-{}", uuid::Uuid::new_v4()))),
+            dry_run: true,
+            ..Default::default()
+        };
+        let problems = check_fields(&config);
+        assert_eq!(fields(&problems), vec!["api_base_url", "watched_folder", "dry_run"]);
+        assert_eq!(problems[2].severity, Severity::Warning);
+
+        let fixed = AppConfig {
+            api_base_url: "http://localhost:9000".to_string(),
+            watched_folder: Some(dir),
+            dry_run: false,
+            ..config
+        };
+        assert!(check_fields(&fixed).is_empty());
+        assert!(ConfigReport::new(&fixed, Vec::new()).ok);
+    }
+}
@@ -0,0 +1,203 @@
+//! Passphrase encryption of `config.json`, for users who can't or won't keep
+//! the API key in the OS keychain.
+//!
+//! An encrypted file is `{"encrypted": {...}}`: the config JSON sealed with
+//! AES-256-GCM under a key derived from the passphrase with Argon2id. The
+//! key is kept in memory once unlocked, so saves stay encrypted without
+//! asking again. `profiles.json` holds API keys too, so it is sealed the
+//! same way with the same key.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::Mutex;
+
+const ENVELOPE_FIELD: &str = "encrypted";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Key of the unlocked config, if any.
+static UNLOCKED: Mutex<Option<ConfigKey>> = Mutex::new(None);
+
+/// Argon2id cost, stored with the file so it can be raised later without
+/// breaking existing files.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Envelope {
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Clone)]
+pub(crate) struct ConfigKey {
+    key: [u8; 32],
+    salt: [u8; SALT_LEN],
+    kdf: KdfParams,
+}
+
+impl ConfigKey {
+    /// A key for a newly set passphrase, with a fresh salt.
+    pub(crate) fn new(passphrase: &str) -> Result<Self, String> {
+        if passphrase.chars().count() < 8 {
+            return Err("Passphrase must be at least 8 characters".to_string());
+        }
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt, KdfParams::default())
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LEN], kdf: KdfParams) -> Result<Self, String> {
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+            .map_err(|e| format!("Invalid key derivation settings: {}", e))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        Ok(Self { key, salt, kdf })
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.key))
+    }
+}
+
+pub(crate) fn is_encrypted(file: &Value) -> bool {
+    file.get(ENVELOPE_FIELD).is_some()
+}
+
+pub(crate) fn unlocked() -> Option<ConfigKey> {
+    UNLOCKED.lock().unwrap().clone()
+}
+
+pub(crate) fn set_unlocked(key: Option<ConfigKey>) {
+    *UNLOCKED.lock().unwrap() = key;
+}
+
+/// Seal `config` into an encrypted file body.
+pub(crate) fn encrypt(key: &ConfigKey, config: &Value) -> Result<Value, String> {
+    let plaintext = serde_json::to_vec(config).map_err(|e| e.to_string())?;
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+    let ciphertext = key
+        .cipher()
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| "Failed to encrypt config".to_string())?;
+    let envelope = Envelope {
+        kdf: key.kdf,
+        salt: BASE64.encode(key.salt),
+        nonce: BASE64.encode(nonce),
+        ciphertext: BASE64.encode(ciphertext),
+    };
+    Ok(json!({ ENVELOPE_FIELD: envelope }))
+}
+
+/// Open an encrypted file body with `passphrase`, returning the config and
+/// the key to seal later saves with.
+pub(crate) fn decrypt(file: &Value, passphrase: &str) -> Result<(Value, ConfigKey), String> {
+    let envelope = envelope(file)?;
+    let salt: [u8; SALT_LEN] = decode(&envelope.salt, "salt")?
+        .try_into()
+        .map_err(|_| "Encrypted config has an invalid salt".to_string())?;
+    let key = ConfigKey::derive(passphrase, salt, envelope.kdf)?;
+    let config = open(&envelope, &key)?;
+    Ok((config, key))
+}
+
+/// Open an encrypted file body with an already unlocked key. Fails if the
+/// file was re-encrypted with a different passphrase since.
+pub(crate) fn decrypt_with(file: &Value, key: &ConfigKey) -> Result<Value, String> {
+    open(&envelope(file)?, key)
+}
+
+fn envelope(file: &Value) -> Result<Envelope, String> {
+    let body = file
+        .get(ENVELOPE_FIELD)
+        .ok_or_else(|| "Config is not encrypted".to_string())?;
+    serde_json::from_value(body.clone()).map_err(|e| format!("Invalid encrypted config: {}", e))
+}
+
+fn open(envelope: &Envelope, key: &ConfigKey) -> Result<Value, String> {
+    let nonce = decode(&envelope.nonce, "nonce")?;
+    if nonce.len() != NONCE_LEN {
+        return Err("Encrypted config has an invalid nonce".to_string());
+    }
+    let ciphertext = decode(&envelope.ciphertext, "ciphertext")?;
+    // A wrong passphrase and a tampered file look the same to AES-GCM
+    let plaintext = key
+        .cipher()
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| "Wrong passphrase, or the config file is damaged".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("Failed to parse config: {}", e))
+}
+
+fn decode(value: &str, what: &str) -> Result<Vec<u8>, String> {
+    BASE64
+        .decode(value)
+        .map_err(|_| format!("Encrypted config has an invalid {}", what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Cheap parameters so tests don't spend seconds in Argon2
+    fn test_key(passphrase: &str) -> ConfigKey {
+        let kdf = KdfParams {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        };
+        ConfigKey::derive(passphrase, [7; SALT_LEN], kdf).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip_and_wrong_passphrase() {
+        let config = json!({"api_key": "secret-key", "environment": "Prod"});
+        let key = test_key("correct horse");
+        let file = encrypt(&key, &config).unwrap();
+        assert!(is_encrypted(&file));
+        assert!(!file.to_string().contains("secret-key"));
+
+        let (opened, reopened_key) = decrypt(&file, "correct horse").unwrap();
+        assert_eq!(opened, config);
+        assert_eq!(decrypt_with(&file, &reopened_key).unwrap(), config);
+        assert!(decrypt(&file, "wrong horse").is_err());
+    }
+
+    #[test]
+    fn test_rejects_tampering_and_short_passphrases() {
+        let key = test_key("correct horse");
+        let mut file = encrypt(&key, &json!({"api_key": "k"})).unwrap();
+        let ciphertext = file[ENVELOPE_FIELD]["ciphertext"].as_str().unwrap().to_string();
+        let mut bytes = BASE64.decode(ciphertext).unwrap();
+        bytes[0] ^= 1;
+        file[ENVELOPE_FIELD]["ciphertext"] = json!(BASE64.encode(bytes));
+        assert!(decrypt_with(&file, &key).is_err());
+
+        assert!(ConfigKey::new("short").is_err());
+        assert!(!is_encrypted(&json!({"api_key": "k"})));
+    }
+}
@@ -0,0 +1,105 @@
+//! Upgrades for `config.json` written by older versions of the client.
+//!
+//! Serde defaults cover fields that are simply new, but not fields that were
+//! renamed or whose meaning changed: those would be dropped or misread
+//! without a word. Each such change gets a step here and bumps
+//! `CONFIG_VERSION`. Files without a `config_version` are version 1.
+
+use crate::config::{DEV_API_URL, PROD_API_URL};
+use serde_json::{json, Map, Value};
+
+/// `MIGRATIONS[i]` turns a version `i + 1` config into version `i + 2`.
+const MIGRATIONS: &[fn(&mut Map<String, Value>)] = &[v1_to_v2];
+
+/// The version this build writes.
+pub const CONFIG_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+pub(crate) const VERSION_FIELD: &str = "config_version";
+
+/// Version 1 configs predate the `environment` field and always talked to
+/// `api_base_url`; the serde default would quietly switch them to Dev.
+fn v1_to_v2(config: &mut Map<String, Value>) {
+    if config.contains_key("environment") {
+        return;
+    }
+    let url = config
+        .get("api_base_url")
+        .and_then(Value::as_str)
+        .map(|u| u.trim().trim_end_matches('/'))
+        .unwrap_or("");
+    let environment = match url {
+        "" | DEV_API_URL => "Dev",
+        PROD_API_URL => "Prod",
+        _ => "Custom",
+    };
+    config.insert("environment".to_string(), json!(environment));
+}
+
+/// The version a config file was written by.
+pub(crate) fn version_of(config: &Value) -> Result<u32, String> {
+    match config.get(VERSION_FIELD) {
+        None => Ok(1),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v >= 1)
+            .ok_or_else(|| format!("Invalid {}: {}", VERSION_FIELD, version)),
+    }
+}
+
+/// Bring `config` up to `CONFIG_VERSION`, returning the version it started
+/// at. Configs from a newer client are refused rather than loaded with
+/// their new settings dropped.
+pub(crate) fn migrate(config: &mut Value) -> Result<u32, String> {
+    let from = version_of(config)?;
+    if from > CONFIG_VERSION {
+        return Err(format!(
+            "config.json is version {}, but this version of Exemem only understands up to version {}. Update Exemem to use it",
+            from, CONFIG_VERSION
+        ));
+    }
+    let fields = config
+        .as_object_mut()
+        .ok_or_else(|| "Config is not an object".to_string())?;
+    for step in &MIGRATIONS[from as usize - 1..] {
+        step(fields);
+    }
+    fields.insert(VERSION_FIELD.to_string(), json!(CONFIG_VERSION));
+    Ok(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_keeps_its_api_url() {
+        let mut config = json!({"api_base_url": "https://exemem.internal.example/", "api_key": "k"});
+        assert_eq!(migrate(&mut config).unwrap(), 1);
+        assert_eq!(config["environment"], "Custom");
+        assert_eq!(config[VERSION_FIELD], CONFIG_VERSION);
+
+        let mut prod = json!({"api_base_url": PROD_API_URL});
+        migrate(&mut prod).unwrap();
+        assert_eq!(prod["environment"], "Prod");
+
+        // An explicit environment is left alone
+        let mut explicit = json!({"api_base_url": "http://localhost:9000", "environment": "Dev"});
+        migrate(&mut explicit).unwrap();
+        assert_eq!(explicit["environment"], "Dev");
+    }
+
+    #[test]
+    fn test_current_and_newer_versions() {
+        let mut current = json!({"environment": "Prod", VERSION_FIELD: CONFIG_VERSION});
+        let before = current.clone();
+        assert_eq!(migrate(&mut current).unwrap(), CONFIG_VERSION);
+        assert_eq!(current, before);
+
+        let mut newer = json!({VERSION_FIELD: CONFIG_VERSION + 1});
+        assert!(migrate(&mut newer).unwrap_err().contains("Update Exemem"));
+
+        let mut invalid = json!({VERSION_FIELD: "two"});
+        assert!(migrate(&mut invalid).is_err());
+    }
+}
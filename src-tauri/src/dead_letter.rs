@@ -0,0 +1,198 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::config::AppConfig;
+use crate::http;
+use crate::ledger::now_secs;
+use crate::persist::JsonStore;
+use crate::server_error::ServerErrorCode;
+use crate::upload_queue::UploadPriority;
+use crate::uploader::{FileContext, UploadResult, UploadStatus, Uploader};
+
+/// An upload that failed and is waiting for a manual retry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedUpload {
+    pub id: String,
+    pub path: PathBuf,
+    pub filename: String,
+    pub error: Option<String>,
+    pub error_code: Option<ServerErrorCode>,
+    pub attempts: u32,
+    /// Seconds since the Unix epoch
+    pub first_failed_at: u64,
+    pub last_failed_at: u64,
+    /// Classification and tags to reuse when retrying
+    pub context: FileContext,
+}
+
+/// Persistent list of failed uploads, keyed by file path. A file that fails
+/// again bumps its attempt count; a file that later succeeds is removed.
+pub struct DeadLetterQueue {
+    store: JsonStore<Vec<FailedUpload>>,
+}
+
+impl DeadLetterQueue {
+    const FILE: &'static str = "failed_uploads.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "failed uploads").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "failed uploads").map(|store| Self { store })
+    }
+
+    pub fn list(&self) -> Vec<FailedUpload> {
+        self.store.get().clone()
+    }
+
+    /// Entries with the given ids, or all entries when `ids` is None.
+    pub fn select(&self, ids: Option<&[String]>) -> Vec<FailedUpload> {
+        self.store
+            .get()
+            .iter()
+            .filter(|e| ids.map_or(true, |ids| ids.contains(&e.id)))
+            .cloned()
+            .collect()
+    }
+
+    /// Track the outcome of an upload: failures are added (or their attempt
+    /// count bumped), successes clear any earlier failure for the same file.
+    pub fn record_outcome(
+        &mut self,
+        file_path: &Path,
+        result: &UploadResult,
+        context: &FileContext,
+    ) -> Result<(), String> {
+        match result.status {
+            UploadStatus::Error => self.record_failure(file_path, result, context),
+            UploadStatus::DryRun => Ok(()),
+            _ => self.resolve(file_path),
+        }
+    }
+
+    fn record_failure(
+        &mut self,
+        file_path: &Path,
+        result: &UploadResult,
+        context: &FileContext,
+    ) -> Result<(), String> {
+        let now = now_secs();
+        self.store.update(|entries| match entries.iter_mut().find(|e| e.path == file_path) {
+            Some(entry) => {
+                entry.attempts += 1;
+                entry.last_failed_at = now;
+                entry.error = result.error.clone();
+                entry.error_code = result.error_code;
+            }
+            None => entries.push(FailedUpload {
+                id: uuid::Uuid::new_v4().to_string(),
+                path: file_path.to_path_buf(),
+                filename: result.filename.clone(),
+                error: result.error.clone(),
+                error_code: result.error_code,
+                attempts: 1,
+                first_failed_at: now,
+                last_failed_at: now,
+                context: context.clone(),
+            }),
+        })
+    }
+
+    fn resolve(&mut self, file_path: &Path) -> Result<(), String> {
+        self.store.update(|entries| entries.retain(|e| e.path != file_path))
+    }
+}
+
+/// List failed uploads from the persisted queue. Used by the CLI.
+pub fn list_failed_uploads() -> Result<Vec<FailedUpload>, String> {
+    Ok(DeadLetterQueue::load()?.list())
+}
+
+/// Retry failed uploads outside the desktop app (all of them when `ids` is
+/// None), updating the persisted queue with the outcome. Used by the CLI.
+pub async fn retry_failed_uploads(ids: Option<&[String]>) -> Result<Vec<UploadResult>, String> {
+    let config = AppConfig::load().map_err(|e| e.to_string())?;
+    let uploader = Uploader::new(http::build_client(&config.proxy, &config.tls, &config.connection));
+    let mut queue = DeadLetterQueue::load()?;
+
+    let mut results = Vec::new();
+    for failed in queue.select(ids) {
+        let result = uploader
+            .upload_and_ingest(
+                &failed.path,
+                &config,
+                UploadPriority::Manual,
+                failed.context.clone(),
+            )
+            .await;
+        queue.record_outcome(&failed.path, &result, &failed.context)?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_queue() -> DeadLetterQueue {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-dlq-{}", uuid::Uuid::new_v4()))
+            .join("failed_uploads.json");
+        open(path)
+    }
+
+    fn open(path: PathBuf) -> DeadLetterQueue {
+        DeadLetterQueue {
+            store: JsonStore::load_from(path, "failed uploads").unwrap(),
+        }
+    }
+
+    fn result(status: UploadStatus) -> UploadResult {
+        UploadResult {
+            filename: "a.json".to_string(),
+            s3_key: String::new(),
+            progress_id: None,
+            status,
+            error: Some("boom".to_string()),
+            error_code: None,
+            suggestion: None,
+            original_bytes: None,
+            uploaded_bytes: None,
+            content_encoding: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_failures_accumulate_attempts() {
+        let mut queue = temp_queue();
+        let path = Path::new("/data/a.json");
+        let context = FileContext::default();
+
+        queue.record_outcome(path, &result(UploadStatus::Error), &context).unwrap();
+        queue.record_outcome(path, &result(UploadStatus::Error), &context).unwrap();
+
+        let failed = queue.list();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].attempts, 2);
+    }
+
+    #[test]
+    fn test_success_clears_failure() {
+        let mut queue = temp_queue();
+        let path = Path::new("/data/a.json");
+        let context = FileContext::default();
+
+        queue.record_outcome(path, &result(UploadStatus::Error), &context).unwrap();
+        queue.record_outcome(path, &result(UploadStatus::DryRun), &context).unwrap();
+        assert_eq!(queue.list().len(), 1);
+
+        queue.record_outcome(path, &result(UploadStatus::Ingesting), &context).unwrap();
+        assert!(queue.list().is_empty());
+
+        let reloaded = open(queue.store.path().to_path_buf());
+        assert!(reloaded.list().is_empty());
+    }
+}
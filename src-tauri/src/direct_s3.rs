@@ -0,0 +1,228 @@
+#[cfg(feature = "direct-s3")]
+use aws_config::sts::AssumeRoleProvider;
+#[cfg(feature = "direct-s3")]
+use aws_config::BehaviorVersion;
+#[cfg(feature = "direct-s3")]
+use aws_sdk_s3::config::{Credentials, Region, SharedCredentialsProvider};
+#[cfg(feature = "direct-s3")]
+use aws_sdk_s3::error::DisplayErrorContext;
+#[cfg(feature = "direct-s3")]
+use aws_sdk_s3::primitives::ByteStream;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "direct-s3")]
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+/// Whether this build can upload to a user-owned bucket (the `direct-s3`
+/// feature pulls in the AWS SDK).
+pub const SUPPORTED: bool = cfg!(feature = "direct-s3");
+
+/// Upload files straight to the user's own bucket instead of Exemem's, and
+/// only register the object location with the ingest endpoint.
+///
+/// Credentials come from, in order: the access key pair, the named AWS
+/// profile, or the default AWS credential chain; `role_arn` is then assumed
+/// on top of whichever was used. The AWS SDK uses its own HTTP stack, so the
+/// proxy and TLS settings don't apply to these uploads.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DirectS3Config {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub bucket: String,
+    #[serde(default)]
+    pub region: String,
+    /// Key prefix inside the bucket, e.g. "exemem/"
+    #[serde(default)]
+    pub prefix: String,
+    /// For S3-compatible stores (MinIO, R2, ...); uses path-style addressing
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    #[serde(default)]
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub role_arn: Option<String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+impl DirectS3Config {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !SUPPORTED {
+            return Err("This build was compiled without direct S3 support".to_string());
+        }
+        if self.bucket.trim().is_empty() {
+            return Err("Direct S3 mode needs a bucket name".to_string());
+        }
+        if self.region.trim().is_empty() {
+            return Err("Direct S3 mode needs a region".to_string());
+        }
+        if non_empty(&self.access_key_id).is_some() != non_empty(&self.secret_access_key).is_some()
+        {
+            return Err("Set both the access key ID and the secret access key, or neither".to_string());
+        }
+        if let Some(endpoint) = non_empty(&self.endpoint_url) {
+            url::Url::parse(endpoint).map_err(|e| format!("Invalid S3 endpoint URL: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// Object key for a new upload of `filename`.
+    pub fn object_key(&self, filename: &str) -> String {
+        let prefix = self.prefix.trim().trim_matches('/');
+        if prefix.is_empty() {
+            format!("{}/{}", Uuid::new_v4(), filename)
+        } else {
+            format!("{}/{}/{}", prefix, Uuid::new_v4(), filename)
+        }
+    }
+
+    #[cfg(feature = "direct-s3")]
+    async fn build_client(&self) -> aws_sdk_s3::Client {
+        let mut loader =
+            aws_config::defaults(BehaviorVersion::latest()).region(Region::new(self.region.clone()));
+        if let (Some(id), Some(secret)) =
+            (non_empty(&self.access_key_id), non_empty(&self.secret_access_key))
+        {
+            loader = loader.credentials_provider(Credentials::new(
+                id,
+                secret,
+                None,
+                None,
+                "exemem-client",
+            ));
+        } else if let Some(profile) = non_empty(&self.profile) {
+            loader = loader.profile_name(profile);
+        }
+        let mut sdk_config = loader.load().await;
+
+        if let Some(role_arn) = non_empty(&self.role_arn) {
+            let provider = AssumeRoleProvider::builder(role_arn)
+                .session_name("exemem-client")
+                .configure(&sdk_config)
+                .build()
+                .await;
+            sdk_config = sdk_config
+                .into_builder()
+                .credentials_provider(SharedCredentialsProvider::new(provider))
+                .build();
+        }
+
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&sdk_config);
+        if let Some(endpoint) = non_empty(&self.endpoint_url) {
+            s3_config = s3_config.endpoint_url(endpoint).force_path_style(true);
+        }
+        aws_sdk_s3::Client::from_conf(s3_config.build())
+    }
+}
+
+/// S3 client for direct mode, rebuilt whenever the settings change.
+#[cfg(feature = "direct-s3")]
+#[derive(Default)]
+pub struct DirectS3Uploader {
+    client: Mutex<Option<(DirectS3Config, aws_sdk_s3::Client)>>,
+}
+
+#[cfg(feature = "direct-s3")]
+impl DirectS3Uploader {
+    async fn client(&self, config: &DirectS3Config) -> aws_sdk_s3::Client {
+        let mut cached = self.client.lock().await;
+        match cached.as_ref() {
+            Some((built_for, client)) if built_for == config => client.clone(),
+            _ => {
+                let client = config.build_client().await;
+                *cached = Some((config.clone(), client.clone()));
+                client
+            }
+        }
+    }
+
+    /// Store `body` under `key` in the configured bucket.
+    pub async fn put(
+        &self,
+        config: &DirectS3Config,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<(), String> {
+        self.client(config)
+            .await
+            .put_object()
+            .bucket(config.bucket.trim())
+            .key(key)
+            .content_type(content_type)
+            .body(ByteStream::from(body))
+            .send()
+            .await
+            .map_err(|e| format!("Upload to s3://{}/{} failed: {}", config.bucket, key, DisplayErrorContext(&e)))?;
+        Ok(())
+    }
+}
+
+/// Stand-in for builds without the AWS SDK; config validation already
+/// rejects direct mode there.
+#[cfg(not(feature = "direct-s3"))]
+#[derive(Default)]
+pub struct DirectS3Uploader;
+
+#[cfg(not(feature = "direct-s3"))]
+impl DirectS3Uploader {
+    pub async fn put(
+        &self,
+        _config: &DirectS3Config,
+        _key: &str,
+        _content_type: &str,
+        _body: Vec<u8>,
+    ) -> Result<(), String> {
+        Err("This build was compiled without direct S3 support".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> DirectS3Config {
+        DirectS3Config {
+            enabled: true,
+            bucket: "my-bucket".to_string(),
+            region: "us-east-1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(DirectS3Config::default().validate().is_ok());
+        assert_eq!(enabled().validate().is_ok(), SUPPORTED);
+
+        let mut missing_secret = enabled();
+        missing_secret.access_key_id = Some("AKIA".to_string());
+        assert!(missing_secret.validate().is_err());
+
+        let mut no_bucket = enabled();
+        no_bucket.bucket = " ".to_string();
+        assert!(no_bucket.validate().is_err());
+    }
+
+    #[test]
+    fn test_object_key_uses_prefix() {
+        let mut config = enabled();
+        config.prefix = "/exemem/".to_string();
+        let key = config.object_key("notes.txt");
+        assert!(key.starts_with("exemem/"));
+        assert!(key.ends_with("/notes.txt"));
+
+        config.prefix.clear();
+        assert!(!config.object_key("notes.txt").starts_with('/'));
+    }
+}
@@ -0,0 +1,350 @@
+//! Setup diagnostics for `exemem-cli doctor`: the config checks, plus the
+//! machine-level problems that make uploads fail in confusing ways (a full
+//! disk, a skewed clock, too few inotify watches).
+
+use serde::Serialize;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::config_check::{self, ConfigProblem, Severity};
+use crate::http;
+use crate::ledger;
+use crate::paths;
+use crate::query::QueryClient;
+
+const CLOCK_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Signed requests and presigned URLs are rejected beyond this skew
+const MAX_CLOCK_SKEW_SECS: u64 = 300;
+const WARN_CLOCK_SKEW_SECS: u64 = 30;
+const MIN_FREE_BYTES: u64 = 100 * 1024 * 1024;
+const WARN_FREE_BYTES: u64 = 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What to do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, message: String) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            message,
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: String, hint: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message,
+            hint: Some(hint.to_string()),
+        }
+    }
+
+    fn fail(name: &'static str, message: String, hint: &str) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message,
+            hint: Some(hint.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    /// No check failed (warnings are allowed)
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+/// Run every check against `config`.
+pub async fn diagnose(config: &AppConfig, client: &QueryClient) -> DoctorReport {
+    let report = config_check::check_config(config, client).await;
+    let (folder_problems, config_problems): (Vec<_>, Vec<_>) = config_check::check_fields(config)
+        .into_iter()
+        .partition(|p| p.field == "watched_folder");
+
+    let mut checks = vec![
+        config_check_result(&config_problems),
+        api_check(&report.api_url, report.api_reachable),
+        auth_check(report.api_key_accepted),
+        folder_check(config.watched_folder.as_deref(), &folder_problems),
+        disk_check(paths::data_dir().ok().as_deref().and_then(available_bytes)),
+        clock_check(clock_skew(config).await),
+    ];
+    #[cfg(target_os = "linux")]
+    checks.push(inotify_check(config.watched_folder.as_deref()));
+
+    DoctorReport {
+        ok: !checks.iter().any(|c| c.status == CheckStatus::Fail),
+        checks,
+    }
+}
+
+fn config_check_result(problems: &[ConfigProblem]) -> Check {
+    let summary = || {
+        problems
+            .iter()
+            .map(|p| format!("{}: {}", p.field, p.message))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    let hint = "Fix these with `exemem-cli config`; `config --effective` shows where each value comes from";
+    if problems.iter().any(|p| p.severity == Severity::Error) {
+        Check::fail("config", summary(), hint)
+    } else if !problems.is_empty() {
+        Check::warn("config", summary(), hint)
+    } else {
+        Check::pass("config", "The configuration is valid".to_string())
+    }
+}
+
+fn api_check(api_url: &str, reachable: Option<bool>) -> Check {
+    match reachable {
+        Some(true) => Check::pass("api", format!("{} is reachable", api_url)),
+        Some(false) => Check::fail(
+            "api",
+            format!("Can't reach {}", api_url),
+            "Check the network, any proxy settings, and the environment with `exemem-cli config --show`",
+        ),
+        None => Check::warn(
+            "api",
+            format!("{} wasn't checked without an API key", api_url),
+            "Set an API key with `exemem-cli config --api-key`",
+        ),
+    }
+}
+
+fn auth_check(accepted: Option<bool>) -> Check {
+    match accepted {
+        Some(true) => Check::pass("auth", "The API key was accepted".to_string()),
+        Some(false) => Check::fail(
+            "auth",
+            "The API rejected the API key".to_string(),
+            "Create a new key and set it with `exemem-cli config --api-key`",
+        ),
+        None => Check::warn(
+            "auth",
+            "The API key wasn't checked".to_string(),
+            "Set an API key and make sure the API is reachable",
+        ),
+    }
+}
+
+fn folder_check(folder: Option<&Path>, problems: &[ConfigProblem]) -> Check {
+    match (folder, problems.first()) {
+        (_, Some(problem)) => Check::fail(
+            "watched_folder",
+            problem.message.clone(),
+            "Choose a folder this user can read, in the app or in the config file",
+        ),
+        (Some(folder), None) => Check::pass("watched_folder", format!("{} can be read", folder.display())),
+        (None, None) => Check::warn(
+            "watched_folder",
+            "No watched folder is set".to_string(),
+            "Choose a folder to watch in the app",
+        ),
+    }
+}
+
+fn disk_check(available: Option<u64>) -> Check {
+    let hint = "Free up space on the disk holding the app's data folder";
+    match available {
+        Some(bytes) if bytes < MIN_FREE_BYTES => {
+            Check::fail("disk_space", format!("Only {} MB free for the upload queue", bytes / (1024 * 1024)), hint)
+        }
+        Some(bytes) if bytes < WARN_FREE_BYTES => {
+            Check::warn("disk_space", format!("{} MB free for the upload queue", bytes / (1024 * 1024)), hint)
+        }
+        Some(bytes) => Check::pass("disk_space", format!("{} MB free", bytes / (1024 * 1024))),
+        None => Check::warn(
+            "disk_space",
+            "Free disk space couldn't be determined".to_string(),
+            "Check that the disk holding the app's data folder isn't full",
+        ),
+    }
+}
+
+fn clock_check(skew_secs: Option<i64>) -> Check {
+    let hint = "Turn on automatic time synchronization";
+    match skew_secs {
+        Some(skew) if skew.unsigned_abs() > MAX_CLOCK_SKEW_SECS => Check::fail(
+            "clock_skew",
+            format!("The clock is {}s off from the server; signed requests will be rejected", skew),
+            hint,
+        ),
+        Some(skew) if skew.unsigned_abs() > WARN_CLOCK_SKEW_SECS => {
+            Check::warn("clock_skew", format!("The clock is {}s off from the server", skew), hint)
+        }
+        Some(skew) => Check::pass("clock_skew", format!("The clock is within {}s of the server", skew.abs())),
+        None => Check::warn(
+            "clock_skew",
+            "The server's time couldn't be read".to_string(),
+            "Make sure the API is reachable",
+        ),
+    }
+}
+
+/// Server time minus local time, from the API's Date header.
+async fn clock_skew(config: &AppConfig) -> Option<i64> {
+    if config.api_url().is_empty() {
+        return None;
+    }
+    let client = http::build_client(&config.proxy, &config.tls, &config.connection);
+    let response = client
+        .get(config.api_url())
+        .timeout(CLOCK_REQUEST_TIMEOUT)
+        .send()
+        .await
+        .ok()?;
+    let server = parse_http_date(response.headers().get(reqwest::header::DATE)?.to_str().ok()?)?;
+    Some(server as i64 - ledger::now_secs() as i64)
+}
+
+/// Seconds since the Unix epoch for an HTTP date such as
+/// "Sun, 06 Nov 1994 08:49:37 GMT".
+fn parse_http_date(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let [_, day, month, year, time, "GMT"] = parts[..] else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? + 1;
+    let midnight = ledger::parse_date(&format!("{}-{:02}-{}", year, month, day)).ok()?;
+    let mut fields = time.split(':').map(|f| f.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (fields.next()??, fields.next()??, fields.next()??);
+    Some(midnight + hours * 3600 + minutes * 60 + seconds)
+}
+
+/// Free space on the disk holding `dir`, from `df`.
+#[cfg(unix)]
+fn available_bytes(dir: &Path) -> Option<u64> {
+    let output = std::process::Command::new("df").arg("-Pk").arg(dir).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let kilobytes: u64 = text.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+#[cfg(not(unix))]
+fn available_bytes(_dir: &Path) -> Option<u64> {
+    None
+}
+
+/// The watcher needs one inotify watch per folder under the watched one.
+#[cfg(target_os = "linux")]
+fn inotify_check(folder: Option<&Path>) -> Check {
+    let limit = std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()
+        .and_then(|s| s.trim().parse::<usize>().ok());
+    let (Some(folder), Some(limit)) = (folder, limit) else {
+        return Check::warn(
+            "inotify",
+            "The inotify watch limit couldn't be checked".to_string(),
+            "Set a watched folder; the limit is in /proc/sys/fs/inotify/max_user_watches",
+        );
+    };
+    inotify_result(count_folders(folder, limit + 1), limit)
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn inotify_result(folders: usize, limit: usize) -> Check {
+    let hint = "Raise fs.inotify.max_user_watches with sysctl, or watch a smaller folder";
+    if folders > limit {
+        Check::fail(
+            "inotify",
+            format!("The watched folder has more than {} subfolders, the inotify watch limit", limit),
+            hint,
+        )
+    } else if folders > limit / 10 * 8 {
+        Check::warn(
+            "inotify",
+            format!("{} subfolders use most of the {} inotify watches", folders, limit),
+            hint,
+        )
+    } else {
+        Check::pass("inotify", format!("{} of {} inotify watches needed", folders, limit))
+    }
+}
+
+/// Folders under and including `root`, counting no further than `cap`.
+#[cfg(target_os = "linux")]
+fn count_folders(root: &Path, cap: usize) -> usize {
+    let mut count = 0;
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        count += 1;
+        if count >= cap {
+            break;
+        }
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().map_or(false, |t| t.is_dir()) {
+                pending.push(entry.path());
+            }
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT"), None);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        assert_eq!(clock_check(Some(-2)).status, CheckStatus::Pass);
+        assert_eq!(clock_check(Some(45)).status, CheckStatus::Warn);
+        assert_eq!(clock_check(Some(-600)).status, CheckStatus::Fail);
+        assert_eq!(disk_check(Some(10 * 1024 * 1024)).status, CheckStatus::Fail);
+        assert_eq!(disk_check(Some(500 * 1024 * 1024)).status, CheckStatus::Warn);
+        assert_eq!(disk_check(Some(5 * WARN_FREE_BYTES)).status, CheckStatus::Pass);
+        assert_eq!(inotify_result(100, 8192).status, CheckStatus::Pass);
+        assert_eq!(inotify_result(7000, 8192).status, CheckStatus::Warn);
+        assert_eq!(inotify_result(8193, 8192).status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn test_config_problems_fail_or_warn_by_severity() {
+        let problem = |severity| ConfigProblem {
+            field: "dry_run",
+            severity,
+            message: "Dry run is on".to_string(),
+        };
+        assert_eq!(config_check_result(&[]).status, CheckStatus::Pass);
+        assert_eq!(config_check_result(&[problem(Severity::Warning)]).status, CheckStatus::Warn);
+        let failed = config_check_result(&[problem(Severity::Warning), problem(Severity::Error)]);
+        assert_eq!(failed.status, CheckStatus::Fail);
+        assert!(failed.hint.is_some());
+    }
+}
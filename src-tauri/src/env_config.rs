@@ -0,0 +1,158 @@
+//! Environment variable overrides for the config file, shared by the app
+//! and the CLI so both resolve settings the same way.
+//!
+//! Precedence, highest first:
+//! 1. `EXEMEM_*` environment variables
+//! 2. The profile picked with `--profile` (CLI only)
+//! 3. `config.json`
+//! 4. Built-in defaults
+//!
+//! Overridden values are never written back to `config.json`: saving keeps
+//! whatever the file had for those fields.
+
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+pub const API_KEY_VAR: &str = "EXEMEM_API_KEY";
+/// Also switches the environment to Custom, like `config --api-url`
+pub const API_URL_VAR: &str = "EXEMEM_API_URL";
+/// Dev, Prod or Custom, in any case
+pub const ENV_VAR: &str = "EXEMEM_ENV";
+pub const WATCHED_FOLDER_VAR: &str = "EXEMEM_WATCHED_FOLDER";
+/// Not an override: the passphrase that unlocks an encrypted `config.json`
+/// without a prompt, e.g. for the CLI
+pub const CONFIG_PASSPHRASE_VAR: &str = "EXEMEM_CONFIG_PASSPHRASE";
+
+/// Overrides found in the environment. Empty variables count as unset.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvOverrides {
+    pub api_key: Option<String>,
+    pub api_url: Option<String>,
+    pub environment: Option<String>,
+    pub watched_folder: Option<PathBuf>,
+}
+
+impl EnvOverrides {
+    pub fn from_env() -> Self {
+        Self::from_lookup(|name| std::env::var(name).ok())
+    }
+
+    fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name| lookup(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        Self {
+            api_key: var(API_KEY_VAR),
+            api_url: var(API_URL_VAR),
+            environment: var(ENV_VAR),
+            watched_folder: var(WATCHED_FOLDER_VAR).map(PathBuf::from),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Config fields set by these overrides, with the variable setting each.
+    pub fn fields(&self) -> Vec<(&'static str, &'static str)> {
+        let mut fields = Vec::new();
+        if self.api_key.is_some() {
+            fields.push(("api_key", API_KEY_VAR));
+        }
+        if self.api_url.is_some() {
+            fields.push(("api_base_url", API_URL_VAR));
+            fields.push(("environment", API_URL_VAR));
+        } else if self.environment.is_some() {
+            fields.push(("environment", ENV_VAR));
+        }
+        if self.watched_folder.is_some() {
+            fields.push(("watched_folder", WATCHED_FOLDER_VAR));
+        }
+        fields
+    }
+
+    /// Apply the overrides to a config serialized as JSON.
+    pub fn apply_json(&self, config: &mut Value) -> Result<(), String> {
+        let fields = config
+            .as_object_mut()
+            .ok_or_else(|| "Config is not an object".to_string())?;
+        if let Some(env) = &self.environment {
+            let env = match env.to_lowercase().as_str() {
+                "dev" => "Dev",
+                "prod" => "Prod",
+                "custom" => "Custom",
+                _ => {
+                    return Err(format!(
+                        "Invalid {}: {}. Use Dev, Prod, or Custom",
+                        ENV_VAR, env
+                    ))
+                }
+            };
+            fields.insert("environment".to_string(), json!(env));
+        }
+        if let Some(url) = &self.api_url {
+            fields.insert("api_base_url".to_string(), json!(url));
+            fields.insert("environment".to_string(), json!("Custom"));
+        }
+        if let Some(key) = &self.api_key {
+            fields.insert("api_key".to_string(), json!(key));
+        }
+        if let Some(folder) = &self.watched_folder {
+            fields.insert("watched_folder".to_string(), json!(folder));
+        }
+        Ok(())
+    }
+
+    /// Put the file's values back for every overridden field, so an
+    /// effective config can be saved without persisting the overrides.
+    pub fn restore_json(&self, config: &mut Value, file: &Value) {
+        let Some(fields) = config.as_object_mut() else {
+            return;
+        };
+        for (field, _) in self.fields() {
+            match file.get(field) {
+                Some(value) => fields.insert(field.to_string(), value.clone()),
+                None => fields.remove(field),
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(vars: &[(&str, &str)]) -> EnvOverrides {
+        EnvOverrides::from_lookup(|name| {
+            vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_env_beats_file() {
+        let env = overrides(&[(API_KEY_VAR, "ci-key"), (ENV_VAR, "prod"), (WATCHED_FOLDER_VAR, " ")]);
+        let mut config = json!({"api_key": "file-key", "environment": "Dev", "watched_folder": "/docs"});
+        env.apply_json(&mut config).unwrap();
+        assert_eq!(config["api_key"], "ci-key");
+        assert_eq!(config["environment"], "Prod");
+        // Blank variables are ignored
+        assert_eq!(config["watched_folder"], "/docs");
+
+        let bad = overrides(&[(ENV_VAR, "staging")]);
+        assert!(bad.apply_json(&mut config).is_err());
+    }
+
+    #[test]
+    fn test_api_url_implies_custom_and_restores() {
+        let env = overrides(&[(API_URL_VAR, "http://localhost:9000"), (ENV_VAR, "Dev")]);
+        let file = json!({"api_key": "k", "environment": "Prod", "api_base_url": ""});
+        let mut config = file.clone();
+        env.apply_json(&mut config).unwrap();
+        assert_eq!(config["environment"], "Custom");
+        assert_eq!(config["api_base_url"], "http://localhost:9000");
+
+        config["api_key"] = json!("edited");
+        env.restore_json(&mut config, &file);
+        assert_eq!(config["environment"], "Prod");
+        assert_eq!(config["api_base_url"], "");
+        assert_eq!(config["api_key"], "edited");
+    }
+}
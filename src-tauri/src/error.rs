@@ -0,0 +1,178 @@
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+
+/// Error returned across the crate's public surface: Tauri commands, the
+/// query client, and config loading. The variant says what kind of problem
+/// it was, so callers can react (re-login, retry, fix input) without
+/// matching on message text.
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum Error {
+    /// Missing, invalid or expired credentials
+    #[error("{0}")]
+    Auth(String),
+    /// The request never got an HTTP response
+    #[error("{0}")]
+    Network(String),
+    /// The server answered with an error
+    #[error("{message}")]
+    Server {
+        status: Option<u16>,
+        message: String,
+    },
+    /// Bad input or configuration
+    #[error("{0}")]
+    Validation(String),
+    /// Reading or writing local files
+    #[error("{0}")]
+    Io(String),
+    /// Aborted with `cancel_command`
+    #[error("Cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    Timeout(String),
+    /// Anything not classified above
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl Error {
+    /// Stable name of the variant, as sent to the frontend and printed by the CLI.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Auth(_) => "auth",
+            Error::Network(_) => "network",
+            Error::Server { .. } => "server",
+            Error::Validation(_) => "validation",
+            Error::Io(_) => "io",
+            Error::Cancelled => "cancelled",
+            Error::Timeout(_) => "timeout",
+            Error::Internal(_) => "internal",
+        }
+    }
+
+    /// Process exit code for the CLI. Codes are stable; `EXIT_CONFIG` and
+    /// `EXIT_PARTIAL` cover failures that aren't a single `Error`.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Internal(_) => 1,
+            Error::Validation(_) => 2,
+            Error::Auth(_) => 3,
+            Error::Network(_) | Error::Timeout(_) => 4,
+            Error::Server { .. } => 5,
+            Error::Io(_) => 6,
+            Error::Cancelled => 130,
+        }
+    }
+
+    /// Classify a non-success HTTP response.
+    pub fn from_status(status: u16, message: String) -> Self {
+        match status {
+            401 | 403 => Error::Auth(message),
+            400 | 404 | 413 | 415 | 422 => Error::Validation(message),
+            _ => Error::Server {
+                status: Some(status),
+                message,
+            },
+        }
+    }
+
+    /// Network error for a request that failed before a response arrived.
+    pub fn network(context: &str, err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            Error::Timeout(format!("{}: {}", context, err))
+        } else {
+            Error::Network(format!("{}: {}", context, err))
+        }
+    }
+}
+
+/// CLI exit code when the configuration is missing or unusable
+pub const EXIT_CONFIG: i32 = 7;
+/// CLI exit code when items of a batch failed; the report says which
+pub const EXIT_PARTIAL: i32 = 8;
+
+/// Untyped errors from modules that haven't been classified yet
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Internal(message)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err.to_string())
+    }
+}
+
+/// Sent to the frontend as `{ "kind": "auth", "message": "...", "status": 401 }`;
+/// `status` only appears for server errors. Credentials in the message are masked.
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Wire<'a> {
+            kind: &'a str,
+            message: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            status: Option<u16>,
+        }
+        let status = match self {
+            Error::Server { status, .. } => *status,
+            _ => None,
+        };
+        Wire {
+            kind: self.kind(),
+            message: crate::redact::redact(&self.to_string()),
+            status,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        assert_eq!(Error::from_status(401, "no".into()).kind(), "auth");
+        assert_eq!(Error::from_status(422, "bad".into()).kind(), "validation");
+        assert_eq!(
+            Error::from_status(502, "down".into()),
+            Error::Server {
+                status: Some(502),
+                message: "down".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let mut codes = vec![
+            Error::Internal(String::new()).exit_code(),
+            Error::Validation(String::new()).exit_code(),
+            Error::Auth(String::new()).exit_code(),
+            Error::Network(String::new()).exit_code(),
+            Error::Server { status: None, message: String::new() }.exit_code(),
+            Error::Io(String::new()).exit_code(),
+            Error::Cancelled.exit_code(),
+            EXIT_CONFIG,
+            EXIT_PARTIAL,
+        ];
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), 9);
+        assert_eq!(Error::Timeout(String::new()).exit_code(), Error::Network(String::new()).exit_code());
+    }
+
+    #[test]
+    fn test_serializes_structurally() {
+        let json = serde_json::to_value(Error::from_status(500, "boom".into())).unwrap();
+        assert_eq!(json["kind"], "server");
+        assert_eq!(json["message"], "boom");
+        assert_eq!(json["status"], 500);
+
+        let json = serde_json::to_value(Error::Cancelled).unwrap();
+        assert_eq!(json["kind"], "cancelled");
+        assert!(json.get("status").is_none());
+    }
+}
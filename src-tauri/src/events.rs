@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// How many events are kept for replay
+const REPLAY_CAPACITY: usize = 500;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BufferedEvent {
+    pub cursor: u64,
+    pub event: String,
+    pub payload: Value,
+    pub timestamp: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MissedEvents {
+    pub events: Vec<BufferedEvent>,
+    /// Pass this back as `since_cursor` on the next call
+    pub cursor: u64,
+    /// Some events after `since_cursor` were already evicted; the caller
+    /// should reload full state instead of relying on the replay alone.
+    pub truncated: bool,
+}
+
+struct BufferState {
+    next_cursor: u64,
+    events: VecDeque<BufferedEvent>,
+}
+
+/// Bounded history of frontend events, so a webview that was hidden or
+/// reloaded can catch up on what it missed.
+pub struct EventBuffer {
+    state: Mutex<BufferState>,
+    capacity: usize,
+}
+
+impl Default for EventBuffer {
+    fn default() -> Self {
+        Self::new(REPLAY_CAPACITY)
+    }
+}
+
+impl EventBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Mutex::new(BufferState {
+                next_cursor: 1,
+                events: VecDeque::with_capacity(capacity),
+            }),
+            capacity,
+        }
+    }
+
+    pub fn push(&self, event: &str, payload: Value, timestamp: String) {
+        let mut state = self.state.lock().unwrap();
+        let cursor = state.next_cursor;
+        state.next_cursor += 1;
+        if state.events.len() == self.capacity {
+            state.events.pop_front();
+        }
+        state.events.push_back(BufferedEvent {
+            cursor,
+            event: event.to_string(),
+            payload,
+            timestamp,
+        });
+    }
+
+    /// Events with a cursor greater than `since_cursor` (all buffered events
+    /// when None), oldest first.
+    pub fn since(&self, since_cursor: Option<u64>) -> MissedEvents {
+        let state = self.state.lock().unwrap();
+        let since = since_cursor.unwrap_or(0);
+        let oldest = state.events.front().map(|e| e.cursor).unwrap_or(state.next_cursor);
+
+        MissedEvents {
+            events: state
+                .events
+                .iter()
+                .filter(|e| e.cursor > since)
+                .cloned()
+                .collect(),
+            cursor: state.next_cursor - 1,
+            truncated: since_cursor.is_some() && since + 1 < oldest,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_since_returns_only_newer_events() {
+        let buffer = EventBuffer::new(10);
+        buffer.push("a", json!(1), "0".to_string());
+        buffer.push("b", json!(2), "0".to_string());
+        buffer.push("c", json!(3), "0".to_string());
+
+        let missed = buffer.since(Some(1));
+        assert_eq!(missed.cursor, 3);
+        assert!(!missed.truncated);
+        let names: Vec<_> = missed.events.iter().map(|e| e.event.as_str()).collect();
+        assert_eq!(names, vec!["b", "c"]);
+
+        assert!(buffer.since(Some(3)).events.is_empty());
+    }
+
+    #[test]
+    fn test_eviction_marks_truncated() {
+        let buffer = EventBuffer::new(2);
+        for i in 0..5 {
+            buffer.push("e", json!(i), "0".to_string());
+        }
+
+        let missed = buffer.since(Some(1));
+        assert!(missed.truncated);
+        assert_eq!(missed.events.len(), 2);
+        assert_eq!(missed.events[0].cursor, 4);
+
+        assert!(!buffer.since(Some(3)).truncated);
+        assert!(!buffer.since(None).truncated);
+    }
+}
@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// File formats query and search results can be exported to.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Markdown,
+}
+
+impl ExportFormat {
+    /// Format implied by a file extension (.json, .csv, .md).
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "md" | "markdown" => Ok(Self::Markdown),
+            _ => Err(format!(
+                "Can't tell the export format from {}; use .json, .csv or .md",
+                path.display()
+            )),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Markdown => "md",
+        }
+    }
+}
+
+/// Flatten a result into (column, text) pairs: nested objects become dotted
+/// column names, arrays are kept as compact JSON, and a non-object result
+/// becomes a single "value" column.
+fn flatten(result: &Value) -> Vec<(String, String)> {
+    fn walk(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (key, child) in map {
+                    let column = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{}.{}", prefix, key)
+                    };
+                    walk(&column, child, out);
+                }
+            }
+            _ => out.push((prefix.to_string(), cell(value))),
+        }
+    }
+
+    let mut out = Vec::new();
+    match result {
+        Value::Object(map) if !map.is_empty() => walk("", result, &mut out),
+        _ => out.push(("value".to_string(), cell(result))),
+    }
+    out
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Object(map) if map.is_empty() => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Column names across all results, in order of first appearance, and each
+/// result's cells keyed by column.
+fn table(results: &[Value]) -> (Vec<String>, Vec<Map<String, Value>>) {
+    let mut columns: Vec<String> = Vec::new();
+    let rows = results
+        .iter()
+        .map(|result| {
+            let mut row = Map::new();
+            for (column, text) in flatten(result) {
+                if !columns.contains(&column) {
+                    columns.push(column.clone());
+                }
+                row.insert(column, Value::String(text));
+            }
+            row
+        })
+        .collect();
+    (columns, rows)
+}
+
+fn row_cells<'a>(
+    columns: &'a [String],
+    row: &'a Map<String, Value>,
+) -> impl Iterator<Item = &'a str> {
+    columns
+        .iter()
+        .map(move |column| row.get(column).and_then(Value::as_str).unwrap_or(""))
+}
+
+/// Results as flattened columns and rows of text, for printing as a table.
+pub fn columns(results: &[Value]) -> (Vec<String>, Vec<Vec<String>>) {
+    let (columns, rows) = table(results);
+    let cells = rows
+        .iter()
+        .map(|row| row_cells(&columns, row).map(str::to_string).collect())
+        .collect();
+    (columns, cells)
+}
+
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Results rendered in `format`.
+pub fn render(results: &[Value], format: ExportFormat) -> String {
+    let mut out = String::new();
+    match format {
+        ExportFormat::Json => {
+            return serde_json::to_string_pretty(results).expect("JSON values serialize");
+        }
+        ExportFormat::Csv => {
+            let (columns, rows) = table(results);
+            let header: Vec<String> = columns.iter().map(|c| csv_field(c)).collect();
+            out.push_str(&header.join(","));
+            out.push_str("\r\n");
+            for row in &rows {
+                let cells: Vec<String> = row_cells(&columns, row).map(csv_field).collect();
+                out.push_str(&cells.join(","));
+                out.push_str("\r\n");
+            }
+        }
+        ExportFormat::Markdown => {
+            let (columns, rows) = table(results);
+            if columns.is_empty() {
+                return "_No results_\n".to_string();
+            }
+            let header: Vec<String> = columns.iter().map(|c| markdown_cell(c)).collect();
+            out.push_str(&format!("| {} |\n", header.join(" | ")));
+            out.push_str(&format!("|{}\n", " --- |".repeat(columns.len())));
+            for row in &rows {
+                let cells: Vec<String> = row_cells(&columns, row).map(markdown_cell).collect();
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+    }
+    out
+}
+
+/// Write results to `path` in `format`.
+pub fn write(path: &Path, results: &[Value], format: ExportFormat) -> Result<(), String> {
+    std::fs::write(path, render(results, format))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn results() -> Vec<Value> {
+        vec![
+            json!({"id": "k1", "meta": {"tags": ["a", "b"], "title": "Trip, Lisbon"}}),
+            json!({"id": "k2", "meta": {"title": "Say \"hi\""}, "score": 0.5}),
+        ]
+    }
+
+    #[test]
+    fn test_csv_flattens_and_quotes() {
+        let csv = render(&results(), ExportFormat::Csv);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], "id,meta.tags,meta.title,score");
+        assert_eq!(lines[1], "k1,\"[\"\"a\"\",\"\"b\"\"]\",\"Trip, Lisbon\",");
+        assert_eq!(lines[2], "k2,,\"Say \"\"hi\"\"\",0.5");
+    }
+
+    #[test]
+    fn test_markdown_escapes_cells() {
+        let md = render(
+            &[json!({"a": "x|y\nz"}), json!("plain")],
+            ExportFormat::Markdown,
+        );
+        assert_eq!(
+            md,
+            "| a | value |\n| --- | --- |\n| x\\|y<br>z |  |\n|  | plain |\n"
+        );
+        assert_eq!(render(&[], ExportFormat::Markdown), "_No results_\n");
+    }
+
+    #[test]
+    fn test_columns_pad_missing_cells() {
+        let (columns, rows) = columns(&results());
+        assert_eq!(columns, vec!["id", "meta.tags", "meta.title", "score"]);
+        assert_eq!(rows[1], vec!["k2", "", "Say \"hi\"", "0.5"]);
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out.CSV")),
+            Ok(ExportFormat::Csv)
+        );
+        assert_eq!(
+            ExportFormat::from_path(Path::new("out.md")),
+            Ok(ExportFormat::Markdown)
+        );
+        assert!(ExportFormat::from_path(Path::new("out")).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::persist::JsonStore;
+use crate::scanner::{FileRecommendation, ScanResult};
+
+/// Net corrections in one direction needed before a directory rule overrides
+/// the built-in heuristics
+const DIRECTORY_THRESHOLD: i64 = 3;
+
+/// Extensions span many folders, so they need more evidence than a directory
+const EXTENSION_THRESHOLD: i64 = 5;
+
+/// How often the user overrode the scanner for files sharing a trait.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Tally {
+    /// Approved although the scanner said skip
+    pub approved: u32,
+    /// Skipped although the scanner recommended it
+    pub skipped: u32,
+}
+
+impl Tally {
+    fn count(&mut self, approved: bool) {
+        if approved {
+            self.approved += 1;
+        } else {
+            self.skipped += 1;
+        }
+    }
+
+    /// The learned decision, once corrections clearly lean one way.
+    fn decision(&self, threshold: i64) -> Option<bool> {
+        let net = self.approved as i64 - self.skipped as i64;
+        if net >= threshold {
+            Some(true)
+        } else if net <= -threshold {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct FeedbackData {
+    #[serde(default)]
+    extensions: HashMap<String, Tally>,
+    #[serde(default)]
+    directories: HashMap<String, Tally>,
+}
+
+/// Corrections the user made to scan recommendations, kept locally so the
+/// same files stop being misclassified scan after scan.
+///
+/// Corrections are tallied per extension and per directory; a directory
+/// rule takes precedence over an extension rule because it is more specific.
+pub struct ClassificationFeedback {
+    store: JsonStore<FeedbackData>,
+}
+
+impl ClassificationFeedback {
+    const FILE: &'static str = "classification_feedback.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "classification feedback").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "classification feedback").map(|store| Self { store })
+    }
+
+    /// Record the user's decisions on recommendations; only decisions that
+    /// disagree with the recommendation count as corrections.
+    pub fn record<'a>(
+        &mut self,
+        decisions: impl IntoIterator<Item = (&'a FileRecommendation, bool)>,
+    ) -> Result<(), String> {
+        self.store.update(|data| {
+            for (rec, approved) in decisions {
+                if approved == rec.should_ingest {
+                    continue;
+                }
+                let (ext, dir) = traits(&rec.path);
+                if let Some(ext) = ext {
+                    data.extensions.entry(ext).or_default().count(approved);
+                }
+                if let Some(dir) = dir {
+                    data.directories.entry(dir).or_default().count(approved);
+                }
+            }
+        })
+    }
+
+    /// Everything learned so far, for a settings export.
+    pub(crate) fn rules(&self) -> &FeedbackData {
+        self.store.get()
+    }
+
+    /// Replace everything learned with imported rules.
+    pub(crate) fn replace(&mut self, rules: FeedbackData) -> Result<(), String> {
+        self.store.update(|data| *data = rules)
+    }
+
+    /// Forget everything learned so far.
+    pub fn reset(&mut self) -> Result<(), String> {
+        self.store.update(|data| *data = FeedbackData::default())
+    }
+
+    /// Override a recommendation if a learned rule applies to it.
+    pub fn adjust(&self, rec: &mut FileRecommendation) {
+        // Converter hints describe what the file is, not a guess; leave them alone
+        if rec.converter.is_some() {
+            return;
+        }
+        let (ext, dir) = traits(&rec.path);
+
+        let learned = dir
+            .and_then(|dir| {
+                let decision = self.store.get().directories.get(&dir)?.decision(DIRECTORY_THRESHOLD)?;
+                Some((decision, format!("files in {}/", dir)))
+            })
+            .or_else(|| {
+                let ext = ext?;
+                let decision = self.store.get().extensions.get(&ext)?.decision(EXTENSION_THRESHOLD)?;
+                Some((decision, format!(".{} files", ext)))
+            });
+
+        if let Some((should_ingest, subject)) = learned {
+            if should_ingest != rec.should_ingest {
+                rec.should_ingest = should_ingest;
+                rec.reason = format!(
+                    "{} (adjusted: you usually {} {})",
+                    rec.reason,
+                    if should_ingest { "ingest" } else { "skip" },
+                    subject
+                );
+            }
+        }
+    }
+
+    /// Apply learned rules to a whole scan, moving files between the
+    /// recommended and skipped lists as needed.
+    pub fn apply_to_scan(&self, result: &mut ScanResult) {
+        let mut all: Vec<FileRecommendation> = result
+            .recommended_files
+            .drain(..)
+            .chain(result.skipped_files.drain(..))
+            .collect();
+        for rec in &mut all {
+            self.adjust(rec);
+        }
+        let (recommended, skipped) = all.into_iter().partition(|rec| rec.should_ingest);
+        result.recommended_files = recommended;
+        result.skipped_files = skipped;
+    }
+}
+
+/// Lowercased extension and parent directory (relative to the scan root) of
+/// a file. Files at the root get no directory rule.
+fn traits(relative_path: &str) -> (Option<String>, Option<String>) {
+    let path = Path::new(relative_path);
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let dir = path
+        .parent()
+        .map(|p| p.to_string_lossy().replace('\\', "/"))
+        .filter(|p| !p.is_empty());
+    (ext, dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_feedback() -> ClassificationFeedback {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-feedback-{}", uuid::Uuid::new_v4()))
+            .join("classification_feedback.json");
+        open(path)
+    }
+
+    fn open(path: PathBuf) -> ClassificationFeedback {
+        ClassificationFeedback {
+            store: JsonStore::load_from(path, "classification feedback").unwrap(),
+        }
+    }
+
+    fn rec(path: &str, should_ingest: bool) -> FileRecommendation {
+        FileRecommendation {
+            path: path.to_string(),
+            absolute_path: PathBuf::from("/root").join(path),
+            should_ingest,
+            category: "unknown".to_string(),
+            reason: "Unknown file type".to_string(),
+            size_bytes: None,
+            converter: None,
+        }
+    }
+
+    #[test]
+    fn test_learns_extension_after_threshold() {
+        let mut feedback = temp_feedback();
+        let files: Vec<_> = (0..5).map(|i| rec(&format!("{}.vcf", i), false)).collect();
+
+        feedback.record(files.iter().take(4).map(|r| (r, true))).unwrap();
+        let mut next = rec("new.vcf", false);
+        feedback.adjust(&mut next);
+        assert!(!next.should_ingest);
+
+        feedback.record([(&files[4], true)]).unwrap();
+        feedback.adjust(&mut next);
+        assert!(next.should_ingest);
+        assert!(next.reason.contains(".vcf files"));
+    }
+
+    #[test]
+    fn test_directory_rule_wins_and_agreements_are_ignored() {
+        let mut feedback = temp_feedback();
+        let skipped: Vec<_> = (0..3).map(|i| rec(&format!("junk/{}.json", i), true)).collect();
+        feedback.record(skipped.iter().map(|r| (r, false))).unwrap();
+
+        // Agreeing with the scanner isn't a correction
+        let agreed: Vec<_> = (0..5).map(|i| rec(&format!("notes/{}.json", i), true)).collect();
+        feedback.record(agreed.iter().map(|r| (r, true))).unwrap();
+
+        let mut junk = rec("junk/new.json", true);
+        feedback.adjust(&mut junk);
+        assert!(!junk.should_ingest);
+
+        // Three skips aren't enough to turn against .json everywhere
+        let mut notes = rec("notes/new.json", true);
+        feedback.adjust(&mut notes);
+        assert!(notes.should_ingest);
+
+        let reloaded = open(feedback.store.path().to_path_buf());
+        assert_eq!(reloaded.rules().directories["junk"].skipped, 3);
+    }
+}
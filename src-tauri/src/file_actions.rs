@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Folder, under the watched folder, that ingested files are moved to
+pub const INGESTED_DIR: &str = "ingested";
+
+/// Dropped in the ingested folder so the scanner and watcher skip it
+const MARKER_FILE: &str = ".exemem-ingested";
+
+/// What to do with a local file once the server confirms it was ingested.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum PostIngestAction {
+    #[default]
+    Leave,
+    /// Move into `ingested/` under the watched folder, keeping its relative path
+    MoveToIngested,
+    Delete,
+}
+
+/// Whether `dir` is a folder this app moves ingested files into.
+pub fn is_ingested_dir(dir: &Path) -> bool {
+    dir.join(MARKER_FILE).is_file()
+}
+
+/// Whether `path` lies inside an ingested folder under `root`.
+pub fn in_ingested_dir(root: &Path, path: &Path) -> bool {
+    path.ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root) && *dir != root)
+        .any(is_ingested_dir)
+}
+
+/// Apply `action` to an ingested file, returning a description for the
+/// activity log (None when there was nothing to do).
+///
+/// Only regular files inside `root` are touched, and never files already
+/// moved to the ingested folder. A file whose contents no longer hash to
+/// `uploaded_sha256` was changed after the upload, so it is left in place.
+pub fn apply(
+    action: PostIngestAction,
+    root: &Path,
+    path: &Path,
+    uploaded_sha256: &str,
+) -> Result<Option<String>, String> {
+    if action == PostIngestAction::Leave {
+        return Ok(None);
+    }
+
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Watched folder unavailable: {}", e))?;
+    let path = path
+        .canonicalize()
+        .map_err(|e| format!("File no longer available: {}", e))?;
+    if !path.is_file() {
+        return Err(format!("Not a regular file: {}", path.display()));
+    }
+    let relative = path
+        .strip_prefix(&root)
+        .map_err(|_| format!("{} is outside the watched folder", path.display()))?
+        .to_path_buf();
+    if in_ingested_dir(&root, &path) {
+        return Ok(None);
+    }
+    let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read: {}", e))?;
+    if format!("{:x}", Sha256::digest(&bytes)) != uploaded_sha256 {
+        return Err(format!(
+            "{} changed since it was uploaded; left in place",
+            relative.display()
+        ));
+    }
+
+    match action {
+        PostIngestAction::Leave => Ok(None),
+        PostIngestAction::Delete => {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to delete: {}", e))?;
+            Ok(Some(format!("Deleted {}", relative.display())))
+        }
+        PostIngestAction::MoveToIngested => {
+            let ingested = root.join(INGESTED_DIR);
+            std::fs::create_dir_all(&ingested)
+                .map_err(|e| format!("Failed to create ingested folder: {}", e))?;
+            let marker = ingested.join(MARKER_FILE);
+            if !marker.exists() {
+                std::fs::write(&marker, b"")
+                    .map_err(|e| format!("Failed to mark ingested folder: {}", e))?;
+            }
+
+            let target = unique_target(&ingested.join(&relative));
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            move_file(&path, &target)?;
+            let shown = target.strip_prefix(&root).unwrap_or(&target);
+            Ok(Some(format!("Moved to {}", shown.display())))
+        }
+    }
+}
+
+/// `target`, or "name (2).ext", "name (3).ext", ... if it already exists.
+fn unique_target(target: &Path) -> PathBuf {
+    if !target.exists() {
+        return target.to_path_buf();
+    }
+    let stem = target
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    (2..)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists())
+        .expect("unbounded range")
+}
+
+fn move_file(from: &Path, to: &Path) -> Result<(), String> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    // Rename fails across filesystems (e.g. a mount inside the watched folder)
+    std::fs::copy(from, to).map_err(|e| format!("Failed to move: {}", e))?;
+    std::fs::remove_file(from).map_err(|e| format!("Copied, but failed to remove original: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(data: &str) -> String {
+        format!("{:x}", Sha256::digest(data.as_bytes()))
+    }
+
+    fn temp_root() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("exemem-actions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        dir.canonicalize().unwrap()
+    }
+
+    #[test]
+    fn test_move_keeps_relative_path() {
+        let root = temp_root();
+        let file = root.join("notes/a.txt");
+        std::fs::write(&file, "hello").unwrap();
+
+        let note = apply(PostIngestAction::MoveToIngested, &root, &file, &sha256("hello")).unwrap();
+        assert_eq!(note.as_deref(), Some("Moved to ingested/notes/a.txt"));
+        assert!(!file.exists());
+        let moved = root.join("ingested/notes/a.txt");
+        assert!(moved.exists());
+        assert!(in_ingested_dir(&root, &moved));
+
+        // A second file with the same name doesn't overwrite the first
+        std::fs::write(&file, "again").unwrap();
+        apply(PostIngestAction::MoveToIngested, &root, &file, &sha256("again")).unwrap();
+        assert!(root.join("ingested/notes/a (2).txt").exists());
+
+        // Files already in the ingested folder are left alone
+        assert_eq!(
+            apply(PostIngestAction::Delete, &root, &moved, &sha256("hello")).unwrap(),
+            None
+        );
+        assert!(moved.exists());
+        let _ = std::fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn test_delete_only_inside_root() {
+        let root = temp_root();
+        let file = root.join("notes/b.txt");
+        std::fs::write(&file, "bye").unwrap();
+        let other = temp_root();
+        let outside = other.join("notes/c.txt");
+        std::fs::write(&outside, "stay").unwrap();
+
+        assert!(apply(PostIngestAction::Delete, &root, &outside, &sha256("stay")).is_err());
+        assert!(outside.exists());
+
+        apply(PostIngestAction::Delete, &root, &file, &sha256("bye")).unwrap();
+        assert!(!file.exists());
+        assert_eq!(
+            apply(PostIngestAction::Leave, &root, &outside, "").unwrap(),
+            None
+        );
+        let _ = std::fs::remove_dir_all(root);
+        let _ = std::fs::remove_dir_all(other);
+    }
+
+    #[test]
+    fn test_file_changed_since_upload_is_left() {
+        let root = temp_root();
+        let file = root.join("notes/d.txt");
+        std::fs::write(&file, "edited after upload").unwrap();
+
+        for action in [PostIngestAction::Delete, PostIngestAction::MoveToIngested] {
+            let err = apply(action, &root, &file, &sha256("as uploaded")).unwrap_err();
+            assert!(err.contains("changed since it was uploaded"));
+            assert!(file.exists());
+        }
+        assert!(!root.join(INGESTED_DIR).exists());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
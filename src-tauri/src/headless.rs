@@ -0,0 +1,631 @@
+//! The scan, watch and upload pipeline without the desktop app, for the CLI.
+//!
+//! It follows the app: approving files teaches the classifier, the watcher
+//! uploads what the scanner recommends when `auto_approve_watched` is on and
+//! honors quiet hours, and results land in the same ledger and failed-upload
+//! list the app reads. A lock file records a running watcher so other
+//! commands can find it.
+
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::AppConfig;
+use crate::dead_letter::DeadLetterQueue;
+use crate::error::Error;
+use crate::feedback::ClassificationFeedback;
+use crate::file_actions;
+use crate::http;
+use crate::ledger::{self, Ledger, LedgerEntry, UploadedFiles};
+use crate::paths;
+use crate::scanner::{self, classify_single_file, FileRecommendation, ScanResult};
+use crate::upload_queue::UploadPriority;
+use crate::uploader::{
+    is_success_status, is_terminal_status, FileContext, ProgressResponse, RequestError, UploadProgressFn,
+    UploadResult, UploadStatus, Uploader, MAX_PROGRESS_POLLS, PROGRESS_POLL_INTERVAL,
+};
+use crate::watcher::{FolderWatcher, WatchEvent};
+
+/// How often uploads held back by quiet hours are checked
+const DEFERRED_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Something the watcher did, printed as one JSON line by the CLI.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WatchUpdate {
+    Started {
+        folder: PathBuf,
+    },
+    Uploaded {
+        file: String,
+        category: String,
+        status: UploadStatus,
+        progress_id: Option<String>,
+    },
+    Failed {
+        file: String,
+        category: String,
+        error: Option<String>,
+    },
+    /// The scanner doesn't recommend the file
+    Skipped {
+        file: String,
+        category: String,
+        reason: String,
+    },
+    /// Recommended, but `auto_approve_watched` is off
+    AwaitingApproval {
+        file: String,
+        category: String,
+    },
+    /// Held back by quiet hours until `until` (seconds since the Unix epoch)
+    Deferred {
+        file: String,
+        category: String,
+        until: u64,
+    },
+    Stopped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WatchRecord {
+    /// Seconds since the Unix epoch
+    pub ts: u64,
+    #[serde(flatten)]
+    pub update: WatchUpdate,
+}
+
+/// Contents of the lock file of a running watcher.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchInfo {
+    pub pid: u32,
+    pub folder: PathBuf,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+}
+
+/// Held while a headless watcher runs; removes the lock file when dropped.
+pub struct WatchLock {
+    path: PathBuf,
+}
+
+impl WatchLock {
+    fn lock_path() -> Result<PathBuf, String> {
+        Ok(paths::data_dir()?.join("watch.lock"))
+    }
+
+    /// Record this process as the running watcher of `folder`. Fails if
+    /// another live process already holds the lock.
+    pub fn acquire(folder: &Path) -> Result<Self, Error> {
+        if let Some(running) = Self::running() {
+            return Err(Error::Validation(format!(
+                "Already watching {} (pid {})",
+                running.folder.display(),
+                running.pid
+            )));
+        }
+        let path = Self::lock_path().map_err(Error::Io)?;
+        let info = WatchInfo {
+            pid: std::process::id(),
+            folder: folder.to_path_buf(),
+            started_at: ledger::now_secs(),
+        };
+        crate::persist::save_json(&path, &info, "watch lock").map_err(Error::Io)?;
+        Ok(Self { path })
+    }
+
+    /// The running headless watcher, if any. A lock left behind by a
+    /// process that has since died is ignored.
+    pub fn running() -> Option<WatchInfo> {
+        let path = Self::lock_path().ok()?;
+        let info: Option<WatchInfo> = crate::persist::load_json(&path, "watch lock").ok()?;
+        info.filter(|info| process_alive(info.pid))
+    }
+}
+
+impl Drop for WatchLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn process_alive(pid: u32) -> bool {
+    Path::new("/proc").join(pid.to_string()).exists()
+}
+
+/// Without a cheap liveness check, trust the lock file; it is removed on
+/// every clean shutdown.
+#[cfg(not(target_os = "linux"))]
+fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+/// Uploads outside the app, recorded where the app keeps them.
+pub struct Pipeline {
+    uploader: Uploader,
+    ledger: Ledger,
+    dead_letters: DeadLetterQueue,
+}
+
+impl Pipeline {
+    pub fn open(config: &AppConfig) -> Result<Self, Error> {
+        Ok(Self {
+            uploader: Uploader::new(http::build_client(&config.proxy, &config.tls, &config.connection)),
+            ledger: Ledger::load().map_err(Error::Io)?,
+            dead_letters: DeadLetterQueue::load().map_err(Error::Io)?,
+        })
+    }
+
+    pub async fn upload(
+        &mut self,
+        config: &AppConfig,
+        path: &Path,
+        context: &FileContext,
+        priority: UploadPriority,
+    ) -> UploadResult {
+        self.upload_with_progress(config, path, context, priority, None)
+            .await
+    }
+
+    /// Like `upload`, reporting bytes sent during the S3 PUT.
+    pub async fn upload_with_progress(
+        &mut self,
+        config: &AppConfig,
+        path: &Path,
+        context: &FileContext,
+        priority: UploadPriority,
+        on_progress: Option<UploadProgressFn>,
+    ) -> UploadResult {
+        let result = self
+            .uploader
+            .upload_and_ingest_with_progress(path, config, priority, context.clone(), on_progress)
+            .await;
+        if let Err(e) = self.dead_letters.record_outcome(path, &result, context) {
+            log::warn!("Failed to update failed-upload list: {}", e);
+        }
+        // Dry runs never reach the ledger, as in the app
+        if result.status != UploadStatus::DryRun {
+            let entry = LedgerEntry::from_result(
+                uuid::Uuid::new_v4().to_string(),
+                &result,
+                context.category.clone(),
+                ledger::now_secs().to_string(),
+            );
+            if let Err(e) = self.ledger.record(entry) {
+                log::warn!("Failed to record ledger entry: {}", e);
+            }
+        }
+        result
+    }
+
+    /// Poll an ingestion until it finishes, passing each update to
+    /// `on_update`. Returns the final status, or None if it didn't finish
+    /// in time.
+    pub async fn wait_for_ingestion(
+        &self,
+        config: &AppConfig,
+        progress_id: &str,
+        mut on_update: impl FnMut(&ProgressResponse),
+    ) -> Option<String> {
+        for _ in 0..MAX_PROGRESS_POLLS {
+            tokio::time::sleep(PROGRESS_POLL_INTERVAL).await;
+            match self.uploader.poll_progress(config, progress_id).await {
+                Ok(resp) => {
+                    on_update(&resp);
+                    if is_terminal_status(&resp.status) {
+                        return Some(resp.status);
+                    }
+                }
+                Err(RequestError::ServerBusy { retry_after, .. }) => {
+                    tokio::time::sleep(retry_after).await;
+                }
+                Err(e) => log::warn!("Progress poll error for {}: {}", progress_id, e),
+            }
+        }
+        None
+    }
+}
+
+/// The files to upload for `paths`: files as given, and directories
+/// expanded to the files the scanner recommends in them.
+pub fn expand_paths(paths: &[PathBuf], config: &AppConfig) -> Result<Vec<(PathBuf, FileContext)>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let result = scan(path, config)?;
+            files.extend(result.recommended_files.iter().map(|f| {
+                (f.absolute_path.clone(), FileContext::from_recommendation(f, Vec::new()))
+            }));
+        } else if path.is_file() {
+            let root = path.parent().unwrap_or(Path::new(""));
+            let recommendation = classify_single_file(root, path, &config.scanner);
+            files.push((path.clone(), FileContext::from_recommendation(&recommendation, Vec::new())));
+        } else {
+            return Err(Error::Validation(format!("No such file or folder: {}", path.display())));
+        }
+    }
+    Ok(files)
+}
+
+fn load_feedback() -> Result<ClassificationFeedback, Error> {
+    ClassificationFeedback::load()
+        .or_else(|e| {
+            log::warn!("Failed to load classification feedback: {}", e);
+            ClassificationFeedback::empty()
+        })
+        .map_err(Error::Io)
+}
+
+/// Scan `folder` with what the classifier learned from past approvals
+/// applied, as the app's scan does.
+pub fn scan(folder: &Path, config: &AppConfig) -> Result<ScanResult, Error> {
+    if !folder.is_dir() {
+        return Err(Error::Validation(format!(
+            "Folder does not exist: {}",
+            folder.display()
+        )));
+    }
+    let mut result = scanner::scan_and_classify(folder, &config.scanner).map_err(Error::Io)?;
+    load_feedback()?.apply_to_scan(&mut result);
+    Ok(result)
+}
+
+/// Upload the files of `scan` whose paths are in `approved`, calling
+/// `report` as each finishes. Approving a skipped file or declining a
+/// recommended one is a correction the classifier learns from; files in
+/// neither list weren't decided on.
+pub async fn ingest_approved(
+    config: &AppConfig,
+    scan: &ScanResult,
+    approved: &[String],
+    declined: &[String],
+    mut report: impl FnMut(&UploadResult),
+) -> Result<Vec<UploadResult>, Error> {
+    let candidates: Vec<&FileRecommendation> =
+        scan.recommended_files.iter().chain(&scan.skipped_files).collect();
+    let decisions = candidates.iter().filter_map(|f| {
+        if approved.contains(&f.path) {
+            Some((*f, true))
+        } else if declined.contains(&f.path) {
+            Some((*f, false))
+        } else {
+            None
+        }
+    });
+    let recorded = load_feedback()
+        .and_then(|mut feedback| feedback.record(decisions).map_err(Error::Io));
+    if let Err(e) = recorded {
+        log::warn!("Failed to record classification feedback: {}", e);
+    }
+
+    let mut pipeline = Pipeline::open(config)?;
+    let mut results = Vec::new();
+    for file in candidates.into_iter().filter(|f| approved.contains(&f.path)) {
+        let context = FileContext::from_recommendation(file, Vec::new());
+        let result = pipeline
+            .upload(config, &file.absolute_path, &context, UploadPriority::Manual)
+            .await;
+        report(&result);
+        results.push(result);
+    }
+    Ok(results)
+}
+
+/// What `sync_once` did.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncSummary {
+    pub scanned: usize,
+    /// Recommended files already uploaded with the same contents
+    pub unchanged: usize,
+    pub uploaded: usize,
+    pub failed: usize,
+    pub ingested: usize,
+    pub ingestion_failed: usize,
+    /// Still ingesting when the wait ran out; they finish on the server
+    pub still_ingesting: usize,
+    pub results: Vec<UploadResult>,
+}
+
+impl SyncSummary {
+    /// Nothing failed. Ingestions still running don't count as failures.
+    pub fn ok(&self) -> bool {
+        self.failed == 0 && self.ingestion_failed == 0
+    }
+}
+
+/// Scan `folder` once, upload the recommended files that are new or have
+/// changed since they were last uploaded, and wait up to `ingest_timeout`
+/// for their ingestion to finish. For cron and other schedulers.
+pub async fn sync_once(
+    config: &AppConfig,
+    folder: &Path,
+    ingest_timeout: Duration,
+    mut report: impl FnMut(&UploadResult),
+) -> Result<SyncSummary, Error> {
+    let scan = scan(folder, config)?;
+    let mut pipeline = Pipeline::open(config)?;
+    let mut summary = SyncSummary {
+        scanned: scan.total_files,
+        ..Default::default()
+    };
+
+    let mut uploaded = pipeline.ledger.uploaded();
+    let mut ingesting = Vec::new();
+    for file in &scan.recommended_files {
+        if !needs_upload(&uploaded, &file.absolute_path) {
+            summary.unchanged += 1;
+            continue;
+        }
+        let context = FileContext::from_recommendation(file, Vec::new());
+        let result = pipeline
+            .upload(config, &file.absolute_path, &context, UploadPriority::Watcher)
+            .await;
+        report(&result);
+        if result.status == UploadStatus::Error {
+            summary.failed += 1;
+        } else {
+            summary.uploaded += 1;
+            // So a copy later in the scan is recognised as a duplicate
+            let counted = result.status != UploadStatus::DryRun;
+            if let Some(metadata) = result.metadata.as_ref().filter(|_| counted) {
+                uploaded.add(&metadata.sha256);
+            }
+            if let (UploadStatus::Ingesting, Some(progress_id)) = (&result.status, &result.progress_id) {
+                ingesting.push(progress_id.clone());
+            }
+        }
+        summary.results.push(result);
+    }
+
+    let deadline = tokio::time::Instant::now() + ingest_timeout;
+    for progress_id in ingesting {
+        let wait = pipeline.wait_for_ingestion(config, &progress_id, |_| {});
+        match tokio::time::timeout_at(deadline, wait).await {
+            Ok(Some(status)) if is_success_status(&status) => summary.ingested += 1,
+            Ok(Some(_)) => summary.ingestion_failed += 1,
+            Ok(None) | Err(_) => summary.still_ingesting += 1,
+        }
+    }
+    Ok(summary)
+}
+
+/// Whether the file at `path` is new or has changed since it was last
+/// uploaded. A matching modification time settles it; otherwise the
+/// contents are hashed and looked up.
+pub fn needs_upload(uploaded: &UploadedFiles, path: &Path) -> bool {
+    let mtime = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+    if mtime.is_some_and(|mtime| uploaded.is_current(&path.to_string_lossy(), mtime)) {
+        return false;
+    }
+    match file_sha256(path) {
+        Ok(hash) => !uploaded.has_uploaded(&hash),
+        Err(e) => {
+            // Uploading reports the problem properly
+            log::warn!("Failed to hash {}: {}", path.display(), e);
+            true
+        }
+    }
+}
+
+fn file_sha256(path: &Path) -> std::io::Result<String> {
+    use sha2::{Digest, Sha256};
+    Ok(format!("{:x}", Sha256::digest(std::fs::read(path)?)))
+}
+
+struct Deferred {
+    at: u64,
+    path: PathBuf,
+    context: FileContext,
+}
+
+/// Watch the configured folder until `shutdown` completes, passing each
+/// thing that happens to `report`. An upload in progress when `shutdown`
+/// fires is finished first.
+pub async fn watch(
+    config: &AppConfig,
+    shutdown: impl Future<Output = ()>,
+    mut report: impl FnMut(WatchRecord),
+) -> Result<(), Error> {
+    if !config.is_configured() {
+        return Err(Error::Validation(
+            "Not configured. Set the API key and watched folder first.".to_string(),
+        ));
+    }
+    let folder = config.watched_folder.clone().unwrap_or_default();
+    if !folder.is_dir() {
+        return Err(Error::Validation(format!(
+            "Watched folder does not exist: {}",
+            folder.display()
+        )));
+    }
+    let _lock = WatchLock::acquire(&folder)?;
+
+    let mut pipeline = Pipeline::open(config)?;
+    let feedback = load_feedback()?;
+
+    let (event_tx, mut event_rx) = mpsc::channel::<WatchEvent>(256);
+    let _watcher = FolderWatcher::start(folder.clone(), event_tx).map_err(Error::Io)?;
+    let mut emit = |update| {
+        report(WatchRecord {
+            ts: ledger::now_secs(),
+            update,
+        })
+    };
+    emit(WatchUpdate::Started {
+        folder: folder.clone(),
+    });
+
+    let mut deferred: Vec<Deferred> = Vec::new();
+    let mut deferred_check = tokio::time::interval(DEFERRED_CHECK_INTERVAL);
+    tokio::pin!(shutdown);
+
+    loop {
+        let (path, context) = tokio::select! {
+            _ = &mut shutdown => break,
+            _ = deferred_check.tick() => {
+                let now = ledger::now_secs();
+                let (due, later): (Vec<_>, Vec<_>) =
+                    std::mem::take(&mut deferred).into_iter().partition(|d| d.at <= now);
+                deferred = later;
+                for item in due {
+                    let result = pipeline
+                        .upload(config, &item.path, &item.context, UploadPriority::Watcher)
+                        .await;
+                    emit(outcome(result, &item.context));
+                }
+                continue;
+            }
+            event = event_rx.recv() => match event {
+                Some(WatchEvent::FileCreated(path) | WatchEvent::FileModified(path)) => {
+                    if file_actions::in_ingested_dir(&folder, &path) {
+                        continue;
+                    }
+                    let mut recommendation = classify_single_file(&folder, &path, &config.scanner);
+                    feedback.adjust(&mut recommendation);
+                    let category = recommendation.category.clone();
+                    if !recommendation.should_ingest {
+                        emit(WatchUpdate::Skipped {
+                            file: recommendation.path,
+                            category,
+                            reason: recommendation.reason,
+                        });
+                        continue;
+                    }
+                    if !config.auto_approve_watched {
+                        emit(WatchUpdate::AwaitingApproval {
+                            file: recommendation.path,
+                            category,
+                        });
+                        continue;
+                    }
+                    let context = FileContext::from_recommendation(&recommendation, Vec::new());
+                    if let Some(at) = config.upload_schedule.defer_until(ledger::now_secs()) {
+                        emit(WatchUpdate::Deferred {
+                            file: recommendation.path,
+                            category,
+                            until: at,
+                        });
+                        deferred.push(Deferred { at, path, context });
+                        continue;
+                    }
+                    (path, context)
+                }
+                None => return Err(Error::Io("Folder watcher stopped".to_string())),
+            },
+        };
+        let result = pipeline
+            .upload(config, &path, &context, UploadPriority::Watcher)
+            .await;
+        emit(outcome(result, &context));
+    }
+
+    if !deferred.is_empty() {
+        log::info!("{} deferred uploads dropped at shutdown", deferred.len());
+    }
+    emit(WatchUpdate::Stopped);
+    Ok(())
+}
+
+fn outcome(result: UploadResult, context: &FileContext) -> WatchUpdate {
+    let category = context.category.clone().unwrap_or_default();
+    match result.status {
+        UploadStatus::Error => WatchUpdate::Failed {
+            file: result.filename,
+            category,
+            error: result.error,
+        },
+        status => WatchUpdate::Uploaded {
+            file: result.filename,
+            category,
+            status,
+            progress_id: result.progress_id,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(status: UploadStatus, error: Option<&str>) -> UploadResult {
+        UploadResult {
+            filename: "notes.md".to_string(),
+            s3_key: String::new(),
+            progress_id: Some("p1".to_string()),
+            status,
+            error: error.map(str::to_string),
+            error_code: None,
+            suggestion: None,
+            original_bytes: None,
+            uploaded_bytes: None,
+            content_encoding: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_records_are_flat_json_lines() {
+        let record = WatchRecord {
+            ts: 5,
+            update: WatchUpdate::Skipped {
+                file: "a.bin".to_string(),
+                category: "binary".to_string(),
+                reason: "Binary file".to_string(),
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&record).unwrap(),
+            json!({"ts": 5, "event": "skipped", "file": "a.bin", "category": "binary", "reason": "Binary file"})
+        );
+    }
+
+    #[test]
+    fn test_outcome_of_upload() {
+        let context = FileContext {
+            category: Some("documents".to_string()),
+            ..Default::default()
+        };
+        match outcome(result(UploadStatus::Ingesting, None), &context) {
+            WatchUpdate::Uploaded {
+                status,
+                category,
+                progress_id,
+                ..
+            } => {
+                assert_eq!(status, UploadStatus::Ingesting);
+                assert_eq!(category, "documents");
+                assert_eq!(progress_id.as_deref(), Some("p1"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            outcome(result(UploadStatus::Error, Some("boom")), &context),
+            WatchUpdate::Failed { error: Some(e), .. } if e == "boom"
+        ));
+    }
+    #[test]
+    fn test_expand_paths_takes_files_as_given() {
+        let dir = std::env::temp_dir().join(format!("exemem-headless-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("notes.md");
+        std::fs::write(&file, "# Notes").unwrap();
+        let config = AppConfig::default();
+
+        let files = expand_paths(&[file.clone()], &config).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, file);
+        assert!(files[0].1.category.is_some());
+
+        let missing = dir.join("missing.md");
+        assert!(matches!(expand_paths(&[file, missing], &config), Err(Error::Validation(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+use crate::config::AppConfig;
+use crate::http;
+
+/// A hook command or webhook taking longer than this is abandoned
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// User automation run when a file finishes ingestion.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HookConfig {
+    /// Shell command; receives the event as JSON on stdin and as
+    /// EXEMEM_* environment variables
+    #[serde(default)]
+    pub command: Option<String>,
+    /// URL the event is POSTed to as JSON
+    #[serde(default)]
+    pub webhook_url: Option<String>,
+}
+
+impl HookConfig {
+    pub fn is_empty(&self) -> bool {
+        self.command().is_none() && self.webhook_url().is_none()
+    }
+
+    fn command(&self) -> Option<&str> {
+        self.command.as_deref().map(str::trim).filter(|c| !c.is_empty())
+    }
+
+    fn webhook_url(&self) -> Option<&str> {
+        self.webhook_url.as_deref().map(str::trim).filter(|u| !u.is_empty())
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(raw) = self.webhook_url() {
+            let url = url::Url::parse(raw).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+            if url.scheme() != "http" && url.scheme() != "https" {
+                return Err(format!("Webhook URL must be http or https: {}", raw));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Payload sent to hooks.
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestionEvent {
+    pub filename: String,
+    pub path: PathBuf,
+    /// Final status: "done", "failed", "error", or "uploaded" when ingestion
+    /// wasn't requested
+    pub status: String,
+    pub s3_key: String,
+    pub progress_id: Option<String>,
+}
+
+/// Run the configured hooks for `event`. Failures are logged, never returned:
+/// a broken hook must not affect the upload itself.
+pub async fn run_hooks(config: &AppConfig, event: &IngestionEvent) {
+    let hooks = &config.hooks;
+    if let Some(url) = hooks.webhook_url() {
+        if let Err(e) = post_webhook(config, url, event).await {
+            log::warn!("Webhook for {} failed: {}", event.filename, e);
+        }
+    }
+    if let Some(command) = hooks.command() {
+        if let Err(e) = run_command(command, event).await {
+            log::warn!("Hook command for {} failed: {}", event.filename, e);
+        }
+    }
+}
+
+async fn post_webhook(config: &AppConfig, url: &str, event: &IngestionEvent) -> Result<(), String> {
+    let client = http::client_builder(&config.proxy, &config.tls)
+        .timeout(HOOK_TIMEOUT)
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?;
+    let resp = client
+        .post(url)
+        .json(event)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    Ok(())
+}
+
+async fn run_command(command: &str, event: &IngestionEvent) -> Result<(), String> {
+    let payload = serde_json::to_vec(event).map_err(|e| e.to_string())?;
+
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = tokio::process::Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    };
+    cmd.env("EXEMEM_FILENAME", &event.filename)
+        .env("EXEMEM_PATH", &event.path)
+        .env("EXEMEM_STATUS", &event.status)
+        .env("EXEMEM_S3_KEY", &event.s3_key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let mut child = cmd.spawn().map_err(|e| format!("Failed to start: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read stdin at all; that's fine
+        let _ = stdin.write_all(&payload).await;
+    }
+
+    let output = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| format!("Timed out after {}s", HOOK_TIMEOUT.as_secs()))?
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "Exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_webhook_url() {
+        let hooks = |url: &str| HookConfig {
+            command: None,
+            webhook_url: Some(url.to_string()),
+        };
+        assert!(hooks("https://example.com/hook").validate().is_ok());
+        assert!(hooks("  ").validate().is_ok());
+        assert!(hooks("ftp://example.com").validate().is_err());
+        assert!(hooks("not a url").validate().is_err());
+        assert!(hooks("  ").is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_receives_event() {
+        let dir = std::env::temp_dir().join(format!("exemem-hooks-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.json");
+        let event = IngestionEvent {
+            filename: "a.txt".to_string(),
+            path: PathBuf::from("/tmp/a.txt"),
+            status: "done".to_string(),
+            s3_key: "user/a.txt".to_string(),
+            progress_id: None,
+        };
+
+        let command = format!("cat > {} && test \"$EXEMEM_STATUS\" = done", out.display());
+        run_command(&command, &event).await.unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(written["s3_key"], "user/a.txt");
+
+        assert!(run_command("exit 3", &event).await.is_err());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,252 @@
+use reqwest::{Certificate, Client, ClientBuilder, Identity, NoProxy, Proxy};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Outbound proxy for every request the client makes (ingestion, S3, query,
+/// storage).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ProxyConfig {
+    /// e.g. "http://proxy.corp.example:3128". When unset, the standard
+    /// HTTPS_PROXY / HTTP_PROXY / NO_PROXY environment variables apply.
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Hosts, domains (".corp.example") or CIDR ranges that bypass the proxy
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxyConfig {
+    fn url(&self) -> Option<&str> {
+        self.url.as_deref().map(str::trim).filter(|u| !u.is_empty())
+    }
+
+    /// Check the settings without building a client, for save_config.
+    pub fn validate(&self) -> Result<(), String> {
+        self.proxy().map(|_| ())
+    }
+
+    fn proxy(&self) -> Result<Option<Proxy>, String> {
+        let Some(url) = self.url() else {
+            return Ok(None);
+        };
+        let mut proxy = Proxy::all(url).map_err(|e| format!("Invalid proxy URL {}: {}", url, e))?;
+        if let Some(username) = self.username.as_deref().filter(|u| !u.is_empty()) {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or(""));
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+        Ok(Some(proxy))
+    }
+}
+
+/// Extra trust and client authentication for TLS, e.g. behind an
+/// intercepting corporate proxy or against a self-hosted deployment with a
+/// private CA.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TlsConfig {
+    /// PEM files with CA certificates to trust in addition to the built-in roots
+    #[serde(default)]
+    pub ca_certificates: Vec<PathBuf>,
+    /// PEM file with a client certificate chain and its private key
+    #[serde(default)]
+    pub client_identity: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Check that every configured file exists and parses, for save_config.
+    pub fn validate(&self) -> Result<(), String> {
+        self.certificates()?;
+        self.identity()?;
+        Ok(())
+    }
+
+    fn certificates(&self) -> Result<Vec<Certificate>, String> {
+        let mut certs = Vec::new();
+        for path in &self.ca_certificates {
+            let pem = read_pem(path)?;
+            certs.extend(Certificate::from_pem_bundle(&pem).map_err(|e| {
+                format!("Invalid CA certificate {}: {}", path.display(), e)
+            })?);
+        }
+        Ok(certs)
+    }
+
+    fn identity(&self) -> Result<Option<Identity>, String> {
+        let Some(path) = &self.client_identity else {
+            return Ok(None);
+        };
+        let pem = read_pem(path)?;
+        Identity::from_pem(&pem)
+            .map(Some)
+            .map_err(|e| format!("Invalid client certificate {}: {}", path.display(), e))
+    }
+}
+
+/// Connection reuse and connect timeout for the HTTP client shared by the
+/// uploader, query client and storage layer.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ConnectionConfig {
+    /// Idle connections kept open per host for reuse
+    pub pool_max_idle_per_host: usize,
+    /// Seconds an idle pooled connection is kept before it's closed
+    pub pool_idle_timeout_secs: u64,
+    /// Seconds between TCP keep-alive probes; 0 turns them off
+    pub tcp_keepalive_secs: u64,
+    /// Use HTTP/2 when the server offers it; off forces HTTP/1.1
+    pub http2: bool,
+    /// Seconds to wait for a connection to be established
+    pub connect_timeout_secs: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self {
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout_secs: 90,
+            tcp_keepalive_secs: 60,
+            http2: true,
+            connect_timeout_secs: 10,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.pool_max_idle_per_host > 256 {
+            return Err("Pool size must be at most 256 connections per host".to_string());
+        }
+        if !(1..=300).contains(&self.connect_timeout_secs) {
+            return Err("Connect timeout must be between 1 and 300 seconds".to_string());
+        }
+        Ok(())
+    }
+
+    fn apply(&self, mut builder: ClientBuilder) -> ClientBuilder {
+        let keepalive = (self.tcp_keepalive_secs > 0).then(|| Duration::from_secs(self.tcp_keepalive_secs));
+        builder = builder
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_secs))
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs.max(1)))
+            .tcp_keepalive(keepalive);
+        if !self.http2 {
+            builder = builder.http1_only();
+        }
+        builder
+    }
+}
+
+fn read_pem(path: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Client builder with the configured proxy and TLS settings applied.
+/// Without an explicit proxy, reqwest falls back to the proxy environment
+/// variables.
+///
+/// Settings are validated when saved, so an invalid setting here is logged
+/// and ignored rather than leaving the app without an HTTP client.
+pub fn client_builder(proxy: &ProxyConfig, tls: &TlsConfig) -> ClientBuilder {
+    let mut builder = reqwest::Client::builder();
+    match proxy.proxy() {
+        Ok(Some(proxy)) => builder = builder.proxy(proxy),
+        Ok(None) => {}
+        Err(e) => log::error!("{}; connecting without the configured proxy", e),
+    }
+    match tls.certificates() {
+        Ok(certs) => {
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Err(e) => log::error!("{}; using only the built-in CA certificates", e),
+    }
+    match tls.identity() {
+        Ok(Some(identity)) => builder = builder.identity(identity),
+        Ok(None) => {}
+        Err(e) => log::error!("{}; connecting without a client certificate", e),
+    }
+    builder
+}
+
+/// The client for Exemem API traffic. Build it once and clone it into each
+/// subsystem: clones share one connection pool.
+///
+/// There is no overall timeout; each subsystem sets one per request.
+pub fn build_client(proxy: &ProxyConfig, tls: &TlsConfig, connection: &ConnectionConfig) -> Client {
+    connection
+        .apply(client_builder(proxy, tls))
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert!(ProxyConfig::default().validate().is_ok());
+
+        let proxy = ProxyConfig {
+            url: Some("http://proxy.example:3128".to_string()),
+            username: Some("user".to_string()),
+            password: Some("secret".to_string()),
+            no_proxy: vec!["localhost".to_string(), ".corp.example".to_string()],
+        };
+        assert!(proxy.validate().is_ok());
+
+        let blank = ProxyConfig {
+            url: Some("  ".to_string()),
+            ..Default::default()
+        };
+        assert!(blank.validate().is_ok());
+
+        let invalid = ProxyConfig {
+            url: Some("not a url".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_connection_validate() {
+        assert!(ConnectionConfig::default().validate().is_ok());
+        let no_timeout = ConnectionConfig {
+            connect_timeout_secs: 0,
+            ..Default::default()
+        };
+        assert!(no_timeout.validate().is_err());
+
+        // Older configs without the section get the defaults
+        let parsed: ConnectionConfig = serde_json::from_str(r#"{"http2": false}"#).unwrap();
+        assert!(!parsed.http2);
+        assert_eq!(parsed.pool_max_idle_per_host, 8);
+    }
+
+    #[test]
+    fn test_tls_validate_reports_bad_files() {
+        assert!(TlsConfig::default().validate().is_ok());
+
+        let missing = TlsConfig {
+            ca_certificates: vec![PathBuf::from("/nonexistent/ca.pem")],
+            client_identity: None,
+        };
+        assert!(missing.validate().unwrap_err().contains("/nonexistent/ca.pem"));
+
+        let path = std::env::temp_dir().join(format!("exemem-ca-{}.pem", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "not a certificate").unwrap();
+        let garbage = TlsConfig {
+            ca_certificates: Vec::new(),
+            client_identity: Some(path.clone()),
+        };
+        assert!(garbage.validate().is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,491 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::paths;
+use crate::persist::JsonStore;
+use crate::uploader::{UploadResult, UploadStatus};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// One upload attempt, kept locally for dedup and provenance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    pub id: String,
+    pub filename: String,
+    pub original_path: Option<String>,
+    pub sha256: Option<String>,
+    pub s3_key: Option<String>,
+    pub status: UploadStatus,
+    pub error: Option<String>,
+    pub category: Option<String>,
+    pub timestamp: String,
+    /// Set when the user hid the entry; seconds since the Unix epoch
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+    /// Size of the file on disk
+    #[serde(default)]
+    pub original_bytes: Option<u64>,
+    /// Bytes sent to S3, after compression
+    #[serde(default)]
+    pub uploaded_bytes: Option<u64>,
+    /// Modification time of the file when it was uploaded, seconds since the
+    /// Unix epoch
+    #[serde(default)]
+    pub mtime: Option<u64>,
+}
+
+/// Criteria for browsing upload history. Unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryFilter {
+    /// Recorded at or after this time, seconds since the Unix epoch
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Recorded before this time, seconds since the Unix epoch
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Upload status name, case-insensitive (e.g. "done", "error")
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    fn matches(&self, entry: &LedgerEntry) -> bool {
+        let recorded_at = entry.recorded_at();
+        self.since.map_or(true, |since| recorded_at.is_some_and(|t| t >= since))
+            && self.until.map_or(true, |until| recorded_at.is_some_and(|t| t < until))
+            && self.status.as_deref().map_or(true, |status| {
+                format!("{:?}", entry.status).eq_ignore_ascii_case(status)
+            })
+            && self
+                .category
+                .as_deref()
+                .map_or(true, |category| entry.category.as_deref() == Some(category))
+    }
+}
+
+impl LedgerEntry {
+    pub fn from_result(id: String, result: &UploadResult, category: Option<String>, timestamp: String) -> Self {
+        let metadata = result.metadata.as_ref();
+        Self {
+            id,
+            filename: result.filename.clone(),
+            original_path: metadata.map(|m| m.original_path.clone()),
+            sha256: metadata.map(|m| m.sha256.clone()),
+            s3_key: Some(result.s3_key.clone()).filter(|k| !k.is_empty()),
+            status: result.status.clone(),
+            error: result.error.clone(),
+            category,
+            timestamp,
+            deleted_at: None,
+            original_bytes: result.original_bytes,
+            uploaded_bytes: result.uploaded_bytes,
+            mtime: metadata.and_then(|m| m.mtime),
+        }
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
+    }
+
+    /// When the entry was recorded, seconds since the Unix epoch.
+    pub fn recorded_at(&self) -> Option<u64> {
+        self.timestamp.parse().ok()
+    }
+}
+
+/// Files and contents the ledger has uploaded, including entries hidden
+/// since. Failed uploads and dry runs don't count.
+#[derive(Debug, Default)]
+pub struct UploadedFiles {
+    hashes: HashSet<String>,
+    /// (original path, modification time) of each upload
+    versions: HashSet<(String, u64)>,
+}
+
+impl UploadedFiles {
+    /// Whether a file with these contents was uploaded.
+    pub fn has_uploaded(&self, sha256: &str) -> bool {
+        self.hashes.contains(sha256)
+    }
+
+    /// Whether the file at `original_path` was uploaded as it was when last
+    /// modified at `mtime`, so it can be skipped without hashing it.
+    pub fn is_current(&self, original_path: &str, mtime: u64) -> bool {
+        self.versions.contains(&(original_path.to_string(), mtime))
+    }
+
+    /// Count contents uploaded since the snapshot was taken.
+    pub fn add(&mut self, sha256: &str) {
+        self.hashes.insert(sha256.to_string());
+    }
+}
+
+/// Persistent record of uploads.
+///
+/// Deleting an entry only hides it from view; hidden entries still count for
+/// dedup and provenance until they are purged, either explicitly or once they
+/// have been hidden longer than the retention window.
+pub struct Ledger {
+    store: JsonStore<Vec<LedgerEntry>>,
+}
+
+impl Ledger {
+    const FILE: &'static str = "ledger.json";
+
+    /// Load the ledger. An unreadable file is moved aside rather than
+    /// overwritten, so the audit trail can still be recovered by hand.
+    pub fn load() -> Result<Self, String> {
+        match JsonStore::load(Self::FILE, "ledger") {
+            Ok(store) => Ok(Self { store }),
+            Err(e) => {
+                let path = paths::data_dir()?.join(Self::FILE);
+                let backup = path.with_extension("json.corrupt");
+                log::error!("{}; moving it to {:?}", e, backup);
+                std::fs::rename(&path, &backup)
+                    .map_err(|e| format!("Failed to move corrupt ledger aside: {}", e))?;
+                Self::empty()
+            }
+        }
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "ledger").map(|store| Self { store })
+    }
+
+    pub fn record(&mut self, entry: LedgerEntry) -> Result<(), String> {
+        self.store.update(|entries| entries.push(entry))
+    }
+
+    /// Entries that haven't been hidden, newest first.
+    pub fn visible(&self) -> Vec<LedgerEntry> {
+        self.store.get().iter().rev().filter(|e| !e.is_deleted()).cloned().collect()
+    }
+
+    /// Visible entries matching `filter`, newest first.
+    pub fn history(&self, filter: &HistoryFilter) -> Vec<LedgerEntry> {
+        self.store
+            .get()
+            .iter()
+            .rev()
+            .filter(|e| !e.is_deleted() && filter.matches(e))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+
+    /// When the newest visible successful upload was recorded, seconds since
+    /// the Unix epoch.
+    pub fn last_success_at(&self) -> Option<u64> {
+        self.store
+            .get()
+            .iter()
+            .rev()
+            .filter(|e| !e.is_deleted())
+            .filter(|e| matches!(e.status, UploadStatus::Uploaded | UploadStatus::Ingesting | UploadStatus::Done))
+            .find_map(|e| e.recorded_at())
+    }
+
+    /// What has been uploaded, for deciding which files still need to be,
+    /// without holding on to the ledger while they are checked.
+    pub fn uploaded(&self) -> UploadedFiles {
+        let mut uploaded = UploadedFiles::default();
+        let entries = self.store.get().iter();
+        for e in entries.filter(|e| !matches!(e.status, UploadStatus::Error | UploadStatus::DryRun)) {
+            if let Some(sha256) = &e.sha256 {
+                uploaded.hashes.insert(sha256.clone());
+            }
+            if let (Some(path), Some(mtime)) = (&e.original_path, e.mtime) {
+                uploaded.versions.insert((path.clone(), mtime));
+            }
+        }
+        uploaded
+    }
+
+    /// Hidden entries that can still be restored, newest first.
+    pub fn deleted(&self) -> Vec<LedgerEntry> {
+        self.store.get().iter().rev().filter(|e| e.is_deleted()).cloned().collect()
+    }
+
+    /// Hide an entry. Returns false if no entry has this id.
+    pub fn soft_delete(&mut self, id: &str) -> Result<bool, String> {
+        self.store.update(|entries| match entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.deleted_at.get_or_insert(now_secs());
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Un-hide an entry. Returns false if no entry has this id.
+    pub fn restore(&mut self, id: &str) -> Result<bool, String> {
+        self.store.update(|entries| match entries.iter_mut().find(|e| e.id == id) {
+            Some(entry) => {
+                entry.deleted_at = None;
+                true
+            }
+            None => false,
+        })
+    }
+
+    /// Permanently remove hidden entries that were hidden at least
+    /// `older_than_days` ago (all hidden entries when None). Returns the ids removed.
+    pub fn purge_deleted(&mut self, older_than_days: Option<u64>) -> Result<Vec<String>, String> {
+        let cutoff = older_than_days.map(|days| now_secs().saturating_sub(days * SECS_PER_DAY));
+        self.store.update(|entries| {
+            let (purged, kept): (Vec<_>, Vec<_>) = entries.drain(..).partition(|e| {
+                match (e.deleted_at, cutoff) {
+                    (Some(at), Some(cutoff)) => at <= cutoff,
+                    (Some(_), None) => true,
+                    (None, _) => false,
+                }
+            });
+            *entries = kept;
+            purged.into_iter().map(|e| e.id).collect()
+        })
+    }
+}
+
+/// Upload history from the persisted ledger without going through the app.
+/// Used by the CLI.
+pub fn upload_history(filter: &HistoryFilter) -> Result<Vec<LedgerEntry>, String> {
+    let store = JsonStore::load(Ledger::FILE, "ledger")?;
+    Ok(Ledger { store }.history(filter))
+}
+
+/// Parse a date given either as seconds since the Unix epoch or as
+/// YYYY-MM-DD (midnight UTC).
+pub fn parse_date(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Ok(secs);
+    }
+
+    let invalid = || format!("Invalid date {:?}; use YYYY-MM-DD or Unix seconds", value);
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts[..] else {
+        return Err(invalid());
+    };
+    let year: i64 = year.parse().map_err(|_| invalid())?;
+    let month: i64 = month.parse().map_err(|_| invalid())?;
+    let day: i64 = day.parse().map_err(|_| invalid())?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || year < 1970 {
+        return Err(invalid());
+    }
+
+    // Days since 1970-01-01 in the proleptic Gregorian calendar
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+    Ok(days as u64 * SECS_PER_DAY)
+}
+
+pub fn now_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_ledger() -> Ledger {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-ledger-{}", uuid::Uuid::new_v4()))
+            .join("ledger.json");
+        open(path)
+    }
+
+    fn open(path: PathBuf) -> Ledger {
+        Ledger {
+            store: JsonStore::load_from(path, "ledger").unwrap(),
+        }
+    }
+
+    fn entry(id: &str, sha256: &str) -> LedgerEntry {
+        LedgerEntry {
+            id: id.to_string(),
+            filename: format!("{}.json", id),
+            original_path: None,
+            sha256: Some(sha256.to_string()),
+            s3_key: None,
+            status: UploadStatus::Done,
+            error: None,
+            category: None,
+            timestamp: "0".to_string(),
+            deleted_at: None,
+            original_bytes: None,
+            uploaded_bytes: None,
+            mtime: None,
+        }
+    }
+
+    #[test]
+    fn test_last_success_skips_errors() {
+        let mut ledger = temp_ledger();
+        assert_eq!(ledger.last_success_at(), None);
+        ledger.record(LedgerEntry { timestamp: "100".to_string(), ..entry("a", "hash-a") }).unwrap();
+        ledger
+            .record(LedgerEntry {
+                timestamp: "200".to_string(),
+                status: UploadStatus::Error,
+                ..entry("b", "hash-b")
+            })
+            .unwrap();
+        assert_eq!(ledger.last_success_at(), Some(100));
+    }
+
+    #[test]
+    fn test_soft_delete_hides_but_keeps_for_dedup() {
+        let mut ledger = temp_ledger();
+        ledger.record(entry("a", "hash-a")).unwrap();
+        ledger.record(entry("b", "hash-b")).unwrap();
+
+        assert!(ledger.soft_delete("a").unwrap());
+        assert_eq!(ledger.visible().len(), 1);
+        assert_eq!(ledger.deleted()[0].sha256.as_deref(), Some("hash-a"));
+
+        assert!(ledger.restore("a").unwrap());
+        assert_eq!(ledger.visible().len(), 2);
+    }
+
+    #[test]
+    fn test_has_uploaded_ignores_failures() {
+        let mut ledger = temp_ledger();
+        ledger.record(entry("a", "hash-a")).unwrap();
+        ledger.record(LedgerEntry {
+            status: UploadStatus::Error,
+            ..entry("b", "hash-b")
+        })
+        .unwrap();
+        ledger.soft_delete("a").unwrap();
+
+        let uploaded = ledger.uploaded();
+        assert!(uploaded.has_uploaded("hash-a"));
+        assert!(!uploaded.has_uploaded("hash-b"));
+        assert!(!uploaded.has_uploaded("hash-c"));
+    }
+
+    #[test]
+    fn test_is_current_needs_same_path_and_mtime() {
+        let mut ledger = temp_ledger();
+        ledger
+            .record(LedgerEntry {
+                original_path: Some("/docs/a.md".to_string()),
+                mtime: Some(100),
+                ..entry("a", "hash-a")
+            })
+            .unwrap();
+        ledger
+            .record(LedgerEntry {
+                original_path: Some("/docs/b.md".to_string()),
+                mtime: Some(100),
+                status: UploadStatus::Error,
+                ..entry("b", "hash-b")
+            })
+            .unwrap();
+
+        let uploaded = ledger.uploaded();
+        assert!(uploaded.is_current("/docs/a.md", 100));
+        assert!(!uploaded.is_current("/docs/a.md", 101));
+        assert!(!uploaded.is_current("/docs/b.md", 100));
+    }
+
+    #[test]
+    fn test_unknown_id() {
+        let mut ledger = temp_ledger();
+        assert!(!ledger.soft_delete("missing").unwrap());
+        assert!(!ledger.restore("missing").unwrap());
+    }
+
+    #[test]
+    fn test_purge_respects_retention() {
+        let mut ledger = temp_ledger();
+        ledger.record(entry("old", "h1")).unwrap();
+        ledger.record(entry("new", "h2")).unwrap();
+        ledger.record(entry("live", "h3")).unwrap();
+        ledger.soft_delete("new").unwrap();
+        ledger
+            .store
+            .update(|entries| entries[0].deleted_at = Some(now_secs() - 40 * SECS_PER_DAY))
+            .unwrap();
+
+        assert_eq!(ledger.purge_deleted(Some(30)).unwrap(), vec!["old".to_string()]);
+        assert_eq!(ledger.purge_deleted(None).unwrap(), vec!["new".to_string()]);
+        assert_eq!(ledger.visible().len(), 1);
+        assert!(ledger.deleted().is_empty());
+    }
+
+    #[test]
+    fn test_history_filters() {
+        let mut ledger = temp_ledger();
+        for (id, ts, status, category) in [
+            ("a", "100", UploadStatus::Done, "work"),
+            ("b", "200", UploadStatus::Error, "work"),
+            ("c", "300", UploadStatus::Done, "media"),
+        ] {
+            let mut e = entry(id, id);
+            e.timestamp = ts.to_string();
+            e.status = status;
+            e.category = Some(category.to_string());
+            ledger.record(e).unwrap();
+        }
+        ledger.soft_delete("c").unwrap();
+
+        let ids = |filter: HistoryFilter| -> Vec<String> {
+            ledger.history(&filter).into_iter().map(|e| e.id).collect()
+        };
+        assert_eq!(ids(HistoryFilter::default()), vec!["b", "a"]);
+        assert_eq!(
+            ids(HistoryFilter {
+                status: Some("done".to_string()),
+                ..Default::default()
+            }),
+            vec!["a"]
+        );
+        assert_eq!(
+            ids(HistoryFilter {
+                since: Some(150),
+                category: Some("work".to_string()),
+                ..Default::default()
+            }),
+            vec!["b"]
+        );
+        assert_eq!(
+            ids(HistoryFilter {
+                limit: Some(1),
+                ..Default::default()
+            }),
+            vec!["b"]
+        );
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1700000000").unwrap(), 1_700_000_000);
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2024-03-01").unwrap(), 1_709_251_200);
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_persists_across_loads() {
+        let mut ledger = temp_ledger();
+        ledger.record(entry("a", "hash-a")).unwrap();
+        ledger.soft_delete("a").unwrap();
+
+        let reloaded = open(ledger.store.path().to_path_buf());
+        assert_eq!(reloaded.deleted().len(), 1);
+    }
+}
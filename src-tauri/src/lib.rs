@@ -1,25 +1,114 @@
-mod config;
+mod archive;
+pub mod batch;
+pub mod cancel;
+pub mod capabilities;
+mod capture;
+mod compression;
+pub mod config;
+pub mod config_check;
+mod config_crypto;
+mod config_migrations;
+pub mod dead_letter;
+pub mod doctor;
+mod direct_s3;
+pub mod env_config;
+pub mod error;
+mod events;
+pub mod export;
+mod feedback;
+mod file_actions;
+pub mod headless;
+mod hooks;
+pub mod http;
+pub mod ledger;
+pub mod logs;
+pub mod naming;
+mod offline_queue;
+pub mod paths;
+mod payload;
+mod pending;
+mod persist;
+pub mod profiles;
+mod presigned;
 pub mod query;
-mod scanner;
+mod query_cache;
+pub mod query_metrics;
+mod query_results;
+pub mod redact;
+pub mod retry;
+pub mod saved_queries;
+mod scan_trends;
+pub mod scanner;
+mod schedule;
+pub mod search;
+pub mod self_update;
+mod server_error;
+pub mod sessions;
+mod settings_bundle;
+pub mod signing;
+pub mod simulate;
+pub mod stats;
+mod startup;
+pub mod status;
 pub mod storage;
-mod uploader;
-mod watcher;
-
-use config::AppConfig;
+mod telemetry;
+mod tray;
+pub mod transcripts;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod upload_queue;
+pub mod uploader;
+pub mod watcher;
+
+use capabilities::Capabilities;
+use cancel::{
+    CommandRegistry, Invocation, DIALOG_TIMEOUT, LOCAL_TIMEOUT, MAX_QUERY_TIMEOUT, QUERY_TIMEOUT, SCAN_TIMEOUT,
+};
+use config::{AppConfig, ConfigEncryption};
+use config_check::ConfigReport;
+use dead_letter::{DeadLetterQueue, FailedUpload};
+use error::Error;
+use export::ExportFormat;
+use events::{EventBuffer, MissedEvents};
+use feedback::ClassificationFeedback;
+use file_actions::PostIngestAction;
+use payload::{Payload, PayloadStore};
+use pending::{PendingApproval, PendingApprovals};
+use profiles::{ProfileSummary, Profiles};
+use ledger::{HistoryFilter, Ledger, LedgerEntry};
 use query::QueryClient;
-use scanner::{classify_single_file, ScanResult};
-use uploader::{UploadResult, UploadStatus, Uploader};
+use query_metrics::{QueryMetrics, QueryMetricsReport};
+use query_results::{ResultCursor, ResultPage, ResultView};
+use offline_queue::{Connectivity, OfflineQueue, QueuedItem, QueuedRequest};
+use saved_queries::{SavedQueries, SavedQuery};
+use scan_trends::{ScanHistory, ScanSnapshot, ScanTrends};
+use scanner::{classify_single_file, FileRecommendation, ScanResult};
+use settings_bundle::{ImportSummary, SettingsBundle};
+use schedule::{ScheduledUpload, ScheduledUploads};
+use hooks::IngestionEvent;
+use startup::{StartupProfile, StartupReport};
+use telemetry::{Telemetry, TelemetryBatch};
+use transcripts::{
+    QueryHistoryEntry, QueryHistoryFilter, SessionSummary, TranscriptHit, TranscriptStore,
+    TranscriptTurn,
+};
+use upload_queue::UploadPriority;
+use uploader::{
+    is_success_status, is_terminal_status, FileContext, RequestError, UploadProgressFn,
+    UploadResult, UploadStatus, Uploader, MAX_PROGRESS_POLLS, PROGRESS_POLL_INTERVAL,
+};
 use watcher::{FolderWatcher, WatchEvent};
 
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tauri::{
-    menu::{MenuBuilder, MenuItemBuilder},
+    menu::{MenuBuilder, MenuItem, MenuItemBuilder},
     tray::TrayIconBuilder,
-    Emitter, Manager, State,
+    Emitter, Manager, State, Wry,
 };
 use tauri_plugin_deep_link::DeepLinkExt;
-use tokio::sync::{mpsc, Mutex};
+use tokio::sync::{mpsc, watch, Mutex, Notify};
+use uuid::Uuid;
 
 const MAX_ACTIVITY_LOG: usize = 50;
 
@@ -28,16 +117,29 @@ pub struct SyncStatus {
     pub watching: bool,
     pub folder: Option<String>,
     pub file_count: usize,
+    pub queued_uploads: usize,
+    pub server_busy: bool,
     pub recent_activity: Vec<ActivityEntry>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActivityEntry {
+    /// Shared with the matching ledger entry for uploads
+    #[serde(default)]
+    pub id: String,
     pub filename: String,
     pub status: UploadStatus,
     pub error: Option<String>,
     pub timestamp: String,
     pub category: Option<String>,
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// Set when hidden from view; seconds since the Unix epoch
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+    /// What happened to the local file after ingestion, e.g. "Moved to ingested/a.txt"
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,37 +149,458 @@ pub struct FileProgress {
     pub status: String,
     pub percent: f64,
     pub message: Option<String>,
+    /// For "scheduled" entries: when the upload will run, seconds since the Unix epoch
+    #[serde(default)]
+    pub scheduled_at: Option<u64>,
 }
 
+/// Minimum gap between progress events sent while a file is being uploaded
+const TRANSFER_PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How often deferred watcher uploads are checked against the quiet-hours window
+const SCHEDULE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// Pause between releasing deferred uploads, and while the upload queue is full
+const SCHEDULE_RELEASE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How often queued offline requests are retried
+const OFFLINE_REPLAY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+/// How often buffered telemetry events are uploaded, when enabled
+const TELEMETRY_UPLOAD_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 pub struct AppState {
     config: Arc<Mutex<AppConfig>>,
+    /// Latest config, for long-running tasks that would otherwise keep
+    /// the snapshot they started with. Updated with `config` by `set_config`.
+    config_updates: watch::Sender<AppConfig>,
     watching: Arc<Mutex<bool>>,
     activity_log: Arc<Mutex<Vec<ActivityEntry>>>,
+    ledger: Arc<Mutex<Ledger>>,
+    dead_letters: Arc<Mutex<DeadLetterQueue>>,
+    feedback: Arc<Mutex<ClassificationFeedback>>,
+    scheduled: Arc<Mutex<ScheduledUploads>>,
+    /// Watched files waiting for the user when auto-approve is off
+    pending: Arc<Mutex<PendingApprovals>>,
+    transcripts: Arc<Mutex<TranscriptStore>>,
+    saved_queries: Arc<Mutex<SavedQueries>>,
+    offline_queue: Arc<Mutex<OfflineQueue>>,
+    connectivity: Arc<Connectivity>,
     stop_tx: Arc<Mutex<Option<mpsc::Sender<()>>>>,
     scan_result: Arc<Mutex<Option<ScanResult>>>,
     ingestion_progress: Arc<Mutex<Vec<FileProgress>>>,
+    uploader: Arc<Uploader>,
+    events: EventBuffer,
+    payloads: PayloadStore,
     query_client: QueryClient,
+    file_count: Arc<Mutex<FileCountCache>>,
+    startup: Arc<StartupProfile>,
+    commands: Arc<CommandRegistry>,
+    telemetry: Arc<Mutex<Telemetry>>,
+    /// Poked whenever a state-change event goes out, so the tray redraws
+    tray_refresh: Arc<Notify>,
+    /// The client shared by `uploader` and `query_client`, for other requests
+    http_client: reqwest::Client,
+}
+
+/// Replace the in-memory config held by `guard` and notify subscribers of
+/// `config_updates`, so running tasks pick the change up immediately.
+fn set_config(state: &AppState, guard: &mut AppConfig, config: AppConfig) {
+    redact::remember_secrets(&config);
+    *guard = config.clone();
+    state.config_updates.send_replace(config);
+}
+
+/// How long a cached file count is served before a background recount
+const FILE_COUNT_MAX_AGE: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Recursive file count of the watched folder. Walking a large tree is slow,
+/// so status requests get the cached value and a recount runs in the background.
+#[derive(Default)]
+struct FileCountCache {
+    folder: Option<std::path::PathBuf>,
+    count: usize,
+    counted_at: Option<std::time::Instant>,
+    refreshing: bool,
 }
 
 #[tauri::command]
-async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, String> {
+async fn get_config(state: State<'_, AppState>) -> Result<AppConfig, Error> {
     let config = state.config.lock().await;
     Ok(config.clone())
 }
 
 #[tauri::command]
 async fn save_config(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
     new_config: AppConfig,
-) -> Result<(), String> {
-    new_config.save()?;
+    invocation_id: Option<String>,
+) -> Result<(), Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            new_config.validate()?;
+            let mut config = state.config.lock().await;
+            if config.launch_at_login != new_config.launch_at_login {
+                apply_autostart(&app, new_config.launch_at_login)?;
+            }
+            if config.quick_query_shortcut != new_config.quick_query_shortcut {
+                apply_quick_query_shortcut(&app, &config.quick_query_shortcut, &new_config.quick_query_shortcut)?;
+            }
+            new_config.save()?;
+            // Nothing collected while opted in is kept after opting out
+            if config.telemetry_enabled && !new_config.telemetry_enabled {
+                if let Err(e) = state.telemetry.lock().await.clear() {
+                    log::warn!("Failed to clear telemetry: {}", e);
+                }
+            }
+            set_config(&state, &mut config, new_config);
+            Ok(())
+        })
+        .await
+}
+
+/// Passed by the login item, so a launch at login starts in the tray
+const HIDDEN_ARG: &str = "--hidden";
+
+/// Register or remove the login item if it isn't already in that state.
+/// Returns whether it's registered afterwards.
+fn apply_autostart(app: &tauri::AppHandle, enabled: bool) -> Result<bool, Error> {
+    use tauri_plugin_autostart::ManagerExt;
+
+    let launcher = app.autolaunch();
+    let failed = |e: tauri_plugin_autostart::Error| Error::Internal(format!("Launch at login: {}", e));
+    if launcher.is_enabled().map_err(failed)? != enabled {
+        if enabled {
+            launcher.enable().map_err(failed)?;
+        } else {
+            launcher.disable().map_err(failed)?;
+        }
+    }
+    launcher.is_enabled().map_err(failed)
+}
+
+/// Label of the compact always-on-top window the global shortcut toggles
+const QUICK_QUERY_WINDOW: &str = "quick-query";
+
+/// Swap the global shortcut from `old` to `new`; either may be empty.
+fn apply_quick_query_shortcut(app: &tauri::AppHandle, old: &str, new: &str) -> Result<(), Error> {
+    use tauri_plugin_global_shortcut::GlobalShortcutExt;
+
+    let shortcuts = app.global_shortcut();
+    let (old, new) = (old.trim(), new.trim());
+    if !old.is_empty() && shortcuts.is_registered(old) {
+        if let Err(e) = shortcuts.unregister(old) {
+            log::warn!("Failed to release shortcut {}: {}", old, e);
+        }
+    }
+    if !new.is_empty() {
+        if let Err(e) = shortcuts.register(new) {
+            // Keep the old one working if the new one is taken
+            if !old.is_empty() {
+                let _ = shortcuts.register(old);
+            }
+            return Err(Error::Validation(format!(
+                "Couldn't use {} as the quick-query shortcut: {}",
+                new, e
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Show the quick-query window, creating it the first time, or hide it if
+/// it's already showing.
+fn toggle_quick_query(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_webview_window(QUICK_QUERY_WINDOW) {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+        } else {
+            let _ = window.center();
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+        return;
+    }
+
+    let built = tauri::WebviewWindowBuilder::new(
+        app,
+        QUICK_QUERY_WINDOW,
+        tauri::WebviewUrl::App("index.html".into()),
+    )
+    .title("Exemem Quick Query")
+    .inner_size(640.0, 360.0)
+    .resizable(false)
+    .decorations(false)
+    .always_on_top(true)
+    .skip_taskbar(true)
+    .center()
+    .focused(true)
+    .build();
+    match built {
+        Ok(window) => {
+            // Like a launcher, it goes away when the user clicks elsewhere
+            let window_clone = window.clone();
+            window.on_window_event(move |event| {
+                if let tauri::WindowEvent::Focused(false) = event {
+                    let _ = window_clone.hide();
+                }
+            });
+        }
+        Err(e) => log::error!("Failed to open the quick-query window: {}", e),
+    }
+}
+
+#[tauri::command]
+async fn hide_quick_query(app: tauri::AppHandle) -> Result<(), Error> {
+    if let Some(window) = app.get_webview_window(QUICK_QUERY_WINDOW) {
+        window
+            .hide()
+            .map_err(|e| Error::Internal(format!("Failed to hide the quick-query window: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Whether the app is registered to start at login.
+#[tauri::command]
+async fn get_autostart(app: tauri::AppHandle) -> Result<bool, Error> {
+    use tauri_plugin_autostart::ManagerExt;
+    app.autolaunch()
+        .is_enabled()
+        .map_err(|e| Error::Internal(format!("Launch at login: {}", e)))
+}
+
+/// Start the app at login or stop doing so, and remember the choice in the
+/// config. Returns whether the login item is registered.
+#[tauri::command]
+async fn set_autostart(app: tauri::AppHandle, state: State<'_, AppState>, enabled: bool) -> Result<bool, Error> {
+    let registered = apply_autostart(&app, enabled)?;
+    let mut config = state.config.lock().await;
+    let updated = AppConfig {
+        launch_at_login: enabled,
+        ..config.clone()
+    };
+    updated.save()?;
+    set_config(&state, &mut config, updated);
+    Ok(registered)
+}
+
+#[tauri::command]
+async fn list_profiles() -> Result<Vec<ProfileSummary>, Error> {
+    Ok(Profiles::load()?.summaries())
+}
+
+/// Save the current account settings as profile `name` and make it current.
+#[tauri::command]
+async fn save_profile(state: State<'_, AppState>, name: String) -> Result<(), Error> {
+    let config = state.config.lock().await;
+    let mut profiles = Profiles::load()?;
+    profiles.save_current(&name, &config).map_err(Error::Validation)?;
+    profiles.save()
+}
+
+/// Load profile `name` into the config. Watching has to be stopped first,
+/// since the watched folder may change.
+#[tauri::command]
+async fn switch_profile(state: State<'_, AppState>, name: String) -> Result<AppConfig, Error> {
+    if *state.watching.lock().await {
+        return Err(Error::Validation(
+            "Stop watching before switching profiles".to_string(),
+        ));
+    }
+    let mut config = state.config.lock().await;
+    let mut profiles = Profiles::load()?;
+    let mut switched = config.clone();
+    profiles.switch(&name, &mut switched).map_err(Error::Validation)?;
+    switched.save()?;
+    profiles.save()?;
+    set_config(&state, &mut config, switched.clone());
+    Ok(switched)
+}
+
+#[tauri::command]
+async fn config_encryption_status() -> Result<ConfigEncryption, Error> {
+    AppConfig::encryption()
+}
+
+/// Unlock an encrypted config.json and make it the current config.
+#[tauri::command]
+async fn unlock_config(
+    state: State<'_, AppState>,
+    passphrase: String,
+    invocation_id: Option<String>,
+) -> Result<AppConfig, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let unlocked = tokio::task::spawn_blocking(move || AppConfig::unlock(&passphrase))
+                .await
+                .map_err(|e| Error::Internal(e.to_string()))??;
+            let mut config = state.config.lock().await;
+            set_config(&state, &mut config, unlocked.clone());
+            Ok(unlocked)
+        })
+        .await
+}
+
+/// Stop watching and forget the passphrase, leaving the app unconfigured
+/// until it's unlocked again.
+#[tauri::command]
+async fn lock_config(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), Error> {
+    if !AppConfig::encryption()?.encrypted {
+        return Err(Error::Validation("config.json is not encrypted".to_string()));
+    }
+    if let Some(tx) = state.stop_tx.lock().await.take() {
+        let _ = tx.send(()).await;
+    }
+    *state.watching.lock().await = false;
+    emit_replayable(&app, "sync-status-changed", false);
+    AppConfig::lock();
     let mut config = state.config.lock().await;
-    *config = new_config;
+    set_config(&state, &mut config, AppConfig::default());
     Ok(())
 }
 
+/// Encrypt config.json with `passphrase`, or store it unencrypted with none.
+#[tauri::command]
+async fn set_config_passphrase(
+    state: State<'_, AppState>,
+    passphrase: Option<String>,
+    invocation_id: Option<String>,
+) -> Result<(), Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            tokio::task::spawn_blocking(move || config.set_passphrase(passphrase.as_deref()))
+                .await
+                .map_err(|e| Error::Internal(e.to_string()))?
+        })
+        .await
+}
+
+/// Save the config, learned classification rules, saved queries and
+/// profiles to a file picked by the user, for setting up another machine.
+/// Credentials are only included when `include_secrets` is set.
+#[tauri::command]
+async fn export_settings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    include_secrets: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<Option<String>, Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    state
+        .commands
+        .run(invocation_id, DIALOG_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            let profiles = Profiles::load()?;
+            let bundle = SettingsBundle::collect(
+                &config,
+                state.feedback.lock().await.rules(),
+                &*state.saved_queries.lock().await,
+                &profiles,
+                include_secrets.unwrap_or(false),
+            )
+            .map_err(Error::Internal)?;
+
+            let path = tokio::task::spawn_blocking(move || {
+                app.dialog()
+                    .file()
+                    .add_filter("json", &["json"])
+                    .set_file_name("exemem-settings.json")
+                    .blocking_save_file()
+                    .and_then(|path| path.into_path().ok())
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))?;
+
+            let Some(path) = path else {
+                return Ok(None);
+            };
+            bundle.write(&path).map_err(Error::Io)?;
+            Ok(Some(path.display().to_string()))
+        })
+        .await
+}
+
+/// Apply a file from `export_settings` over the local settings. Learned
+/// classification rules are replaced; saved queries and profiles are added,
+/// replacing any with the same name.
+#[tauri::command]
+async fn import_settings(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    invocation_id: Option<String>,
+) -> Result<Option<ImportSummary>, Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    // Only the dialog wait is cancellable, so settings aren't left half-imported
+    let path = state
+        .commands
+        .run(invocation_id, DIALOG_TIMEOUT, async {
+            tokio::task::spawn_blocking(move || {
+                app.dialog()
+                    .file()
+                    .add_filter("json", &["json"])
+                    .blocking_pick_file()
+                    .and_then(|path| path.into_path().ok())
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))
+        })
+        .await?;
+    let Some(path) = path else {
+        return Ok(None);
+    };
+
+    let bundle = SettingsBundle::read(&path).map_err(Error::Validation)?;
+    let mut config = state.config.lock().await;
+    let imported = bundle.merged_config(&config).map_err(Error::Validation)?;
+    imported.validate()?;
+    let (profiles, profile_count) = bundle
+        .merged_profiles(&Profiles::load()?)
+        .map_err(Error::Validation)?;
+
+    imported.save()?;
+    profiles.save()?;
+    state
+        .feedback
+        .lock()
+        .await
+        .replace(bundle.classification_rules.clone())
+        .map_err(Error::Io)?;
+    let mut saved = state.saved_queries.lock().await;
+    for query in &bundle.saved_queries {
+        saved.upsert(&query.name, &query.query).map_err(Error::Validation)?;
+    }
+    set_config(&state, &mut config, imported.clone());
+
+    Ok(Some(ImportSummary {
+        config: imported,
+        saved_queries: bundle.saved_queries.len(),
+        profiles: profile_count,
+        includes_secrets: bundle.includes_secrets(),
+    }))
+}
+
+/// Check the current config for missing or invalid settings and try an
+/// authenticated request against the API.
 #[tauri::command]
-async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, String> {
+async fn validate_config(
+    state: State<'_, AppState>,
+    invocation_id: Option<String>,
+) -> Result<ConfigReport, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            Ok(config_check::check_config(&config, &state.query_client).await)
+        })
+        .await
+}
+
+#[tauri::command]
+async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, Error> {
     use tauri_plugin_dialog::DialogExt;
 
     let app_clone = app.clone();
@@ -86,54 +609,418 @@ async fn select_folder(app: tauri::AppHandle) -> Result<Option<String>, String>
         folder.map(|f| f.to_string())
     })
     .await
-    .map_err(|e| format!("Dialog task failed: {}", e))
+    .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))
 }
 
 #[tauri::command]
-async fn get_sync_status(state: State<'_, AppState>) -> Result<SyncStatus, String> {
+async fn get_sync_status(state: State<'_, AppState>) -> Result<SyncStatus, Error> {
     let watching = *state.watching.lock().await;
     let config = state.config.lock().await;
     let activity = state.activity_log.lock().await;
 
-    let file_count = config
-        .watched_folder
-        .as_ref()
-        .and_then(|folder| count_files(folder).ok())
-        .unwrap_or(0);
+    let file_count = match &config.watched_folder {
+        Some(folder) => cached_file_count(&state.file_count, folder).await,
+        None => 0,
+    };
 
     Ok(SyncStatus {
         watching,
         folder: config.watched_folder.as_ref().map(|p| p.display().to_string()),
         file_count,
-        recent_activity: activity.clone(),
+        queued_uploads: state.uploader.queued(),
+        server_busy: state.uploader.server_busy(),
+        recent_activity: visible_activity(&activity),
     })
 }
 
 #[tauri::command]
-async fn get_recent_activity(state: State<'_, AppState>) -> Result<Vec<ActivityEntry>, String> {
+async fn get_recent_activity(state: State<'_, AppState>) -> Result<Vec<ActivityEntry>, Error> {
     let activity = state.activity_log.lock().await;
-    Ok(activity.clone())
+    Ok(visible_activity(&activity))
 }
 
+/// Persisted upload history, newest first. Unlike the activity log this
+/// covers every upload, not just the most recent ones.
 #[tauri::command]
-async fn scan_folder(state: State<'_, AppState>) -> Result<ScanResult, String> {
+async fn get_upload_history(
+    state: State<'_, AppState>,
+    filter: Option<HistoryFilter>,
+) -> Result<Vec<LedgerEntry>, Error> {
+    Ok(state.ledger.lock().await.history(&filter.unwrap_or_default()))
+}
+
+/// Hide an activity/ledger entry from view. It stays in the ledger for dedup
+/// and provenance until purged.
+#[tauri::command]
+async fn delete_entry(state: State<'_, AppState>, id: String) -> Result<bool, Error> {
+    let in_ledger = state.ledger.lock().await.soft_delete(&id)?;
+
+    let mut activity = state.activity_log.lock().await;
+    let in_activity = match activity.iter_mut().find(|e| e.id == id) {
+        Some(entry) => {
+            entry.deleted_at.get_or_insert(ledger::now_secs());
+            true
+        }
+        None => false,
+    };
+
+    Ok(in_ledger || in_activity)
+}
+
+#[tauri::command]
+async fn restore_entry(state: State<'_, AppState>, id: String) -> Result<bool, Error> {
+    let in_ledger = state.ledger.lock().await.restore(&id)?;
+
+    let mut activity = state.activity_log.lock().await;
+    let in_activity = match activity.iter_mut().find(|e| e.id == id) {
+        Some(entry) => {
+            entry.deleted_at = None;
+            true
+        }
+        None => false,
+    };
+
+    Ok(in_ledger || in_activity)
+}
+
+#[tauri::command]
+async fn get_deleted_entries(state: State<'_, AppState>) -> Result<Vec<LedgerEntry>, Error> {
+    Ok(state.ledger.lock().await.deleted())
+}
+
+/// Permanently remove hidden entries, optionally only those hidden at least
+/// `older_than_days` ago. Returns the number of ledger entries removed.
+#[tauri::command]
+async fn purge_deleted_entries(
+    state: State<'_, AppState>,
+    older_than_days: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<usize, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let purged = state.ledger.lock().await.purge_deleted(older_than_days)?;
+
+            let cutoff = older_than_days
+                .map(|days| ledger::now_secs().saturating_sub(days * 24 * 60 * 60))
+                .unwrap_or(u64::MAX);
+            state
+                .activity_log
+                .lock()
+                .await
+                .retain(|e| !purged.contains(&e.id) && e.deleted_at.map_or(true, |at| at > cutoff));
+
+            Ok(purged.len())
+        })
+        .await
+}
+
+#[tauri::command]
+async fn get_failed_uploads(state: State<'_, AppState>) -> Result<Vec<FailedUpload>, Error> {
+    Ok(state.dead_letters.lock().await.list())
+}
+
+/// Re-run failed uploads (all of them when `ids` is None) through the same
+/// pipeline as `retry_ingestion`. Returns how many were queued; outcomes
+/// arrive as `sync-activity` events.
+#[tauri::command]
+async fn retry_failed_uploads(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    ids: Option<Vec<String>>,
+) -> Result<usize, Error> {
+    let failed = state.dead_letters.lock().await.select(ids.as_deref());
+    let selected = failed.into_iter().map(|entry| (entry.filename.clone(), entry)).collect();
+    Ok(requeue_failed(&app, &state, selected).await)
+}
+
+/// Re-run failed uploads picked by file name (or by path relative to the
+/// watched folder) through the same pipeline as approved files, with their
+/// progress entries reset. Returns how many were queued; outcomes arrive as
+/// `sync-activity` events like any other upload.
+#[tauri::command]
+async fn retry_ingestion(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    filenames: Vec<String>,
+) -> Result<usize, Error> {
+    let failed = state.dead_letters.lock().await.list();
+    let mut selected = Vec::new();
+    let mut unknown = Vec::new();
+    for name in &filenames {
+        match failed
+            .iter()
+            .find(|f| f.filename == *name || f.path.ends_with(name))
+        {
+            Some(entry) => selected.push((name.clone(), entry.clone())),
+            None => unknown.push(name.as_str()),
+        }
+    }
+    if !unknown.is_empty() {
+        return Err(Error::Validation(format!(
+            "Not in the failed uploads: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(requeue_failed(&app, &state, selected).await)
+}
+
+/// Reset the progress entries of failed uploads and re-ingest them at manual
+/// priority, each under the name its progress entry is keyed by.
+async fn requeue_failed(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    selected: Vec<(String, FailedUpload)>,
+) -> usize {
+    {
+        let mut progress = state.ingestion_progress.lock().await;
+        for (name, _) in &selected {
+            let fresh = FileProgress {
+                filename: name.clone(),
+                progress_id: None,
+                status: "pending".to_string(),
+                percent: 0.0,
+                message: None,
+                scheduled_at: None,
+            };
+            match progress.iter_mut().find(|p| p.filename == *name) {
+                Some(entry) => *entry = fresh,
+                None => progress.push(fresh),
+            }
+        }
+    }
+    emit_replayable(app, "ingestion-progress", get_progress_snapshot(&state.ingestion_progress).await);
+
     let config = state.config.lock().await.clone();
+    let count = selected.len();
+    for (name, entry) in selected {
+        let retry = ingest_tracked(app.clone(), entry.path, name, entry.context, config.clone(), UploadPriority::Manual);
+        tokio::spawn(retry);
+    }
+    count
+}
 
-    let folder = config
-        .watched_folder
-        .ok_or_else(|| "No watched folder configured".to_string())?;
+/// Abort a command started with `invocation_id`. The command fails with a
+/// `cancelled` error; for approve_and_ingest, uploads still in progress are aborted
+/// (files the server already accepted keep ingesting there). Returns false
+/// if the invocation already finished.
+#[tauri::command]
+async fn cancel_command(state: State<'_, AppState>, invocation_id: String) -> Result<bool, Error> {
+    let cancelled = state.commands.cancel(&invocation_id);
+    if cancelled {
+        log::info!("Cancelled command invocation {}", invocation_id);
+    }
+    Ok(cancelled)
+}
 
-    if !folder.exists() {
-        return Err(format!("Folder does not exist: {:?}", folder));
+/// Documents nearest in meaning to `text`, most similar first. Complements
+/// `search_index`, which matches words.
+#[tauri::command]
+async fn semantic_search(
+    state: State<'_, AppState>,
+    text: String,
+    limit: Option<usize>,
+    force_refresh: Option<bool>,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<query::SemanticSearchResponse, Error> {
+    let timeout = query_timeout(&state, timeout_secs).await;
+    let work = state
+        .commands
+        .run(invocation_id, timeout, async {
+            let config = state.config.lock().await.clone();
+            let result = state
+                .query_client
+                .semantic_search(&config, &text, limit, force_refresh.unwrap_or(false))
+                .await;
+            track_connectivity(&state, result)
+        });
+    tracked(&state, "semantic_search", work).await
+}
+
+/// Abort a running query, follow-up or search started with `invocation_id`.
+/// Nothing is recorded for the aborted turn, so the session stays usable
+/// for the next question. Returns false if it already finished.
+#[tauri::command]
+async fn cancel_query(state: State<'_, AppState>, invocation_id: String) -> Result<bool, Error> {
+    let cancelled = state.commands.cancel(&invocation_id);
+    if cancelled {
+        log::info!("Cancelled query invocation {}", invocation_id);
+    }
+    Ok(cancelled)
+}
+
+/// Timeout for a query command: `timeout_secs` if the caller gave one, else
+/// the configured default.
+async fn query_timeout(state: &AppState, timeout_secs: Option<u64>) -> std::time::Duration {
+    cancel::query_timeout(state.config.lock().await.query_timeout_secs, timeout_secs)
+}
+
+/// Await a command's work and note its use for telemetry.
+async fn tracked<T>(
+    state: &AppState,
+    feature: &'static str,
+    work: impl std::future::Future<Output = Result<T, Error>>,
+) -> Result<T, Error> {
+    let started = std::time::Instant::now();
+    let result = work.await;
+    let enabled = state.config.lock().await.telemetry_enabled;
+    let error_kind = result.as_ref().err().map(Error::kind);
+    record_usage(&state.telemetry, enabled, feature, started, error_kind).await;
+    result
+}
+
+/// Buffer a telemetry event, if the user opted in.
+async fn record_usage(
+    telemetry: &Mutex<Telemetry>,
+    enabled: bool,
+    feature: &'static str,
+    started: std::time::Instant,
+    error_kind: Option<&'static str>,
+) {
+    if !enabled {
+        return;
+    }
+    if let Err(e) = telemetry
+        .lock()
+        .await
+        .record(feature, started.elapsed(), error_kind)
+    {
+        log::warn!("Failed to record telemetry: {}", e);
+    }
+}
+
+/// Events emitted after `since_cursor`, so a webview that was hidden or just
+/// mounted can catch up without waiting for the next poll.
+#[tauri::command]
+async fn get_missed_events(
+    state: State<'_, AppState>,
+    since_cursor: Option<u64>,
+) -> Result<MissedEvents, Error> {
+    Ok(state.events.since(since_cursor))
+}
+
+/// Emit a state-change event to the frontend and keep it for replay.
+fn emit_replayable<S: Serialize + Clone>(app: &tauri::AppHandle, event: &str, payload: S) {
+    if let Some(state) = app.try_state::<AppState>() {
+        match serde_json::to_value(&payload) {
+            Ok(value) => state.events.push(event, value, chrono_now()),
+            Err(e) => log::warn!("Failed to buffer {} event: {}", event, e),
+        }
+        state.tray_refresh.notify_one();
     }
+    let _ = app.emit(event, payload);
+}
+
+fn visible_activity(activity: &[ActivityEntry]) -> Vec<ActivityEntry> {
+    activity
+        .iter()
+        .filter(|e| e.deleted_at.is_none())
+        .cloned()
+        .collect()
+}
+
+#[tauri::command]
+async fn scan_folder(
+    state: State<'_, AppState>,
+    handoff: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<Payload<ScanResult>, Error> {
+    let work = state
+        .commands
+        .run(invocation_id, SCAN_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+
+            let folder = config
+                .watched_folder
+                .ok_or_else(|| Error::Validation("No watched folder configured".to_string()))?;
+
+            if !folder.exists() {
+                return Err(Error::Validation(format!("Folder does not exist: {:?}", folder)));
+            }
+
+            let folder_name = folder.to_string_lossy().to_string();
+            let scanner_config = config.scanner.clone();
+            let mut result = tokio::task::spawn_blocking(move || scanner::scan_and_classify(&folder, &scanner_config))
+                .await
+                .map_err(|e| format!("Scan task failed: {}", e))??;
+            state.feedback.lock().await.apply_to_scan(&mut result);
+
+            // A scan is still useful if its trend point can't be saved
+            let snapshot = ScanSnapshot::from_result(folder_name, &result);
+            if let Err(e) = ScanHistory::load().and_then(|mut history| history.record(snapshot)) {
+                log::warn!("Failed to record scan history: {}", e);
+            }
 
-    let result = tokio::task::spawn_blocking(move || scanner::scan_and_classify(&folder))
+            *state.scan_result.lock().await = Some(result.clone());
+
+            Ok(state.payloads.wrap(result, handoff.unwrap_or(false))?)
+        });
+    tracked(&state, "scan", work).await
+}
+
+/// Per-category counts and total size across past scans of the watched
+/// folder (or `folder`, if given), with sudden jumps flagged.
+#[tauri::command]
+async fn get_scan_trends(
+    state: State<'_, AppState>,
+    folder: Option<String>,
+    invocation_id: Option<String>,
+) -> Result<ScanTrends, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let folder = match folder {
+                Some(folder) => Some(folder),
+                None => state
+                    .config
+                    .lock()
+                    .await
+                    .watched_folder
+                    .as_ref()
+                    .map(|f| f.to_string_lossy().to_string()),
+            };
+            Ok(ScanHistory::load()?.trends(folder.as_deref()))
+        })
         .await
-        .map_err(|e| format!("Scan task failed: {}", e))??;
+}
+
+/// Classify a single file and explain the recommendation, including converter
+/// guidance for recognized-but-unsupported types.
+#[tauri::command]
+async fn explain_file(
+    state: State<'_, AppState>,
+    path: String,
+    invocation_id: Option<String>,
+) -> Result<FileRecommendation, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let file_path = std::path::PathBuf::from(&path);
+            if !file_path.exists() {
+                return Err(Error::Validation(format!("File does not exist: {}", path)));
+            }
 
-    *state.scan_result.lock().await = Some(result.clone());
+            let config = state.config.lock().await.clone();
+            let root = config
+                .watched_folder
+                .clone()
+                .filter(|folder| file_path.starts_with(folder))
+                .or_else(|| file_path.parent().map(|p| p.to_path_buf()))
+                .unwrap_or_default();
+
+            let mut recommendation = classify_single_file(&root, &file_path, &config.scanner);
+            state.feedback.lock().await.adjust(&mut recommendation);
+            Ok(recommendation)
+        })
+        .await
+}
 
-    Ok(result)
+/// Forget the classification adjustments learned from past approvals.
+#[tauri::command]
+async fn reset_classification_feedback(state: State<'_, AppState>) -> Result<(), Error> {
+    Ok(state.feedback.lock().await.reset()?)
 }
 
 #[tauri::command]
@@ -141,15 +1028,28 @@ async fn approve_and_ingest(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     approved_paths: Vec<String>,
-) -> Result<(), String> {
-    let config = state.config.lock().await.clone();
+    declined_paths: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    dry_run: Option<bool>,
+    as_archive: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<(), Error> {
+    // Registered for as long as the uploads run, so they can be cancelled
+    let invocation = state.commands.register(invocation_id);
+    let mut config = state.config.lock().await.clone();
+    if let Some(dry_run) = dry_run {
+        config.dry_run = dry_run;
+    }
 
     if !config.is_configured() {
-        return Err("App not configured. Set API URL, API key, and watched folder.".to_string());
+        return Err(Error::Validation(
+            "App not configured. Set API URL, API key, and watched folder.".to_string(),
+        ));
     }
 
     let scan_result = state.scan_result.lock().await.clone();
-    let scan = scan_result.ok_or_else(|| "No scan result available. Run scan first.".to_string())?;
+    let scan = scan_result
+        .ok_or_else(|| Error::Validation("No scan result available. Run scan first.".to_string()))?;
 
     // Build list of files to ingest from approved paths
     let files_to_ingest: Vec<_> = scan
@@ -161,224 +1061,1921 @@ async fn approve_and_ingest(
         .collect();
 
     if files_to_ingest.is_empty() {
-        return Err("No files selected for ingestion.".to_string());
+        return Err(Error::Validation("No files selected for ingestion.".to_string()));
     }
 
-    // Initialize progress tracking
-    {
-        let mut progress = state.ingestion_progress.lock().await;
-        *progress = files_to_ingest
-            .iter()
-            .map(|f| FileProgress {
-                filename: f.path.clone(),
-                progress_id: None,
-                status: "pending".to_string(),
-                percent: 0.0,
-                message: None,
-            })
-            .collect();
+    // Approving a skipped file or declining a recommended one is a
+    // correction the scanner learns from. Files merely left unselected
+    // weren't decided on, so they don't count against their kind.
+    let declined_paths = declined_paths.unwrap_or_default();
+    let decisions = scan
+        .recommended_files
+        .iter()
+        .chain(scan.skipped_files.iter())
+        .filter_map(|f| {
+            if approved_paths.contains(&f.path) {
+                Some((f, true))
+            } else if declined_paths.contains(&f.path) {
+                Some((f, false))
+            } else {
+                None
+            }
+        });
+    if let Err(e) = state.feedback.lock().await.record(decisions) {
+        log::warn!("Failed to record classification feedback: {}", e);
     }
 
-    // Spawn ingestion tasks
-    let activity_log = state.activity_log.clone();
+    if as_archive.unwrap_or(false) && files_to_ingest.len() > 1 {
+        return ingest_as_archive(
+            app,
+            &state,
+            invocation,
+            config,
+            files_to_ingest,
+            tags.unwrap_or_default(),
+        )
+        .await;
+    }
+
+    let tags = tags.unwrap_or_default();
+    spawn_ingestion(app, &state, invocation, config, files_to_ingest, tags, UploadPriority::Manual).await;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SyncNowSummary {
+    /// Files the scan looked at
+    scanned: usize,
+    /// Recommended files already uploaded as they are now
+    unchanged: usize,
+    /// New or changed files sent for upload
+    queued: usize,
+}
+
+/// Rescan the watched folder and upload every recommended file that is new
+/// or has changed since it was last uploaded, catching up on changes the
+/// watcher missed or made while the app wasn't running. The uploads report
+/// through `ingestion-progress` and `ingestion-complete` like approved files.
+#[tauri::command]
+async fn sync_now(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    invocation_id: Option<String>,
+) -> Result<SyncNowSummary, Error> {
+    let invocation = state.commands.register(invocation_id);
+    let config = state.config.lock().await.clone();
+    if !config.is_configured() {
+        return Err(Error::Validation(
+            "App not configured. Set API URL, API key, and watched folder.".to_string(),
+        ));
+    }
+
+    let (scanned, recommended, changed) = invocation
+        .run(SCAN_TIMEOUT, async {
+            let folder = config
+                .watched_folder
+                .clone()
+                .ok_or_else(|| Error::Validation("No watched folder configured".to_string()))?;
+            if !folder.exists() {
+                return Err(Error::Validation(format!("Folder does not exist: {:?}", folder)));
+            }
+
+            let scanner_config = config.scanner.clone();
+            let mut scan = tokio::task::spawn_blocking(move || scanner::scan_and_classify(&folder, &scanner_config))
+                .await
+                .map_err(|e| format!("Scan task failed: {}", e))??;
+            state.feedback.lock().await.apply_to_scan(&mut scan);
+
+            // Hashing can take a while; uploads finishing meanwhile still
+            // need to record to the ledger
+            let uploaded = state.ledger.lock().await.uploaded();
+            let (scanned, files) = (scan.total_files, scan.recommended_files);
+            let recommended = files.len();
+            let changed: Vec<FileRecommendation> = tokio::task::spawn_blocking(move || {
+                files
+                    .into_iter()
+                    .filter(|f| headless::needs_upload(&uploaded, &f.absolute_path))
+                    .collect()
+            })
+            .await
+            .map_err(|e| format!("Sync check failed: {}", e))?;
+            Ok((scanned, recommended, changed))
+        })
+        .await?;
+
+    let summary = SyncNowSummary {
+        scanned,
+        unchanged: recommended - changed.len(),
+        queued: changed.len(),
+    };
+    log::info!(
+        "Sync now: {} scanned, {} unchanged, {} to upload",
+        summary.scanned,
+        summary.unchanged,
+        summary.queued
+    );
+    if !changed.is_empty() {
+        // A re-sync of everything missed yields to files the user picks meanwhile
+        spawn_ingestion(app, &state, invocation, config, changed, Vec::new(), UploadPriority::Backfill).await;
+    }
+    Ok(summary)
+}
+
+/// Classify and upload files from anywhere on disk, e.g. dropped on the
+/// window, without touching the watched folder. They go through the same
+/// pipeline as approved files, whatever the scanner recommends, since the
+/// user picked them; the classifications are returned for display.
+#[tauri::command]
+async fn ingest_files(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    paths: Vec<String>,
+    tags: Option<Vec<String>>,
+    invocation_id: Option<String>,
+) -> Result<Vec<FileRecommendation>, Error> {
+    let paths = paths.into_iter().map(std::path::PathBuf::from).collect();
+    ingest_paths(app, &state, paths, tags.unwrap_or_default(), invocation_id).await
+}
+
+/// Let the user pick files in a dialog and ingest them like `ingest_files`.
+/// Returns nothing if the dialog was dismissed.
+#[tauri::command]
+async fn pick_and_ingest(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    tags: Option<Vec<String>>,
+    invocation_id: Option<String>,
+) -> Result<Vec<FileRecommendation>, Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    let app_clone = app.clone();
+    let picked = tokio::task::spawn_blocking(move || app_clone.dialog().file().blocking_pick_files())
+        .await
+        .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))?;
+    let paths: Vec<_> = picked
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|f| f.into_path().ok())
+        .collect();
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    ingest_paths(app, &state, paths, tags.unwrap_or_default(), invocation_id).await
+}
+
+/// Save text that has no file, like a quick note or pasted clipboard
+/// contents, as a document and ingest it like `ingest_files`. The document
+/// is named from `title`, or from the text's first line without one.
+#[tauri::command]
+async fn ingest_text(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    title: Option<String>,
+    content: String,
+    from_clipboard: Option<bool>,
+    tags: Option<Vec<String>>,
+    invocation_id: Option<String>,
+) -> Result<Vec<FileRecommendation>, Error> {
+    let naming = {
+        let config = state.config.lock().await;
+        // Checked before saving, so a note isn't left behind unsent
+        require_credentials(&config)?;
+        config.capture_naming.clone()
+    };
+    let kind = if from_clipboard.unwrap_or(false) { "clipboard" } else { "note" };
+    let path = capture::save_text(
+        &capture::captures_dir()?,
+        &naming,
+        kind,
+        title.as_deref(),
+        &content,
+        ledger::now_secs(),
+    )?;
+    log::info!("Saved {} as {}", kind, path.display());
+    ingest_paths(app, &state, vec![path], tags.unwrap_or_default(), invocation_id).await
+}
+
+async fn ingest_paths(
+    app: tauri::AppHandle,
+    state: &AppState,
+    paths: Vec<std::path::PathBuf>,
+    tags: Vec<String>,
+    invocation_id: Option<String>,
+) -> Result<Vec<FileRecommendation>, Error> {
+    let invocation = state.commands.register(invocation_id);
+    let config = state.config.lock().await.clone();
+    require_credentials(&config)?;
+    if paths.is_empty() {
+        return Err(Error::Validation("No files selected for ingestion.".to_string()));
+    }
+    let not_files: Vec<String> = paths
+        .iter()
+        .filter(|p| !p.is_file())
+        .map(|p| p.display().to_string())
+        .collect();
+    if !not_files.is_empty() {
+        return Err(Error::Validation(format!("Not a file: {}", not_files.join(", "))));
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    {
+        let feedback = state.feedback.lock().await;
+        for path in paths {
+            let root = path.parent().map(|p| p.to_path_buf()).unwrap_or_default();
+            let mut recommendation = classify_single_file(&root, &path, &config.scanner);
+            feedback.adjust(&mut recommendation);
+            // Files from different folders may share a name, so they're
+            // tracked by full path
+            recommendation.path = path.display().to_string();
+            files.push(recommendation);
+        }
+    }
+
+    spawn_ingestion(app, state, invocation, config, files.clone(), tags, UploadPriority::Manual).await;
+    Ok(files)
+}
+
+/// Uploads outside the watched folder only need somewhere to send them.
+fn require_credentials(config: &AppConfig) -> Result<(), Error> {
+    if config.api_url().is_empty() || config.api_key.is_empty() {
+        return Err(Error::Validation(
+            "App not configured. Set API URL and API key.".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Track `files` in `ingestion_progress` in place of whatever was there and
+/// upload them in the background at `priority`, sending `ingestion-complete`
+/// when they've all finished or `invocation` is cancelled.
+async fn spawn_ingestion(
+    app: tauri::AppHandle,
+    state: &AppState,
+    invocation: Invocation,
+    config: AppConfig,
+    files: Vec<FileRecommendation>,
+    tags: Vec<String>,
+    priority: UploadPriority,
+) {
+    // Initialize progress tracking
+    {
+        let mut progress = state.ingestion_progress.lock().await;
+        *progress = files
+            .iter()
+            .map(|f| FileProgress {
+                filename: f.path.clone(),
+                progress_id: None,
+                status: "pending".to_string(),
+                percent: 0.0,
+                message: None,
+                scheduled_at: None,
+            })
+            .collect();
+    }
+
+    // Spawn ingestion tasks; they share the app-wide uploader so manual
+    // approvals jump ahead of any watcher or backfill backlog
     let ingestion_progress = state.ingestion_progress.clone();
-    let app_handle = app.clone();
 
     tokio::spawn(async move {
         let mut handles = Vec::new();
 
-        for file_rec in files_to_ingest {
+        for file_rec in files {
             let file_path = file_rec.absolute_path.clone();
             let file_name = file_rec.path.clone();
-            let cfg = config.clone();
-            let act_log = activity_log.clone();
-            let ing_prog = ingestion_progress.clone();
-            let app_h = app_handle.clone();
+            let context = FileContext::from_recommendation(&file_rec, tags.clone());
+            let handle = tokio::spawn(ingest_tracked(
+                app.clone(),
+                file_path,
+                file_name,
+                context,
+                config.clone(),
+                priority,
+            ));
+            handles.push(handle);
+        }
 
-            let handle = tokio::spawn(async move {
-                let uploader = Uploader::new();
+        // Wait for all uploads to complete, or abort the rest if cancelled
+        let finished = invocation
+            .until_cancelled(async {
+                for handle in handles.iter_mut() {
+                    let _ = handle.await;
+                }
+            })
+            .await;
+        if finished.is_none() {
+            log::info!("Ingestion cancelled");
+            handles.iter().for_each(|handle| handle.abort());
+            cancel_progress(&ingestion_progress).await;
+            emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ingestion_progress).await);
+        }
 
-                // Update progress to uploading
-                update_file_progress(&ing_prog, &file_name, "uploading", 10.0, None).await;
-                let _ = app_h.emit("ingestion-progress", get_progress_snapshot(&ing_prog).await);
+        emit_replayable(&app, "ingestion-complete", true);
+    });
+}
 
-                let result = uploader.upload_and_ingest(&file_path, &cfg).await;
+/// Upload one file through the normal pipeline at `priority`, tracking it in
+/// `ingestion_progress` under `file_name` and recording the outcome in the
+/// activity log, ledger and failed-upload list.
+async fn ingest_tracked(
+    app: tauri::AppHandle,
+    file_path: std::path::PathBuf,
+    file_name: String,
+    context: FileContext,
+    cfg: AppConfig,
+    priority: UploadPriority,
+) {
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let ing_prog = &state.ingestion_progress;
+    let act_log = &state.activity_log;
+    let ledger = &state.ledger;
+    let dead_letters = &state.dead_letters;
+    let uploader = &state.uploader;
+    let telemetry = &state.telemetry;
+
+    let started = std::time::Instant::now();
+    // Update progress to uploading
+    update_file_progress(ing_prog, &file_name, "uploading", 10.0, None).await;
+    emit_replayable(&app, "ingestion-progress", get_progress_snapshot(ing_prog).await);
+
+    let on_progress = transfer_progress(&app, ing_prog, &file_name);
+    let result = uploader
+        .upload_and_ingest_with_progress(
+            &file_path,
+            &cfg,
+            priority,
+            context.clone(),
+            Some(on_progress),
+        )
+        .await;
+    record_dead_letter(dead_letters, &file_path, &result, &context).await;
+
+    // Update progress based on result
+    match &result.status {
+        UploadStatus::Ingesting => {
+            update_file_progress(ing_prog, &file_name, "ingesting", 50.0, result.progress_id.clone()).await;
+
+            // Poll for completion
+            let final_status = match &result.progress_id {
+                Some(pid) => poll_until_done(uploader, &cfg, pid, ing_prog, &file_name, &app).await,
+                None => None,
+            };
+            notify_hooks(&cfg, &file_path, &result, final_status.clone()).await;
+            apply_post_ingest_action(act_log, &cfg, &file_path, &result, final_status).await;
+        }
+        UploadStatus::Uploaded => {
+            update_file_progress(ing_prog, &file_name, "uploaded", 100.0, None).await;
+            notify_hooks(&cfg, &file_path, &result, None).await;
+        }
+        UploadStatus::DryRun => {
+            update_file_progress(ing_prog, &file_name, "dry_run", 100.0, None).await;
+        }
+        UploadStatus::Error => {
+            update_file_progress(ing_prog, &file_name, "error", 0.0, None).await;
+            notify_hooks(&cfg, &file_path, &result, None).await;
+        }
+        _ => {}
+    }
+
+    log_activity_with_category(act_log, ledger, &result, context.category.clone()).await;
+    let error_kind = matches!(result.status, UploadStatus::Error).then_some("upload");
+    record_usage(telemetry, cfg.telemetry_enabled, "manual_upload", started, error_kind).await;
+    emit_replayable(&app, "sync-activity", &result);
+    emit_replayable(&app, "ingestion-progress", get_progress_snapshot(ing_prog).await);
+}
+
+/// Bundle the approved files into one zip, upload it once, and ask the server
+/// to expand it. Progress is tracked for the archive as a single entry.
+async fn ingest_as_archive(
+    app: tauri::AppHandle,
+    state: &AppState,
+    invocation: Invocation,
+    config: AppConfig,
+    files: Vec<FileRecommendation>,
+    tags: Vec<String>,
+) -> Result<(), Error> {
+    let archive_name = format!("batch of {} files", files.len());
+    *state.ingestion_progress.lock().await = vec![FileProgress {
+        filename: archive_name.clone(),
+        progress_id: None,
+        status: "archiving".to_string(),
+        percent: 0.0,
+        message: None,
+        scheduled_at: None,
+    }];
+
+    let activity_log = state.activity_log.clone();
+    let ledger = state.ledger.clone();
+    let ing_prog = state.ingestion_progress.clone();
+    let uploader = state.uploader.clone();
+
+    tokio::spawn(async move {
+        let finished = invocation
+            .until_cancelled(async {
+                emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ing_prog).await);
+
+                let entries: Vec<_> = files
+                    .iter()
+                    .map(|f| (f.absolute_path.clone(), f.path.clone()))
+                    .collect();
+                let archive = match tokio::task::spawn_blocking(move || archive::build_archive(&entries))
+                    .await
+                    .map_err(|e| format!("Archive task failed: {}", e))
+                    .and_then(|r| r)
+                {
+                    Ok(archive) => archive,
+                    Err(e) => {
+                        log::error!("Failed to build batch archive: {}", e);
+                        {
+                            let mut prog = ing_prog.lock().await;
+                            if let Some(entry) = prog.first_mut() {
+                                entry.status = "error".to_string();
+                                entry.message = Some(e);
+                            }
+                        }
+                        emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ing_prog).await);
+                        emit_replayable(&app, "ingestion-complete", true);
+                        return;
+                    }
+                };
+
+                update_file_progress(&ing_prog, &archive_name, "uploading", 10.0, None).await;
+                emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ing_prog).await);
+
+                let context = FileContext {
+                    category: None,
+                    reason: Some(format!("Batch archive of {} files", archive.file_count)),
+                    tags,
+                    expand_archive: true,
+                };
+                let on_progress = transfer_progress(&app, &ing_prog, &archive_name);
+                let result = uploader
+                    .upload_and_ingest_with_progress(
+                        &archive.path,
+                        &config,
+                        UploadPriority::Manual,
+                        context,
+                        Some(on_progress),
+                    )
+                    .await;
 
-                // Update progress based on result
                 match &result.status {
                     UploadStatus::Ingesting => {
                         update_file_progress(
                             &ing_prog,
-                            &file_name,
+                            &archive_name,
                             "ingesting",
                             50.0,
                             result.progress_id.clone(),
                         )
                         .await;
-
-                        // Poll for completion
                         if let Some(pid) = &result.progress_id {
-                            poll_until_done(&uploader, &cfg, pid, &ing_prog, &file_name, &app_h)
-                                .await;
+                            // Hooks are per file, and the archive's files are only
+                            // known to the server, so none run for batch uploads
+                            poll_until_done(&uploader, &config, pid, &ing_prog, &archive_name, &app).await;
                         }
                     }
                     UploadStatus::Uploaded => {
-                        update_file_progress(&ing_prog, &file_name, "uploaded", 100.0, None).await;
+                        update_file_progress(&ing_prog, &archive_name, "uploaded", 100.0, None).await;
+                    }
+                    UploadStatus::DryRun => {
+                        update_file_progress(&ing_prog, &archive_name, "dry_run", 100.0, None).await;
                     }
                     UploadStatus::Error => {
-                        update_file_progress(
-                            &ing_prog,
-                            &file_name,
-                            "error",
-                            0.0,
-                            None,
-                        )
-                        .await;
+                        update_file_progress(&ing_prog, &archive_name, "error", 0.0, None).await;
                     }
                     _ => {}
                 }
 
-                log_activity(&act_log, &result).await;
-                let _ = app_h.emit("sync-activity", &result);
-                let _ = app_h.emit("ingestion-progress", get_progress_snapshot(&ing_prog).await);
-            });
+                log_activity(&activity_log, &ledger, &result).await;
+                emit_replayable(&app, "sync-activity", &result);
+                emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ing_prog).await);
+                emit_replayable(&app, "ingestion-complete", true);
+            })
+            .await;
+        if finished.is_none() {
+            log::info!("Batch ingestion cancelled");
+            cancel_progress(&ing_prog).await;
+            emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&ing_prog).await);
+            emit_replayable(&app, "ingestion-complete", true);
+        }
+    });
+
+    Ok(())
+}
+
+/// Mark every entry that hasn't reached a final state as cancelled. Uploads
+/// already handed to the server keep ingesting there; only local work stops.
+async fn cancel_progress(progress: &Arc<Mutex<Vec<FileProgress>>>) {
+    let mut prog = progress.lock().await;
+    for entry in prog
+        .iter_mut()
+        .filter(|p| !is_terminal_status(&p.status) && p.status != "uploaded" && p.status != "dry_run")
+    {
+        entry.status = "cancelled".to_string();
+        entry.message = None;
+    }
+}
+
+async fn update_file_progress(
+    progress: &Arc<Mutex<Vec<FileProgress>>>,
+    filename: &str,
+    status: &str,
+    percent: f64,
+    progress_id: Option<String>,
+) {
+    let mut prog = progress.lock().await;
+    if let Some(entry) = prog.iter_mut().find(|p| p.filename == filename) {
+        entry.status = status.to_string();
+        entry.percent = percent;
+        if let Some(pid) = progress_id {
+            entry.progress_id = Some(pid);
+        }
+    }
+}
+
+/// Progress callback for the S3 PUT: maps bytes sent onto the 10-50%
+/// "uploading" band of the file's entry and emits throttled progress events.
+fn transfer_progress(
+    app: &tauri::AppHandle,
+    progress: &Arc<Mutex<Vec<FileProgress>>>,
+    filename: &str,
+) -> UploadProgressFn {
+    let app = app.clone();
+    let progress = progress.clone();
+    let filename = filename.to_string();
+    let last_emit = std::sync::Mutex::new(None::<std::time::Instant>);
+
+    Arc::new(move |sent, total| {
+        {
+            let mut last = last_emit.lock().unwrap();
+            let recent = last.is_some_and(|at| at.elapsed() < TRANSFER_PROGRESS_INTERVAL);
+            if recent && sent < total {
+                return;
+            }
+            *last = Some(std::time::Instant::now());
+        }
+
+        let percent = 10.0 + 40.0 * sent as f64 / total.max(1) as f64;
+        let (app, progress, filename) = (app.clone(), progress.clone(), filename.clone());
+        tokio::spawn(async move {
+            {
+                let mut prog = progress.lock().await;
+                // Updates can land after the upload finished; never move an
+                // entry backwards or out of a later state
+                match prog.iter_mut().find(|p| p.filename == filename) {
+                    Some(entry) if entry.status == "uploading" && entry.percent < percent => {
+                        entry.percent = percent;
+                    }
+                    _ => return,
+                }
+            }
+            emit_replayable(&app, "ingestion-progress", get_progress_snapshot(&progress).await);
+        });
+    })
+}
+
+async fn get_progress_snapshot(progress: &Arc<Mutex<Vec<FileProgress>>>) -> Vec<FileProgress> {
+    progress.lock().await.clone()
+}
+
+async fn poll_until_done(
+    uploader: &Uploader,
+    config: &AppConfig,
+    progress_id: &str,
+    progress: &Arc<Mutex<Vec<FileProgress>>>,
+    filename: &str,
+    app: &tauri::AppHandle,
+) -> Option<String> {
+    let started = std::time::Instant::now();
+    for _ in 0..MAX_PROGRESS_POLLS {
+        tokio::time::sleep(PROGRESS_POLL_INTERVAL).await;
+
+        match uploader.poll_progress(config, progress_id).await {
+            Ok(resp) => {
+                simulate::record_trace_point(filename, started.elapsed(), &resp);
+                let percent = resp.percent.unwrap_or(50.0);
+                let status = resp.status.as_str();
+
+                {
+                    let mut prog = progress.lock().await;
+                    if let Some(entry) = prog.iter_mut().find(|p| p.filename == filename) {
+                        entry.status = status.to_string();
+                        entry.percent = percent;
+                        entry.message = resp.message.clone();
+                    }
+                }
+
+                emit_replayable(app, "ingestion-progress", get_progress_snapshot(progress).await);
+
+                if is_terminal_status(status) {
+                    if is_success_status(status) {
+                        update_file_progress(progress, filename, "done", 100.0, None).await;
+                    }
+                    return Some(status.to_string());
+                }
+            }
+            Err(RequestError::ServerBusy { retry_after, .. }) => {
+                // Surface backpressure instead of counting it as a failed poll
+                {
+                    let mut prog = progress.lock().await;
+                    if let Some(entry) = prog.iter_mut().find(|p| p.filename == filename) {
+                        entry.status = "server_busy".to_string();
+                        entry.message = Some(format!(
+                            "Server busy, retrying in {}s",
+                            retry_after.as_secs()
+                        ));
+                    }
+                }
+                emit_replayable(app, "ingestion-progress", get_progress_snapshot(progress).await);
+                tokio::time::sleep(retry_after).await;
+            }
+            Err(e) => {
+                log::warn!("Progress poll error for {}: {}", filename, e);
+                // Don't break on poll errors, just keep trying
+            }
+        }
+    }
+    None
+}
+
+/// Poll an ingestion without progress reporting, returning its final status
+/// (None if it didn't finish in time).
+async fn wait_for_ingestion(uploader: &Uploader, config: &AppConfig, progress_id: &str) -> Option<String> {
+    for _ in 0..MAX_PROGRESS_POLLS {
+        tokio::time::sleep(PROGRESS_POLL_INTERVAL).await;
+        match uploader.poll_progress(config, progress_id).await {
+            Ok(resp) if is_terminal_status(&resp.status) => return Some(resp.status),
+            Ok(_) => {}
+            Err(RequestError::ServerBusy { retry_after, .. }) => {
+                tokio::time::sleep(retry_after).await;
+            }
+            Err(e) => log::warn!("Progress poll error for {}: {}", progress_id, e),
+        }
+    }
+    None
+}
+
+/// Run the user's ingestion hooks for a finished upload. `final_status` is
+/// the last progress status seen for uploads that went on to ingestion.
+async fn notify_hooks(
+    config: &AppConfig,
+    path: &std::path::Path,
+    result: &UploadResult,
+    final_status: Option<String>,
+) {
+    if config.hooks.is_empty() {
+        return;
+    }
+    let status = match result.status {
+        UploadStatus::DryRun => return,
+        UploadStatus::Error => "error".to_string(),
+        UploadStatus::Uploaded => "uploaded".to_string(),
+        _ => match final_status {
+            Some(status) if is_success_status(&status) => "done".to_string(),
+            Some(_) => "failed".to_string(),
+            None => "timed_out".to_string(),
+        },
+    };
+    let event = IngestionEvent {
+        filename: result.filename.clone(),
+        path: path.to_path_buf(),
+        status,
+        s3_key: result.s3_key.clone(),
+        progress_id: result.progress_id.clone(),
+    };
+    hooks::run_hooks(config, &event).await;
+}
+
+/// Apply the configured post-ingest action to the local file once the server
+/// has confirmed ingestion, and record the outcome in the activity log.
+async fn apply_post_ingest_action(
+    activity_log: &Arc<Mutex<Vec<ActivityEntry>>>,
+    config: &AppConfig,
+    path: &std::path::Path,
+    result: &UploadResult,
+    final_status: Option<String>,
+) {
+    if config.post_ingest_action == PostIngestAction::Leave {
+        return;
+    }
+    // Only act on confirmed ingestion, never on plain uploads, failures or timeouts
+    let confirmed = result.status == UploadStatus::Ingesting
+        && final_status.as_deref().is_some_and(is_success_status);
+    if !confirmed {
+        log::info!("Leaving {:?} in place: ingestion not confirmed ({:?})", path, final_status);
+        return;
+    }
+    let Some(root) = &config.watched_folder else {
+        log::warn!("Leaving {:?} in place: no watched folder configured", path);
+        return;
+    };
+
+    let Some(metadata) = &result.metadata else {
+        log::warn!("Leaving {:?} in place: no hash of the uploaded content", path);
+        return;
+    };
+
+    let action = config.post_ingest_action;
+    let (status, error, note) = match file_actions::apply(action, root, path, &metadata.sha256) {
+        Ok(None) => return,
+        Ok(Some(note)) => {
+            log::info!("Post-ingest action for {:?}: {}", path, note);
+            (UploadStatus::Done, None, Some(note))
+        }
+        Err(e) => {
+            log::warn!("Post-ingest action for {:?} failed: {}", path, e);
+            (UploadStatus::Error, Some(e), None)
+        }
+    };
+    let entry = ActivityEntry {
+        id: Uuid::new_v4().to_string(),
+        filename: result.filename.clone(),
+        status,
+        error,
+        timestamp: chrono_now(),
+        category: None,
+        suggestion: None,
+        deleted_at: None,
+        note,
+    };
+    let mut activity = activity_log.lock().await;
+    activity.insert(0, entry);
+    activity.truncate(MAX_ACTIVITY_LOG);
+}
+
+/// For uploads nobody is polling (watcher, scheduled): wait for ingestion to
+/// finish in the background, then run the hooks and the post-ingest action.
+fn spawn_after_ingestion(
+    uploader: Arc<Uploader>,
+    activity_log: Arc<Mutex<Vec<ActivityEntry>>>,
+    config: AppConfig,
+    path: std::path::PathBuf,
+    result: UploadResult,
+) {
+    let nothing_to_do =
+        config.hooks.is_empty() && config.post_ingest_action == PostIngestAction::Leave;
+    if nothing_to_do || result.status == UploadStatus::DryRun {
+        return;
+    }
+    tokio::spawn(async move {
+        let final_status = match (&result.status, &result.progress_id) {
+            (UploadStatus::Ingesting, Some(pid)) => wait_for_ingestion(&uploader, &config, pid).await,
+            _ => None,
+        };
+        // Hooks first, so a hook command can still read the file
+        notify_hooks(&config, &path, &result, final_status.clone()).await;
+        apply_post_ingest_action(&activity_log, &config, &path, &result, final_status).await;
+    });
+}
+
+/// Upload volume and throughput per day, for the last `days` days (default 30).
+#[tauri::command]
+async fn get_upload_stats(
+    state: State<'_, AppState>,
+    days: Option<usize>,
+) -> Result<stats::UploadStatsReport, Error> {
+    Ok(state.uploader.stats(days.unwrap_or(30)))
+}
+
+#[tauri::command]
+async fn get_ingestion_progress(
+    state: State<'_, AppState>,
+) -> Result<Vec<FileProgress>, Error> {
+    let mut progress = state.ingestion_progress.lock().await.clone();
+    progress.extend(scheduled_progress(&state.scheduled.lock().await.list()));
+    Ok(progress)
+}
+
+fn scheduled_progress(entries: &[ScheduledUpload]) -> Vec<FileProgress> {
+    entries
+        .iter()
+        .map(|e| FileProgress {
+            filename: e.filename.clone(),
+            progress_id: None,
+            status: "scheduled".to_string(),
+            percent: 0.0,
+            message: Some("Waiting for quiet hours".to_string()),
+            scheduled_at: Some(e.scheduled_at),
+        })
+        .collect()
+}
+
+/// Hold a watcher upload until quiet hours and show it in the progress UI.
+async fn schedule_upload(
+    app: &tauri::AppHandle,
+    scheduled: &Arc<Mutex<ScheduledUploads>>,
+    file_path: &std::path::Path,
+    context: FileContext,
+    at: u64,
+) {
+    log::info!("Deferring upload of {:?} until {}", file_path, at);
+    let mut queue = scheduled.lock().await;
+    if let Err(e) = queue.add(file_path, context, at) {
+        log::warn!("Failed to save scheduled uploads: {}", e);
+    }
+    let progress = scheduled_progress(&queue.list());
+    drop(queue);
+    emit_replayable(app, "scheduled-uploads", progress);
+}
+
+/// Background loop that releases deferred watcher uploads once the
+/// quiet-hours window opens (or scheduling is turned off).
+async fn run_scheduled_uploads(app: tauri::AppHandle) {
+    loop {
+        tokio::time::sleep(SCHEDULE_CHECK_INTERVAL).await;
+        if let Some(state) = app.try_state::<AppState>() {
+            release_scheduled_uploads(&app, &state).await;
+        }
+    }
+}
+
+/// Hand deferred uploads to the upload queue at backfill priority, one at a
+/// time as slots free up, until none are left, watching stops or the window
+/// closes. Whatever isn't released stays scheduled for the next window.
+async fn release_scheduled_uploads(app: &tauri::AppHandle, state: &AppState) {
+    let mut released = 0;
+    loop {
+        if !*state.watching.lock().await {
+            break;
+        }
+        // Re-read each time, so a schedule change takes effect mid-batch
+        let config = state.config.lock().await.clone();
+        if config.upload_schedule.defer_until(ledger::now_secs()).is_some() {
+            break;
+        }
+        // Waiters mean every slot is busy; queueing more would only move
+        // the stop checks above further from the actual uploads
+        if state.uploader.queued() > 0 {
+            tokio::time::sleep(SCHEDULE_RELEASE_INTERVAL).await;
+            continue;
+        }
+
+        let (entry, remaining) = {
+            let mut queue = state.scheduled.lock().await;
+            match queue.take_next() {
+                Ok(Some(entry)) => (entry, scheduled_progress(&queue.list())),
+                Ok(None) => break,
+                Err(e) => {
+                    log::warn!("Failed to update scheduled uploads: {}", e);
+                    break;
+                }
+            }
+        };
+        emit_replayable(app, "scheduled-uploads", remaining);
+        if !entry.path.exists() {
+            log::info!("Deferred file no longer exists: {:?}", entry.path);
+            continue;
+        }
+
+        let pending = FileProgress {
+            filename: entry.filename.clone(),
+            progress_id: None,
+            status: "pending".to_string(),
+            percent: 0.0,
+            message: None,
+            scheduled_at: None,
+        };
+        {
+            let mut progress = state.ingestion_progress.lock().await;
+            match progress.iter_mut().find(|p| p.filename == entry.filename) {
+                Some(existing) => *existing = pending,
+                None => progress.push(pending),
+            }
+        }
+        emit_replayable(app, "ingestion-progress", get_progress_snapshot(&state.ingestion_progress).await);
+
+        tokio::spawn(ingest_tracked(
+            app.clone(),
+            entry.path,
+            entry.filename,
+            entry.context,
+            config,
+            UploadPriority::Backfill,
+        ));
+        released += 1;
+        // Give the upload time to claim its slot before checking the queue again
+        tokio::time::sleep(SCHEDULE_RELEASE_INTERVAL).await;
+    }
+    if released > 0 {
+        log::info!("Released {} deferred uploads", released);
+    }
+}
+
+#[tauri::command]
+async fn run_query(
+    state: State<'_, AppState>,
+    query: String,
+    session_id: Option<String>,
+    view: Option<ResultView>,
+    handoff: Option<bool>,
+    force_refresh: Option<bool>,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<Payload<query::RunQueryResponse>, Error> {
+    let timeout = query_timeout(&state, timeout_secs).await;
+    let work = state
+        .commands
+        .run(invocation_id, timeout, async {
+            execute_query(&state, query, session_id, view, handoff, force_refresh).await
+        });
+    tracked(&state, "query", work).await
+}
+
+/// Shared by `run_query` and `run_saved_query`.
+async fn execute_query(
+    state: &AppState,
+    query: String,
+    session_id: Option<String>,
+    view: Option<ResultView>,
+    handoff: Option<bool>,
+    force_refresh: Option<bool>,
+) -> Result<Payload<query::RunQueryResponse>, Error> {
+    let config = state.config.lock().await.clone();
+    let result = state
+        .query_client
+        .run_query(&config, &query, session_id.as_deref(), force_refresh.unwrap_or(false))
+        .await;
+    let mut response = track_connectivity(state, result)?;
+
+    record_transcript(
+        state,
+        TranscriptTurn {
+            session_id: response.session_id.clone(),
+            kind: "query".to_string(),
+            question: query,
+            answer: response.ai_interpretation.clone(),
+            context_used: false,
+            result_count: Some(response.raw_results.len()),
+            created_at: ledger::now_secs(),
+        },
+    )
+    .await;
+
+    // Pagination and projection are applied here; the full payload stays on
+    // disk instead of crossing IPC
+    let view = view.unwrap_or_default();
+    if !view.is_full() {
+        let (page, has_more, next_cursor) =
+            store_and_page(&response.session_id, &view, &response.raw_results);
+        response.raw_results = page;
+        response.has_more = has_more;
+        response.next_cursor = next_cursor;
+    }
+    Ok(state.payloads.wrap(response, handoff.unwrap_or(false))?)
+}
+
+/// Insert, update or delete records. Deletes are refused unless `confirm`
+/// is set, so the UI has to ask first. Successful mutations are added to
+/// the activity log.
+#[tauri::command]
+async fn mutate(
+    state: State<'_, AppState>,
+    schema: String,
+    operation: String,
+    data: serde_json::Value,
+    confirm: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<query::MutateResponse, Error> {
+    let parsed = query::MutationOperation::parse(&operation).map_err(Error::Validation)?;
+    if parsed == query::MutationOperation::Delete && !confirm.unwrap_or(false) {
+        return Err(Error::Validation(
+            "Deleting records needs confirmation; call again with confirm set".to_string(),
+        ));
+    }
+
+    let response = state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            let result = state
+                .query_client
+                .mutate(&config, &schema, parsed.as_str(), data)
+                .await;
+            track_connectivity(&state, result)
+        })
+        .await?;
+
+    if response.success {
+        let label = format!("{} {}", parsed.as_str(), schema.trim());
+        record_request_activity(&state, label, "mutation", Ok(response.message.clone())).await;
+    }
+    Ok(response)
+}
+
+/// Add a query or mutation outcome to the activity log.
+async fn record_request_activity(
+    state: &AppState,
+    label: String,
+    category: &str,
+    outcome: Result<Option<String>, String>,
+) {
+    let (status, error, note) = match outcome {
+        Ok(note) => (UploadStatus::Done, None, note),
+        Err(e) => (UploadStatus::Error, Some(e), None),
+    };
+    let entry = ActivityEntry {
+        id: Uuid::new_v4().to_string(),
+        filename: label,
+        status,
+        error,
+        timestamp: chrono_now(),
+        category: Some(category.to_string()),
+        suggestion: None,
+        deleted_at: None,
+        note,
+    };
+    let mut activity = state.activity_log.lock().await;
+    activity.insert(0, entry);
+    activity.truncate(MAX_ACTIVITY_LOG);
+}
+
+/// Note whether a backend call got through, for the offline queue.
+fn track_connectivity<T>(state: &AppState, result: Result<T, Error>) -> Result<T, Error> {
+    let reached = !matches!(result, Err(Error::Network(_)));
+    if state.connectivity.set_online(reached) {
+        log::info!("Backend is {}", if reached { "reachable again" } else { "unreachable" });
+    }
+    result
+}
+
+/// Queue a query or mutation to be sent once the backend is reachable. It
+/// is attempted right away if the client is online. Deletes need `confirm`,
+/// as with `mutate`.
+#[tauri::command]
+async fn queue_request(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request: QueuedRequest,
+    confirm: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<QueuedItem, Error> {
+    if request.is_delete() && !confirm.unwrap_or(false) {
+        return Err(Error::Validation(
+            "Deleting records needs confirmation; call again with confirm set".to_string(),
+        ));
+    }
+    // Only the push is cancellable; the replay below reports through events
+    let item = state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            state.offline_queue.lock().await.push(request).map_err(Error::Validation)
+        })
+        .await?;
+    emit_offline_queue(&app, &state).await;
+    if state.connectivity.is_online() {
+        tauri::async_runtime::spawn(async move {
+            if let Some(state) = app.try_state::<AppState>() {
+                replay_offline_queue(&app, &state).await;
+            }
+        });
+    }
+    Ok(item)
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OfflineQueueStatus {
+    online: bool,
+    items: Vec<QueuedItem>,
+}
+
+/// Requests waiting for the backend, oldest first, and whether it was
+/// reachable last time it was tried.
+#[tauri::command]
+async fn get_offline_queue(state: State<'_, AppState>) -> Result<OfflineQueueStatus, Error> {
+    Ok(OfflineQueueStatus {
+        online: state.connectivity.is_online(),
+        items: state.offline_queue.lock().await.list(),
+    })
+}
+
+/// Drop a queued request without sending it. Returns false if it was
+/// already sent or removed.
+#[tauri::command]
+async fn remove_queued_request(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<bool, Error> {
+    let removed = state.offline_queue.lock().await.remove(&id).map_err(Error::Io)?;
+    emit_offline_queue(&app, &state).await;
+    Ok(removed)
+}
+
+async fn emit_offline_queue(app: &tauri::AppHandle, state: &AppState) {
+    let status = OfflineQueueStatus {
+        online: state.connectivity.is_online(),
+        items: state.offline_queue.lock().await.list(),
+    };
+    emit_replayable(app, "offline-queue", status);
+}
+
+/// Send queued requests in order, stopping at the first one that can't
+/// reach the backend. Other failures are retried up to
+/// `offline_queue::MAX_ATTEMPTS` times, then logged and dropped.
+async fn replay_offline_queue(app: &tauri::AppHandle, state: &AppState) {
+    let items = state.offline_queue.lock().await.list();
+    if items.is_empty() {
+        return;
+    }
+    let config = state.config.lock().await.clone();
+    log::info!("Replaying {} queued requests", items.len());
+
+    for item in items {
+        let label = item.request.describe();
+        let (category, result) = match &item.request {
+            QueuedRequest::Query { query } => {
+                // Results are stored for `get_full_results`; only the answer
+                // goes into the transcript
+                let view = ResultView {
+                    limit: Some(0),
+                    ..Default::default()
+                };
+                let result = execute_query(state, query.clone(), None, Some(view), None, None)
+                    .await
+                    .map(|_| None);
+                ("queued_query", result)
+            }
+            QueuedRequest::Mutation {
+                schema,
+                operation,
+                data,
+            } => {
+                // The item id goes with every replay, so a mutation the
+                // server applied before the connection dropped isn't applied twice
+                let result = state
+                    .query_client
+                    .mutate_once(&config, schema, operation, data.clone(), &item.id)
+                    .await;
+                let result = track_connectivity(state, result).and_then(|response| {
+                    if response.success {
+                        Ok(response.message)
+                    } else {
+                        Err(Error::Server {
+                            status: None,
+                            message: response
+                                .message
+                                .unwrap_or_else(|| "Mutation failed".to_string()),
+                        })
+                    }
+                });
+                ("mutation", result)
+            }
+        };
+
+        let mut queue = state.offline_queue.lock().await;
+        match result {
+            Ok(note) => {
+                if let Err(e) = queue.remove(&item.id) {
+                    log::warn!("Failed to update offline queue: {}", e);
+                }
+                drop(queue);
+                record_request_activity(state, label, category, Ok(note)).await;
+            }
+            Err(e @ (Error::Network(_) | Error::Timeout(_))) => {
+                log::info!("Backend unreachable, keeping queued requests: {}", e);
+                break;
+            }
+            Err(e) => match queue.record_failure(&item.id, &e.to_string()) {
+                Ok(true) => {
+                    drop(queue);
+                    log::warn!("Giving up on queued request {}: {}", item.id, e);
+                    record_request_activity(state, label, category, Err(e.to_string())).await;
+                }
+                Ok(false) => {}
+                Err(save_err) => log::warn!("Failed to update offline queue: {}", save_err),
+            },
+        }
+    }
+    emit_offline_queue(app, state).await;
+}
+
+/// Watch for connectivity to return and replay the offline queue when it does.
+async fn run_offline_queue(app: tauri::AppHandle) {
+    loop {
+        tokio::time::sleep(OFFLINE_REPLAY_INTERVAL).await;
+        let Some(state) = app.try_state::<AppState>() else {
+            continue;
+        };
+        if state.offline_queue.lock().await.is_empty() {
+            continue;
+        }
+        let config = state.config.lock().await.clone();
+        let reachable = state.query_client.is_reachable(&config).await;
+        if state.connectivity.set_online(reachable) {
+            emit_offline_queue(&app, &state).await;
+        }
+        if reachable {
+            replay_offline_queue(&app, &state).await;
+        }
+    }
+}
+
+/// How often the tray redraws without a state change, so "last sync" ages
+const TRAY_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// Tray menu entries that change with the sync state.
+struct TrayMenu {
+    status: MenuItem<Wry>,
+    last_sync: MenuItem<Wry>,
+    counts: MenuItem<Wry>,
+    toggle: MenuItem<Wry>,
+}
+
+/// Keep the tray icon, tooltip and menu in step with the uploader and
+/// watcher. Redraws when an upload starts or finishes, when a state-change
+/// event goes out, and every `TRAY_REFRESH_INTERVAL`.
+async fn run_tray_updates(app: tauri::AppHandle, menu: TrayMenu) {
+    // An owned copy of the app icon to draw each state from
+    let Some(base) = app.default_window_icon().map(|icon| tray::icon_for(icon, tray::TrayState::Idle)) else {
+        return;
+    };
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let mut active = state.uploader.active();
+    let mut shown: Option<(tray::TraySummary, String)> = None;
+    loop {
+        let uploading = *active.borrow_and_update();
+        let watching = *state.watching.lock().await;
+        let scheduled = state.scheduled.lock().await.list().len();
+        let offline = state.offline_queue.lock().await.list().len();
+        let approvals = state.pending.lock().await.list().len();
+        let failed = state.dead_letters.lock().await.list().len();
+        let last_sync = state.ledger.lock().await.last_success_at();
+        let summary = tray::TraySummary {
+            watching,
+            online: state.connectivity.is_online(),
+            uploading,
+            pending: scheduled + offline + approvals,
+            failed,
+            last_sync,
+        };
+        let last_sync_text = summary.last_sync_label(ledger::now_secs());
+        if shown.as_ref() != Some(&(summary.clone(), last_sync_text.clone())) {
+            if let Some(tray_icon) = app.tray_by_id(tray::TRAY_ID) {
+                let _ = tray_icon.set_icon(Some(tray::icon_for(&base, summary.state())));
+                let _ = tray_icon.set_tooltip(Some(summary.tooltip()));
+            }
+            let _ = menu.status.set_text(summary.tooltip());
+            let _ = menu.last_sync.set_text(&last_sync_text);
+            let _ = menu.counts.set_text(summary.counts_label());
+            let _ = menu.toggle.set_text(if summary.watching { "Pause" } else { "Resume" });
+            shown = Some((summary, last_sync_text));
+        }
+
+        tokio::select! {
+            Ok(()) = active.changed() => {}
+            _ = state.tray_refresh.notified() => {}
+            _ = tokio::time::sleep(TRAY_REFRESH_INTERVAL) => {}
+        }
+    }
+}
+
+/// Upload buffered telemetry events in batches while the user has opted in.
+async fn run_telemetry_uploads(app: tauri::AppHandle) {
+    loop {
+        tokio::time::sleep(TELEMETRY_UPLOAD_INTERVAL).await;
+        let Some(state) = app.try_state::<AppState>() else {
+            continue;
+        };
+        let config = state.config.lock().await.clone();
+        if !config.telemetry_enabled {
+            continue;
+        }
+        loop {
+            let batch = {
+                let telemetry = state.telemetry.lock().await;
+                if telemetry.is_empty() {
+                    break;
+                }
+                telemetry.batch()
+            };
+            if let Err(e) = telemetry::upload(&state.http_client, config.api_url(), &batch).await {
+                log::debug!("Telemetry upload failed, keeping events for later: {}", e);
+                break;
+            }
+            if let Err(e) = state.telemetry.lock().await.acknowledge(batch.events.len()) {
+                log::warn!("Failed to drop uploaded telemetry: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Exactly what the next telemetry upload would send. Nothing is sent, or
+/// recorded, unless `telemetry_enabled` is on.
+#[tauri::command]
+async fn get_telemetry_preview(state: State<'_, AppState>) -> Result<TelemetryBatch, Error> {
+    Ok(state.telemetry.lock().await.batch())
+}
+
+/// Calls, latency, results and token usage of queries, follow-ups and
+/// searches per day, for the `days` most recent days.
+#[tauri::command]
+async fn get_query_metrics(
+    state: State<'_, AppState>,
+    days: Option<usize>,
+) -> Result<QueryMetricsReport, Error> {
+    state
+        .query_client
+        .metrics_report(days.unwrap_or(30))
+        .ok_or_else(|| Error::Internal("Query metrics aren't being kept".to_string()))
+}
+
+/// Schemas in the user's database, for building mutations.
+#[tauri::command]
+async fn list_schemas(
+    state: State<'_, AppState>,
+    invocation_id: Option<String>,
+) -> Result<Vec<query::SchemaSummary>, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            state.query_client.list_schemas(&config).await
+        })
+        .await
+}
+
+/// Documents, storage and queries the account has used against its plan,
+/// so a quota can be spotted before uploads or queries start failing.
+#[tauri::command]
+async fn get_account_usage(
+    state: State<'_, AppState>,
+    invocation_id: Option<String>,
+) -> Result<query::AccountUsage, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            state.query_client.get_account_usage(&config).await
+        })
+        .await
+}
+
+/// Fields of one schema.
+#[tauri::command]
+async fn describe_schema(
+    state: State<'_, AppState>,
+    name: String,
+    invocation_id: Option<String>,
+) -> Result<query::SchemaDescription, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let config = state.config.lock().await.clone();
+            state.query_client.describe_schema(&config, &name).await
+        })
+        .await
+}
+
+/// Drop all cached query and search responses. Returns how many there were.
+#[tauri::command]
+async fn clear_query_cache(state: State<'_, AppState>) -> Result<usize, Error> {
+    Ok(state.query_client.clear_cache())
+}
+
+/// Save `query` under `name`, replacing any saved query with that name.
+/// `{param}` placeholders are filled in by `run_saved_query`.
+#[tauri::command]
+async fn save_query(
+    state: State<'_, AppState>,
+    name: String,
+    query: String,
+) -> Result<SavedQuery, Error> {
+    state
+        .saved_queries
+        .lock()
+        .await
+        .upsert(&name, &query)
+        .map_err(Error::Validation)
+}
+
+#[tauri::command]
+async fn list_saved_queries(state: State<'_, AppState>) -> Result<Vec<SavedQuery>, Error> {
+    Ok(state.saved_queries.lock().await.list())
+}
+
+#[tauri::command]
+async fn delete_saved_query(state: State<'_, AppState>, name: String) -> Result<bool, Error> {
+    state.saved_queries.lock().await.delete(&name).map_err(Error::Io)
+}
+
+/// Run a saved query with its placeholders filled from `params`. Accepts the
+/// same paging options as `run_query`.
+#[tauri::command]
+async fn run_saved_query(
+    state: State<'_, AppState>,
+    name: String,
+    params: Option<std::collections::HashMap<String, String>>,
+    view: Option<ResultView>,
+    handoff: Option<bool>,
+    force_refresh: Option<bool>,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<Payload<query::RunQueryResponse>, Error> {
+    let query = {
+        let saved_queries = state.saved_queries.lock().await;
+        let saved = saved_queries
+            .get(&name)
+            .ok_or_else(|| Error::Validation(format!("No saved query named '{}'", name)))?;
+        saved
+            .render(&params.unwrap_or_default())
+            .map_err(Error::Validation)?
+    };
+    let timeout = query_timeout(&state, timeout_secs).await;
+    state
+        .commands
+        .run(invocation_id, timeout, async {
+            execute_query(&state, query, None, view, handoff, force_refresh).await
+        })
+        .await
+}
+
+/// Keep the full results on disk and return the first page, whether more
+/// remain, and a cursor for them. The cursor is dropped if the results
+/// couldn't be stored, since it would point at nothing.
+fn store_and_page(
+    results_id: &str,
+    view: &ResultView,
+    results: &[serde_json::Value],
+) -> (Vec<serde_json::Value>, bool, Option<String>) {
+    let stored = match query_results::store_full_results(results_id, results) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("Failed to store full query results: {}", e);
+            false
+        }
+    };
+    let (page, next_cursor) = view.page(results_id, results);
+    let has_more = next_cursor.is_some();
+    (page, has_more, next_cursor.filter(|_| stored))
+}
+
+/// Next page of a paginated `run_query` or `search_index` result.
+#[tauri::command]
+async fn get_more_results(
+    state: State<'_, AppState>,
+    cursor: String,
+    handoff: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<Payload<ResultPage>, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let cursor = ResultCursor::decode(&cursor).map_err(Error::Validation)?;
+            let page = query_results::load_page(&cursor)?;
+            Ok(state.payloads.wrap(page, handoff.unwrap_or(false))?)
+        })
+        .await
+}
+
+/// Ask where to save results and write them in `format`. With a `cursor`
+/// from a paginated response the complete result set is exported; otherwise
+/// `results` is written as given. Returns the saved path, or None if the
+/// dialog was cancelled.
+#[tauri::command]
+async fn export_results(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    format: ExportFormat,
+    results: Option<Vec<serde_json::Value>>,
+    cursor: Option<String>,
+    file_name: Option<String>,
+    invocation_id: Option<String>,
+) -> Result<Option<String>, Error> {
+    use tauri_plugin_dialog::DialogExt;
+
+    state
+        .commands
+        .run(invocation_id, DIALOG_TIMEOUT, async {
+            let results = match cursor {
+                Some(cursor) => {
+                    let cursor = ResultCursor::decode(&cursor).map_err(Error::Validation)?;
+                    query_results::load_full_results(&cursor.results_id).map_err(Error::Io)?
+                }
+                None => results.unwrap_or_default(),
+            };
+            let file_name = format!(
+                "{}.{}",
+                file_name.as_deref().unwrap_or("exemem-results"),
+                format.extension()
+            );
+
+            let path = tokio::task::spawn_blocking(move || {
+                app.dialog()
+                    .file()
+                    .add_filter(format.extension(), &[format.extension()])
+                    .set_file_name(file_name)
+                    .blocking_save_file()
+                    .and_then(|path| path.into_path().ok())
+            })
+            .await
+            .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))?;
+
+            let Some(path) = path else {
+                return Ok(None);
+            };
+            export::write(&path, &results, format).map_err(Error::Io)?;
+            Ok(Some(path.display().to_string()))
+        })
+        .await
+}
+
+/// Complete results of the latest query in a session, optionally re-sliced
+/// with a different view.
+#[tauri::command]
+async fn get_full_results(
+    state: State<'_, AppState>,
+    session_id: String,
+    view: Option<ResultView>,
+    handoff: Option<bool>,
+    invocation_id: Option<String>,
+) -> Result<Payload<Vec<serde_json::Value>>, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            let results = query_results::load_full_results(&session_id)?;
+            let results = match view {
+                Some(view) => view.apply(&results).0,
+                None => results,
+            };
+            Ok(state.payloads.wrap(results, handoff.unwrap_or(false))?)
+        })
+        .await
+}
 
-            handles.push(handle);
-        }
+/// Raw bytes of a handed-off payload (serialized JSON), for chunked reads.
+#[tauri::command]
+async fn read_payload(
+    state: State<'_, AppState>,
+    handle: String,
+    offset: u64,
+    len: u64,
+) -> Result<tauri::ipc::Response, Error> {
+    let bytes = state.payloads.read(&handle, offset, len)?;
+    Ok(tauri::ipc::Response::new(bytes))
+}
 
-        // Wait for all uploads to complete
-        for handle in handles {
-            let _ = handle.await;
-        }
+#[tauri::command]
+async fn release_payload(state: State<'_, AppState>, handle: String) -> Result<bool, Error> {
+    Ok(state.payloads.release(&handle))
+}
 
-        let _ = app_handle.emit("ingestion-complete", true);
-    });
+#[tauri::command]
+async fn chat_followup(
+    state: State<'_, AppState>,
+    session_id: String,
+    question: String,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<query::ChatResponse, Error> {
+    let timeout = query_timeout(&state, timeout_secs).await;
+    let work = state
+        .commands
+        .run(invocation_id, timeout, async {
+            let config = state.config.lock().await.clone();
+            let response = state
+                .query_client
+                .chat_followup(&config, &session_id, &question)
+                .await?;
+
+            record_transcript(
+                &state,
+                TranscriptTurn {
+                    session_id,
+                    kind: "followup".to_string(),
+                    question,
+                    answer: response.answer.clone(),
+                    context_used: response.context_used,
+                    result_count: None,
+                    created_at: ledger::now_secs(),
+                },
+            )
+            .await;
+            Ok(response)
+        });
+    tracked(&state, "followup", work).await
+}
 
-    Ok(())
+#[derive(Debug, Clone, Serialize)]
+struct QuickAnswer {
+    session_id: String,
+    answer: String,
+    /// Whether the question continued the last session
+    followup: bool,
+    /// Results behind the answer, for a new session's first query
+    result_count: Option<usize>,
 }
 
-async fn update_file_progress(
-    progress: &Arc<Mutex<Vec<FileProgress>>>,
-    filename: &str,
-    status: &str,
-    percent: f64,
-    progress_id: Option<String>,
-) {
-    let mut prog = progress.lock().await;
-    if let Some(entry) = prog.iter_mut().find(|p| p.filename == filename) {
-        entry.status = status.to_string();
-        entry.percent = percent;
-        if let Some(pid) = progress_id {
-            entry.progress_id = Some(pid);
-        }
+/// Answer a question from the quick-query window. It continues the most
+/// recent session, as a follow-up, unless `new_session` is set or there is
+/// none, so a question asked there can build on what was asked before.
+#[tauri::command]
+async fn quick_query(
+    state: State<'_, AppState>,
+    question: String,
+    new_session: Option<bool>,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<QuickAnswer, Error> {
+    let timeout = query_timeout(&state, timeout_secs).await;
+    let work = state
+        .commands
+        .run(invocation_id, timeout, async {
+            if question.trim().is_empty() {
+                return Err(Error::Validation("Question can't be empty".to_string()));
+            }
+            let last_session = if new_session.unwrap_or(false) {
+                None
+            } else {
+                let sessions = state.transcripts.lock().await.sessions(1).map_err(Error::Io)?;
+                sessions.into_iter().next().map(|s| s.session_id)
+            };
+            let config = state.config.lock().await.clone();
+
+            let (answer, turn) = match last_session {
+                Some(session_id) => {
+                    let result = state.query_client.chat_followup(&config, &session_id, &question).await;
+                    let response = track_connectivity(&state, result)?;
+                    let answer = QuickAnswer {
+                        session_id: session_id.clone(),
+                        answer: response.answer.clone(),
+                        followup: true,
+                        result_count: None,
+                    };
+                    let turn = TranscriptTurn {
+                        session_id,
+                        kind: "followup".to_string(),
+                        question,
+                        answer: response.answer,
+                        context_used: response.context_used,
+                        result_count: None,
+                        created_at: ledger::now_secs(),
+                    };
+                    (answer, turn)
+                }
+                None => {
+                    let result = state.query_client.run_query(&config, &question, None, false).await;
+                    let response = track_connectivity(&state, result)?;
+                    let answer = QuickAnswer {
+                        session_id: response.session_id.clone(),
+                        answer: response.ai_interpretation.clone(),
+                        followup: false,
+                        result_count: Some(response.raw_results.len()),
+                    };
+                    let turn = TranscriptTurn {
+                        session_id: response.session_id,
+                        kind: "query".to_string(),
+                        question,
+                        answer: response.ai_interpretation,
+                        context_used: false,
+                        result_count: Some(response.raw_results.len()),
+                        created_at: ledger::now_secs(),
+                    };
+                    (answer, turn)
+                }
+            };
+            record_transcript(&state, turn).await;
+            Ok(answer)
+        });
+    tracked(&state, "quick_query", work).await
+}
+
+async fn record_transcript(state: &AppState, turn: TranscriptTurn) {
+    if let Err(e) = state.transcripts.lock().await.record(&turn) {
+        log::warn!("Failed to save chat transcript: {}", e);
     }
 }
 
-async fn get_progress_snapshot(progress: &Arc<Mutex<Vec<FileProgress>>>) -> Vec<FileProgress> {
-    progress.lock().await.clone()
+/// Past questions and answers containing every word of `term`, searched
+/// locally without contacting the backend.
+#[tauri::command]
+async fn search_transcripts(
+    state: State<'_, AppState>,
+    term: String,
+    limit: Option<usize>,
+    invocation_id: Option<String>,
+) -> Result<Vec<TranscriptHit>, Error> {
+    state
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            state
+                .transcripts
+                .lock()
+                .await
+                .search(&term, limit.unwrap_or(50))
+                .map_err(Error::Io)
+        })
+        .await
 }
 
-async fn poll_until_done(
-    uploader: &Uploader,
-    config: &AppConfig,
-    progress_id: &str,
-    progress: &Arc<Mutex<Vec<FileProgress>>>,
-    filename: &str,
-    app: &tauri::AppHandle,
-) {
-    let max_polls = 120; // 4 minutes at 2s intervals
-    for _ in 0..max_polls {
-        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+/// Past conversations for the session picker, most recently active first.
+/// Sessions recorded locally come first; sessions the backend knows about
+/// but this machine has no transcript for are appended when it's reachable.
+#[tauri::command]
+async fn list_sessions(
+    state: State<'_, AppState>,
+    limit: Option<usize>,
+    invocation_id: Option<String>,
+) -> Result<Vec<SessionSummary>, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let limit = limit.unwrap_or(50);
+            let mut sessions = state.transcripts.lock().await.sessions(limit).map_err(Error::Io)?;
+
+            let config = state.config.lock().await.clone();
+            match state.query_client.list_sessions(&config).await {
+                Ok(remote) => sessions = sessions::merge_remote(sessions, remote, limit),
+                Err(e) => log::warn!("Couldn't list backend sessions: {}", e),
+            }
+            Ok(sessions)
+        })
+        .await
+}
 
-        match uploader.poll_progress(config, progress_id).await {
-            Ok(resp) => {
-                let percent = resp.percent.unwrap_or(50.0);
-                let status = resp.status.as_str();
+/// Every turn of a session, oldest first, so it can be resumed. Falls back
+/// to the backend when the session wasn't recorded on this machine.
+#[tauri::command]
+async fn get_session_transcript(
+    state: State<'_, AppState>,
+    session_id: String,
+    invocation_id: Option<String>,
+) -> Result<Vec<TranscriptTurn>, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, session_turns(&state, &session_id))
+        .await
+}
 
-                {
-                    let mut prog = progress.lock().await;
-                    if let Some(entry) = prog.iter_mut().find(|p| p.filename == filename) {
-                        entry.status = status.to_string();
-                        entry.percent = percent;
-                        entry.message = resp.message.clone();
-                    }
-                }
+async fn session_turns(state: &AppState, session_id: &str) -> Result<Vec<TranscriptTurn>, Error> {
+    let turns = state.transcripts.lock().await.session(session_id).map_err(Error::Io)?;
+    if !turns.is_empty() {
+        return Ok(turns);
+    }
+    let config = state.config.lock().await.clone();
+    state.query_client.get_session_transcript(&config, session_id).await
+}
 
-                let _ = app.emit("ingestion-progress", get_progress_snapshot(progress).await);
+/// Ask where to save a session's transcript and write it as Markdown.
+/// Returns the saved path, or None if the dialog was cancelled.
+#[tauri::command]
+async fn export_session_transcript(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    session_id: String,
+    invocation_id: Option<String>,
+) -> Result<Option<String>, Error> {
+    use tauri_plugin_dialog::DialogExt;
 
-                if status == "completed" || status == "done" || status == "error" || status == "failed" {
-                    if status == "completed" || status == "done" {
-                        update_file_progress(progress, filename, "done", 100.0, None).await;
-                    }
-                    break;
-                }
-            }
-            Err(e) => {
-                log::warn!("Progress poll error for {}: {}", filename, e);
-                // Don't break on poll errors, just keep trying
-            }
-        }
+    let turns = state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, session_turns(&state, &session_id))
+        .await?;
+    if turns.is_empty() {
+        return Err(Error::Validation(format!("Session {} has no turns to export", session_id)));
     }
+
+    let path = tokio::task::spawn_blocking(move || {
+        app.dialog()
+            .file()
+            .add_filter("md", &["md"])
+            .set_file_name("exemem-conversation.md")
+            .blocking_save_file()
+            .and_then(|path| path.into_path().ok())
+    })
+    .await
+    .map_err(|e| Error::Internal(format!("Dialog task failed: {}", e)))?;
+
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    std::fs::write(&path, transcripts::to_markdown(&turns))
+        .map_err(|e| Error::Io(format!("Failed to write {}: {}", path.display(), e)))?;
+    Ok(Some(path.display().to_string()))
 }
 
+/// Forget a session: its local transcript, its stored results, and the
+/// backend's copy. Returns how many local turns were removed.
 #[tauri::command]
-async fn get_ingestion_progress(
+async fn delete_session(
     state: State<'_, AppState>,
-) -> Result<Vec<FileProgress>, String> {
-    let progress = state.ingestion_progress.lock().await;
-    Ok(progress.clone())
+    session_id: String,
+    invocation_id: Option<String>,
+) -> Result<usize, Error> {
+    state
+        .commands
+        .run(invocation_id, QUERY_TIMEOUT, async {
+            let removed = sessions::forget_local(&*state.transcripts.lock().await, &session_id)?;
+            let config = state.config.lock().await.clone();
+            sessions::forget_remote(&config, &state.query_client, &session_id, removed).await
+        })
+        .await
 }
 
+/// Past questions, newest first, with answers truncated. Kept locally, so
+/// this works without the backend.
 #[tauri::command]
-async fn run_query(
+async fn get_query_history(
     state: State<'_, AppState>,
-    query: String,
-    session_id: Option<String>,
-) -> Result<query::RunQueryResponse, String> {
-    let config = state.config.lock().await.clone();
+    filter: Option<QueryHistoryFilter>,
+    invocation_id: Option<String>,
+) -> Result<Vec<QueryHistoryEntry>, Error> {
     state
-        .query_client
-        .run_query(&config, &query, session_id.as_deref())
+        .commands
+        .run(invocation_id, LOCAL_TIMEOUT, async {
+            state
+                .transcripts
+                .lock()
+                .await
+                .history(&filter.unwrap_or_default())
+                .map_err(Error::Io)
+        })
         .await
 }
 
+/// Delete query history entries (and their transcripts), or all of them
+/// when `ids` is omitted. Returns how many were removed.
 #[tauri::command]
-async fn chat_followup(
+async fn delete_query_history(
     state: State<'_, AppState>,
-    session_id: String,
-    question: String,
-) -> Result<query::ChatResponse, String> {
-    let config = state.config.lock().await.clone();
+    ids: Option<Vec<i64>>,
+) -> Result<usize, Error> {
     state
-        .query_client
-        .chat_followup(&config, &session_id, &question)
+        .transcripts
+        .lock()
         .await
+        .delete_history(ids.as_deref())
+        .map_err(Error::Io)
 }
 
 #[tauri::command]
 async fn search_index(
     state: State<'_, AppState>,
     term: String,
-) -> Result<query::SearchResponse, String> {
-    let config = state.config.lock().await.clone();
-    state.query_client.search_index(&config, &term).await
+    filters: Option<query::SearchFilters>,
+    view: Option<ResultView>,
+    force_refresh: Option<bool>,
+    timeout_secs: Option<u64>,
+    invocation_id: Option<String>,
+) -> Result<query::SearchResponse, Error> {
+    let timeout = query_timeout(&state, timeout_secs).await;
+    let work = state
+        .commands
+        .run(invocation_id, timeout, async {
+            let config = state.config.lock().await.clone();
+            let mut response = state
+                .query_client
+                .search_index(
+                    &config,
+                    &term,
+                    &filters.unwrap_or_default(),
+                    force_refresh.unwrap_or(false),
+                )
+                .await?;
+
+            let view = view.unwrap_or_default();
+            if !view.is_full() {
+                let results_id = format!("search-{}", uuid::Uuid::new_v4());
+                let hits: Vec<serde_json::Value> = response
+                    .results
+                    .iter()
+                    .filter_map(|hit| serde_json::to_value(hit).ok())
+                    .collect();
+                let (page, has_more, next_cursor) = store_and_page(&results_id, &view, &hits);
+                response.results = page
+                    .into_iter()
+                    .filter_map(|hit| serde_json::from_value(hit).ok())
+                    .collect();
+                response.has_more = has_more;
+                response.next_cursor = next_cursor;
+            }
+            Ok(response)
+        });
+    tracked(&state, "search", work).await
 }
 
 #[tauri::command]
 async fn start_watching(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), Error> {
     let config = state.config.lock().await.clone();
 
     if !config.is_configured() {
-        return Err("App not configured. Set API URL, API key, and watched folder.".to_string());
+        return Err(Error::Validation(
+            "App not configured. Set API URL, API key, and watched folder.".to_string(),
+        ));
     }
 
-    let folder = config.watched_folder.clone().unwrap();
+    let mut folder = config.watched_folder.clone().unwrap();
 
     if !folder.exists() {
-        return Err(format!("Watched folder does not exist: {:?}", folder));
+        return Err(Error::Validation(format!("Watched folder does not exist: {:?}", folder)));
     }
 
     // Stop existing watcher if any
@@ -392,54 +2989,84 @@ async fn start_watching(
     *state.stop_tx.lock().await = Some(stop_tx);
     *state.watching.lock().await = true;
 
-    let _watcher = FolderWatcher::start(folder.clone(), event_tx)?;
+    let mut watcher = FolderWatcher::start(folder.clone(), event_tx.clone())?;
 
     // Spawn upload processing task
     let activity_log = state.activity_log.clone();
+    let ledger = state.ledger.clone();
+    let dead_letters = state.dead_letters.clone();
+    let feedback = state.feedback.clone();
+    let scheduled = state.scheduled.clone();
+    let pending = state.pending.clone();
     let watching = state.watching.clone();
+    let uploader = state.uploader.clone();
     let app_handle = app.clone();
-    let auto_approve = config.auto_approve_watched;
+    let mut config_rx = state.config_updates.subscribe();
+    let mut config = config;
 
     tokio::spawn(async move {
-        let uploader = Uploader::new();
-        let _watcher_handle = _watcher;
-
         loop {
             tokio::select! {
+                Ok(()) = config_rx.changed() => {
+                    config = config_rx.borrow_and_update().clone();
+                    follow_watched_folder(&app_handle, &config, &mut folder, &mut watcher, &event_tx);
+                }
                 Some(event) = event_rx.recv() => {
                     let file_path = match &event {
                         WatchEvent::FileCreated(p) | WatchEvent::FileModified(p) => p.clone(),
                     };
 
+                    // Events still queued from a folder no longer watched
+                    if !file_path.starts_with(&folder) || file_actions::in_ingested_dir(&folder, &file_path) {
+                        continue;
+                    }
+
                     log::info!("File event: {:?}", file_path);
 
                     // Classify the new file
-                    let recommendation = classify_single_file(&folder, &file_path);
+                    let mut recommendation = classify_single_file(&folder, &file_path, &config.scanner);
+                    feedback.lock().await.adjust(&mut recommendation);
 
                     // Emit classification info to frontend
-                    let _ = app_handle.emit("new-file-detected", &recommendation);
+                    emit_replayable(&app_handle, "new-file-detected", &recommendation);
 
-                    if auto_approve && recommendation.should_ingest {
-                        let result = uploader.upload_and_ingest(&file_path, &config).await;
-                        log_activity_with_category(&activity_log, &result, Some(recommendation.category)).await;
-                        let _ = app_handle.emit("sync-activity", &result);
+                    if config.auto_approve_watched && recommendation.should_ingest {
+                        let context = FileContext::from_recommendation(&recommendation, Vec::new());
+                        if let Some(at) = config.upload_schedule.defer_until(ledger::now_secs()) {
+                            schedule_upload(&app_handle, &scheduled, &file_path, context, at).await;
+                            continue;
+                        }
+                        let result = uploader
+                            .upload_and_ingest(
+                                &file_path,
+                                &config,
+                                UploadPriority::Watcher,
+                                context.clone(),
+                            )
+                            .await;
+                        record_dead_letter(&dead_letters, &file_path, &result, &context).await;
+                        spawn_after_ingestion(uploader.clone(), activity_log.clone(), config.clone(), file_path.clone(), result.clone());
+                        log_activity_with_category(&activity_log, &ledger, &result, Some(recommendation.category)).await;
+                        emit_replayable(&app_handle, "sync-activity", &result);
+                    } else if recommendation.should_ingest {
+                        queue_for_approval(&app_handle, &pending, recommendation).await;
                     } else {
                         // Log as skipped
                         let entry = ActivityEntry {
+                            id: Uuid::new_v4().to_string(),
                             filename: recommendation.path,
                             status: UploadStatus::Uploaded, // Not uploaded, just detected
-                            error: if recommendation.should_ingest {
-                                Some("Waiting for approval".to_string())
-                            } else {
-                                Some(format!("Skipped ({})", recommendation.category))
-                            },
+                            error: Some(format!("Skipped ({})", recommendation.category)),
                             timestamp: chrono_now(),
                             category: Some(recommendation.category),
+                            suggestion: None,
+                            deleted_at: None,
+                            note: None,
                         };
                         let mut activity = activity_log.lock().await;
                         activity.insert(0, entry.clone());
                         activity.truncate(MAX_ACTIVITY_LOG);
-                        let _ = app_handle.emit("sync-activity", &entry);
+                        emit_replayable(&app_handle, "sync-activity", &entry);
                     }
                 }
                 _ = stop_rx.recv() => {
@@ -451,39 +3078,182 @@ async fn start_watching(
         }
     });
 
-    let _ = app.emit("sync-status-changed", true);
+    emit_replayable(&app, "sync-status-changed", true);
 
     Ok(())
 }
 
+/// Move the watcher to the configured folder once it changes, so a folder
+/// picked in settings is watched without stopping and starting again. If
+/// the new folder can't be watched, the old one still is.
+fn follow_watched_folder(
+    app: &tauri::AppHandle,
+    config: &AppConfig,
+    folder: &mut std::path::PathBuf,
+    watcher: &mut FolderWatcher,
+    event_tx: &mpsc::Sender<WatchEvent>,
+) {
+    let Some(new_folder) = config.watched_folder.as_ref().filter(|f| *f != folder) else {
+        return;
+    };
+    match FolderWatcher::start(new_folder.clone(), event_tx.clone()) {
+        Ok(new_watcher) => {
+            // Dropping the old watcher stops its events
+            *watcher = new_watcher;
+            *folder = new_folder.clone();
+            log::info!("Watched folder changed; now watching {:?}", folder);
+            emit_replayable(app, "watched-folder-changed", &*folder);
+        }
+        Err(e) => log::warn!("Still watching {:?}; can't watch {:?}: {}", folder, new_folder, e),
+    }
+}
+
+/// Hold a recommended watched file until the user approves or rejects it.
+async fn queue_for_approval(
+    app: &tauri::AppHandle,
+    pending: &Arc<Mutex<PendingApprovals>>,
+    recommendation: FileRecommendation,
+) {
+    let mut queue = pending.lock().await;
+    if let Err(e) = queue.add(recommendation, ledger::now_secs()) {
+        log::warn!("Failed to save pending approvals: {}", e);
+    }
+    emit_replayable(app, "pending-approvals", queue.list());
+}
+
+/// Watched files waiting for approval, oldest first.
+#[tauri::command]
+async fn get_pending_approvals(state: State<'_, AppState>) -> Result<Vec<PendingApproval>, Error> {
+    Ok(state.pending.lock().await.list())
+}
+
+/// Upload the waiting files at `paths`, or all of them without `paths`,
+/// through the same pipeline as approved scan results. Returns how many
+/// were queued; `ingestion-complete` is sent when they've finished.
+#[tauri::command]
+async fn approve_pending(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    paths: Option<Vec<String>>,
+    tags: Option<Vec<String>>,
+    invocation_id: Option<String>,
+) -> Result<usize, Error> {
+    let invocation = state.commands.register(invocation_id);
+    let config = state.config.lock().await.clone();
+    // Checked first, so the files stay queued if they can't be sent
+    require_credentials(&config)?;
+
+    let approved = take_pending(&app, &state, paths, true).await?;
+    let files: Vec<FileRecommendation> = approved
+        .into_iter()
+        .map(|e| e.recommendation)
+        .filter(|f| f.absolute_path.exists())
+        .collect();
+    let count = files.len();
+    if count > 0 {
+        let tags = tags.unwrap_or_default();
+        spawn_ingestion(app, &state, invocation, config, files, tags, UploadPriority::Manual).await;
+    }
+    Ok(count)
+}
+
+/// Drop the waiting files at `paths`, or all of them without `paths`,
+/// without uploading them. Returns how many were dropped.
+#[tauri::command]
+async fn reject_pending(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    paths: Option<Vec<String>>,
+) -> Result<usize, Error> {
+    Ok(take_pending(&app, &state, paths, false).await?.len())
+}
+
+/// Remove entries from the approval queue, teaching the scanner from the
+/// decision like approvals after a scan do.
+async fn take_pending(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    paths: Option<Vec<String>>,
+    approved: bool,
+) -> Result<Vec<PendingApproval>, Error> {
+    let paths: Option<Vec<std::path::PathBuf>> =
+        paths.map(|paths| paths.into_iter().map(std::path::PathBuf::from).collect());
+    let mut queue = state.pending.lock().await;
+    let taken = queue.take(paths.as_deref()).map_err(Error::Io)?;
+    emit_replayable(app, "pending-approvals", queue.list());
+    drop(queue);
+
+    let decisions = taken.iter().map(|e| (&e.recommendation, approved));
+    if let Err(e) = state.feedback.lock().await.record(decisions) {
+        log::warn!("Failed to record classification feedback: {}", e);
+    }
+    Ok(taken)
+}
+
 #[tauri::command]
 async fn stop_watching(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
-) -> Result<(), String> {
+) -> Result<(), Error> {
     if let Some(tx) = state.stop_tx.lock().await.take() {
         let _ = tx.send(()).await;
     }
     *state.watching.lock().await = false;
-    let _ = app.emit("sync-status-changed", false);
+    emit_replayable(&app, "sync-status-changed", false);
     Ok(())
 }
 
-async fn log_activity(log: &Arc<Mutex<Vec<ActivityEntry>>>, result: &UploadResult) {
-    log_activity_with_category(log, result, None).await;
+async fn record_dead_letter(
+    dead_letters: &Arc<Mutex<DeadLetterQueue>>,
+    file_path: &std::path::Path,
+    result: &UploadResult,
+    context: &FileContext,
+) {
+    if let Err(e) = dead_letters
+        .lock()
+        .await
+        .record_outcome(file_path, result, context)
+    {
+        log::warn!("Failed to update failed-upload list: {}", e);
+    }
+}
+
+async fn log_activity(
+    log: &Arc<Mutex<Vec<ActivityEntry>>>,
+    ledger: &Arc<Mutex<Ledger>>,
+    result: &UploadResult,
+) {
+    log_activity_with_category(log, ledger, result, None).await;
 }
 
 async fn log_activity_with_category(
     log: &Arc<Mutex<Vec<ActivityEntry>>>,
+    ledger: &Arc<Mutex<Ledger>>,
     result: &UploadResult,
     category: Option<String>,
 ) {
+    let id = Uuid::new_v4().to_string();
+    let timestamp = chrono_now();
+
+    // Dry runs show up in the activity log but never in the ledger
+    if result.status != UploadStatus::DryRun {
+        let ledger_entry =
+            LedgerEntry::from_result(id.clone(), result, category.clone(), timestamp.clone());
+        if let Err(e) = ledger.lock().await.record(ledger_entry) {
+            log::warn!("Failed to record ledger entry: {}", e);
+        }
+    }
+
     let entry = ActivityEntry {
+        id,
         filename: result.filename.clone(),
         status: result.status.clone(),
         error: result.error.clone(),
-        timestamp: chrono_now(),
+        timestamp,
         category,
+        suggestion: result.suggestion.clone(),
+        deleted_at: None,
+        note: None,
     };
 
     let mut activity = log.lock().await;
@@ -498,6 +3268,64 @@ fn chrono_now() -> String {
     format!("{}", now.as_secs())
 }
 
+/// Cached count for `folder`, starting a background recount when it is
+/// missing or stale. Returns 0 until the first count of a new folder finishes.
+async fn cached_file_count(cache: &Arc<Mutex<FileCountCache>>, folder: &std::path::Path) -> usize {
+    let mut guard = cache.lock().await;
+    let same_folder = guard.folder.as_deref() == Some(folder);
+    let fresh = same_folder
+        && guard
+            .counted_at
+            .is_some_and(|at| at.elapsed() < FILE_COUNT_MAX_AGE);
+
+    if !fresh && !(same_folder && guard.refreshing) {
+        if !same_folder {
+            guard.folder = Some(folder.to_path_buf());
+            guard.count = 0;
+            guard.counted_at = None;
+        }
+        guard.refreshing = true;
+        tokio::spawn(refresh_file_count(cache.clone(), folder.to_path_buf()));
+    }
+    guard.count
+}
+
+async fn refresh_file_count(cache: Arc<Mutex<FileCountCache>>, folder: std::path::PathBuf) -> usize {
+    let counted = {
+        let folder = folder.clone();
+        tokio::task::spawn_blocking(move || count_files(&folder))
+            .await
+            .ok()
+            .and_then(|r| r.ok())
+    };
+
+    let mut guard = cache.lock().await;
+    if guard.folder.as_deref() != Some(folder.as_path()) {
+        // The watched folder changed while we were counting
+        return guard.count;
+    }
+    guard.refreshing = false;
+    if let Some(count) = counted {
+        guard.count = count;
+        guard.counted_at = Some(std::time::Instant::now());
+    }
+    guard.count
+}
+
+/// Timings of each startup phase, including work deferred until after the
+/// tray was ready.
+#[tauri::command]
+async fn get_startup_report(state: State<'_, AppState>) -> Result<StartupReport, Error> {
+    Ok(state.startup.report())
+}
+
+/// Features, importers, preprocessors and auth methods this build supports,
+/// plus the commands it registers.
+#[tauri::command]
+async fn get_client_capabilities() -> Result<Capabilities, Error> {
+    Ok(Capabilities::current())
+}
+
 fn count_files(folder: &std::path::Path) -> Result<usize, std::io::Error> {
     let mut count = 0;
     if folder.is_dir() {
@@ -540,41 +3368,226 @@ fn handle_deep_link_url(app: &tauri::AppHandle, url: &url::Url) {
     }
 }
 
+/// Load a local store, starting empty if its file can't be read. Without a
+/// data dir nothing could be kept, so the app can't run.
+fn load_store<S>(
+    what: &str,
+    load: impl FnOnce() -> Result<S, String>,
+    empty: impl FnOnce() -> Result<S, String>,
+) -> S {
+    load()
+        .or_else(|e| {
+            log::error!("Failed to load {}, starting empty: {}", what, e);
+            empty()
+        })
+        .unwrap_or_else(|e| panic!("Nowhere to keep {}: {}", what, e))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let config = AppConfig::load().unwrap_or_default();
+    let startup = Arc::new(StartupProfile::default());
+
+    // Portable mode must be decided before anything touches config or data files
+    let portable = std::env::args().any(|arg| arg == "--portable");
+    if let Some(root) = paths::init(portable) {
+        log::info!("Using data directory {:?}", root);
+    }
+
+    let config = startup.measure("config", || AppConfig::load().unwrap_or_default());
+    redact::remember_secrets(&config);
+
+    let ledger = startup.measure("ledger", || {
+        load_store("ledger", Ledger::load, Ledger::empty)
+    });
+
+    let dead_letters = startup.measure("failed_uploads", || {
+        load_store("failed-upload list", DeadLetterQueue::load, DeadLetterQueue::empty)
+    });
+
+    let feedback = startup.measure("classification_feedback", || {
+        load_store(
+            "classification feedback",
+            ClassificationFeedback::load,
+            ClassificationFeedback::empty,
+        )
+    });
+
+    let transcripts = startup.measure("transcripts", || {
+        TranscriptStore::load()
+            .or_else(|e| {
+                log::error!("Failed to open transcript store, keeping transcripts in memory: {}", e);
+                TranscriptStore::in_memory()
+            })
+            .expect("in-memory transcript store")
+    });
+
+    let saved_queries = startup.measure("saved_queries", || {
+        load_store("saved queries", SavedQueries::load, SavedQueries::empty)
+    });
+
+    let query_metrics = startup.measure("query_metrics", || {
+        load_store("query metrics", QueryMetrics::load, QueryMetrics::empty)
+    });
+
+    let offline_queue = startup.measure("offline_queue", || {
+        load_store("offline queue", OfflineQueue::load, OfflineQueue::empty)
+    });
+
+    let telemetry = startup.measure("telemetry", || {
+        load_store("telemetry", Telemetry::load, Telemetry::empty)
+    });
+
+    let scheduled = startup.measure("scheduled_uploads", || {
+        load_store("scheduled uploads", ScheduledUploads::load, ScheduledUploads::empty)
+    });
+
+    let pending = startup.measure("pending_approvals", || {
+        let mut pending =
+            load_store("pending approvals", PendingApprovals::load, PendingApprovals::empty);
+        // Files deleted while the app wasn't running can't be approved
+        if let Err(e) = pending.prune_missing() {
+            log::warn!("Failed to update pending approvals: {}", e);
+        }
+        pending
+    });
 
     tauri::Builder::default()
         .plugin(tauri_plugin_deep_link::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_notification::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, _shortcut, event| {
+                    // The quick-query shortcut is the only one registered
+                    if event.state() == tauri_plugin_global_shortcut::ShortcutState::Pressed {
+                        toggle_quick_query(app);
+                    }
+                })
+                .build(),
+        )
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            Some(vec![HIDDEN_ARG]),
+        ))
+        // Keep in sync with capabilities::COMMANDS
         .invoke_handler(tauri::generate_handler![
             get_config,
             save_config,
+            list_profiles,
+            save_profile,
+            switch_profile,
+            validate_config,
+            config_encryption_status,
+            unlock_config,
+            lock_config,
+            set_config_passphrase,
+            get_autostart,
+            set_autostart,
+            hide_quick_query,
+            quick_query,
+            get_pending_approvals,
+            approve_pending,
+            reject_pending,
+            export_settings,
+            import_settings,
             select_folder,
             get_sync_status,
             get_recent_activity,
+            get_upload_history,
+            get_upload_stats,
+            delete_entry,
+            restore_entry,
+            get_deleted_entries,
+            purge_deleted_entries,
+            get_missed_events,
+            get_startup_report,
+            get_client_capabilities,
+            cancel_command,
+            cancel_query,
+            get_failed_uploads,
+            retry_failed_uploads,
+            retry_ingestion,
+            sync_now,
+            get_account_usage,
+            ingest_files,
+            pick_and_ingest,
+            ingest_text,
             scan_folder,
+            reset_classification_feedback,
+            get_scan_trends,
+            explain_file,
             approve_and_ingest,
             get_ingestion_progress,
             run_query,
+            clear_query_cache,
+            get_query_metrics,
+            get_telemetry_preview,
+            save_query,
+            list_saved_queries,
+            delete_saved_query,
+            run_saved_query,
+            get_full_results,
+            get_more_results,
+            export_results,
+            read_payload,
+            release_payload,
             chat_followup,
+            search_transcripts,
+            list_sessions,
+            get_session_transcript,
+            export_session_transcript,
+            delete_session,
+            get_query_history,
+            delete_query_history,
             search_index,
+            semantic_search,
+            mutate,
+            queue_request,
+            get_offline_queue,
+            remove_queued_request,
+            list_schemas,
+            describe_schema,
             start_watching,
             stop_watching,
         ])
         .setup(move |app| {
-            // Logging
+            let setup_start = std::time::Instant::now();
+
+            // Logging: JSON lines in a rotating file that `exemem-cli logs`
+            // can tail, plus stdout in debug builds
+            let mut log_builder = tauri_plugin_log::Builder::default()
+                .level(log::LevelFilter::Info)
+                .clear_targets()
+                .max_file_size(logs::MAX_LOG_FILE_BYTES)
+                .rotation_strategy(tauri_plugin_log::RotationStrategy::KeepOne)
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "{}",
+                        logs::format_line(record.level(), record.target(), &message.to_string())
+                    ))
+                });
+            match logs::log_dir() {
+                Ok(log_dir) => {
+                    log_builder = log_builder.target(tauri_plugin_log::Target::new(
+                        tauri_plugin_log::TargetKind::Folder {
+                            path: log_dir,
+                            file_name: Some(logs::LOG_FILE_NAME.to_string()),
+                        },
+                    ));
+                }
+                Err(e) => eprintln!("Logging to file disabled: {}", e),
+            }
             if cfg!(debug_assertions) {
-                app.handle().plugin(
-                    tauri_plugin_log::Builder::default()
-                        .level(log::LevelFilter::Info)
-                        .build(),
-                )?;
+                log_builder = log_builder.target(tauri_plugin_log::Target::new(
+                    tauri_plugin_log::TargetKind::Stdout,
+                ));
             }
+            app.handle().plugin(log_builder.build())?;
+            startup.record("logging", setup_start, false);
 
             // Deep link handling
+            let deep_link_start = std::time::Instant::now();
             #[cfg(any(windows, target_os = "linux"))]
             {
                 let _ = app.deep_link().register_all();
@@ -593,20 +3606,36 @@ pub fn run() {
                 }
             });
 
+            startup.record("deep_links", deep_link_start, false);
+
             // System tray
+            let tray_start = std::time::Instant::now();
+            let status_item = MenuItemBuilder::with_id("status", "Exemem Client").enabled(false).build(app)?;
+            let last_sync_item = MenuItemBuilder::with_id("last_sync", "Last sync: never").enabled(false).build(app)?;
+            let counts_item = MenuItemBuilder::with_id("counts", "Pending: 0 · Failed: 0").enabled(false).build(app)?;
             let open_item = MenuItemBuilder::with_id("open", "Open").build(app)?;
             let pause_item = MenuItemBuilder::with_id("toggle", "Pause").build(app)?;
             let quit_item = MenuItemBuilder::with_id("quit", "Quit").build(app)?;
 
             let menu = MenuBuilder::new(app)
+                .item(&status_item)
+                .item(&last_sync_item)
+                .item(&counts_item)
+                .separator()
                 .item(&open_item)
                 .item(&pause_item)
                 .separator()
                 .item(&quit_item)
                 .build()?;
+            let tray_menu = TrayMenu {
+                status: status_item,
+                last_sync: last_sync_item,
+                counts: counts_item,
+                toggle: pause_item.clone(),
+            };
 
             let app_handle = app.handle().clone();
-            TrayIconBuilder::new()
+            TrayIconBuilder::with_id(tray::TRAY_ID)
                 .icon(app.default_window_icon().cloned().unwrap())
                 .menu(&menu)
                 .tooltip("Exemem Client")
@@ -628,20 +3657,96 @@ pub fn run() {
                     }
                 })
                 .build(app)?;
+            startup.record("tray", tray_start, false);
 
             // Manage state
+            let state_start = std::time::Instant::now();
+            // One connection pool for uploads and queries
+            let http_client = http::build_client(&config.proxy, &config.tls, &config.connection);
             app.manage(AppState {
                 config: Arc::new(Mutex::new(config.clone())),
+                config_updates: watch::channel(config.clone()).0,
                 watching: Arc::new(Mutex::new(false)),
                 activity_log: Arc::new(Mutex::new(Vec::new())),
+                ledger: Arc::new(Mutex::new(ledger)),
+                dead_letters: Arc::new(Mutex::new(dead_letters)),
+                feedback: Arc::new(Mutex::new(feedback)),
+                scheduled: Arc::new(Mutex::new(scheduled)),
+                pending: Arc::new(Mutex::new(pending)),
+                transcripts: Arc::new(Mutex::new(transcripts)),
+                saved_queries: Arc::new(Mutex::new(saved_queries)),
+                offline_queue: Arc::new(Mutex::new(offline_queue)),
+                connectivity: Arc::new(Connectivity::default()),
                 stop_tx: Arc::new(Mutex::new(None)),
                 scan_result: Arc::new(Mutex::new(None)),
                 ingestion_progress: Arc::new(Mutex::new(Vec::new())),
-                query_client: QueryClient::new(),
+                uploader: Arc::new(Uploader::new(http_client.clone())),
+                events: EventBuffer::default(),
+                payloads: PayloadStore::default(),
+                // Each command enforces its own, shorter, timeout
+                query_client: QueryClient::with_client(http_client.clone())
+                    .with_timeout(MAX_QUERY_TIMEOUT)
+                    .with_metrics(query_metrics),
+                file_count: Arc::new(Mutex::new(FileCountCache::default())),
+                startup: startup.clone(),
+                commands: Arc::new(CommandRegistry::default()),
+                telemetry: Arc::new(Mutex::new(telemetry)),
+                tray_refresh: Arc::new(Notify::new()),
+                http_client,
+            });
+            startup.record("state", state_start, false);
+            startup.mark_tray_ready();
+
+            tauri::async_runtime::spawn(run_scheduled_uploads(app.handle().clone()));
+            tauri::async_runtime::spawn(run_offline_queue(app.handle().clone()));
+            tauri::async_runtime::spawn(run_telemetry_uploads(app.handle().clone()));
+            tauri::async_runtime::spawn(run_tray_updates(app.handle().clone(), tray_menu));
+
+            // Housekeeping that doesn't need to block the tray
+            let deferred_handle = app.handle().clone();
+            let deferred_config = config.clone();
+            let deferred_startup = startup.clone();
+            tauri::async_runtime::spawn(async move {
+                let Some(state) = deferred_handle.try_state::<AppState>() else {
+                    return;
+                };
+
+                // Entries hidden longer than the retention window are dropped for good
+                let purge_start = std::time::Instant::now();
+                let purged = state
+                    .ledger
+                    .lock()
+                    .await
+                    .purge_deleted(Some(deferred_config.deleted_retention_days));
+                if let Err(e) = purged {
+                    log::warn!("Failed to purge expired ledger entries: {}", e);
+                }
+                deferred_startup.record("ledger_purge", purge_start, true);
+
+                // Warm the file count so the first status request is instant
+                if let Some(folder) = deferred_config.watched_folder.clone() {
+                    let count_start = std::time::Instant::now();
+                    state.file_count.lock().await.folder = Some(folder.clone());
+                    refresh_file_count(state.file_count.clone(), folder).await;
+                    deferred_startup.record("file_count", count_start, true);
+                }
             });
 
-            // Hide window on close (stay in tray)
+            // Keep the login item in step with the config, e.g. after an import
+            if let Err(e) = apply_autostart(app.handle(), config.launch_at_login) {
+                log::warn!("{}", e);
+            }
+            // Another app may already hold the shortcut; everything else still works
+            if let Err(e) = apply_quick_query_shortcut(app.handle(), "", &config.quick_query_shortcut) {
+                log::warn!("{}", e);
+            }
+
+            // Hide window on close (stay in tray), and from the start when
+            // launched at login
             if let Some(window) = app.get_webview_window("main") {
+                if std::env::args().any(|arg| arg == HIDDEN_ARG) {
+                    let _ = window.hide();
+                }
                 let window_clone = window.clone();
                 window.on_window_event(move |event| {
                     if let tauri::WindowEvent::CloseRequested { api, .. } = event {
@@ -654,9 +3759,11 @@ pub fn run() {
             // Auto-start watching if configured
             if config.is_configured() {
                 let handle = app_handle.clone();
+                let watch_startup = startup.clone();
                 tauri::async_runtime::spawn(async move {
                     // Small delay to let state initialize
                     tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+                    let watch_start = std::time::Instant::now();
                     if let Some(state) = handle.try_state::<AppState>() {
                         let config = state.config.lock().await.clone();
                         if config.is_configured() {
@@ -666,33 +3773,55 @@ pub fn run() {
                                 *state.stop_tx.lock().await = Some(stop_tx);
                                 *state.watching.lock().await = true;
 
-                                let folder_clone = folder.clone();
-                                match FolderWatcher::start(folder.clone(), event_tx) {
-                                    Ok(_watcher) => {
+                                let mut folder_clone = folder.clone();
+                                match FolderWatcher::start(folder.clone(), event_tx.clone()) {
+                                    Ok(mut watcher) => {
                                         log::info!("Auto-started watching: {:?}", folder);
                                         let activity_log = state.activity_log.clone();
+                                        let ledger = state.ledger.clone();
+                                        let dead_letters = state.dead_letters.clone();
+                                        let feedback = state.feedback.clone();
+                                        let scheduled = state.scheduled.clone();
+                                        let pending = state.pending.clone();
                                         let watching = state.watching.clone();
+                                        let uploader = state.uploader.clone();
                                         let app_handle = handle.clone();
-                                        let auto_approve = config.auto_approve_watched;
+                                        let mut config_rx = state.config_updates.subscribe();
+                                        let mut config = config.clone();
 
                                         tokio::spawn(async move {
-                                            let uploader = Uploader::new();
-                                            let _watcher_handle = _watcher;
-
                                             loop {
                                                 tokio::select! {
+                                                    Ok(()) = config_rx.changed() => {
+                                                        config = config_rx.borrow_and_update().clone();
+                                                        follow_watched_folder(&app_handle, &config, &mut folder_clone, &mut watcher, &event_tx);
+                                                    }
                                                     Some(event) = event_rx.recv() => {
                                                         let file_path = match &event {
                                                             WatchEvent::FileCreated(p) | WatchEvent::FileModified(p) => p.clone(),
                                                         };
 
-                                                        let recommendation = classify_single_file(&folder_clone, &file_path);
-                                                        let _ = app_handle.emit("new-file-detected", &recommendation);
+                                                        if !file_path.starts_with(&folder_clone) || file_actions::in_ingested_dir(&folder_clone, &file_path) {
+                                                            continue;
+                                                        }
 
-                                                        if auto_approve && recommendation.should_ingest {
-                                                            let result = uploader.upload_and_ingest(&file_path, &config).await;
-                                                            log_activity_with_category(&activity_log, &result, Some(recommendation.category)).await;
-                                                            let _ = app_handle.emit("sync-activity", &result);
+                                                        let mut recommendation = classify_single_file(&folder_clone, &file_path, &config.scanner);
+                                                        feedback.lock().await.adjust(&mut recommendation);
+                                                        emit_replayable(&app_handle, "new-file-detected", &recommendation);
+
+                                                        if config.auto_approve_watched && recommendation.should_ingest {
+                                                            let context = FileContext::from_recommendation(&recommendation, Vec::new());
+                                                            if let Some(at) = config.upload_schedule.defer_until(ledger::now_secs()) {
+                                                                schedule_upload(&app_handle, &scheduled, &file_path, context, at).await;
+                                                                continue;
+                                                            }
+                                                            let result = uploader.upload_and_ingest(&file_path, &config, UploadPriority::Watcher, context.clone()).await;
+                                                            record_dead_letter(&dead_letters, &file_path, &result, &context).await;
+                                                            spawn_after_ingestion(uploader.clone(), activity_log.clone(), config.clone(), file_path.clone(), result.clone());
+                                                            log_activity_with_category(&activity_log, &ledger, &result, Some(recommendation.category)).await;
+                                                            emit_replayable(&app_handle, "sync-activity", &result);
+                                                        } else if recommendation.should_ingest {
+                                                            queue_for_approval(&app_handle, &pending, recommendation).await;
                                                         }
                                                     }
                                                     _ = stop_rx.recv() => {
@@ -710,6 +3839,7 @@ pub fn run() {
                             }
                         }
                     }
+                    watch_startup.record("auto_watch", watch_start, true);
                 });
             }
 
@@ -0,0 +1,432 @@
+use log::{Level, LevelFilter};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::paths;
+use crate::redact::redact;
+
+/// Base name of the log file; the plugin appends ".log"
+pub const LOG_FILE_NAME: &str = "exemem-client";
+
+/// The log file rotates once it reaches this size
+pub const MAX_LOG_FILE_BYTES: u128 = 5 * 1024 * 1024;
+
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// One line of the app's log, written as JSON so tools can filter it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// Milliseconds since the Unix epoch
+    pub ts: u64,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+impl LogRecord {
+    /// Parse a log line. Lines that aren't JSON (e.g. from older versions)
+    /// become an info record with the whole line as the message.
+    pub fn parse(line: &str) -> Self {
+        serde_json::from_str(line).unwrap_or_else(|_| LogRecord {
+            ts: 0,
+            level: "INFO".to_string(),
+            target: String::new(),
+            message: line.to_string(),
+        })
+    }
+
+    pub fn level(&self) -> Level {
+        Level::from_str(&self.level).unwrap_or(Level::Info)
+    }
+
+    /// Human-readable form: "2024-03-01 12:00:00 WARN exemem_client_lib::uploader: message"
+    pub fn display(&self) -> String {
+        format!(
+            "{} {:<5} {}: {}",
+            format_timestamp(self.ts),
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+}
+
+/// Render a log entry as one JSON line, with credentials masked. Used as
+/// the app's log format.
+pub fn format_line(level: Level, target: &str, message: &str) -> String {
+    let record = LogRecord {
+        ts: now_millis(),
+        level: level.to_string(),
+        target: target.to_string(),
+        message: redact(message),
+    };
+    serde_json::to_string(&record).unwrap_or_else(|_| record.message.clone())
+}
+
+/// Directory the app writes its log file to.
+pub fn log_dir() -> Result<PathBuf, String> {
+    paths::log_dir()
+}
+
+pub fn log_file() -> Result<PathBuf, String> {
+    Ok(log_dir()?.join(format!("{}.log", LOG_FILE_NAME)))
+}
+
+/// Which log records to show.
+#[derive(Debug, Clone)]
+pub struct LogFilter {
+    pub level: LevelFilter,
+    /// Substring of the record's target (module path)
+    pub module: Option<String>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            module: None,
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        record.level() <= self.level
+            && self
+                .module
+                .as_deref()
+                .map_or(true, |module| record.target.contains(module))
+    }
+}
+
+/// Log levels per module, from env-filter style directives such as
+/// "info,exemem_client_lib::uploader=debug,hyper=off". The most specific
+/// module prefix wins; a bare level sets the default.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogDirectives {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogDirectives {
+    pub fn new(default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: Vec::new(),
+        }
+    }
+
+    /// `default`, overridden by the directives in `spec`.
+    pub fn parse(spec: &str, default: LevelFilter) -> Result<Self, String> {
+        let mut directives = Self::new(default);
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalid = || format!("Invalid log directive {:?}; use LEVEL or MODULE=LEVEL", directive);
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let level = LevelFilter::from_str(level.trim()).map_err(|_| invalid())?;
+                    directives.modules.push((module.trim().to_string(), level));
+                }
+                None => match LevelFilter::from_str(directive) {
+                    Ok(level) => directives.default = level,
+                    // A bare module name turns on everything for it
+                    Err(_) => directives.modules.push((directive.to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        // Longest first, so the most specific module is found first
+        directives.modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(directives)
+    }
+
+    /// The same module levels with another default.
+    pub fn with_default(&self, default: LevelFilter) -> Self {
+        Self {
+            default,
+            modules: self.modules.clone(),
+        }
+    }
+
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module || target.strip_prefix(module.as_str()).is_some_and(|rest| rest.starts_with("::"))
+            })
+            .map_or(self.default, |(_, level)| *level)
+    }
+
+    /// The most verbose level any module logs at.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.default, LevelFilter::max)
+    }
+}
+
+/// Logger for the CLI, which has no app to install one: readable lines on
+/// stderr, and optionally the app's JSON lines appended to a file.
+struct CliLogger {
+    stderr: LogDirectives,
+    file: Option<(LogDirectives, Mutex<File>)>,
+}
+
+impl log::Log for CliLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let level = metadata.level();
+        level <= self.stderr.level_for(metadata.target())
+            || self
+                .file
+                .as_ref()
+                .is_some_and(|(directives, _)| level <= directives.level_for(metadata.target()))
+    }
+
+    fn log(&self, record: &log::Record) {
+        let (level, target) = (record.level(), record.target());
+        if level <= self.stderr.level_for(target) {
+            let line = LogRecord {
+                ts: now_millis(),
+                level: level.to_string(),
+                target: target.to_string(),
+                message: redact(&record.args().to_string()),
+            };
+            eprintln!("{}", line.display());
+        }
+        if let Some((directives, file)) = &self.file {
+            if level <= directives.level_for(target) {
+                let line = format_line(level, target, &record.args().to_string());
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{}", line);
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = file.flush();
+            }
+        }
+    }
+}
+
+/// Install the CLI's logger: `stderr` decides what is printed, and
+/// `file`, if given, gets records at `file_directives` appended in the
+/// format `exemem-cli logs` reads.
+pub fn init_cli_logger(
+    stderr: LogDirectives,
+    file: Option<(&Path, LogDirectives)>,
+) -> Result<(), String> {
+    let file = match file {
+        Some((path, directives)) => {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            let handle = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+            Some((directives, Mutex::new(handle)))
+        }
+        None => None,
+    };
+    let max_level = file
+        .as_ref()
+        .map_or(LevelFilter::Off, |(directives, _)| directives.max_level())
+        .max(stderr.max_level());
+    // Leaked rather than boxed: the logger lives as long as the process
+    log::set_logger(Box::leak(Box::new(CliLogger { stderr, file })))
+        .map_err(|e| format!("Failed to install logger: {}", e))?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+fn now_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// The last `count` matching records in the log file.
+pub fn tail(path: &Path, count: usize, filter: &LogFilter) -> Result<Vec<LogRecord>, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut records: Vec<LogRecord> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| LogRecord::parse(&line))
+        .filter(|record| filter.matches(record))
+        .collect();
+    let skip = records.len().saturating_sub(count);
+    Ok(records.split_off(skip))
+}
+
+/// Print matching records as they are appended, starting at the current end
+/// of the file. Survives rotation by reopening the file when it shrinks or
+/// is replaced. Runs until `on_record` returns false.
+pub fn follow(
+    path: &Path,
+    filter: &LogFilter,
+    mut on_record: impl FnMut(&LogRecord) -> bool,
+) -> Result<(), String> {
+    let open = |path: &Path| -> Option<(BufReader<File>, u64)> {
+        let mut file = File::open(path).ok()?;
+        let end = file.seek(SeekFrom::End(0)).ok()?;
+        Some((BufReader::new(file), end))
+    };
+
+    let (mut reader, mut position) =
+        open(path).ok_or_else(|| format!("Failed to open {}", path.display()))?;
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| format!("Failed to read log: {}", e))?;
+
+        if read > 0 && line.ends_with('\n') {
+            position += read as u64;
+            let record = LogRecord::parse(line.trim_end());
+            if filter.matches(&record) && !on_record(&record) {
+                return Ok(());
+            }
+            continue;
+        }
+        if read > 0 {
+            // Partial line; wait for the writer to finish it
+            reader
+                .seek(SeekFrom::Start(position))
+                .map_err(|e| format!("Failed to read log: {}", e))?;
+        }
+
+        std::thread::sleep(FOLLOW_POLL_INTERVAL);
+
+        // Rotation: the file at `path` is now shorter than what we've read
+        let current_len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        if current_len < position {
+            if let Ok(file) = File::open(path) {
+                reader = BufReader::new(file);
+                position = 0;
+            }
+        }
+    }
+}
+
+/// Format milliseconds since the Unix epoch as "YYYY-MM-DD HH:MM:SS" (UTC).
+pub(crate) fn format_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let rem = secs % 86_400;
+    let (year, month, day) = civil_date((secs / 86_400) as i64);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// (year, month, day) from days since 1970-01-01 (proleptic Gregorian)
+pub(crate) fn civil_date(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_directives_pick_most_specific_module() {
+        let directives =
+            LogDirectives::parse("info, exemem_client_lib=warn,exemem_client_lib::uploader=debug,hyper=off", LevelFilter::Warn)
+                .unwrap();
+        assert_eq!(directives.level_for("exemem_client_lib::uploader"), LevelFilter::Debug);
+        assert_eq!(directives.level_for("exemem_client_lib::uploader::s3"), LevelFilter::Debug);
+        assert_eq!(directives.level_for("exemem_client_lib::uploader_extra"), LevelFilter::Warn);
+        assert_eq!(directives.level_for("hyper::client"), LevelFilter::Off);
+        assert_eq!(directives.level_for("reqwest"), LevelFilter::Info);
+        assert_eq!(directives.max_level(), LevelFilter::Debug);
+        assert_eq!(directives.with_default(LevelFilter::Error).level_for("reqwest"), LevelFilter::Error);
+
+        assert_eq!(
+            LogDirectives::parse("watcher", LevelFilter::Warn).unwrap().level_for("watcher"),
+            LevelFilter::Trace
+        );
+        assert!(LogDirectives::parse("uploader=loud", LevelFilter::Warn).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_and_filter() {
+        let line = format_line(Level::Warn, "exemem_client_lib::uploader", "slow upload");
+        let record = LogRecord::parse(&line);
+        assert_eq!(record.level(), Level::Warn);
+        assert_eq!(record.message, "slow upload");
+
+        let warn_only = LogFilter {
+            level: LevelFilter::Warn,
+            module: Some("uploader".to_string()),
+        };
+        assert!(warn_only.matches(&record));
+
+        let info = LogRecord::parse(&format_line(Level::Info, "exemem_client_lib::uploader", "ok"));
+        assert!(!warn_only.matches(&info));
+
+        let other = LogFilter {
+            level: LevelFilter::Trace,
+            module: Some("watcher".to_string()),
+        };
+        assert!(!other.matches(&record));
+    }
+
+    #[test]
+    fn test_tail_returns_last_matching() {
+        let path = std::env::temp_dir().join(format!("exemem-log-{}.log", uuid::Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        for i in 0..5 {
+            let level = if i % 2 == 0 { Level::Error } else { Level::Info };
+            writeln!(file, "{}", format_line(level, "t", &format!("m{}", i))).unwrap();
+        }
+        writeln!(file, "plain text line").unwrap();
+
+        let errors = LogFilter {
+            level: LevelFilter::Error,
+            module: None,
+        };
+        let messages: Vec<_> = tail(&path, 2, &errors)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.message)
+            .collect();
+        assert_eq!(messages, vec!["m2", "m4"]);
+
+        let all = tail(&path, 1, &LogFilter::default()).unwrap();
+        assert_eq!(all[0].message, "plain text line");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(1_709_251_200_000 + 3_661_000), "2024-03-01 01:01:01");
+    }
+}
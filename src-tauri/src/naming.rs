@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use crate::logs::civil_date;
+
+const TOKENS: &[&str] = &["{date}", "{time}", "{counter}", "{title}", "{kind}"];
+
+/// How auto-created documents (clipboard text, screenshots, quick notes) are
+/// named before upload, so they get readable, queryable titles.
+///
+/// The template may use `{date}` (YYYY-MM-DD), `{time}` (HHMMSS), `{counter}`
+/// (per-day sequence number), `{title}` (slug of the first line of text) and
+/// `{kind}` (e.g. "clipboard"). The extension is appended by the caller.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CaptureNaming {
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Longest title slug, in characters
+    #[serde(default = "default_max_title_len")]
+    pub max_title_len: usize,
+    /// Local time offset from UTC used for `{date}` and `{time}`
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+fn default_template() -> String {
+    "{date}-{title}".to_string()
+}
+
+fn default_max_title_len() -> usize {
+    60
+}
+
+impl Default for CaptureNaming {
+    fn default() -> Self {
+        Self {
+            template: default_template(),
+            max_title_len: default_max_title_len(),
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+/// Something captured for ingestion that needs a file name.
+pub struct Capture<'a> {
+    /// "clipboard", "screenshot", "note", ...
+    pub kind: &'a str,
+    /// Text content, or OCR output for images when available
+    pub text: Option<&'a str>,
+    /// Seconds since the Unix epoch
+    pub captured_at: u64,
+    /// Number of earlier captures the same day, starting at 0
+    pub counter: u32,
+}
+
+impl CaptureNaming {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.template.trim().is_empty() {
+            return Err("Capture name template is empty".to_string());
+        }
+        let mut rest = self.template.as_str();
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed placeholder in {:?}", self.template))?;
+            let token = &rest[open..open + close + 1];
+            if !TOKENS.contains(&token) {
+                return Err(format!(
+                    "Unknown placeholder {} (expected one of {})",
+                    token,
+                    TOKENS.join(", ")
+                ));
+            }
+            rest = &rest[open + close + 1..];
+        }
+        if self.max_title_len == 0 {
+            return Err("Title length must be at least 1".to_string());
+        }
+        Ok(())
+    }
+
+    /// File stem for a capture, without extension. Never empty and safe to
+    /// use as a file name on every platform.
+    pub fn file_stem(&self, capture: &Capture) -> String {
+        let local = capture.captured_at as i64 + i64::from(self.utc_offset_minutes) * 60;
+        let (year, month, day) = civil_date(local.div_euclid(86_400));
+        let secs = local.rem_euclid(86_400);
+
+        let title = capture
+            .text
+            .map(|text| title_slug(text, self.max_title_len))
+            .filter(|slug| !slug.is_empty())
+            .unwrap_or_else(|| capture.kind.to_string());
+
+        let name = self
+            .template
+            .replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+            .replace(
+                "{time}",
+                &format!("{:02}{:02}{:02}", secs / 3600, secs % 3600 / 60, secs % 60),
+            )
+            .replace("{counter}", &(capture.counter + 1).to_string())
+            .replace("{title}", &title)
+            .replace("{kind}", capture.kind);
+
+        let name = sanitize(&name);
+        if name.is_empty() {
+            capture.kind.to_string()
+        } else {
+            name
+        }
+    }
+}
+
+/// Lowercase, hyphen-separated slug of the first non-empty line.
+fn title_slug(text: &str, max_len: usize) -> String {
+    let line = text.lines().map(str::trim).find(|l| !l.is_empty()).unwrap_or("");
+    let mut slug = String::new();
+    for word in line.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+        let word = word.to_lowercase();
+        let extra = if slug.is_empty() { word.chars().count() } else { word.chars().count() + 1 };
+        if slug.chars().count() + extra > max_len {
+            if slug.is_empty() {
+                slug = word.chars().take(max_len).collect();
+            }
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    slug
+}
+
+/// Replace characters that aren't allowed in file names on some platform.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c == '.' || c == ' ' || c == '-')
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture<'a>(text: Option<&'a str>) -> Capture<'a> {
+        Capture {
+            kind: "clipboard",
+            text,
+            // 2024-03-01 13:05:09 UTC
+            captured_at: 1_709_298_309,
+            counter: 2,
+        }
+    }
+
+    #[test]
+    fn test_default_template_uses_first_line() {
+        let naming = CaptureNaming::default();
+        let stem = naming.file_stem(&capture(Some("\n  Meeting notes: Q1 planning!\nmore")));
+        assert_eq!(stem, "2024-03-01-meeting-notes-q1-planning");
+    }
+
+    #[test]
+    fn test_falls_back_to_kind_without_text() {
+        let naming = CaptureNaming {
+            template: "{kind} {date} {time} #{counter}".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(naming.file_stem(&capture(None)), "clipboard 2024-03-01 130509 #3");
+    }
+
+    #[test]
+    fn test_offset_and_title_length() {
+        let naming = CaptureNaming {
+            template: "{date}_{title}".to_string(),
+            max_title_len: 12,
+            utc_offset_minutes: 12 * 60,
+        };
+        assert_eq!(
+            naming.file_stem(&capture(Some("quarterly budget review"))),
+            "2024-03-02_quarterly"
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CaptureNaming::default().validate().is_ok());
+        let bad = CaptureNaming {
+            template: "{date}-{author}".to_string(),
+            ..Default::default()
+        };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_sanitizes_path_separators() {
+        let naming = CaptureNaming::default();
+        let stem = naming.file_stem(&Capture {
+            kind: "a/b",
+            text: None,
+            captured_at: 0,
+            counter: 0,
+        });
+        assert_eq!(stem, "1970-01-01-a-b");
+    }
+}
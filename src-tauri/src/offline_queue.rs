@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::ledger::now_secs;
+use crate::persist::JsonStore;
+use crate::query::MutationOperation;
+
+/// Items kept at most; the oldest are dropped first
+const MAX_ITEMS: usize = 100;
+
+/// Replays that fail for a reason other than connectivity are given up on
+/// after this many attempts
+pub const MAX_ATTEMPTS: u32 = 3;
+
+/// A query or mutation that doesn't need an answer right away, so it can
+/// wait for the backend to be reachable.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueuedRequest {
+    Query {
+        query: String,
+    },
+    Mutation {
+        schema: String,
+        operation: String,
+        data: Value,
+    },
+}
+
+impl QueuedRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Query { query } if query.trim().is_empty() => {
+                Err("Query text can't be empty".to_string())
+            }
+            Self::Query { .. } => Ok(()),
+            Self::Mutation {
+                schema,
+                operation,
+                data,
+            } => {
+                MutationOperation::parse(operation)?;
+                if schema.trim().is_empty() {
+                    return Err("Mutation needs a schema name".to_string());
+                }
+                if !data.is_object() {
+                    return Err("Mutation data must be a JSON object".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn is_delete(&self) -> bool {
+        matches!(self, Self::Mutation { operation, .. }
+            if MutationOperation::parse(operation) == Ok(MutationOperation::Delete))
+    }
+
+    /// Short label for the activity log.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Query { query } => query.trim().to_string(),
+            Self::Mutation {
+                schema, operation, ..
+            } => format!("{} {}", operation.trim().to_lowercase(), schema.trim()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedItem {
+    /// Also the Idempotency-Key sent with every replay of a mutation
+    pub id: String,
+    pub request: QueuedRequest,
+    /// Seconds since the Unix epoch
+    pub queued_at: u64,
+    /// Replays that reached the backend but failed
+    pub attempts: u32,
+    pub last_error: Option<String>,
+}
+
+/// Persistent outbound queue, replayed in order once the backend is
+/// reachable again.
+pub struct OfflineQueue {
+    store: JsonStore<Vec<QueuedItem>>,
+}
+
+impl OfflineQueue {
+    const FILE: &'static str = "offline_queue.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "offline queue").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "offline queue").map(|store| Self { store })
+    }
+
+    /// Queued items, oldest first.
+    pub fn list(&self) -> Vec<QueuedItem> {
+        self.store.get().clone()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.get().is_empty()
+    }
+
+    pub fn push(&mut self, request: QueuedRequest) -> Result<QueuedItem, String> {
+        request.validate()?;
+        let item = QueuedItem {
+            id: uuid::Uuid::new_v4().to_string(),
+            request,
+            queued_at: now_secs(),
+            attempts: 0,
+            last_error: None,
+        };
+        self.store.update(|entries| {
+            entries.push(item.clone());
+            if entries.len() > MAX_ITEMS {
+                let excess = entries.len() - MAX_ITEMS;
+                entries.drain(..excess);
+            }
+        })?;
+        Ok(item)
+    }
+
+    /// Returns false if there was no item with that id.
+    pub fn remove(&mut self, id: &str) -> Result<bool, String> {
+        self.store.update(|entries| {
+            let before = entries.len();
+            entries.retain(|e| e.id != id);
+            entries.len() != before
+        })
+    }
+
+    /// Note a failed replay. Returns true once the item has used up its
+    /// attempts and was removed.
+    pub fn record_failure(&mut self, id: &str, error: &str) -> Result<bool, String> {
+        self.store.update(|entries| {
+            let Some(item) = entries.iter_mut().find(|e| e.id == id) else {
+                return false;
+            };
+            item.attempts += 1;
+            item.last_error = Some(error.to_string());
+            let exhausted = item.attempts >= MAX_ATTEMPTS;
+            if exhausted {
+                entries.retain(|e| e.id != id);
+            }
+            exhausted
+        })
+    }
+}
+
+/// Whether the backend answered the last time anything talked to it.
+pub struct Connectivity {
+    online: AtomicBool,
+}
+
+impl Default for Connectivity {
+    fn default() -> Self {
+        Self {
+            online: AtomicBool::new(true),
+        }
+    }
+}
+
+impl Connectivity {
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    /// Returns true if this changed the state.
+    pub fn set_online(&self, online: bool) -> bool {
+        self.online.swap(online, Ordering::SeqCst) != online
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn open(path: PathBuf) -> OfflineQueue {
+        OfflineQueue {
+            store: JsonStore::load_from(path, "offline queue").unwrap(),
+        }
+    }
+
+    fn queue() -> OfflineQueue {
+        open(std::env::temp_dir().join(format!("exemem-offline-{}.json", uuid::Uuid::new_v4())))
+    }
+
+    #[test]
+    fn test_push_validates_and_replays_in_order() {
+        let mut queue = queue();
+        assert!(queue
+            .push(QueuedRequest::Query {
+                query: "  ".to_string()
+            })
+            .is_err());
+        assert!(queue
+            .push(QueuedRequest::Mutation {
+                schema: "Notes".to_string(),
+                operation: "drop".to_string(),
+                data: json!({}),
+            })
+            .is_err());
+
+        let first = queue
+            .push(QueuedRequest::Query {
+                query: "trips".to_string(),
+            })
+            .unwrap();
+        let second = queue
+            .push(QueuedRequest::Mutation {
+                schema: "Notes".to_string(),
+                operation: "Delete".to_string(),
+                data: json!({"id": "n1"}),
+            })
+            .unwrap();
+        assert!(second.request.is_delete());
+        assert_eq!(second.request.describe(), "delete Notes");
+
+        let reloaded = open(queue.store.path().to_path_buf());
+        let ids: Vec<String> = reloaded.list().into_iter().map(|i| i.id).collect();
+        assert_eq!(ids, vec![first.id.clone(), second.id.clone()]);
+
+        assert!(!queue.record_failure(&first.id, "bad request").unwrap());
+        assert!(!queue.record_failure(&first.id, "bad request").unwrap());
+        assert!(queue.record_failure(&first.id, "bad request").unwrap());
+        assert_eq!(queue.list().len(), 1);
+        assert!(queue.remove(&second.id).unwrap());
+        assert!(queue.is_empty());
+        let _ = std::fs::remove_file(queue.store.path());
+    }
+
+    #[test]
+    fn test_connectivity_reports_changes() {
+        let connectivity = Connectivity::default();
+        assert!(connectivity.is_online());
+        assert!(connectivity.set_online(false));
+        assert!(!connectivity.set_online(false));
+        assert!(connectivity.set_online(true));
+    }
+}
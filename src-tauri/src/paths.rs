@@ -0,0 +1,68 @@
+use directories::ProjectDirs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Environment variable naming a directory that holds all client state
+pub const DATA_DIR_ENV: &str = "EXEMEM_DATA_DIR";
+
+/// Directory created next to the executable in portable mode
+const PORTABLE_DIR_NAME: &str = "exemem-data";
+
+static ROOT_OVERRIDE: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Decide where this process keeps its state: `EXEMEM_DATA_DIR` if set,
+/// otherwise next to the executable when `portable` is true, otherwise the
+/// platform's standard directories.
+///
+/// The choice is made once per process so every subsystem agrees on it;
+/// later calls return the root picked by the first one.
+pub fn init(portable: bool) -> Option<PathBuf> {
+    ROOT_OVERRIDE
+        .get_or_init(|| resolve_override(portable))
+        .clone()
+}
+
+fn resolve_override(portable: bool) -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os(DATA_DIR_ENV).filter(|v| !v.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    if portable {
+        return std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(PORTABLE_DIR_NAME)));
+    }
+    None
+}
+
+fn root_override() -> Option<PathBuf> {
+    init(false)
+}
+
+fn project_dirs() -> Result<ProjectDirs, String> {
+    ProjectDirs::from("ai", "exemem", "exemem-client")
+        .ok_or_else(|| "Could not determine config directory".to_string())
+}
+
+/// Directory holding config.json.
+pub fn config_dir() -> Result<PathBuf, String> {
+    match root_override() {
+        Some(root) => Ok(root.join("config")),
+        None => Ok(project_dirs()?.config_dir().to_path_buf()),
+    }
+}
+
+/// Directory for local state other than config (ledger, caches, history).
+pub fn data_dir() -> Result<PathBuf, String> {
+    match root_override() {
+        Some(root) => Ok(root.join("data")),
+        None => Ok(project_dirs()?.data_dir().to_path_buf()),
+    }
+}
+
+/// Directory for the app's log file, shared with the CLI's `logs` command.
+pub fn log_dir() -> Result<PathBuf, String> {
+    match root_override() {
+        Some(root) => Ok(root.join("logs")),
+        None => Ok(project_dirs()?.data_local_dir().join("logs")),
+    }
+}
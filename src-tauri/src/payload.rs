@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Payloads up to this size are returned inline over IPC
+const INLINE_LIMIT: usize = 1024 * 1024;
+
+/// Largest chunk `read` hands out at once
+const MAX_CHUNK: u64 = 4 * 1024 * 1024;
+
+/// Handed-off payloads nobody released are removed after this long
+const PAYLOAD_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Reference to a payload written to disk instead of sent over IPC. The
+/// frontend reads it with `read_payload` and frees it with `release_payload`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PayloadHandle {
+    pub payload_handle: String,
+    /// Size of the serialized JSON in bytes
+    pub size: u64,
+}
+
+/// A command result that is either the value itself or a handle to it.
+/// Inline values serialize exactly like `T`, so callers that never ask for
+/// a handoff see no difference.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Payload<T> {
+    Inline(T),
+    Handoff(PayloadHandle),
+}
+
+struct StoredPayload {
+    path: PathBuf,
+    size: u64,
+    created: Instant,
+}
+
+/// Temporary files backing handed-off payloads.
+pub struct PayloadStore {
+    dir: PathBuf,
+    inline_limit: usize,
+    payloads: Mutex<HashMap<String, StoredPayload>>,
+}
+
+impl Default for PayloadStore {
+    fn default() -> Self {
+        let dir = std::env::temp_dir().join(format!("exemem-payloads-{}", std::process::id()));
+        Self::new(dir, INLINE_LIMIT)
+    }
+}
+
+impl PayloadStore {
+    pub fn new(dir: PathBuf, inline_limit: usize) -> Self {
+        // Files from an earlier process with the same pid are stale
+        let _ = std::fs::remove_dir_all(&dir);
+        Self {
+            dir,
+            inline_limit,
+            payloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return `value` inline, or write it to disk and return a handle when
+    /// the caller accepts a handoff and the serialized value is too large.
+    pub fn wrap<T: Serialize>(&self, value: T, handoff: bool) -> Result<Payload<T>, String> {
+        if !handoff {
+            return Ok(Payload::Inline(value));
+        }
+        let bytes = serde_json::to_vec(&value)
+            .map_err(|e| format!("Failed to serialize payload: {}", e))?;
+        if bytes.len() <= self.inline_limit {
+            return Ok(Payload::Inline(value));
+        }
+        Ok(Payload::Handoff(self.store(bytes)?))
+    }
+
+    fn store(&self, bytes: Vec<u8>) -> Result<PayloadHandle, String> {
+        self.prune_expired();
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create payload dir: {}", e))?;
+
+        let handle = uuid::Uuid::new_v4().to_string();
+        let path = self.dir.join(format!("{}.json", handle));
+        std::fs::write(&path, &bytes).map_err(|e| format!("Failed to write payload: {}", e))?;
+
+        let size = bytes.len() as u64;
+        self.payloads.lock().unwrap().insert(
+            handle.clone(),
+            StoredPayload {
+                path,
+                size,
+                created: Instant::now(),
+            },
+        );
+        Ok(PayloadHandle {
+            payload_handle: handle,
+            size,
+        })
+    }
+
+    /// Up to `len` bytes of a payload starting at `offset`.
+    pub fn read(&self, handle: &str, offset: u64, len: u64) -> Result<Vec<u8>, String> {
+        let (path, size) = {
+            let payloads = self.payloads.lock().unwrap();
+            let stored = payloads
+                .get(handle)
+                .ok_or_else(|| format!("Unknown or expired payload: {}", handle))?;
+            (stored.path.clone(), stored.size)
+        };
+        let offset = offset.min(size);
+        let len = len.min(MAX_CHUNK).min(size - offset);
+
+        let mut file =
+            std::fs::File::open(&path).map_err(|e| format!("Failed to open payload: {}", e))?;
+        file.seek(SeekFrom::Start(offset))
+            .map_err(|e| format!("Failed to read payload: {}", e))?;
+        let mut buf = Vec::with_capacity(len as usize);
+        file.take(len)
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("Failed to read payload: {}", e))?;
+        Ok(buf)
+    }
+
+    /// Delete a payload once the frontend has read it. Returns false if the
+    /// handle is unknown.
+    pub fn release(&self, handle: &str) -> bool {
+        match self.payloads.lock().unwrap().remove(handle) {
+            Some(stored) => {
+                let _ = std::fs::remove_file(&stored.path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn prune_expired(&self) {
+        self.payloads.lock().unwrap().retain(|_, stored| {
+            let keep = stored.created.elapsed() < PAYLOAD_TTL;
+            if !keep {
+                let _ = std::fs::remove_file(&stored.path);
+            }
+            keep
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_store(inline_limit: usize) -> PayloadStore {
+        let dir = std::env::temp_dir().join(format!("exemem-payload-test-{}", uuid::Uuid::new_v4()));
+        PayloadStore::new(dir, inline_limit)
+    }
+
+    #[test]
+    fn test_small_or_unrequested_payloads_stay_inline() {
+        let store = temp_store(100);
+        assert!(matches!(store.wrap(json!({"a": 1}), true).unwrap(), Payload::Inline(_)));
+        assert!(matches!(store.wrap("x".repeat(500), false).unwrap(), Payload::Inline(_)));
+        assert_eq!(
+            serde_json::to_value(store.wrap(json!({"a": 1}), true).unwrap()).unwrap(),
+            json!({"a": 1})
+        );
+    }
+
+    #[test]
+    fn test_large_payload_read_in_chunks() {
+        let store = temp_store(10);
+        let value = json!({"results": (0..100).collect::<Vec<_>>()});
+        let handle = match store.wrap(&value, true).unwrap() {
+            Payload::Handoff(handle) => handle,
+            Payload::Inline(_) => panic!("expected handoff"),
+        };
+
+        let mut bytes = Vec::new();
+        let mut offset = 0;
+        while offset < handle.size {
+            let chunk = store.read(&handle.payload_handle, offset, 64).unwrap();
+            offset += chunk.len() as u64;
+            bytes.extend(chunk);
+        }
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(), value);
+        assert!(store.read(&handle.payload_handle, handle.size, 64).unwrap().is_empty());
+
+        assert!(store.release(&handle.payload_handle));
+        assert!(store.read(&handle.payload_handle, 0, 64).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::persist::JsonStore;
+use crate::scanner::FileRecommendation;
+
+/// A watched file the scanner recommends, held until the user approves or
+/// rejects it because `auto_approve_watched` is off.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub path: PathBuf,
+    pub recommendation: FileRecommendation,
+    /// Seconds since the Unix epoch; the latest change if it changed again
+    pub detected_at: u64,
+}
+
+/// Persistent list of files waiting for approval, keyed by file path so a
+/// file modified again while it waits is listed once.
+pub struct PendingApprovals {
+    store: JsonStore<Vec<PendingApproval>>,
+}
+
+impl PendingApprovals {
+    const FILE: &'static str = "pending_approvals.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "pending approvals").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "pending approvals").map(|store| Self { store })
+    }
+
+    /// Oldest first.
+    pub fn list(&self) -> Vec<PendingApproval> {
+        self.store.get().clone()
+    }
+
+    pub fn add(
+        &mut self,
+        recommendation: FileRecommendation,
+        detected_at: u64,
+    ) -> Result<(), String> {
+        self.store.update(|entries| {
+            let path = recommendation.absolute_path.clone();
+            entries.retain(|e| e.path != path);
+            entries.push(PendingApproval {
+                path,
+                recommendation,
+                detected_at,
+            });
+        })
+    }
+
+    /// Remove and return the entries for `paths`, or every entry if `paths`
+    /// is None. Paths that aren't waiting are ignored.
+    pub fn take(&mut self, paths: Option<&[PathBuf]>) -> Result<Vec<PendingApproval>, String> {
+        let wanted = |e: &PendingApproval| match paths {
+            Some(paths) => paths.contains(&e.path),
+            None => true,
+        };
+        self.store.update(|entries| {
+            let (taken, waiting): (Vec<_>, Vec<_>) =
+                std::mem::take(entries).into_iter().partition(wanted);
+            *entries = waiting;
+            taken
+        })
+    }
+
+    /// Drop entries whose files are gone, e.g. deleted before anyone
+    /// looked. Returns how many were dropped.
+    pub fn prune_missing(&mut self) -> Result<usize, String> {
+        self.store.update(|entries| {
+            let before = entries.len();
+            entries.retain(|e| e.path.exists());
+            before - entries.len()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn recommendation(path: &Path) -> FileRecommendation {
+        FileRecommendation {
+            path: path.file_name().unwrap().to_string_lossy().to_string(),
+            absolute_path: path.to_path_buf(),
+            should_ingest: true,
+            category: "notes".to_string(),
+            reason: "Markdown".to_string(),
+            size_bytes: None,
+            converter: None,
+        }
+    }
+
+    #[test]
+    fn test_dedupes_by_path_and_survives_reload() {
+        let dir = std::env::temp_dir().join(format!("exemem-pending-{}", uuid::Uuid::new_v4()));
+        let file = dir.join("pending.json");
+        let open = || PendingApprovals {
+            store: JsonStore::load_from(file.clone(), "pending approvals").unwrap(),
+        };
+        let mut pending = open();
+        let (a, b) = (Path::new("/tmp/a.md"), Path::new("/tmp/b.md"));
+        pending.add(recommendation(a), 100).unwrap();
+        pending.add(recommendation(b), 110).unwrap();
+        pending.add(recommendation(a), 120).unwrap();
+
+        let mut reloaded = open();
+        let listed: Vec<_> = reloaded
+            .list()
+            .into_iter()
+            .map(|e| (e.path, e.detected_at))
+            .collect();
+        assert_eq!(listed, [(b.to_path_buf(), 110), (a.to_path_buf(), 120)]);
+
+        let taken = reloaded
+            .take(Some(&[a.to_path_buf(), PathBuf::from("/tmp/c.md")]))
+            .unwrap();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(reloaded.take(None).unwrap().len(), 1);
+        assert!(reloaded.list().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::paths;
+
+/// Read a JSON state file, returning the default value if it doesn't exist yet.
+pub fn load_json<T: DeserializeOwned + Default>(path: &Path, what: &str) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", what, e))?;
+    serde_json::from_str(&data).map_err(|e| format!("Failed to parse {}: {}", what, e))
+}
+
+/// Write a JSON state file, creating its directory if needed.
+pub fn save_json<T: Serialize>(path: &Path, value: &T, what: &str) -> Result<(), String> {
+    write_json(path, &to_json(value, what)?, what)
+}
+
+fn to_json<T: Serialize>(value: &T, what: &str) -> Result<String, String> {
+    serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize {}: {}", what, e))
+}
+
+/// Write through a temporary file, so a reader never sees half a file.
+fn write_json(path: &Path, data: &str, what: &str) -> Result<(), String> {
+    create_parent(path)?;
+    let temp = sibling(path, ".tmp");
+    std::fs::write(&temp, data).map_err(|e| format!("Failed to write {}: {}", what, e))?;
+    std::fs::rename(&temp, path).map_err(|e| format!("Failed to write {}: {}", what, e))
+}
+
+fn create_parent(path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create data dir: {}", e))?;
+    }
+    Ok(())
+}
+
+/// `path` with `suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Take an exclusive lock on `path`'s lock file, waiting for other
+/// processes to release it. Held until the returned file is dropped.
+fn lock(path: &Path, what: &str) -> Result<std::fs::File, String> {
+    create_parent(path)?;
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(sibling(path, ".lock"))
+        .map_err(|e| format!("Failed to lock {}: {}", what, e))?;
+    fs2::FileExt::lock_exclusive(&file).map_err(|e| format!("Failed to lock {}: {}", what, e))?;
+    Ok(file)
+}
+
+/// A JSON state file in the data dir and its contents, written back
+/// whenever they change.
+///
+/// The app and the CLI can have the same file open at once, so every
+/// update starts from what is on disk, not from the copy loaded earlier.
+pub struct JsonStore<T> {
+    path: PathBuf,
+    what: &'static str,
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned + Default> JsonStore<T> {
+    /// Load `file_name` from the data dir; `what` names it in errors.
+    pub fn load(file_name: &str, what: &'static str) -> Result<Self, String> {
+        Self::load_from(paths::data_dir()?.join(file_name), what)
+    }
+
+    pub fn load_from(path: PathBuf, what: &'static str) -> Result<Self, String> {
+        let value = load_json(&path, what)?;
+        Ok(Self { path, what, value })
+    }
+
+    /// Start over with default contents at `file_name` in the data dir, for
+    /// when the file can't be read; the first change replaces it. Fails
+    /// without a data dir rather than writing somewhere else.
+    pub fn empty(file_name: &str, what: &'static str) -> Result<Self, String> {
+        Ok(Self {
+            path: paths::data_dir()?.join(file_name),
+            what,
+            value: T::default(),
+        })
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Re-read the file under an exclusive lock, apply `change` and write
+    /// the result, unless it left the contents as they were. A file that
+    /// can't be parsed is replaced with the change applied to the copy in
+    /// memory.
+    pub fn update<R>(&mut self, change: impl FnOnce(&mut T) -> R) -> Result<R, String> {
+        let _lock = lock(&self.path, self.what)?;
+        let before = match std::fs::read_to_string(&self.path) {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(format!("Failed to read {}: {}", self.what, e)),
+        };
+        match before.as_deref().map(serde_json::from_str::<T>) {
+            Some(Ok(value)) => self.value = value,
+            Some(Err(e)) => log::warn!("Failed to parse {}, replacing it: {}", self.what, e),
+            None => self.value = T::default(),
+        }
+
+        let result = change(&mut self.value);
+        let after = to_json(&self.value, self.what)?;
+        if before.as_deref() != Some(after.as_str()) {
+            write_json(&self.path, &after, self.what)?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_writes_only_changes() {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-persist-{}", uuid::Uuid::new_v4()))
+            .join("store.json");
+        let mut store: JsonStore<Vec<u32>> = JsonStore::load_from(path.clone(), "numbers").unwrap();
+
+        assert!(store.update(|v| v.is_empty()).unwrap());
+        assert!(!path.exists());
+
+        store.update(|v| v.push(7)).unwrap();
+        let reloaded: JsonStore<Vec<u32>> = JsonStore::load_from(path.clone(), "numbers").unwrap();
+        assert_eq!(reloaded.get(), &[7]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_updates_from_two_copies_are_merged() {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-persist-{}", uuid::Uuid::new_v4()))
+            .join("store.json");
+        let open = || JsonStore::<Vec<u32>>::load_from(path.clone(), "numbers").unwrap();
+        // Like the app and the CLI, each loaded before the other wrote
+        let (mut app, mut cli) = (open(), open());
+
+        app.update(|v| v.push(1)).unwrap();
+        cli.update(|v| v.push(2)).unwrap();
+        app.update(|v| v.push(3)).unwrap();
+
+        assert_eq!(open().get(), &[1, 2, 3]);
+        assert_eq!(app.get(), &[1, 2, 3]);
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
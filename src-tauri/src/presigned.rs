@@ -0,0 +1,264 @@
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+/// Bucket assumed when neither the response nor the URL names one
+const DEFAULT_BUCKET: &str = "exemem-user-data";
+
+/// Where to PUT a file, as returned by the upload-url endpoint.
+///
+/// Self-hosted deployments (MinIO and other S3-compatible stores behind a
+/// Custom environment) don't always answer in the hosted API's exact shape,
+/// so common field spellings, a `data`/`result` envelope, relative URLs and
+/// path-style addressing are all accepted.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PresignedUrlResponse {
+    #[serde(alias = "uploadUrl", alias = "presigned_url", alias = "url")]
+    pub upload_url: String,
+    #[serde(alias = "s3Key", alias = "object_key", alias = "key")]
+    pub s3_key: String,
+    #[serde(default, alias = "s3Bucket", alias = "bucket")]
+    pub s3_bucket: Option<String>,
+    /// Echoed back when the server accepted our requested content encoding
+    #[serde(default, alias = "contentEncoding")]
+    pub content_encoding: Option<String>,
+    /// Seconds until the upload URL stops working
+    #[serde(default, alias = "expiresIn")]
+    pub expires_in: Option<u64>,
+}
+
+impl PresignedUrlResponse {
+    /// Parse an upload-url response. `api_url` resolves relative upload URLs.
+    pub fn parse(body: &str, api_url: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(body)
+            .map_err(|e| format!("Failed to parse presigned URL response: {}", e))?;
+        let payload = ["data", "result"]
+            .iter()
+            .filter_map(|field| json.get(field))
+            .find(|inner| inner.is_object())
+            .unwrap_or(&json);
+
+        let mut response: Self = serde_json::from_value(payload.clone())
+            .map_err(|e| format!("Failed to parse presigned URL response: {}", e))?;
+
+        let url = resolve_url(&response.upload_url, api_url)?;
+        if response.expires_in.is_none() {
+            response.expires_in = amz_expires(&url);
+        }
+        if response.s3_bucket.as_deref().map_or(true, str::is_empty) {
+            response.s3_bucket = infer_bucket(&url, &response.s3_key);
+        }
+        response.upload_url = url.to_string();
+        Ok(response)
+    }
+
+    pub fn bucket(&self) -> String {
+        self.s3_bucket
+            .clone()
+            .unwrap_or_else(|| DEFAULT_BUCKET.to_string())
+    }
+}
+
+fn resolve_url(upload_url: &str, api_url: &str) -> Result<Url, String> {
+    match Url::parse(upload_url) {
+        Ok(url) => Ok(url),
+        Err(url::ParseError::RelativeUrlWithoutBase) => Url::parse(api_url)
+            .and_then(|base| base.join(upload_url))
+            .map_err(|e| format!("Invalid upload URL {}: {}", upload_url, e)),
+        Err(e) => Err(format!("Invalid upload URL {}: {}", upload_url, e)),
+    }
+}
+
+/// Lifetime from a SigV4 query string, when the server didn't say.
+fn amz_expires(url: &Url) -> Option<u64> {
+    url.query_pairs()
+        .find(|(name, _)| name.eq_ignore_ascii_case("X-Amz-Expires"))
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// Bucket named by the URL: the first path segment for path-style URLs
+/// (`http://minio:9000/bucket/key`), or the first host label for
+/// virtual-hosted ones (`https://bucket.s3.amazonaws.com/key`).
+fn infer_bucket(url: &Url, key: &str) -> Option<String> {
+    let path = url.path().trim_start_matches('/');
+    let key = key.trim_start_matches('/');
+
+    if let Some((first, rest)) = path.split_once('/') {
+        if !first.is_empty() && percent_decode(rest) == key {
+            return Some(first.to_string());
+        }
+    }
+
+    let host = url.host_str()?;
+    let (label, domain) = host.split_once('.')?;
+    (domain.starts_with("s3.") || domain.starts_with("s3-") || domain.contains(".s3."))
+        .then(|| label.to_string())
+}
+
+fn percent_decode(value: &str) -> String {
+    url::form_urlencoded::parse(format!("k={}", value.replace('+', "%2B")).as_bytes())
+        .next()
+        .map(|(_, v)| v.into_owned())
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const API: &str = "https://exemem.internal.example";
+
+    #[test]
+    fn test_parse_hosted_shape() {
+        let body = r#"{
+            "upload_url": "https://exemem-user-data.s3.amazonaws.com/u/1/a.json?X-Amz-Expires=600",
+            "s3_key": "u/1/a.json",
+            "s3_bucket": "exemem-user-data"
+        }"#;
+        let resp = PresignedUrlResponse::parse(body, API).unwrap();
+        assert_eq!(resp.bucket(), "exemem-user-data");
+        assert_eq!(resp.expires_in, Some(600));
+    }
+
+    #[test]
+    fn test_parse_minio_path_style_in_envelope() {
+        let body = r#"{"ok": true, "data": {
+            "url": "http://localhost:9000/uploads/u/1/my%20file.json?X-Amz-Signature=abc",
+            "key": "u/1/my file.json"
+        }}"#;
+        let resp = PresignedUrlResponse::parse(body, API).unwrap();
+        assert_eq!(resp.bucket(), "uploads");
+        assert_eq!(resp.s3_key, "u/1/my file.json");
+        assert!(resp.upload_url.starts_with("http://localhost:9000/uploads/"));
+    }
+
+    #[test]
+    fn test_parse_relative_url_and_virtual_host() {
+        let body = r#"{"uploadUrl": "/storage/u/1/a.json?sig=1", "s3Key": "u/1/a.json"}"#;
+        let resp = PresignedUrlResponse::parse(body, API).unwrap();
+        assert_eq!(resp.upload_url, "https://exemem.internal.example/storage/u/1/a.json?sig=1");
+        assert_eq!(resp.bucket(), "storage");
+
+        let body = r#"{"upload_url": "https://photos.s3.us-west-2.amazonaws.com/k", "s3_key": "other"}"#;
+        assert_eq!(PresignedUrlResponse::parse(body, API).unwrap().bucket(), "photos");
+
+        let body = r#"{"upload_url": "https://cdn.example.com/x", "s3_key": "other"}"#;
+        assert_eq!(PresignedUrlResponse::parse(body, API).unwrap().bucket(), DEFAULT_BUCKET);
+    }
+
+    #[test]
+    fn test_parse_missing_fields() {
+        assert!(PresignedUrlResponse::parse(r#"{"ok": true}"#, API).is_err());
+        assert!(PresignedUrlResponse::parse("<html>", API).is_err());
+    }
+}
+
+/// Upload through each response shape to a real MinIO, e.g. one started with
+/// `docker run -p 9000:9000 minio/minio server /data`:
+///
+/// ```text
+/// EXEMEM_TEST_MINIO_URL=http://localhost:9000 cargo test --features direct-s3 minio -- --ignored
+/// ```
+///
+/// Credentials default to MinIO's `minioadmin`; override them with
+/// `EXEMEM_TEST_MINIO_ACCESS_KEY` and `EXEMEM_TEST_MINIO_SECRET_KEY`.
+#[cfg(all(test, feature = "direct-s3"))]
+mod minio_tests {
+    use super::*;
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+    use aws_sdk_s3::presigning::PresigningConfig;
+    use std::time::Duration;
+
+    const BUCKET: &str = "exemem-presigned-test";
+    const CONTENT_TYPE: &str = "application/json";
+    const EXPIRES_IN: u64 = 300;
+
+    fn env_or(name: &str, default: &str) -> String {
+        std::env::var(name).unwrap_or_else(|_| default.to_string())
+    }
+
+    async fn minio() -> (String, aws_sdk_s3::Client) {
+        let endpoint = std::env::var("EXEMEM_TEST_MINIO_URL")
+            .expect("set EXEMEM_TEST_MINIO_URL to run the MinIO tests");
+        let credentials = Credentials::new(
+            env_or("EXEMEM_TEST_MINIO_ACCESS_KEY", "minioadmin"),
+            env_or("EXEMEM_TEST_MINIO_SECRET_KEY", "minioadmin"),
+            None,
+            None,
+            "exemem-test",
+        );
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(credentials)
+            .endpoint_url(&endpoint)
+            .force_path_style(true)
+            .build();
+        let client = aws_sdk_s3::Client::from_conf(config);
+        // Fails if an earlier run already created it
+        let _ = client.create_bucket().bucket(BUCKET).send().await;
+        (endpoint, client)
+    }
+
+    /// A path-style presigned PUT URL, as a self-hosted server would hand out.
+    async fn presign_put(client: &aws_sdk_s3::Client, key: &str) -> String {
+        let presigning = PresigningConfig::expires_in(Duration::from_secs(EXPIRES_IN)).unwrap();
+        client
+            .put_object()
+            .bucket(BUCKET)
+            .key(key)
+            .content_type(CONTENT_TYPE)
+            .presigned(presigning)
+            .await
+            .unwrap()
+            .uri()
+            .to_string()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a MinIO server; set EXEMEM_TEST_MINIO_URL"]
+    async fn test_minio_upload_through_each_shape() {
+        let (endpoint, client) = minio().await;
+        let run = uuid::Uuid::new_v4();
+
+        type Shape = fn(&str, &str) -> Value;
+        let shapes: [(&str, Shape); 3] = [
+            ("envelope", |url, key| serde_json::json!({"ok": true, "data": {"url": url, "key": key}})),
+            ("result", |url, key| {
+                serde_json::json!({"result": {"presigned_url": url, "object_key": key}})
+            }),
+            ("relative", |url, key| {
+                let url = Url::parse(url).unwrap();
+                let relative = format!("{}?{}", url.path(), url.query().unwrap_or(""));
+                serde_json::json!({"uploadUrl": relative, "s3Key": key})
+            }),
+        ];
+
+        for (name, shape) in shapes {
+            // A space checks the key survives re-encoding the URL
+            let key = format!("u/{}/{} notes.json", run, name);
+            let url = presign_put(&client, &key).await;
+            let body = shape(&url, &key).to_string();
+
+            let resp = PresignedUrlResponse::parse(&body, &endpoint).unwrap();
+            assert_eq!(resp.bucket(), BUCKET, "{}", name);
+            assert_eq!(resp.s3_key, key, "{}", name);
+            assert_eq!(resp.expires_in, Some(EXPIRES_IN), "{}", name);
+
+            let content = format!(r#"{{"shape": "{}"}}"#, name);
+            let put = reqwest::Client::new()
+                .put(&resp.upload_url)
+                .header("Content-Type", CONTENT_TYPE)
+                .body(content.clone())
+                .send()
+                .await
+                .unwrap();
+            let status = put.status();
+            assert!(status.is_success(), "{}: {} {}", name, status, put.text().await.unwrap_or_default());
+
+            let stored = client.get_object().bucket(BUCKET).key(&key).send().await.unwrap();
+            let stored = stored.body.collect().await.unwrap().into_bytes();
+            assert_eq!(stored.as_ref(), content.as_bytes(), "{}", name);
+        }
+    }
+}
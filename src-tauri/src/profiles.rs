@@ -0,0 +1,231 @@
+use crate::config::{self, AppConfig};
+pub use crate::config::Environment;
+use crate::config_crypto;
+use crate::error::Error;
+use crate::paths;
+use crate::persist;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The account-specific part of the config, saved under a name so Dev and
+/// Prod setups can be switched without retyping keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Profile {
+    #[serde(default)]
+    pub environment: Environment,
+    /// Only used with the Custom environment
+    #[serde(default)]
+    pub api_base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub user_hash: Option<String>,
+    #[serde(default)]
+    pub watched_folder: Option<PathBuf>,
+}
+
+impl Profile {
+    pub(crate) fn from_config(config: &AppConfig) -> Self {
+        Self {
+            environment: config.environment.clone(),
+            api_base_url: config.api_base_url.clone(),
+            api_key: config.api_key.clone(),
+            user_hash: config.user_hash.clone(),
+            watched_folder: config.watched_folder.clone(),
+        }
+    }
+
+    pub(crate) fn apply(&self, config: &mut AppConfig) {
+        config.environment = self.environment.clone();
+        config.api_base_url = self.api_base_url.clone();
+        config.api_key = self.api_key.clone();
+        config.user_hash = self.user_hash.clone();
+        config.watched_folder = self.watched_folder.clone();
+    }
+
+    /// Overwrite this profile's fields in a config serialized as JSON, for
+    /// callers with their own config type.
+    pub fn apply_json(&self, config: &mut Value) -> Result<(), String> {
+        let Value::Object(fields) = serde_json::to_value(self).map_err(|e| e.to_string())? else {
+            return Err("Profile is not an object".to_string());
+        };
+        let target = config
+            .as_object_mut()
+            .ok_or_else(|| "Config is not an object".to_string())?;
+        target.extend(fields);
+        Ok(())
+    }
+}
+
+/// Listed by `list_profiles`. The API key is left out.
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfileSummary {
+    pub name: String,
+    pub environment: Environment,
+    pub watched_folder: Option<PathBuf>,
+    pub current: bool,
+}
+
+/// Named profiles and which one the config currently holds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profiles {
+    #[serde(default)]
+    pub current: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+impl Profiles {
+    fn path() -> Result<PathBuf, String> {
+        Ok(paths::config_dir()?.join("profiles.json"))
+    }
+
+    /// The file as stored, encrypted or not.
+    fn read_file() -> Result<Option<Value>, Error> {
+        persist::load_json(&Self::path().map_err(Error::Io)?, "profiles").map_err(Error::Io)
+    }
+
+    /// Load the profiles, decrypting them like the config when it has a
+    /// passphrase.
+    pub fn load() -> Result<Self, Error> {
+        let value = match Self::read_file()? {
+            Some(file) if config_crypto::is_encrypted(&file) => AppConfig::open_encrypted(&file)?,
+            Some(file) => file,
+            None => return Ok(Self::default()),
+        };
+        serde_json::from_value(value)
+            .map_err(|e| Error::Validation(format!("Failed to parse profiles: {}", e)))
+    }
+
+    /// Save the profiles, sealed with the config's key while it has a
+    /// passphrase, so their API keys aren't left on disk in the clear.
+    pub fn save(&self) -> Result<(), Error> {
+        let key = config_crypto::unlocked();
+        if key.is_none() && AppConfig::encryption()?.encrypted {
+            return Err(config::locked_error());
+        }
+        let value = serde_json::to_value(self)
+            .map_err(|e| Error::Internal(format!("Failed to serialize profiles: {}", e)))?;
+        let value = match key {
+            Some(key) => config_crypto::encrypt(&key, &value).map_err(Error::Internal)?,
+            None => value,
+        };
+        persist::save_json(&Self::path().map_err(Error::Io)?, &value, "profiles").map_err(Error::Io)
+    }
+
+    /// Re-save a profiles file written in the clear, now that the config
+    /// is unlocked.
+    pub(crate) fn seal() -> Result<(), Error> {
+        match Self::read_file()? {
+            Some(file) if !config_crypto::is_encrypted(&file) => Self::load()?.save(),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    pub fn summaries(&self) -> Vec<ProfileSummary> {
+        self.profiles
+            .iter()
+            .map(|(name, profile)| ProfileSummary {
+                name: name.clone(),
+                environment: profile.environment.clone(),
+                watched_folder: profile.watched_folder.clone(),
+                current: self.current.as_deref() == Some(name.as_str()),
+            })
+            .collect()
+    }
+
+    /// Save the config's account settings as `name`, replacing any profile
+    /// with that name, and mark it current.
+    pub(crate) fn save_current(&mut self, name: &str, config: &AppConfig) -> Result<(), String> {
+        let name = validate_name(name)?;
+        self.profiles.insert(name.to_string(), Profile::from_config(config));
+        self.current = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Load profile `name` into `config`. The settings being switched away
+    /// from are first saved back to the current profile, so edits made
+    /// since the last switch aren't lost.
+    pub(crate) fn switch(&mut self, name: &str, config: &mut AppConfig) -> Result<(), String> {
+        let target = self
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("No profile named '{}'", name))?;
+        if let Some(current) = self.current.clone() {
+            self.profiles.insert(current, Profile::from_config(config));
+        }
+        target.apply(config);
+        self.current = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        if self.current.as_deref() == Some(name) {
+            self.current = None;
+        }
+        self.profiles.remove(name).is_some()
+    }
+}
+
+fn validate_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err("Profile name must be 1 to 64 characters".to_string());
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(env: Environment, key: &str) -> AppConfig {
+        AppConfig {
+            environment: env,
+            api_key: key.to_string(),
+            watched_folder: Some(PathBuf::from(format!("/data/{}", key))),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_switch_keeps_edits_to_current_profile() {
+        let mut profiles = Profiles::default();
+        let mut active = config(Environment::Dev, "dev-key");
+        profiles.save_current("dev", &active).unwrap();
+        profiles.save_current("prod", &config(Environment::Prod, "prod-key")).unwrap();
+        profiles.current = Some("dev".to_string());
+
+        // Edited after the dev profile was saved
+        active.user_hash = Some("tester".to_string());
+        profiles.switch("prod", &mut active).unwrap();
+        assert_eq!(active.api_key, "prod-key");
+        assert_eq!(active.environment, Environment::Prod);
+        assert_eq!(active.user_hash, None);
+
+        profiles.switch("dev", &mut active).unwrap();
+        assert_eq!(active.user_hash.as_deref(), Some("tester"));
+        assert!(profiles.switch("missing", &mut active).is_err());
+
+        let summaries = profiles.summaries();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries[0].current && summaries[0].name == "dev");
+    }
+
+    #[test]
+    fn test_apply_json_overwrites_account_fields() {
+        let profile = Profile::from_config(&config(Environment::Prod, "prod-key"));
+        let mut json = serde_json::json!({"api_key": "old", "auto_ingest": false});
+        profile.apply_json(&mut json).unwrap();
+        assert_eq!(json["api_key"], "prod-key");
+        assert_eq!(json["environment"], "Prod");
+        assert_eq!(json["auto_ingest"], false);
+    }
+}
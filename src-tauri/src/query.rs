@@ -1,7 +1,20 @@
 use crate::config::AppConfig;
+use crate::error::Error;
+use crate::http::{self, ConnectionConfig, ProxyConfig, TlsConfig};
+use crate::query_cache::{cache_key, QueryCache};
+use crate::query_metrics::{CallRecord, QueryMetrics, QueryMetricsReport, TokenUsage};
+use crate::retry::{self, Retry, RetryError, RetryPolicy};
+use crate::search::{self, SearchHit};
+use crate::server_error::{Locale, ServerErrorCode};
+use crate::signing::{RequestSigner, SendSigned};
+use crate::transcripts::TranscriptTurn;
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// What we return to the frontend for run_query (ai_native_index endpoint)
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +22,18 @@ pub struct RunQueryResponse {
     pub session_id: String,
     pub ai_interpretation: String,
     pub raw_results: Vec<Value>,
+    /// Number of results the query produced, before any pagination
+    #[serde(default)]
+    pub total_results: usize,
+    /// More results are available via `get_more_results` or `get_full_results`
+    #[serde(default)]
+    pub has_more: bool,
+    /// Pass to `get_more_results` for the next page
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+    /// Tokens and cost, when the server reports them
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
 }
 
 /// What we return to the frontend for chat_followup
@@ -16,13 +41,144 @@ pub struct RunQueryResponse {
 pub struct ChatResponse {
     pub answer: String,
     pub context_used: bool,
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+/// Responses whose calls are counted in the query metrics.
+trait Measured {
+    /// Number of results and any usage the server reported
+    fn summary(&self) -> (usize, Option<TokenUsage>);
+}
+
+impl Measured for RunQueryResponse {
+    fn summary(&self) -> (usize, Option<TokenUsage>) {
+        (self.raw_results.len(), self.usage.clone())
+    }
+}
+
+impl Measured for ChatResponse {
+    fn summary(&self) -> (usize, Option<TokenUsage>) {
+        (0, self.usage.clone())
+    }
+}
+
+impl Measured for SearchResponse {
+    fn summary(&self) -> (usize, Option<TokenUsage>) {
+        (self.count, None)
+    }
+}
+
+impl Measured for SemanticSearchResponse {
+    fn summary(&self) -> (usize, Option<TokenUsage>) {
+        (self.count, None)
+    }
 }
 
 /// What we return to the frontend for search_index
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SearchResponse {
-    pub results: Vec<Value>,
+    /// Best matches first when the server reports scores
+    pub results: Vec<SearchHit>,
+    /// Number of matches, before any pagination
     pub count: usize,
+    #[serde(default)]
+    pub has_more: bool,
+    /// Pass to `get_more_results` for the next page
+    #[serde(default)]
+    pub next_cursor: Option<String>,
+    /// The filters the search was narrowed by
+    #[serde(default)]
+    pub filters: SearchFilters,
+}
+
+/// What we return for semantic_search: the documents nearest in meaning to
+/// the query text, most similar first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticSearchResponse {
+    /// `score` holds the similarity, from 0 (unrelated) to 1 (identical)
+    pub results: Vec<SearchHit>,
+    pub count: usize,
+}
+
+/// Queries, follow-ups, searches and idempotent mutations are tried three
+/// times when the backend is unreachable or answers with a 5xx
+const QUERY_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_millis(500),
+    max_busy_waits: 0,
+};
+
+/// Nearest documents returned when no limit is given
+pub const DEFAULT_SEMANTIC_LIMIT: usize = 20;
+/// Most nearest documents one semantic search may ask for
+const MAX_SEMANTIC_LIMIT: usize = 200;
+
+/// Narrows an index search. Unset fields don't filter.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SearchFilters {
+    /// Scanner category of the originating file, e.g. "personal_data"
+    #[serde(default)]
+    pub category: Option<String>,
+    /// Only records ingested at or after this time (seconds since the Unix epoch)
+    #[serde(default)]
+    pub ingested_after: Option<u64>,
+    /// Only records ingested before this time
+    #[serde(default)]
+    pub ingested_before: Option<u64>,
+    /// Only records from files whose name contains this text
+    #[serde(default)]
+    pub source_file: Option<String>,
+}
+
+impl SearchFilters {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Check the filters; `custom_categories` are accepted on top of the
+    /// built-in ones.
+    pub fn validate(&self, custom_categories: &[String]) -> Result<(), String> {
+        if let Some(category) = &self.category {
+            let known = crate::scanner::CATEGORIES.contains(&category.as_str())
+                || custom_categories.contains(category);
+            if !known {
+                let mut expected: Vec<&str> = crate::scanner::CATEGORIES.to_vec();
+                expected.extend(custom_categories.iter().map(String::as_str));
+                return Err(format!(
+                    "Unknown category {:?}; expected one of: {}",
+                    category,
+                    expected.join(", ")
+                ));
+            }
+        }
+        if let (Some(after), Some(before)) = (self.ingested_after, self.ingested_before) {
+            if after >= before {
+                return Err("The start of the date range must be before its end".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// Query parameters for the search endpoint.
+    fn query_params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(category) = &self.category {
+            params.push(("category", category.clone()));
+        }
+        if let Some(after) = self.ingested_after {
+            params.push(("ingested_after", after.to_string()));
+        }
+        if let Some(before) = self.ingested_before {
+            params.push(("ingested_before", before.to_string()));
+        }
+        if let Some(source_file) = self.source_file.as_deref().map(str::trim) {
+            if !source_file.is_empty() {
+                params.push(("source_file", source_file.to_string()));
+            }
+        }
+        params
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,15 +188,147 @@ pub struct MutateResponse {
     pub data: Option<Value>,
 }
 
+/// Kinds of mutation the server accepts.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MutationOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl MutationOperation {
+    /// Case-insensitive; "create" is accepted for insert.
+    pub fn parse(operation: &str) -> Result<Self, String> {
+        match operation.trim().to_lowercase().as_str() {
+            "insert" | "create" => Ok(Self::Insert),
+            "update" => Ok(Self::Update),
+            "delete" => Ok(Self::Delete),
+            other => Err(format!(
+                "Unknown mutation operation {:?}; use insert, update or delete",
+                other
+            )),
+        }
+    }
+
+    /// Whether applying it twice has the same effect as applying it once,
+    /// so a failed attempt can safely be repeated.
+    pub fn is_idempotent(&self) -> bool {
+        !matches!(self, Self::Insert)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Insert => "insert",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+}
+
+/// A schema in the user's database, as listed by `list_schemas`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaSummary {
+    pub name: String,
+    /// Server-side state, e.g. "approved"
+    #[serde(default)]
+    pub state: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaField {
+    pub name: String,
+    #[serde(default)]
+    pub field_type: Option<String>,
+    /// Everything else the server reports about the field
+    #[serde(default)]
+    pub details: Value,
+}
+
+/// Full definition of one schema, from `describe_schema`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaDescription {
+    pub name: String,
+    pub fields: Vec<SchemaField>,
+    /// The raw definition as the server sent it
+    #[serde(default)]
+    pub raw: Value,
+}
+
+/// A chat session the backend holds context for, from `list_sessions`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSession {
+    pub session_id: String,
+    pub title: Option<String>,
+    /// Seconds since the Unix epoch, if the server reports it
+    pub created_at: Option<u64>,
+}
+
+/// What the account has used of its plan, from `get_account_usage`. A
+/// limit is None when the plan doesn't have one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AccountUsage {
+    pub documents_ingested: u64,
+    pub documents_limit: Option<u64>,
+    pub storage_bytes: u64,
+    pub storage_limit_bytes: Option<u64>,
+    pub queries_used: u64,
+    pub queries_limit: Option<u64>,
+    /// When the query quota starts over, seconds since the Unix epoch
+    pub quota_resets_at: Option<u64>,
+}
+
+impl AccountUsage {
+    pub fn queries_remaining(&self) -> Option<u64> {
+        self.queries_limit.map(|limit| limit.saturating_sub(self.queries_used))
+    }
+
+    /// Quotas that are used up ("documents", "storage" or "queries"), which
+    /// is why the server starts refusing uploads or queries.
+    pub fn exhausted(&self) -> Vec<&'static str> {
+        [
+            ("documents", self.documents_ingested, self.documents_limit),
+            ("storage", self.storage_bytes, self.storage_limit_bytes),
+            ("queries", self.queries_used, self.queries_limit),
+        ]
+        .into_iter()
+        .filter(|(_, used, limit)| limit.is_some_and(|limit| *used >= limit))
+        .map(|(name, _, _)| name)
+        .collect()
+    }
+}
+
 /// Lightweight config adapter for CLI usage (avoids depending on full AppConfig)
 pub struct AdapterConfig {
     pub api_url: String,
     pub api_key: String,
     pub user_hash: Option<String>,
+    /// HMAC-sign requests instead of sending the API key
+    pub sign_requests: bool,
+    /// Custom scanner categories, accepted as search filters
+    pub custom_categories: Vec<String>,
+    /// Language for server error messages; defaults to the system locale
+    pub locale: Option<String>,
+}
+
+/// Credentials for one call: headers for every request, plus a signer when
+/// the environment signs requests.
+struct ApiAuth {
+    headers: reqwest::header::HeaderMap,
+    signer: Option<RequestSigner>,
+    /// Language to describe known server errors in
+    locale: Locale,
 }
 
 pub struct QueryClient {
     client: Client,
+    /// Used only when `query_cache` is enabled in the config
+    cache: QueryCache,
+    /// Longest a single request may take
+    timeout: Duration,
+    /// Where calls are counted, if anywhere
+    metrics: Option<Mutex<QueryMetrics>>,
 }
 
 impl Default for QueryClient {
@@ -51,17 +339,84 @@ impl Default for QueryClient {
 
 impl QueryClient {
     pub fn new() -> Self {
+        Self::with_network(&ProxyConfig::default(), &TlsConfig::default())
+    }
+
+    /// Client that goes through the given proxy and trusts the given CAs.
+    pub fn with_network(proxy: &ProxyConfig, tls: &TlsConfig) -> Self {
+        Self::with_client(http::build_client(proxy, tls, &ConnectionConfig::default()))
+    }
+
+    /// Use a client shared with the rest of the app, from `http::build_client`.
+    pub fn with_client(client: Client) -> Self {
         Self {
-            client: Client::builder()
-                .timeout(std::time::Duration::from_secs(120))
-                .build()
-                .expect("Failed to build HTTP client"),
+            client,
+            cache: QueryCache::default(),
+            timeout: crate::cancel::QUERY_TIMEOUT,
+            metrics: None,
         }
     }
 
-    fn build_headers(&self, api_key: &str, user_hash: Option<&str>) -> reqwest::header::HeaderMap {
+    /// Count queries, follow-ups and searches in `metrics`.
+    pub fn with_metrics(mut self, metrics: QueryMetrics) -> Self {
+        self.metrics = Some(Mutex::new(metrics));
+        self
+    }
+
+    /// Usage over the `days` most recent days, if metrics are being kept.
+    pub fn metrics_report(&self, days: usize) -> Option<QueryMetricsReport> {
+        let metrics = self.metrics.as_ref()?;
+        let metrics = metrics.lock().unwrap_or_else(|e| e.into_inner());
+        Some(metrics.report(crate::ledger::now_secs(), days))
+    }
+
+    /// Time a call and add it to the metrics. Cached responses never get
+    /// here, so they aren't counted.
+    async fn measured<T: Measured>(
+        &self,
+        kind: &'static str,
+        call: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        let Some(metrics) = &self.metrics else {
+            return call.await;
+        };
+        let started = Instant::now();
+        let result = call.await;
+        let (results, usage) = result.as_ref().map(Measured::summary).unwrap_or((0, None));
+        let record = CallRecord {
+            kind,
+            latency: started.elapsed(),
+            ok: result.is_ok(),
+            results,
+            usage,
+        };
+        let recorded = metrics
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .record(crate::ledger::now_secs(), &record);
+        if let Err(e) = recorded {
+            log::warn!("Failed to update query metrics: {}", e);
+        }
+        result
+    }
+
+    /// Give up on each request after `timeout` instead of the default two
+    /// minutes.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn build_auth(
+        &self,
+        api_key: &str,
+        user_hash: Option<&str>,
+        signer: Option<RequestSigner>,
+        locale: Option<&str>,
+    ) -> ApiAuth {
         let mut headers = reqwest::header::HeaderMap::new();
-        if !api_key.is_empty() {
+        // Signed requests carry a signature instead of the key
+        if !api_key.is_empty() && signer.is_none() {
             if let Ok(val) = reqwest::header::HeaderValue::from_str(api_key) {
                 headers.insert("X-API-Key", val);
             }
@@ -71,38 +426,97 @@ impl QueryClient {
                 headers.insert("X-User-Hash", val);
             }
         }
-        headers
+        ApiAuth {
+            headers,
+            signer,
+            locale: Locale::resolve(locale),
+        }
     }
 
-    fn headers_from_config(&self, config: &AppConfig) -> reqwest::header::HeaderMap {
-        self.build_headers(&config.api_key, config.user_hash.as_deref())
+    fn auth_from_config(&self, config: &AppConfig) -> ApiAuth {
+        self.build_auth(&config.api_key, config.user_hash.as_deref(), config.signer(), config.locale.as_deref())
     }
 
-    fn headers_from_adapter(&self, config: &AdapterConfig) -> reqwest::header::HeaderMap {
-        self.build_headers(&config.api_key, config.user_hash.as_deref())
+    fn auth_from_adapter(&self, config: &AdapterConfig) -> ApiAuth {
+        let signer = (config.sign_requests && !config.api_key.is_empty())
+            .then(|| RequestSigner::from_api_key(&config.api_key));
+        self.build_auth(&config.api_key, config.user_hash.as_deref(), signer, config.locale.as_deref())
     }
 
     /// Parse API response, check ok field, return raw JSON value for further extraction
-    fn parse_api_response(body: Value) -> Result<Value, String> {
+    fn parse_api_response(body: Value, locale: Locale) -> Result<Value, Error> {
         let ok = body.get("ok").and_then(|v| v.as_bool()).unwrap_or(false);
         if !ok {
             let error = body.get("error")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Unknown server error");
-            return Err(error.to_string());
+            // Translate known error codes into something the user can act on
+            let message = match ServerErrorCode::parse(error) {
+                Some(code) => code.describe(locale),
+                None => error.to_string(),
+            };
+            return Err(Error::Server {
+                status: None,
+                message,
+            });
         }
         Ok(body)
     }
 
+    /// Serve `fetch` from the response cache when it is enabled, unless
+    /// `force_refresh` is set; fresh responses are cached either way.
+    async fn cached<T, F>(
+        &self,
+        config: &AppConfig,
+        kind: &str,
+        text: &str,
+        force_refresh: bool,
+        fetch: F,
+    ) -> Result<T, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        F: Future<Output = Result<T, Error>>,
+    {
+        if !config.query_cache.enabled {
+            return fetch.await;
+        }
+        let user = config.user_hash.as_deref().unwrap_or(&config.api_key);
+        let key = cache_key(kind, config.api_url(), user, text);
+        if !force_refresh {
+            let ttl = Duration::from_secs(config.query_cache.ttl_secs);
+            if let Some(hit) = self.cache.get(&key, ttl).and_then(|v| serde_json::from_value(v).ok()) {
+                return Ok(hit);
+            }
+        }
+        let fresh = fetch.await?;
+        if let Ok(value) = serde_json::to_value(&fresh) {
+            self.cache.insert(key, value);
+        }
+        Ok(fresh)
+    }
+
+    /// Drop all cached responses, returning how many there were.
+    pub fn clear_cache(&self) -> usize {
+        self.cache.clear()
+    }
+
     // --- Tauri command methods (use AppConfig) ---
 
+    /// Follow-ups in an existing session depend on its context, so only
+    /// new queries are cached.
     pub async fn run_query(
         &self,
         config: &AppConfig,
         query: &str,
         session_id: Option<&str>,
-    ) -> Result<RunQueryResponse, String> {
-        self.run_query_internal(config.api_url(), &self.headers_from_config(config), query, session_id).await
+        force_refresh: bool,
+    ) -> Result<RunQueryResponse, Error> {
+        let auth = self.auth_from_config(config);
+        let fetch = self.measured("query", retrying(|| self.run_query_internal(config.api_url(), &auth, query, session_id)));
+        if session_id.is_some() {
+            return fetch.await;
+        }
+        self.cached(config, "query", query, force_refresh, fetch).await
     }
 
     pub async fn chat_followup(
@@ -110,16 +524,43 @@ impl QueryClient {
         config: &AppConfig,
         session_id: &str,
         question: &str,
-    ) -> Result<ChatResponse, String> {
-        self.chat_followup_internal(config.api_url(), &self.headers_from_config(config), session_id, question).await
+    ) -> Result<ChatResponse, Error> {
+        let auth = self.auth_from_config(config);
+        self.measured("chat", retrying(|| self.chat_followup_internal(config.api_url(), &auth, session_id, question))).await
     }
 
     pub async fn search_index(
         &self,
         config: &AppConfig,
         term: &str,
-    ) -> Result<SearchResponse, String> {
-        self.search_index_internal(config.api_url(), &self.headers_from_config(config), term).await
+        filters: &SearchFilters,
+        force_refresh: bool,
+    ) -> Result<SearchResponse, Error> {
+        filters
+            .validate(&config.scanner.category_names())
+            .map_err(Error::Validation)?;
+        let auth = self.auth_from_config(config);
+        let fetch = self.measured("search", retrying(|| self.search_index_internal(config.api_url(), &auth, term, filters)));
+        // Filters are part of the request, so they're part of the cache key
+        let key_text = match serde_json::to_string(filters) {
+            Ok(filters) if !filters.is_empty() => format!("{}\n{}", term, filters),
+            _ => term.to_string(),
+        };
+        self.cached(config, "search", &key_text, force_refresh, fetch).await
+    }
+
+    pub async fn semantic_search(
+        &self,
+        config: &AppConfig,
+        text: &str,
+        limit: Option<usize>,
+        force_refresh: bool,
+    ) -> Result<SemanticSearchResponse, Error> {
+        let auth = self.auth_from_config(config);
+        let limit = limit.unwrap_or(DEFAULT_SEMANTIC_LIMIT);
+        let fetch = self.measured("semantic", self.semantic_search_internal(config.api_url(), &auth, text, limit));
+        let key_text = format!("{}\n{}", limit, text);
+        self.cached(config, "semantic", &key_text, force_refresh, fetch).await
     }
 
     pub async fn mutate(
@@ -128,8 +569,70 @@ impl QueryClient {
         schema: &str,
         operation: &str,
         data: Value,
-    ) -> Result<MutateResponse, String> {
-        self.mutate_internal(config.api_url(), &self.headers_from_config(config), schema, operation, data).await
+    ) -> Result<MutateResponse, Error> {
+        self.mutate_internal(config.api_url(), &self.auth_from_config(config), schema, operation, data).await
+    }
+
+    /// One attempt at a mutation, sent with `idempotency_key`. Callers that
+    /// retry pass the same key each time so the server can drop a repeat of a
+    /// mutation it already applied.
+    pub async fn mutate_once(
+        &self,
+        config: &AppConfig,
+        schema: &str,
+        operation: &str,
+        data: Value,
+        idempotency_key: &str,
+    ) -> Result<MutateResponse, Error> {
+        let body = mutation_body(schema, operation, data)?.1;
+        let url = format!("{}/api/mutation/execute", config.api_url());
+        self.send_mutation(&url, &self.auth_from_config(config), &body, idempotency_key).await
+    }
+
+    pub async fn list_schemas(&self, config: &AppConfig) -> Result<Vec<SchemaSummary>, Error> {
+        self.list_schemas_internal(config.api_url(), &self.auth_from_config(config)).await
+    }
+
+    pub async fn describe_schema(
+        &self,
+        config: &AppConfig,
+        name: &str,
+    ) -> Result<SchemaDescription, Error> {
+        self.describe_schema_internal(config.api_url(), &self.auth_from_config(config), name).await
+    }
+
+    pub async fn list_sessions(&self, config: &AppConfig) -> Result<Vec<RemoteSession>, Error> {
+        self.list_sessions_internal(config.api_url(), &self.auth_from_config(config)).await
+    }
+
+    pub async fn get_session_transcript(
+        &self,
+        config: &AppConfig,
+        session_id: &str,
+    ) -> Result<Vec<TranscriptTurn>, Error> {
+        self.get_session_transcript_internal(config.api_url(), &self.auth_from_config(config), session_id).await
+    }
+
+    pub async fn delete_session(&self, config: &AppConfig, session_id: &str) -> Result<(), Error> {
+        self.delete_session_internal(config.api_url(), &self.auth_from_config(config), session_id).await
+    }
+
+    /// Documents, storage and queries used against the account's plan.
+    pub async fn get_account_usage(&self, config: &AppConfig) -> Result<AccountUsage, Error> {
+        let url = Self::api_endpoint(config.api_url(), &["account", "usage"])?;
+        let data = self.get_api(url, &self.auth_from_config(config), "Get account usage").await?;
+        parse_account_usage(&data)
+    }
+
+    /// Whether the backend answers at all. Any HTTP response counts, even
+    /// an error status.
+    pub async fn is_reachable(&self, config: &AppConfig) -> bool {
+        self.client
+            .get(config.api_url())
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .is_ok()
     }
 
     // --- CLI adapter methods (use AdapterConfig) ---
@@ -139,8 +642,9 @@ impl QueryClient {
         config: &AdapterConfig,
         query: &str,
         session_id: Option<&str>,
-    ) -> Result<RunQueryResponse, String> {
-        self.run_query_internal(&config.api_url, &self.headers_from_adapter(config), query, session_id).await
+    ) -> Result<RunQueryResponse, Error> {
+        let auth = self.auth_from_adapter(config);
+        self.measured("query", retrying(|| self.run_query_internal(&config.api_url, &auth, query, session_id))).await
     }
 
     pub async fn chat_followup_with_adapter(
@@ -148,16 +652,30 @@ impl QueryClient {
         config: &AdapterConfig,
         session_id: &str,
         question: &str,
-    ) -> Result<ChatResponse, String> {
-        self.chat_followup_internal(&config.api_url, &self.headers_from_adapter(config), session_id, question).await
+    ) -> Result<ChatResponse, Error> {
+        let auth = self.auth_from_adapter(config);
+        self.measured("chat", retrying(|| self.chat_followup_internal(&config.api_url, &auth, session_id, question))).await
     }
 
     pub async fn search_index_with_adapter(
         &self,
         config: &AdapterConfig,
         term: &str,
-    ) -> Result<SearchResponse, String> {
-        self.search_index_internal(&config.api_url, &self.headers_from_adapter(config), term).await
+        filters: &SearchFilters,
+    ) -> Result<SearchResponse, Error> {
+        filters.validate(&config.custom_categories).map_err(Error::Validation)?;
+        let auth = self.auth_from_adapter(config);
+        self.measured("search", retrying(|| self.search_index_internal(&config.api_url, &auth, term, filters))).await
+    }
+
+    pub async fn semantic_search_with_adapter(
+        &self,
+        config: &AdapterConfig,
+        text: &str,
+        limit: Option<usize>,
+    ) -> Result<SemanticSearchResponse, Error> {
+        let limit = limit.unwrap_or(DEFAULT_SEMANTIC_LIMIT);
+        self.measured("semantic", self.semantic_search_internal(&config.api_url, &self.auth_from_adapter(config), text, limit)).await
     }
 
     pub async fn mutate_with_adapter(
@@ -166,19 +684,168 @@ impl QueryClient {
         schema: &str,
         operation: &str,
         data: Value,
-    ) -> Result<MutateResponse, String> {
-        self.mutate_internal(&config.api_url, &self.headers_from_adapter(config), schema, operation, data).await
+    ) -> Result<MutateResponse, Error> {
+        self.mutate_internal(&config.api_url, &self.auth_from_adapter(config), schema, operation, data).await
+    }
+
+    pub async fn list_schemas_with_adapter(
+        &self,
+        config: &AdapterConfig,
+    ) -> Result<Vec<SchemaSummary>, Error> {
+        self.list_schemas_internal(&config.api_url, &self.auth_from_adapter(config)).await
+    }
+
+    pub async fn describe_schema_with_adapter(
+        &self,
+        config: &AdapterConfig,
+        name: &str,
+    ) -> Result<SchemaDescription, Error> {
+        self.describe_schema_internal(&config.api_url, &self.auth_from_adapter(config), name).await
+    }
+
+    pub async fn list_sessions_with_adapter(
+        &self,
+        config: &AdapterConfig,
+    ) -> Result<Vec<RemoteSession>, Error> {
+        self.list_sessions_internal(&config.api_url, &self.auth_from_adapter(config)).await
+    }
+
+    pub async fn get_session_transcript_with_adapter(
+        &self,
+        config: &AdapterConfig,
+        session_id: &str,
+    ) -> Result<Vec<TranscriptTurn>, Error> {
+        self.get_session_transcript_internal(&config.api_url, &self.auth_from_adapter(config), session_id).await
+    }
+
+    pub async fn delete_session_with_adapter(
+        &self,
+        config: &AdapterConfig,
+        session_id: &str,
+    ) -> Result<(), Error> {
+        self.delete_session_internal(&config.api_url, &self.auth_from_adapter(config), session_id).await
     }
 
     // --- Internal implementations ---
 
+    /// GET an API endpoint and return its checked JSON body.
+    async fn get_api(
+        &self,
+        url: url::Url,
+        auth: &ApiAuth,
+        what: &str,
+    ) -> Result<Value, Error> {
+        self.request_api(reqwest::Method::GET, url, auth, what).await
+    }
+
+    async fn request_api(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+        auth: &ApiAuth,
+        what: &str,
+    ) -> Result<Value, Error> {
+        let resp = self
+            .client
+            .request(method, url)
+            .headers(auth.headers.clone())
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
+            .await
+            .map_err(|e| Error::network(&format!("{} request failed", what), e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(response_error(status, &format!("{} failed", what), &text, auth.locale));
+        }
+
+        let json: Value = resp.json().await
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read {} response: {}", what.to_lowercase(), e),
+            })?;
+        Self::parse_api_response(json, auth.locale)
+    }
+
+    /// `{api_url}/api/<segments>`, with each segment percent-encoded.
+    fn api_endpoint(api_url: &str, segments: &[&str]) -> Result<url::Url, Error> {
+        let mut url = url::Url::parse(api_url)
+            .map_err(|e| Error::Validation(format!("Invalid API URL {:?}: {}", api_url, e)))?;
+        url.path_segments_mut()
+            .map_err(|_| Error::Validation(format!("Invalid API URL {:?}", api_url)))?
+            .pop_if_empty()
+            .push("api")
+            .extend(segments);
+        Ok(url)
+    }
+
+    async fn list_schemas_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+    ) -> Result<Vec<SchemaSummary>, Error> {
+        let url = Self::api_endpoint(api_url, &["schemas"])?;
+        let data = self.get_api(url, auth, "List schemas").await?;
+        Ok(parse_schema_list(&data))
+    }
+
+    async fn describe_schema_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+        name: &str,
+    ) -> Result<SchemaDescription, Error> {
+        if name.trim().is_empty() {
+            return Err(Error::Validation("Schema name can't be empty".to_string()));
+        }
+        let url = Self::api_endpoint(api_url, &["schema", name.trim()])?;
+        let data = self.get_api(url, auth, "Describe schema").await?;
+        Ok(parse_schema_description(name.trim(), &data))
+    }
+
+    async fn list_sessions_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+    ) -> Result<Vec<RemoteSession>, Error> {
+        let url = Self::api_endpoint(api_url, &["llm-query", "sessions"])?;
+        let data = self.get_api(url, auth, "List sessions").await?;
+        Ok(parse_session_list(&data))
+    }
+
+    async fn get_session_transcript_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+        session_id: &str,
+    ) -> Result<Vec<TranscriptTurn>, Error> {
+        let session_id = validate_session_id(session_id)?;
+        let url = Self::api_endpoint(api_url, &["llm-query", "sessions", session_id])?;
+        let data = self.get_api(url, auth, "Get session").await?;
+        Ok(parse_session_transcript(session_id, &data))
+    }
+
+    async fn delete_session_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+        session_id: &str,
+    ) -> Result<(), Error> {
+        let session_id = validate_session_id(session_id)?;
+        let url = Self::api_endpoint(api_url, &["llm-query", "sessions", session_id])?;
+        self.request_api(reqwest::Method::DELETE, url, auth, "Delete session")
+            .await
+            .map(|_| ())
+    }
+
     async fn run_query_internal(
         &self,
         api_url: &str,
-        headers: &reqwest::header::HeaderMap,
+        auth: &ApiAuth,
         query: &str,
         session_id: Option<&str>,
-    ) -> Result<RunQueryResponse, String> {
+    ) -> Result<RunQueryResponse, Error> {
         // Use ai_native_index endpoint: LLM searches word index, hydrates, interprets
         let url = format!("{}/api/llm-query/native-index", api_url);
         let mut body = serde_json::json!({ "query": query });
@@ -189,21 +856,29 @@ impl QueryClient {
         let resp = self
             .client
             .post(&url)
-            .headers(headers.clone())
+            .headers(auth.headers.clone())
             .json(&body)
-            .send()
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
             .await
-            .map_err(|e| format!("Query request failed: {}", e))?;
+            .map_err(|e| Error::network("Query request failed", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            return Err(format!("Query failed ({}): {}", status, text));
+            return Err(response_error(status, "Query failed", &text, auth.locale));
         }
 
         let json: Value = resp.json().await
-            .map_err(|e| format!("Failed to read query response: {}", e))?;
-        let data = Self::parse_api_response(json)?;
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read query response: {}", e),
+            })?;
+        let data = Self::parse_api_response(json, auth.locale)?;
+        let raw_results = data.get("raw_results")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
 
         Ok(RunQueryResponse {
             session_id: data.get("session_id")
@@ -214,20 +889,21 @@ impl QueryClient {
                 .and_then(|v| v.as_str())
                 .unwrap_or("")
                 .to_string(),
-            raw_results: data.get("raw_results")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default(),
+            total_results: raw_results.len(),
+            has_more: false,
+            next_cursor: None,
+            usage: TokenUsage::parse(&data),
+            raw_results,
         })
     }
 
     async fn chat_followup_internal(
         &self,
         api_url: &str,
-        headers: &reqwest::header::HeaderMap,
+        auth: &ApiAuth,
         session_id: &str,
         question: &str,
-    ) -> Result<ChatResponse, String> {
+    ) -> Result<ChatResponse, Error> {
         let url = format!("{}/api/llm-query/chat", api_url);
         let body = serde_json::json!({
             "session_id": session_id,
@@ -237,21 +913,25 @@ impl QueryClient {
         let resp = self
             .client
             .post(&url)
-            .headers(headers.clone())
+            .headers(auth.headers.clone())
             .json(&body)
-            .send()
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
             .await
-            .map_err(|e| format!("Chat request failed: {}", e))?;
+            .map_err(|e| Error::network("Chat request failed", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            return Err(format!("Chat failed ({}): {}", status, text));
+            return Err(response_error(status, "Chat failed", &text, auth.locale));
         }
 
         let json: Value = resp.json().await
-            .map_err(|e| format!("Failed to read chat response: {}", e))?;
-        let data = Self::parse_api_response(json)?;
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read chat response: {}", e),
+            })?;
+        let data = Self::parse_api_response(json, auth.locale)?;
 
         Ok(ChatResponse {
             answer: data.get("answer")
@@ -261,79 +941,171 @@ impl QueryClient {
             context_used: data.get("context_used")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false),
+            usage: TokenUsage::parse(&data),
         })
     }
 
     async fn search_index_internal(
         &self,
         api_url: &str,
-        headers: &reqwest::header::HeaderMap,
+        auth: &ApiAuth,
         term: &str,
-    ) -> Result<SearchResponse, String> {
-        // Native index search is GET with query param
+        filters: &SearchFilters,
+    ) -> Result<SearchResponse, Error> {
+        // Native index search is GET with query params
         let url = format!("{}/api/native-index/search", api_url);
 
         let resp = self
             .client
             .get(&url)
             .query(&[("term", term)])
-            .headers(headers.clone())
-            .send()
+            .query(&filters.query_params())
+            .headers(auth.headers.clone())
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
             .await
-            .map_err(|e| format!("Search request failed: {}", e))?;
+            .map_err(|e| Error::network("Search request failed", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            return Err(format!("Search failed ({}): {}", status, text));
+            return Err(response_error(status, "Search failed", &text, auth.locale));
         }
 
         let json: Value = resp.json().await
-            .map_err(|e| format!("Failed to read search response: {}", e))?;
-        let data = Self::parse_api_response(json)?;
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read search response: {}", e),
+            })?;
+        let data = Self::parse_api_response(json, auth.locale)?;
 
-        let results = data.get("results")
+        let mut results: Vec<SearchHit> = data.get("results")
             .and_then(|v| v.as_array())
-            .cloned()
+            .map(|items| items.iter().map(|item| search::parse_hit(item, term)).collect())
+            .unwrap_or_default();
+        // Stable, so unscored results keep the server's order (after scored ones)
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        let count = results.len();
+
+        Ok(SearchResponse {
+            results,
+            count,
+            has_more: false,
+            next_cursor: None,
+            filters: filters.clone(),
+        })
+    }
+
+    async fn semantic_search_internal(
+        &self,
+        api_url: &str,
+        auth: &ApiAuth,
+        text: &str,
+        limit: usize,
+    ) -> Result<SemanticSearchResponse, Error> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(Error::Validation("Semantic search needs some text".to_string()));
+        }
+        if !(1..=MAX_SEMANTIC_LIMIT).contains(&limit) {
+            return Err(Error::Validation(format!(
+                "Semantic search limit must be between 1 and {}",
+                MAX_SEMANTIC_LIMIT
+            )));
+        }
+
+        let url = format!("{}/api/native-index/semantic-search", api_url);
+        let body = serde_json::json!({ "query": text, "limit": limit });
+
+        let resp = self
+            .client
+            .post(&url)
+            .headers(auth.headers.clone())
+            .json(&body)
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
+            .await
+            .map_err(|e| Error::network("Semantic search request failed", e))?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let text = resp.text().await.unwrap_or_default();
+            return Err(response_error(status, "Semantic search failed", &text, auth.locale));
+        }
+
+        let json: Value = resp.json().await
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read semantic search response: {}", e),
+            })?;
+        let data = Self::parse_api_response(json, auth.locale)?;
+
+        let mut results: Vec<SearchHit> = data.get("results")
+            .and_then(|v| v.as_array())
+            // Matches are by meaning, not words, so there's nothing to highlight
+            .map(|items| items.iter().map(|item| search::parse_hit(item, "")).collect())
             .unwrap_or_default();
+        results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        results.truncate(limit);
         let count = results.len();
 
-        Ok(SearchResponse { results, count })
+        Ok(SemanticSearchResponse { results, count })
     }
 
     async fn mutate_internal(
         &self,
         api_url: &str,
-        headers: &reqwest::header::HeaderMap,
+        auth: &ApiAuth,
         schema: &str,
         operation: &str,
         data: Value,
-    ) -> Result<MutateResponse, String> {
+    ) -> Result<MutateResponse, Error> {
+        let (operation, body) = mutation_body(schema, operation, data)?;
         let url = format!("{}/api/mutation/execute", api_url);
-        let body = serde_json::json!({
-            "schema": schema,
-            "operation": operation,
-            "data": data,
-        });
+        // Same key on every attempt, so the server can tell a retry from a
+        // second mutation
+        let idempotency_key = uuid::Uuid::new_v4().to_string();
+        let send = || self.send_mutation(&url, auth, &body, &idempotency_key);
 
+        // An insert that failed with a 5xx may still have been applied;
+        // repeating it could create a duplicate record
+        if operation.is_idempotent() {
+            retrying(send).await
+        } else {
+            send().await
+        }
+    }
+
+    async fn send_mutation(
+        &self,
+        url: &str,
+        auth: &ApiAuth,
+        body: &Value,
+        idempotency_key: &str,
+    ) -> Result<MutateResponse, Error> {
         let resp = self
             .client
-            .post(&url)
-            .headers(headers.clone())
-            .json(&body)
-            .send()
+            .post(url)
+            .headers(auth.headers.clone())
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .timeout(self.timeout)
+            .send_signed(auth.signer.as_ref())
             .await
-            .map_err(|e| format!("Mutate request failed: {}", e))?;
+            .map_err(|e| Error::network("Mutate request failed", e))?;
 
         if !resp.status().is_success() {
             let status = resp.status();
             let text = resp.text().await.unwrap_or_default();
-            return Err(format!("Mutate failed ({}): {}", status, text));
+            return Err(response_error(status, "Mutate failed", &text, auth.locale));
         }
 
         let json: Value = resp.json().await
-            .map_err(|e| format!("Failed to read mutate response: {}", e))?;
-        let data = Self::parse_api_response(json)?;
+            .map_err(|e| Error::Server {
+                status: None,
+                message: format!("Failed to read mutate response: {}", e),
+            })?;
+        let data = Self::parse_api_response(json, auth.locale)?;
 
         Ok(MutateResponse {
             success: data.get("ok")
@@ -346,3 +1118,382 @@ impl QueryClient {
         })
     }
 }
+
+/// Error for a non-success response. A signed request rejected for clock
+/// skew is an auth problem with a known fix, so it gets its own message.
+fn response_error(status: reqwest::StatusCode, context: &str, body: &str, locale: Locale) -> Error {
+    if ServerErrorCode::from_body(body) == Some(ServerErrorCode::ClockSkew) {
+        return Error::Auth(ServerErrorCode::ClockSkew.describe(locale));
+    }
+    Error::from_status(status.as_u16(), format!("{} ({}): {}", context, status, body))
+}
+
+/// Check a mutation and build its request body.
+fn mutation_body(schema: &str, operation: &str, data: Value) -> Result<(MutationOperation, Value), Error> {
+    let operation = MutationOperation::parse(operation).map_err(Error::Validation)?;
+    if schema.trim().is_empty() {
+        return Err(Error::Validation("Mutation needs a schema name".to_string()));
+    }
+    if !data.is_object() {
+        return Err(Error::Validation("Mutation data must be a JSON object".to_string()));
+    }
+    let body = serde_json::json!({
+        "schema": schema.trim(),
+        "operation": operation.as_str(),
+        "data": data,
+    });
+    Ok((operation, body))
+}
+
+/// Connection failures and 5xx responses are worth another try. Timeouts
+/// already waited the full limit, and other errors won't change on retry.
+pub fn transient(err: &Error) -> Retry {
+    match err {
+        Error::Network(_) => Retry::Backoff,
+        Error::Server {
+            status: Some(status),
+            ..
+        } if *status >= 500 => Retry::Backoff,
+        _ => Retry::Never,
+    }
+}
+
+/// Run a request, retrying transient failures with backoff.
+async fn retrying<F, Fut, T>(f: F) -> Result<T, Error>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    retry::with_backoff(&QUERY_RETRY, transient, f)
+        .await
+        .map_err(|err| match err {
+            RetryError::Permanent(err) | RetryError::Exhausted { error: err, .. } => err,
+        })
+}
+
+/// Schemas come back as a list of names, a list of objects with a "name",
+/// or a map of name -> state (or definition).
+fn parse_schema_list(data: &Value) -> Vec<SchemaSummary> {
+    let state_of = |item: &Value| {
+        item.get("state")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    match data.get("schemas") {
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|item| match item {
+                Value::String(name) => Some(SchemaSummary {
+                    name: name.clone(),
+                    state: None,
+                }),
+                _ => Some(SchemaSummary {
+                    name: item.get("name")?.as_str()?.to_string(),
+                    state: state_of(item),
+                }),
+            })
+            .collect(),
+        Some(Value::Object(map)) => map
+            .iter()
+            .map(|(name, item)| SchemaSummary {
+                name: name.clone(),
+                state: item.as_str().map(|s| s.to_string()).or_else(|| state_of(item)),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Fields come back either as a map of name -> definition or as a list of
+/// definitions with a "name".
+fn parse_schema_description(name: &str, data: &Value) -> SchemaDescription {
+    let schema = data.get("schema").unwrap_or(data);
+    let field_type = |def: &Value| {
+        def.get("field_type")
+            .or_else(|| def.get("type"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+    };
+    let fields = match schema.get("fields") {
+        Some(Value::Object(map)) => map
+            .iter()
+            .map(|(field, def)| SchemaField {
+                name: field.clone(),
+                field_type: field_type(def),
+                details: def.clone(),
+            })
+            .collect(),
+        Some(Value::Array(items)) => items
+            .iter()
+            .filter_map(|def| {
+                Some(SchemaField {
+                    name: def.get("name")?.as_str()?.to_string(),
+                    field_type: field_type(def),
+                    details: def.clone(),
+                })
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    SchemaDescription {
+        name: schema
+            .get("name")
+            .and_then(|v| v.as_str())
+            .unwrap_or(name)
+            .to_string(),
+        fields,
+        raw: schema.clone(),
+    }
+}
+
+fn validate_session_id(session_id: &str) -> Result<&str, Error> {
+    let session_id = session_id.trim();
+    if session_id.is_empty() {
+        return Err(Error::Validation("Session ID can't be empty".to_string()));
+    }
+    Ok(session_id)
+}
+
+fn parse_account_usage(data: &Value) -> Result<AccountUsage, Error> {
+    // The usage may come wrapped in the API's envelope or at its top level
+    let usage = data.get("usage").unwrap_or(data);
+    serde_json::from_value(usage.clone()).map_err(|e| Error::Server {
+        status: None,
+        message: format!("Invalid account usage: {}", e),
+    })
+}
+
+fn parse_session_list(data: &Value) -> Vec<RemoteSession> {
+    let items = data
+        .get("sessions")
+        .and_then(|v| v.as_array())
+        .or_else(|| data.as_array());
+    items
+        .into_iter()
+        .flatten()
+        .filter_map(|item| match item {
+            Value::String(id) => Some(RemoteSession {
+                session_id: id.clone(),
+                title: None,
+                created_at: None,
+            }),
+            _ => Some(RemoteSession {
+                session_id: item
+                    .get("session_id")
+                    .or_else(|| item.get("id"))?
+                    .as_str()?
+                    .to_string(),
+                title: item
+                    .get("title")
+                    .or_else(|| item.get("query"))
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+                created_at: item.get("created_at").and_then(|v| v.as_u64()),
+            }),
+        })
+        .collect()
+}
+
+/// The server keeps a session as a list of chat messages; pair each user
+/// message with the assistant reply that follows it.
+fn parse_session_transcript(session_id: &str, data: &Value) -> Vec<TranscriptTurn> {
+    let messages = data
+        .get("messages")
+        .or_else(|| data.get("history"))
+        .and_then(|v| v.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let text = |message: &Value| {
+        message
+            .get("content")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let mut turns: Vec<TranscriptTurn> = Vec::new();
+    for message in &messages {
+        match message.get("role").and_then(|v| v.as_str()) {
+            Some("user") => turns.push(TranscriptTurn {
+                session_id: session_id.to_string(),
+                kind: if turns.is_empty() { "query" } else { "followup" }.to_string(),
+                question: text(message),
+                answer: String::new(),
+                context_used: true,
+                result_count: None,
+                created_at: message.get("timestamp").and_then(|v| v.as_u64()).unwrap_or(0),
+            }),
+            Some("assistant") => {
+                if let Some(turn) = turns.last_mut().filter(|t| t.answer.is_empty()) {
+                    turn.answer = text(message);
+                }
+            }
+            _ => {}
+        }
+    }
+    turns
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_search_filters() {
+        assert!(SearchFilters::default().is_empty());
+        assert!(SearchFilters::default().query_params().is_empty());
+
+        let filters = SearchFilters {
+            category: Some("media".to_string()),
+            ingested_after: Some(10),
+            ingested_before: Some(20),
+            source_file: Some("  ".to_string()),
+        };
+        assert!(filters.validate(&[]).is_ok());
+        let params = filters.query_params();
+        assert_eq!(params.len(), 3);
+        assert_eq!(params[0], ("category", "media".to_string()));
+
+        let bad_category = SearchFilters {
+            category: Some("photos".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_category.validate(&[]).is_err());
+        assert!(bad_category.validate(&["photos".to_string()]).is_ok());
+        let bad_range = SearchFilters {
+            ingested_after: Some(20),
+            ingested_before: Some(10),
+            ..Default::default()
+        };
+        assert!(bad_range.validate(&[]).is_err());
+    }
+
+    #[test]
+    fn test_parse_sessions() {
+        let sessions = parse_session_list(&json!({"sessions": [
+            "s1",
+            {"id": "s2", "title": "Trips", "created_at": 10},
+            {"title": "no id"}
+        ]}));
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].title.as_deref(), Some("Trips"));
+        assert_eq!(sessions[1].created_at, Some(10));
+
+        let turns = parse_session_transcript("s2", &json!({"messages": [
+            {"role": "system", "content": "ignored"},
+            {"role": "user", "content": "Where did I go?"},
+            {"role": "assistant", "content": "Lisbon"},
+            {"role": "user", "content": "When?"}
+        ]}));
+        assert_eq!(turns.len(), 2);
+        assert_eq!((turns[0].kind.as_str(), turns[0].answer.as_str()), ("query", "Lisbon"));
+        assert_eq!((turns[1].kind.as_str(), turns[1].answer.as_str()), ("followup", ""));
+    }
+
+    #[test]
+    fn test_mutation_operation_parse() {
+        assert_eq!(MutationOperation::parse(" Create "), Ok(MutationOperation::Insert));
+        assert_eq!(MutationOperation::parse("DELETE"), Ok(MutationOperation::Delete));
+        assert!(MutationOperation::parse("drop").is_err());
+        assert!(!MutationOperation::Insert.is_idempotent());
+        assert!(MutationOperation::Delete.is_idempotent());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert_eq!(transient(&Error::Network("reset".into())), Retry::Backoff);
+        assert_eq!(transient(&Error::from_status(502, "down".into())), Retry::Backoff);
+        assert_eq!(transient(&Error::from_status(422, "bad".into())), Retry::Never);
+        assert_eq!(transient(&Error::Timeout("slow".into())), Retry::Never);
+        // An `ok: false` body has no status; the server did answer
+        assert_eq!(
+            transient(&Error::Server { status: None, message: "nope".into() }),
+            Retry::Never
+        );
+    }
+
+    #[test]
+    fn test_clock_skew_is_an_auth_error() {
+        let skewed = response_error(
+            reqwest::StatusCode::UNAUTHORIZED,
+            "Query failed",
+            r#"{"ok": false, "code": "clock_skew"}"#,
+            Locale::En,
+        );
+        assert!(matches!(skewed, Error::Auth(ref m) if m.contains("clock")));
+
+        let other = response_error(reqwest::StatusCode::UNAUTHORIZED, "Query failed", "bad key", Locale::En);
+        assert_eq!(other, Error::Auth("Query failed (401 Unauthorized): bad key".to_string()));
+    }
+
+    #[test]
+    fn test_server_errors_use_configured_locale() {
+        let client = QueryClient::new();
+        let config = AdapterConfig {
+            api_url: "http://localhost".to_string(),
+            api_key: String::new(),
+            user_hash: None,
+            sign_requests: false,
+            custom_categories: Vec::new(),
+            locale: Some("es".to_string()),
+        };
+        let auth = client.auth_from_adapter(&config);
+        let err = QueryClient::parse_api_response(
+            serde_json::json!({"ok": false, "error": "quota_exceeded"}),
+            auth.locale,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::Server { ref message, .. } if message.contains("cuota")));
+    }
+
+    #[test]
+    fn test_parse_schema_list_shapes() {
+        let from_names = parse_schema_list(&json!({"schemas": ["Notes", "Photos"]}));
+        assert_eq!(from_names.len(), 2);
+        assert_eq!(from_names[0].name, "Notes");
+        assert_eq!(from_names[0].state, None);
+
+        let from_objects = parse_schema_list(&json!({"schemas": [{"name": "Notes", "state": "approved"}]}));
+        assert_eq!(from_objects[0].state.as_deref(), Some("approved"));
+
+        let from_map = parse_schema_list(&json!({"schemas": {"Notes": "Approved"}}));
+        assert_eq!(from_map[0].name, "Notes");
+        assert_eq!(from_map[0].state.as_deref(), Some("Approved"));
+    }
+
+    #[test]
+    fn test_parse_account_usage() {
+        let usage = parse_account_usage(&json!({
+            "ok": true,
+            "usage": {"documents_ingested": 120, "documents_limit": 100, "storage_bytes": 5, "queries_used": 7, "queries_limit": 50}
+        }))
+        .unwrap();
+        assert_eq!(usage.queries_remaining(), Some(43));
+        assert_eq!(usage.storage_limit_bytes, None);
+        assert_eq!(usage.exhausted(), ["documents"]);
+
+        let bare = parse_account_usage(&json!({"ok": true, "queries_used": 3})).unwrap();
+        assert_eq!(bare.queries_used, 3);
+        assert!(bare.exhausted().is_empty());
+        assert!(parse_account_usage(&json!({"usage": {"storage_bytes": "lots"}})).is_err());
+    }
+
+    #[test]
+    fn test_parse_schema_description_shapes() {
+        let from_map = parse_schema_description(
+            "Notes",
+            &json!({"schema": {"fields": {"title": {"field_type": "Single"}}}}),
+        );
+        assert_eq!(from_map.name, "Notes");
+        assert_eq!(from_map.fields[0].name, "title");
+        assert_eq!(from_map.fields[0].field_type.as_deref(), Some("Single"));
+
+        let from_list = parse_schema_description(
+            "Notes",
+            &json!({"schema": {"name": "Notes", "fields": [{"name": "body", "type": "Range"}, {"bad": 1}]}}),
+        );
+        assert_eq!(from_list.fields.len(), 1);
+        assert_eq!(from_list.fields[0].field_type.as_deref(), Some("Range"));
+    }
+}
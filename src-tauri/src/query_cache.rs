@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Oldest entries are evicted beyond this many
+const MAX_ENTRIES: usize = 200;
+
+fn default_ttl_secs() -> u64 {
+    300
+}
+
+/// Reuse responses to identical queries and searches instead of sending them
+/// to the backend again. Off by default: answers can change as new files are
+/// ingested.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueryCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long a cached response is reused
+    #[serde(default = "default_ttl_secs")]
+    pub ttl_secs: u64,
+}
+
+impl Default for QueryCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_secs: default_ttl_secs(),
+        }
+    }
+}
+
+/// Cache key for a request: the kind of request, the account it was made
+/// for, and the text with case and whitespace normalized.
+pub fn cache_key(kind: &str, api_url: &str, user: &str, text: &str) -> String {
+    let normalized = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    format!("{}\n{}\n{}\n{}", kind, api_url, user, normalized)
+}
+
+/// In-memory responses, each stored as JSON with the time it was cached.
+#[derive(Default)]
+pub struct QueryCache {
+    entries: Mutex<HashMap<String, (Instant, Value)>>,
+}
+
+impl QueryCache {
+    /// The response cached under `key`, if it is younger than `ttl`.
+    pub fn get(&self, key: &str, ttl: Duration) -> Option<Value> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        match entries.get(key) {
+            Some((cached_at, value)) if cached_at.elapsed() < ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: String, value: Value) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= MAX_ENTRIES && !entries.contains_key(&key) {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, (cached_at, _))| *cached_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    /// Drop every cached response, returning how many there were.
+    pub fn clear(&self) -> usize {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let count = entries.len();
+        entries.clear();
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_key_normalizes_text() {
+        assert_eq!(
+            cache_key("query", "https://api", "u1", "  Where did I  GO? "),
+            cache_key("query", "https://api", "u1", "where did i go?")
+        );
+        assert_ne!(
+            cache_key("query", "https://api", "u1", "trips"),
+            cache_key("search", "https://api", "u1", "trips")
+        );
+        assert_ne!(
+            cache_key("query", "https://api", "u1", "trips"),
+            cache_key("query", "https://api", "u2", "trips")
+        );
+    }
+
+    #[test]
+    fn test_ttl_and_clear() {
+        let cache = QueryCache::default();
+        cache.insert("k".to_string(), json!({"answer": 1}));
+        assert_eq!(
+            cache.get("k", Duration::from_secs(60)),
+            Some(json!({"answer": 1}))
+        );
+        assert_eq!(cache.get("k", Duration::ZERO), None);
+        // Expired entries are dropped on lookup
+        assert_eq!(cache.clear(), 0);
+
+        cache.insert("k".to_string(), json!(2));
+        assert_eq!(cache.clear(), 1);
+        assert_eq!(cache.get("k", Duration::from_secs(60)), None);
+    }
+}
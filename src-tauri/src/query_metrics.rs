@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::logs::civil_date;
+use crate::persist::JsonStore;
+
+/// Daily totals older than this are dropped
+const MAX_DAYS: usize = 365;
+
+/// Token counts and cost the server reports for an LLM call. Every field
+/// is optional because the server doesn't always send them.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TokenUsage {
+    #[serde(default)]
+    pub prompt_tokens: Option<u64>,
+    #[serde(default)]
+    pub completion_tokens: Option<u64>,
+    #[serde(default)]
+    pub total_tokens: Option<u64>,
+    #[serde(default)]
+    pub cost_usd: Option<f64>,
+}
+
+impl TokenUsage {
+    /// Usage from a response's "usage" object, or from the same fields at
+    /// its top level. None when nothing is reported.
+    pub fn parse(data: &Value) -> Option<Self> {
+        let source = data.get("usage").filter(|v| v.is_object()).unwrap_or(data);
+        let count = |keys: &[&str]| keys.iter().find_map(|k| source.get(*k)?.as_u64());
+        let usage = Self {
+            prompt_tokens: count(&["prompt_tokens", "input_tokens"]),
+            completion_tokens: count(&["completion_tokens", "output_tokens"]),
+            total_tokens: count(&["total_tokens"]),
+            cost_usd: ["cost_usd", "cost"]
+                .iter()
+                .find_map(|k| source.get(*k)?.as_f64()),
+        };
+        (usage != Self::default()).then_some(usage)
+    }
+
+    fn total(&self) -> u64 {
+        self.total_tokens.unwrap_or_else(|| {
+            self.prompt_tokens.unwrap_or(0) + self.completion_tokens.unwrap_or(0)
+        })
+    }
+}
+
+/// Totals for one kind of call ("query", "chat", "search", ...) over a day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CallTotals {
+    pub calls: u64,
+    pub errors: u64,
+    pub latency_ms: u64,
+    pub max_latency_ms: u64,
+    pub results: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl CallTotals {
+    fn add(&mut self, other: &CallTotals) {
+        self.calls += other.calls;
+        self.errors += other.errors;
+        self.latency_ms += other.latency_ms;
+        self.max_latency_ms = self.max_latency_ms.max(other.max_latency_ms);
+        self.results += other.results;
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+
+    fn average_latency_ms(&self) -> Option<u64> {
+        (self.calls > 0).then(|| self.latency_ms / self.calls)
+    }
+}
+
+/// One finished call, as recorded by the query client.
+#[derive(Debug, Clone)]
+pub struct CallRecord {
+    pub kind: &'static str,
+    pub latency: Duration,
+    pub ok: bool,
+    pub results: usize,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CallStats {
+    #[serde(flatten)]
+    pub totals: CallTotals,
+    pub average_latency_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryDayStats {
+    /// YYYY-MM-DD (UTC)
+    pub date: String,
+    /// Keyed by kind of call
+    pub calls: BTreeMap<String, CallStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMetricsReport {
+    /// Oldest first; days without calls are omitted
+    pub days: Vec<QueryDayStats>,
+    /// Sums over `days`, keyed by kind of call
+    pub total: BTreeMap<String, CallStats>,
+}
+
+fn date_key(secs: u64) -> String {
+    let (year, month, day) = civil_date((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn with_averages(totals: &BTreeMap<String, CallTotals>) -> BTreeMap<String, CallStats> {
+    totals
+        .iter()
+        .map(|(kind, totals)| {
+            (
+                kind.clone(),
+                CallStats {
+                    totals: totals.clone(),
+                    average_latency_ms: totals.average_latency_ms(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Persistent per-day totals of calls to the query endpoints.
+pub struct QueryMetrics {
+    store: JsonStore<BTreeMap<String, BTreeMap<String, CallTotals>>>,
+}
+
+impl QueryMetrics {
+    const FILE: &'static str = "query_metrics.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "query metrics").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "query metrics").map(|store| Self { store })
+    }
+
+    /// Count one call finished at `now` (Unix seconds).
+    pub fn record(&mut self, now: u64, call: &CallRecord) -> Result<(), String> {
+        let latency_ms = call.latency.as_millis() as u64;
+        let usage = call.usage.clone().unwrap_or_default();
+        let totals = CallTotals {
+            calls: 1,
+            errors: u64::from(!call.ok),
+            latency_ms,
+            max_latency_ms: latency_ms,
+            results: call.results as u64,
+            prompt_tokens: usage.prompt_tokens.unwrap_or(0),
+            completion_tokens: usage.completion_tokens.unwrap_or(0),
+            total_tokens: usage.total(),
+            cost_usd: usage.cost_usd.unwrap_or(0.0),
+        };
+        self.store.update(|days| {
+            days.entry(date_key(now))
+                .or_default()
+                .entry(call.kind.to_string())
+                .or_default()
+                .add(&totals);
+            while days.len() > MAX_DAYS {
+                days.pop_first();
+            }
+        })
+    }
+
+    /// Totals for the `days` most recent days up to `now`, including today.
+    pub fn report(&self, now: u64, days: usize) -> QueryMetricsReport {
+        let first = date_key(now.saturating_sub(days.saturating_sub(1) as u64 * 86_400));
+        let mut total: BTreeMap<String, CallTotals> = BTreeMap::new();
+        let days = self
+            .store
+            .get()
+            .range(first..)
+            .map(|(date, kinds)| {
+                for (kind, totals) in kinds {
+                    total.entry(kind.clone()).or_default().add(totals);
+                }
+                QueryDayStats {
+                    date: date.clone(),
+                    calls: with_averages(kinds),
+                }
+            })
+            .collect();
+
+        QueryMetricsReport {
+            days,
+            total: with_averages(&total),
+        }
+    }
+}
+
+/// Query metrics as recorded by the app and CLI, for tools outside them.
+pub fn query_metrics(days: usize) -> Result<QueryMetricsReport, String> {
+    Ok(QueryMetrics::load()?.report(crate::ledger::now_secs(), days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // 2024-03-01 12:00:00 UTC
+    const NOON: u64 = 1_709_294_400;
+
+    fn call(kind: &'static str, ms: u64, ok: bool, usage: Option<TokenUsage>) -> CallRecord {
+        CallRecord {
+            kind,
+            latency: Duration::from_millis(ms),
+            ok,
+            results: 3,
+            usage,
+        }
+    }
+
+    #[test]
+    fn test_usage_parsing() {
+        assert_eq!(TokenUsage::parse(&json!({"answer": "x"})), None);
+        let nested =
+            TokenUsage::parse(&json!({"usage": {"input_tokens": 10, "output_tokens": 5}})).unwrap();
+        assert_eq!(nested.total(), 15);
+        let flat = TokenUsage::parse(&json!({"total_tokens": 7, "cost": 0.01})).unwrap();
+        assert_eq!((flat.total(), flat.cost_usd), (7, Some(0.01)));
+    }
+
+    #[test]
+    fn test_totals_per_kind_and_day() {
+        let dir = std::env::temp_dir().join(format!("exemem-qmetrics-{}", uuid::Uuid::new_v4()));
+        let open = || QueryMetrics {
+            store: JsonStore::load_from(dir.join("query_metrics.json"), "query metrics").unwrap(),
+        };
+        let mut metrics = open();
+        let usage = TokenUsage {
+            prompt_tokens: Some(100),
+            completion_tokens: Some(20),
+            ..Default::default()
+        };
+        metrics
+            .record(NOON, &call("query", 300, true, Some(usage)))
+            .unwrap();
+        metrics
+            .record(NOON, &call("query", 100, false, None))
+            .unwrap();
+        metrics
+            .record(NOON - 86_400, &call("search", 50, true, None))
+            .unwrap();
+
+        let reloaded = open();
+        let report = reloaded.report(NOON, 7);
+        assert_eq!(report.days.len(), 2);
+        let query = &report.total["query"];
+        assert_eq!((query.totals.calls, query.totals.errors), (2, 1));
+        assert_eq!(query.totals.total_tokens, 120);
+        assert_eq!(query.totals.max_latency_ms, 300);
+        assert_eq!(query.average_latency_ms, Some(200));
+        assert_eq!(report.total["search"].totals.results, 3);
+
+        assert_eq!(reloaded.report(NOON, 1).days.len(), 1);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,280 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+
+use crate::paths;
+use crate::persist;
+
+/// Full payloads are kept for this many of the most recent sessions
+const MAX_STORED_SESSIONS: usize = 50;
+
+/// Which slice of `raw_results` to send over IPC, and which fields of each.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResultView {
+    #[serde(default)]
+    pub offset: usize,
+    /// Max results to return; all remaining when None
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// Dotted paths to keep in each result (e.g. "fields.title"); everything
+    /// when None
+    #[serde(default)]
+    pub fields: Option<Vec<String>>,
+}
+
+impl ResultView {
+    pub fn is_full(&self) -> bool {
+        self.offset == 0 && self.limit.is_none() && self.fields.is_none()
+    }
+
+    /// Apply the view, returning the selected results and whether more
+    /// remain after them.
+    pub fn apply(&self, results: &[Value]) -> (Vec<Value>, bool) {
+        let start = self.offset.min(results.len());
+        let end = self
+            .limit
+            .map_or(results.len(), |limit| (start + limit).min(results.len()));
+
+        let page = results[start..end]
+            .iter()
+            .map(|result| match &self.fields {
+                Some(fields) => project(result, fields),
+                None => result.clone(),
+            })
+            .collect();
+        (page, end < results.len())
+    }
+
+    /// Apply the view to stored results, returning the page and a cursor for
+    /// the one after it (None on the last page).
+    pub fn page(&self, results_id: &str, results: &[Value]) -> (Vec<Value>, Option<String>) {
+        let (page, has_more) = self.apply(results);
+        let next_cursor = has_more.then(|| {
+            ResultCursor {
+                results_id: results_id.to_string(),
+                view: ResultView {
+                    offset: self.offset + page.len(),
+                    ..self.clone()
+                },
+            }
+            .encode()
+        });
+        (page, next_cursor)
+    }
+}
+
+/// Opaque position in a stored result set, handed to the frontend as
+/// `next_cursor` and redeemed with `get_more_results`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultCursor {
+    /// Session id for queries, generated id for searches
+    pub results_id: String,
+    pub view: ResultView,
+}
+
+impl ResultCursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor serializes"))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, String> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| "Invalid result cursor".to_string())
+    }
+}
+
+/// One page of stored results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultPage {
+    pub results: Vec<Value>,
+    /// Size of the whole result set
+    pub total: usize,
+    pub next_cursor: Option<String>,
+}
+
+/// The page a cursor points at.
+pub fn load_page(cursor: &ResultCursor) -> Result<ResultPage, String> {
+    let all = load_full_results(&cursor.results_id)?;
+    let (results, next_cursor) = cursor.view.page(&cursor.results_id, &all);
+    Ok(ResultPage {
+        results,
+        total: all.len(),
+        next_cursor,
+    })
+}
+
+/// Keep only the given dotted paths of a JSON object, preserving nesting.
+/// Non-object values are returned unchanged.
+fn project(value: &Value, fields: &[String]) -> Value {
+    if !value.is_object() {
+        return value.clone();
+    }
+    let mut out = Value::Object(Map::new());
+    for field in fields {
+        let parts: Vec<&str> = field.split('.').collect();
+        if let Some(found) = lookup(value, &parts) {
+            insert(&mut out, &parts, found.clone());
+        }
+    }
+    out
+}
+
+fn lookup<'a>(value: &'a Value, parts: &[&str]) -> Option<&'a Value> {
+    parts.iter().try_fold(value, |v, part| v.get(part))
+}
+
+fn insert(target: &mut Value, parts: &[&str], value: Value) {
+    let (last, parents) = match parts.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+    let mut current = target;
+    for part in parents {
+        current = current
+            .as_object_mut()
+            .expect("projection target is always an object")
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+    if let Some(obj) = current.as_object_mut() {
+        obj.insert(last.to_string(), value);
+    }
+}
+
+fn results_dir() -> Result<PathBuf, String> {
+    Ok(paths::data_dir()?.join("query_results"))
+}
+
+fn results_path(session_id: &str) -> Result<PathBuf, String> {
+    // Session ids come from the server; don't let one escape the directory
+    if session_id.is_empty()
+        || !session_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("Invalid session id: {:?}", session_id));
+    }
+    Ok(results_dir()?.join(format!("{}.json", session_id)))
+}
+
+/// Keep the complete results of a query so the frontend can fetch them later
+/// with `get_full_results`, pruning the oldest sessions beyond the limit.
+pub fn store_full_results(session_id: &str, results: &[Value]) -> Result<(), String> {
+    persist::save_json(&results_path(session_id)?, &results, "query results")?;
+    prune_stored_results()
+}
+
+/// Complete results of the latest query in a session, or of a paged search.
+pub fn load_full_results(session_id: &str) -> Result<Vec<Value>, String> {
+    let path = results_path(session_id)?;
+    if !path.exists() {
+        return Err(format!("No stored results for {}", session_id));
+    }
+    persist::load_json(&path, "query results")
+}
+
+/// Forget the stored results of a session, if any.
+pub fn delete_full_results(session_id: &str) -> Result<(), String> {
+    let path = results_path(session_id)?;
+    match std::fs::remove_file(&path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to delete stored query results: {}", e))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn prune_stored_results() -> Result<(), String> {
+    let dir = results_dir()?;
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read query results dir: {}", e))?
+        .flatten()
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    if files.len() <= MAX_STORED_SESSIONS {
+        return Ok(());
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - MAX_STORED_SESSIONS] {
+        let _ = std::fs::remove_file(path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_paginates() {
+        let results: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+        let view = ResultView {
+            offset: 1,
+            limit: Some(2),
+            fields: None,
+        };
+        assert_eq!(view.apply(&results), (vec![json!(1), json!(2)], true));
+
+        let tail = ResultView {
+            offset: 3,
+            limit: Some(10),
+            fields: None,
+        };
+        assert_eq!(tail.apply(&results), (vec![json!(3), json!(4)], false));
+    }
+
+    #[test]
+    fn test_apply_projects_nested_fields() {
+        let results = vec![json!({
+            "key": "k1",
+            "fields": {"title": "Trip", "body": "long text"},
+            "score": 0.5,
+        })];
+        let view = ResultView {
+            fields: Some(vec!["key".to_string(), "fields.title".to_string(), "missing".to_string()]),
+            ..Default::default()
+        };
+        let (page, more) = view.apply(&results);
+        assert!(!more);
+        assert_eq!(page[0], json!({"key": "k1", "fields": {"title": "Trip"}}));
+    }
+
+    #[test]
+    fn test_page_cursor_round_trips() {
+        let results: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+        let view = ResultView {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let (page, cursor) = view.page("sess-1", &results);
+        assert_eq!(page, vec![json!(0), json!(1)]);
+
+        let cursor = ResultCursor::decode(&cursor.unwrap()).unwrap();
+        assert_eq!(cursor.results_id, "sess-1");
+        let (page, cursor) = cursor.view.page("sess-1", &results);
+        assert_eq!(page, vec![json!(2), json!(3)]);
+
+        let cursor = ResultCursor::decode(&cursor.unwrap()).unwrap();
+        let (page, cursor) = cursor.view.page("sess-1", &results);
+        assert_eq!(page, vec![json!(4)]);
+        assert!(cursor.is_none());
+
+        assert!(ResultCursor::decode("not a cursor").is_err());
+    }
+
+    #[test]
+    fn test_rejects_unsafe_session_ids() {
+        assert!(results_path("../config").is_err());
+        assert!(results_path("").is_err());
+        assert!(results_path("abc-123_x").is_ok());
+    }
+}
@@ -0,0 +1,251 @@
+//! Masking of credentials in text bound for the log, the frontend or the
+//! activity log.
+//!
+//! Three things are masked: the current config's own secrets wherever they
+//! appear, the values of fields with a secret-looking name (`api_key=...`,
+//! `"session_token": "..."`, `Authorization: Bearer ...`), and the query
+//! string of signed URLs, which is all it takes to use a presigned URL.
+
+use std::sync::RwLock;
+
+use crate::config::AppConfig;
+
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Secret values shorter than this aren't masked by value, so that a short
+/// test key doesn't blank out every matching word.
+const MIN_SECRET_LEN: usize = 8;
+
+/// Field names whose values are masked, lowercase
+const SECRET_FIELDS: &[&str] = &[
+    "api_key",
+    "apikey",
+    "x-api-key",
+    "session_token",
+    "sessiontoken",
+    "user_hash",
+    "userhash",
+    "access_token",
+    "refresh_token",
+    "authorization",
+    "password",
+    "secret_access_key",
+    "aws_secret_access_key",
+    "x-amz-security-token",
+];
+
+/// A URL whose query contains any of these (lowercase) is signed
+const SIGNED_QUERY_MARKERS: &[&str] = &["x-amz-", "signature", "token", "key=", "credential"];
+
+/// Credentials of the loaded config, masked wherever they appear.
+static KNOWN_SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Mask `config`'s credentials wherever they appear from now on, replacing
+/// any remembered before.
+pub fn remember_secrets(config: &AppConfig) {
+    let mut secrets: Vec<String> = [
+        Some(&config.api_key),
+        config.session_token.as_ref(),
+        config.user_hash.as_ref(),
+        config.proxy.password.as_ref(),
+        config.direct_s3.secret_access_key.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|s| s.len() >= MIN_SECRET_LEN)
+    .cloned()
+    .collect();
+    // Longest first, so a secret containing another is masked whole
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    *KNOWN_SECRETS.write().unwrap() = secrets;
+}
+
+/// `text` with every credential it contains masked.
+pub fn redact(text: &str) -> String {
+    let mut out = text.to_string();
+    for secret in KNOWN_SECRETS.read().unwrap().iter() {
+        if out.contains(secret.as_str()) {
+            out = out.replace(secret.as_str(), REDACTED);
+        }
+    }
+    redact_fields(&redact_signed_urls(&out))
+}
+
+/// Replace the query string of signed URLs.
+fn redact_signed_urls(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut search = 0;
+    while let Some(offset) = lower[search..].find("http") {
+        let start = search + offset;
+        let rest = &lower[start..];
+        if !(rest.starts_with("http://") || rest.starts_with("https://")) {
+            search = start + 4;
+            continue;
+        }
+        let end = start
+            + rest
+                .find(|c: char| c.is_whitespace() || "\"'<>)\\".contains(c))
+                .unwrap_or(rest.len());
+        if let Some(q) = lower[start..end].find('?') {
+            let query_start = start + q + 1;
+            let query = &lower[query_start..end];
+            if SIGNED_QUERY_MARKERS.iter().any(|m| query.contains(m)) {
+                out.push_str(&text[copied..query_start]);
+                out.push_str(REDACTED);
+                copied = end;
+            }
+        }
+        search = end.max(start + 4);
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Replace the values of secret-named fields in `key=value`, `key: value`
+/// and JSON (escaped or not) forms.
+fn redact_fields(text: &str) -> String {
+    let lower = text.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let Some(name) = SECRET_FIELDS.iter().find(|name| {
+            bytes[i..].starts_with(name.as_bytes())
+                && (i == 0 || !is_name_byte(bytes[i - 1]))
+                && bytes.get(i + name.len()).map_or(true, |b| !is_name_byte(*b))
+        }) else {
+            i += 1;
+            continue;
+        };
+        let Some((value_start, value_end)) = field_value(bytes, i + name.len()) else {
+            i += name.len();
+            continue;
+        };
+        if &text[value_start..value_end] != REDACTED {
+            out.push_str(&text[copied..value_start]);
+            out.push_str(REDACTED);
+            copied = value_end;
+        }
+        i = value_end.max(i + name.len());
+    }
+    out.push_str(&text[copied..]);
+    out
+}
+
+/// Byte range of the value following a field name that ends at `pos`, if a
+/// `:` or `=` separator follows.
+fn field_value(bytes: &[u8], mut pos: usize) -> Option<(usize, usize)> {
+    let skip = |pos: &mut usize, set: &[u8]| {
+        while *pos < bytes.len() && set.contains(&bytes[*pos]) {
+            *pos += 1;
+        }
+    };
+    // The closing quote of a JSON key, possibly escaped
+    skip(&mut pos, b"\\\"'");
+    skip(&mut pos, b" \t");
+    if !matches!(bytes.get(pos), Some(b':') | Some(b'=')) {
+        return None;
+    }
+    pos += 1;
+    skip(&mut pos, b" \t");
+    skip(&mut pos, b"\\");
+    let quote = match bytes.get(pos) {
+        Some(q @ (b'"' | b'\'')) => {
+            pos += 1;
+            Some(*q)
+        }
+        _ => None,
+    };
+    let start = pos;
+    let mut end = match quote {
+        Some(q) => bytes[start..]
+            .iter()
+            .position(|b| *b == q || *b == b'\\')
+            .map_or(bytes.len(), |n| start + n),
+        None => value_end(bytes, start),
+    };
+    // "Bearer <token>" and "Basic <credentials>": the scheme isn't secret
+    let scheme = &bytes[start..end];
+    if quote.is_none() && (scheme == b"bearer" || scheme == b"basic") {
+        let mut token = end;
+        skip(&mut token, b" ");
+        if token > end {
+            return Some((token, value_end(bytes, token))).filter(|(s, e)| e > s);
+        }
+    } else if quote.is_some() && (scheme.starts_with(b"bearer ") || scheme.starts_with(b"basic ")) {
+        let token = start + scheme.iter().position(|b| *b == b' ').unwrap() + 1;
+        return Some((token, end)).filter(|(s, e)| e > s);
+    }
+    while end > start && bytes[end - 1] == b'\\' {
+        end -= 1;
+    }
+    Some((start, end)).filter(|(s, e)| e > s)
+}
+
+fn value_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|b| b.is_ascii_whitespace() || b",;&}])\"'\\".contains(b))
+        .map_or(bytes.len(), |n| start + n)
+}
+
+fn is_name_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'-'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_masks_secret_fields() {
+        assert_eq!(
+            redact("request failed: api_key=abc123&limit=5"),
+            "request failed: api_key=[REDACTED]&limit=5"
+        );
+        assert_eq!(
+            redact(r#"{"session_token": "tok-1", "user_hash":"h1", "name": "x"}"#),
+            r#"{"session_token": "[REDACTED]", "user_hash":"[REDACTED]", "name": "x"}"#
+        );
+        assert_eq!(
+            redact(r#"body: {\"apiKey\":\"k-9\"}"#),
+            r#"body: {\"apiKey\":\"[REDACTED]\"}"#
+        );
+        assert_eq!(
+            redact("Authorization: Bearer eyJhbGci.x.y sent"),
+            "Authorization: Bearer [REDACTED] sent"
+        );
+        // Names that merely contain a secret field name are left alone
+        assert_eq!(redact("my_api_key_count=3"), "my_api_key_count=3");
+        assert_eq!(redact("no api key configured"), "no api key configured");
+    }
+
+    #[test]
+    fn test_masks_signed_url_queries() {
+        let message = "S3 upload failed: error sending request for url \
+            (https://bucket.s3.amazonaws.com/u/a.pdf?X-Amz-Algorithm=AWS4&X-Amz-Signature=deadbeef)";
+        assert_eq!(
+            redact(message),
+            "S3 upload failed: error sending request for url \
+            (https://bucket.s3.amazonaws.com/u/a.pdf?[REDACTED])"
+        );
+        let plain = "GET https://api.example.com/api/query?limit=5 failed";
+        assert_eq!(redact(plain), plain);
+    }
+
+    #[test]
+    fn test_masks_remembered_config_secrets() {
+        remember_secrets(&AppConfig {
+            api_key: "remembered-api-key-1".to_string(),
+            user_hash: Some("short".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            redact("key remembered-api-key-1 was rejected for short"),
+            "key [REDACTED] was rejected for short"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How many times to try a request and how long to wait in between.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Tries in total, including the first
+    pub max_attempts: u32,
+    /// Wait before the second try; doubled before each one after that
+    pub base_delay: Duration,
+    /// Server-requested waits allowed before they start counting as attempts
+    pub max_busy_waits: u32,
+}
+
+impl RetryPolicy {
+    /// Wait after the `attempt`th failure (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+/// What to do after a failed try.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Retry {
+    /// Transient failure: back off and try again
+    Backoff,
+    /// The server asked us to wait this long; doesn't use up an attempt
+    After(Duration),
+    /// Trying again won't help
+    Never,
+}
+
+/// Why [`with_backoff`] gave up.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RetryError<E> {
+    /// The error wasn't worth retrying
+    Permanent(E),
+    /// Every attempt failed; this is the last error
+    Exhausted { error: E, attempts: u32 },
+}
+
+/// Run `f` until it succeeds, `classify` says to stop, or the policy's
+/// attempts run out.
+pub async fn with_backoff<F, Fut, T, E, C>(
+    policy: &RetryPolicy,
+    classify: C,
+    f: F,
+) -> Result<T, RetryError<E>>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    C: Fn(&E) -> Retry,
+    E: Display,
+{
+    let mut attempt = 0;
+    let mut busy_waits = 0;
+    loop {
+        let err = match f().await {
+            Ok(val) => return Ok(val),
+            Err(err) => err,
+        };
+        match classify(&err) {
+            Retry::Never => return Err(RetryError::Permanent(err)),
+            Retry::After(wait) if busy_waits < policy.max_busy_waits => {
+                busy_waits += 1;
+                log::warn!("Server busy, backing off for {:?}: {}", wait, err);
+                sleep(wait).await;
+            }
+            Retry::After(_) | Retry::Backoff => {
+                attempt += 1;
+                if attempt >= policy.max_attempts {
+                    return Err(RetryError::Exhausted {
+                        error: err,
+                        attempts: attempt,
+                    });
+                }
+                let delay = policy.delay(attempt);
+                log::warn!("Attempt {} failed, retrying in {:?}: {}", attempt, delay, err);
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const FAST: RetryPolicy = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+        max_busy_waits: 2,
+    };
+
+    #[test]
+    fn test_delay_doubles() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(500),
+            ..FAST
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let calls = &AtomicU32::new(0);
+        let result = with_backoff(&FAST, |_: &String| Retry::Backoff, || async move {
+            match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err("flaky".to_string()),
+                n => Ok(n),
+            }
+        })
+        .await;
+        assert_eq!(result, Ok(2));
+    }
+
+    #[tokio::test]
+    async fn test_gives_up() {
+        let calls = &AtomicU32::new(0);
+        let result: Result<(), _> = with_backoff(&FAST, |_: &String| Retry::Backoff, || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err("down".to_string())
+        })
+        .await;
+        assert_eq!(
+            result,
+            Err(RetryError::Exhausted {
+                error: "down".to_string(),
+                attempts: 3
+            })
+        );
+
+        calls.store(0, Ordering::SeqCst);
+        let result: Result<(), _> = with_backoff(&FAST, |_: &String| Retry::Never, || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err("bad input".to_string())
+        })
+        .await;
+        assert_eq!(result, Err(RetryError::Permanent("bad input".to_string())));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_busy_waits_are_free_up_to_limit() {
+        let calls = &AtomicU32::new(0);
+        let busy = |_: &String| Retry::After(Duration::from_millis(1));
+        let result: Result<(), _> = with_backoff(&FAST, busy, || async move {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err("busy".to_string())
+        })
+        .await;
+        assert!(matches!(result, Err(RetryError::Exhausted { attempts: 3, .. })));
+        // Two free waits, then three counted attempts
+        assert_eq!(calls.load(Ordering::SeqCst), 5);
+    }
+}
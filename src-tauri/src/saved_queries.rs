@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ledger::now_secs;
+use crate::persist::JsonStore;
+
+/// A named query. `{name}` in the text is a parameter filled in when it is
+/// run; `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SavedQuery {
+    pub name: String,
+    pub query: String,
+    /// Placeholder names in the order they first appear
+    pub params: Vec<String>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl SavedQuery {
+    /// The query text with every placeholder replaced from `values`.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, String> {
+        let missing: Vec<&str> = self
+            .params
+            .iter()
+            .filter(|p| !values.contains_key(*p))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "Saved query '{}' needs a value for: {}",
+                self.name,
+                missing.join(", ")
+            ));
+        }
+        let mut out = String::new();
+        for part in parse_template(&self.query)? {
+            match part {
+                Part::Text(text) => out.push_str(&text),
+                Part::Param(name) => out.push_str(&values[&name]),
+            }
+        }
+        Ok(out)
+    }
+}
+
+enum Part {
+    Text(String),
+    Param(String),
+}
+
+fn parse_template(template: &str) -> Result<Vec<Part>, String> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    return Err("Unclosed '{' in query; write '{{' for a literal brace".to_string());
+                }
+                let name = name.trim();
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+                {
+                    return Err(format!(
+                        "Invalid placeholder {{{}}}: use letters, digits, '_' or '-'",
+                        name
+                    ));
+                }
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Param(name.to_string()));
+            }
+            '}' => return Err("Unmatched '}' in query; write '}}' for a literal brace".to_string()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(Part::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Placeholder names in `template`, in order of first appearance.
+pub fn placeholders(template: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    for part in parse_template(template)? {
+        if let Part::Param(name) = part {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Persistent set of saved queries, unique by name.
+pub struct SavedQueries {
+    store: JsonStore<Vec<SavedQuery>>,
+}
+
+impl SavedQueries {
+    const FILE: &'static str = "saved_queries.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "saved queries").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "saved queries").map(|store| Self { store })
+    }
+
+    /// Saved queries sorted by name.
+    pub fn list(&self) -> Vec<SavedQuery> {
+        let mut entries = self.store.get().clone();
+        entries.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedQuery> {
+        self.store.get().iter().find(|q| q.name == name.trim())
+    }
+
+    /// Save a query under `name`, replacing any existing one with that name.
+    pub fn upsert(&mut self, name: &str, query: &str) -> Result<SavedQuery, String> {
+        let name = name.trim();
+        let query = query.trim();
+        if name.is_empty() {
+            return Err("Saved query name can't be empty".to_string());
+        }
+        if query.is_empty() {
+            return Err("Saved query text can't be empty".to_string());
+        }
+        let params = placeholders(query)?;
+        let now = now_secs();
+
+        self.store.update(|entries| match entries.iter_mut().find(|q| q.name == name) {
+            Some(existing) => {
+                existing.query = query.to_string();
+                existing.params = params;
+                existing.updated_at = now;
+                existing.clone()
+            }
+            None => {
+                let saved = SavedQuery {
+                    name: name.to_string(),
+                    query: query.to_string(),
+                    params,
+                    created_at: now,
+                    updated_at: now,
+                };
+                entries.push(saved.clone());
+                saved
+            }
+        })
+    }
+
+    /// Returns false if there was no saved query with that name.
+    pub fn delete(&mut self, name: &str) -> Result<bool, String> {
+        self.store.update(|entries| {
+            let before = entries.len();
+            entries.retain(|q| q.name != name.trim());
+            entries.len() != before
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let path = std::env::temp_dir().join(format!("exemem-saved-{}.json", uuid::Uuid::new_v4()));
+        let mut saved = SavedQueries {
+            store: JsonStore::load_from(path, "saved queries").unwrap(),
+        };
+        let query = saved
+            .upsert(
+                " trips ",
+                "Trips to {city} in {year} about {city} {{not a param}}",
+            )
+            .unwrap();
+        assert_eq!(query.name, "trips");
+        assert_eq!(query.params, vec!["city", "year"]);
+
+        let rendered = query
+            .render(&values(&[("city", "Lisbon"), ("year", "2024")]))
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "Trips to Lisbon in 2024 about Lisbon {not a param}"
+        );
+
+        let err = query.render(&values(&[("city", "Lisbon")])).unwrap_err();
+        assert!(err.contains("year"));
+
+        // Saving under the same name replaces the query
+        saved.upsert("trips", "All trips").unwrap();
+        assert_eq!(saved.list().len(), 1);
+        assert!(saved.get("trips").unwrap().params.is_empty());
+        assert!(saved.delete("trips").unwrap());
+        assert!(!saved.delete("trips").unwrap());
+        let _ = std::fs::remove_file(saved.store.path());
+    }
+
+    #[test]
+    fn test_rejects_bad_templates() {
+        assert!(placeholders("Trips to {}").is_err());
+        assert!(placeholders("Trips to {a city}").is_err());
+        assert!(placeholders("Unmatched } brace").is_err());
+        assert!(placeholders("Unclosed {brace").is_err());
+        assert_eq!(placeholders("no params").unwrap(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::ledger::now_secs;
+use crate::persist::JsonStore;
+use crate::scanner::{ScanResult, ScanSummary};
+
+/// Oldest snapshots are dropped beyond this many
+const MAX_SNAPSHOTS: usize = 1000;
+
+/// A category is flagged when it grows by at least this many files between
+/// consecutive scans of the same folder...
+const ANOMALY_MIN_INCREASE: usize = 1000;
+
+/// ...and at least doubles in size.
+const ANOMALY_GROWTH_FACTOR: usize = 2;
+
+/// Counts from one scan of a folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanSnapshot {
+    /// Seconds since the Unix epoch
+    pub timestamp: u64,
+    pub folder: String,
+    pub total_files: usize,
+    pub total_bytes: u64,
+    pub summary: ScanSummary,
+}
+
+impl ScanSnapshot {
+    pub fn from_result(folder: String, result: &ScanResult) -> Self {
+        Self {
+            timestamp: now_secs(),
+            folder,
+            total_files: result.total_files,
+            total_bytes: result.total_bytes(),
+            summary: result.summary.clone(),
+        }
+    }
+}
+
+/// A sudden jump in one category between two scans, e.g. a build step that
+/// dropped thousands of scaffolding files into the watched folder.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ScanAnomaly {
+    pub timestamp: u64,
+    pub category: String,
+    pub previous: usize,
+    pub current: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanTrends {
+    /// Oldest first
+    pub points: Vec<ScanSnapshot>,
+    /// Change in total bytes between the first and latest scan
+    pub bytes_growth: i64,
+    pub anomalies: Vec<ScanAnomaly>,
+}
+
+/// Persistent history of scan summaries, used to chart how a folder grows.
+pub struct ScanHistory {
+    store: JsonStore<Vec<ScanSnapshot>>,
+}
+
+impl ScanHistory {
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load("scan_history.json", "scan history").map(|store| Self { store })
+    }
+
+    pub fn record(&mut self, snapshot: ScanSnapshot) -> Result<(), String> {
+        self.store.update(|snapshots| {
+            snapshots.push(snapshot);
+            if snapshots.len() > MAX_SNAPSHOTS {
+                let excess = snapshots.len() - MAX_SNAPSHOTS;
+                snapshots.drain(..excess);
+            }
+        })
+    }
+
+    /// Time series for one folder (every folder when None).
+    pub fn trends(&self, folder: Option<&str>) -> ScanTrends {
+        let points: Vec<ScanSnapshot> = self
+            .store
+            .get()
+            .iter()
+            .filter(|s| folder.map_or(true, |folder| s.folder == folder))
+            .cloned()
+            .collect();
+
+        let bytes_growth = match (points.first(), points.last()) {
+            (Some(first), Some(last)) => last.total_bytes as i64 - first.total_bytes as i64,
+            _ => 0,
+        };
+
+        let anomalies = points
+            .windows(2)
+            .filter(|pair| pair[0].folder == pair[1].folder)
+            .flat_map(|pair| find_anomalies(&pair[0], &pair[1]))
+            .collect();
+
+        ScanTrends {
+            points,
+            bytes_growth,
+            anomalies,
+        }
+    }
+}
+
+fn find_anomalies(previous: &ScanSnapshot, current: &ScanSnapshot) -> Vec<ScanAnomaly> {
+    // Matched by name: custom categories can come and go between scans
+    let previous: HashMap<String, usize> = previous.summary.category_counts().into_iter().collect();
+    current
+        .summary
+        .category_counts()
+        .into_iter()
+        .map(|(category, after)| (previous.get(&category).copied().unwrap_or(0), category, after))
+        .filter(|(before, _, after)| {
+            *after >= before + ANOMALY_MIN_INCREASE && *after >= before * ANOMALY_GROWTH_FACTOR
+        })
+        .map(|(before, category, after)| ScanAnomaly {
+            timestamp: current.timestamp,
+            category,
+            previous: before,
+            current: after,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_history() -> ScanHistory {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-scans-{}", uuid::Uuid::new_v4()))
+            .join("scan_history.json");
+        open(path)
+    }
+
+    fn open(path: PathBuf) -> ScanHistory {
+        ScanHistory {
+            store: JsonStore::load_from(path, "scan history").unwrap(),
+        }
+    }
+
+    fn snapshot(folder: &str, timestamp: u64, scaffolding: usize, bytes: u64) -> ScanSnapshot {
+        ScanSnapshot {
+            timestamp,
+            folder: folder.to_string(),
+            total_files: scaffolding + 10,
+            total_bytes: bytes,
+            summary: ScanSummary {
+                personal_data_count: 10,
+                media_count: 0,
+                config_count: 0,
+                website_scaffolding_count: scaffolding,
+                work_count: 0,
+                unknown_count: 0,
+                needs_converter_count: 0,
+                custom_counts: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_trends_flag_sudden_growth() {
+        let mut history = temp_history();
+        history.record(snapshot("/data", 1, 50, 1_000)).unwrap();
+        history.record(snapshot("/data", 2, 80, 1_500)).unwrap();
+        history.record(snapshot("/data", 3, 12_000, 9_000)).unwrap();
+
+        let trends = history.trends(Some("/data"));
+        assert_eq!(trends.points.len(), 3);
+        assert_eq!(trends.bytes_growth, 8_000);
+        assert_eq!(
+            trends.anomalies,
+            vec![ScanAnomaly {
+                timestamp: 3,
+                category: "website_scaffolding".to_string(),
+                previous: 80,
+                current: 12_000,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_trends_filter_by_folder() {
+        let mut history = temp_history();
+        history.record(snapshot("/a", 1, 0, 100)).unwrap();
+        history.record(snapshot("/b", 2, 5_000, 500)).unwrap();
+
+        let trends = history.trends(Some("/a"));
+        assert_eq!(trends.points.len(), 1);
+        assert_eq!(trends.bytes_growth, 0);
+
+        // Switching folders is not an anomaly
+        assert!(history.trends(None).anomalies.is_empty());
+
+        let reloaded = open(history.store.path().to_path_buf());
+        assert_eq!(reloaded.trends(None).points.len(), 2);
+    }
+}
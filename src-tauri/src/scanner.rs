@@ -1,10 +1,17 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-const MAX_DEPTH: usize = 10;
-const MAX_FILES: usize = 5000;
+const DEFAULT_MAX_DEPTH: usize = 10;
+const DEFAULT_MAX_FILES: usize = 5000;
 
-const SKIP_DIRS: &[&str] = &[
+/// Upper bounds for the configurable limits, so a typo can't turn a scan
+/// into a walk of the whole disk
+const MAX_DEPTH_LIMIT: usize = 64;
+const MAX_FILES_LIMIT: usize = 100_000;
+const MAX_CUSTOM_CATEGORIES: usize = 50;
+
+const DEFAULT_SKIP_DIRS: &[&str] = &[
     "node_modules",
     "__pycache__",
     ".git",
@@ -17,6 +24,162 @@ const SKIP_DIRS: &[&str] = &[
     ".venv",
 ];
 
+fn default_true() -> bool {
+    true
+}
+
+/// A user-defined category. Custom categories are checked in order, before
+/// the built-in heuristics; a file matches when it matches every list that
+/// isn't empty.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CustomCategory {
+    /// Lowercase name, e.g. "receipts"; shown in scans and the activity log
+    pub name: String,
+    /// Extensions without the dot, e.g. ["ofx", "qfx"]
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Text the path relative to the watched folder must contain (any of
+    /// them), case-insensitively, e.g. ["finance/", "receipt"]
+    #[serde(default)]
+    pub path_contains: Vec<String>,
+    /// Whether matching files are recommended for ingestion
+    #[serde(default = "default_true")]
+    pub ingest: bool,
+}
+
+impl CustomCategory {
+    fn matches(&self, lower_path: &str, ext: &str) -> bool {
+        let ext_matches = self.extensions.is_empty()
+            || self.extensions.iter().any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(ext));
+        let path_matches = self.path_contains.is_empty()
+            || self.path_contains.iter().any(|p| lower_path.contains(&p.to_lowercase()));
+        ext_matches && path_matches
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let valid_name = !self.name.is_empty()
+            && self.name.len() <= 40
+            && self.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !valid_name {
+            return Err(format!(
+                "Category name {:?} must be 1 to 40 lowercase letters, digits or underscores",
+                self.name
+            ));
+        }
+        if CATEGORIES.contains(&self.name.as_str()) {
+            return Err(format!("{} is a built-in category", self.name));
+        }
+        let has_rule = self.extensions.iter().chain(&self.path_contains).any(|r| !r.trim().is_empty());
+        if !has_rule {
+            return Err(format!("Category {} needs at least one extension or path pattern", self.name));
+        }
+        Ok(())
+    }
+}
+
+/// Limits and rules for scanning and classifying the watched folder.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScannerConfig {
+    /// Directory levels below the watched folder that are scanned
+    pub max_depth: usize,
+    /// A scan stops after this many files
+    pub max_files: usize,
+    /// Directory names skipped wherever they appear; replaces the built-in list
+    pub skip_dirs: Vec<String>,
+    pub custom_categories: Vec<CustomCategory>,
+}
+
+impl Default for ScannerConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_files: DEFAULT_MAX_FILES,
+            skip_dirs: DEFAULT_SKIP_DIRS.iter().map(|d| d.to_string()).collect(),
+            custom_categories: Vec::new(),
+        }
+    }
+}
+
+impl ScannerConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_depth > MAX_DEPTH_LIMIT {
+            return Err(format!("Scan depth must be at most {}", MAX_DEPTH_LIMIT));
+        }
+        if !(1..=MAX_FILES_LIMIT).contains(&self.max_files) {
+            return Err(format!("Scan file limit must be between 1 and {}", MAX_FILES_LIMIT));
+        }
+        if let Some(dir) = self.skip_dirs.iter().find(|d| d.trim().is_empty() || d.contains(['/', '\\'])) {
+            return Err(format!("Skipped directory {:?} must be a single folder name", dir));
+        }
+        if self.custom_categories.len() > MAX_CUSTOM_CATEGORIES {
+            return Err(format!("At most {} custom categories are allowed", MAX_CUSTOM_CATEGORIES));
+        }
+        for (i, category) in self.custom_categories.iter().enumerate() {
+            category.validate()?;
+            if self.custom_categories[..i].iter().any(|c| c.name == category.name) {
+                return Err(format!("Category {} is defined twice", category.name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Names of the custom categories, e.g. for validating search filters.
+    pub fn category_names(&self) -> Vec<String> {
+        self.custom_categories.iter().map(|c| c.name.clone()).collect()
+    }
+}
+
+/// Unrecognized files at or above this size get an explicit note instead of a
+/// bare "Unknown file type".
+const LARGE_FILE_THRESHOLD: u64 = 10 * 1024 * 1024;
+
+/// File types we recognize but can't ingest directly, with the importers or
+/// converters that turn them into something we can.
+const CONVERTERS: &[(&[&str], &str, &[&str])] = &[
+    (
+        &["sqlite", "sqlite3", "db"],
+        "SQLite database",
+        &[
+            "Export tables to CSV: sqlite3 -header -csv <file> \"SELECT * FROM <table>;\"",
+            "DB Browser for SQLite: File > Export > Table(s) as CSV",
+        ],
+    ),
+    (
+        &["pst", "ost"],
+        "Outlook mailbox",
+        &[
+            "readpst (libpst) to convert to mbox/eml",
+            "Outlook: File > Open & Export > Import/Export > Export to CSV",
+        ],
+    ),
+    (
+        &["mbox", "mbx"],
+        "Mailbox archive",
+        &["Split into .eml files with mb2md or Thunderbird ImportExportTools NG"],
+    ),
+    (
+        &["numbers", "pages"],
+        "Apple iWork document",
+        &["Open in the iWork app and use File > Export To > CSV/Word/PDF"],
+    ),
+    (
+        &["odt", "ods", "odp"],
+        "OpenDocument file",
+        &["LibreOffice: soffice --headless --convert-to docx|xlsx|pdf <file>"],
+    ),
+    (
+        &["heic", "heif"],
+        "HEIC image",
+        &["Convert to JPEG with Preview (macOS), heif-convert, or ImageMagick"],
+    ),
+    (
+        &["epub", "mobi", "azw", "azw3"],
+        "E-book",
+        &["Calibre: ebook-convert <file> output.txt (or .pdf)"],
+    ),
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileRecommendation {
     pub path: String,
@@ -24,9 +187,21 @@ pub struct FileRecommendation {
     pub should_ingest: bool,
     pub category: String,
     pub reason: String,
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    /// Set when the file type is recognized but needs converting before it
+    /// can be ingested.
+    #[serde(default)]
+    pub converter: Option<ConverterHint>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConverterHint {
+    pub file_type: String,
+    pub converters: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScanSummary {
     pub personal_data_count: usize,
     pub media_count: usize,
@@ -34,6 +209,43 @@ pub struct ScanSummary {
     pub website_scaffolding_count: usize,
     pub work_count: usize,
     pub unknown_count: usize,
+    #[serde(default)]
+    pub needs_converter_count: usize,
+    /// Files per custom category, by name
+    #[serde(default)]
+    pub custom_counts: BTreeMap<String, usize>,
+}
+
+/// Every built-in category a file can be classified into, as used by
+/// `FileRecommendation::category`. Custom categories come on top.
+pub const CATEGORIES: &[&str] = &[
+    "personal_data",
+    "media",
+    "config",
+    "website_scaffolding",
+    "work",
+    "unknown",
+    "needs_converter",
+];
+
+impl ScanSummary {
+    /// Per-category counts, keyed by the same names as `FileRecommendation::category`.
+    pub fn category_counts(&self) -> Vec<(String, usize)> {
+        let builtin = [
+            ("personal_data", self.personal_data_count),
+            ("media", self.media_count),
+            ("config", self.config_count),
+            ("website_scaffolding", self.website_scaffolding_count),
+            ("work", self.work_count),
+            ("unknown", self.unknown_count),
+            ("needs_converter", self.needs_converter_count),
+        ];
+        builtin
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .chain(self.custom_counts.iter().map(|(name, count)| (name.clone(), *count)))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,20 +253,39 @@ pub struct ScanResult {
     pub total_files: usize,
     pub recommended_files: Vec<FileRecommendation>,
     pub skipped_files: Vec<FileRecommendation>,
+    /// Recognized-but-unsupported files, with converter guidance. These are
+    /// not part of `skipped_files`.
+    #[serde(default)]
+    pub needs_converter_files: Vec<FileRecommendation>,
     pub summary: ScanSummary,
 }
 
+impl ScanResult {
+    /// Combined size of every classified file whose size could be read.
+    pub fn total_bytes(&self) -> u64 {
+        self.recommended_files
+            .iter()
+            .chain(&self.skipped_files)
+            .chain(&self.needs_converter_files)
+            .filter_map(|f| f.size_bytes)
+            .sum()
+    }
+}
+
 /// Scan a directory tree and classify all files using heuristics.
-pub fn scan_and_classify(root: &Path) -> Result<ScanResult, String> {
-    let files = scan_directory_tree(root, MAX_DEPTH, MAX_FILES)?;
-    let recommendations = classify_files(root, &files);
+pub fn scan_and_classify(root: &Path, config: &ScannerConfig) -> Result<ScanResult, String> {
+    let files = scan_directory_tree(root, config)?;
+    let recommendations = classify_files(root, &files, config);
 
     let mut recommended = Vec::new();
     let mut skipped = Vec::new();
+    let mut needs_converter = Vec::new();
 
     for rec in &recommendations {
         if rec.should_ingest {
             recommended.push(rec.clone());
+        } else if rec.converter.is_some() {
+            needs_converter.push(rec.clone());
         } else {
             skipped.push(rec.clone());
         }
@@ -66,17 +297,14 @@ pub fn scan_and_classify(root: &Path) -> Result<ScanResult, String> {
         total_files: files.len(),
         recommended_files: recommended,
         skipped_files: skipped,
+        needs_converter_files: needs_converter,
         summary,
     })
 }
 
-fn scan_directory_tree(
-    root: &Path,
-    max_depth: usize,
-    max_files: usize,
-) -> Result<Vec<String>, String> {
+fn scan_directory_tree(root: &Path, config: &ScannerConfig) -> Result<Vec<String>, String> {
     let mut files = Vec::new();
-    scan_recursive(root, root, 0, max_depth, max_files, &mut files)?;
+    scan_recursive(root, root, 0, config, &mut files)?;
     Ok(files)
 }
 
@@ -84,11 +312,10 @@ fn scan_recursive(
     root: &Path,
     current: &Path,
     depth: usize,
-    max_depth: usize,
-    max_files: usize,
+    config: &ScannerConfig,
     files: &mut Vec<String>,
 ) -> Result<(), String> {
-    if depth > max_depth || files.len() >= max_files {
+    if depth > config.max_depth || files.len() >= config.max_files {
         return Ok(());
     }
 
@@ -96,7 +323,7 @@ fn scan_recursive(
         .map_err(|e| format!("Failed to read directory {}: {}", current.display(), e))?;
 
     for entry in entries.flatten() {
-        if files.len() >= max_files {
+        if files.len() >= config.max_files {
             break;
         }
 
@@ -109,12 +336,22 @@ fn scan_recursive(
         }
 
         // Skip common non-data directories
-        if path.is_dir() && SKIP_DIRS.contains(&file_name) {
+        if path.is_dir() && config.skip_dirs.iter().any(|d| d == file_name) {
+            continue;
+        }
+
+        // Files already ingested and moved aside
+        if path.is_dir() && crate::file_actions::is_ingested_dir(&path) {
+            continue;
+        }
+
+        // Files already ingested and moved aside
+        if path.is_dir() && crate::file_actions::is_ingested_dir(&path) {
             continue;
         }
 
         if path.is_dir() {
-            scan_recursive(root, &path, depth + 1, max_depth, max_files, files)?;
+            scan_recursive(root, &path, depth + 1, config, files)?;
         } else if path.is_file() {
             if let Ok(relative) = path.strip_prefix(root) {
                 files.push(relative.to_string_lossy().to_string());
@@ -125,7 +362,7 @@ fn scan_recursive(
     Ok(())
 }
 
-fn classify_files(root: &Path, file_tree: &[String]) -> Vec<FileRecommendation> {
+fn classify_files(root: &Path, file_tree: &[String], config: &ScannerConfig) -> Vec<FileRecommendation> {
     file_tree
         .iter()
         .map(|path| {
@@ -135,6 +372,19 @@ fn classify_files(root: &Path, file_tree: &[String]) -> Vec<FileRecommendation>
                 .and_then(|e| e.to_str())
                 .unwrap_or("")
                 .to_lowercase();
+            let size_bytes = std::fs::metadata(root.join(path)).ok().map(|m| m.len());
+
+            if let Some(custom) = config.custom_categories.iter().find(|c| c.matches(&lower, &ext)) {
+                return FileRecommendation {
+                    path: path.clone(),
+                    absolute_path: root.join(path),
+                    should_ingest: custom.ingest,
+                    category: custom.name.clone(),
+                    reason: format!("Matches your {} category", custom.name),
+                    size_bytes,
+                    converter: None,
+                };
+            }
 
             // Website scaffolding patterns
             let is_scaffolding = lower.contains("node_modules")
@@ -179,6 +429,8 @@ fn classify_files(root: &Path, file_tree: &[String]) -> Vec<FileRecommendation>
                 || ext == "mp3"
                 || ext == "wav";
 
+            let converter = converter_for(&ext);
+
             let (should_ingest, category, reason) = if is_scaffolding {
                 (
                     false,
@@ -191,6 +443,22 @@ fn classify_files(root: &Path, file_tree: &[String]) -> Vec<FileRecommendation>
                 (true, "media", "User media file")
             } else if is_personal {
                 (true, "personal_data", "Potential personal data file")
+            } else if let Some(hint) = &converter {
+                return FileRecommendation {
+                    path: path.clone(),
+                    absolute_path: root.join(path),
+                    should_ingest: false,
+                    category: "needs_converter".to_string(),
+                    reason: format!("{} needs converting before it can be ingested", hint.file_type),
+                    size_bytes,
+                    converter,
+                };
+            } else if size_bytes.is_some_and(|size| size >= LARGE_FILE_THRESHOLD) {
+                (
+                    false,
+                    "unknown",
+                    "Large file of unrecognized type; not ingested",
+                )
             } else {
                 (false, "unknown", "Unknown file type")
             };
@@ -201,20 +469,25 @@ fn classify_files(root: &Path, file_tree: &[String]) -> Vec<FileRecommendation>
                 should_ingest,
                 category: category.to_string(),
                 reason: reason.to_string(),
+                size_bytes,
+                converter: None,
             }
         })
         .collect()
 }
 
+fn converter_for(ext: &str) -> Option<ConverterHint> {
+    CONVERTERS
+        .iter()
+        .find(|(exts, _, _)| exts.contains(&ext))
+        .map(|(_, file_type, converters)| ConverterHint {
+            file_type: file_type.to_string(),
+            converters: converters.iter().map(|c| c.to_string()).collect(),
+        })
+}
+
 fn build_summary(recommendations: &[FileRecommendation]) -> ScanSummary {
-    let mut summary = ScanSummary {
-        personal_data_count: 0,
-        media_count: 0,
-        config_count: 0,
-        website_scaffolding_count: 0,
-        work_count: 0,
-        unknown_count: 0,
-    };
+    let mut summary = ScanSummary::default();
 
     for rec in recommendations {
         match rec.category.as_str() {
@@ -223,7 +496,9 @@ fn build_summary(recommendations: &[FileRecommendation]) -> ScanSummary {
             "config" => summary.config_count += 1,
             "website_scaffolding" => summary.website_scaffolding_count += 1,
             "work" => summary.work_count += 1,
-            _ => summary.unknown_count += 1,
+            "needs_converter" => summary.needs_converter_count += 1,
+            "unknown" => summary.unknown_count += 1,
+            custom => *summary.custom_counts.entry(custom.to_string()).or_default() += 1,
         }
     }
 
@@ -232,7 +507,13 @@ fn build_summary(recommendations: &[FileRecommendation]) -> ScanSummary {
 
 /// Classify a single file path using the same heuristics.
 /// Used by the watcher to classify newly detected files.
-pub fn classify_single_file(root: &Path, absolute_path: &Path) -> FileRecommendation {
+///
+/// Classification only looks at the path and file size; file contents are
+/// never parsed on this machine. Uploading hashes, compresses or zips them
+/// as opaque bytes, and text extraction for PDFs, Office documents and
+/// archives happens server-side, so untrusted input never reaches a parser
+/// inside the resident app.
+pub fn classify_single_file(root: &Path, absolute_path: &Path, config: &ScannerConfig) -> FileRecommendation {
     let relative = absolute_path
         .strip_prefix(root)
         .map(|p| p.to_string_lossy().to_string())
@@ -243,13 +524,15 @@ pub fn classify_single_file(root: &Path, absolute_path: &Path) -> FileRecommenda
                 .unwrap_or_else(|| "unknown".to_string())
         });
 
-    let results = classify_files(root, &[relative]);
+    let results = classify_files(root, &[relative], config);
     results.into_iter().next().unwrap_or(FileRecommendation {
         path: absolute_path.to_string_lossy().to_string(),
         absolute_path: absolute_path.to_path_buf(),
         should_ingest: false,
         category: "unknown".to_string(),
         reason: "Could not classify".to_string(),
+        size_bytes: None,
+        converter: None,
     })
 }
 
@@ -261,7 +544,7 @@ mod tests {
     fn test_classify_json_file() {
         let root = Path::new("/tmp/test");
         let files = vec!["data/export.json".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(results[0].should_ingest);
         assert_eq!(results[0].category, "personal_data");
@@ -271,7 +554,7 @@ mod tests {
     fn test_classify_node_modules() {
         let root = Path::new("/tmp/test");
         let files = vec!["node_modules/react/index.js".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(!results[0].should_ingest);
         assert_eq!(results[0].category, "website_scaffolding");
@@ -281,7 +564,7 @@ mod tests {
     fn test_classify_media() {
         let root = Path::new("/tmp/test");
         let files = vec!["photos/vacation.jpg".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(results[0].should_ingest);
         assert_eq!(results[0].category, "media");
@@ -291,7 +574,7 @@ mod tests {
     fn test_classify_config() {
         let root = Path::new("/tmp/test");
         let files = vec!["config/settings.yaml".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(!results[0].should_ingest);
         assert_eq!(results[0].category, "config");
@@ -301,7 +584,7 @@ mod tests {
     fn test_classify_media_in_assets_skipped() {
         let root = Path::new("/tmp/test");
         let files = vec!["web/assets/logo.png".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(!results[0].should_ingest);
     }
@@ -310,9 +593,72 @@ mod tests {
     fn test_classify_unknown() {
         let root = Path::new("/tmp/test");
         let files = vec!["something.xyz".to_string()];
-        let results = classify_files(root, &files);
+        let results = classify_files(root, &files, &ScannerConfig::default());
         assert_eq!(results.len(), 1);
         assert!(!results[0].should_ingest);
         assert_eq!(results[0].category, "unknown");
     }
+
+    #[test]
+    fn test_classify_needs_converter() {
+        let root = Path::new("/tmp/test");
+        let files = vec!["mail/archive.pst".to_string()];
+        let results = classify_files(root, &files, &ScannerConfig::default());
+        assert_eq!(results.len(), 1);
+        assert!(!results[0].should_ingest);
+        assert_eq!(results[0].category, "needs_converter");
+        let hint = results[0].converter.as_ref().unwrap();
+        assert_eq!(hint.file_type, "Outlook mailbox");
+        assert!(!hint.converters.is_empty());
+    }
+
+    #[test]
+    fn test_custom_categories_come_first() {
+        let root = Path::new("/tmp/test");
+        let config = ScannerConfig {
+            custom_categories: vec![CustomCategory {
+                name: "receipts".to_string(),
+                extensions: vec!["pdf".to_string()],
+                path_contains: vec!["Receipts/".to_string()],
+                ingest: true,
+            }],
+            ..Default::default()
+        };
+        let files = vec!["receipts/2024/store.PDF".to_string(), "notes/store.pdf".to_string()];
+        let results = classify_files(root, &files, &config);
+        assert_eq!(results[0].category, "receipts");
+        assert!(results[0].should_ingest);
+        assert_eq!(results[1].category, "personal_data");
+
+        let summary = build_summary(&results);
+        assert_eq!(summary.custom_counts.get("receipts"), Some(&1));
+        assert_eq!(summary.personal_data_count, 1);
+        assert_eq!(summary.unknown_count, 0);
+    }
+
+    #[test]
+    fn test_scanner_config_validate() {
+        assert!(ScannerConfig::default().validate().is_ok());
+        let too_deep = ScannerConfig {
+            max_depth: MAX_DEPTH_LIMIT + 1,
+            ..Default::default()
+        };
+        assert!(too_deep.validate().is_err());
+
+        let category = |name: &str, extensions: &[&str]| CustomCategory {
+            name: name.to_string(),
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            path_contains: Vec::new(),
+            ingest: true,
+        };
+        let with = |categories| ScannerConfig {
+            custom_categories: categories,
+            ..Default::default()
+        };
+        assert!(with(vec![category("receipts", &["ofx"])]).validate().is_ok());
+        assert!(with(vec![category("media", &["ofx"])]).validate().is_err());
+        assert!(with(vec![category("Receipts", &["ofx"])]).validate().is_err());
+        assert!(with(vec![category("receipts", &[])]).validate().is_err());
+        assert!(with(vec![category("receipts", &["ofx"]), category("receipts", &["qfx"])]).validate().is_err());
+    }
 }
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::persist::JsonStore;
+use crate::uploader::FileContext;
+
+const SECS_PER_DAY: i64 = 86_400;
+
+/// Quiet hours during which watcher-originated uploads are allowed to run.
+/// Outside the window they are queued until it next opens; manually approved
+/// ingests are never deferred.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UploadSchedule {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Window start, "HH:MM" local time
+    #[serde(default = "default_start")]
+    pub start: String,
+    /// Window end, "HH:MM" local time; may be earlier than `start` to span midnight
+    #[serde(default = "default_end")]
+    pub end: String,
+    /// Local time offset from UTC, filled in by the UI from the system timezone
+    #[serde(default)]
+    pub utc_offset_minutes: i32,
+}
+
+fn default_start() -> String {
+    "01:00".to_string()
+}
+
+fn default_end() -> String {
+    "06:00".to_string()
+}
+
+impl Default for UploadSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            start: default_start(),
+            end: default_end(),
+            utc_offset_minutes: 0,
+        }
+    }
+}
+
+/// Parse "HH:MM" into seconds after midnight.
+fn parse_time_of_day(value: &str) -> Result<i64, String> {
+    let (hours, minutes) = value
+        .split_once(':')
+        .ok_or_else(|| format!("Invalid time {:?}, expected HH:MM", value))?;
+    let hours: i64 = hours
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid hour in {:?}", value))?;
+    let minutes: i64 = minutes
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid minute in {:?}", value))?;
+    if !(0..24).contains(&hours) || !(0..60).contains(&minutes) {
+        return Err(format!("Time out of range: {:?}", value));
+    }
+    Ok(hours * 3600 + minutes * 60)
+}
+
+impl UploadSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        let start = parse_time_of_day(&self.start)?;
+        let end = parse_time_of_day(&self.end)?;
+        if self.enabled && start == end {
+            return Err("Quiet hours start and end must differ".to_string());
+        }
+        if self.utc_offset_minutes.abs() > 14 * 60 {
+            return Err(format!(
+                "UTC offset out of range: {} minutes",
+                self.utc_offset_minutes
+            ));
+        }
+        Ok(())
+    }
+
+    /// When a watcher upload at `now` (Unix seconds) should run instead, or
+    /// None if it may run right away.
+    pub fn defer_until(&self, now: u64) -> Option<u64> {
+        if !self.enabled {
+            return None;
+        }
+        let (Ok(start), Ok(end)) = (
+            parse_time_of_day(&self.start),
+            parse_time_of_day(&self.end),
+        ) else {
+            // Invalid settings are rejected on save; don't hold uploads hostage
+            return None;
+        };
+        if start == end {
+            return None;
+        }
+
+        let local = now as i64 + i64::from(self.utc_offset_minutes) * 60;
+        let time_of_day = local.rem_euclid(SECS_PER_DAY);
+        let in_window = if start < end {
+            time_of_day >= start && time_of_day < end
+        } else {
+            time_of_day >= start || time_of_day < end
+        };
+        if in_window {
+            return None;
+        }
+
+        let wait = (start - time_of_day).rem_euclid(SECS_PER_DAY);
+        Some(now + wait as u64)
+    }
+}
+
+/// A watcher upload held back until quiet hours.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledUpload {
+    pub path: PathBuf,
+    pub filename: String,
+    pub context: FileContext,
+    /// Seconds since the Unix epoch
+    pub scheduled_at: u64,
+}
+
+/// Persistent list of deferred watcher uploads, keyed by file path so a file
+/// modified several times before the window opens is uploaded once.
+pub struct ScheduledUploads {
+    store: JsonStore<Vec<ScheduledUpload>>,
+}
+
+impl ScheduledUploads {
+    const FILE: &'static str = "scheduled_uploads.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "scheduled uploads").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "scheduled uploads").map(|store| Self { store })
+    }
+
+    pub fn list(&self) -> Vec<ScheduledUpload> {
+        self.store.get().clone()
+    }
+
+    pub fn add(
+        &mut self,
+        file_path: &Path,
+        context: FileContext,
+        scheduled_at: u64,
+    ) -> Result<(), String> {
+        self.store.update(|entries| {
+            entries.retain(|e| e.path != file_path);
+            entries.push(ScheduledUpload {
+                path: file_path.to_path_buf(),
+                filename: file_path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                context,
+                scheduled_at,
+            });
+        })
+    }
+
+    /// Remove and return the entry scheduled earliest.
+    pub fn take_next(&mut self) -> Result<Option<ScheduledUpload>, String> {
+        self.store.update(|entries| {
+            let next = entries
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.scheduled_at)
+                .map(|(i, _)| i)?;
+            Some(entries.remove(next))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(start: &str, end: &str) -> UploadSchedule {
+        UploadSchedule {
+            enabled: true,
+            start: start.to_string(),
+            end: end.to_string(),
+            utc_offset_minutes: 0,
+        }
+    }
+
+    const DAY: u64 = 86_400;
+
+    #[test]
+    fn test_disabled_never_defers() {
+        let s = UploadSchedule::default();
+        assert_eq!(s.defer_until(DAY * 100 + 12 * 3600), None);
+    }
+
+    #[test]
+    fn test_defers_to_window_start() {
+        let s = schedule("01:00", "06:00");
+        let noon = DAY * 100 + 12 * 3600;
+        assert_eq!(s.defer_until(noon), Some(DAY * 101 + 3600));
+        assert_eq!(s.defer_until(DAY * 100 + 3 * 3600), None);
+    }
+
+    #[test]
+    fn test_window_spanning_midnight() {
+        let s = schedule("22:00", "06:00");
+        assert_eq!(s.defer_until(DAY * 100 + 23 * 3600), None);
+        assert_eq!(s.defer_until(DAY * 100 + 2 * 3600), None);
+        assert_eq!(
+            s.defer_until(DAY * 100 + 7 * 3600),
+            Some(DAY * 100 + 22 * 3600)
+        );
+    }
+
+    #[test]
+    fn test_utc_offset_shifts_window() {
+        let mut s = schedule("01:00", "06:00");
+        s.utc_offset_minutes = -300;
+        // 03:00 UTC is 22:00 local, so wait until 01:00 local (06:00 UTC)
+        assert_eq!(
+            s.defer_until(DAY * 100 + 3 * 3600),
+            Some(DAY * 100 + 6 * 3600)
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_times() {
+        assert!(schedule("25:00", "06:00").validate().is_err());
+        assert!(schedule("01:00", "01:00").validate().is_err());
+        assert!(schedule("1", "06:00").validate().is_err());
+        assert!(schedule("01:00", "06:30").validate().is_ok());
+    }
+
+    #[test]
+    fn test_take_next_dedupes_by_path() {
+        let dir = std::env::temp_dir().join(format!("exemem-schedule-{}", uuid::Uuid::new_v4()));
+        let open = || ScheduledUploads {
+            store: JsonStore::load_from(dir.join("scheduled.json"), "scheduled uploads").unwrap(),
+        };
+        let mut queue = open();
+        let path = Path::new("/tmp/a.txt");
+        queue
+            .add(Path::new("/tmp/b.txt"), FileContext::default(), 500)
+            .unwrap();
+        queue.add(path, FileContext::default(), 100).unwrap();
+        queue.add(path, FileContext::default(), 200).unwrap();
+        assert_eq!(queue.list().len(), 2);
+
+        let next = queue.take_next().unwrap().unwrap();
+        assert_eq!(next.path, path);
+        assert_eq!(next.scheduled_at, 200);
+        assert_eq!(open().list().len(), 1);
+
+        assert_eq!(queue.take_next().unwrap().unwrap().scheduled_at, 500);
+        assert!(queue.take_next().unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,189 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A matched span of a snippet, in characters (not bytes) so the frontend
+/// can slice the string directly.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Highlight {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// One index search result. Every field has a default so a page projected
+/// with `ResultView::fields` still deserializes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SearchHit {
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Text around the match
+    #[serde(default)]
+    pub snippet: String,
+    /// Relevance as reported by the server; higher is better
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// File the record was ingested from
+    #[serde(default)]
+    pub source_file: Option<String>,
+    #[serde(default)]
+    pub matched_terms: Vec<String>,
+    /// Where `matched_terms` occur in `snippet`
+    #[serde(default)]
+    pub highlights: Vec<Highlight>,
+    /// The result as the server sent it
+    #[serde(default)]
+    pub raw: Value,
+}
+
+fn first_str(item: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|key| item.get(*key).and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+}
+
+/// Build a hit from a raw server result. Results that aren't objects (or
+/// have no recognizable text) use their JSON as the snippet. Matched terms
+/// come from the server when it reports them, otherwise from the words of
+/// `term` that occur in the snippet.
+pub fn parse_hit(item: &Value, term: &str) -> SearchHit {
+    let snippet = match item {
+        Value::String(s) => s.clone(),
+        _ => first_str(item, &["snippet", "text", "content", "value"])
+            .unwrap_or_else(|| item.to_string()),
+    };
+    let score = ["score", "relevance", "similarity"]
+        .iter()
+        .find_map(|key| item.get(*key).and_then(|v| v.as_f64()));
+    let reported: Vec<String> = item
+        .get("matched_terms")
+        .and_then(|v| v.as_array())
+        .map(|terms| {
+            terms
+                .iter()
+                .filter_map(|t| t.as_str().map(|s| s.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let candidates: Vec<String> = if reported.is_empty() {
+        term.split_whitespace().map(|w| w.to_string()).collect()
+    } else {
+        reported
+    };
+    let highlights = highlight(&snippet, &candidates);
+    let matched_terms = candidates
+        .into_iter()
+        .filter(|t| contains_ignore_case(&snippet, t))
+        .collect();
+
+    SearchHit {
+        id: first_str(item, &["id", "key", "atom_uuid"]),
+        snippet,
+        score,
+        source_file: first_str(item, &["source_file", "file_name", "filename", "source"]),
+        matched_terms,
+        highlights,
+        raw: item.clone(),
+    }
+}
+
+fn contains_ignore_case(text: &str, term: &str) -> bool {
+    !term.is_empty() && text.to_lowercase().contains(&term.to_lowercase())
+}
+
+/// Case-insensitive occurrences of `terms` in `text`, sorted and merged
+/// where they overlap.
+pub fn highlight(text: &str, terms: &[String]) -> Vec<Highlight> {
+    let chars: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    // Lowercasing can change the length of some characters; fall back to no
+    // highlights rather than misplace them
+    if chars.len() != text.chars().count() {
+        return Vec::new();
+    }
+    let mut spans: Vec<Highlight> = Vec::new();
+    for term in terms {
+        let needle: Vec<char> = term.chars().flat_map(char::to_lowercase).collect();
+        if needle.is_empty() || needle.len() > chars.len() {
+            continue;
+        }
+        for start in 0..=chars.len() - needle.len() {
+            if chars[start..start + needle.len()] == needle[..] {
+                spans.push(Highlight {
+                    start,
+                    end: start + needle.len(),
+                });
+            }
+        }
+    }
+    spans.sort_by_key(|s| (s.start, s.end));
+    let mut merged: Vec<Highlight> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// `text` with each highlighted span wrapped in `open` and `close`, e.g.
+/// ANSI color codes for a terminal.
+pub fn render_highlights(text: &str, highlights: &[Highlight], open: &str, close: &str) -> String {
+    let mut out = String::new();
+    let mut spans = highlights.iter().peekable();
+    for (i, c) in text.chars().enumerate() {
+        if spans.peek().is_some_and(|s| s.start == i) {
+            out.push_str(open);
+        }
+        out.push(c);
+        if spans.peek().is_some_and(|s| s.end == i + 1) {
+            out.push_str(close);
+            spans.next();
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_hit_fields() {
+        let hit = parse_hit(
+            &json!({"key": "k1", "content": "Trip to Lisbon in May", "relevance": 0.8, "file_name": "trips.md"}),
+            "lisbon may june",
+        );
+        assert_eq!(hit.id.as_deref(), Some("k1"));
+        assert_eq!(hit.score, Some(0.8));
+        assert_eq!(hit.source_file.as_deref(), Some("trips.md"));
+        assert_eq!(hit.matched_terms, vec!["lisbon", "may"]);
+        assert_eq!(
+            hit.highlights,
+            vec![
+                Highlight { start: 8, end: 14 },
+                Highlight { start: 18, end: 21 }
+            ]
+        );
+
+        let plain = parse_hit(&json!("just text"), "text");
+        assert_eq!(plain.snippet, "just text");
+        assert_eq!(plain.matched_terms, vec!["text"]);
+    }
+
+    #[test]
+    fn test_highlights_merge_and_render() {
+        let terms = vec!["lis".to_string(), "lisbon".to_string(), "ÉTÉ".to_string()];
+        let spans = highlight("Lisbon été", &terms);
+        assert_eq!(
+            spans,
+            vec![
+                Highlight { start: 0, end: 6 },
+                Highlight { start: 7, end: 10 }
+            ]
+        );
+        assert_eq!(
+            render_highlights("Lisbon été", &spans, "[", "]"),
+            "[Lisbon] [été]"
+        );
+    }
+}
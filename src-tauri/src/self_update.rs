@@ -0,0 +1,316 @@
+//! Updating the CLI in place, for servers that don't get the desktop app's
+//! updates. The release manifest gives the download's SHA-256 and an
+//! Ed25519 signature over it; nothing is installed unless the download
+//! matches both. The signature is checked against a public key compiled
+//! into the binary, so whoever controls the API (or the manifest response)
+//! can't ship a binary of their own.
+
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::error::Error;
+
+/// Longest the manifest request may take
+const CHECK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Longest the binary download may take
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Base64 Ed25519 public key that releases are signed with, set by the
+/// release build. Builds without one can check for updates but not install.
+const RELEASE_PUBLIC_KEY: Option<&str> = option_env!("EXEMEM_RELEASE_PUBLIC_KEY");
+
+/// A published build of the CLI for this platform.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Release {
+    pub version: String,
+    /// Where to download the binary
+    pub url: String,
+    /// Hex SHA-256 of the binary
+    pub sha256: String,
+    /// Base64 Ed25519 signature of the binary, by the release key
+    pub signature: String,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UpdateCheck {
+    pub current_version: &'static str,
+    pub latest_version: String,
+    pub update_available: bool,
+    pub release: Release,
+}
+
+/// The platform the release endpoint serves builds for, e.g. "linux-x86_64".
+pub fn target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Ask the configured API for the latest CLI release for this platform.
+pub async fn check(client: &Client, config: &AppConfig) -> Result<UpdateCheck, Error> {
+    let url = format!("{}/api/client/releases/latest", config.api_url());
+    let resp = client
+        .get(&url)
+        .query(&[("channel", "cli"), ("target", target().as_str())])
+        .timeout(CHECK_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to check for updates: {}", e)))?;
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to read the release manifest: {}", e)))?;
+    if !status.is_success() {
+        return Err(Error::from_status(
+            status.as_u16(),
+            format!("Release check failed ({}): {}", status, body),
+        ));
+    }
+    let release = parse_manifest(&body)?;
+
+    let current = env!("CARGO_PKG_VERSION");
+    Ok(UpdateCheck {
+        current_version: current,
+        latest_version: release.version.clone(),
+        update_available: is_newer(&release.version, current),
+        release,
+    })
+}
+
+/// Download `release`, check it against its SHA-256 and signature and swap
+/// it in for the binary at `exe`. The old binary stays in place if anything
+/// fails.
+pub async fn install(client: &Client, release: &Release, exe: &Path) -> Result<(), Error> {
+    let public_key = RELEASE_PUBLIC_KEY.ok_or_else(|| {
+        Error::Validation(
+            "This build has no release signing key, so it can't install updates".to_string(),
+        )
+    })?;
+    check_url(&release.url)?;
+    let resp = client
+        .get(&release.url)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .send()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to download {}: {}", release.version, e)))?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(Error::from_status(
+            status.as_u16(),
+            format!("Download of {} failed ({})", release.version, status),
+        ));
+    }
+    let bytes = resp
+        .bytes()
+        .await
+        .map_err(|e| Error::Network(format!("Failed to download {}: {}", release.version, e)))?;
+
+    verify_sha256(&bytes, &release.sha256)?;
+    verify_signature(&bytes, &release.signature, public_key)?;
+    replace_binary(exe, &bytes)
+}
+
+/// Only download over HTTPS, whatever the manifest says.
+fn check_url(url: &str) -> Result<(), Error> {
+    match url::Url::parse(url) {
+        Ok(parsed) if parsed.scheme() == "https" => Ok(()),
+        _ => Err(Error::Server {
+            status: None,
+            message: format!("Refusing to download an update from {}: not an https URL", url),
+        }),
+    }
+}
+
+fn parse_manifest(body: &str) -> Result<Release, Error> {
+    let json: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| Error::Server {
+            status: None,
+            message: format!("Invalid release manifest: {}", e),
+        })?;
+    // Accept the manifest bare or wrapped in the API's usual envelope
+    let release = json.get("release").cloned().unwrap_or(json);
+    serde_json::from_value(release).map_err(|e| Error::Server {
+        status: None,
+        message: format!("Invalid release manifest: {}", e),
+    })
+}
+
+/// Whether dotted version `latest` is past `current`. Pre-release and
+/// build suffixes are ignored, and missing parts count as zero.
+fn is_newer(latest: &str, current: &str) -> bool {
+    let parts = |version: &str| -> Vec<u64> {
+        version
+            .trim_start_matches('v')
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (mut latest, mut current) = (parts(latest), parts(current));
+    let len = latest.len().max(current.len());
+    latest.resize(len, 0);
+    current.resize(len, 0);
+    latest > current
+}
+
+fn verify_sha256(bytes: &[u8], expected: &str) -> Result<(), Error> {
+    let actual = format!("{:x}", Sha256::digest(bytes));
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(Error::Server {
+            status: None,
+            message: format!(
+                "Downloaded binary doesn't match the published checksum (expected {}, got {}); not installed",
+                expected.trim(),
+                actual
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn verify_signature(bytes: &[u8], signature: &str, public_key: &str) -> Result<(), Error> {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let key = engine
+        .decode(public_key.trim())
+        .ok()
+        .and_then(|key| <[u8; 32]>::try_from(key).ok())
+        .and_then(|key| VerifyingKey::from_bytes(&key).ok())
+        .ok_or_else(|| Error::Internal("Invalid release signing key in this build".to_string()))?;
+    let rejected = |reason: &str| Error::Server {
+        status: None,
+        message: format!("Downloaded binary {}; not installed", reason),
+    };
+    let signature = engine
+        .decode(signature.trim())
+        .ok()
+        .and_then(|sig| Signature::from_slice(&sig).ok())
+        .ok_or_else(|| rejected("has a malformed signature"))?;
+    key.verify_strict(bytes, &signature)
+        .map_err(|_| rejected("isn't signed by the release key"))
+}
+
+/// Write `bytes` next to `exe` and rename it over `exe`, so a crash leaves
+/// either the old binary or the new one. Windows can't overwrite a running
+/// binary, so there the old one is moved aside first.
+fn replace_binary(exe: &Path, bytes: &[u8]) -> Result<(), Error> {
+    let io = |what: &str, path: &Path, e: std::io::Error| {
+        Error::Io(format!("Failed to {} {}: {}", what, path.display(), e))
+    };
+    let staged = sibling(exe, "new");
+    write_executable(&staged, bytes).map_err(|e| {
+        let _ = std::fs::remove_file(&staged);
+        io("write", &staged, e)
+    })?;
+
+    if cfg!(windows) {
+        let old = sibling(exe, "old");
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old).map_err(|e| io("move aside", exe, e))?;
+        if let Err(e) = std::fs::rename(&staged, exe) {
+            let _ = std::fs::rename(&old, exe);
+            return Err(io("replace", exe, e));
+        }
+    } else {
+        std::fs::rename(&staged, exe).map_err(|e| {
+            let _ = std::fs::remove_file(&staged);
+            io("replace", exe, e)
+        })?;
+    }
+    Ok(())
+}
+
+fn sibling(exe: &Path, suffix: &str) -> PathBuf {
+    let mut name = exe.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", suffix));
+    exe.with_file_name(name)
+}
+
+fn write_executable(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    let mut file = std::fs::File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.9"));
+        assert!(is_newer("v1.0", "0.9.9"));
+        assert!(is_newer("0.1.1", "0.1"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.1.0-beta", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+    }
+
+    #[test]
+    fn test_parse_manifest_bare_or_wrapped() {
+        let bare = r#"{"version":"1.2.3","url":"https://x/cli","sha256":"ab","signature":"cd"}"#;
+        let wrapped = format!(r#"{{"ok":true,"release":{}}}"#, bare);
+        assert_eq!(parse_manifest(bare).unwrap().version, "1.2.3");
+        assert_eq!(parse_manifest(&wrapped).unwrap(), parse_manifest(bare).unwrap());
+        assert!(parse_manifest(r#"{"ok":true}"#).is_err());
+    }
+
+    #[test]
+    fn test_replace_binary_checks_and_swaps() {
+        let dir = std::env::temp_dir().join(format!("exemem-update-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("exemem-cli");
+        std::fs::write(&exe, b"old").unwrap();
+
+        let new = b"new build";
+        let checksum = format!("{:x}", Sha256::digest(new));
+        assert!(verify_sha256(new, "00").is_err());
+        verify_sha256(new, &checksum.to_uppercase()).unwrap();
+
+        replace_binary(&exe, new).unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), new);
+        assert!(!sibling(&exe, "new").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        use ed25519_dalek::{Signer, SigningKey};
+        let engine = base64::engine::general_purpose::STANDARD;
+        let release_key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = engine.encode(release_key.verifying_key().as_bytes());
+        let signature = engine.encode(release_key.sign(b"new build").to_bytes());
+
+        verify_signature(b"new build", &signature, &public_key).unwrap();
+        assert!(verify_signature(b"other build", &signature, &public_key).is_err());
+        assert!(verify_signature(b"new build", "not base64!", &public_key).is_err());
+
+        let other_key = SigningKey::from_bytes(&[8; 32]);
+        let forged = engine.encode(other_key.sign(b"new build").to_bytes());
+        assert!(verify_signature(b"new build", &forged, &public_key).is_err());
+    }
+
+    #[test]
+    fn test_check_url_needs_https() {
+        check_url("https://releases.example.com/exemem-cli").unwrap();
+        assert!(check_url("http://releases.example.com/exemem-cli").is_err());
+        assert!(check_url("file:///tmp/exemem-cli").is_err());
+        assert!(check_url("not a url").is_err());
+    }
+}
@@ -0,0 +1,215 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Error codes the ingestion and query APIs return in their `error` field
+/// that we know how to explain to the user.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerErrorCode {
+    QuotaExceeded,
+    UnsupportedType,
+    DocumentTooLarge,
+    RateLimited,
+    /// A signed request's timestamp was too far from the server's clock
+    ClockSkew,
+}
+
+/// Languages we ship messages for. Anything else falls back to English.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Locale {
+    En,
+    Es,
+    Fr,
+    De,
+}
+
+impl Locale {
+    /// Parse a language tag such as "es", "fr-CA", or "de_DE.UTF-8".
+    pub fn from_tag(tag: &str) -> Self {
+        let lang = tag
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or("")
+            .to_lowercase();
+        match lang.as_str() {
+            "es" => Locale::Es,
+            "fr" => Locale::Fr,
+            "de" => Locale::De,
+            _ => Locale::En,
+        }
+    }
+
+    /// Use the configured locale if set, otherwise the system LANG.
+    pub fn resolve(configured: Option<&str>) -> Self {
+        configured
+            .map(str::to_string)
+            .or_else(|| std::env::var("LC_ALL").ok())
+            .or_else(|| std::env::var("LANG").ok())
+            .map(|tag| Self::from_tag(&tag))
+            .unwrap_or(Locale::En)
+    }
+}
+
+impl ServerErrorCode {
+    pub fn parse(code: &str) -> Option<Self> {
+        match code.trim() {
+            "quota_exceeded" => Some(Self::QuotaExceeded),
+            "unsupported_type" => Some(Self::UnsupportedType),
+            "document_too_large" => Some(Self::DocumentTooLarge),
+            "rate_limited" => Some(Self::RateLimited),
+            "clock_skew" => Some(Self::ClockSkew),
+            _ => None,
+        }
+    }
+
+    /// Extract a known code from a response body, which is either JSON with
+    /// an `error` (or `code`) field or a bare code string.
+    pub fn from_body(body: &str) -> Option<Self> {
+        match serde_json::from_str::<Value>(body) {
+            Ok(json) => ["code", "error"]
+                .iter()
+                .filter_map(|field| json.get(field).and_then(|v| v.as_str()))
+                .find_map(Self::parse),
+            Err(_) => Self::parse(body),
+        }
+    }
+
+    /// Whether retrying the same request could succeed.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited)
+    }
+
+    pub fn message(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::QuotaExceeded, Locale::En) => "Your Exemem storage quota is used up.",
+            (Self::QuotaExceeded, Locale::Es) => "Has agotado tu cuota de almacenamiento de Exemem.",
+            (Self::QuotaExceeded, Locale::Fr) => "Votre quota de stockage Exemem est épuisé.",
+            (Self::QuotaExceeded, Locale::De) => "Ihr Exemem-Speicherkontingent ist aufgebraucht.",
+            (Self::UnsupportedType, Locale::En) => "Exemem can't ingest this file type.",
+            (Self::UnsupportedType, Locale::Es) => "Exemem no puede procesar este tipo de archivo.",
+            (Self::UnsupportedType, Locale::Fr) => "Exemem ne peut pas importer ce type de fichier.",
+            (Self::UnsupportedType, Locale::De) => "Exemem kann diesen Dateityp nicht verarbeiten.",
+            (Self::DocumentTooLarge, Locale::En) => "This file is too large to ingest.",
+            (Self::DocumentTooLarge, Locale::Es) => "Este archivo es demasiado grande para procesarlo.",
+            (Self::DocumentTooLarge, Locale::Fr) => "Ce fichier est trop volumineux pour être importé.",
+            (Self::DocumentTooLarge, Locale::De) => "Diese Datei ist zu groß für die Verarbeitung.",
+            (Self::RateLimited, Locale::En) => "Too many requests were sent to Exemem.",
+            (Self::RateLimited, Locale::Es) => "Se enviaron demasiadas solicitudes a Exemem.",
+            (Self::RateLimited, Locale::Fr) => "Trop de requêtes ont été envoyées à Exemem.",
+            (Self::RateLimited, Locale::De) => "Es wurden zu viele Anfragen an Exemem gesendet.",
+            (Self::ClockSkew, Locale::En) => "This computer's clock is too far off for Exemem to accept signed requests.",
+            (Self::ClockSkew, Locale::Es) => "El reloj de este equipo está demasiado desfasado para que Exemem acepte solicitudes firmadas.",
+            (Self::ClockSkew, Locale::Fr) => "L'horloge de cet ordinateur est trop décalée pour qu'Exemem accepte les requêtes signées.",
+            (Self::ClockSkew, Locale::De) => "Die Uhr dieses Computers weicht zu stark ab, als dass Exemem signierte Anfragen akzeptiert.",
+        }
+    }
+
+    pub fn suggestion(&self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Self::QuotaExceeded, Locale::En) => {
+                "Free up space or upgrade your plan, then retry the failed files."
+            }
+            (Self::QuotaExceeded, Locale::Es) => {
+                "Libera espacio o mejora tu plan y vuelve a intentar los archivos fallidos."
+            }
+            (Self::QuotaExceeded, Locale::Fr) => {
+                "Libérez de l'espace ou changez d'offre, puis relancez les fichiers en échec."
+            }
+            (Self::QuotaExceeded, Locale::De) => {
+                "Geben Sie Speicher frei oder wechseln Sie den Tarif und wiederholen Sie dann die fehlgeschlagenen Dateien."
+            }
+            (Self::UnsupportedType, Locale::En) => {
+                "Convert it to PDF, CSV, JSON, or plain text and try again."
+            }
+            (Self::UnsupportedType, Locale::Es) => {
+                "Conviértelo a PDF, CSV, JSON o texto plano e inténtalo de nuevo."
+            }
+            (Self::UnsupportedType, Locale::Fr) => {
+                "Convertissez-le en PDF, CSV, JSON ou texte brut puis réessayez."
+            }
+            (Self::UnsupportedType, Locale::De) => {
+                "Konvertieren Sie sie in PDF, CSV, JSON oder Text und versuchen Sie es erneut."
+            }
+            (Self::DocumentTooLarge, Locale::En) => {
+                "Split it into smaller files or remove content you don't need indexed."
+            }
+            (Self::DocumentTooLarge, Locale::Es) => {
+                "Divídelo en archivos más pequeños o elimina el contenido que no necesites indexar."
+            }
+            (Self::DocumentTooLarge, Locale::Fr) => {
+                "Découpez-le en fichiers plus petits ou retirez le contenu inutile à l'index."
+            }
+            (Self::DocumentTooLarge, Locale::De) => {
+                "Teilen Sie sie in kleinere Dateien auf oder entfernen Sie nicht benötigte Inhalte."
+            }
+            (Self::RateLimited, Locale::En) => "Wait a few minutes; queued files will be retried.",
+            (Self::RateLimited, Locale::Es) => {
+                "Espera unos minutos; los archivos en cola se reintentarán."
+            }
+            (Self::RateLimited, Locale::Fr) => {
+                "Patientez quelques minutes ; les fichiers en attente seront relancés."
+            }
+            (Self::RateLimited, Locale::De) => {
+                "Warten Sie einige Minuten; Dateien in der Warteschlange werden erneut versucht."
+            }
+            (Self::ClockSkew, Locale::En) => "Turn on automatic time sync, then try again.",
+            (Self::ClockSkew, Locale::Es) => {
+                "Activa la sincronización automática de la hora y vuelve a intentarlo."
+            }
+            (Self::ClockSkew, Locale::Fr) => {
+                "Activez la synchronisation automatique de l'heure, puis réessayez."
+            }
+            (Self::ClockSkew, Locale::De) => {
+                "Aktivieren Sie die automatische Zeitsynchronisierung und versuchen Sie es erneut."
+            }
+        }
+    }
+
+    /// Message and next action as a single line for logs and activity entries.
+    pub fn describe(&self, locale: Locale) -> String {
+        format!("{} {}", self.message(locale), self.suggestion(locale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_body_json_error_field() {
+        let body = r#"{"ok": false, "error": "quota_exceeded"}"#;
+        assert_eq!(
+            ServerErrorCode::from_body(body),
+            Some(ServerErrorCode::QuotaExceeded)
+        );
+    }
+
+    #[test]
+    fn test_from_body_bare_code() {
+        assert_eq!(
+            ServerErrorCode::from_body("document_too_large"),
+            Some(ServerErrorCode::DocumentTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_from_body_clock_skew() {
+        let body = r#"{"ok": false, "code": "clock_skew", "error": "timestamp out of range"}"#;
+        assert_eq!(ServerErrorCode::from_body(body), Some(ServerErrorCode::ClockSkew));
+        assert!(!ServerErrorCode::ClockSkew.is_retryable());
+    }
+
+    #[test]
+    fn test_from_body_unknown() {
+        assert_eq!(ServerErrorCode::from_body(r#"{"error": "boom"}"#), None);
+        assert_eq!(ServerErrorCode::from_body("Internal Server Error"), None);
+    }
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from_tag("es"), Locale::Es);
+        assert_eq!(Locale::from_tag("fr-CA"), Locale::Fr);
+        assert_eq!(Locale::from_tag("de_DE.UTF-8"), Locale::De);
+        assert_eq!(Locale::from_tag("ja_JP"), Locale::En);
+    }
+}
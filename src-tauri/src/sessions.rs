@@ -0,0 +1,100 @@
+//! Conversations as the app and the CLI both see them: the transcripts
+//! recorded on this machine, plus whatever the backend still holds.
+
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::query::{QueryClient, RemoteSession};
+use crate::query_results;
+use crate::transcripts::{SessionSummary, TranscriptStore};
+
+/// `local` followed by the backend sessions it doesn't already have, up to
+/// `limit` in all. Backend sessions have no recorded turns.
+pub fn merge_remote(
+    mut local: Vec<SessionSummary>,
+    remote: Vec<RemoteSession>,
+    limit: usize,
+) -> Vec<SessionSummary> {
+    for session in remote {
+        if local.len() >= limit {
+            break;
+        }
+        if local.iter().any(|s| s.session_id == session.session_id) {
+            continue;
+        }
+        let created_at = session.created_at.unwrap_or(0);
+        local.push(SessionSummary {
+            session_id: session.session_id,
+            title: session.title.unwrap_or_default(),
+            turns: 0,
+            started_at: created_at,
+            last_active_at: created_at,
+        });
+    }
+    local
+}
+
+/// Delete a session's local transcript and stored results, returning how
+/// many turns were removed.
+pub fn forget_local(transcripts: &TranscriptStore, session_id: &str) -> Result<usize, Error> {
+    let removed = transcripts.delete_session(session_id).map_err(Error::Io)?;
+    if let Err(e) = query_results::delete_full_results(session_id) {
+        log::warn!("{}", e);
+    }
+    Ok(removed)
+}
+
+/// Delete the backend's copy of a session after `forget_local` removed
+/// `removed` turns. A backend failure only counts when there was nothing
+/// local either.
+pub async fn forget_remote(
+    config: &AppConfig,
+    client: &QueryClient,
+    session_id: &str,
+    removed: usize,
+) -> Result<usize, Error> {
+    match client.delete_session(config, session_id).await {
+        Ok(()) => Ok(removed),
+        Err(e) if removed > 0 => {
+            log::warn!("Couldn't delete backend session {}: {}", session_id, e);
+            Ok(removed)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(id: &str) -> SessionSummary {
+        SessionSummary {
+            session_id: id.to_string(),
+            title: "local".to_string(),
+            turns: 2,
+            started_at: 10,
+            last_active_at: 20,
+        }
+    }
+
+    fn remote(id: &str) -> RemoteSession {
+        RemoteSession {
+            session_id: id.to_string(),
+            title: None,
+            created_at: Some(5),
+        }
+    }
+
+    #[test]
+    fn test_merge_remote_skips_known_and_stops_at_limit() {
+        let merged = merge_remote(
+            vec![local("a")],
+            vec![remote("a"), remote("b"), remote("c")],
+            2,
+        );
+        let ids: Vec<&str> = merged.iter().map(|s| s.session_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        assert_eq!(merged[0].turns, 2);
+        assert_eq!(merged[1].turns, 0);
+        assert_eq!(merged[1].last_active_at, 5);
+    }
+}
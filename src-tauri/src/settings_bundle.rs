@@ -0,0 +1,236 @@
+//! One portable file with everything that's tedious to set up again on a
+//! second machine: the config, learned classification rules, saved queries
+//! and profiles.
+//!
+//! Credentials are left out unless asked for. Importing lays the bundle over
+//! the local settings, so anything it leaves out (such as the API key of a
+//! bundle without secrets) keeps its local value.
+
+use crate::config::AppConfig;
+use crate::config_migrations;
+use crate::feedback::FeedbackData;
+use crate::ledger;
+use crate::profiles::Profiles;
+use crate::saved_queries::{SavedQueries, SavedQuery};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+const BUNDLE_VERSION: u32 = 1;
+
+/// Config fields holding credentials
+const SECRET_FIELDS: &[&[&str]] = &[
+    &["api_key"],
+    &["session_token"],
+    &["proxy", "password"],
+    &["direct_s3", "access_key_id"],
+    &["direct_s3", "secret_access_key"],
+];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SettingsBundle {
+    bundle_version: u32,
+    /// Seconds since the Unix epoch
+    exported_at: u64,
+    includes_secrets: bool,
+    config: Value,
+    #[serde(default)]
+    pub(crate) classification_rules: FeedbackData,
+    #[serde(default)]
+    pub(crate) saved_queries: Vec<SavedQuery>,
+    /// Profiles by name
+    #[serde(default)]
+    profiles: Value,
+}
+
+/// What an import changed, for the UI.
+#[derive(Debug, Serialize)]
+pub(crate) struct ImportSummary {
+    pub config: AppConfig,
+    pub saved_queries: usize,
+    pub profiles: usize,
+    pub includes_secrets: bool,
+}
+
+impl SettingsBundle {
+    pub(crate) fn collect(
+        config: &AppConfig,
+        feedback: &FeedbackData,
+        saved_queries: &SavedQueries,
+        profiles: &Profiles,
+        include_secrets: bool,
+    ) -> Result<Self, String> {
+        let mut config = serde_json::to_value(config).map_err(|e| e.to_string())?;
+        let mut profiles = serde_json::to_value(profiles).map_err(|e| e.to_string())?;
+        let mut profiles = profiles["profiles"].take();
+        if !include_secrets {
+            strip_secrets(&mut config);
+            if let Some(profiles) = profiles.as_object_mut() {
+                for profile in profiles.values_mut() {
+                    remove_path(profile, &["api_key"]);
+                }
+            }
+        }
+        Ok(Self {
+            bundle_version: BUNDLE_VERSION,
+            exported_at: ledger::now_secs(),
+            includes_secrets: include_secrets,
+            config,
+            classification_rules: feedback.clone(),
+            saved_queries: saved_queries.list(),
+            profiles,
+        })
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> Result<(), String> {
+        let data = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub(crate) fn read(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let bundle: Self = serde_json::from_str(&data)
+            .map_err(|e| format!("{} is not an Exemem settings file: {}", path.display(), e))?;
+        if bundle.bundle_version > BUNDLE_VERSION {
+            return Err(format!(
+                "{} was exported by a newer version of Exemem; update to import it",
+                path.display()
+            ));
+        }
+        Ok(bundle)
+    }
+
+    pub(crate) fn includes_secrets(&self) -> bool {
+        self.includes_secrets
+    }
+
+    /// `current` with the bundle's config laid over it.
+    pub(crate) fn merged_config(&self, current: &AppConfig) -> Result<AppConfig, String> {
+        let mut imported = self.config.clone();
+        config_migrations::migrate(&mut imported)?;
+        let mut config = serde_json::to_value(current).map_err(|e| e.to_string())?;
+        merge(&mut config, imported);
+        serde_json::from_value(config).map_err(|e| format!("Invalid config in settings file: {}", e))
+    }
+
+    /// `current` with the bundle's profiles added, replacing same-named
+    /// ones field by field. Which profile is current doesn't change.
+    pub(crate) fn merged_profiles(&self, current: &Profiles) -> Result<(Profiles, usize), String> {
+        let count = self.profiles.as_object().map_or(0, |p| p.len());
+        let mut profiles = serde_json::to_value(current).map_err(|e| e.to_string())?;
+        merge(&mut profiles["profiles"], self.profiles.clone());
+        let profiles = serde_json::from_value(profiles)
+            .map_err(|e| format!("Invalid profiles in settings file: {}", e))?;
+        Ok((profiles, count))
+    }
+}
+
+fn strip_secrets(config: &mut Value) {
+    for path in SECRET_FIELDS {
+        remove_path(config, path);
+    }
+}
+
+fn remove_path(value: &mut Value, path: &[&str]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut target = value;
+    for key in parents {
+        match target.get_mut(*key) {
+            Some(next) => target = next,
+            None => return,
+        }
+    }
+    if let Some(fields) = target.as_object_mut() {
+        fields.remove(*last);
+    }
+}
+
+/// Copy `source` into `target`, recursing into objects so fields missing
+/// from `source` keep their `target` value.
+fn merge(target: &mut Value, source: Value) {
+    match (target, source) {
+        (Value::Object(target), Value::Object(source)) => {
+            for (key, value) in source {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, source) => *target = source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::ProxyConfig;
+
+    fn local_config() -> AppConfig {
+        AppConfig {
+            api_key: "local-key".to_string(),
+            default_tags: vec!["laptop".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_export_without_secrets_keeps_local_credentials() {
+        let exported = AppConfig {
+            api_key: "exported-key".to_string(),
+            session_token: Some("token".to_string()),
+            proxy: ProxyConfig {
+                url: Some("http://proxy.example:3128".to_string()),
+                password: Some("hunter2".to_string()),
+                ..Default::default()
+            },
+            default_tags: vec!["work".to_string()],
+            ..Default::default()
+        };
+        let bundle = SettingsBundle::collect(
+            &exported,
+            &FeedbackData::default(),
+            &SavedQueries::empty().unwrap(),
+            &Profiles::default(),
+            false,
+        )
+        .unwrap();
+        let text = serde_json::to_string(&bundle).unwrap();
+        assert!(!text.contains("exported-key") && !text.contains("hunter2") && !text.contains("\"token\""));
+
+        let merged = bundle.merged_config(&local_config()).unwrap();
+        assert_eq!(merged.api_key, "local-key");
+        assert_eq!(merged.default_tags, vec!["work".to_string()]);
+        assert_eq!(merged.proxy.url.as_deref(), Some("http://proxy.example:3128"));
+        assert_eq!(merged.proxy.password, None);
+    }
+
+    #[test]
+    fn test_export_with_secrets() {
+        let bundle = SettingsBundle::collect(
+            &AppConfig {
+                api_key: "exported-key".to_string(),
+                ..Default::default()
+            },
+            &FeedbackData::default(),
+            &SavedQueries::empty().unwrap(),
+            &Profiles::default(),
+            true,
+        )
+        .unwrap();
+        assert!(bundle.includes_secrets());
+        assert_eq!(bundle.merged_config(&local_config()).unwrap().api_key, "exported-key");
+    }
+
+    #[test]
+    fn test_merge_recurses_into_objects() {
+        let mut target = serde_json::json!({"a": {"x": 1, "y": 2}, "b": [1, 2]});
+        merge(&mut target, serde_json::json!({"a": {"x": 3}, "b": [3], "c": true}));
+        assert_eq!(target, serde_json::json!({"a": {"x": 3, "y": 2}, "b": [3], "c": true}));
+    }
+}
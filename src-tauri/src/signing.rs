@@ -0,0 +1,150 @@
+use hmac::{Hmac, Mac};
+use reqwest::{Request, RequestBuilder, Response};
+use sha2::{Digest, Sha256};
+
+/// Header naming which key signed the request, without revealing it
+pub const KEY_ID_HEADER: &str = "X-Exemem-Key-Id";
+/// Unix seconds when the request was signed; the server rejects requests
+/// too far from its own clock with a `clock_skew` error
+pub const TIMESTAMP_HEADER: &str = "X-Exemem-Timestamp";
+/// Hex SHA-256 of the body, or `UNSIGNED-PAYLOAD` for streamed bodies
+pub const CONTENT_HASH_HEADER: &str = "X-Exemem-Content-SHA256";
+/// Hex HMAC-SHA256 over the method, path, body hash and timestamp
+pub const SIGNATURE_HEADER: &str = "X-Exemem-Signature";
+
+const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Signs API requests with an HMAC keyed by the API key, so the key itself
+/// never appears in a request and a captured request can't be replayed once
+/// its timestamp is stale.
+#[derive(Clone)]
+pub struct RequestSigner {
+    key_id: String,
+    secret: Vec<u8>,
+}
+
+impl std::fmt::Debug for RequestSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RequestSigner").field("key_id", &self.key_id).finish()
+    }
+}
+
+impl RequestSigner {
+    pub fn from_api_key(api_key: &str) -> Self {
+        let digest = format!("{:x}", Sha256::digest(api_key.as_bytes()));
+        Self {
+            key_id: digest[..16].to_string(),
+            secret: api_key.as_bytes().to_vec(),
+        }
+    }
+
+    /// Add the signature headers to `req`, dropping any raw API key header.
+    pub fn sign(&self, req: &mut Request, now_secs: u64) {
+        let content_hash = match req.body().map(|b| b.as_bytes()) {
+            Some(Some(bytes)) => format!("{:x}", Sha256::digest(bytes)),
+            Some(None) => UNSIGNED_PAYLOAD.to_string(),
+            None => format!("{:x}", Sha256::digest(b"")),
+        };
+        let url = req.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let signature = self.signature(req.method().as_str(), &path, &content_hash, now_secs);
+
+        let headers = req.headers_mut();
+        headers.remove("X-API-Key");
+        for (name, value) in [
+            (KEY_ID_HEADER, self.key_id.clone()),
+            (TIMESTAMP_HEADER, now_secs.to_string()),
+            (CONTENT_HASH_HEADER, content_hash),
+            (SIGNATURE_HEADER, signature),
+        ] {
+            if let Ok(value) = value.parse() {
+                headers.insert(name, value);
+            }
+        }
+    }
+
+    fn signature(&self, method: &str, path: &str, content_hash: &str, timestamp: u64) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret)
+            .expect("HMAC accepts keys of any length");
+        mac.update(format!("{}\n{}\n{}\n{}", method, path, content_hash, timestamp).as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// `send` for requests that may need signing.
+pub(crate) trait SendSigned {
+    /// Send as-is without a signer, otherwise sign the built request first.
+    async fn send_signed(self, signer: Option<&RequestSigner>) -> reqwest::Result<Response>;
+}
+
+impl SendSigned for RequestBuilder {
+    async fn send_signed(self, signer: Option<&RequestSigner>) -> reqwest::Result<Response> {
+        let Some(signer) = signer else {
+            return self.send().await;
+        };
+        let (client, req) = self.build_split();
+        let mut req = req?;
+        signer.sign(&mut req, crate::ledger::now_secs());
+        client.execute(req).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post(body: &str) -> Request {
+        reqwest::Client::new()
+            .post("https://api.example.com/api/storage/get?x=1")
+            .header("X-API-Key", "secret-key")
+            .body(body.to_string())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sign_replaces_api_key() {
+        let signer = RequestSigner::from_api_key("secret-key");
+        let mut req = post("{}");
+        signer.sign(&mut req, 1_700_000_000);
+
+        let headers = req.headers();
+        assert!(headers.get("X-API-Key").is_none());
+        assert_eq!(headers[TIMESTAMP_HEADER], "1700000000");
+        assert_eq!(headers[KEY_ID_HEADER].len(), 16);
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            signer.signature(
+                "POST",
+                "/api/storage/get?x=1",
+                &format!("{:x}", Sha256::digest(b"{}")),
+                1_700_000_000
+            )
+        );
+    }
+
+    #[test]
+    fn test_signature_covers_body_and_time() {
+        let signer = RequestSigner::from_api_key("secret-key");
+        let signed = |body: &str, now: u64| {
+            let mut req = post(body);
+            signer.sign(&mut req, now);
+            req.headers()[SIGNATURE_HEADER].clone()
+        };
+        assert_eq!(signed("a", 1), signed("a", 1));
+        assert_ne!(signed("a", 1), signed("b", 1));
+        assert_ne!(signed("a", 1), signed("a", 2));
+
+        let other = RequestSigner::from_api_key("other-key");
+        let mut req = post("a");
+        other.sign(&mut req, 1);
+        assert_ne!(req.headers()[SIGNATURE_HEADER], signed("a", 1));
+    }
+}
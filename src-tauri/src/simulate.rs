@@ -0,0 +1,301 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::paths;
+use crate::uploader::{
+    is_success_status, is_terminal_status, ProgressResponse, MAX_CONCURRENT_UPLOADS,
+    MAX_PROGRESS_POLLS, PROGRESS_POLL_INTERVAL,
+};
+
+/// Set to any value to append every ingestion progress poll to the trace file
+pub const RECORD_TRACES_ENV: &str = "EXEMEM_RECORD_TRACES";
+
+/// One recorded progress poll, relative to when polling for that ingestion began.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracePoint {
+    pub progress_id: String,
+    pub file: String,
+    pub elapsed_ms: u64,
+    pub status: String,
+    #[serde(default)]
+    pub percent: Option<f64>,
+}
+
+/// All recorded polls for one ingestion.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub file: String,
+    pub points: Vec<TracePoint>,
+}
+
+pub fn traces_path() -> Result<PathBuf, String> {
+    Ok(paths::data_dir()?.join("progress_traces.jsonl"))
+}
+
+/// Append a poll result to the trace file when recording is enabled.
+pub(crate) fn record_trace_point(file: &str, elapsed: Duration, resp: &ProgressResponse) {
+    if std::env::var_os(RECORD_TRACES_ENV).is_none() {
+        return;
+    }
+    let point = TracePoint {
+        progress_id: resp.progress_id.clone(),
+        file: file.to_string(),
+        elapsed_ms: elapsed.as_millis() as u64,
+        status: resp.status.clone(),
+        percent: resp.percent,
+    };
+    let written = traces_path().and_then(|path| {
+        let mut out = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open trace file: {}", e))?;
+        let line = serde_json::to_string(&point).map_err(|e| e.to_string())?;
+        writeln!(out, "{}", line).map_err(|e| format!("Failed to write trace: {}", e))
+    });
+    if let Err(e) = written {
+        log::warn!("Failed to record progress trace: {}", e);
+    }
+}
+
+/// Read a trace file, grouping polls by ingestion in first-seen order.
+pub fn load_traces(path: &Path) -> Result<Vec<Trace>, String> {
+    let file = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut traces: Vec<(String, Trace)> = Vec::new();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read trace: {}", e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let point: TracePoint = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid trace line {}: {}", number + 1, e))?;
+        match traces.iter_mut().find(|(id, _)| *id == point.progress_id) {
+            Some((_, trace)) => trace.points.push(point),
+            None => traces.push((
+                point.progress_id.clone(),
+                Trace {
+                    file: point.file.clone(),
+                    points: vec![point],
+                },
+            )),
+        }
+    }
+    Ok(traces
+        .into_iter()
+        .map(|(_, mut trace)| {
+            trace.points.sort_by_key(|p| p.elapsed_ms);
+            trace
+        })
+        .collect())
+}
+
+/// Knobs for replaying traces under different conditions.
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    /// Concurrent upload slots
+    pub slots: usize,
+    /// Simulated time to upload each file
+    pub upload_ms: u64,
+    /// Stretch server-side indexing time by this factor (2.0 = twice as slow)
+    pub slowdown: f64,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            slots: MAX_CONCURRENT_UPLOADS,
+            upload_ms: 1000,
+            slowdown: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileOutcome {
+    pub file: String,
+    /// Simulated milliseconds since the batch was approved
+    pub upload_started_ms: u64,
+    pub uploaded_ms: u64,
+    pub finished_ms: u64,
+    /// "done", "failed" or "timed_out"
+    pub outcome: String,
+    pub polls: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SimulationReport {
+    pub files: Vec<FileOutcome>,
+    pub done: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    /// Files that finished before one approved ahead of them
+    pub reordered: usize,
+    pub makespan_ms: u64,
+    pub mean_latency_ms: u64,
+}
+
+/// Run the upload scheduler and progress polling over recorded traces in
+/// simulated time, as if every traced file was approved in one batch.
+///
+/// Uses the same slot count, poll interval, poll limit and terminal statuses
+/// as the real pipeline, so changing any of them shows up here first.
+pub fn simulate(traces: &[Trace], config: &SimulationConfig) -> SimulationReport {
+    let interval = PROGRESS_POLL_INTERVAL.as_millis() as u64;
+    let slowdown = if config.slowdown > 0.0 { config.slowdown } else { 1.0 };
+
+    // Upload slots, by the time they next become free. Files take slots in
+    // approval order, like equal-priority waiters in the upload queue.
+    let mut free_at: BinaryHeap<Reverse<u64>> = (0..config.slots.max(1)).map(|_| Reverse(0)).collect();
+
+    let files: Vec<FileOutcome> = traces
+        .iter()
+        .map(|trace| {
+            let Reverse(start) = free_at.pop().unwrap_or(Reverse(0));
+            let uploaded = start + config.upload_ms;
+            free_at.push(Reverse(uploaded));
+
+            let status_at = |since_upload: u64| {
+                let server_ms = (since_upload as f64 / slowdown) as u64;
+                trace
+                    .points
+                    .iter()
+                    .take_while(|p| p.elapsed_ms <= server_ms)
+                    .last()
+                    .map(|p| p.status.as_str())
+            };
+
+            let mut outcome = FileOutcome {
+                file: trace.file.clone(),
+                upload_started_ms: start,
+                uploaded_ms: uploaded,
+                finished_ms: uploaded + interval * u64::from(MAX_PROGRESS_POLLS),
+                outcome: "timed_out".to_string(),
+                polls: MAX_PROGRESS_POLLS,
+            };
+            for poll in 1..=MAX_PROGRESS_POLLS {
+                let since_upload = interval * u64::from(poll);
+                if let Some(status) = status_at(since_upload).filter(|s| is_terminal_status(s)) {
+                    outcome.finished_ms = uploaded + since_upload;
+                    outcome.polls = poll;
+                    outcome.outcome = if is_success_status(status) {
+                        "done".to_string()
+                    } else {
+                        "failed".to_string()
+                    };
+                    break;
+                }
+            }
+            outcome
+        })
+        .collect();
+
+    let count = |what: &str| files.iter().filter(|f| f.outcome == what).count();
+    let mut latest_before = 0;
+    let mut reordered = 0;
+    for file in &files {
+        if file.finished_ms < latest_before {
+            reordered += 1;
+        }
+        latest_before = latest_before.max(file.finished_ms);
+    }
+
+    SimulationReport {
+        done: count("done"),
+        failed: count("failed"),
+        timed_out: count("timed_out"),
+        reordered,
+        makespan_ms: files.iter().map(|f| f.finished_ms).max().unwrap_or(0),
+        mean_latency_ms: if files.is_empty() {
+            0
+        } else {
+            files.iter().map(|f| f.finished_ms).sum::<u64>() / files.len() as u64
+        },
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(file: &str, points: &[(u64, &str)]) -> Trace {
+        Trace {
+            file: file.to_string(),
+            points: points
+                .iter()
+                .map(|(ms, status)| TracePoint {
+                    progress_id: file.to_string(),
+                    file: file.to_string(),
+                    elapsed_ms: *ms,
+                    status: status.to_string(),
+                    percent: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_slots_and_outcomes() {
+        let traces = vec![
+            trace("a", &[(2000, "indexing"), (9000, "completed")]),
+            trace("b", &[(3000, "failed")]),
+            trace("c", &[(1000, "done")]),
+        ];
+        let config = SimulationConfig {
+            slots: 2,
+            upload_ms: 1000,
+            slowdown: 1.0,
+        };
+        let report = simulate(&traces, &config);
+
+        assert_eq!(report.done, 2);
+        assert_eq!(report.failed, 1);
+        // c waits for a's slot
+        assert_eq!(report.files[2].upload_started_ms, 1000);
+        // a finishes at the first poll after 9s: 1s upload + 10s
+        assert_eq!(report.files[0].finished_ms, 11_000);
+        // b and c both finish before a
+        assert_eq!(report.reordered, 2);
+    }
+
+    #[test]
+    fn test_slow_indexing_times_out() {
+        let traces = vec![trace("a", &[(200_000, "done")])];
+        let normal = simulate(&traces, &SimulationConfig::default());
+        assert_eq!(normal.done, 1);
+
+        let slow = simulate(
+            &traces,
+            &SimulationConfig {
+                slowdown: 2.0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(slow.timed_out, 1);
+        assert_eq!(slow.files[0].polls, MAX_PROGRESS_POLLS);
+    }
+
+    #[test]
+    fn test_load_groups_by_ingestion() {
+        let dir = std::env::temp_dir().join(format!("exemem-traces-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("traces.jsonl");
+        let lines = [
+            r#"{"progress_id":"p1","file":"a.txt","elapsed_ms":4000,"status":"done"}"#,
+            r#"{"progress_id":"p2","file":"b.txt","elapsed_ms":2000,"status":"indexing"}"#,
+            r#"{"progress_id":"p1","file":"a.txt","elapsed_ms":2000,"status":"indexing"}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let traces = load_traces(&path).unwrap();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[0].file, "a.txt");
+        assert_eq!(traces[0].points[0].elapsed_ms, 2000);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
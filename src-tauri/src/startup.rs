@@ -0,0 +1,107 @@
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Tray readiness slower than this is logged as a warning
+const TRAY_READY_TARGET_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupPhase {
+    pub name: String,
+    /// Milliseconds since the process started
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    /// Ran in the background after the tray was ready
+    pub deferred: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StartupReport {
+    pub phases: Vec<StartupPhase>,
+    /// Milliseconds from process start until the tray icon was up
+    pub tray_ready_ms: Option<u64>,
+    pub tray_ready_target_ms: u64,
+}
+
+/// Timings for each startup phase, so slow cold starts can be diagnosed
+/// from `get_startup_report` instead of guessed at.
+pub struct StartupProfile {
+    origin: Instant,
+    phases: Mutex<Vec<StartupPhase>>,
+    tray_ready_ms: Mutex<Option<u64>>,
+}
+
+impl Default for StartupProfile {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+            phases: Mutex::new(Vec::new()),
+            tray_ready_ms: Mutex::new(None),
+        }
+    }
+}
+
+impl StartupProfile {
+    /// Time a phase on the critical path.
+    pub fn measure<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let value = f();
+        self.record(name, start, false);
+        value
+    }
+
+    /// Record a phase that began at `start` and has just finished.
+    pub fn record(&self, name: &str, start: Instant, deferred: bool) {
+        let phase = StartupPhase {
+            name: name.to_string(),
+            started_ms: start.saturating_duration_since(self.origin).as_millis() as u64,
+            duration_ms: start.elapsed().as_millis() as u64,
+            deferred,
+        };
+        log::debug!("Startup phase {} took {}ms", phase.name, phase.duration_ms);
+        self.phases.lock().unwrap().push(phase);
+    }
+
+    pub fn mark_tray_ready(&self) {
+        let elapsed = self.origin.elapsed().as_millis() as u64;
+        *self.tray_ready_ms.lock().unwrap() = Some(elapsed);
+        if elapsed > TRAY_READY_TARGET_MS {
+            log::warn!(
+                "Tray ready after {}ms (target {}ms)",
+                elapsed,
+                TRAY_READY_TARGET_MS
+            );
+        } else {
+            log::info!("Tray ready after {}ms", elapsed);
+        }
+    }
+
+    pub fn report(&self) -> StartupReport {
+        StartupReport {
+            phases: self.phases.lock().unwrap().clone(),
+            tray_ready_ms: *self.tray_ready_ms.lock().unwrap(),
+            tray_ready_target_ms: TRAY_READY_TARGET_MS,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_phases_in_order() {
+        let profile = StartupProfile::default();
+        let value = profile.measure("config", || 42);
+        assert_eq!(value, 42);
+        profile.mark_tray_ready();
+        profile.record("file_count", Instant::now(), true);
+
+        let report = profile.report();
+        let names: Vec<_> = report.phases.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["config", "file_count"]);
+        assert!(!report.phases[0].deferred);
+        assert!(report.phases[1].deferred);
+        assert!(report.tray_ready_ms.is_some());
+    }
+}
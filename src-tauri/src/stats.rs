@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::logs::civil_date;
+use crate::persist::JsonStore;
+
+/// Daily totals older than this are dropped
+const MAX_DAYS: usize = 365;
+
+/// Upload totals for one UTC day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DayTotals {
+    pub files: u64,
+    /// Bytes sent over the network, after compression
+    pub uploaded_bytes: u64,
+    /// Size of the files on disk
+    pub original_bytes: u64,
+    /// Time spent in S3 PUTs, including retries
+    pub transfer_ms: u64,
+}
+
+impl DayTotals {
+    fn add(&mut self, other: &DayTotals) {
+        self.files += other.files;
+        self.uploaded_bytes += other.uploaded_bytes;
+        self.original_bytes += other.original_bytes;
+        self.transfer_ms += other.transfer_ms;
+    }
+
+    /// Average bytes per second while transferring, if anything was sent.
+    fn throughput(&self) -> Option<u64> {
+        (self.transfer_ms > 0).then(|| self.uploaded_bytes * 1000 / self.transfer_ms)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DayStats {
+    /// YYYY-MM-DD (UTC)
+    pub date: String,
+    #[serde(flatten)]
+    pub totals: DayTotals,
+    /// Bytes per second
+    pub throughput: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UploadStatsReport {
+    /// Oldest first; days without uploads are omitted
+    pub days: Vec<DayStats>,
+    /// Sums over `days`
+    pub total: DayTotals,
+    pub today: DayTotals,
+    /// Bytes per second over `days`
+    pub average_throughput: Option<u64>,
+}
+
+/// Persistent per-day upload totals, kept separately from the ledger so
+/// usage figures survive history purges.
+pub struct UploadStats {
+    store: JsonStore<BTreeMap<String, DayTotals>>,
+}
+
+fn date_key(secs: u64) -> String {
+    let (year, month, day) = civil_date((secs / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl UploadStats {
+    const FILE: &'static str = "upload_stats.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "upload stats").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "upload stats").map(|store| Self { store })
+    }
+
+    /// Count one successful upload finished at `now` (Unix seconds).
+    pub fn record(
+        &mut self,
+        now: u64,
+        original_bytes: u64,
+        uploaded_bytes: u64,
+        transfer: Duration,
+    ) -> Result<(), String> {
+        self.store.update(|days| {
+            days.entry(date_key(now)).or_default().add(&DayTotals {
+                files: 1,
+                uploaded_bytes,
+                original_bytes,
+                transfer_ms: transfer.as_millis() as u64,
+            });
+            while days.len() > MAX_DAYS {
+                days.pop_first();
+            }
+        })
+    }
+
+    /// Totals for the `days` most recent days up to `now`, including today.
+    pub fn report(&self, now: u64, days: usize) -> UploadStatsReport {
+        let first = date_key(now.saturating_sub(days.saturating_sub(1) as u64 * 86_400));
+        let mut total = DayTotals::default();
+        let recorded = self.store.get();
+        let days: Vec<DayStats> = recorded
+            .range(first..)
+            .map(|(date, totals)| {
+                total.add(totals);
+                DayStats {
+                    date: date.clone(),
+                    totals: totals.clone(),
+                    throughput: totals.throughput(),
+                }
+            })
+            .collect();
+
+        UploadStatsReport {
+            today: recorded.get(&date_key(now)).cloned().unwrap_or_default(),
+            average_throughput: total.throughput(),
+            total,
+            days,
+        }
+    }
+}
+
+/// Upload statistics as recorded by the app, for tools outside it.
+pub fn upload_stats(days: usize) -> Result<UploadStatsReport, String> {
+    Ok(UploadStats::load()?.report(crate::ledger::now_secs(), days))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_stats() -> UploadStats {
+        let dir = std::env::temp_dir().join(format!("exemem-stats-{}", uuid::Uuid::new_v4()));
+        open(dir.join("upload_stats.json"))
+    }
+
+    fn open(path: PathBuf) -> UploadStats {
+        UploadStats {
+            store: JsonStore::load_from(path, "upload stats").unwrap(),
+        }
+    }
+
+    // 2024-03-01 12:00:00 UTC
+    const NOON: u64 = 1_709_294_400;
+
+    #[test]
+    fn test_daily_totals_and_throughput() {
+        let mut stats = temp_stats();
+        stats
+            .record(NOON, 4000, 2000, Duration::from_millis(500))
+            .unwrap();
+        stats
+            .record(NOON + 60, 1000, 1000, Duration::from_millis(500))
+            .unwrap();
+        stats
+            .record(NOON - 86_400, 500, 500, Duration::from_millis(100))
+            .unwrap();
+
+        let report = stats.report(NOON, 7);
+        assert_eq!(report.days.len(), 2);
+        assert_eq!(report.days[0].date, "2024-02-29");
+        assert_eq!(report.today.files, 2);
+        assert_eq!(report.today.uploaded_bytes, 3000);
+        assert_eq!(report.days[1].throughput, Some(3000));
+        assert_eq!(report.total.original_bytes, 5500);
+        assert_eq!(report.average_throughput, Some(3500 * 1000 / 1100));
+
+        let today_only = stats.report(NOON, 1);
+        assert_eq!(today_only.days.len(), 1);
+    }
+
+    #[test]
+    fn test_persists_across_loads() {
+        let mut stats = temp_stats();
+        stats.record(NOON, 10, 10, Duration::ZERO).unwrap();
+        let reloaded = open(stats.store.path().to_path_buf());
+        let report = reloaded.report(NOON, 1);
+        assert_eq!(report.today.files, 1);
+        assert_eq!(report.average_throughput, None);
+        let _ = std::fs::remove_dir_all(stats.store.path().parent().unwrap());
+    }
+}
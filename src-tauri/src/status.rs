@@ -0,0 +1,175 @@
+//! What the client is doing, read from the state the app and the CLI share
+//! on disk, so both report the same thing.
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::config::AppConfig;
+use crate::config_check;
+use crate::dead_letter::DeadLetterQueue;
+use crate::headless::{WatchInfo, WatchLock};
+use crate::ledger::{self, LedgerEntry};
+use crate::offline_queue::OfflineQueue;
+use crate::query::{AccountUsage, QueryClient};
+use crate::schedule::ScheduledUploads;
+use crate::uploader::UploadStatus;
+
+/// Work waiting to go out.
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct PendingWork {
+    /// Watcher uploads held back by quiet hours
+    pub scheduled_uploads: usize,
+    /// Requests queued while the backend was unreachable
+    pub offline_requests: usize,
+    /// Failed uploads waiting for a retry
+    pub failed_uploads: usize,
+}
+
+/// The most recent failure, from either the ledger or the failed-upload list.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LastError {
+    pub filename: String,
+    pub error: Option<String>,
+    /// Seconds since the Unix epoch
+    pub at: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Connectivity {
+    pub api_url: String,
+    /// None when it wasn't checked, e.g. without an API key
+    pub reachable: Option<bool>,
+    pub key_accepted: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientStatus {
+    /// The running headless watcher, if any
+    pub watcher: Option<WatchInfo>,
+    pub watched_folders: Vec<PathBuf>,
+    pub pending: PendingWork,
+    pub last_upload: Option<LedgerEntry>,
+    pub last_error: Option<LastError>,
+    /// Left out when the API wasn't contacted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connectivity: Option<Connectivity>,
+    /// Left out when the API wasn't contacted or didn't report it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<AccountUsage>,
+}
+
+/// Status from local state only. Stores that can't be read count as empty.
+pub fn local_status(config: &AppConfig) -> ClientStatus {
+    let warn = |what: &str, e: String| log::warn!("Failed to read {}: {}", what, e);
+    let history = ledger::upload_history(&Default::default()).unwrap_or_else(|e| {
+        warn("upload history", e);
+        Vec::new()
+    });
+    let failed = DeadLetterQueue::load()
+        .map(|queue| queue.list())
+        .unwrap_or_else(|e| {
+            warn("failed uploads", e);
+            Vec::new()
+        });
+    let pending = PendingWork {
+        scheduled_uploads: ScheduledUploads::load()
+            .map(|s| s.list().len())
+            .unwrap_or_else(|e| {
+                warn("scheduled uploads", e);
+                0
+            }),
+        offline_requests: OfflineQueue::load()
+            .map(|q| q.list().len())
+            .unwrap_or_else(|e| {
+                warn("offline queue", e);
+                0
+            }),
+        failed_uploads: failed.len(),
+    };
+
+    let last_ledger_error = history
+        .iter()
+        .find(|e| e.status == UploadStatus::Error)
+        .map(|e| LastError {
+            filename: e.filename.clone(),
+            error: e.error.clone(),
+            at: e.recorded_at(),
+        });
+    let last_failed_upload = failed.iter().max_by_key(|f| f.last_failed_at).map(|f| LastError {
+        filename: f.filename.clone(),
+        error: f.error.clone(),
+        at: Some(f.last_failed_at),
+    });
+
+    ClientStatus {
+        watcher: WatchLock::running(),
+        watched_folders: config.watched_folder.iter().cloned().collect(),
+        pending,
+        last_upload: history.into_iter().find(|e| is_successful(&e.status)),
+        last_error: latest(last_ledger_error, last_failed_upload),
+        connectivity: None,
+        usage: None,
+    }
+}
+
+/// `local_status`, plus what the configured API says about the key and
+/// the account's usage.
+pub async fn status(config: &AppConfig, client: &QueryClient) -> ClientStatus {
+    let report = config_check::check_config(config, client).await;
+    let usage = if report.api_key_accepted == Some(true) {
+        client
+            .get_account_usage(config)
+            .await
+            .map_err(|e| log::warn!("Failed to get account usage: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    ClientStatus {
+        connectivity: Some(Connectivity {
+            api_url: report.api_url,
+            reachable: report.api_reachable,
+            key_accepted: report.api_key_accepted,
+        }),
+        usage,
+        ..local_status(config)
+    }
+}
+
+fn is_successful(status: &UploadStatus) -> bool {
+    matches!(status, UploadStatus::Uploaded | UploadStatus::Ingesting | UploadStatus::Done)
+}
+
+fn latest(a: Option<LastError>, b: Option<LastError>) -> Option<LastError> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(if b.at > a.at { b } else { a }),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(filename: &str, at: Option<u64>) -> LastError {
+        LastError {
+            filename: filename.to_string(),
+            error: None,
+            at,
+        }
+    }
+
+    #[test]
+    fn test_latest_error_wins() {
+        assert_eq!(
+            latest(Some(error("a", Some(5))), Some(error("b", Some(9)))),
+            Some(error("b", Some(9)))
+        );
+        assert_eq!(
+            latest(Some(error("a", Some(5))), Some(error("b", None))),
+            Some(error("a", Some(5)))
+        );
+        assert_eq!(latest(None, Some(error("b", None))), Some(error("b", None)));
+        assert_eq!(latest(None, None), None);
+    }
+}
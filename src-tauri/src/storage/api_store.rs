@@ -3,9 +3,50 @@ use fold_db::storage::traits::{ExecutionModel, FlushBehavior, KvStore};
 use async_trait::async_trait;
 use base64::engine::general_purpose::STANDARD as BASE64;
 use base64::Engine as _;
-use reqwest::Client;
+use futures_util::stream::{self, Stream};
+use reqwest::{Client, StatusCode};
 use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::circuit::CircuitBreaker;
+use crate::config::AppConfig;
+use super::metrics::{CallSample, OperationStats, StorageMetrics};
+use crate::retry::{self, Retry, RetryError, RetryPolicy};
+use crate::signing::{RequestSigner, SendSigned};
+
+use super::journal::{JournalAction, OfflineSync};
+use super::value_codec;
+
+/// Most items the Storage API accepts in one batch request
+const BATCH_SIZE: usize = 25;
+
+/// Longest a single Storage API request may take by default
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Three tries over about a second and a half
+const DEFAULT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_millis(500),
+    max_busy_waits: 0,
+};
+
+/// Actions whose outcome depends on the state they find, so a retry after
+/// a lost response could report the wrong result
+const NON_IDEMPOTENT_ACTIONS: &[&str] = &["conditional-put"];
+
+/// Most operations in one transaction (DynamoDB TransactWriteItems limit)
+pub const MAX_TRANSACT_OPS: usize = 100;
+
+/// Most encoded bytes in one transaction, under DynamoDB's 4 MB cap
+const MAX_TRANSACT_BYTES: usize = 4_000_000;
+
+/// Items per scan-prefix request, small enough to stay under the Lambda
+/// response size limit for typical values
+const SCAN_PAGE_SIZE: usize = 500;
 
 /// Authentication method for the Exemem Storage API.
 #[derive(Clone, Debug)]
@@ -16,167 +57,706 @@ pub enum ExememAuth {
     ApiKey(String),
     /// Authorization: Bearer <token>
     BearerToken(String),
+    /// HMAC signature keyed by the API key; the key itself is never sent
+    SignedApiKey(String),
 }
 
-/// KvStore implementation that routes operations through the Exemem Storage API.
-///
-/// Each instance is bound to a specific namespace. All keys and values are
-/// base64-encoded in transit. The Storage API Lambda handles DynamoDB routing,
-/// user isolation, and namespace-to-table mapping.
-pub struct ExememApiStore {
+impl ExememAuth {
+    /// The credentials the rest of the app uses for `config`: a signed API
+    /// key where signing is on, else the API key, else the user hash. None
+    /// when nothing is configured.
+    pub fn from_config(config: &AppConfig) -> Option<Self> {
+        if config.api_key.is_empty() {
+            return config.user_hash.clone().map(ExememAuth::UserHash);
+        }
+        Some(match config.signer() {
+            Some(_) => ExememAuth::SignedApiKey(config.api_key.clone()),
+            None => ExememAuth::ApiKey(config.api_key.clone()),
+        })
+    }
+
+    fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            ExememAuth::UserHash(hash) => req.header("X-User-Hash", hash),
+            ExememAuth::ApiKey(key) => req.header("X-API-Key", key),
+            ExememAuth::BearerToken(token) => {
+                req.header("Authorization", format!("Bearer {}", token))
+            }
+            // Signed in `StorageApi::send`, once the body is final
+            ExememAuth::SignedApiKey(_) => req,
+        }
+    }
+
+    fn signer(&self) -> Option<RequestSigner> {
+        match self {
+            ExememAuth::SignedApiKey(key) => Some(RequestSigner::from_api_key(key)),
+            _ => None,
+        }
+    }
+}
+
+/// Failure of a single Storage API call.
+#[derive(Debug)]
+pub(super) enum ApiError {
+    /// No response: connection refused, DNS failure or timeout
+    Unreachable(String),
+    /// The server answered with a 5xx
+    Unavailable { status: u16, message: String },
+    /// Too many recent failures; the call wasn't attempted
+    CircuitOpen,
+    /// The server answered, with an error or something we couldn't read
+    Storage(StorageError),
+}
+
+impl ApiError {
+    /// Whether the backend couldn't handle the call at all, so trying again
+    /// later may succeed.
+    pub(super) fn is_offline(&self) -> bool {
+        !matches!(self, ApiError::Storage(_))
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Unreachable(message) => write!(f, "HTTP request failed: {message}"),
+            ApiError::Unavailable { status, message } => {
+                write!(f, "Storage API unavailable ({status}): {message}")
+            }
+            ApiError::CircuitOpen => write!(f, "circuit open"),
+            ApiError::Storage(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<ApiError> for StorageError {
+    fn from(err: ApiError) -> Self {
+        match err {
+            ApiError::Storage(err) => err,
+            other => StorageError::BackendError(other.to_string()),
+        }
+    }
+}
+
+/// Where the Storage API lives, how to authenticate to it, and how patient
+/// to be with it. Clones share one circuit breaker, so the stores for every
+/// namespace back off together.
+#[derive(Clone)]
+pub(super) struct StorageApi {
     client: Arc<Client>,
     base_url: String,
-    namespace: String,
     auth: ExememAuth,
+    timeout: Duration,
+    retry: RetryPolicy,
+    breaker: Arc<CircuitBreaker>,
+    metrics: Arc<StorageMetrics>,
+    signer: Option<RequestSigner>,
 }
 
-impl ExememApiStore {
-    pub fn new(client: Arc<Client>, base_url: String, namespace: String, auth: ExememAuth) -> Self {
+impl StorageApi {
+    pub(super) fn new(client: Arc<Client>, base_url: String, auth: ExememAuth) -> Self {
         Self {
             client,
             base_url,
-            namespace,
+            signer: auth.signer(),
             auth,
+            timeout: DEFAULT_TIMEOUT,
+            retry: DEFAULT_RETRY,
+            breaker: Arc::new(CircuitBreaker::default()),
+            metrics: Arc::new(StorageMetrics::default()),
         }
     }
 
+    pub(super) fn metrics(&self) -> &StorageMetrics {
+        &self.metrics
+    }
+
+    pub(super) fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub(super) fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    pub(super) fn set_circuit_breaker(&mut self, threshold: u32, cooldown: Duration) {
+        self.breaker = Arc::new(CircuitBreaker::new(threshold, cooldown));
+    }
+
     fn endpoint(&self, action: &str) -> String {
         format!("{}/api/storage/{}", self.base_url, action)
     }
 
-    fn apply_auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        match &self.auth {
-            ExememAuth::UserHash(hash) => req.header("X-User-Hash", hash),
-            ExememAuth::ApiKey(key) => req.header("X-API-Key", key),
-            ExememAuth::BearerToken(token) => {
-                req.header("Authorization", format!("Bearer {}", token))
+    /// POST `body` to a Storage API action and return the response if it
+    /// has `"ok": true`.
+    pub(super) async fn post(&self, action: &str, body: Value) -> StorageResult<Value> {
+        self.try_post(action, body).await.map_err(StorageError::from)
+    }
+
+    /// Like `post`, but tells an unreachable server apart from one that
+    /// answered with an error. Connection failures and 5xx responses are
+    /// retried with backoff, except for actions that aren't safe to repeat.
+    pub(super) async fn try_post(&self, action: &str, body: Value) -> Result<Value, ApiError> {
+        let namespace = body
+            .get("namespace")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string();
+        let call = self.call(action, &namespace, body);
+        #[cfg(feature = "tracing")]
+        let call = tracing::Instrument::instrument(
+            call,
+            tracing::info_span!("storage_api", action, namespace = %namespace),
+        );
+        call.await
+    }
+
+    /// One operation, with retries, counted once in the metrics.
+    async fn call(&self, action: &str, namespace: &str, body: Value) -> Result<Value, ApiError> {
+        if !self.breaker.allow() {
+            return Err(ApiError::CircuitOpen);
+        }
+
+        let policy = if NON_IDEMPOTENT_ACTIONS.contains(&action) {
+            RetryPolicy {
+                max_attempts: 1,
+                ..self.retry
+            }
+        } else {
+            self.retry
+        };
+        let classify = |err: &ApiError| {
+            if err.is_offline() {
+                Retry::Backoff
+            } else {
+                Retry::Never
             }
+        };
+        let started = Instant::now();
+        let sent = AtomicU64::new(0);
+        let received = AtomicU64::new(0);
+        let result = retry::with_backoff(&policy, classify, || self.send(action, &body, &sent, &received))
+            .await
+            .map_err(|err| match err {
+                RetryError::Permanent(err) | RetryError::Exhausted { error: err, .. } => err,
+            });
+
+        match &result {
+            Err(err) if err.is_offline() => self.breaker.record_failure(),
+            _ => self.breaker.record_success(),
         }
+        self.metrics.record(&CallSample {
+            namespace,
+            action,
+            latency: started.elapsed(),
+            ok: result.is_ok(),
+            bytes_sent: sent.load(Ordering::Relaxed),
+            bytes_received: received.load(Ordering::Relaxed),
+        });
+        result
     }
 
-    async fn post(&self, action: &str, body: Value) -> StorageResult<Value> {
-        let req = self.client.post(self.endpoint(action)).json(&body);
-        let req = self.apply_auth(req);
+    /// One HTTP attempt, adding the bytes it moved to `sent` and `received`.
+    async fn send(
+        &self,
+        action: &str,
+        body: &Value,
+        sent: &AtomicU64,
+        received: &AtomicU64,
+    ) -> Result<Value, ApiError> {
+        let payload = serde_json::to_vec(body).map_err(|e| {
+            ApiError::Storage(StorageError::BackendError(format!("Failed to encode request: {e}")))
+        })?;
+        sent.fetch_add(payload.len() as u64, Ordering::Relaxed);
+        let req = self.auth.apply(
+            self.client
+                .post(self.endpoint(action))
+                .timeout(self.timeout)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(payload),
+        );
 
-        let response = req
-            .send()
-            .await
-            .map_err(|e| StorageError::BackendError(format!("HTTP request failed: {e}")))?;
+        let response = req.send_signed(self.signer.as_ref()).await.map_err(|e| {
+            if e.is_connect() || e.is_timeout() {
+                ApiError::Unreachable(e.to_string())
+            } else {
+                ApiError::Storage(StorageError::BackendError(format!("HTTP request failed: {e}")))
+            }
+        })?;
 
         let status = response.status();
-        let text = response
-            .text()
-            .await
-            .map_err(|e| StorageError::BackendError(format!("Failed to read response body: {e}")))?;
-
-        let json: Value = serde_json::from_str(&text).map_err(|e| {
-            StorageError::BackendError(format!(
-                "Invalid JSON response (status {status}): {e}: {text}"
-            ))
+        let text = response.text().await.map_err(|e| {
+            ApiError::Storage(StorageError::BackendError(format!(
+                "Failed to read response body: {e}"
+            )))
         })?;
+        received.fetch_add(text.len() as u64, Ordering::Relaxed);
+
+        if status.is_server_error() {
+            return Err(ApiError::Unavailable {
+                status: status.as_u16(),
+                message: text,
+            });
+        }
+
+        let json: Value = match serde_json::from_str(&text) {
+            Ok(json) => json,
+            // Older deployments answer unknown actions with a bare 404
+            Err(_) if status == StatusCode::NOT_FOUND => {
+                return Err(ApiError::Storage(StorageError::InvalidOperation(format!(
+                    "Storage API does not support '{action}'"
+                ))))
+            }
+            Err(e) => {
+                return Err(ApiError::Storage(StorageError::BackendError(format!(
+                    "Invalid JSON response (status {status}): {e}: {text}"
+                ))))
+            }
+        };
 
         if json.get("ok").and_then(|v| v.as_bool()) != Some(true) {
             let error = json
                 .get("error")
                 .and_then(|v| v.as_str())
                 .unwrap_or("Unknown error");
-            return Err(StorageError::BackendError(format!(
+            return Err(ApiError::Storage(StorageError::BackendError(format!(
                 "Storage API error: {error}"
-            )));
+            ))));
         }
 
         Ok(json)
     }
+}
 
-    fn encode_key(key: &[u8]) -> String {
-        BASE64.encode(key)
+/// Part of a scan, and where the next part starts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanPage {
+    pub items: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Pass back to get the next page; None on the last one
+    pub next_cursor: Option<String>,
+}
+
+/// Result of a conditional write.
+///
+/// A lost race comes back as `Ok(CasOutcome::Conflict { .. })` rather than an
+/// error: `StorageError` is defined in fold_db and has no `Conflict` variant,
+/// so this is the distinct CAS-failure case callers match on. `Err` is kept
+/// for transport and server failures.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CasOutcome {
+    Applied,
+    /// Someone else changed the key first; nothing was written
+    Conflict {
+        /// What the key holds now (None if it doesn't exist)
+        current: Option<Vec<u8>>,
+    },
+}
+
+/// One write in a [`ExememApiStore::transact`] call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransactOp {
+    Put { key: Vec<u8>, value: Vec<u8> },
+    Delete { key: Vec<u8> },
+}
+
+impl TransactOp {
+    fn key(&self) -> &[u8] {
+        match self {
+            TransactOp::Put { key, .. } | TransactOp::Delete { key } => key,
+        }
     }
+}
 
-    fn encode_value(value: &[u8]) -> String {
-        BASE64.encode(value)
+/// KvStore implementation that routes operations through the Exemem Storage API.
+///
+/// Each instance is bound to a specific namespace. All keys and values are
+/// base64-encoded in transit. The Storage API Lambda handles DynamoDB routing,
+/// user isolation, and namespace-to-table mapping.
+///
+/// Values at or above the compression threshold, if one is set, are stored
+/// zstd-compressed; compressed values are recognized and expanded on read
+/// whether or not this instance compresses.
+///
+/// With an offline journal attached, puts and deletes (batched or not) that
+/// can't reach the server are journaled and replayed later; see
+/// [`OfflineSync`].
+pub struct ExememApiStore {
+    api: StorageApi,
+    namespace: String,
+    offline: Option<Arc<OfflineSync>>,
+    compress_above: Option<usize>,
+}
+
+impl ExememApiStore {
+    pub fn new(client: Arc<Client>, base_url: String, namespace: String, auth: ExememAuth) -> Self {
+        Self::with_api(StorageApi::new(client, base_url, auth), namespace)
     }
 
-    fn decode_value(b64: &str) -> StorageResult<Vec<u8>> {
-        BASE64
-            .decode(b64)
-            .map_err(|e| StorageError::BackendError(format!("Invalid base64 in response: {e}")))
+    pub(super) fn with_api(api: StorageApi, namespace: String) -> Self {
+        Self {
+            api,
+            namespace,
+            offline: None,
+            compress_above: None,
+        }
     }
-}
 
-#[async_trait]
-impl KvStore for ExememApiStore {
-    async fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
-        let body = json!({
-            "namespace": self.namespace,
-            "key": Self::encode_key(key),
-        });
+    /// Calls, errors, latency and bytes per operation since the store was
+    /// created. Stores opened from the same `ExememNamespacedStore` share
+    /// these counters.
+    pub fn metrics_snapshot(&self) -> Vec<OperationStats> {
+        self.api.metrics().snapshot()
+    }
 
-        let resp = self.post("get", body).await?;
+    /// Give up on each request after `timeout` instead of 30 seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.api.set_timeout(timeout);
+        self
+    }
+
+    /// Retry connection failures and 5xx responses per `retry` instead of
+    /// three tries.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry);
+        self
+    }
+
+    /// Fail fast for `cooldown` after `threshold` calls in a row couldn't
+    /// reach the server.
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.api.set_circuit_breaker(threshold, cooldown);
+        self
+    }
+
+    /// Compress values of at least `threshold` bytes before sending them.
+    pub fn with_compression(mut self, threshold: usize) -> Self {
+        self.compress_above = Some(threshold);
+        self
+    }
+
+    pub(super) fn with_offline(mut self, offline: Arc<OfflineSync>) -> Self {
+        self.offline = Some(offline);
+        self
+    }
 
-        match resp.get("value") {
-            Some(Value::String(b64)) => Ok(Some(Self::decode_value(b64)?)),
-            Some(Value::Null) | None => Ok(None),
-            _ => Err(StorageError::BackendError(
-                "Unexpected 'value' type in get response".to_string(),
-            )),
+    async fn post(&self, action: &str, body: Value) -> StorageResult<Value> {
+        self.api.post(action, body).await
+    }
+
+    /// Apply a put or delete now, or journal it if offline mode is on.
+    async fn write(&self, key: &[u8], action: JournalAction) -> StorageResult<()> {
+        let key = Self::encode_key(key);
+        match &self.offline {
+            Some(offline) => offline.write(&self.api, &self.namespace, key, action).await,
+            None => {
+                let (name, body) = action.request(&self.namespace, &key);
+                self.post(name, body).await.map(|_| ())
+            }
         }
     }
 
-    async fn put(&self, key: &[u8], value: Vec<u8>) -> StorageResult<()> {
+    /// Apply up to `BATCH_SIZE` puts or deletes in one `action` request, or
+    /// journal them like `write` does.
+    async fn write_batch(&self, action: &str, writes: Vec<(String, JournalAction)>) -> StorageResult<()> {
+        let items: Vec<Value> = writes
+            .iter()
+            .map(|(key, write)| match write {
+                JournalAction::Put { value } => json!({ "key": key, "value": value }),
+                JournalAction::Delete => json!({ "key": key }),
+            })
+            .collect();
         let body = json!({
             "namespace": self.namespace,
-            "key": Self::encode_key(key),
-            "value": Self::encode_value(&value),
+            "items": items,
         });
+        match &self.offline {
+            Some(offline) => offline.write_batch(&self.api, &self.namespace, action, body, writes).await,
+            None => self.post(action, body).await.map(|_| ()),
+        }
+    }
 
-        self.post("put", body).await?;
-        Ok(())
+    /// Fetch many keys with one round trip per 25, returning the values in
+    /// the same order as `keys` (None where a key doesn't exist). Keys with
+    /// journaled writes are answered from the journal, as `get` does.
+    pub async fn batch_get(&self, keys: &[Vec<u8>]) -> StorageResult<Vec<Option<Vec<u8>>>> {
+        let mut values = vec![None; keys.len()];
+        let mut to_fetch = Vec::with_capacity(keys.len());
+        for (i, key) in keys.iter().enumerate() {
+            let key_b64 = Self::encode_key(key);
+            let pending = match &self.offline {
+                Some(offline) => offline.pending_value(&self.namespace, &key_b64).await,
+                None => None,
+            };
+            match pending {
+                Some(pending) => values[i] = pending.as_deref().map(Self::decode_stored).transpose()?,
+                None => to_fetch.push((i, key_b64)),
+            }
+        }
+
+        for chunk in to_fetch.chunks(BATCH_SIZE) {
+            let encoded_keys: Vec<String> = chunk.iter().map(|(_, k)| k.clone()).collect();
+            let body = json!({
+                "namespace": self.namespace,
+                "keys": encoded_keys,
+            });
+
+            let resp = self.post("batch-get", body).await?;
+            let found = Self::parse_batch_get(&resp, &encoded_keys)?;
+            for ((i, key_b64), value) in chunk.iter().zip(found) {
+                if let Some(offline) = &self.offline {
+                    offline.remember(&self.namespace, key_b64, value);
+                }
+                values[*i] = value.map(Self::decode_stored).transpose()?;
+            }
+        }
+
+        Ok(values)
     }
 
-    async fn delete(&self, key: &[u8]) -> StorageResult<bool> {
-        let body = json!({
-            "namespace": self.namespace,
-            "key": Self::encode_key(key),
-        });
+    /// Items come back keyed, in no particular order; missing keys are
+    /// either left out or have a null value. Values are returned as stored.
+    fn parse_batch_get<'a>(resp: &'a Value, encoded_keys: &[String]) -> StorageResult<Vec<Option<&'a str>>> {
+        let items = resp
+            .get("items")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| {
+                StorageError::BackendError(
+                    "Missing 'items' array in batch-get response".to_string(),
+                )
+            })?;
 
-        self.post("delete", body).await?;
-        // The Storage API does not indicate whether the key existed,
-        // so we return true on success.
-        Ok(true)
+        let mut found = HashMap::with_capacity(items.len());
+        for item in items {
+            let key_b64 = item
+                .get("key")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| {
+                    StorageError::BackendError("Missing 'key' in batch-get item".to_string())
+                })?;
+            if let Some(value_b64) = item.get("value").and_then(|v| v.as_str()) {
+                found.insert(key_b64, value_b64);
+            }
+        }
+
+        Ok(encoded_keys.iter().map(|k| found.get(k.as_str()).copied()).collect())
     }
 
-    async fn exists(&self, key: &[u8]) -> StorageResult<bool> {
+    /// Write `value` only if `key` doesn't exist yet.
+    pub async fn put_if_absent(&self, key: &[u8], value: Vec<u8>) -> StorageResult<CasOutcome> {
+        self.compare_and_swap(key, None, value).await
+    }
+
+    /// Write `new` only if the key currently holds `expected` (None: the key
+    /// must not exist). The comparison is on stored bytes, so `expected` must
+    /// have been written with the same compression setting.
+    ///
+    /// Conditional writes need the server, so they are never journaled.
+    /// A mismatch is `Ok(CasOutcome::Conflict)`, not an error (see
+    /// [`CasOutcome`]).
+    pub async fn compare_and_swap(
+        &self,
+        key: &[u8],
+        expected: Option<&[u8]>,
+        new: Vec<u8>,
+    ) -> StorageResult<CasOutcome> {
+        let key_b64 = Self::encode_key(key);
+        if let Some(offline) = &self.offline {
+            if offline.pending_value(&self.namespace, &key_b64).await.is_some() {
+                return Err(StorageError::InvalidOperation(
+                    "Key has offline writes waiting to sync; retry after sync".to_string(),
+                ));
+            }
+        }
+
+        let expected = expected.map(|v| self.encode_stored(v)).transpose()?;
+        let value = self.encode_stored(&new)?;
         let body = json!({
             "namespace": self.namespace,
-            "key": Self::encode_key(key),
+            "key": key_b64,
+            "value": value,
+            "expected": expected,
         });
 
-        let resp = self.post("exists", body).await?;
+        let resp = self.post("conditional-put", body).await?;
+        let outcome = Self::parse_conditional_put(&resp)?;
+        if let (Some(offline), CasOutcome::Applied) = (&self.offline, &outcome) {
+            offline.remember(&self.namespace, &key_b64, Some(&value));
+        }
+        Ok(outcome)
+    }
 
-        resp.get("exists")
+    fn parse_conditional_put(resp: &Value) -> StorageResult<CasOutcome> {
+        let applied = resp
+            .get("applied")
             .and_then(|v| v.as_bool())
             .ok_or_else(|| {
                 StorageError::BackendError(
-                    "Missing 'exists' field in exists response".to_string(),
+                    "Missing 'applied' in conditional-put response".to_string(),
                 )
-            })
+            })?;
+        if applied {
+            return Ok(CasOutcome::Applied);
+        }
+        let current = resp
+            .get("current")
+            .and_then(|v| v.as_str())
+            .map(Self::decode_stored)
+            .transpose()?;
+        Ok(CasOutcome::Conflict { current })
     }
 
-    async fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    /// Apply all of `ops` or none of them. At most [`MAX_TRANSACT_OPS`]
+    /// operations, each on a different key, and about 4 MB in total.
+    ///
+    /// Transactions need the server, so they are never journaled.
+    pub async fn transact(&self, ops: Vec<TransactOp>) -> StorageResult<()> {
+        if ops.is_empty() {
+            return Ok(());
+        }
+        if ops.len() > MAX_TRANSACT_OPS {
+            return Err(StorageError::InvalidOperation(format!(
+                "Transaction has {} operations; the Storage API allows at most {MAX_TRANSACT_OPS}",
+                ops.len()
+            )));
+        }
+
+        let mut keys = HashSet::with_capacity(ops.len());
+        let mut size = 0;
+        let mut encoded_ops = Vec::with_capacity(ops.len());
+        for op in &ops {
+            let key_b64 = Self::encode_key(op.key());
+            if !keys.insert(key_b64.clone()) {
+                return Err(StorageError::InvalidOperation(format!(
+                    "Transaction touches key {key_b64} more than once"
+                )));
+            }
+            if let Some(offline) = &self.offline {
+                if offline.pending_value(&self.namespace, &key_b64).await.is_some() {
+                    return Err(StorageError::InvalidOperation(
+                        "Key has offline writes waiting to sync; retry after sync".to_string(),
+                    ));
+                }
+            }
+            let encoded = match op {
+                TransactOp::Put { value, .. } => {
+                    json!({ "op": "put", "key": key_b64, "value": self.encode_stored(value)? })
+                }
+                TransactOp::Delete { .. } => json!({ "op": "delete", "key": key_b64 }),
+            };
+            size += encoded.to_string().len();
+            encoded_ops.push(encoded);
+        }
+        if size > MAX_TRANSACT_BYTES {
+            return Err(StorageError::InvalidOperation(format!(
+                "Transaction is {size} bytes encoded; the Storage API allows at most {MAX_TRANSACT_BYTES}"
+            )));
+        }
+
         let body = json!({
+            "namespace": self.namespace,
+            "ops": encoded_ops,
+        });
+        self.post("transact", body).await?;
+
+        if let Some(offline) = &self.offline {
+            for op in &encoded_ops {
+                let key_b64 = op["key"].as_str().unwrap_or_default();
+                offline.remember(&self.namespace, key_b64, op.get("value").and_then(|v| v.as_str()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` items with keys from `start` (inclusive) to `end`
+    /// (exclusive), in key order. Only the requested range is fetched, so
+    /// the latest entries of a timestamped layout don't cost a full scan.
+    pub async fn scan_range(
+        &self,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut results = Vec::new();
+        if start >= end {
+            return Ok(results);
+        }
+
+        let mut cursor: Option<String> = None;
+        while results.len() < limit {
+            let mut body = json!({
+                "namespace": self.namespace,
+                "start": Self::encode_key(start),
+                "end": Self::encode_key(end),
+                "limit": (limit - results.len()).min(SCAN_PAGE_SIZE),
+            });
+            if let Some(cursor) = &cursor {
+                body["cursor"] = json!(cursor);
+            }
+
+            let resp = self.post("scan-range", body).await?;
+            let page = Self::parse_scan_page(&resp, "scan-range")?;
+            results.extend(page.items);
+            match page.next_cursor {
+                Some(next) if cursor.as_deref() == Some(next.as_str()) => {
+                    return Err(StorageError::BackendError(
+                        "scan-range returned the same cursor twice".to_string(),
+                    ))
+                }
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        results.truncate(limit);
+        Ok(results)
+    }
+
+    /// One page of a prefix scan, starting after `cursor`.
+    pub async fn scan_prefix_page(
+        &self,
+        prefix: &[u8],
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> StorageResult<ScanPage> {
+        let mut body = json!({
             "namespace": self.namespace,
             "prefix": Self::encode_key(prefix),
+            "limit": limit,
         });
+        if let Some(cursor) = cursor {
+            body["cursor"] = json!(cursor);
+        }
 
         let resp = self.post("scan-prefix", body).await?;
+        Self::parse_scan_page(&resp, "scan-prefix")
+    }
+
+    /// A prefix scan delivered a page at a time, so large prefixes can be
+    /// processed without holding every item in memory. The stream ends after
+    /// the last page or the first error.
+    pub fn scan_prefix_pages<'a>(
+        &'a self,
+        prefix: &'a [u8],
+        page_size: usize,
+    ) -> impl Stream<Item = StorageResult<Vec<(Vec<u8>, Vec<u8>)>>> + 'a {
+        // State is the cursor for the next page; None once there are no more
+        stream::unfold(Some(None::<String>), move |state| async move {
+            let cursor = state?;
+            match self.scan_prefix_page(prefix, cursor.as_deref(), page_size).await {
+                Ok(page) => {
+                    let next = page.next_cursor.filter(|next| cursor.as_ref() != Some(next));
+                    Some((Ok(page.items), next.map(Some)))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        })
+    }
 
+    fn parse_scan_page(resp: &Value, action: &str) -> StorageResult<ScanPage> {
         let items = resp
             .get("items")
             .and_then(|v| v.as_array())
             .ok_or_else(|| {
-                StorageError::BackendError(
-                    "Missing 'items' array in scan-prefix response".to_string(),
-                )
+                StorageError::BackendError(format!("Missing 'items' array in {action} response"))
             })?;
 
         let mut results = Vec::with_capacity(items.len());
@@ -185,69 +765,163 @@ impl KvStore for ExememApiStore {
                 .get("key")
                 .and_then(|v| v.as_str())
                 .ok_or_else(|| {
-                    StorageError::BackendError(
-                        "Missing 'key' in scan-prefix item".to_string(),
-                    )
+                    StorageError::BackendError(format!("Missing 'key' in {action} item"))
                 })?;
             let value_b64 = item
                 .get("value")
                 .and_then(|v| v.as_str())
                 .ok_or_else(|| {
-                    StorageError::BackendError(
-                        "Missing 'value' in scan-prefix item".to_string(),
-                    )
+                    StorageError::BackendError(format!("Missing 'value' in {action} item"))
                 })?;
 
-            results.push((Self::decode_value(key_b64)?, Self::decode_value(value_b64)?));
+            results.push((Self::decode_value(key_b64)?, Self::decode_stored(value_b64)?));
         }
 
-        Ok(results)
+        let next_cursor = resp
+            .get("next_cursor")
+            .and_then(|v| v.as_str())
+            .filter(|c| !c.is_empty())
+            .map(str::to_string);
+        Ok(ScanPage {
+            items: results,
+            next_cursor,
+        })
     }
 
-    async fn batch_put(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> StorageResult<()> {
-        const BATCH_SIZE: usize = 25;
+    fn encode_key(key: &[u8]) -> String {
+        BASE64.encode(key)
+    }
+
+    fn encode_value(value: &[u8]) -> String {
+        BASE64.encode(value)
+    }
 
+    /// A value as it goes over the wire: compressed if large, then base64.
+    fn encode_stored(&self, value: &[u8]) -> StorageResult<String> {
+        Ok(Self::encode_value(&value_codec::pack(value, self.compress_above)?))
+    }
+
+    /// Inverse of `encode_stored`.
+    fn decode_stored(b64: &str) -> StorageResult<Vec<u8>> {
+        value_codec::unpack(Self::decode_value(b64)?)
+    }
+
+    fn decode_value(b64: &str) -> StorageResult<Vec<u8>> {
+        BASE64
+            .decode(b64)
+            .map_err(|e| StorageError::BackendError(format!("Invalid base64 in response: {e}")))
+    }
+}
+
+#[async_trait]
+impl KvStore for ExememApiStore {
+    async fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        let key_b64 = Self::encode_key(key);
+        // Journaled writes aren't on the server yet but should be visible
+        if let Some(offline) = &self.offline {
+            if let Some(pending) = offline.pending_value(&self.namespace, &key_b64).await {
+                return pending.as_deref().map(Self::decode_stored).transpose();
+            }
+        }
+
+        let body = json!({
+            "namespace": self.namespace,
+            "key": key_b64,
+        });
+
+        let resp = self.post("get", body).await?;
+
+        let value = match resp.get("value") {
+            Some(Value::String(b64)) => Some(b64.as_str()),
+            Some(Value::Null) | None => None,
+            _ => {
+                return Err(StorageError::BackendError(
+                    "Unexpected 'value' type in get response".to_string(),
+                ))
+            }
+        };
+        if let Some(offline) = &self.offline {
+            offline.remember(&self.namespace, &key_b64, value);
+        }
+        value.map(Self::decode_stored).transpose()
+    }
+
+    async fn put(&self, key: &[u8], value: Vec<u8>) -> StorageResult<()> {
+        let value = self.encode_stored(&value)?;
+        self.write(key, JournalAction::Put { value }).await
+    }
+
+    async fn delete(&self, key: &[u8]) -> StorageResult<bool> {
+        self.write(key, JournalAction::Delete).await?;
+        // The Storage API does not indicate whether the key existed,
+        // so we return true on success.
+        Ok(true)
+    }
+
+    async fn exists(&self, key: &[u8]) -> StorageResult<bool> {
+        if let Some(offline) = &self.offline {
+            if let Some(pending) = offline.pending_value(&self.namespace, &Self::encode_key(key)).await {
+                return Ok(pending.is_some());
+            }
+        }
+
+        let body = json!({
+            "namespace": self.namespace,
+            "key": Self::encode_key(key),
+        });
+
+        let resp = self.post("exists", body).await?;
+
+        resp.get("exists")
+            .and_then(|v| v.as_bool())
+            .ok_or_else(|| {
+                StorageError::BackendError(
+                    "Missing 'exists' field in exists response".to_string(),
+                )
+            })
+    }
+
+    async fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut results = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let page = self.scan_prefix_page(prefix, cursor.as_deref(), SCAN_PAGE_SIZE).await?;
+            results.extend(page.items);
+            match page.next_cursor {
+                // A server that hands back the same cursor would loop forever
+                Some(next) if cursor.as_deref() == Some(next.as_str()) => {
+                    return Err(StorageError::BackendError(
+                        "scan-prefix returned the same cursor twice".to_string(),
+                    ))
+                }
+                Some(next) => cursor = Some(next),
+                None => return Ok(results),
+            }
+        }
+    }
+
+    async fn batch_put(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> StorageResult<()> {
         for chunk in items.chunks(BATCH_SIZE) {
-            let encoded_items: Vec<Value> = chunk
+            let writes = chunk
                 .iter()
                 .map(|(k, v)| {
-                    json!({
-                        "key": Self::encode_key(k),
-                        "value": Self::encode_value(v),
-                    })
+                    let value = self.encode_stored(v)?;
+                    Ok((Self::encode_key(k), JournalAction::Put { value }))
                 })
-                .collect();
-
-            let body = json!({
-                "namespace": self.namespace,
-                "items": encoded_items,
-            });
-
-            self.post("batch-put", body).await?;
+                .collect::<StorageResult<Vec<_>>>()?;
+            self.write_batch("batch-put", writes).await?;
         }
 
         Ok(())
     }
 
     async fn batch_delete(&self, keys: Vec<Vec<u8>>) -> StorageResult<()> {
-        const BATCH_SIZE: usize = 25;
-
         for chunk in keys.chunks(BATCH_SIZE) {
-            let encoded_items: Vec<Value> = chunk
+            let writes = chunk
                 .iter()
-                .map(|k| {
-                    json!({
-                        "key": Self::encode_key(k),
-                    })
-                })
+                .map(|k| (Self::encode_key(k), JournalAction::Delete))
                 .collect();
-
-            let body = json!({
-                "namespace": self.namespace,
-                "items": encoded_items,
-            });
-
-            self.post("batch-delete", body).await?;
+            self.write_batch("batch-delete", writes).await?;
         }
 
         Ok(())
@@ -290,12 +964,169 @@ mod tests {
         assert_eq!(decoded, value);
     }
 
+    #[test]
+    fn test_auth_from_config() {
+        let mut config = AppConfig::default();
+        assert!(ExememAuth::from_config(&config).is_none());
+
+        config.user_hash = Some("hash".to_string());
+        assert!(matches!(ExememAuth::from_config(&config), Some(ExememAuth::UserHash(h)) if h == "hash"));
+
+        config.api_key = "key".to_string();
+        config.request_signing.clear();
+        assert!(matches!(ExememAuth::from_config(&config), Some(ExememAuth::ApiKey(k)) if k == "key"));
+
+        config.request_signing.push(config.environment.clone());
+        assert!(matches!(ExememAuth::from_config(&config), Some(ExememAuth::SignedApiKey(k)) if k == "key"));
+    }
+
     #[test]
     fn test_decode_value_invalid_base64() {
         let result = ExememApiStore::decode_value("not!valid!base64!!");
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_batch_get_keeps_request_order() {
+        let keys = vec![
+            ExememApiStore::encode_key(b"a"),
+            ExememApiStore::encode_key(b"b"),
+            ExememApiStore::encode_key(b"c"),
+        ];
+        let resp = json!({
+            "ok": true,
+            "items": [
+                {"key": keys[2], "value": BASE64.encode(b"3")},
+                {"key": keys[0], "value": BASE64.encode(b"1")},
+                {"key": keys[1], "value": null},
+            ],
+        });
+        let values = ExememApiStore::parse_batch_get(&resp, &keys).unwrap();
+        let (one, three) = (BASE64.encode(b"1"), BASE64.encode(b"3"));
+        assert_eq!(values, vec![Some(one.as_str()), None, Some(three.as_str())]);
+
+        assert!(ExememApiStore::parse_batch_get(&json!({"ok": true}), &keys).is_err());
+    }
+
+    #[test]
+    fn test_parse_conditional_put() {
+        let applied = json!({"ok": true, "applied": true});
+        assert_eq!(ExememApiStore::parse_conditional_put(&applied).unwrap(), CasOutcome::Applied);
+
+        let lost = json!({"ok": true, "applied": false, "current": BASE64.encode(b"theirs")});
+        assert_eq!(
+            ExememApiStore::parse_conditional_put(&lost).unwrap(),
+            CasOutcome::Conflict { current: Some(b"theirs".to_vec()) }
+        );
+
+        let gone = json!({"ok": true, "applied": false, "current": null});
+        assert_eq!(
+            ExememApiStore::parse_conditional_put(&gone).unwrap(),
+            CasOutcome::Conflict { current: None }
+        );
+        assert!(ExememApiStore::parse_conditional_put(&json!({"ok": true})).is_err());
+    }
+
+    #[test]
+    fn test_parse_scan_page() {
+        let resp = json!({
+            "ok": true,
+            "items": [{"key": BASE64.encode(b"k1"), "value": BASE64.encode(b"v1")}],
+            "next_cursor": "c2",
+        });
+        let page = ExememApiStore::parse_scan_page(&resp, "scan-prefix").unwrap();
+        assert_eq!(page.items, vec![(b"k1".to_vec(), b"v1".to_vec())]);
+        assert_eq!(page.next_cursor.as_deref(), Some("c2"));
+
+        let last = json!({"ok": true, "items": []});
+        assert_eq!(ExememApiStore::parse_scan_page(&last, "scan-prefix").unwrap().next_cursor, None);
+    }
+
+    #[tokio::test]
+    async fn test_transact_rejects_oversized_and_duplicate_ops() {
+        // Validation fails before any request, so the URL is never used
+        let store = ExememApiStore::new(
+            Arc::new(Client::new()),
+            "http://127.0.0.1:9".to_string(),
+            "main".to_string(),
+            ExememAuth::ApiKey("test_key".to_string()),
+        );
+
+        let too_many = (0..=MAX_TRANSACT_OPS)
+            .map(|i| TransactOp::Delete { key: i.to_string().into_bytes() })
+            .collect();
+        let err = store.transact(too_many).await.unwrap_err();
+        assert!(matches!(err, StorageError::InvalidOperation(ref m) if m.contains("at most 100")));
+
+        let dup = vec![
+            TransactOp::Put { key: b"k".to_vec(), value: b"v".to_vec() },
+            TransactOp::Delete { key: b"k".to_vec() },
+        ];
+        assert!(matches!(store.transact(dup).await, Err(StorageError::InvalidOperation(_))));
+
+        let huge = vec![TransactOp::Put { key: b"k".to_vec(), value: vec![7; MAX_TRANSACT_BYTES] }];
+        assert!(matches!(store.transact(huge).await, Err(StorageError::InvalidOperation(_))));
+
+        assert!(store.transact(Vec::new()).await.is_ok());
+    }
+
+    /// Store whose server can't be reached, with an offline journal.
+    fn offline_store(name: &str) -> (ExememApiStore, Arc<OfflineSync>, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("exemem-journal-{}-{}.json", name, uuid::Uuid::new_v4()));
+        let offline = Arc::new(OfflineSync::open(path.clone(), Default::default()).unwrap());
+        let store = ExememApiStore::new(
+            Arc::new(Client::new()),
+            "http://127.0.0.1:9".to_string(),
+            "main".to_string(),
+            ExememAuth::ApiKey("test_key".to_string()),
+        )
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_busy_waits: 0,
+        })
+        .with_offline(offline.clone());
+        (store, offline, path)
+    }
+
+    #[tokio::test]
+    async fn test_batch_writes_are_journaled_behind_pending_writes() {
+        let (store, offline, path) = offline_store("batch");
+        store.put(b"k", b"v1".to_vec()).await.unwrap();
+
+        // Journaled after the put, so replay can't let the older value win
+        store
+            .batch_put(vec![(b"k".to_vec(), b"v2".to_vec()), (b"j".to_vec(), b"v3".to_vec())])
+            .await
+            .unwrap();
+        assert_eq!(store.get(b"k").await.unwrap(), Some(b"v2".to_vec()));
+        assert_eq!(store.get(b"j").await.unwrap(), Some(b"v3".to_vec()));
+
+        store.batch_delete(vec![b"j".to_vec()]).await.unwrap();
+        assert_eq!(store.get(b"j").await.unwrap(), None);
+
+        // Answered from the journal, without asking the server
+        let keys = vec![b"k".to_vec(), b"j".to_vec()];
+        assert_eq!(store.batch_get(&keys).await.unwrap(), vec![Some(b"v2".to_vec()), None]);
+
+        let ops = offline.status().await.pending;
+        assert_eq!(ops, 4);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_scan_range_empty_bounds() {
+        // An empty range is answered without a request
+        let store = ExememApiStore::new(
+            Arc::new(Client::new()),
+            "http://127.0.0.1:9".to_string(),
+            "main".to_string(),
+            ExememAuth::ApiKey("test_key".to_string()),
+        );
+        assert!(store.scan_range(b"b", b"a", 10).await.unwrap().is_empty());
+        assert!(store.scan_range(b"a", b"a", 10).await.unwrap().is_empty());
+    }
+
     #[test]
     fn test_endpoint_construction() {
         let client = Arc::new(Client::new());
@@ -306,11 +1137,11 @@ mod tests {
             ExememAuth::UserHash("test_user".to_string()),
         );
         assert_eq!(
-            store.endpoint("get"),
+            store.api.endpoint("get"),
             "https://api.example.com/api/storage/get"
         );
         assert_eq!(
-            store.endpoint("scan-prefix"),
+            store.api.endpoint("scan-prefix"),
             "https://api.example.com/api/storage/scan-prefix"
         );
     }
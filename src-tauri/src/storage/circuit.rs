@@ -0,0 +1,125 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Consecutive failed calls that open the circuit by default
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit fails fast before letting a trial call through
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    /// Cooldown is over and one trial call is in flight
+    HalfOpen,
+}
+
+/// Stops calling a backend that keeps failing, so a down Storage API costs
+/// each caller an immediate error instead of a full round of retries.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            threshold: threshold.max(1),
+            cooldown,
+            state: Mutex::new(State::Closed { failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go ahead now.
+    pub fn allow(&self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } if now >= until => {
+                *state = State::HalfOpen;
+                true
+            }
+            State::Open { .. } | State::HalfOpen => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().unwrap_or_else(|e| e.into_inner()) = State::Closed { failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        *state = match *state {
+            State::Closed { failures } if failures + 1 < self.threshold => State::Closed {
+                failures: failures + 1,
+            },
+            _ => {
+                log::warn!("Storage API circuit open for {:?}", self.cooldown);
+                State::Open {
+                    until: now + self.cooldown,
+                }
+            }
+        };
+    }
+
+    pub fn is_open(&self) -> bool {
+        !matches!(
+            *self.state.lock().unwrap_or_else(|e| e.into_inner()),
+            State::Closed { .. }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opens_after_threshold_and_recovers() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+
+        breaker.record_failure_at(start);
+        assert!(breaker.allow_at(start));
+        breaker.record_failure_at(start);
+        assert!(!breaker.allow_at(start + Duration::from_secs(5)));
+
+        // After cooldown a single trial goes through
+        let later = start + Duration::from_secs(11);
+        assert!(breaker.allow_at(later));
+        assert!(!breaker.allow_at(later));
+
+        breaker.record_success();
+        assert!(breaker.allow_at(later));
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_failed_trial_reopens() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+        let start = Instant::now();
+        breaker.record_failure_at(start);
+
+        let later = start + Duration::from_secs(10);
+        assert!(breaker.allow_at(later));
+        breaker.record_failure_at(later);
+        assert!(!breaker.allow_at(later + Duration::from_secs(9)));
+    }
+}
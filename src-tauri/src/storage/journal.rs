@@ -0,0 +1,400 @@
+use fold_db::storage::error::{StorageError, StorageResult};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::api_store::StorageApi;
+use crate::persist;
+
+/// Last-seen server values kept for conflict checks; past this the map is
+/// cleared, which only means later offline writes can't be checked
+const MAX_KNOWN_KEYS: usize = 10_000;
+
+/// What to do when the server's copy of a key changed while a journaled
+/// write was waiting to be replayed.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Replay anyway, overwriting the server's copy
+    #[default]
+    LastWriterWins,
+    /// Stop replaying and report the conflict until it is resolved
+    FailAndSurface,
+}
+
+/// How to settle the conflict that stopped replay.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    /// Apply the journaled write over the server's copy
+    KeepLocal,
+    /// Drop the journaled write
+    KeepServer,
+}
+
+/// The server's copy of a key as this client last saw it.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum KnownValue {
+    /// Never read or written here, so nothing to compare against
+    Unknown,
+    Absent,
+    /// SHA-256 of the base64 value
+    Present { sha256: String },
+}
+
+impl KnownValue {
+    fn of(value: Option<&str>) -> Self {
+        match value {
+            Some(value) => KnownValue::Present {
+                sha256: format!("{:x}", Sha256::digest(value.as_bytes())),
+            },
+            None => KnownValue::Absent,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalAction {
+    /// `value` is base64, as sent to the Storage API
+    Put { value: String },
+    Delete,
+}
+
+impl JournalAction {
+    /// The Storage API action and request body that apply this write.
+    pub(super) fn request(&self, namespace: &str, key: &str) -> (&'static str, Value) {
+        match self {
+            JournalAction::Put { value } => (
+                "put",
+                json!({ "namespace": namespace, "key": key, "value": value }),
+            ),
+            JournalAction::Delete => ("delete", json!({ "namespace": namespace, "key": key })),
+        }
+    }
+
+    /// The key's value once this write is applied.
+    fn result(&self) -> Option<&str> {
+        match self {
+            JournalAction::Put { value } => Some(value),
+            JournalAction::Delete => None,
+        }
+    }
+}
+
+/// A put or delete made while the Storage API was unreachable.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JournalOp {
+    pub id: u64,
+    pub namespace: String,
+    /// base64, as sent to the Storage API
+    pub key: String,
+    pub action: JournalAction,
+    /// The server's copy when the write was made, for conflict checks
+    pub base: KnownValue,
+    /// Seconds since the Unix epoch
+    pub recorded_at: u64,
+}
+
+/// A journaled write that replay stopped at.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncConflict {
+    pub op: JournalOp,
+    pub reason: String,
+}
+
+/// Progress of getting offline writes onto the server.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SyncStatus {
+    /// Journaled writes not yet on the server
+    pub pending: usize,
+    /// Set when replay is stopped until `resolve_conflict` is called
+    pub conflict: Option<SyncConflict>,
+    pub policy: ConflictPolicy,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JournalState {
+    ops: Vec<JournalOp>,
+    next_id: u64,
+    conflict: Option<SyncConflict>,
+}
+
+/// Persistent, ordered log of writes waiting for the Storage API.
+struct SyncJournal {
+    path: PathBuf,
+    state: JournalState,
+}
+
+impl SyncJournal {
+    fn load(path: PathBuf) -> StorageResult<Self> {
+        let state = persist::load_json(&path, "sync journal").map_err(StorageError::BackendError)?;
+        Ok(Self { path, state })
+    }
+
+    fn save(&self) -> StorageResult<()> {
+        persist::save_json(&self.path, &self.state, "sync journal").map_err(StorageError::BackendError)
+    }
+
+    fn append(&mut self, namespace: &str, key: String, action: JournalAction, base: KnownValue) -> StorageResult<()> {
+        self.state.next_id += 1;
+        self.state.ops.push(JournalOp {
+            id: self.state.next_id,
+            namespace: namespace.to_string(),
+            key,
+            action,
+            base,
+            recorded_at: crate::ledger::now_secs(),
+        });
+        self.save()
+    }
+
+    fn pop_front(&mut self) -> StorageResult<()> {
+        if !self.state.ops.is_empty() {
+            self.state.ops.remove(0);
+        }
+        self.save()
+    }
+
+    /// The most recent journaled write to a key.
+    fn latest(&self, namespace: &str, key: &str) -> Option<&JournalOp> {
+        self.state
+            .ops
+            .iter()
+            .rev()
+            .find(|op| op.namespace == namespace && op.key == key)
+    }
+}
+
+/// Offline mode for the Storage API stores: writes that can't reach the
+/// server go to a journal on disk and are replayed in order once it is back.
+///
+/// Replay is attempted before each new write and by
+/// `ExememNamespacedStore::sync`. Reads of journaled keys are answered from
+/// the journal so callers see their own writes.
+pub struct OfflineSync {
+    journal: tokio::sync::Mutex<SyncJournal>,
+    policy: ConflictPolicy,
+    known: Mutex<HashMap<(String, String), KnownValue>>,
+}
+
+impl OfflineSync {
+    pub fn open(path: PathBuf, policy: ConflictPolicy) -> StorageResult<Self> {
+        Ok(Self {
+            journal: tokio::sync::Mutex::new(SyncJournal::load(path)?),
+            policy,
+            known: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub async fn status(&self) -> SyncStatus {
+        let journal = self.journal.lock().await;
+        SyncStatus {
+            pending: journal.state.ops.len(),
+            conflict: journal.state.conflict.clone(),
+            policy: self.policy,
+        }
+    }
+
+    /// Note what the server holds for a key, for later conflict checks.
+    pub(super) fn remember(&self, namespace: &str, key: &str, value: Option<&str>) {
+        let mut known = self.known.lock().unwrap_or_else(|e| e.into_inner());
+        if known.len() >= MAX_KNOWN_KEYS {
+            known.clear();
+        }
+        known.insert((namespace.to_string(), key.to_string()), KnownValue::of(value));
+    }
+
+    fn known(&self, namespace: &str, key: &str) -> KnownValue {
+        self.known
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&(namespace.to_string(), key.to_string()))
+            .cloned()
+            .unwrap_or(KnownValue::Unknown)
+    }
+
+    /// The value a journaled write will leave for `key` (None for a delete),
+    /// or None if nothing is journaled for it.
+    pub(super) async fn pending_value(&self, namespace: &str, key: &str) -> Option<Option<String>> {
+        let journal = self.journal.lock().await;
+        journal
+            .latest(namespace, key)
+            .map(|op| op.action.result().map(str::to_string))
+    }
+
+    /// Send a write to the server, or journal it if the server can't be
+    /// reached or earlier writes are still waiting.
+    pub(super) async fn write(
+        &self,
+        api: &StorageApi,
+        namespace: &str,
+        key: String,
+        action: JournalAction,
+    ) -> StorageResult<()> {
+        let (name, body) = action.request(namespace, &key);
+        self.write_batch(api, namespace, name, body, vec![(key, action)]).await
+    }
+
+    /// Send `body` to `action`, a request applying every one of `writes`,
+    /// once earlier journaled writes have been replayed. If they can't all
+    /// be, or the server can't be reached, each write is journaled instead,
+    /// so none of them lands ahead of an older write to the same key.
+    pub(super) async fn write_batch(
+        &self,
+        api: &StorageApi,
+        namespace: &str,
+        action: &str,
+        body: Value,
+        writes: Vec<(String, JournalAction)>,
+    ) -> StorageResult<()> {
+        let mut journal = self.journal.lock().await;
+        if self.replay_locked(&mut journal, api).await? {
+            match api.try_post(action, body).await {
+                Ok(_) => {
+                    for (key, write) in &writes {
+                        self.remember(namespace, key, write.result());
+                    }
+                    return Ok(());
+                }
+                Err(err) if err.is_offline() => {
+                    log::warn!("Storage API unreachable, journaling {}: {}", action, err);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        for (key, write) in writes {
+            let base = self.known(namespace, &key);
+            self.remember(namespace, &key, write.result());
+            journal.append(namespace, key, write, base)?;
+        }
+        Ok(())
+    }
+
+    /// Replay journaled writes now. Stops at the first one the server can't
+    /// take yet.
+    pub(super) async fn replay(&self, api: &StorageApi) -> StorageResult<SyncStatus> {
+        {
+            let mut journal = self.journal.lock().await;
+            self.replay_locked(&mut journal, api).await?;
+        }
+        Ok(self.status().await)
+    }
+
+    /// Settle the conflict that stopped replay, then carry on replaying.
+    pub(super) async fn resolve_conflict(
+        &self,
+        api: &StorageApi,
+        resolution: ConflictResolution,
+    ) -> StorageResult<SyncStatus> {
+        {
+            let mut journal = self.journal.lock().await;
+            let Some(conflict) = journal.state.conflict.take() else {
+                return Err(StorageError::InvalidOperation(
+                    "No sync conflict to resolve".to_string(),
+                ));
+            };
+            match resolution {
+                ConflictResolution::KeepLocal => {
+                    if let Some(op) = journal.state.ops.first_mut() {
+                        op.base = KnownValue::Unknown;
+                    }
+                    journal.save()?;
+                }
+                ConflictResolution::KeepServer => {
+                    let op = conflict.op;
+                    self.known
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .remove(&(op.namespace, op.key));
+                    journal.pop_front()?;
+                }
+            }
+            self.replay_locked(&mut journal, api).await?;
+        }
+        Ok(self.status().await)
+    }
+
+    /// Replay in order; true once the journal is empty.
+    async fn replay_locked(&self, journal: &mut SyncJournal, api: &StorageApi) -> StorageResult<bool> {
+        if journal.state.conflict.is_some() {
+            return Ok(false);
+        }
+        while let Some(op) = journal.state.ops.first().cloned() {
+            if self.policy == ConflictPolicy::FailAndSurface && op.base != KnownValue::Unknown {
+                let body = json!({ "namespace": op.namespace, "key": op.key });
+                let current = match api.try_post("get", body).await {
+                    Ok(resp) => KnownValue::of(resp.get("value").and_then(|v| v.as_str())),
+                    Err(err) if err.is_offline() => return Ok(false),
+                    Err(err) => return Err(err.into()),
+                };
+                if current != op.base {
+                    journal.state.conflict = Some(SyncConflict {
+                        op,
+                        reason: "The server's copy changed after this write was made offline"
+                            .to_string(),
+                    });
+                    journal.save()?;
+                    return Ok(false);
+                }
+            }
+
+            let (name, body) = op.action.request(&op.namespace, &op.key);
+            match api.try_post(name, body).await {
+                Ok(_) => journal.pop_front()?,
+                Err(err) if err.is_offline() => return Ok(false),
+                // Retrying the same write won't help; hold it for the user
+                Err(err) => {
+                    journal.state.conflict = Some(SyncConflict {
+                        op,
+                        reason: err.to_string(),
+                    });
+                    journal.save()?;
+                    return Ok(false);
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_journal(name: &str) -> SyncJournal {
+        let path = std::env::temp_dir().join(format!("exemem-journal-{}-{}.json", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SyncJournal::load(path).unwrap()
+    }
+
+    #[test]
+    fn test_journal_keeps_order_and_persists() {
+        let mut journal = temp_journal("order");
+        let put = JournalAction::Put { value: "djE=".to_string() };
+        journal.append("main", "a2V5".to_string(), put.clone(), KnownValue::Absent).unwrap();
+        journal.append("main", "a2V5".to_string(), JournalAction::Delete, KnownValue::of(Some("djE="))).unwrap();
+
+        let reloaded = SyncJournal::load(journal.path.clone()).unwrap();
+        assert_eq!(reloaded.state.ops.len(), 2);
+        assert_eq!(reloaded.state.ops[0].action, put);
+        assert_eq!(reloaded.latest("main", "a2V5").unwrap().action, JournalAction::Delete);
+        assert!(reloaded.latest("other", "a2V5").is_none());
+
+        journal.pop_front().unwrap();
+        assert_eq!(journal.state.ops[0].id, 2);
+        let _ = std::fs::remove_file(&journal.path);
+    }
+
+    #[test]
+    fn test_known_value_compares_by_content() {
+        assert_eq!(KnownValue::of(Some("YQ==")), KnownValue::of(Some("YQ==")));
+        assert_ne!(KnownValue::of(Some("YQ==")), KnownValue::of(Some("Yg==")));
+        assert_eq!(KnownValue::of(None), KnownValue::Absent);
+    }
+}
@@ -0,0 +1,229 @@
+use fold_db::storage::error::StorageResult;
+use fold_db::storage::traits::{ExecutionModel, FlushBehavior, KvStore};
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// KvStore kept in a map, for testing code that takes a `KvStore` without
+/// a Storage API to talk to.
+#[derive(Debug, Default)]
+pub struct MemoryKvStore {
+    items: Mutex<BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl MemoryKvStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Everything stored, in key order.
+    pub fn items(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.lock().iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Vec<u8>, Vec<u8>>> {
+        self.items.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl KvStore for MemoryKvStore {
+    async fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        Ok(self.lock().get(key).cloned())
+    }
+
+    async fn put(&self, key: &[u8], value: Vec<u8>) -> StorageResult<()> {
+        self.lock().insert(key.to_vec(), value);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &[u8]) -> StorageResult<bool> {
+        Ok(self.lock().remove(key).is_some())
+    }
+
+    async fn exists(&self, key: &[u8]) -> StorageResult<bool> {
+        Ok(self.lock().contains_key(key))
+    }
+
+    async fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        Ok(self
+            .lock()
+            .range(prefix.to_vec()..)
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect())
+    }
+
+    async fn batch_put(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> StorageResult<()> {
+        self.lock().extend(items);
+        Ok(())
+    }
+
+    async fn batch_delete(&self, keys: Vec<Vec<u8>>) -> StorageResult<()> {
+        let mut items = self.lock();
+        for key in keys {
+            items.remove(&key);
+        }
+        Ok(())
+    }
+
+    async fn flush(&self) -> StorageResult<()> {
+        Ok(())
+    }
+
+    fn backend_name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn execution_model(&self) -> ExecutionModel {
+        ExecutionModel::Async
+    }
+
+    fn flush_behavior(&self) -> FlushBehavior {
+        FlushBehavior::NoOp
+    }
+}
+
+/// A call made through a [`RecordingKvStore`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedOp {
+    Get(Vec<u8>),
+    Put(Vec<u8>, Vec<u8>),
+    Delete(Vec<u8>),
+    Exists(Vec<u8>),
+    ScanPrefix(Vec<u8>),
+    BatchPut(Vec<(Vec<u8>, Vec<u8>)>),
+    BatchDelete(Vec<Vec<u8>>),
+    Flush,
+}
+
+/// Wraps another KvStore and records every call made through it, so tests
+/// can assert on what a consumer did as well as on the resulting data.
+pub struct RecordingKvStore {
+    inner: Arc<dyn KvStore>,
+    ops: Mutex<Vec<RecordedOp>>,
+}
+
+impl RecordingKvStore {
+    pub fn new(inner: Arc<dyn KvStore>) -> Self {
+        Self {
+            inner,
+            ops: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Recording over a fresh [`MemoryKvStore`].
+    pub fn in_memory() -> Self {
+        Self::new(Arc::new(MemoryKvStore::new()))
+    }
+
+    /// Calls so far, oldest first.
+    pub fn ops(&self) -> Vec<RecordedOp> {
+        self.ops.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn clear(&self) {
+        self.ops.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    fn record(&self, op: RecordedOp) {
+        self.ops.lock().unwrap_or_else(|e| e.into_inner()).push(op);
+    }
+}
+
+#[async_trait]
+impl KvStore for RecordingKvStore {
+    async fn get(&self, key: &[u8]) -> StorageResult<Option<Vec<u8>>> {
+        self.record(RecordedOp::Get(key.to_vec()));
+        self.inner.get(key).await
+    }
+
+    async fn put(&self, key: &[u8], value: Vec<u8>) -> StorageResult<()> {
+        self.record(RecordedOp::Put(key.to_vec(), value.clone()));
+        self.inner.put(key, value).await
+    }
+
+    async fn delete(&self, key: &[u8]) -> StorageResult<bool> {
+        self.record(RecordedOp::Delete(key.to_vec()));
+        self.inner.delete(key).await
+    }
+
+    async fn exists(&self, key: &[u8]) -> StorageResult<bool> {
+        self.record(RecordedOp::Exists(key.to_vec()));
+        self.inner.exists(key).await
+    }
+
+    async fn scan_prefix(&self, prefix: &[u8]) -> StorageResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.record(RecordedOp::ScanPrefix(prefix.to_vec()));
+        self.inner.scan_prefix(prefix).await
+    }
+
+    async fn batch_put(&self, items: Vec<(Vec<u8>, Vec<u8>)>) -> StorageResult<()> {
+        self.record(RecordedOp::BatchPut(items.clone()));
+        self.inner.batch_put(items).await
+    }
+
+    async fn batch_delete(&self, keys: Vec<Vec<u8>>) -> StorageResult<()> {
+        self.record(RecordedOp::BatchDelete(keys.clone()));
+        self.inner.batch_delete(keys).await
+    }
+
+    async fn flush(&self) -> StorageResult<()> {
+        self.record(RecordedOp::Flush);
+        self.inner.flush().await
+    }
+
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    fn execution_model(&self) -> ExecutionModel {
+        self.inner.execution_model()
+    }
+
+    fn flush_behavior(&self) -> FlushBehavior {
+        self.inner.flush_behavior()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_store_basics() {
+        let store = MemoryKvStore::new();
+        store.put(b"a:1", b"one".to_vec()).await.unwrap();
+        store
+            .batch_put(vec![(b"a:2".to_vec(), b"two".to_vec()), (b"b:1".to_vec(), b"x".to_vec())])
+            .await
+            .unwrap();
+
+        assert_eq!(store.get(b"a:1").await.unwrap(), Some(b"one".to_vec()));
+        assert!(store.exists(b"b:1").await.unwrap());
+        let scanned = store.scan_prefix(b"a:").await.unwrap();
+        assert_eq!(scanned.len(), 2);
+        assert_eq!(scanned[1].0, b"a:2".to_vec());
+
+        assert!(store.delete(b"a:1").await.unwrap());
+        assert!(!store.delete(b"a:1").await.unwrap());
+        store.batch_delete(vec![b"a:2".to_vec()]).await.unwrap();
+        assert_eq!(store.items(), vec![(b"b:1".to_vec(), b"x".to_vec())]);
+    }
+
+    #[tokio::test]
+    async fn test_recording_store_records_calls() {
+        let store = RecordingKvStore::in_memory();
+        store.put(b"k", b"v".to_vec()).await.unwrap();
+        assert_eq!(store.get(b"k").await.unwrap(), Some(b"v".to_vec()));
+
+        assert_eq!(
+            store.ops(),
+            vec![
+                RecordedOp::Put(b"k".to_vec(), b"v".to_vec()),
+                RecordedOp::Get(b"k".to_vec()),
+            ]
+        );
+        assert_eq!(store.backend_name(), "memory");
+    }
+}
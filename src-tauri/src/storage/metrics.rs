@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Latencies kept per operation for percentiles; older ones are dropped
+const MAX_SAMPLES: usize = 512;
+
+/// One Storage API call, as counted in the metrics.
+pub(super) struct CallSample<'a> {
+    pub namespace: &'a str,
+    pub action: &'a str,
+    pub latency: Duration,
+    pub ok: bool,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Default)]
+struct OperationTotals {
+    calls: u64,
+    errors: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    latencies_ms: VecDeque<u64>,
+}
+
+/// Counts for one action in one namespace since the store was created.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OperationStats {
+    pub namespace: String,
+    /// Storage API action, e.g. "get" or "batch-put"
+    pub operation: String,
+    pub calls: u64,
+    pub errors: u64,
+    /// errors / calls
+    pub error_rate: f64,
+    /// Over the most recent calls
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// In-memory per-operation counters for the Storage API client.
+#[derive(Debug, Default)]
+pub struct StorageMetrics {
+    operations: Mutex<BTreeMap<(String, String), OperationTotals>>,
+}
+
+impl StorageMetrics {
+    pub(super) fn record(&self, sample: &CallSample) {
+        let mut operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+        let totals = operations
+            .entry((sample.namespace.to_string(), sample.action.to_string()))
+            .or_default();
+        totals.calls += 1;
+        if !sample.ok {
+            totals.errors += 1;
+        }
+        totals.bytes_sent += sample.bytes_sent;
+        totals.bytes_received += sample.bytes_received;
+        if totals.latencies_ms.len() == MAX_SAMPLES {
+            totals.latencies_ms.pop_front();
+        }
+        totals.latencies_ms.push_back(sample.latency.as_millis() as u64);
+    }
+
+    /// Stats for every operation seen so far, by namespace then action.
+    pub fn snapshot(&self) -> Vec<OperationStats> {
+        let operations = self.operations.lock().unwrap_or_else(|e| e.into_inner());
+        operations
+            .iter()
+            .map(|((namespace, operation), totals)| {
+                let mut latencies: Vec<u64> = totals.latencies_ms.iter().copied().collect();
+                latencies.sort_unstable();
+                OperationStats {
+                    namespace: namespace.clone(),
+                    operation: operation.clone(),
+                    calls: totals.calls,
+                    errors: totals.errors,
+                    error_rate: if totals.calls == 0 {
+                        0.0
+                    } else {
+                        totals.errors as f64 / totals.calls as f64
+                    },
+                    p50_ms: percentile(&latencies, 50),
+                    p95_ms: percentile(&latencies, 95),
+                    bytes_sent: totals.bytes_sent,
+                    bytes_received: totals.bytes_received,
+                }
+            })
+            .collect()
+    }
+
+    pub fn reset(&self) {
+        self.operations.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+/// Nearest-rank percentile of sorted values; 0 when there are none.
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (pct * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(action: &str, ms: u64, ok: bool) -> CallSample<'_> {
+        CallSample {
+            namespace: "main",
+            action,
+            latency: Duration::from_millis(ms),
+            ok,
+            bytes_sent: 10,
+            bytes_received: 100,
+        }
+    }
+
+    #[test]
+    fn test_percentile() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50), 50);
+        assert_eq!(percentile(&values, 95), 95);
+        assert_eq!(percentile(&[7], 95), 7);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn test_snapshot_per_operation() {
+        let metrics = StorageMetrics::default();
+        for ms in 1..=20 {
+            metrics.record(&sample("get", ms, true));
+        }
+        metrics.record(&sample("put", 40, false));
+
+        let stats = metrics.snapshot();
+        assert_eq!(stats.len(), 2);
+        let get = &stats[0];
+        assert_eq!((get.operation.as_str(), get.calls, get.errors), ("get", 20, 0));
+        assert_eq!((get.p50_ms, get.p95_ms), (10, 19));
+        assert_eq!(get.bytes_received, 2000);
+        assert_eq!(stats[1].error_rate, 1.0);
+
+        metrics.reset();
+        assert!(metrics.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+//! A fake Storage API on a local port, for integration tests of the storage
+//! layer that run without network access. Every action the client uses is
+//! served from memory, and each request is kept for later assertions.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use wiremock::matchers::{method, path_regex};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use super::{ExememAuth, ExememNamespacedStore};
+
+#[derive(Debug, Default)]
+struct FakeState {
+    /// (namespace, raw key) -> base64 value
+    items: BTreeMap<(String, Vec<u8>), String>,
+    /// Requests left to answer with a 503
+    unavailable: u32,
+}
+
+/// Serves `/api/storage/<action>` like the real Storage API.
+struct FakeStorage {
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl Respond for FakeStorage {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.unavailable > 0 {
+            state.unavailable -= 1;
+            return ResponseTemplate::new(503).set_body_string("Service Unavailable");
+        }
+
+        let action = request.url.path().trim_start_matches("/api/storage/");
+        let body: Value = match request.body_json() {
+            Ok(body) => body,
+            Err(e) => return error(&format!("bad request body: {e}")),
+        };
+        match handle(&mut state, action, &body) {
+            Some(resp) => ResponseTemplate::new(200).set_body_json(resp),
+            None => ResponseTemplate::new(404).set_body_string("Not Found"),
+        }
+    }
+}
+
+fn error(message: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_json(json!({ "ok": false, "error": message }))
+}
+
+fn str_field<'a>(body: &'a Value, name: &str) -> &'a str {
+    body.get(name).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+fn raw_key(b64: &str) -> Vec<u8> {
+    BASE64.decode(b64).unwrap_or_default()
+}
+
+/// The response for one action, or None if the action doesn't exist.
+fn handle(state: &mut FakeState, action: &str, body: &Value) -> Option<Value> {
+    let namespace = str_field(body, "namespace").to_string();
+    let slot = |key: &str| (namespace.clone(), raw_key(key));
+
+    let resp = match action {
+        "get" => json!({ "ok": true, "value": state.items.get(&slot(str_field(body, "key"))) }),
+        "exists" => json!({ "ok": true, "exists": state.items.contains_key(&slot(str_field(body, "key"))) }),
+        "put" => {
+            state
+                .items
+                .insert(slot(str_field(body, "key")), str_field(body, "value").to_string());
+            json!({ "ok": true })
+        }
+        "delete" => {
+            state.items.remove(&slot(str_field(body, "key")));
+            json!({ "ok": true })
+        }
+        "batch-put" => {
+            for item in body["items"].as_array().into_iter().flatten() {
+                state
+                    .items
+                    .insert(slot(str_field(item, "key")), str_field(item, "value").to_string());
+            }
+            json!({ "ok": true })
+        }
+        "batch-delete" => {
+            for item in body["items"].as_array().into_iter().flatten() {
+                state.items.remove(&slot(str_field(item, "key")));
+            }
+            json!({ "ok": true })
+        }
+        "batch-get" => {
+            let items: Vec<Value> = body["keys"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|k| k.as_str())
+                .map(|k| json!({ "key": k, "value": state.items.get(&slot(k)) }))
+                .collect();
+            json!({ "ok": true, "items": items })
+        }
+        "scan-prefix" | "scan-range" => {
+            let prefix = raw_key(str_field(body, "prefix"));
+            let start = raw_key(str_field(body, "start"));
+            let end = body.get("end").and_then(|v| v.as_str()).map(raw_key);
+            let limit = body["limit"].as_u64().unwrap_or(u64::MAX) as usize;
+            let after = body.get("cursor").and_then(|v| v.as_str()).map(raw_key);
+            let mut matching = state
+                .items
+                .iter()
+                .filter(|((ns, key), _)| *ns == namespace && key.starts_with(&prefix))
+                .filter(|((_, key), _)| *key >= start && end.as_ref().map_or(true, |end| key < end))
+                .filter(|((_, key), _)| after.as_ref().map_or(true, |after| key > after));
+            let page: Vec<(Vec<u8>, String)> = matching
+                .by_ref()
+                .take(limit)
+                .map(|((_, key), value)| (key.clone(), value.clone()))
+                .collect();
+            let next_cursor = match (matching.next(), page.last()) {
+                (Some(_), Some((last, _))) => Some(BASE64.encode(last)),
+                _ => None,
+            };
+            let items: Vec<Value> = page
+                .iter()
+                .map(|(key, value)| json!({ "key": BASE64.encode(key), "value": value }))
+                .collect();
+            json!({ "ok": true, "items": items, "next_cursor": next_cursor })
+        }
+        "conditional-put" => {
+            let slot = slot(str_field(body, "key"));
+            let current = state.items.get(&slot).cloned();
+            if current.as_deref() == body["expected"].as_str() {
+                state.items.insert(slot, str_field(body, "value").to_string());
+                json!({ "ok": true, "applied": true })
+            } else {
+                json!({ "ok": true, "applied": false, "current": current })
+            }
+        }
+        "transact" => {
+            for op in body["ops"].as_array().into_iter().flatten() {
+                let slot = slot(str_field(op, "key"));
+                match str_field(op, "op") {
+                    "put" => {
+                        state.items.insert(slot, str_field(op, "value").to_string());
+                    }
+                    _ => {
+                        state.items.remove(&slot);
+                    }
+                }
+            }
+            json!({ "ok": true })
+        }
+        "list-namespaces" => {
+            let mut names: Vec<&String> = state.items.keys().map(|(ns, _)| ns).collect();
+            names.dedup();
+            json!({ "ok": true, "namespaces": names, "next_cursor": null })
+        }
+        "delete-namespace" => {
+            let before = state.items.len();
+            state.items.retain(|(ns, _), _| *ns != namespace);
+            json!({ "ok": true, "deleted": state.items.len() != before })
+        }
+        _ => return None,
+    };
+    Some(resp)
+}
+
+/// A running fake Storage API. Dropping it shuts the server down.
+pub struct MockStorageApi {
+    server: MockServer,
+    state: Arc<Mutex<FakeState>>,
+}
+
+impl MockStorageApi {
+    pub async fn start() -> Self {
+        let server = MockServer::start().await;
+        let state = Arc::new(Mutex::new(FakeState::default()));
+        Mock::given(method("POST"))
+            .and(path_regex(r"^/api/storage/[a-z-]+$"))
+            .respond_with(FakeStorage {
+                state: state.clone(),
+            })
+            .mount(&server)
+            .await;
+        Self { server, state }
+    }
+
+    /// Base URL to hand to the store.
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// A store pointed at this server.
+    pub fn namespaced_store(&self) -> ExememNamespacedStore {
+        ExememNamespacedStore::new(self.uri(), ExememAuth::ApiKey("test-key".to_string()))
+    }
+
+    /// Answer the next `count` requests with a 503, to exercise retries and
+    /// the circuit breaker.
+    pub fn fail_next(&self, count: u32) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).unavailable = count;
+    }
+
+    /// Raw value bytes held for a key, as the server stores them.
+    pub fn stored(&self, namespace: &str, key: &[u8]) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state
+            .items
+            .get(&(namespace.to_string(), key.to_vec()))
+            .and_then(|v| BASE64.decode(v).ok())
+    }
+
+    /// Actions requested so far, in order, e.g. `["put", "get"]`.
+    pub async fn actions(&self) -> Vec<String> {
+        self.server
+            .received_requests()
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|r| r.url.path().trim_start_matches("/api/storage/").to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{ExememApiStore, TransactOp};
+    use fold_db::storage::traits::{KvStore, NamespacedStore};
+
+    #[tokio::test]
+    async fn test_round_trip_through_fake_api() {
+        let api = MockStorageApi::start().await;
+        let store = api.namespaced_store().open_namespace("main").await.unwrap();
+
+        store.put(b"note:1", b"hello".to_vec()).await.unwrap();
+        store
+            .batch_put(vec![(b"note:2".to_vec(), b"world".to_vec())])
+            .await
+            .unwrap();
+
+        assert_eq!(store.get(b"note:1").await.unwrap(), Some(b"hello".to_vec()));
+        assert_eq!(store.scan_prefix(b"note:").await.unwrap().len(), 2);
+        assert_eq!(api.stored("main", b"note:2"), Some(b"world".to_vec()));
+        assert_eq!(api.actions().await, vec!["put", "batch-put", "get", "scan-prefix"]);
+    }
+
+    #[tokio::test]
+    async fn test_transact_applies_every_op() {
+        let api = MockStorageApi::start().await;
+        let store = ExememApiStore::new(
+            Arc::new(reqwest::Client::new()),
+            api.uri(),
+            "main".to_string(),
+            ExememAuth::ApiKey("test-key".to_string()),
+        );
+        store.put(b"old", b"1".to_vec()).await.unwrap();
+
+        store
+            .transact(vec![
+                TransactOp::Put { key: b"new".to_vec(), value: b"2".to_vec() },
+                TransactOp::Delete { key: b"old".to_vec() },
+            ])
+            .await
+            .unwrap();
+        assert_eq!(api.stored("main", b"new"), Some(b"2".to_vec()));
+        assert_eq!(api.stored("main", b"old"), None);
+    }
+
+    #[tokio::test]
+    async fn test_scan_range_between_bounds() {
+        let api = MockStorageApi::start().await;
+        let store = ExememApiStore::new(
+            Arc::new(reqwest::Client::new()),
+            api.uri(),
+            "main".to_string(),
+            ExememAuth::ApiKey("test-key".to_string()),
+        );
+        for day in ["log:01", "log:02", "log:03", "log:04"] {
+            store.put(day.as_bytes(), b"entry".to_vec()).await.unwrap();
+        }
+
+        let keys = |items: Vec<(Vec<u8>, Vec<u8>)>| -> Vec<Vec<u8>> {
+            items.into_iter().map(|(k, _)| k).collect()
+        };
+        let range = store.scan_range(b"log:02", b"log:04", 10).await.unwrap();
+        assert_eq!(keys(range), vec![b"log:02".to_vec(), b"log:03".to_vec()]);
+        let capped = store.scan_range(b"log:", b"log:~", 1).await.unwrap();
+        assert_eq!(keys(capped), vec![b"log:01".to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_retries_past_unavailable_responses() {
+        let api = MockStorageApi::start().await;
+        let store = api.namespaced_store().open_namespace("main").await.unwrap();
+
+        api.fail_next(1);
+        store.put(b"k", b"v".to_vec()).await.unwrap();
+        assert_eq!(api.stored("main", b"k"), Some(b"v".to_vec()));
+    }
+}
@@ -1,5 +1,17 @@
 pub mod api_store;
+mod circuit;
+pub mod journal;
+#[cfg(any(test, feature = "test-support"))]
+pub mod memory;
+pub mod metrics;
+#[cfg(feature = "test-support")]
+pub mod mock_api;
 pub mod namespaced_store;
+mod value_codec;
 
-pub use api_store::{ExememApiStore, ExememAuth};
+pub use api_store::{CasOutcome, ExememApiStore, ExememAuth, ScanPage, TransactOp, MAX_TRANSACT_OPS};
+pub use journal::{ConflictPolicy, ConflictResolution, SyncConflict, SyncStatus};
+#[cfg(any(test, feature = "test-support"))]
+pub use memory::{MemoryKvStore, RecordedOp, RecordingKvStore};
+pub use metrics::OperationStats;
 pub use namespaced_store::ExememNamespacedStore;
@@ -1,9 +1,19 @@
 use fold_db::storage::error::{StorageError, StorageResult};
 use fold_db::storage::traits::{KvStore, NamespacedStore};
-use super::api_store::{ExememApiStore, ExememAuth};
+use super::api_store::{ExememApiStore, ExememAuth, StorageApi};
+use super::journal::{ConflictPolicy, ConflictResolution, OfflineSync, SyncStatus};
+use super::metrics::OperationStats;
 use async_trait::async_trait;
 use reqwest::Client;
+use serde_json::{json, Value};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+
+use crate::retry::RetryPolicy;
+
+/// Namespaces requested per list-namespaces call
+const LIST_PAGE_SIZE: usize = 100;
 
 /// NamespacedStore implementation for the Exemem Storage API.
 ///
@@ -11,44 +21,213 @@ use std::sync::Arc;
 /// No server call is needed because the namespace is just a field in each
 /// request body — the Storage API Lambda resolves it to the correct
 /// DynamoDB table on the server side.
+///
+/// `delete_namespace` drops every key in the namespace, so it is refused
+/// unless the store was built with `allow_namespace_deletion`.
 pub struct ExememNamespacedStore {
-    client: Arc<Client>,
-    base_url: String,
-    auth: ExememAuth,
+    api: StorageApi,
+    allow_deletion: bool,
+    /// Passed to each namespace's store; see `ExememApiStore::with_compression`
+    compress_above: Option<usize>,
+    /// Shared by every namespace's store when offline mode is on
+    offline: Option<Arc<OfflineSync>>,
 }
 
 impl ExememNamespacedStore {
     pub fn new(base_url: String, auth: ExememAuth) -> Self {
+        Self::with_client(Arc::new(Client::new()), base_url, auth)
+    }
+
+    /// Use a preconfigured client, normally the app's shared one from
+    /// `crate::http::build_client`, so storage calls reuse its connections.
+    pub fn with_client(client: Arc<Client>, base_url: String, auth: ExememAuth) -> Self {
         Self {
-            client: Arc::new(Client::new()),
-            base_url,
-            auth,
+            api: StorageApi::new(client, base_url, auth),
+            allow_deletion: false,
+            compress_above: None,
+            offline: None,
         }
     }
+
+    /// Calls, errors, latency and bytes per namespace and operation, across
+    /// every namespace opened from here.
+    pub fn metrics_snapshot(&self) -> Vec<OperationStats> {
+        self.api.metrics().snapshot()
+    }
+
+    /// Zero the counters behind `metrics_snapshot`.
+    pub fn reset_metrics(&self) {
+        self.api.metrics().reset();
+    }
+
+    /// Give up on each Storage API request after `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.api.set_timeout(timeout);
+        self
+    }
+
+    /// How to retry connection failures and 5xx responses.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.api.set_retry_policy(retry);
+        self
+    }
+
+    /// Fail fast for `cooldown` after `threshold` calls in a row couldn't
+    /// reach the server. The breaker is shared by every namespace opened
+    /// from here.
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.api.set_circuit_breaker(threshold, cooldown);
+        self
+    }
+
+    /// Store values of at least `threshold` bytes zstd-compressed in every
+    /// namespace opened from here.
+    pub fn with_compression(mut self, threshold: usize) -> Self {
+        self.compress_above = Some(threshold);
+        self
+    }
+
+    /// Keep working while the Storage API is unreachable: puts and deletes
+    /// are journaled at `journal_path` and replayed in order once it is back,
+    /// with `policy` deciding what happens if the server's copy changed.
+    pub fn with_offline_journal(mut self, journal_path: PathBuf, policy: ConflictPolicy) -> StorageResult<Self> {
+        self.offline = Some(Arc::new(OfflineSync::open(journal_path, policy)?));
+        Ok(self)
+    }
+
+    /// How many offline writes are waiting and whether replay is stopped on a
+    /// conflict. Always empty when offline mode is off.
+    pub async fn sync_status(&self) -> SyncStatus {
+        match &self.offline {
+            Some(offline) => offline.status().await,
+            None => SyncStatus {
+                pending: 0,
+                conflict: None,
+                policy: ConflictPolicy::default(),
+            },
+        }
+    }
+
+    /// Replay journaled writes now rather than waiting for the next write.
+    pub async fn sync(&self) -> StorageResult<SyncStatus> {
+        match &self.offline {
+            Some(offline) => offline.replay(&self.api).await,
+            None => Ok(self.sync_status().await),
+        }
+    }
+
+    /// Settle the conflict reported in `sync_status` and resume replay.
+    pub async fn resolve_conflict(&self, resolution: ConflictResolution) -> StorageResult<SyncStatus> {
+        let Some(offline) = &self.offline else {
+            return Err(StorageError::InvalidOperation(
+                "Offline mode is not enabled on this store".to_string(),
+            ));
+        };
+        offline.resolve_conflict(&self.api, resolution).await
+    }
+
+    /// Let `delete_namespace` actually delete. Without this it fails with
+    /// `InvalidOperation`, so a stray call can't wipe a namespace.
+    pub fn allow_namespace_deletion(mut self) -> Self {
+        self.allow_deletion = true;
+        self
+    }
+
+    /// One page of namespace names, starting after `cursor`, and the cursor
+    /// for the next page if there is one.
+    pub async fn list_namespaces_page(
+        &self,
+        cursor: Option<&str>,
+    ) -> StorageResult<(Vec<String>, Option<String>)> {
+        let mut body = json!({ "limit": LIST_PAGE_SIZE });
+        if let Some(cursor) = cursor {
+            body["cursor"] = json!(cursor);
+        }
+        let resp = self.post("list-namespaces", body).await?;
+        parse_namespace_page(&resp)
+    }
+
+    async fn post(&self, action: &str, body: Value) -> StorageResult<Value> {
+        self.api.post(action, body).await
+    }
+}
+
+fn parse_namespace_page(resp: &Value) -> StorageResult<(Vec<String>, Option<String>)> {
+    let names = resp
+        .get("namespaces")
+        .and_then(|v| v.as_array())
+        .ok_or_else(|| {
+            StorageError::BackendError(
+                "Missing 'namespaces' array in list-namespaces response".to_string(),
+            )
+        })?
+        .iter()
+        .map(|v| {
+            v.as_str().map(str::to_string).ok_or_else(|| {
+                StorageError::BackendError(
+                    "Non-string namespace in list-namespaces response".to_string(),
+                )
+            })
+        })
+        .collect::<StorageResult<Vec<_>>>()?;
+    let next = resp
+        .get("next_cursor")
+        .and_then(|v| v.as_str())
+        .filter(|c| !c.is_empty())
+        .map(str::to_string);
+    Ok((names, next))
 }
 
 #[async_trait]
 impl NamespacedStore for ExememNamespacedStore {
     async fn open_namespace(&self, name: &str) -> StorageResult<Arc<dyn KvStore>> {
-        let store = ExememApiStore::new(
-            self.client.clone(),
-            self.base_url.clone(),
-            name.to_string(),
-            self.auth.clone(),
-        );
+        let mut store = ExememApiStore::with_api(self.api.clone(), name.to_string());
+        if let Some(threshold) = self.compress_above {
+            store = store.with_compression(threshold);
+        }
+        let store = match &self.offline {
+            Some(offline) => store.with_offline(offline.clone()),
+            None => store,
+        };
         Ok(Arc::new(store))
     }
 
     async fn list_namespaces(&self) -> StorageResult<Vec<String>> {
-        Err(StorageError::InvalidOperation(
-            "list_namespaces not supported via Exemem Storage API".to_string(),
-        ))
+        let mut names = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let (page, next) = self.list_namespaces_page(cursor.as_deref()).await?;
+            names.extend(page);
+            match next {
+                // A server that hands back the same cursor would loop forever
+                Some(next) if cursor.as_deref() == Some(next.as_str()) => {
+                    return Err(StorageError::BackendError(
+                        "list-namespaces returned the same cursor twice".to_string(),
+                    ))
+                }
+                Some(next) => cursor = Some(next),
+                None => return Ok(names),
+            }
+        }
     }
 
-    async fn delete_namespace(&self, _name: &str) -> StorageResult<bool> {
-        Err(StorageError::InvalidOperation(
-            "delete_namespace not supported via Exemem Storage API".to_string(),
-        ))
+    async fn delete_namespace(&self, name: &str) -> StorageResult<bool> {
+        if !self.allow_deletion {
+            return Err(StorageError::InvalidOperation(format!(
+                "Refusing to delete namespace '{name}': deletion is not enabled on this store"
+            )));
+        }
+        if name.trim().is_empty() {
+            return Err(StorageError::InvalidOperation(
+                "Namespace name can't be empty".to_string(),
+            ));
+        }
+
+        let resp = self
+            .post("delete-namespace", json!({ "namespace": name, "confirm": true }))
+            .await?;
+        // Older responses don't say whether the namespace existed
+        Ok(resp.get("deleted").and_then(|v| v.as_bool()).unwrap_or(true))
     }
 }
 
@@ -67,25 +246,42 @@ mod tests {
         assert_eq!(ns.backend_name(), "exemem-api");
     }
 
+    #[test]
+    fn test_parse_namespace_page() {
+        let (names, next) = parse_namespace_page(&json!({
+            "ok": true,
+            "namespaces": ["main", "schemas"],
+            "next_cursor": "abc",
+        }))
+        .unwrap();
+        assert_eq!(names, vec!["main", "schemas"]);
+        assert_eq!(next.as_deref(), Some("abc"));
+
+        let (_, next) = parse_namespace_page(&json!({"ok": true, "namespaces": [], "next_cursor": ""})).unwrap();
+        assert_eq!(next, None);
+        assert!(parse_namespace_page(&json!({"ok": true})).is_err());
+    }
+
     #[tokio::test]
-    async fn test_list_namespaces_unsupported() {
+    async fn test_sync_status_without_offline_mode() {
         let store = ExememNamespacedStore::new(
             "https://api.example.com".to_string(),
             ExememAuth::UserHash("test_user".to_string()),
         );
 
-        let result = store.list_namespaces().await;
-        assert!(result.is_err());
+        assert_eq!(store.sync_status().await.pending, 0);
+        let result = store.resolve_conflict(ConflictResolution::KeepServer).await;
+        assert!(matches!(result, Err(StorageError::InvalidOperation(_))));
     }
 
     #[tokio::test]
-    async fn test_delete_namespace_unsupported() {
+    async fn test_delete_namespace_needs_opt_in() {
         let store = ExememNamespacedStore::new(
             "https://api.example.com".to_string(),
             ExememAuth::UserHash("test_user".to_string()),
         );
 
         let result = store.delete_namespace("main").await;
-        assert!(result.is_err());
+        assert!(matches!(result, Err(StorageError::InvalidOperation(_))));
     }
 }
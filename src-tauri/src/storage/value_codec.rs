@@ -0,0 +1,70 @@
+use fold_db::storage::error::{StorageError, StorageResult};
+
+/// Prefix marking a stored value as a zstd frame rather than raw bytes
+const COMPRESSED_MARKER: &[u8] = b"\0EXZ1";
+
+/// zstd level: fast, and most of the win on JSON comes early
+const ZSTD_LEVEL: i32 = 3;
+
+/// Bytes to store for `value`. With `threshold` set, values at least that
+/// large are compressed when it makes them smaller.
+///
+/// A raw value that happens to start with the marker is always compressed,
+/// so `unpack` never mistakes it for a compressed one.
+pub fn pack(value: &[u8], threshold: Option<usize>) -> StorageResult<Vec<u8>> {
+    let collides = value.starts_with(COMPRESSED_MARKER);
+    let wanted = threshold.is_some_and(|threshold| value.len() >= threshold);
+    if !collides && !wanted {
+        return Ok(value.to_vec());
+    }
+
+    let compressed = zstd::encode_all(value, ZSTD_LEVEL)
+        .map_err(|e| StorageError::BackendError(format!("Failed to compress value: {e}")))?;
+    if !collides && compressed.len() + COMPRESSED_MARKER.len() >= value.len() {
+        return Ok(value.to_vec());
+    }
+    let mut packed = Vec::with_capacity(COMPRESSED_MARKER.len() + compressed.len());
+    packed.extend_from_slice(COMPRESSED_MARKER);
+    packed.extend_from_slice(&compressed);
+    Ok(packed)
+}
+
+/// The original value from stored bytes, whether or not they were compressed.
+pub fn unpack(stored: Vec<u8>) -> StorageResult<Vec<u8>> {
+    match stored.strip_prefix(COMPRESSED_MARKER) {
+        Some(frame) => zstd::decode_all(frame)
+            .map_err(|e| StorageError::BackendError(format!("Failed to decompress value: {e}"))),
+        None => Ok(stored),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_values_stay_raw() {
+        let value = b"{\"a\":1}".to_vec();
+        assert_eq!(pack(&value, Some(1024)).unwrap(), value);
+        assert_eq!(pack(&value, None).unwrap(), value);
+        assert_eq!(unpack(value.clone()).unwrap(), value);
+    }
+
+    #[test]
+    fn test_large_values_round_trip() {
+        let value = "{\"field\":\"repeated\"}".repeat(200).into_bytes();
+        let packed = pack(&value, Some(1024)).unwrap();
+        assert!(packed.starts_with(COMPRESSED_MARKER));
+        assert!(packed.len() < value.len() / 4);
+        assert_eq!(unpack(packed).unwrap(), value);
+    }
+
+    #[test]
+    fn test_marker_collision_is_escaped() {
+        let mut value = COMPRESSED_MARKER.to_vec();
+        value.extend_from_slice(b"not really zstd");
+        let packed = pack(&value, None).unwrap();
+        assert_ne!(packed, value);
+        assert_eq!(unpack(packed).unwrap(), value);
+    }
+}
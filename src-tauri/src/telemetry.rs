@@ -0,0 +1,217 @@
+//! Opt-in, anonymous usage telemetry.
+//!
+//! Events only name a feature, how long it took and, when it failed, the
+//! error kind. Names and kinds are `&'static str` so nothing derived from the
+//! user's data (file names, queries, messages) can end up in an event. Events
+//! are buffered locally and uploaded in batches; `batch` is exactly what the
+//! next upload sends, so the user can inspect it first.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::ledger::now_secs;
+use crate::persist::JsonStore;
+
+/// Events kept at most; the oldest are dropped first
+const MAX_EVENTS: usize = 1000;
+/// Events sent per upload
+const BATCH_SIZE: usize = 200;
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TelemetryEvent {
+    pub feature: String,
+    /// Seconds since the Unix epoch, rounded down to the hour
+    pub hour: u64,
+    pub duration_ms: u64,
+    /// Kind of failure, such as `Error::kind`; never the message
+    pub error: Option<String>,
+}
+
+/// One upload's worth of events.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryBatch {
+    /// Random id of this install, unrelated to the account
+    pub install_id: String,
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub events: Vec<TelemetryEvent>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TelemetryData {
+    install_id: String,
+    events: Vec<TelemetryEvent>,
+}
+
+pub struct Telemetry {
+    store: JsonStore<TelemetryData>,
+}
+
+impl Telemetry {
+    const FILE: &'static str = "telemetry.json";
+
+    pub fn load() -> Result<Self, String> {
+        JsonStore::load(Self::FILE, "telemetry").map(|store| Self { store })
+    }
+
+    pub fn empty() -> Result<Self, String> {
+        JsonStore::empty(Self::FILE, "telemetry").map(|store| Self { store })
+    }
+
+    pub fn record(
+        &mut self,
+        feature: &'static str,
+        duration: Duration,
+        error_kind: Option<&'static str>,
+    ) -> Result<(), String> {
+        self.store.update(|data| {
+            // Picked with the first event, so an unused buffer has no id
+            if data.install_id.is_empty() {
+                data.install_id = new_install_id();
+            }
+            data.events.push(TelemetryEvent {
+                feature: feature.to_string(),
+                hour: now_secs() / 3600 * 3600,
+                duration_ms: duration.as_millis() as u64,
+                error: error_kind.map(str::to_string),
+            });
+            if data.events.len() > MAX_EVENTS {
+                let excess = data.events.len() - MAX_EVENTS;
+                data.events.drain(..excess);
+            }
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.get().events.is_empty()
+    }
+
+    /// The oldest buffered events, as the next upload would send them.
+    pub fn batch(&self) -> TelemetryBatch {
+        let data = self.store.get();
+        TelemetryBatch {
+            install_id: data.install_id.clone(),
+            app_version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            events: data.events.iter().take(BATCH_SIZE).cloned().collect(),
+        }
+    }
+
+    /// Drop the first `count` events once they've been uploaded.
+    pub fn acknowledge(&mut self, count: usize) -> Result<(), String> {
+        self.store.update(|data| {
+            let count = count.min(data.events.len());
+            data.events.drain(..count);
+        })
+    }
+
+    /// Forget everything, including the install id, for when telemetry is
+    /// turned off.
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.store.update(|data| *data = TelemetryData::default())
+    }
+}
+
+fn new_install_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Send `batch` to the backend. No credentials are attached, so the events
+/// can't be tied to an account.
+pub async fn upload(
+    client: &reqwest::Client,
+    api_url: &str,
+    batch: &TelemetryBatch,
+) -> Result<(), Error> {
+    let url = format!("{}/api/telemetry", api_url.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .timeout(UPLOAD_TIMEOUT)
+        .json(batch)
+        .send()
+        .await
+        .map_err(|e| Error::network("Telemetry upload failed", e))?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::from_status(status.as_u16(), body));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn telemetry() -> Telemetry {
+        let path = std::env::temp_dir()
+            .join(format!("exemem-telemetry-{}", uuid::Uuid::new_v4()))
+            .join("telemetry.json");
+        open(path)
+    }
+
+    fn open(path: PathBuf) -> Telemetry {
+        Telemetry {
+            store: JsonStore::load_from(path, "telemetry").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_records_only_feature_duration_and_error_kind() {
+        let mut telemetry = telemetry();
+        telemetry.record("search", Duration::from_millis(42), None).unwrap();
+        let error = Error::Validation("/home/alice/secret.pdf is too large".to_string());
+        telemetry.record("scan", Duration::from_secs(2), Some(error.kind())).unwrap();
+
+        let batch = telemetry.batch();
+        assert_eq!(batch.events.len(), 2);
+        assert_eq!(batch.events[0].duration_ms, 42);
+        assert_eq!(batch.events[1].error.as_deref(), Some("validation"));
+        assert_eq!(batch.events[1].hour % 3600, 0);
+        assert!(!serde_json::to_string(&batch).unwrap().contains("secret.pdf"));
+    }
+
+    #[test]
+    fn test_acknowledge_clear_and_persistence() {
+        let mut telemetry = telemetry();
+        for _ in 0..3 {
+            telemetry.record("query", Duration::ZERO, None).unwrap();
+        }
+        telemetry.acknowledge(2).unwrap();
+        let reloaded = open(telemetry.store.path().to_path_buf());
+        assert_eq!(reloaded.batch().events.len(), 1);
+        assert_eq!(reloaded.batch().install_id, telemetry.batch().install_id);
+
+        let install_id = telemetry.batch().install_id;
+        telemetry.clear().unwrap();
+        assert!(telemetry.is_empty());
+        assert_ne!(telemetry.batch().install_id, install_id);
+    }
+
+    #[test]
+    fn test_buffer_is_capped() {
+        let mut telemetry = telemetry();
+        telemetry
+            .store
+            .update(|data| {
+                data.events = vec![
+                    TelemetryEvent {
+                        feature: "query".to_string(),
+                        hour: 0,
+                        duration_ms: 0,
+                        error: None,
+                    };
+                    MAX_EVENTS + 5
+                ]
+            })
+            .unwrap();
+        telemetry.record("search", Duration::ZERO, None).unwrap();
+        let events = &telemetry.store.get().events;
+        assert_eq!(events.len(), MAX_EVENTS);
+        assert_eq!(events.last().unwrap().feature, "search");
+        assert_eq!(telemetry.batch().events.len(), BATCH_SIZE);
+    }
+}
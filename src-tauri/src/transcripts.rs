@@ -0,0 +1,468 @@
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::logs::format_timestamp;
+use crate::paths;
+
+/// Answers in query history are cut to this many characters
+const HISTORY_ANSWER_CHARS: usize = 280;
+
+/// History entries returned when no limit is given
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// Session titles (their first question) are cut to this many characters
+const SESSION_TITLE_CHARS: usize = 80;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS turns (
+    id INTEGER PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    question TEXT NOT NULL,
+    answer TEXT NOT NULL,
+    context_used INTEGER NOT NULL DEFAULT 0,
+    result_count INTEGER,
+    created_at INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS turns_session ON turns (session_id, id);
+CREATE VIRTUAL TABLE IF NOT EXISTS turns_fts USING fts5(
+    question, answer, content='turns', content_rowid='id'
+);
+CREATE TRIGGER IF NOT EXISTS turns_ai AFTER INSERT ON turns BEGIN
+    INSERT INTO turns_fts (rowid, question, answer) VALUES (new.id, new.question, new.answer);
+END;
+CREATE TRIGGER IF NOT EXISTS turns_ad AFTER DELETE ON turns BEGIN
+    INSERT INTO turns_fts (turns_fts, rowid, question, answer)
+        VALUES ('delete', old.id, old.question, old.answer);
+END;
+";
+
+/// One question and the answer it got.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TranscriptTurn {
+    pub session_id: String,
+    /// "query" for the opening question of a session, "followup" after that
+    pub kind: String,
+    pub question: String,
+    pub answer: String,
+    pub context_used: bool,
+    /// Number of records the query returned, for "query" turns
+    pub result_count: Option<usize>,
+    /// Seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptHit {
+    pub turn: TranscriptTurn,
+    /// Matching text with the search terms wrapped in [brackets]
+    pub snippet: String,
+}
+
+/// A past question, for browsing query history.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct QueryHistoryEntry {
+    pub id: i64,
+    pub session_id: String,
+    /// "query" or "followup", as in [`TranscriptTurn`]
+    pub kind: String,
+    pub question: String,
+    /// The answer, truncated; the full text is in the session transcript
+    pub answer: String,
+    pub result_count: Option<usize>,
+    pub created_at: u64,
+}
+
+/// A conversation, for picking one to resume.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SessionSummary {
+    pub session_id: String,
+    /// The opening question, truncated
+    pub title: String,
+    pub turns: usize,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    pub last_active_at: u64,
+}
+
+/// Criteria for browsing query history. Unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueryHistoryFilter {
+    /// Asked at or after this time, seconds since the Unix epoch
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Asked before this time, seconds since the Unix epoch
+    #[serde(default)]
+    pub until: Option<u64>,
+    /// Words that must all appear in the question or answer
+    #[serde(default)]
+    pub search: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// Local copy of every chat exchange, searchable without the backend.
+pub struct TranscriptStore {
+    conn: Connection,
+}
+
+impl TranscriptStore {
+    pub fn load() -> Result<Self, String> {
+        let dir = paths::data_dir()?;
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create data dir: {}", e))?;
+        Self::open(&dir.join("transcripts.db"))
+    }
+
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let conn = Connection::open(path)
+            .map_err(|e| format!("Failed to open transcript store: {}", e))?;
+        Self::init(conn)
+    }
+
+    /// In-memory store, used when the database can't be opened.
+    pub fn in_memory() -> Result<Self, String> {
+        let conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open transcript store: {}", e))?;
+        Self::init(conn)
+    }
+
+    fn init(conn: Connection) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA)
+            .map_err(|e| format!("Failed to set up transcript store: {}", e))?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&self, turn: &TranscriptTurn) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO turns (session_id, kind, question, answer, context_used, result_count, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    turn.session_id,
+                    turn.kind,
+                    turn.question,
+                    turn.answer,
+                    turn.context_used,
+                    turn.result_count.map(|n| n as i64),
+                    turn.created_at as i64,
+                ],
+            )
+            .map_err(|e| format!("Failed to save transcript: {}", e))?;
+        Ok(())
+    }
+
+    /// Every turn of a session, oldest first.
+    pub fn session(&self, session_id: &str) -> Result<Vec<TranscriptTurn>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT session_id, kind, question, answer, context_used, result_count, created_at
+                 FROM turns WHERE session_id = ?1 ORDER BY id",
+            )
+            .map_err(|e| format!("Failed to read transcript: {}", e))?;
+        let turns = stmt
+            .query_map(params![session_id], row_to_turn)
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to read transcript: {}", e))?;
+        Ok(turns)
+    }
+
+    /// Turns whose question or answer contains every word of `term`, best
+    /// matches first.
+    pub fn search(&self, term: &str, limit: usize) -> Result<Vec<TranscriptHit>, String> {
+        let Some(query) = fts_query(term) else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT t.session_id, t.kind, t.question, t.answer, t.context_used, t.result_count,
+                        t.created_at, snippet(turns_fts, -1, '[', ']', '…', 16)
+                 FROM turns_fts JOIN turns t ON t.id = turns_fts.rowid
+                 WHERE turns_fts MATCH ?1
+                 ORDER BY bm25(turns_fts), t.id DESC
+                 LIMIT ?2",
+            )
+            .map_err(|e| format!("Failed to search transcripts: {}", e))?;
+        let hits = stmt
+            .query_map(params![query, limit as i64], |row| {
+                Ok(TranscriptHit {
+                    turn: row_to_turn(row)?,
+                    snippet: row.get(7)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to search transcripts: {}", e))?;
+        Ok(hits)
+    }
+
+    /// Past questions matching `filter`, newest first.
+    pub fn history(&self, filter: &QueryHistoryFilter) -> Result<Vec<QueryHistoryEntry>, String> {
+        let search = match filter.search.as_deref().map(str::trim) {
+            Some(term) if !term.is_empty() => fts_query(term),
+            _ => None,
+        };
+        let sql = format!(
+            "SELECT id, session_id, kind, question, answer, result_count, created_at
+             FROM turns
+             WHERE (?1 IS NULL OR created_at >= ?1)
+               AND (?2 IS NULL OR created_at < ?2)
+               {}
+             ORDER BY created_at DESC, id DESC
+             LIMIT ?3",
+            if search.is_some() {
+                "AND id IN (SELECT rowid FROM turns_fts WHERE turns_fts MATCH ?4)"
+            } else {
+                "AND ?4 IS NULL"
+            }
+        );
+        let mut stmt = self
+            .conn
+            .prepare(&sql)
+            .map_err(|e| format!("Failed to read query history: {}", e))?;
+        let entries = stmt
+            .query_map(
+                params![
+                    filter.since.map(|t| t as i64),
+                    filter.until.map(|t| t as i64),
+                    filter.limit.unwrap_or(DEFAULT_HISTORY_LIMIT) as i64,
+                    search,
+                ],
+                |row| {
+                    Ok(QueryHistoryEntry {
+                        id: row.get(0)?,
+                        session_id: row.get(1)?,
+                        kind: row.get(2)?,
+                        question: row.get(3)?,
+                        answer: truncate(&row.get::<_, String>(4)?, HISTORY_ANSWER_CHARS),
+                        result_count: row.get::<_, Option<i64>>(5)?.map(|n| n as usize),
+                        created_at: row.get::<_, i64>(6)? as u64,
+                    })
+                },
+            )
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to read query history: {}", e))?;
+        Ok(entries)
+    }
+
+    /// Sessions with at least one recorded turn, most recently active first.
+    pub fn sessions(&self, limit: usize) -> Result<Vec<SessionSummary>, String> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT session_id,
+                        (SELECT question FROM turns first
+                         WHERE first.session_id = t.session_id ORDER BY id LIMIT 1),
+                        COUNT(*), MIN(created_at), MAX(created_at)
+                 FROM turns t
+                 GROUP BY session_id
+                 ORDER BY MAX(created_at) DESC, MAX(id) DESC
+                 LIMIT ?1",
+            )
+            .map_err(|e| format!("Failed to list sessions: {}", e))?;
+        let sessions = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok(SessionSummary {
+                    session_id: row.get(0)?,
+                    title: truncate(&row.get::<_, String>(1)?, SESSION_TITLE_CHARS),
+                    turns: row.get::<_, i64>(2)? as usize,
+                    started_at: row.get::<_, i64>(3)? as u64,
+                    last_active_at: row.get::<_, i64>(4)? as u64,
+                })
+            })
+            .and_then(|rows| rows.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| format!("Failed to list sessions: {}", e))?;
+        Ok(sessions)
+    }
+
+    /// Delete every turn of a session, returning how many there were.
+    pub fn delete_session(&self, session_id: &str) -> Result<usize, String> {
+        self.conn
+            .execute("DELETE FROM turns WHERE session_id = ?1", params![session_id])
+            .map_err(|e| format!("Failed to delete session: {}", e))
+    }
+
+    /// Delete the given history entries, or all of them when `ids` is None.
+    /// Returns how many were removed.
+    pub fn delete_history(&self, ids: Option<&[i64]>) -> Result<usize, String> {
+        let deleted = match ids {
+            None => self.conn.execute("DELETE FROM turns", []),
+            Some(ids) => ids.iter().try_fold(0, |total, id| {
+                self.conn
+                    .execute("DELETE FROM turns WHERE id = ?1", params![id])
+                    .map(|n| total + n)
+            }),
+        };
+        deleted.map_err(|e| format!("Failed to delete query history: {}", e))
+    }
+}
+
+/// A session's turns as a Markdown chat thread, for sharing or archiving.
+pub fn to_markdown(turns: &[TranscriptTurn]) -> String {
+    let time = |secs: u64| format!("{} UTC", format_timestamp(secs * 1000));
+    let mut out = String::from("# Exemem conversation\n\n");
+    if let Some(first) = turns.first() {
+        out.push_str(&format!(
+            "Session `{}`, started {}\n\n",
+            first.session_id,
+            time(first.created_at)
+        ));
+    }
+    for turn in turns {
+        out.push_str(&format!("---\n\n**You** ({})\n\n", time(turn.created_at)));
+        out.push_str(turn.question.trim());
+        out.push_str("\n\n**Exemem**");
+        match turn.result_count {
+            Some(1) => out.push_str(" (1 result)"),
+            Some(n) => out.push_str(&format!(" ({} results)", n)),
+            None => {}
+        }
+        out.push_str("\n\n");
+        out.push_str(turn.answer.trim());
+        out.push_str("\n\n");
+    }
+    out
+}
+
+/// `text` cut to at most `max` characters, marked with an ellipsis.
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+fn row_to_turn(row: &rusqlite::Row) -> rusqlite::Result<TranscriptTurn> {
+    Ok(TranscriptTurn {
+        session_id: row.get(0)?,
+        kind: row.get(1)?,
+        question: row.get(2)?,
+        answer: row.get(3)?,
+        context_used: row.get(4)?,
+        result_count: row.get::<_, Option<i64>>(5)?.map(|n| n as usize),
+        created_at: row.get::<_, i64>(6)? as u64,
+    })
+}
+
+/// Quote each word so user input is never parsed as FTS query syntax.
+fn fts_query(term: &str) -> Option<String> {
+    let words: Vec<String> = term
+        .split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(session: &str, question: &str, answer: &str, at: u64) -> TranscriptTurn {
+        TranscriptTurn {
+            session_id: session.to_string(),
+            kind: "followup".to_string(),
+            question: question.to_string(),
+            answer: answer.to_string(),
+            context_used: true,
+            result_count: None,
+            created_at: at,
+        }
+    }
+
+    #[test]
+    fn test_search_finds_answers() {
+        let store = TranscriptStore::in_memory().unwrap();
+        store
+            .record(&turn("s1", "When is the dentist?", "Your appointment is on March 3rd.", 1))
+            .unwrap();
+        store
+            .record(&turn("s2", "Flight number?", "You fly UA 123 to Denver.", 2))
+            .unwrap();
+
+        let hits = store.search("appointment march", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].turn.session_id, "s1");
+        assert!(hits[0].snippet.contains("[appointment]"));
+
+        assert!(store.search("nothing here", 10).unwrap().is_empty());
+        assert!(store.search("   ", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_escapes_query_syntax() {
+        let store = TranscriptStore::in_memory().unwrap();
+        store
+            .record(&turn("s1", "what about \"quotes\" AND NOT stuff", "ok", 1))
+            .unwrap();
+        let hits = store.search("\"quotes AND NOT", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+    }
+
+    #[test]
+    fn test_history_filters_and_deletes() {
+        let store = TranscriptStore::in_memory().unwrap();
+        store.record(&turn("s1", "dentist?", "March 3rd", 100)).unwrap();
+        store.record(&turn("s2", "flight?", &"x".repeat(1000), 200)).unwrap();
+        store.record(&turn("s3", "dentist again?", "April", 300)).unwrap();
+
+        let all = store.history(&QueryHistoryFilter::default()).unwrap();
+        let questions: Vec<_> = all.iter().map(|e| e.question.as_str()).collect();
+        assert_eq!(questions, vec!["dentist again?", "flight?", "dentist?"]);
+        assert_eq!(all[1].answer.chars().count(), HISTORY_ANSWER_CHARS + 1);
+
+        let filter = QueryHistoryFilter {
+            search: Some("dentist".to_string()),
+            until: Some(300),
+            ..Default::default()
+        };
+        let found = store.history(&filter).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].session_id, "s1");
+
+        assert_eq!(store.delete_history(Some(&[found[0].id])).unwrap(), 1);
+        assert!(store.search("March", 10).unwrap().is_empty());
+        assert_eq!(store.delete_history(None).unwrap(), 2);
+        assert!(store.history(&QueryHistoryFilter::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sessions_newest_first() {
+        let store = TranscriptStore::in_memory().unwrap();
+        store.record(&turn("s1", "first question", "a", 1)).unwrap();
+        store.record(&turn("s2", "other", "b", 2)).unwrap();
+        store.record(&turn("s1", "second question", "c", 3)).unwrap();
+
+        let sessions = store.sessions(10).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].session_id, "s1");
+        assert_eq!(sessions[0].title, "first question");
+        assert_eq!((sessions[0].turns, sessions[0].started_at, sessions[0].last_active_at), (2, 1, 3));
+
+        assert_eq!(store.delete_session("s1").unwrap(), 2);
+        assert!(store.session("s1").unwrap().is_empty());
+        assert_eq!(store.sessions(10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_markdown_thread() {
+        let mut first = turn("s1", "Where did I go?", "Lisbon", 0);
+        first.result_count = Some(2);
+        let md = to_markdown(&[first, turn("s1", "When?", "In May", 61)]);
+        assert!(md.starts_with("# Exemem conversation\n\nSession `s1`, started 1970-01-01 00:00:00 UTC"));
+        assert!(md.contains("**You** (1970-01-01 00:01:01 UTC)\n\nWhen?\n\n**Exemem**\n\nIn May\n"));
+        assert!(md.contains("**Exemem** (2 results)\n\nLisbon"));
+    }
+
+    #[test]
+    fn test_session_in_order() {
+        let store = TranscriptStore::in_memory().unwrap();
+        store.record(&turn("s1", "first", "a", 1)).unwrap();
+        store.record(&turn("s2", "other", "b", 2)).unwrap();
+        store.record(&turn("s1", "second", "c", 3)).unwrap();
+
+        let session = store.session("s1").unwrap();
+        let questions: Vec<_> = session.iter().map(|t| t.question.as_str()).collect();
+        assert_eq!(questions, vec!["first", "second"]);
+    }
+}
@@ -0,0 +1,183 @@
+//! What the tray icon, its tooltip and the status lines of its menu show.
+//! Kept apart from the Tauri wiring so the rules can be tested.
+
+use tauri::image::Image;
+
+/// Id of the tray icon built in `run`, for `AppHandle::tray_by_id`. Not
+/// "main", which Tauri may give the icon from `tauri.conf.json`
+pub const TRAY_ID: &str = "exemem-status";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayState {
+    Idle,
+    Syncing { remaining: usize },
+    Paused,
+    /// Some uploads failed and are waiting for a retry
+    Error,
+    Offline,
+}
+
+/// The numbers the tray is drawn from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraySummary {
+    pub watching: bool,
+    pub online: bool,
+    /// Uploads in progress or waiting for a slot
+    pub uploading: usize,
+    /// Uploads held for quiet hours, requests queued while offline and
+    /// watched files waiting for approval
+    pub pending: usize,
+    /// Failed uploads waiting for a retry
+    pub failed: usize,
+    /// Newest successful upload, seconds since the Unix epoch
+    pub last_sync: Option<u64>,
+}
+
+impl TraySummary {
+    /// Being offline trumps everything, since nothing can sync; work in
+    /// progress is shown before failures from earlier.
+    pub fn state(&self) -> TrayState {
+        if !self.online {
+            TrayState::Offline
+        } else if self.uploading > 0 {
+            TrayState::Syncing {
+                remaining: self.uploading,
+            }
+        } else if self.failed > 0 {
+            TrayState::Error
+        } else if !self.watching {
+            TrayState::Paused
+        } else {
+            TrayState::Idle
+        }
+    }
+
+    pub fn tooltip(&self) -> String {
+        let status = match self.state() {
+            TrayState::Idle => "Up to date".to_string(),
+            TrayState::Syncing { remaining: 1 } => "Syncing 1 file".to_string(),
+            TrayState::Syncing { remaining } => format!("Syncing {} files", remaining),
+            TrayState::Paused => "Paused".to_string(),
+            TrayState::Error => format!("{} failed {}", self.failed, plural(self.failed, "upload")),
+            TrayState::Offline => "Offline".to_string(),
+        };
+        format!("Exemem Client: {}", status)
+    }
+
+    pub fn last_sync_label(&self, now: u64) -> String {
+        match self.last_sync {
+            None => "Last sync: never".to_string(),
+            Some(at) => format!("Last sync: {}", ago(now.saturating_sub(at))),
+        }
+    }
+
+    pub fn counts_label(&self) -> String {
+        format!("Pending: {} · Failed: {}", self.pending, self.failed)
+    }
+}
+
+fn plural(n: usize, word: &str) -> String {
+    if n == 1 {
+        word.to_string()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+fn ago(secs: u64) -> String {
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86_399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} d ago", secs / 86_400),
+    }
+}
+
+/// `base` marked for `state`: greyed out when paused or offline, with a
+/// coloured dot in the corner while syncing, after failures and offline.
+pub fn icon_for(base: &Image<'_>, state: TrayState) -> Image<'static> {
+    let (width, height) = (base.width(), base.height());
+    let mut rgba = base.rgba().to_vec();
+    if matches!(state, TrayState::Paused | TrayState::Offline) {
+        grayscale(&mut rgba);
+    }
+    let dot = match state {
+        TrayState::Syncing { .. } => Some([0x25, 0x63, 0xeb]),
+        TrayState::Error => Some([0xdc, 0x26, 0x26]),
+        TrayState::Offline => Some([0xd9, 0x77, 0x06]),
+        TrayState::Idle | TrayState::Paused => None,
+    };
+    if let Some(color) = dot {
+        draw_dot(&mut rgba, width, height, color);
+    }
+    Image::new_owned(rgba, width, height)
+}
+
+fn grayscale(rgba: &mut [u8]) {
+    for px in rgba.chunks_exact_mut(4) {
+        let luma = (px[0] as u32 * 299 + px[1] as u32 * 587 + px[2] as u32 * 114) / 1000;
+        px[..3].fill(luma as u8);
+    }
+}
+
+/// Fill a circle a quarter of the icon wide in its bottom-right corner.
+fn draw_dot(rgba: &mut [u8], width: u32, height: u32, color: [u8; 3]) {
+    let radius = (width.min(height) / 4).max(1) as i64;
+    let (cx, cy) = (width as i64 - radius - 1, height as i64 - radius - 1);
+    for y in (cy - radius).max(0)..=(cy + radius).min(height as i64 - 1) {
+        for x in (cx - radius).max(0)..=(cx + radius).min(width as i64 - 1) {
+            if (x - cx).pow(2) + (y - cy).pow(2) <= radius.pow(2) {
+                let i = ((y * width as i64 + x) * 4) as usize;
+                rgba[i..i + 4].copy_from_slice(&[color[0], color[1], color[2], 0xff]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> TraySummary {
+        TraySummary {
+            watching: true,
+            online: true,
+            uploading: 0,
+            pending: 0,
+            failed: 0,
+            last_sync: None,
+        }
+    }
+
+    #[test]
+    fn test_state_priority() {
+        assert_eq!(summary().state(), TrayState::Idle);
+        assert_eq!(TraySummary { watching: false, ..summary() }.state(), TrayState::Paused);
+        assert_eq!(TraySummary { failed: 2, watching: false, ..summary() }.state(), TrayState::Error);
+        assert_eq!(
+            TraySummary { uploading: 3, failed: 2, ..summary() }.state(),
+            TrayState::Syncing { remaining: 3 }
+        );
+        assert_eq!(TraySummary { online: false, uploading: 3, ..summary() }.state(), TrayState::Offline);
+    }
+
+    #[test]
+    fn test_labels() {
+        let s = TraySummary { uploading: 1, last_sync: Some(1_000), ..summary() };
+        assert_eq!(s.tooltip(), "Exemem Client: Syncing 1 file");
+        assert_eq!(s.last_sync_label(1_030), "Last sync: just now");
+        assert_eq!(s.last_sync_label(1_000 + 7_200), "Last sync: 2 h ago");
+        assert_eq!(summary().last_sync_label(5), "Last sync: never");
+        assert_eq!(TraySummary { failed: 1, ..summary() }.tooltip(), "Exemem Client: 1 failed upload");
+    }
+
+    #[test]
+    fn test_icon_dot_marks_corner_only() {
+        let base = Image::new_owned(vec![0x80; 16 * 16 * 4], 16, 16);
+        let icon = icon_for(&base, TrayState::Error);
+        let pixel = |x: usize, y: usize| icon.rgba()[(y * 16 + x) * 4..(y * 16 + x) * 4 + 4].to_vec();
+        assert_eq!(pixel(11, 11), [0xdc, 0x26, 0x26, 0xff]);
+        assert_eq!(pixel(2, 2), [0x80; 4]);
+        assert_eq!(icon_for(&base, TrayState::Idle).rgba(), base.rgba());
+    }
+}
@@ -0,0 +1,373 @@
+//! Terminal dashboard for `exemem-cli tui`: what the watcher did, what's
+//! waiting to upload, recent failures, and a query prompt, for machines
+//! without a desktop.
+//!
+//! Everything but the query answer is read from the state shared with the
+//! app, so the dashboard shows the desktop app's or a `watch --daemon`'s
+//! uploads as well as its own. With `watch` set it also runs the watcher
+//! itself, and shows its events as they happen.
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Wrap};
+use ratatui::Frame;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::config::AppConfig;
+use crate::dead_letter::{self, FailedUpload};
+use crate::error::Error;
+use crate::headless::{self, WatchRecord, WatchUpdate};
+use crate::ledger::{self, HistoryFilter, LedgerEntry};
+use crate::query::{QueryClient, RunQueryResponse};
+use crate::status::{self, ClientStatus};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+/// Activity lines kept for the watcher pane
+const MAX_ACTIVITY: usize = 200;
+/// Results shown under a query answer
+const MAX_ANSWER_RESULTS: usize = 20;
+
+/// What a key press asks for.
+enum Action {
+    None,
+    Quit,
+    Ask(String),
+}
+
+struct Dashboard {
+    config: AppConfig,
+    /// Running the watcher in this process
+    watching: bool,
+    activity: VecDeque<String>,
+    status: ClientStatus,
+    failed: Vec<FailedUpload>,
+    input: String,
+    answer: Vec<String>,
+    asking: bool,
+}
+
+impl Dashboard {
+    fn new(config: AppConfig, watching: bool) -> Self {
+        let mut dashboard = Self {
+            status: status::local_status(&config),
+            config,
+            watching,
+            activity: VecDeque::new(),
+            failed: Vec::new(),
+            input: String::new(),
+            answer: vec!["Type a question below and press Enter".to_string()],
+            asking: false,
+        };
+        dashboard.refresh();
+        dashboard
+    }
+
+    /// Re-read the shared state.
+    fn refresh(&mut self) {
+        self.status = status::local_status(&self.config);
+        self.failed = dead_letter::list_failed_uploads().unwrap_or_else(|e| {
+            log::warn!("Failed to read failed uploads: {}", e);
+            Vec::new()
+        });
+        // The in-process watcher reports its own activity as it happens
+        if !self.watching {
+            let filter = HistoryFilter {
+                limit: Some(MAX_ACTIVITY),
+                ..Default::default()
+            };
+            let entries = ledger::upload_history(&filter).unwrap_or_default();
+            self.activity = entries.iter().map(describe_entry).collect();
+        }
+    }
+
+    fn push_activity(&mut self, line: String) {
+        self.activity.push_front(line);
+        self.activity.truncate(MAX_ACTIVITY);
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Esc => Action::Quit,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
+            KeyCode::Char(c) => {
+                self.input.push(c);
+                Action::None
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+                Action::None
+            }
+            KeyCode::Enter if !self.asking && !self.input.trim().is_empty() => {
+                self.asking = true;
+                self.answer = vec![format!("Asking: {}", self.input.trim())];
+                Action::Ask(std::mem::take(&mut self.input).trim().to_string())
+            }
+            _ => Action::None,
+        }
+    }
+
+    fn show_answer(&mut self, result: Result<RunQueryResponse, Error>) {
+        self.asking = false;
+        self.answer = match result {
+            Ok(response) => {
+                let mut lines = vec![
+                    response.ai_interpretation.clone(),
+                    String::new(),
+                    format!("{} results", response.total_results.max(response.raw_results.len())),
+                ];
+                lines.extend(
+                    response
+                        .raw_results
+                        .iter()
+                        .take(MAX_ANSWER_RESULTS)
+                        .map(|r| format!("• {}", r)),
+                );
+                lines
+            }
+            Err(e) => vec![format!("Query failed: {}", crate::redact::redact(&e.to_string()))],
+        };
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let [panes, prompt] =
+            Layout::vertical([Constraint::Min(6), Constraint::Length(3)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)]).areas(panes);
+        let [activity, errors] =
+            Layout::vertical([Constraint::Percentage(65), Constraint::Percentage(35)]).areas(left);
+        let [queue, answer] =
+            Layout::vertical([Constraint::Length(8), Constraint::Min(3)]).areas(right);
+
+        let title = if self.watching { " Watcher (running here) " } else { " Uploads " };
+        let items: Vec<ListItem> = self.activity.iter().map(|l| ListItem::new(l.as_str())).collect();
+        frame.render_widget(List::new(items).block(Block::bordered().title(title)), activity);
+
+        frame.render_widget(
+            Paragraph::new(self.queue_lines()).block(Block::bordered().title(" Queue ")),
+            queue,
+        );
+
+        let items: Vec<ListItem> = self
+            .failed
+            .iter()
+            .map(|f| {
+                ListItem::new(format!(
+                    "{} ({}x): {}",
+                    f.filename,
+                    f.attempts,
+                    f.error.as_deref().unwrap_or("unknown error")
+                ))
+                .style(Style::default().fg(Color::Red))
+            })
+            .collect();
+        frame.render_widget(
+            List::new(items).block(Block::bordered().title(" Recent errors ")),
+            errors,
+        );
+
+        let lines: Vec<Line> = self.answer.iter().map(|l| Line::from(l.as_str())).collect();
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title(" Answer ")),
+            answer,
+        );
+
+        let hint = if self.asking { " Query (waiting for the answer…) " } else { " Query (Enter to ask, Esc to quit) " };
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("> ", Style::default().add_modifier(Modifier::BOLD)),
+                Span::raw(self.input.as_str()),
+            ]))
+            .block(Block::bordered().title(hint)),
+            prompt,
+        );
+        frame.set_cursor_position((
+            prompt.x + 3 + self.input.chars().count() as u16,
+            prompt.y + 1,
+        ));
+    }
+
+    fn queue_lines(&self) -> Vec<Line<'static>> {
+        let status = &self.status;
+        let watcher = match (&status.watcher, self.watching) {
+            (_, true) => "running in this dashboard".to_string(),
+            (Some(info), false) => format!("running (pid {}) on {}", info.pid, info.folder.display()),
+            (None, false) => "not running".to_string(),
+        };
+        let last_upload = status
+            .last_upload
+            .as_ref()
+            .map(|e| format!("{} at {}", e.filename, e.recorded_at().map(clock).unwrap_or_default()))
+            .unwrap_or_else(|| "none".to_string());
+        vec![
+            Line::from(format!("Watcher:     {}", watcher)),
+            Line::from(format!(
+                "Folder:      {}",
+                status
+                    .watched_folders
+                    .first()
+                    .map(|f| f.display().to_string())
+                    .unwrap_or_else(|| "not set".to_string())
+            )),
+            Line::from(format!("Scheduled:   {}", status.pending.scheduled_uploads)),
+            Line::from(format!("Offline:     {}", status.pending.offline_requests)),
+            Line::from(format!("Failed:      {}", status.pending.failed_uploads)),
+            Line::from(format!("Last upload: {}", last_upload)),
+        ]
+    }
+}
+
+/// UTC time of day of `secs`, as "HH:MM:SS".
+fn clock(secs: u64) -> String {
+    format!("{:02}:{:02}:{:02}", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+fn describe_entry(entry: &LedgerEntry) -> String {
+    let time = entry.recorded_at().map(clock).unwrap_or_default();
+    match &entry.error {
+        Some(error) => format!("{} failed    {}: {}", time, entry.filename, error),
+        None => format!("{} {:<11} {}", time, format!("{:?}", entry.status).to_lowercase(), entry.filename),
+    }
+}
+
+fn describe_record(record: &WatchRecord) -> String {
+    let time = clock(record.ts);
+    match &record.update {
+        WatchUpdate::Started { folder } => format!("{} watching   {}", time, folder.display()),
+        WatchUpdate::Uploaded { file, .. } => format!("{} uploaded   {}", time, file),
+        WatchUpdate::Failed { file, error, .. } => format!(
+            "{} failed     {}: {}",
+            time,
+            file,
+            error.as_deref().unwrap_or("unknown error")
+        ),
+        WatchUpdate::Skipped { file, reason, .. } => format!("{} skipped    {} ({})", time, file, reason),
+        WatchUpdate::AwaitingApproval { file, .. } => format!("{} approval?  {}", time, file),
+        WatchUpdate::Deferred { file, until, .. } => {
+            format!("{} deferred   {} until {}", time, file, clock(*until))
+        }
+        WatchUpdate::Stopped => format!("{} stopped", time),
+    }
+}
+
+/// Run the dashboard until the user quits. Times are shown in UTC.
+pub async fn run(config: AppConfig, client: QueryClient, watch: bool) -> Result<(), Error> {
+    let (event_tx, mut events) = mpsc::unbounded_channel();
+    // crossterm's reads block, so they get a thread of their own
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if event_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut terminal = ratatui::init();
+    let result = run_loop(&mut terminal, config, &client, watch, &mut events).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_loop(
+    terminal: &mut ratatui::DefaultTerminal,
+    config: AppConfig,
+    client: &QueryClient,
+    watch: bool,
+    events: &mut mpsc::UnboundedReceiver<Event>,
+) -> Result<(), Error> {
+    let mut dashboard = Dashboard::new(config.clone(), watch);
+    let mut ticks = tokio::time::interval(REFRESH_INTERVAL);
+
+    let (record_tx, mut records) = mpsc::unbounded_channel();
+    let watcher = async {
+        if !watch {
+            return std::future::pending().await;
+        }
+        headless::watch(&config, std::future::pending(), |record| {
+            let _ = record_tx.send(record);
+        })
+        .await
+    };
+    tokio::pin!(watcher);
+    let mut watcher_running = watch;
+    let mut query: Option<Pin<Box<dyn Future<Output = Result<RunQueryResponse, Error>> + '_>>> = None;
+
+    loop {
+        terminal.draw(|frame| dashboard.render(frame))?;
+        tokio::select! {
+            _ = ticks.tick() => dashboard.refresh(),
+            Some(event) = events.recv() => {
+                if let Event::Key(key) = event {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match dashboard.handle_key(key) {
+                        Action::Quit => return Ok(()),
+                        Action::Ask(text) => {
+                            let config = &config;
+                            query = Some(Box::pin(async move {
+                                client.run_query(config, &text, None, false).await
+                            }));
+                        }
+                        Action::None => {}
+                    }
+                }
+            }
+            Some(record) = records.recv() => dashboard.push_activity(describe_record(&record)),
+            result = &mut watcher, if watcher_running => {
+                watcher_running = false;
+                let message = match result {
+                    Ok(()) => "Watcher stopped".to_string(),
+                    Err(e) => format!("Watcher stopped: {}", crate::redact::redact(&e.to_string())),
+                };
+                dashboard.push_activity(message);
+            }
+            result = async { query.as_mut().unwrap().await }, if query.is_some() => {
+                query = None;
+                dashboard.show_answer(result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_records() {
+        let record = WatchRecord {
+            ts: 3600 * 25 + 61,
+            update: WatchUpdate::Deferred {
+                file: "a.pdf".to_string(),
+                category: "documents".to_string(),
+                until: 3600 * 3,
+            },
+        };
+        assert_eq!(describe_record(&record), "01:01:01 deferred   a.pdf until 03:00:00");
+    }
+
+    #[test]
+    fn test_enter_takes_the_question() {
+        let mut dashboard = Dashboard::new(AppConfig::default(), false);
+        for c in " when was my last trip? ".chars() {
+            dashboard.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        match dashboard.handle_key(KeyEvent::from(KeyCode::Enter)) {
+            Action::Ask(text) => assert_eq!(text, "when was my last trip?"),
+            _ => panic!("expected a question"),
+        }
+        assert!(dashboard.input.is_empty());
+        // A second question waits for the first answer
+        dashboard.handle_key(KeyEvent::from(KeyCode::Char('x')));
+        assert!(matches!(dashboard.handle_key(KeyEvent::from(KeyCode::Enter)), Action::None));
+        assert!(matches!(dashboard.handle_key(KeyEvent::from(KeyCode::Esc)), Action::Quit));
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+
+/// Where an upload request came from. Higher priorities are served first
+/// when the uploader is saturated.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UploadPriority {
+    /// Bulk re-ingestion work; yields to everything else.
+    Backfill,
+    /// Files picked up automatically by the folder watcher.
+    Watcher,
+    /// Files the user explicitly approved in the UI.
+    Manual,
+}
+
+struct Waiter {
+    priority: UploadPriority,
+    seq: u64,
+    tx: oneshot::Sender<UploadPermit>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority && self.seq == other.seq
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    // Max-heap: highest priority first, then oldest request (FIFO within a priority)
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct QueueState {
+    available: usize,
+    next_seq: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+/// Concurrency gate for uploads that hands out slots in priority order.
+///
+/// Works like a semaphore, except that when all slots are busy the next free
+/// slot goes to the highest-priority waiter rather than whoever polled first.
+pub struct UploadQueue {
+    state: Mutex<QueueState>,
+}
+
+/// A held upload slot. The slot is handed to the next waiter when dropped.
+pub struct UploadPermit {
+    queue: Arc<UploadQueue>,
+}
+
+impl UploadQueue {
+    pub fn new(slots: usize) -> Arc<Self> {
+        Arc::new(Self {
+            state: Mutex::new(QueueState {
+                available: slots,
+                next_seq: 0,
+                waiting: BinaryHeap::new(),
+            }),
+        })
+    }
+
+    /// Wait for an upload slot.
+    pub async fn acquire(self: &Arc<Self>, priority: UploadPriority) -> UploadPermit {
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            if state.available > 0 && state.waiting.is_empty() {
+                state.available -= 1;
+                return UploadPermit {
+                    queue: self.clone(),
+                };
+            }
+
+            let (tx, rx) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiting.push(Waiter { priority, seq, tx });
+            rx
+        };
+
+        // The queue owns every sender until it hands over a permit, and the
+        // queue outlives this call (we hold an Arc), so this cannot fail.
+        rx.await.expect("upload queue dropped a waiter")
+    }
+
+    /// Number of requests currently waiting for a slot.
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().waiting.len()
+    }
+
+    fn release(self: &Arc<Self>) {
+        let mut state = self.state.lock().unwrap();
+        let mut permit = UploadPermit {
+            queue: self.clone(),
+        };
+
+        // Hand the slot to the best waiter still listening. Waiters whose
+        // acquire() future was dropped give the permit straight back.
+        while let Some(waiter) = state.waiting.pop() {
+            match waiter.tx.send(permit) {
+                Ok(()) => return,
+                Err(returned) => permit = returned,
+            }
+        }
+
+        // Nobody waiting: return the slot without re-entering release().
+        std::mem::forget(permit);
+        state.available += 1;
+    }
+}
+
+impl Drop for UploadPermit {
+    fn drop(&mut self) {
+        self.queue.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_acquire_within_capacity() {
+        let queue = UploadQueue::new(2);
+        let _a = queue.acquire(UploadPriority::Watcher).await;
+        let _b = queue.acquire(UploadPriority::Watcher).await;
+        assert_eq!(queue.pending(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_manual_jumps_ahead_of_watcher() {
+        let queue = UploadQueue::new(1);
+        let held = queue.acquire(UploadPriority::Watcher).await;
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for priority in [
+            UploadPriority::Backfill,
+            UploadPriority::Watcher,
+            UploadPriority::Manual,
+        ] {
+            let q = queue.clone();
+            let o = order.clone();
+            handles.push(tokio::spawn(async move {
+                let _permit = q.acquire(priority).await;
+                o.lock().unwrap().push(priority);
+            }));
+            // Make sure each waiter is enqueued before the next one
+            while queue.pending() < handles.len() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+
+        drop(held);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![
+                UploadPriority::Manual,
+                UploadPriority::Watcher,
+                UploadPriority::Backfill
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_does_not_leak_slot() {
+        let queue = UploadQueue::new(1);
+        let held = queue.acquire(UploadPriority::Watcher).await;
+
+        let q = queue.clone();
+        let waiter = tokio::spawn(async move {
+            let _permit = q.acquire(UploadPriority::Manual).await;
+        });
+        while queue.pending() < 1 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        waiter.abort();
+        let _ = waiter.await;
+
+        drop(held);
+        let _again = tokio::time::timeout(
+            Duration::from_secs(1),
+            queue.acquire(UploadPriority::Backfill),
+        )
+        .await
+        .expect("slot should be free again");
+    }
+}
@@ -1,16 +1,241 @@
-use reqwest::Client;
+use reqwest::header::{CONTENT_LENGTH, RETRY_AFTER};
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
 use std::path::Path;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::Semaphore;
-use tokio::time::sleep;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
 use uuid::Uuid;
 
+use crate::compression;
 use crate::config::AppConfig;
+use crate::direct_s3::DirectS3Uploader;
+use crate::error::Error;
+use crate::presigned::PresignedUrlResponse;
+use crate::redact::redact;
+use crate::retry::{self, Retry, RetryError, RetryPolicy};
+use crate::scanner::FileRecommendation;
+use crate::server_error::{Locale, ServerErrorCode};
+use crate::signing::SendSigned;
+use crate::stats::{UploadStats, UploadStatsReport};
+use crate::upload_queue::{UploadPriority, UploadQueue};
 
 /// Max concurrent uploads
-const MAX_CONCURRENT_UPLOADS: usize = 3;
+pub const MAX_CONCURRENT_UPLOADS: usize = 3;
+
+/// Longest a single upload, ingest or progress request may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Backoff used for 429/503 responses without a usable Retry-After header
+const DEFAULT_BUSY_DELAY: Duration = Duration::from_secs(5);
+
+/// Cap on how long a single Retry-After is honored
+const MAX_BUSY_DELAY: Duration = Duration::from_secs(300);
+
+/// Three tries per request; busy responses don't use up attempts, but stop
+/// waiting after ten of them
+const UPLOAD_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 3,
+    base_delay: Duration::from_millis(500),
+    max_busy_waits: 10,
+};
+
+/// Lifetime assumed for presigned URLs when the server doesn't say
+const DEFAULT_PRESIGNED_URL_TTL: Duration = Duration::from_secs(900);
+
+/// Treat a presigned URL as expired this long before it actually is, so a
+/// PUT that starts just in time doesn't race the deadline
+const PRESIGNED_URL_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
+
+/// How many fresh presigned URLs to request for one upload
+const MAX_URL_REFRESHES: u32 = 2;
+
+/// Failure of a single request to the ingestion API or S3.
+#[derive(Debug, Clone)]
+pub enum RequestError {
+    /// The server answered 429 or 503 and asked us to come back later.
+    ServerBusy {
+        retry_after: Duration,
+        message: String,
+    },
+    /// The server refused the request with a known error code; retrying
+    /// the same request won't help.
+    Rejected {
+        code: ServerErrorCode,
+        message: String,
+    },
+    /// The presigned URL expired before the upload went through; a fresh
+    /// URL is needed rather than another attempt with the same one.
+    UrlExpired(String),
+    /// Any other non-success response
+    Http { status: u16, message: String },
+    /// No response at all: DNS, connection, TLS or timeout
+    Network(String),
+    Failed(String),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::ServerBusy { message, .. }
+            | RequestError::Rejected { message, .. }
+            | RequestError::UrlExpired(message)
+            | RequestError::Http { message, .. }
+            | RequestError::Network(message)
+            | RequestError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for RequestError {
+    fn from(message: String) -> Self {
+        RequestError::Failed(message)
+    }
+}
+
+impl From<RequestError> for Error {
+    fn from(err: RequestError) -> Self {
+        match err {
+            RequestError::Rejected {
+                code: ServerErrorCode::DocumentTooLarge | ServerErrorCode::UnsupportedType,
+                message,
+            } => Error::Validation(message),
+            RequestError::Rejected {
+                code: ServerErrorCode::ClockSkew,
+                message,
+            } => Error::Auth(message),
+            RequestError::ServerBusy { message, .. }
+            | RequestError::Rejected { message, .. }
+            | RequestError::UrlExpired(message) => Error::Server {
+                status: None,
+                message,
+            },
+            RequestError::Http { status, message } => Error::from_status(status, message),
+            RequestError::Network(message) => Error::Network(message),
+            RequestError::Failed(message) => Error::Internal(message),
+        }
+    }
+}
+
+impl RequestError {
+    fn network(context: &str, err: reqwest::Error) -> Self {
+        RequestError::Network(format!("{}: {}", context, err))
+    }
+
+    /// Same error with `prefix` prepended to its message.
+    fn with_prefix(self, prefix: &str) -> Self {
+        let prefixed = |message: String| format!("{}: {}", prefix, message);
+        match self {
+            RequestError::ServerBusy {
+                retry_after,
+                message,
+            } => RequestError::ServerBusy {
+                retry_after,
+                message: prefixed(message),
+            },
+            RequestError::Rejected { code, message } => RequestError::Rejected {
+                code,
+                message: prefixed(message),
+            },
+            RequestError::UrlExpired(message) => RequestError::UrlExpired(prefixed(message)),
+            RequestError::Http { status, message } => RequestError::Http {
+                status,
+                message: prefixed(message),
+            },
+            RequestError::Network(message) => RequestError::Network(prefixed(message)),
+            RequestError::Failed(message) => RequestError::Failed(prefixed(message)),
+        }
+    }
+
+    /// Build an error from a non-success response, recognizing backpressure.
+    async fn from_response(resp: Response, context: &str) -> Self {
+        let status = resp.status();
+        let retry_after = busy_delay(&resp);
+        let body = resp.text().await.unwrap_or_default();
+        let code = ServerErrorCode::from_body(&body);
+        let message = format!("{} ({}): {}", context, status, body);
+
+        match (retry_after, code) {
+            (Some(retry_after), _) => RequestError::ServerBusy {
+                retry_after,
+                message,
+            },
+            (None, Some(code)) if code.is_retryable() => RequestError::ServerBusy {
+                retry_after: DEFAULT_BUSY_DELAY,
+                message,
+            },
+            (None, Some(code)) => RequestError::Rejected { code, message },
+            (None, None) => RequestError::Http {
+                status: status.as_u16(),
+                message,
+            },
+        }
+    }
+}
+
+/// Backoff requested by a 429/503 response, or None for any other status.
+fn busy_delay(resp: &Response) -> Option<Duration> {
+    let status = resp.status();
+    if status != StatusCode::TOO_MANY_REQUESTS && status != StatusCode::SERVICE_UNAVAILABLE {
+        return None;
+    }
+
+    let delay = resp
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_retry_after)
+        .unwrap_or(DEFAULT_BUSY_DELAY);
+    Some(delay.min(MAX_BUSY_DELAY))
+}
+
+/// Whether an S3 error body says the signature or credentials behind a
+/// presigned URL have expired.
+fn is_expired_url_error(body: &str) -> bool {
+    body.contains("Request has expired")
+        || body.contains("<Code>ExpiredToken</Code>")
+        || body.contains("<Code>TokenRefreshRequired</Code>")
+}
+
+/// Collect provenance for a file we're about to upload. Config-level default
+/// tags are merged with any tags supplied by the caller.
+async fn build_metadata(
+    file_path: &Path,
+    file_bytes: &[u8],
+    config: &AppConfig,
+    context: FileContext,
+) -> IngestMetadata {
+    let mtime = tokio::fs::metadata(file_path)
+        .await
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs());
+
+    let mut tags = config.default_tags.clone();
+    for tag in context.tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    IngestMetadata {
+        original_path: file_path.to_string_lossy().to_string(),
+        category: context.category,
+        classification_reason: context.reason,
+        mtime,
+        sha256: format!("{:x}", Sha256::digest(file_bytes)),
+        tags,
+    }
+}
+
+/// Parse the delay-seconds form of Retry-After. HTTP-date values fall back to
+/// the default delay.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    value.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UploadResult {
@@ -19,6 +244,58 @@ pub struct UploadResult {
     pub progress_id: Option<String>,
     pub status: UploadStatus,
     pub error: Option<String>,
+    /// Known server error code behind `error`, if any
+    #[serde(default)]
+    pub error_code: Option<ServerErrorCode>,
+    /// Suggested next step for the user when the upload failed
+    #[serde(default)]
+    pub suggestion: Option<String>,
+    /// Size of the file on disk
+    #[serde(default)]
+    pub original_bytes: Option<u64>,
+    /// Bytes actually sent to S3 (smaller than original when compressed)
+    #[serde(default)]
+    pub uploaded_bytes: Option<u64>,
+    #[serde(default)]
+    pub content_encoding: Option<String>,
+    /// Provenance sent to the server with the ingest request
+    #[serde(default)]
+    pub metadata: Option<IngestMetadata>,
+}
+
+/// What the caller knows about a file beyond its path, e.g. from the scanner.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileContext {
+    pub category: Option<String>,
+    pub reason: Option<String>,
+    pub tags: Vec<String>,
+    /// The file is a batch zip the server should unpack into separate documents
+    #[serde(default)]
+    pub expand_archive: bool,
+}
+
+impl FileContext {
+    pub fn from_recommendation(rec: &FileRecommendation, tags: Vec<String>) -> Self {
+        Self {
+            category: Some(rec.category.clone()),
+            reason: Some(rec.reason.clone()),
+            tags,
+            expand_archive: false,
+        }
+    }
+}
+
+/// Provenance sidecar attached to the ingest-s3 request so the server index
+/// knows where a document came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IngestMetadata {
+    pub original_path: String,
+    pub category: Option<String>,
+    pub classification_reason: Option<String>,
+    /// Last modification time, seconds since the Unix epoch
+    pub mtime: Option<u64>,
+    pub sha256: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -28,14 +305,44 @@ pub enum UploadStatus {
     Ingesting,
     Done,
     Error,
+    /// Went through the pipeline with `dry_run` set; nothing was sent
+    DryRun,
 }
 
-#[derive(Debug, Deserialize)]
-struct PresignedUrlResponse {
-    upload_url: String,
+/// A presigned URL along with when we received it, so uploads that waited
+/// in the queue can tell whether it is still usable.
+struct PresignedUpload {
+    response: PresignedUrlResponse,
+    issued_at: Instant,
+}
+
+impl PresignedUpload {
+    fn new(response: PresignedUrlResponse) -> Self {
+        Self {
+            response,
+            issued_at: Instant::now(),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        let ttl = self
+            .response
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_PRESIGNED_URL_TTL);
+        self.issued_at.elapsed() + PRESIGNED_URL_EXPIRY_MARGIN >= ttl
+    }
+}
+
+/// Where an uploaded file ended up.
+struct StoredObject {
     s3_key: String,
-    #[allow(dead_code)]
-    s3_bucket: Option<String>,
+    bucket: String,
+    /// Set for the user's own bucket in direct mode
+    region: Option<String>,
+    uploaded_bytes: u64,
+    content_encoding: Option<&'static str>,
+    transfer: Duration,
 }
 
 #[derive(Debug, Deserialize)]
@@ -43,6 +350,19 @@ struct IngestResponse {
     progress_id: String,
 }
 
+/// Request body chunk size when streaming to S3; progress is reported per chunk
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Called with (bytes sent, total bytes) while a file is sent to S3. Starts
+/// again from zero if the PUT is retried.
+pub type UploadProgressFn = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// How often ingestion progress is polled after upload
+pub const PROGRESS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Polls before giving up on an ingestion (4 minutes at the default interval)
+pub const MAX_PROGRESS_POLLS: u32 = 120;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProgressResponse {
     pub progress_id: String,
@@ -51,47 +371,191 @@ pub struct ProgressResponse {
     pub message: Option<String>,
 }
 
+/// Stream `bytes` in chunks, reporting how much has been handed to the
+/// connection after each one.
+fn progress_body(bytes: Vec<u8>, on_progress: UploadProgressFn) -> reqwest::Body {
+    reqwest::Body::wrap_stream(progress_chunks(bytes, on_progress))
+}
+
+fn progress_chunks(
+    bytes: Vec<u8>,
+    on_progress: UploadProgressFn,
+) -> impl futures_util::Stream<Item = Result<bytes::Bytes, std::io::Error>> + Send + 'static {
+    let total = bytes.len() as u64;
+    if total == 0 {
+        // No chunks to report on, but the upload is still complete
+        on_progress(0, 0);
+    }
+    let bytes = bytes::Bytes::from(bytes);
+    let chunks = (0..bytes.len())
+        .step_by(UPLOAD_CHUNK_SIZE)
+        .map(move |start| {
+            let end = (start + UPLOAD_CHUNK_SIZE).min(bytes.len());
+            on_progress(end as u64, total);
+            Ok(bytes.slice(start..end))
+        });
+    futures_util::stream::iter(chunks)
+}
+
+/// Whether a progress status means the server has stopped working on it.
+pub fn is_terminal_status(status: &str) -> bool {
+    is_success_status(status) || status == "error" || status == "failed"
+}
+
+pub fn is_success_status(status: &str) -> bool {
+    status == "completed" || status == "done"
+}
+
+/// Counts one upload in `Uploader::active` until dropped, so cancelled
+/// uploads are uncounted too.
+struct ActiveUpload<'a>(&'a watch::Sender<usize>);
+
+impl<'a> ActiveUpload<'a> {
+    fn start(active: &'a watch::Sender<usize>) -> Self {
+        active.send_modify(|n| *n += 1);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveUpload<'_> {
+    fn drop(&mut self) {
+        self.0.send_modify(|n| *n = n.saturating_sub(1));
+    }
+}
+
 pub struct Uploader {
     client: Client,
-    semaphore: Arc<Semaphore>,
+    queue: Arc<UploadQueue>,
+    /// Uploads started and not yet finished, including those waiting for a slot
+    active: watch::Sender<usize>,
+    busy_until: Mutex<Option<Instant>>,
+    /// None without a data dir to keep them in
+    stats: Mutex<Option<UploadStats>>,
+    direct_s3: DirectS3Uploader,
 }
 
 impl Uploader {
-    pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(120))
-            .build()
-            .expect("Failed to create HTTP client");
+    /// Uploader sending through `client`, normally the app's shared one
+    /// from `http::build_client`.
+    pub fn new(client: Client) -> Self {
         Self {
             client,
-            semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_UPLOADS)),
+            queue: UploadQueue::new(MAX_CONCURRENT_UPLOADS),
+            active: watch::channel(0).0,
+            busy_until: Mutex::new(None),
+            stats: Mutex::new(
+                UploadStats::load()
+                    .or_else(|e| {
+                        log::error!("Failed to load upload stats, starting empty: {}", e);
+                        UploadStats::empty()
+                    })
+                    .map_err(|e| log::error!("Not keeping upload stats: {}", e))
+                    .ok(),
+            ),
+            direct_s3: DirectS3Uploader::default(),
         }
     }
 
+    /// Upload volume and throughput for the last `days` days.
+    pub fn stats(&self, days: usize) -> UploadStatsReport {
+        self.stats
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|stats| stats.report(crate::ledger::now_secs(), days))
+            .unwrap_or_default()
+    }
+
+    /// Whether the server recently asked us to back off.
+    pub fn server_busy(&self) -> bool {
+        self.busy_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    fn mark_busy(&self, retry_after: Duration) {
+        *self.busy_until.lock().unwrap() = Some(Instant::now() + retry_after);
+    }
+
+    /// Number of uploads waiting for a free slot.
+    pub fn queued(&self) -> usize {
+        self.queue.pending()
+    }
+
+    /// Number of uploads in progress or waiting for a slot, updated as
+    /// they start and finish.
+    pub fn active(&self) -> watch::Receiver<usize> {
+        self.active.subscribe()
+    }
+
     pub async fn upload_and_ingest(
         &self,
         file_path: &Path,
         config: &AppConfig,
+        priority: UploadPriority,
+        context: FileContext,
+    ) -> UploadResult {
+        self.upload_and_ingest_with_progress(file_path, config, priority, context, None)
+            .await
+    }
+
+    /// Like `upload_and_ingest`, reporting bytes sent during the S3 PUT.
+    pub async fn upload_and_ingest_with_progress(
+        &self,
+        file_path: &Path,
+        config: &AppConfig,
+        priority: UploadPriority,
+        context: FileContext,
+        on_progress: Option<UploadProgressFn>,
     ) -> UploadResult {
         let filename = file_path
             .file_name()
             .map(|n| n.to_string_lossy().to_string())
             .unwrap_or_else(|| "unknown".to_string());
 
-        // Acquire semaphore permit for concurrency limiting
-        let _permit = self.semaphore.acquire().await;
+        let _active = ActiveUpload::start(&self.active);
+        // Wait for an upload slot; manual approvals are served before watcher
+        // and backfill work when all slots are busy
+        let _permit = self.queue.acquire(priority).await;
 
-        let result = self.try_upload_and_ingest(file_path, config, &filename).await;
+        let result = self
+            .try_upload_and_ingest(file_path, config, &filename, context, on_progress)
+            .await;
 
         match result {
             Ok(upload_result) => upload_result,
-            Err(err) => UploadResult {
-                filename,
-                s3_key: String::new(),
-                progress_id: None,
-                status: UploadStatus::Error,
-                error: Some(err),
-            },
+            Err(err) => {
+                // Show known server errors as a friendly message, keeping the
+                // raw backend response in the log only
+                let locale = Locale::resolve(config.locale.as_deref());
+                let (error, error_code, suggestion) = match &err {
+                    RequestError::Rejected { code, message } => {
+                        log::warn!("Upload of {} rejected: {}", filename, message);
+                        (
+                            code.message(locale).to_string(),
+                            Some(*code),
+                            Some(code.suggestion(locale).to_string()),
+                        )
+                    }
+                    _ => (err.to_string(), None, None),
+                };
+
+                UploadResult {
+                    filename,
+                    s3_key: String::new(),
+                    progress_id: None,
+                    status: UploadStatus::Error,
+                    // Failure bodies can echo presigned URLs and credentials
+                    error: Some(redact(&error)),
+                    error_code,
+                    suggestion,
+                    original_bytes: None,
+                    uploaded_bytes: None,
+                    content_encoding: None,
+                    metadata: None,
+                }
+            }
         }
     }
 
@@ -100,57 +564,199 @@ impl Uploader {
         file_path: &Path,
         config: &AppConfig,
         filename: &str,
-    ) -> Result<UploadResult, String> {
+        context: FileContext,
+        on_progress: Option<UploadProgressFn>,
+    ) -> Result<UploadResult, RequestError> {
         // Determine content type upfront so presigned URL is signed with the same type
         let content_type = mime_guess::from_path(file_path)
             .first_or_octet_stream()
             .to_string();
 
-        // Step 1: Get presigned URL (signed with our content_type)
-        let presigned = self
-            .with_retry(|| self.get_presigned_url(config, filename, &content_type))
-            .await?;
-
-        // Step 2: Upload file to S3
         let file_bytes = tokio::fs::read(file_path)
             .await
             .map_err(|e| format!("Failed to read file: {}", e))?;
+        let original_bytes = file_bytes.len() as u64;
+        let expand_archive = context.expand_archive;
+        let metadata = build_metadata(file_path, &file_bytes, config, context).await;
 
-        self.with_retry(|| {
-            self.upload_to_s3(&presigned.upload_url, file_bytes.clone(), &content_type)
-        })
-        .await?;
+        // Offer compression for large text-like files; the server decides
+        let requested_encoding = config
+            .compression
+            .algorithm_for(file_path, original_bytes);
+
+        if config.dry_run {
+            log::info!(
+                "[dry run] Would upload {} ({}, {} bytes{}) and {}; metadata: {}",
+                file_path.display(),
+                content_type,
+                original_bytes,
+                requested_encoding
+                    .map(|a| format!(", {} requested", a.content_encoding()))
+                    .unwrap_or_default(),
+                if config.auto_ingest {
+                    "trigger ingestion"
+                } else {
+                    "skip ingestion"
+                },
+                serde_json::to_string(&metadata).unwrap_or_default(),
+            );
+            return Ok(UploadResult {
+                filename: filename.to_string(),
+                s3_key: String::new(),
+                progress_id: None,
+                status: UploadStatus::DryRun,
+                error: None,
+                error_code: None,
+                suggestion: None,
+                original_bytes: Some(original_bytes),
+                uploaded_bytes: None,
+                content_encoding: None,
+                metadata: Some(metadata),
+            });
+        }
+
+        let stored = if config.direct_s3.enabled {
+            // Steps 1 and 2, direct mode: the object goes to the user's own
+            // bucket and Exemem only learns its location
+            let s3_key = config.direct_s3.object_key(filename);
+            let put_start = Instant::now();
+            self.direct_s3
+                .put(
+                    &config.direct_s3,
+                    &s3_key,
+                    &content_type,
+                    file_bytes.clone(),
+                )
+                .await?;
+            if let Some(on_progress) = &on_progress {
+                on_progress(original_bytes, original_bytes);
+            }
+            StoredObject {
+                s3_key,
+                bucket: config.direct_s3.bucket.trim().to_string(),
+                region: Some(config.direct_s3.region.trim().to_string()),
+                uploaded_bytes: original_bytes,
+                content_encoding: None,
+                transfer: put_start.elapsed(),
+            }
+        } else {
+            // Steps 1 and 2: get a presigned URL (signed with our content_type
+            // and encoding) and upload to S3. If the URL expires before the PUT
+            // goes through, e.g. after a long busy backoff, get a fresh one.
+            let mut url_refreshes = 0;
+            loop {
+                let presigned = PresignedUpload::new(
+                    self.with_retry(|| {
+                        self.get_presigned_url(
+                            config,
+                            filename,
+                            &content_type,
+                            requested_encoding.map(|a| a.content_encoding()),
+                        )
+                    })
+                    .await?,
+                );
+
+                let accepted_encoding = requested_encoding.filter(|algorithm| {
+                    presigned.response.content_encoding.as_deref()
+                        == Some(algorithm.content_encoding())
+                });
+                let body = match accepted_encoding {
+                    Some(algorithm) => {
+                        let compressed = compression::compress(&file_bytes, algorithm)?;
+                        log::info!(
+                            "Compressed {} with {}: {} -> {} bytes",
+                            filename,
+                            algorithm.content_encoding(),
+                            original_bytes,
+                            compressed.len()
+                        );
+                        compressed
+                    }
+                    None => file_bytes.clone(),
+                };
+                let uploaded_bytes = body.len() as u64;
+                let content_encoding = accepted_encoding.map(|a| a.content_encoding());
+
+                let put_start = Instant::now();
+                let uploaded = self
+                    .with_retry(|| {
+                        self.upload_presigned(
+                            &presigned,
+                            body.clone(),
+                            &content_type,
+                            content_encoding,
+                            on_progress.clone(),
+                        )
+                    })
+                    .await;
+
+                match uploaded {
+                    Ok(()) => {
+                        break StoredObject {
+                            bucket: presigned.response.bucket(),
+                            s3_key: presigned.response.s3_key,
+                            region: None,
+                            uploaded_bytes,
+                            content_encoding,
+                            transfer: put_start.elapsed(),
+                        }
+                    }
+                    Err(RequestError::UrlExpired(message)) if url_refreshes < MAX_URL_REFRESHES => {
+                        url_refreshes += 1;
+                        log::warn!(
+                            "Upload URL for {} expired, requesting a new one: {}",
+                            filename,
+                            message
+                        );
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        };
+
+        let recorded = self.stats.lock().unwrap().as_mut().map(|stats| {
+            stats.record(
+                crate::ledger::now_secs(),
+                original_bytes,
+                stored.uploaded_bytes,
+                stored.transfer,
+            )
+        });
+        if let Some(Err(e)) = recorded {
+            log::warn!("Failed to update upload stats: {}", e);
+        }
+
+        let mut result = UploadResult {
+            filename: filename.to_string(),
+            s3_key: stored.s3_key.clone(),
+            progress_id: None,
+            status: UploadStatus::Uploaded,
+            error: None,
+            error_code: None,
+            suggestion: None,
+            original_bytes: Some(original_bytes),
+            uploaded_bytes: Some(stored.uploaded_bytes),
+            content_encoding: stored.content_encoding.map(str::to_string),
+            metadata: None,
+        };
 
         // Step 3: Trigger ingestion if auto_ingest is enabled
         if config.auto_ingest {
             let progress_id = Uuid::new_v4().to_string();
-            let s3_bucket = presigned
-                .s3_bucket
-                .clone()
-                .unwrap_or_else(|| "exemem-user-data".to_string());
 
             let ingest_resp = self
                 .with_retry(|| {
-                    self.trigger_ingest(config, &presigned.s3_key, &s3_bucket, &progress_id)
+                    self.trigger_ingest(config, &stored, &progress_id, &metadata, expand_archive)
                 })
                 .await?;
 
-            Ok(UploadResult {
-                filename: filename.to_string(),
-                s3_key: presigned.s3_key,
-                progress_id: Some(ingest_resp.progress_id),
-                status: UploadStatus::Ingesting,
-                error: None,
-            })
-        } else {
-            Ok(UploadResult {
-                filename: filename.to_string(),
-                s3_key: presigned.s3_key,
-                progress_id: None,
-                status: UploadStatus::Uploaded,
-                error: None,
-            })
+            result.progress_id = Some(ingest_resp.progress_id);
+            result.status = UploadStatus::Ingesting;
         }
+
+        result.metadata = Some(metadata);
+        Ok(result)
     }
 
     async fn get_presigned_url(
@@ -158,35 +764,59 @@ impl Uploader {
         config: &AppConfig,
         filename: &str,
         content_type: &str,
-    ) -> Result<PresignedUrlResponse, String> {
+        content_encoding: Option<&str>,
+    ) -> Result<PresignedUrlResponse, RequestError> {
         let url = format!("{}/api/ingestion/upload-url", config.api_url());
-        let mut req = self
-            .client
-            .post(&url)
-            .header("X-API-Key", &config.api_key)
-            .json(&serde_json::json!({
-                "filename": filename,
-                "file_type": content_type,
-            }));
-
-        if let Some(user_hash) = &config.user_hash {
-            req = req.header("X-User-Hash", user_hash);
+        let mut body = serde_json::json!({
+            "filename": filename,
+            "file_type": content_type,
+        });
+        if let Some(encoding) = content_encoding {
+            body["content_encoding"] = serde_json::json!(encoding);
         }
 
-        let resp = req
-            .send()
+        let resp = self
+            .api_request(self.client.post(&url), config)
+            .json(&body)
+            .send_signed(config.signer().as_ref())
             .await
-            .map_err(|e| format!("Failed to request presigned URL: {}", e))?;
+            .map_err(|e| RequestError::network("Failed to request presigned URL", e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("Presigned URL request failed ({}): {}", status, body));
+            return Err(RequestError::from_response(resp, "Presigned URL request failed").await);
         }
 
-        resp.json::<PresignedUrlResponse>()
+        let body = resp
+            .text()
             .await
-            .map_err(|e| format!("Failed to parse presigned URL response: {}", e))
+            .map_err(|e| format!("Failed to read presigned URL response: {}", e))?;
+        PresignedUrlResponse::parse(&body, config.api_url()).map_err(RequestError::Failed)
+    }
+
+    /// PUT to a presigned URL, failing fast with `UrlExpired` if it has
+    /// already lapsed instead of sending a request S3 will refuse.
+    async fn upload_presigned(
+        &self,
+        presigned: &PresignedUpload,
+        file_bytes: Vec<u8>,
+        content_type: &str,
+        content_encoding: Option<&str>,
+        on_progress: Option<UploadProgressFn>,
+    ) -> Result<(), RequestError> {
+        if presigned.is_expired() {
+            return Err(RequestError::UrlExpired(format!(
+                "Upload URL expired after {:?}",
+                presigned.issued_at.elapsed()
+            )));
+        }
+        self.upload_to_s3(
+            &presigned.response.upload_url,
+            file_bytes,
+            content_type,
+            content_encoding,
+            on_progress,
+        )
+        .await
     }
 
     async fn upload_to_s3(
@@ -194,20 +824,48 @@ impl Uploader {
         upload_url: &str,
         file_bytes: Vec<u8>,
         content_type: &str,
-    ) -> Result<(), String> {
-        let resp = self
+        content_encoding: Option<&str>,
+        on_progress: Option<UploadProgressFn>,
+    ) -> Result<(), RequestError> {
+        // Presigned PUTs need an explicit length; a streamed body would
+        // otherwise go out chunked, which S3 rejects
+        let mut req = self
             .client
             .put(upload_url)
+            .timeout(REQUEST_TIMEOUT)
             .header("Content-Type", content_type)
-            .body(file_bytes)
+            .header(CONTENT_LENGTH, file_bytes.len());
+        if let Some(encoding) = content_encoding {
+            req = req.header("Content-Encoding", encoding);
+        }
+
+        let body = match on_progress {
+            Some(on_progress) => progress_body(file_bytes, on_progress),
+            None => reqwest::Body::from(file_bytes),
+        };
+
+        let resp = req
+            .body(body)
             .send()
             .await
-            .map_err(|e| format!("Failed to upload to S3: {}", e))?;
+            .map_err(|e| RequestError::network("Failed to upload to S3", e))?;
 
-        if !resp.status().is_success() {
-            let status = resp.status();
+        // S3 answers an expired signature with a plain 403
+        if resp.status() == StatusCode::FORBIDDEN {
             let body = resp.text().await.unwrap_or_default();
-            return Err(format!("S3 upload failed ({}): {}", status, body));
+            let message = format!("S3 upload failed (403 Forbidden): {}", body);
+            return Err(if is_expired_url_error(&body) {
+                RequestError::UrlExpired(message)
+            } else {
+                RequestError::Http {
+                    status: 403,
+                    message,
+                }
+            });
+        }
+
+        if !resp.status().is_success() {
+            return Err(RequestError::from_response(resp, "S3 upload failed").await);
         }
 
         Ok(())
@@ -216,103 +874,200 @@ impl Uploader {
     async fn trigger_ingest(
         &self,
         config: &AppConfig,
-        s3_key: &str,
-        s3_bucket: &str,
+        stored: &StoredObject,
         progress_id: &str,
-    ) -> Result<IngestResponse, String> {
+        metadata: &IngestMetadata,
+        expand_archive: bool,
+    ) -> Result<IngestResponse, RequestError> {
         let url = format!("{}/api/ingestion/ingest-s3", config.api_url());
-        let mut req = self
-            .client
-            .post(&url)
-            .header("X-API-Key", &config.api_key)
-            .json(&serde_json::json!({
-                "s3_key": s3_key,
-                "s3_bucket": s3_bucket,
-                "progress_id": progress_id,
-            }));
-
-        if let Some(user_hash) = &config.user_hash {
-            req = req.header("X-User-Hash", user_hash);
+        let mut body = serde_json::json!({
+            "s3_key": stored.s3_key,
+            "s3_bucket": stored.bucket,
+            "progress_id": progress_id,
+            "metadata": metadata,
+        });
+        if let Some(region) = &stored.region {
+            // Objects in the user's own bucket; the server reads them there
+            body["s3_region"] = serde_json::json!(region);
+        }
+        if expand_archive {
+            body["expand_archive"] = serde_json::json!(true);
         }
 
-        let resp = req
-            .send()
+        let resp = self
+            .api_request(self.client.post(&url), config)
+            .json(&body)
+            .send_signed(config.signer().as_ref())
             .await
-            .map_err(|e| format!("Failed to trigger ingestion: {}", e))?;
+            .map_err(|e| RequestError::network("Failed to trigger ingestion", e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("Ingestion trigger failed ({}): {}", status, body));
+            return Err(RequestError::from_response(resp, "Ingestion trigger failed").await);
         }
 
         resp.json::<IngestResponse>()
             .await
-            .map_err(|e| format!("Failed to parse ingestion response: {}", e))
+            .map_err(|e| format!("Failed to parse ingestion response: {}", e).into())
     }
 
     pub async fn poll_progress(
         &self,
         config: &AppConfig,
         progress_id: &str,
-    ) -> Result<ProgressResponse, String> {
+    ) -> Result<ProgressResponse, RequestError> {
         let url = format!(
             "{}/api/ingestion/progress/{}",
             config.api_url(),
             progress_id
         );
-        let mut req = self.client.get(&url).header("X-API-Key", &config.api_key);
-
-        if let Some(user_hash) = &config.user_hash {
-            req = req.header("X-User-Hash", user_hash);
-        }
-
-        let resp = req
-            .send()
+        let resp = self
+            .api_request(self.client.get(&url), config)
+            .send_signed(config.signer().as_ref())
             .await
-            .map_err(|e| format!("Failed to poll progress: {}", e))?;
+            .map_err(|e| RequestError::network("Failed to poll progress", e))?;
 
         if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(format!("Progress poll failed ({}): {}", status, body));
+            let err = RequestError::from_response(resp, "Progress poll failed").await;
+            if let RequestError::ServerBusy { retry_after, .. } = &err {
+                self.mark_busy(*retry_after);
+            }
+            return Err(err);
         }
 
         resp.json::<ProgressResponse>()
             .await
-            .map_err(|e| format!("Failed to parse progress response: {}", e))
+            .map_err(|e| format!("Failed to parse progress response: {}", e).into())
+    }
+
+    /// Attach credentials and the timeout for an Exemem API call: the API
+    /// key, unless the request will be signed instead, and the user hash.
+    fn api_request(&self, mut req: RequestBuilder, config: &AppConfig) -> RequestBuilder {
+        if config.signer().is_none() {
+            req = req.header("X-API-Key", &config.api_key);
+        }
+        if let Some(user_hash) = &config.user_hash {
+            req = req.header("X-User-Hash", user_hash);
+        }
+        req.timeout(REQUEST_TIMEOUT)
     }
 
-    async fn with_retry<F, Fut, T>(&self, f: F) -> Result<T, String>
+    async fn with_retry<F, Fut, T>(&self, f: F) -> Result<T, RequestError>
     where
         F: Fn() -> Fut,
-        Fut: std::future::Future<Output = Result<T, String>>,
+        Fut: std::future::Future<Output = Result<T, RequestError>>,
     {
-        let max_attempts = 3;
-        let mut last_err = String::new();
-
-        for attempt in 0..max_attempts {
-            match f().await {
-                Ok(val) => return Ok(val),
-                Err(err) => {
-                    last_err = err;
-                    if attempt < max_attempts - 1 {
-                        let delay = Duration::from_millis(500 * 2u64.pow(attempt as u32));
-                        log::warn!(
-                            "Attempt {} failed, retrying in {:?}: {}",
-                            attempt + 1,
-                            delay,
-                            last_err
-                        );
-                        sleep(delay).await;
-                    }
-                }
+        let classify = |err: &RequestError| match err {
+            // Backpressure: wait as long as the server asked without
+            // spending one of our retry attempts
+            RequestError::ServerBusy { retry_after, .. } => {
+                self.mark_busy(*retry_after);
+                Retry::After(*retry_after)
             }
+            RequestError::Rejected { .. } | RequestError::UrlExpired(_) => Retry::Never,
+            _ => Retry::Backoff,
+        };
+
+        match retry::with_backoff(&UPLOAD_RETRY, classify, f).await {
+            Ok(val) => Ok(val),
+            Err(RetryError::Permanent(err)) => Err(err),
+            // Keep the last error's kind so callers can still tell network
+            // failures from server errors
+            Err(RetryError::Exhausted { error, attempts }) => {
+                Err(error.with_prefix(&format!("Failed after {} attempts", attempts)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn presigned(expires_in: Option<u64>, age: Duration) -> PresignedUpload {
+        let mut body = serde_json::json!({
+            "upload_url": "https://exemem-user-data.s3.amazonaws.com/u/1/a.json",
+            "s3_key": "u/1/a.json",
+        });
+        if let Some(secs) = expires_in {
+            body["expires_in"] = serde_json::json!(secs);
+        }
+        let response = PresignedUrlResponse::parse(&body.to_string(), "https://api.example").unwrap();
+        PresignedUpload {
+            response,
+            issued_at: Instant::now().checked_sub(age).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_presigned_upload_expiry() {
+        assert!(!presigned(Some(600), Duration::ZERO).is_expired());
+        assert!(!presigned(Some(600), Duration::from_secs(500)).is_expired());
+        // Within the safety margin counts as expired
+        assert!(presigned(Some(600), Duration::from_secs(580)).is_expired());
+        assert!(presigned(Some(600), Duration::from_secs(700)).is_expired());
+
+        // Falls back to the default lifetime when the server didn't say
+        assert!(!presigned(None, Duration::from_secs(60)).is_expired());
+        assert!(presigned(None, DEFAULT_PRESIGNED_URL_TTL).is_expired());
+    }
+
+    #[test]
+    fn test_expired_url_error_on_s3_bodies() {
+        // What S3 and MinIO send for a presigned URL past X-Amz-Expires
+        let s3_expired = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>AccessDenied</Code><Message>Request has expired</Message><X-Amz-Expires>600</X-Amz-Expires><Expires>2024-05-01T10:10:00Z</Expires><ServerTime>2024-05-01T10:12:41Z</ServerTime><RequestId>4Q7MNY0SD1DVQ2W9</RequestId><HostId>f0Hk3t5N1cXx2vPZ</HostId></Error>"#;
+        let minio_expired = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>AccessDenied</Code><Message>Request has expired</Message><Key>u/1/a.json</Key><BucketName>uploads</BucketName><Resource>/uploads/u/1/a.json</Resource><RequestId>17C9A2E1B3D4F5A6</RequestId><HostId>dd9025bab4ad464b</HostId></Error>"#;
+        // Signed with temporary credentials that have since lapsed
+        let token_expired = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>ExpiredToken</Code><Message>The provided token has expired.</Message><Token-0>FwoGZXIvYXdzEBAaDH</Token-0><RequestId>8V1BC9K2M3N4P5Q6</RequestId><HostId>Zm9vYmFy</HostId></Error>"#;
+        for body in [s3_expired, minio_expired, token_expired] {
+            assert!(is_expired_url_error(body), "{}", body);
+        }
+
+        // Other 403s mean the URL is wrong, not stale; a fresh one won't help
+        let bad_signature = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>SignatureDoesNotMatch</Code><Message>The request signature we calculated does not match the signature you provided. Check your key and signing method.</Message><AWSAccessKeyId>AKIAEXAMPLE</AWSAccessKeyId><RequestId>1A2B3C4D5E6F7G8H</RequestId></Error>"#;
+        let denied = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Error><Code>AccessDenied</Code><Message>Access Denied</Message><RequestId>9H8G7F6E5D4C3B2A</RequestId><HostId>YmF6cXV4</HostId></Error>"#;
+        for body in [bad_signature, denied, ""] {
+            assert!(!is_expired_url_error(body), "{}", body);
+        }
+    }
+
+    async fn send_with_progress(len: usize) -> (Vec<u8>, Vec<(u64, u64)>) {
+        use futures_util::StreamExt;
+
+        let reports = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = reports.clone();
+        let on_progress: UploadProgressFn =
+            Arc::new(move |sent, total| recorder.lock().unwrap().push((sent, total)));
+
+        let input: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let mut chunks = Box::pin(progress_chunks(input, on_progress));
+        let mut sent = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            sent.extend_from_slice(&chunk.unwrap());
         }
+        let reports = reports.lock().unwrap().clone();
+        (sent, reports)
+    }
+
+    #[tokio::test]
+    async fn test_progress_reaches_total() {
+        let len = UPLOAD_CHUNK_SIZE * 2 + UPLOAD_CHUNK_SIZE / 2;
+        let (sent, reports) = send_with_progress(len).await;
+        assert_eq!(sent, (0..len).map(|i| i as u8).collect::<Vec<_>>());
+
+        let total = len as u64;
+        let chunk = UPLOAD_CHUNK_SIZE as u64;
+        assert_eq!(reports, vec![(chunk, total), (chunk * 2, total), (total, total)]);
 
-        Err(format!(
-            "Failed after {} attempts: {}",
-            max_attempts, last_err
-        ))
+        // Exactly one chunk, and nothing at all
+        let (_, reports) = send_with_progress(UPLOAD_CHUNK_SIZE).await;
+        assert_eq!(reports, vec![(chunk, chunk)]);
+        let (sent, reports) = send_with_progress(0).await;
+        assert!(sent.is_empty());
+        assert_eq!(reports, vec![(0, 0)]);
     }
 }
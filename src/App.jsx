@@ -2,11 +2,14 @@ import { useState, useEffect, useCallback } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import { onOpenUrl } from "@tauri-apps/plugin-deep-link";
+import { errorMessage } from "./commands";
+import { loadCapabilities } from "./capabilities";
 
 import Sidebar from "./components/Sidebar";
 import SettingsPanel from "./components/SettingsPanel";
 import SyncPanel from "./components/SyncPanel";
 import QueryPanel from "./components/QueryPanel";
+import UnlockPrompt from "./components/UnlockPrompt";
 
 export default function App() {
   const [activeView, setActiveView] = useState("settings");
@@ -27,15 +30,19 @@ export default function App() {
     recent_activity: [],
   });
   const [error, setError] = useState(null);
+  const [capabilities, setCapabilities] = useState(null);
   const [success, setSuccess] = useState(null);
+  const [locked, setLocked] = useState(false);
 
   const loadState = useCallback(async () => {
     try {
-      const [cfg, status] = await Promise.all([
+      const [cfg, status, encryption] = await Promise.all([
         invoke("get_config"),
         invoke("get_sync_status"),
+        invoke("config_encryption_status"),
       ]);
       setConfig(cfg);
+      setLocked(encryption.locked);
       setSyncStatus(status);
       if (status.watching) {
         setActiveView("sync");
@@ -54,7 +61,7 @@ export default function App() {
         user_hash: data.user_hash,
         session_token: data.session_token || null,
       };
-      invoke("save_config", { newConfig }).catch((err) => setError(String(err)));
+      invoke("save_config", { newConfig }).catch((err) => setError(errorMessage(err)));
       return newConfig;
     });
     setSuccess("Signed in and API key saved.");
@@ -63,6 +70,7 @@ export default function App() {
 
   useEffect(() => {
     loadState();
+    loadCapabilities().then(setCapabilities);
 
     const unlistenActivity = listen("sync-activity", (event) => {
       setSyncStatus((prev) => {
@@ -83,6 +91,10 @@ export default function App() {
       if (event.payload) setActiveView("sync");
     });
 
+    const unlistenFolder = listen("watched-folder-changed", (event) => {
+      setSyncStatus((prev) => ({ ...prev, folder: event.payload }));
+    });
+
     const unlistenTray = listen("tray-toggle-watching", async () => {
       try {
         const status = await invoke("get_sync_status");
@@ -94,7 +106,7 @@ export default function App() {
         const newStatus = await invoke("get_sync_status");
         setSyncStatus(newStatus);
       } catch (err) {
-        setError(String(err));
+        setError(errorMessage(err));
       }
     });
 
@@ -127,6 +139,7 @@ export default function App() {
     return () => {
       unlistenActivity.then((f) => f());
       unlistenStatus.then((f) => f());
+      unlistenFolder.then((f) => f());
       unlistenTray.then((f) => f());
       unlistenDeepLink.then((f) => f());
       if (unlistenDeepLinkJs) unlistenDeepLinkJs();
@@ -166,8 +179,18 @@ export default function App() {
             </div>
           )}
 
+          {locked && (
+            <UnlockPrompt
+              onUnlocked={(cfg) => {
+                setConfig(cfg);
+                setLocked(false);
+              }}
+              setError={setError}
+            />
+          )}
+
           {/* View Router */}
-          {activeView === "settings" && (
+          {!locked && activeView === "settings" && (
             <SettingsPanel
               config={config}
               setConfig={setConfig}
@@ -175,10 +198,11 @@ export default function App() {
               setError={setError}
               setSuccess={setSuccess}
               onScanAndWatch={handleScanAndWatch}
+              onLock={() => setLocked(true)}
             />
           )}
 
-          {activeView === "sync" && (
+          {!locked && activeView === "sync" && (
             <SyncPanel
               config={config}
               saveConfig={saveConfig}
@@ -189,9 +213,10 @@ export default function App() {
             />
           )}
 
-          {activeView === "query" && (
+          {!locked && activeView === "query" && (
             <QueryPanel
               config={config}
+              capabilities={capabilities}
               setError={setError}
             />
           )}
@@ -0,0 +1,21 @@
+/**
+ * Build capabilities
+ *
+ * Optional subsystems can be compiled out of the backend. Check
+ * `get_client_capabilities` before showing UI that depends on them.
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+export async function loadCapabilities() {
+  try {
+    return await invoke("get_client_capabilities");
+  } catch (err) {
+    // Older backends don't have the command; assume everything is there
+    console.warn("Capabilities unavailable:", err);
+    return null;
+  }
+}
+
+export function hasFeature(capabilities, feature) {
+  return !capabilities || capabilities.features.includes(feature);
+}
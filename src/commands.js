@@ -0,0 +1,29 @@
+/**
+ * Cancellable commands
+ *
+ * Long-running commands accept an `invocationId`. Passing the same id to
+ * `cancel_command` aborts the command, which then rejects with a
+ * `cancelled` error.
+ *
+ * Commands reject with `{ kind, message, status? }`, where kind is one of
+ * auth, network, server, validation, io, cancelled, timeout or internal.
+ */
+import { invoke } from "@tauri-apps/api/core";
+import { invokeLarge } from "./payload";
+
+export function startCommand(command, args = {}, { large = false, cancelCommand = "cancel_command" } = {}) {
+  const invocationId = crypto.randomUUID();
+  const call = large ? invokeLarge : invoke;
+  return {
+    promise: call(command, { ...args, invocationId }),
+    cancel: () => invoke(cancelCommand, { invocationId }).catch(() => false),
+  };
+}
+
+export function isCancelled(err) {
+  return err?.kind === "cancelled";
+}
+
+export function errorMessage(err) {
+  return err?.message ?? String(err);
+}
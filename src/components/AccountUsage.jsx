@@ -0,0 +1,68 @@
+import { useState, useEffect } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../commands";
+import ProgressBar from "./shared/ProgressBar";
+
+function formatBytes(bytes) {
+  const units = ["B", "KB", "MB", "GB", "TB"];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+}
+
+function UsageRow({ label, used, limit, format = String }) {
+  const full = limit != null && used >= limit;
+  return (
+    <div className="space-y-1">
+      <div className="flex items-center justify-between text-xs">
+        <span className="text-gray-600">{label}</span>
+        <span className={full ? "text-red-600 font-medium" : "text-gray-500"}>
+          {format(used)}{limit != null && ` of ${format(limit)}`}
+        </span>
+      </div>
+      {limit != null && (
+        <ProgressBar percent={limit > 0 ? (used / limit) * 100 : 100} status={full ? "error" : "ingesting"} />
+      )}
+    </div>
+  );
+}
+
+export default function AccountUsage() {
+  const [usage, setUsage] = useState(null);
+  const [error, setError] = useState(null);
+
+  const refresh = () => {
+    setError(null);
+    invoke("get_account_usage")
+      .then(setUsage)
+      .catch((err) => setError(errorMessage(err)));
+  };
+
+  useEffect(refresh, []);
+
+  return (
+    <div className="space-y-2">
+      <div className="flex items-center justify-between">
+        <label className="block text-xs text-gray-500">Usage</label>
+        <button onClick={refresh} className="text-xs text-gray-500 hover:text-gray-700 underline">Refresh</button>
+      </div>
+      {error && <p className="text-xs text-red-500">{error}</p>}
+      {usage && (
+        <>
+          <UsageRow label="Documents" used={usage.documents_ingested} limit={usage.documents_limit} />
+          <UsageRow label="Storage" used={usage.storage_bytes} limit={usage.storage_limit_bytes} format={formatBytes} />
+          <UsageRow label="Queries" used={usage.queries_used} limit={usage.queries_limit} />
+          {usage.quota_resets_at && (
+            <p className="text-xs text-gray-400">
+              Query quota resets {new Date(usage.quota_resets_at * 1000).toLocaleDateString()}
+            </p>
+          )}
+        </>
+      )}
+    </div>
+  );
+}
@@ -1,13 +1,33 @@
 import { useState, useRef, useEffect } from "react";
 import { invoke } from "@tauri-apps/api/core";
+import { startCommand, isCancelled, errorMessage } from "../commands";
+import { hasFeature } from "../capabilities";
+import { invokeLarge } from "../payload";
 
-export default function QueryPanel({ config, setError }) {
+// Results are fetched a page at a time; "Load more" asks for the next one
+const PAGE_SIZE = 50;
+
+// Scanner categories an index search can be narrowed to
+const SEARCH_CATEGORIES = [
+  ["personal_data", "Personal data"],
+  ["work", "Work"],
+  ["media", "Media"],
+  ["config", "Config"],
+  ["unknown", "Other"],
+];
+
+export default function QueryPanel({ config, capabilities, setError }) {
   const [messages, setMessages] = useState([]);
   const [input, setInput] = useState("");
   const [loading, setLoading] = useState(false);
   const [sessionId, setSessionId] = useState(null);
-  const [mode, setMode] = useState("ai"); // "ai" or "search"
+  const [mode, setMode] = useState("ai"); // "ai", "search" or "history"
+  const [recentQuestions, setRecentQuestions] = useState([]);
+  const [sessions, setSessions] = useState([]);
+  const [searchCategory, setSearchCategory] = useState("");
+  const [semantic, setSemantic] = useState(false);
   const messagesEndRef = useRef(null);
+  const cancelRef = useRef(null);
 
   const scrollToBottom = () => {
     messagesEndRef.current?.scrollIntoView({ behavior: "smooth" });
@@ -17,8 +37,92 @@ export default function QueryPanel({ config, setError }) {
     scrollToBottom();
   }, [messages]);
 
+  useEffect(() => {
+    if (mode !== "history") return;
+    invoke("get_query_history", { filter: { limit: 10 } })
+      .then(setRecentQuestions)
+      .catch((err) => console.error("Failed to load query history:", err));
+  }, [mode]);
+
+  useEffect(() => {
+    if (mode !== "ai" || sessionId || !config.api_key) return;
+    invoke("list_sessions", { limit: 10 })
+      .then(setSessions)
+      .catch((err) => console.error("Failed to load sessions:", err));
+  }, [mode, sessionId, config.api_key]);
+
+  const handleResumeSession = async (id) => {
+    try {
+      const turns = await invoke("get_session_transcript", { sessionId: id });
+      setMessages(turns.flatMap((turn) => [
+        { role: "user", content: turn.question, mode: "ai" },
+        { role: "assistant", content: turn.answer, mode: "ai", askedAt: turn.created_at, contextUsed: turn.context_used },
+      ]));
+      setSessionId(id);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const handleDeleteSession = async (id) => {
+    try {
+      await invoke("delete_session", { sessionId: id });
+      setSessions((prev) => prev.filter((s) => s.session_id !== id));
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const handleExportSession = async () => {
+    try {
+      const path = await invoke("export_session_transcript", { sessionId });
+      if (path) setMessages((prev) => [...prev, { role: "assistant", content: `Saved conversation to ${path}` }]);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const handleClearHistory = async () => {
+    try {
+      await invoke("delete_query_history", {});
+      setRecentQuestions([]);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
   const isAuthenticated = !!(config.api_key);
 
+  const runCommand = async (command, args, options) => {
+    const { promise, cancel } = startCommand(command, args, { ...options, cancelCommand: "cancel_query" });
+    cancelRef.current = cancel;
+    try {
+      return await promise;
+    } finally {
+      cancelRef.current = null;
+    }
+  };
+
+  // A cancelled query isn't an error; the session is still open
+  const failureMessage = (err) => (isCancelled(err)
+    ? { role: "assistant", content: "Cancelled." }
+    : { role: "error", content: errorMessage(err) });
+
+  // Offline: send the question once the backend is reachable; the answer
+  // shows up under Past Answers
+  const handleQueueQuery = async (index) => {
+    const query = messages[index]?.queueable;
+    if (!query) return;
+    try {
+      await invoke("queue_request", { request: { type: "query", query } });
+      setMessages((prev) => prev.map((m, i) => (i === index
+        ? { ...m, queueable: null, content: `${m.content}\nQueued; it will be sent when you're back online.` }
+        : m)));
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
   const handleSubmit = async (e) => {
     e.preventDefault();
     const trimmed = input.trim();
@@ -30,17 +134,46 @@ export default function QueryPanel({ config, setError }) {
       setMessages((prev) => [...prev, { role: "user", content: trimmed, mode: "search" }]);
       setLoading(true);
       try {
-        const resp = await invoke("search_index", { term: trimmed });
+        const resp = semantic
+          ? await runCommand("semantic_search", { text: trimmed, limit: PAGE_SIZE })
+          : await runCommand("search_index", {
+            term: trimmed,
+            filters: { category: searchCategory || null },
+            view: { limit: PAGE_SIZE },
+          });
         setMessages((prev) => [...prev, {
           role: "assistant",
           content: resp.count > 0
-            ? `Found ${resp.count} results for "${resp.term}"`
-            : `No results found for "${resp.term}"`,
+            ? `Found ${resp.count} results for "${trimmed}"`
+            : `No results found for "${trimmed}"`,
           data: resp.results,
+          total: resp.count,
+          nextCursor: resp.next_cursor,
           mode: "search",
         }]);
       } catch (err) {
-        setMessages((prev) => [...prev, { role: "error", content: String(err) }]);
+        setMessages((prev) => [...prev, failureMessage(err)]);
+      } finally {
+        setLoading(false);
+      }
+      return;
+    }
+
+    if (mode === "history") {
+      setMessages((prev) => [...prev, { role: "user", content: trimmed, mode: "history" }]);
+      setLoading(true);
+      try {
+        const hits = await runCommand("search_transcripts", { term: trimmed });
+        setMessages((prev) => [...prev, {
+          role: "assistant",
+          content: hits.length > 0
+            ? `Found ${hits.length} past ${hits.length === 1 ? "answer" : "answers"} for "${trimmed}"`
+            : `No past answers mention "${trimmed}"`,
+          hits,
+          mode: "history",
+        }]);
+      } catch (err) {
+        setMessages((prev) => [...prev, failureMessage(err)]);
       } finally {
         setLoading(false);
       }
@@ -53,14 +186,14 @@ export default function QueryPanel({ config, setError }) {
       setMessages((prev) => [...prev, { role: "user", content: trimmed, mode: "ai" }]);
       setLoading(true);
       try {
-        const resp = await invoke("chat_followup", { sessionId, question: trimmed });
+        const resp = await runCommand("chat_followup", { sessionId, question: trimmed });
         setMessages((prev) => [...prev, {
           role: "assistant",
           content: resp.answer,
           mode: "ai",
         }]);
       } catch (err) {
-        setMessages((prev) => [...prev, { role: "error", content: String(err) }]);
+        setMessages((prev) => [...prev, failureMessage(err)]);
       } finally {
         setLoading(false);
       }
@@ -69,27 +202,137 @@ export default function QueryPanel({ config, setError }) {
       setMessages((prev) => [...prev, { role: "user", content: trimmed, mode: "ai" }]);
       setLoading(true);
       try {
-        const resp = await invoke("run_query", { query: trimmed, sessionId: null });
+        const resp = await runCommand(
+          "run_query",
+          { query: trimmed, sessionId: null, view: { limit: PAGE_SIZE } },
+          { large: true },
+        );
         setSessionId(resp.session_id);
         setMessages((prev) => [...prev, {
           role: "assistant",
           content: resp.ai_interpretation || "Query completed.",
           data: resp.raw_results,
+          total: resp.total_results,
+          nextCursor: resp.next_cursor,
           mode: "ai",
         }]);
       } catch (err) {
-        setMessages((prev) => [...prev, { role: "error", content: String(err) }]);
+        setMessages((prev) => [...prev, { ...failureMessage(err), queueable: err?.kind === "network" ? trimmed : null }]);
       } finally {
         setLoading(false);
       }
     }
   };
 
+  const handleLoadMore = async (index) => {
+    const cursor = messages[index]?.nextCursor;
+    if (!cursor) return;
+    setMessages((prev) => prev.map((m, i) => (i === index ? { ...m, loadingMore: true } : m)));
+    try {
+      const page = await invokeLarge("get_more_results", { cursor });
+      setMessages((prev) => prev.map((m, i) => (i === index
+        ? { ...m, data: [...m.data, ...page.results], nextCursor: page.next_cursor, loadingMore: false }
+        : m)));
+    } catch (err) {
+      setMessages((prev) => prev.map((m, i) => (i === index ? { ...m, loadingMore: false } : m)));
+      setError(errorMessage(err));
+    }
+  };
+
   const handleNewSession = () => {
     setSessionId(null);
     setMessages([]);
   };
 
+  const renderHits = (hits) => {
+    if (!hits || hits.length === 0) return null;
+    return (
+      <div className="mt-2 space-y-2">
+        {hits.map((hit, i) => (
+          <div key={i} className="px-2 py-1 bg-gray-50 rounded border border-gray-100">
+            <p className="text-xs font-medium text-gray-700">{hit.turn.question}</p>
+            <p className="text-xs text-gray-600 whitespace-pre-wrap">{hit.snippet}</p>
+            <p className="text-[10px] text-gray-400">{new Date(hit.turn.created_at * 1000).toLocaleString()}</p>
+          </div>
+        ))}
+      </div>
+    );
+  };
+
+  const renderMore = (msg, index) => {
+    if (!msg.data || !msg.nextCursor) return null;
+    return (
+      <div className="flex items-center gap-2 mt-1">
+        <p className="text-xs text-gray-400">Showing {msg.data.length} of {msg.total} results</p>
+        <button
+          onClick={() => handleLoadMore(index)}
+          disabled={msg.loadingMore}
+          className="text-xs text-primary hover:underline disabled:opacity-50"
+        >
+          {msg.loadingMore ? "Loading..." : "Load more"}
+        </button>
+      </div>
+    );
+  };
+
+  const handleExport = async (msg, format) => {
+    try {
+      // A cursor means only part of the results is loaded; export them all
+      const path = await invoke("export_results", {
+        format,
+        cursor: msg.nextCursor || null,
+        results: msg.nextCursor ? null : (msg.mode === "search" ? msg.data.map((hit) => hit.raw) : msg.data),
+      });
+      if (path) setMessages((prev) => [...prev, { role: "assistant", content: `Exported results to ${path}` }]);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const renderExport = (msg) => {
+    if (!msg.data || msg.data.length === 0) return null;
+    return (
+      <div className="flex items-center gap-2 mt-1 text-xs text-gray-400">
+        <span>Export:</span>
+        {[["json", "JSON"], ["csv", "CSV"], ["markdown", "Markdown"]].map(([format, label]) => (
+          <button key={format} onClick={() => handleExport(msg, format)} className="hover:text-gray-700 hover:underline">
+            {label}
+          </button>
+        ))}
+      </div>
+    );
+  };
+
+  // Wrap each highlighted span of a snippet; offsets are in characters
+  const renderSnippet = (snippet, highlights) => {
+    const chars = Array.from(snippet || "");
+    const parts = [];
+    let pos = 0;
+    (highlights || []).forEach(({ start, end }, i) => {
+      if (start > pos) parts.push(chars.slice(pos, start).join(""));
+      parts.push(<mark key={i} className="bg-yellow-100 text-gray-900 rounded-sm">{chars.slice(start, end).join("")}</mark>);
+      pos = end;
+    });
+    if (pos < chars.length) parts.push(chars.slice(pos).join(""));
+    return parts;
+  };
+
+  const renderSearchHits = (hits) => {
+    if (!hits || hits.length === 0) return null;
+    return (
+      <div className="mt-2 space-y-2">
+        {hits.map((hit, i) => (
+          <div key={hit.id || i} className="px-2 py-1 bg-gray-50 rounded border border-gray-100">
+            <p className="text-xs text-gray-700 whitespace-pre-wrap">{renderSnippet(hit.snippet, hit.highlights)}</p>
+            <p className="text-[10px] text-gray-400">
+              {[hit.source_file, hit.score != null ? `score ${hit.score.toFixed(2)}` : null].filter(Boolean).join(" · ")}
+            </p>
+          </div>
+        ))}
+      </div>
+    );
+  };
+
   const renderData = (data) => {
     if (!data || data.length === 0) return null;
 
@@ -109,7 +352,7 @@ export default function QueryPanel({ config, setError }) {
               </tr>
             </thead>
             <tbody>
-              {data.slice(0, 50).map((row, i) => (
+              {data.map((row, i) => (
                 <tr key={i} className={i % 2 === 0 ? "bg-white" : "bg-gray-50"}>
                   {keys.map((key) => (
                     <td key={key} className="px-2 py-1 text-gray-700 border-b border-gray-100 max-w-[200px] truncate">
@@ -120,9 +363,6 @@ export default function QueryPanel({ config, setError }) {
               ))}
             </tbody>
           </table>
-          {data.length > 50 && (
-            <p className="text-xs text-gray-400 mt-1">Showing 50 of {data.length} results</p>
-          )}
         </div>
       );
     }
@@ -180,14 +420,32 @@ export default function QueryPanel({ config, setError }) {
             >
               Index Search
             </button>
+            {hasFeature(capabilities, "transcripts") && (
+              <button
+                onClick={() => setMode("history")}
+                className={`px-2.5 py-1 rounded-md text-xs font-medium transition-colors ${
+                  mode === "history" ? "bg-white text-gray-900 shadow-sm" : "text-gray-500"
+                }`}
+              >
+                Past Answers
+              </button>
+            )}
           </div>
           {sessionId && (
-            <button
-              onClick={handleNewSession}
-              className="px-2 py-1 text-xs text-gray-500 hover:text-gray-700 border border-gray-200 rounded-lg"
-            >
-              New Session
-            </button>
+            <div className="flex items-center gap-1">
+              <button
+                onClick={handleExportSession}
+                className="px-2 py-1 text-xs text-gray-500 hover:text-gray-700 border border-gray-200 rounded-lg"
+              >
+                Export
+              </button>
+              <button
+                onClick={handleNewSession}
+                className="px-2 py-1 text-xs text-gray-500 hover:text-gray-700 border border-gray-200 rounded-lg"
+              >
+                New Session
+              </button>
+            </div>
           )}
         </div>
       </div>
@@ -203,8 +461,59 @@ export default function QueryPanel({ config, setError }) {
               <p className="text-sm">
                 {mode === "ai"
                   ? "Ask a question about your data"
-                  : "Search your indexed content"}
+                  : mode === "search"
+                    ? "Search your indexed content"
+                    : "Search answers you've received before"}
               </p>
+              {mode === "ai" && sessions.length > 0 && (
+                <div className="mt-4 text-left space-y-1">
+                  <p className="text-xs font-medium text-gray-500">Resume a conversation</p>
+                  {sessions.map((session) => (
+                    <div key={session.session_id} className="flex items-center gap-2">
+                      <button
+                        onClick={() => handleResumeSession(session.session_id)}
+                        className="flex-1 text-left text-xs text-gray-600 hover:text-gray-900 truncate"
+                      >
+                        {session.title || session.session_id}
+                        {session.last_active_at > 0 && (
+                          <span className="ml-2 text-[10px] text-gray-400">
+                            {new Date(session.last_active_at * 1000).toLocaleDateString()}
+                          </span>
+                        )}
+                      </button>
+                      <button
+                        onClick={() => handleDeleteSession(session.session_id)}
+                        className="text-[10px] text-gray-400 hover:text-red-500"
+                      >
+                        Delete
+                      </button>
+                    </div>
+                  ))}
+                </div>
+              )}
+              {mode === "history" && recentQuestions.length > 0 && (
+                <div className="mt-4 text-left space-y-1">
+                  <p className="text-xs font-medium text-gray-500">Recent questions</p>
+                  {recentQuestions.map((entry) => (
+                    <button
+                      key={entry.id}
+                      onClick={() => setInput(entry.question)}
+                      className="block w-full text-left text-xs text-gray-600 hover:text-gray-900 truncate"
+                    >
+                      {entry.question}
+                      <span className="ml-2 text-[10px] text-gray-400">
+                        {new Date(entry.created_at * 1000).toLocaleDateString()}
+                      </span>
+                    </button>
+                  ))}
+                  <button
+                    onClick={handleClearHistory}
+                    className="text-[10px] text-gray-400 hover:text-red-500"
+                  >
+                    Clear history
+                  </button>
+                </div>
+              )}
             </div>
           </div>
         )}
@@ -219,12 +528,29 @@ export default function QueryPanel({ config, setError }) {
             {msg.role === "assistant" && (
               <div className="max-w-[90%] px-3 py-2 bg-white border border-gray-200 rounded-xl rounded-bl-sm shadow-sm">
                 <p className="text-sm text-gray-800 whitespace-pre-wrap">{msg.content}</p>
-                {renderData(msg.data)}
+                {msg.mode === "search" ? renderSearchHits(msg.data) : renderData(msg.data)}
+                {renderMore(msg, i)}
+                {renderExport(msg)}
+                {renderHits(msg.hits)}
+                {msg.askedAt > 0 && (
+                  <p className="mt-1 text-[10px] text-gray-400">
+                    {new Date(msg.askedAt * 1000).toLocaleString()}
+                    {msg.contextUsed && " · used earlier context"}
+                  </p>
+                )}
               </div>
             )}
             {msg.role === "error" && (
-              <div className="max-w-[90%] px-3 py-2 bg-red-50 border border-red-200 rounded-xl text-sm text-red-700">
+              <div className="max-w-[90%] px-3 py-2 bg-red-50 border border-red-200 rounded-xl text-sm text-red-700 whitespace-pre-wrap">
                 {msg.content}
+                {msg.queueable && (
+                  <button
+                    onClick={() => handleQueueQuery(i)}
+                    className="block mt-1 text-xs text-red-600 hover:underline"
+                  >
+                    Ask when back online
+                  </button>
+                )}
               </div>
             )}
           </div>
@@ -238,6 +564,12 @@ export default function QueryPanel({ config, setError }) {
                 <path className="opacity-75" fill="currentColor" d="M4 12a8 8 0 018-8V0C5.373 0 0 5.373 0 12h4z" />
               </svg>
               <span className="text-sm text-gray-500">Thinking...</span>
+              <button
+                onClick={() => cancelRef.current?.()}
+                className="text-xs text-gray-400 hover:text-gray-600 underline"
+              >
+                Cancel
+              </button>
             </div>
           </div>
         )}
@@ -247,13 +579,37 @@ export default function QueryPanel({ config, setError }) {
 
       {/* Input bar */}
       <form onSubmit={handleSubmit} className="query-input-bar">
+        {mode === "search" && hasFeature(capabilities, "semantic_search") && (
+          <label className="flex items-center gap-1 text-xs text-gray-500 whitespace-nowrap">
+            <input
+              type="checkbox"
+              checked={semantic}
+              onChange={(e) => setSemantic(e.target.checked)}
+              disabled={loading}
+            />
+            By meaning
+          </label>
+        )}
+        {mode === "search" && !semantic && (
+          <select
+            value={searchCategory}
+            onChange={(e) => setSearchCategory(e.target.value)}
+            className="px-2 py-2 border border-gray-300 rounded-lg text-sm text-gray-700"
+            disabled={loading}
+          >
+            <option value="">All categories</option>
+            {SEARCH_CATEGORIES.map(([value, label]) => (
+              <option key={value} value={value}>{label}</option>
+            ))}
+          </select>
+        )}
         <input
           type="text"
           value={input}
           onChange={(e) => setInput(e.target.value)}
           placeholder={mode === "ai"
             ? (sessionId ? "Ask a follow-up question..." : "Ask about your data...")
-            : "Search for a term..."}
+            : mode === "search" ? "Search for a term..." : "Search past answers..."}
           className="flex-1 px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
           disabled={loading}
         />
@@ -0,0 +1,58 @@
+import { useState } from "react";
+
+export default function QuickNote({ onSubmit, onCancel, setError }) {
+  const [title, setTitle] = useState("");
+  const [content, setContent] = useState("");
+  const [fromClipboard, setFromClipboard] = useState(false);
+
+  const handlePaste = async () => {
+    try {
+      const text = await navigator.clipboard.readText();
+      setContent(text);
+      setFromClipboard(true);
+    } catch (err) {
+      setError(`Couldn't read the clipboard: ${err?.message ?? err}`);
+    }
+  };
+
+  const empty = !title.trim() && !content.trim();
+
+  return (
+    <div className="space-y-2 p-3 bg-gray-50 rounded-lg border border-gray-200">
+      <input
+        type="text"
+        className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+        placeholder="Title (optional)"
+        value={title}
+        onChange={(e) => setTitle(e.target.value)}
+      />
+      <textarea
+        className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+        rows={5}
+        placeholder="Write a note or paste text"
+        value={content}
+        onChange={(e) => {
+          setContent(e.target.value);
+          setFromClipboard(false);
+        }}
+      />
+      <div className="flex items-center justify-between">
+        <button onClick={handlePaste} className="text-xs text-gray-500 hover:text-gray-700 underline">
+          Paste clipboard
+        </button>
+        <div className="flex gap-2">
+          <button onClick={onCancel} className="px-3 py-1 rounded-lg text-xs font-medium text-gray-600 hover:bg-gray-100">
+            Cancel
+          </button>
+          <button
+            onClick={() => onSubmit({ title: title.trim() || null, content, fromClipboard })}
+            disabled={empty}
+            className="px-3 py-1 rounded-lg text-xs font-medium bg-primary text-white hover:bg-secondary transition-colors disabled:opacity-50"
+          >
+            Ingest
+          </button>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -0,0 +1,80 @@
+import { useState, useEffect, useRef } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import { startCommand, isCancelled, errorMessage } from "../commands";
+
+// Shown in the always-on-top window the global shortcut toggles
+export default function QuickQuery() {
+  const [question, setQuestion] = useState("");
+  const [answer, setAnswer] = useState(null);
+  const [error, setError] = useState(null);
+  const [loading, setLoading] = useState(false);
+  const [newSession, setNewSession] = useState(false);
+  const inputRef = useRef(null);
+  const cancelRef = useRef(null);
+
+  useEffect(() => {
+    const unlistenFocus = getCurrentWindow().onFocusChanged(({ payload: focused }) => {
+      if (focused) inputRef.current?.select();
+    });
+    return () => {
+      unlistenFocus.then((f) => f());
+    };
+  }, []);
+
+  const handleAsk = async () => {
+    if (!question.trim() || loading) return;
+    setError(null);
+    setLoading(true);
+    try {
+      const { promise, cancel } = startCommand("quick_query", { question, newSession });
+      cancelRef.current = cancel;
+      setAnswer(await promise);
+      setNewSession(false);
+    } catch (err) {
+      if (!isCancelled(err)) setError(errorMessage(err));
+    } finally {
+      cancelRef.current = null;
+      setLoading(false);
+    }
+  };
+
+  const handleKeyDown = (e) => {
+    if (e.key === "Enter") {
+      handleAsk();
+    } else if (e.key === "Escape") {
+      if (cancelRef.current) cancelRef.current();
+      else invoke("hide_quick_query").catch(() => {});
+    }
+  };
+
+  return (
+    <div className="h-screen flex flex-col bg-white border border-gray-200 rounded-xl overflow-hidden">
+      <input
+        ref={inputRef}
+        autoFocus
+        type="text"
+        className="w-full px-4 py-3 text-base border-b border-gray-200 focus:outline-none"
+        placeholder="Ask Exemem..."
+        value={question}
+        onChange={(e) => setQuestion(e.target.value)}
+        onKeyDown={handleKeyDown}
+      />
+      <div className="flex-1 overflow-y-auto px-4 py-3 text-sm text-gray-700 whitespace-pre-wrap">
+        {loading && <p className="text-gray-400">Thinking...</p>}
+        {!loading && error && <p className="text-red-600">{error}</p>}
+        {!loading && !error && answer && answer.answer}
+      </div>
+      <div className="flex items-center justify-between px-4 py-2 border-t border-gray-100 text-xs text-gray-400">
+        <label className="flex items-center gap-1">
+          <input type="checkbox" checked={newSession} onChange={(e) => setNewSession(e.target.checked)} />
+          New session
+        </label>
+        <span>
+          {answer && !loading && (answer.followup ? "Continued last session" : `${answer.result_count ?? 0} results`)}
+          {" · "}Enter to ask, Esc to close
+        </span>
+      </div>
+    </div>
+  );
+}
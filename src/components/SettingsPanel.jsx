@@ -1,5 +1,9 @@
+import { useState, useEffect } from "react";
 import { open } from "@tauri-apps/plugin-shell";
 import { open as openDialog } from "@tauri-apps/plugin-dialog";
+import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../commands";
+import AccountUsage from "./AccountUsage";
 
 const ENV_URLS = {
   Dev: "https://ygyu7ritx8.execute-api.us-west-2.amazonaws.com",
@@ -18,12 +22,101 @@ export default function SettingsPanel({
   setError,
   setSuccess,
   onScanAndWatch,
+  onLock,
 }) {
   const isAuthenticated = !!(config.session_token && config.user_hash && config.api_key);
   const apiBaseUrl = config.environment === "Custom"
     ? config.api_base_url
     : ENV_URLS[config.environment] || ENV_URLS.Dev;
 
+  const queryCache = config.query_cache || { enabled: false, ttl_secs: 300 };
+  const handleClearQueryCache = async () => {
+    try {
+      const cleared = await invoke("clear_query_cache");
+      setSuccess(`Cleared ${cleared} cached ${cleared === 1 ? "answer" : "answers"}`);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const [encrypted, setEncrypted] = useState(false);
+  const [passphrase, setPassphrase] = useState("");
+  const [includeSecrets, setIncludeSecrets] = useState(false);
+  useEffect(() => {
+    invoke("config_encryption_status")
+      .then((status) => setEncrypted(status.encrypted))
+      .catch(() => {});
+  }, []);
+  const handleSetPassphrase = async (next) => {
+    try {
+      await invoke("set_config_passphrase", { passphrase: next });
+      setEncrypted(!!next);
+      setPassphrase("");
+      setSuccess(next ? "Settings are now encrypted" : "Settings are no longer encrypted");
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+  const handleExportSettings = async () => {
+    try {
+      const path = await invoke("export_settings", { includeSecrets });
+      if (path) setSuccess(`Settings exported to ${path}`);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+  const handleImportSettings = async () => {
+    try {
+      const summary = await invoke("import_settings");
+      if (!summary) return;
+      setConfig(summary.config);
+      setSuccess(`Imported settings, ${summary.saved_queries} saved queries and ${summary.profiles} profiles`);
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+  const handleToggleAutostart = async () => {
+    const enabled = !config.launch_at_login;
+    try {
+      await invoke("set_autostart", { enabled });
+      setConfig((prev) => ({ ...prev, launch_at_login: enabled }));
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+  const [telemetryPreview, setTelemetryPreview] = useState(null);
+  const handleTelemetryPreview = async () => {
+    if (telemetryPreview) {
+      setTelemetryPreview(null);
+      return;
+    }
+    try {
+      setTelemetryPreview(await invoke("get_telemetry_preview"));
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+  const handleLock = async () => {
+    try {
+      await invoke("lock_config");
+      onLock();
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  const scanner = config.scanner || { max_depth: 10, max_files: 5000, skip_dirs: [], custom_categories: [] };
+  const updateScanner = (changes) => setConfig((prev) => ({ ...prev, scanner: { ...scanner, ...changes } }));
+
+  const schedule = config.upload_schedule || { enabled: false, start: "01:00", end: "06:00", utc_offset_minutes: 0 };
+  const updateSchedule = (changes) => {
+    // Quiet hours are local times; the backend has no timezone database
+    setConfig((prev) => ({
+      ...prev,
+      upload_schedule: { ...schedule, ...changes, utc_offset_minutes: -new Date().getTimezoneOffset() },
+    }));
+  };
+
   const handleSelectFolder = async () => {
     setError(null);
     try {
@@ -32,7 +125,7 @@ export default function SettingsPanel({
         setConfig((prev) => ({ ...prev, watched_folder: folder }));
       }
     } catch (err) {
-      setError("Folder selection failed: " + String(err));
+      setError("Folder selection failed: " + errorMessage(err));
     }
   };
 
@@ -47,7 +140,7 @@ export default function SettingsPanel({
       setSuccess("Browser opened. Complete sign-in there, then click \"Open Exemem Client\" to return.");
       setTimeout(() => setSuccess(null), 10000);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     }
   };
 
@@ -66,7 +159,7 @@ export default function SettingsPanel({
       setSuccess("Configuration saved.");
       setTimeout(() => setSuccess(null), 3000);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
     }
   };
 
@@ -114,6 +207,7 @@ export default function SettingsPanel({
               <label className="block text-xs text-gray-500 mb-1">API Key</label>
               <input type="password" className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm bg-gray-50" value={config.api_key} readOnly />
             </div>
+            <AccountUsage />
           </div>
         ) : (
           <div className="space-y-2">
@@ -168,6 +262,241 @@ export default function SettingsPanel({
         </button>
       </div>
 
+      <div className="space-y-2">
+        <div className="flex items-center justify-between">
+          <label className="text-sm font-medium text-gray-700">Upload watched files only during quiet hours</label>
+          <button
+            onClick={() => updateSchedule({ enabled: !schedule.enabled })}
+            className={`relative inline-flex h-6 w-11 items-center rounded-full transition-colors ${schedule.enabled ? "bg-primary" : "bg-gray-300"}`}
+          >
+            <span className={`inline-block h-4 w-4 transform rounded-full bg-white transition-transform ${schedule.enabled ? "translate-x-6" : "translate-x-1"}`} />
+          </button>
+        </div>
+        {schedule.enabled && (
+          <div className="flex items-center gap-2 text-sm text-gray-600">
+            <input
+              type="time"
+              className="px-2 py-1 border border-gray-300 rounded-lg text-sm"
+              value={schedule.start}
+              onChange={(e) => updateSchedule({ start: e.target.value })}
+            />
+            <span>to</span>
+            <input
+              type="time"
+              className="px-2 py-1 border border-gray-300 rounded-lg text-sm"
+              value={schedule.end}
+              onChange={(e) => updateSchedule({ end: e.target.value })}
+            />
+          </div>
+        )}
+      </div>
+
+      <div className="space-y-2">
+        <div className="flex items-center justify-between">
+          <label className="text-sm font-medium text-gray-700">Reuse answers to repeated queries</label>
+          <button
+            onClick={() => setConfig((prev) => ({ ...prev, query_cache: { ...queryCache, enabled: !queryCache.enabled } }))}
+            className={`relative inline-flex h-6 w-11 items-center rounded-full transition-colors ${queryCache.enabled ? "bg-primary" : "bg-gray-300"}`}
+          >
+            <span className={`inline-block h-4 w-4 transform rounded-full bg-white transition-transform ${queryCache.enabled ? "translate-x-6" : "translate-x-1"}`} />
+          </button>
+        </div>
+        {queryCache.enabled && (
+          <div className="flex items-center gap-2 text-sm text-gray-600">
+            <span>for</span>
+            <input
+              type="number"
+              min="1"
+              className="w-20 px-2 py-1 border border-gray-300 rounded-lg text-sm"
+              value={Math.round(queryCache.ttl_secs / 60)}
+              onChange={(e) => setConfig((prev) => ({
+                ...prev,
+                query_cache: { ...queryCache, ttl_secs: Math.max(1, Number(e.target.value) || 1) * 60 },
+              }))}
+            />
+            <span>minutes</span>
+            <button
+              onClick={handleClearQueryCache}
+              className="ml-auto text-xs text-gray-500 hover:text-gray-700"
+            >
+              Clear cached answers
+            </button>
+          </div>
+        )}
+      </div>
+
+      <div className="space-y-2">
+        <label className="block text-sm font-medium text-gray-700">Scanning</label>
+        <div className="flex items-center gap-2 text-sm text-gray-600">
+          <span>Up to</span>
+          <input
+            type="number"
+            min="1"
+            max="100000"
+            className="w-24 px-2 py-1 border border-gray-300 rounded-lg text-sm"
+            value={scanner.max_files}
+            onChange={(e) => updateScanner({ max_files: Math.min(100000, Math.max(1, Number(e.target.value) || 1)) })}
+          />
+          <span>files,</span>
+          <input
+            type="number"
+            min="0"
+            max="64"
+            className="w-16 px-2 py-1 border border-gray-300 rounded-lg text-sm"
+            value={scanner.max_depth}
+            onChange={(e) => updateScanner({ max_depth: Math.min(64, Math.max(0, Number(e.target.value) || 0)) })}
+          />
+          <span>folders deep</span>
+        </div>
+        <input
+          type="text"
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+          placeholder="Folders to skip, comma-separated"
+          value={scanner.skip_dirs.join(", ")}
+          onChange={(e) => updateScanner({ skip_dirs: e.target.value.split(",").map((d) => d.trim()).filter(Boolean) })}
+        />
+      </div>
+
+      <div className="flex items-center gap-2 text-sm text-gray-600">
+        <label className="text-sm font-medium text-gray-700">Give up on queries after</label>
+        <input
+          type="number"
+          min="1"
+          max="3600"
+          className="w-20 px-2 py-1 border border-gray-300 rounded-lg text-sm"
+          value={config.query_timeout_secs ?? 120}
+          onChange={(e) => setConfig((prev) => ({
+            ...prev,
+            query_timeout_secs: Math.min(3600, Math.max(1, Number(e.target.value) || 1)),
+          }))}
+        />
+        <span>seconds</span>
+      </div>
+
+      <div className="space-y-2">
+        <label className="block text-sm font-medium text-gray-700">After ingestion</label>
+        <select
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+          value={config.post_ingest_action || "Leave"}
+          onChange={(e) => setConfig((prev) => ({ ...prev, post_ingest_action: e.target.value }))}
+        >
+          <option value="Leave">Leave files in place</option>
+          <option value="MoveToIngested">Move files to an "ingested" subfolder</option>
+          <option value="Delete">Delete files</option>
+        </select>
+        <input
+          type="text"
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+          placeholder="Shell command (receives event JSON on stdin)"
+          value={config.hooks?.command || ""}
+          onChange={(e) => setConfig((prev) => ({ ...prev, hooks: { ...prev.hooks, command: e.target.value || null } }))}
+        />
+        <input
+          type="text"
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+          placeholder="Webhook URL"
+          value={config.hooks?.webhook_url || ""}
+          onChange={(e) => setConfig((prev) => ({ ...prev, hooks: { ...prev.hooks, webhook_url: e.target.value || null } }))}
+        />
+      </div>
+
+      <div className="space-y-2">
+        <label className="block text-sm font-medium text-gray-700">
+          {encrypted ? "Settings are encrypted with a passphrase" : "Encrypt settings with a passphrase"}
+        </label>
+        <div className="flex items-center gap-2">
+          <input
+            type="password"
+            className="flex-1 px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+            placeholder={encrypted ? "New passphrase" : "Passphrase (at least 8 characters)"}
+            value={passphrase}
+            onChange={(e) => setPassphrase(e.target.value)}
+          />
+          <button
+            onClick={() => handleSetPassphrase(passphrase)}
+            disabled={!passphrase}
+            className="px-3 py-2 bg-gray-200 text-gray-700 rounded-lg text-sm hover:bg-gray-300 disabled:opacity-50"
+          >
+            {encrypted ? "Change" : "Encrypt"}
+          </button>
+        </div>
+        {encrypted && (
+          <div className="flex gap-4 text-xs text-gray-500">
+            <button onClick={handleLock} className="hover:text-gray-700">Lock now</button>
+            <button onClick={() => handleSetPassphrase(null)} className="hover:text-gray-700">Remove encryption</button>
+          </div>
+        )}
+      </div>
+
+      <div className="space-y-2">
+        <div className="flex items-center justify-between">
+          <label className="text-sm font-medium text-gray-700">Launch at login</label>
+          <button
+            onClick={handleToggleAutostart}
+            className={`relative inline-flex h-6 w-11 items-center rounded-full transition-colors ${config.launch_at_login ? "bg-primary" : "bg-gray-300"}`}
+          >
+            <span className={`inline-block h-4 w-4 transform rounded-full bg-white transition-transform ${config.launch_at_login ? "translate-x-6" : "translate-x-1"}`} />
+          </button>
+        </div>
+        <p className="text-xs text-gray-500">
+          Start in the tray when you log in, so new files sync without opening the app.
+        </p>
+      </div>
+
+      <div>
+        <label className="block text-sm font-medium text-gray-700 mb-1">Quick query shortcut</label>
+        <input
+          type="text"
+          className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+          placeholder="CommandOrControl+Shift+Space"
+          value={config.quick_query_shortcut ?? ""}
+          onChange={(e) => setConfig((prev) => ({ ...prev, quick_query_shortcut: e.target.value }))}
+        />
+        <p className="mt-1 text-xs text-gray-500">
+          Opens a small answer box from anywhere. Leave empty to turn it off.
+        </p>
+      </div>
+
+      <div className="space-y-2">
+        <div className="flex items-center justify-between">
+          <label className="text-sm font-medium text-gray-700">Share anonymous usage statistics</label>
+          <button
+            onClick={() => setConfig((prev) => ({ ...prev, telemetry_enabled: !prev.telemetry_enabled }))}
+            className={`relative inline-flex h-6 w-11 items-center rounded-full transition-colors ${config.telemetry_enabled ? "bg-primary" : "bg-gray-300"}`}
+          >
+            <span className={`inline-block h-4 w-4 transform rounded-full bg-white transition-transform ${config.telemetry_enabled ? "translate-x-6" : "translate-x-1"}`} />
+          </button>
+        </div>
+        <p className="text-xs text-gray-500">
+          Which features are used, how long they take and what kind of error they hit. Never file names, queries or content.
+          {" "}
+          <button onClick={handleTelemetryPreview} className="text-gray-600 hover:text-gray-800 underline">
+            {telemetryPreview ? "Hide" : "See what would be sent"}
+          </button>
+        </p>
+        {telemetryPreview && (
+          <pre className="max-h-48 overflow-auto p-2 bg-gray-50 border border-gray-200 rounded text-xs text-gray-600">
+            {JSON.stringify(telemetryPreview, null, 2)}
+          </pre>
+        )}
+      </div>
+
+      <div className="space-y-2">
+        <label className="block text-sm font-medium text-gray-700">Move settings to another machine</label>
+        <div className="flex items-center gap-2">
+          <button onClick={handleExportSettings} className="px-3 py-2 bg-gray-200 text-gray-700 rounded-lg text-sm hover:bg-gray-300">
+            Export settings
+          </button>
+          <button onClick={handleImportSettings} className="px-3 py-2 bg-gray-200 text-gray-700 rounded-lg text-sm hover:bg-gray-300">
+            Import settings
+          </button>
+          <label className="flex items-center gap-1 text-xs text-gray-500">
+            <input type="checkbox" checked={includeSecrets} onChange={(e) => setIncludeSecrets(e.target.checked)} />
+            Include API keys and passwords
+          </label>
+        </div>
+      </div>
+
       <div className="flex gap-2 pt-2">
         <button onClick={handleSave} className="flex-1 px-4 py-2 bg-gray-200 text-gray-700 rounded-lg text-sm font-medium hover:bg-gray-300 transition-colors">
           Save Settings
@@ -1,8 +1,11 @@
-import { useState, useEffect, useCallback } from "react";
+import { useState, useEffect, useCallback, useRef } from "react";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
+import { getCurrentWebview } from "@tauri-apps/api/webview";
+import { startCommand, isCancelled, errorMessage } from "../commands";
 import CategoryBadge from "./shared/CategoryBadge";
 import ProgressBar from "./shared/ProgressBar";
+import QuickNote from "./QuickNote";
 
 function StatusIcon({ status }) {
   switch (status) {
@@ -19,8 +22,16 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
   const [subPhase, setSubPhase] = useState("idle"); // idle, scanning, review, ingesting, watching
   const [scanResult, setScanResult] = useState(null);
   const [selectedFiles, setSelectedFiles] = useState(new Set());
+  // Recommended files the user unticked one by one, as opposed to in bulk
+  const [declinedFiles, setDeclinedFiles] = useState(new Set());
   const [showSkipped, setShowSkipped] = useState(false);
   const [ingestionProgress, setIngestionProgress] = useState([]);
+  const [scheduledUploads, setScheduledUploads] = useState([]);
+  const [pendingApprovals, setPendingApprovals] = useState([]);
+  const [syncing, setSyncing] = useState(false);
+  const [dragging, setDragging] = useState(false);
+  const [showNote, setShowNote] = useState(false);
+  const cancelRef = useRef(null);
 
   // Auto-detect if already watching
   useEffect(() => {
@@ -35,13 +46,30 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
     });
 
     const unlistenComplete = listen("ingestion-complete", () => {
+      cancelRef.current = null;
       setSubPhase("watching");
       handleStartWatching();
     });
 
+    const unlistenScheduled = listen("scheduled-uploads", (event) => {
+      setScheduledUploads(event.payload);
+    });
+
+    const unlistenPending = listen("pending-approvals", (event) => {
+      setPendingApprovals(event.payload);
+    });
+
+    invoke("get_ingestion_progress")
+      .then((progress) => setScheduledUploads(progress.filter((p) => p.status === "scheduled")))
+      .catch(() => {});
+
+    invoke("get_pending_approvals").then(setPendingApprovals).catch(() => {});
+
     return () => {
       unlistenProgress.then((f) => f());
       unlistenComplete.then((f) => f());
+      unlistenScheduled.then((f) => f());
+      unlistenPending.then((f) => f());
     };
   }, []);
 
@@ -50,14 +78,19 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
     try {
       await saveConfig(config);
       setSubPhase("scanning");
-      const result = await invoke("scan_folder");
+      const { promise, cancel } = startCommand("scan_folder", {}, { large: true });
+      cancelRef.current = cancel;
+      const result = await promise;
       setScanResult(result);
       const recommended = new Set(result.recommended_files.map((f) => f.path));
       setSelectedFiles(recommended);
+      setDeclinedFiles(new Set());
       setSubPhase("review");
     } catch (err) {
-      setError(String(err));
+      if (!isCancelled(err)) setError(errorMessage(err));
       setSubPhase("idle");
+    } finally {
+      cancelRef.current = null;
     }
   };
 
@@ -71,13 +104,94 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
         return;
       }
       setSubPhase("ingesting");
-      await invoke("approve_and_ingest", { approvedPaths });
+      // Stays cancellable until "ingestion-complete", not just until this returns
+      const declinedPaths = Array.from(declinedFiles).filter((path) => !selectedFiles.has(path));
+      const { promise, cancel } = startCommand("approve_and_ingest", { approvedPaths, declinedPaths });
+      cancelRef.current = cancel;
+      await promise;
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
       setSubPhase("review");
     }
   };
 
+  // Files dropped on the window or picked in a dialog, from anywhere on disk
+  const handleIngestFiles = async (command, args = {}) => {
+    setError(null);
+    try {
+      // Stays cancellable until "ingestion-complete", not just until this returns
+      const { promise, cancel } = startCommand(command, args);
+      cancelRef.current = cancel;
+      const files = await promise;
+      if (files.length > 0) setSubPhase("ingesting");
+      else cancelRef.current = null;
+    } catch (err) {
+      cancelRef.current = null;
+      if (!isCancelled(err)) setError(errorMessage(err));
+    }
+  };
+
+  // Watched files held for approval; without paths, the whole queue
+  const handleApprovePending = async (paths = null) => {
+    setError(null);
+    try {
+      // Stays cancellable until "ingestion-complete", not just until this returns
+      const { promise, cancel } = startCommand("approve_pending", { paths });
+      cancelRef.current = cancel;
+      const count = await promise;
+      if (count > 0) setSubPhase("ingesting");
+      else cancelRef.current = null;
+    } catch (err) {
+      cancelRef.current = null;
+      if (!isCancelled(err)) setError(errorMessage(err));
+    }
+  };
+
+  const handleRejectPending = async (paths = null) => {
+    setError(null);
+    try {
+      await invoke("reject_pending", { paths });
+    } catch (err) {
+      setError(errorMessage(err));
+    }
+  };
+
+  useEffect(() => {
+    const unlistenDrop = getCurrentWebview().onDragDropEvent((event) => {
+      const { type, paths } = event.payload;
+      setDragging(type === "enter" || type === "over");
+      // Ignore drops while a scan or ingestion is running
+      if (type === "drop" && paths.length > 0 && !cancelRef.current) {
+        handleIngestFiles("ingest_files", { paths });
+      }
+    });
+    return () => {
+      unlistenDrop.then((f) => f());
+    };
+  }, []);
+
+  const handleSyncNow = async () => {
+    setError(null);
+    setSyncing(true);
+    try {
+      // Stays cancellable until "ingestion-complete" if anything was queued
+      const { promise, cancel } = startCommand("sync_now");
+      cancelRef.current = cancel;
+      const summary = await promise;
+      if (summary.queued > 0) {
+        setSubPhase("ingesting");
+      } else {
+        cancelRef.current = null;
+        setSuccess(`Up to date: ${summary.unchanged} files already synced`);
+      }
+    } catch (err) {
+      cancelRef.current = null;
+      if (!isCancelled(err)) setError(errorMessage(err));
+    } finally {
+      setSyncing(false);
+    }
+  };
+
   const handleStartWatching = async () => {
     try {
       await invoke("start_watching");
@@ -101,17 +215,33 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
       const status = await invoke("get_sync_status");
       setSyncStatus(status);
     } catch (err) {
-      setError(String(err));
+      setError(errorMessage(err));
+    }
+  };
+
+  const handleRetry = async (filenames) => {
+    setError(null);
+    try {
+      await invoke("retry_ingestion", { filenames });
+    } catch (err) {
+      setError(errorMessage(err));
     }
   };
 
   const toggleFileSelection = (path) => {
+    const deselecting = selectedFiles.has(path);
     setSelectedFiles((prev) => {
       const next = new Set(prev);
-      if (next.has(path)) next.delete(path);
+      if (deselecting) next.delete(path);
       else next.add(path);
       return next;
     });
+    setDeclinedFiles((prev) => {
+      const next = new Set(prev);
+      if (deselecting) next.add(path);
+      else next.delete(path);
+      return next;
+    });
   };
 
   const formatTime = (timestamp) => {
@@ -175,6 +305,12 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
           <h2 className="text-lg font-semibold text-gray-900">Scanning folder...</h2>
           <p className="text-sm text-gray-500 mt-1">Classifying files by category</p>
         </div>
+        <button
+          onClick={() => cancelRef.current?.()}
+          className="px-4 py-2 bg-gray-100 text-gray-700 rounded-lg text-sm font-medium hover:bg-gray-200 transition-colors"
+        >
+          Cancel
+        </button>
       </div>
     );
   }
@@ -222,6 +358,11 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
                 Unknown: {scanResult.summary.unknown_count}
               </span>
             )}
+            {Object.entries(scanResult.summary.custom_counts || {}).map(([name, count]) => count > 0 && (
+              <span key={name} className="inline-flex items-center gap-1 px-2 py-1 rounded bg-indigo-100 text-indigo-700 text-xs font-medium">
+                {name.replace(/_/g, " ")}: {count}
+              </span>
+            ))}
           </div>
         </div>
 
@@ -306,11 +447,16 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
       <div className="bg-white rounded-xl shadow-sm border border-gray-200 p-5 space-y-4">
         <div className="flex items-center justify-between">
           <h2 className="text-sm font-semibold text-gray-700 uppercase tracking-wide">Ingesting</h2>
-          <span className="text-xs text-gray-500">
-            {progressSummary.done}/{ingestionProgress.length} complete
-            {progressSummary.inProgress > 0 && `, ${progressSummary.inProgress} in progress`}
-            {progressSummary.error > 0 && `, ${progressSummary.error} errors`}
-          </span>
+          <div className="flex items-center gap-3">
+            <span className="text-xs text-gray-500">
+              {progressSummary.done}/{ingestionProgress.length} complete
+              {progressSummary.inProgress > 0 && `, ${progressSummary.inProgress} in progress`}
+              {progressSummary.error > 0 && `, ${progressSummary.error} errors`}
+            </span>
+            <button onClick={() => cancelRef.current?.()} className="text-xs text-gray-500 hover:text-gray-700 underline">
+              Cancel
+            </button>
+          </div>
         </div>
 
         <ProgressBar
@@ -343,13 +489,32 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
 
   // Watching
   return (
-    <div className="bg-white rounded-xl shadow-sm border border-gray-200 p-5 space-y-4">
+    <div className={`bg-white rounded-xl shadow-sm border p-5 space-y-4 ${dragging ? "border-blue-400 ring-2 ring-blue-200" : "border-gray-200"}`}>
       <div className="flex items-center justify-between">
         <h2 className="text-sm font-semibold text-gray-700 uppercase tracking-wide">Activity</h2>
         <div className="flex items-center gap-3">
           {syncStatus.folder && (
             <span className="text-xs text-gray-500">{syncStatus.file_count} files</span>
           )}
+          <button
+            onClick={() => setShowNote((open) => !open)}
+            className="px-3 py-1 rounded-lg text-xs font-medium bg-gray-100 text-gray-700 hover:bg-gray-200 transition-colors"
+          >
+            Add note
+          </button>
+          <button
+            onClick={() => handleIngestFiles("pick_and_ingest")}
+            className="px-3 py-1 rounded-lg text-xs font-medium bg-gray-100 text-gray-700 hover:bg-gray-200 transition-colors"
+          >
+            Add files
+          </button>
+          <button
+            onClick={handleSyncNow}
+            disabled={syncing}
+            className="px-3 py-1 rounded-lg text-xs font-medium bg-blue-50 text-blue-700 hover:bg-blue-100 disabled:opacity-50 transition-colors"
+          >
+            {syncing ? "Syncing..." : "Sync now"}
+          </button>
           <button
             onClick={toggleWatching}
             className={`px-3 py-1 rounded-lg text-xs font-medium transition-colors ${
@@ -363,9 +528,64 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
         </div>
       </div>
 
+      {showNote && (
+        <QuickNote
+          setError={setError}
+          onCancel={() => setShowNote(false)}
+          onSubmit={(note) => {
+            setShowNote(false);
+            handleIngestFiles("ingest_text", note);
+          }}
+        />
+      )}
+
+      {scheduledUploads.length > 0 && (
+        <div className="space-y-1">
+          <p className="text-xs font-medium text-gray-500">
+            {scheduledUploads.length} {scheduledUploads.length === 1 ? "upload" : "uploads"} scheduled for quiet hours
+          </p>
+          {scheduledUploads.map((fp) => (
+            <div key={fp.filename} className="flex items-center justify-between px-3 py-1 bg-indigo-50 rounded-lg">
+              <span className="text-sm text-gray-700 truncate flex-1">{fp.filename}</span>
+              <span className="text-xs text-indigo-600 ml-2 whitespace-nowrap">scheduled {formatTime(fp.scheduled_at)}</span>
+            </div>
+          ))}
+        </div>
+      )}
+
+      {pendingApprovals.length > 0 && (
+        <div className="space-y-1">
+          <div className="flex items-center justify-between">
+            <p className="text-xs font-medium text-gray-500">
+              {pendingApprovals.length} {pendingApprovals.length === 1 ? "file" : "files"} waiting for approval
+            </p>
+            <div className="flex gap-2">
+              <button onClick={() => handleApprovePending()} className="text-xs text-blue-600 hover:text-blue-800 underline">
+                Approve all
+              </button>
+              <button onClick={() => handleRejectPending()} className="text-xs text-gray-500 hover:text-gray-700 underline">
+                Reject all
+              </button>
+            </div>
+          </div>
+          {pendingApprovals.map((item) => (
+            <div key={item.path} className="flex items-center gap-2 px-3 py-1 bg-amber-50 rounded-lg">
+              <span className="text-sm text-gray-700 truncate flex-1">{item.recommendation.path}</span>
+              <CategoryBadge category={item.recommendation.category} />
+              <button onClick={() => handleApprovePending([item.path])} className="text-xs text-blue-600 hover:text-blue-800 underline">
+                Approve
+              </button>
+              <button onClick={() => handleRejectPending([item.path])} className="text-xs text-gray-500 hover:text-gray-700 underline">
+                Reject
+              </button>
+            </div>
+          ))}
+        </div>
+      )}
+
       {syncStatus.recent_activity.length === 0 ? (
         <p className="text-sm text-gray-400 text-center py-6">
-          Watching for changes. New files will appear here.
+          Watching for changes. New files will appear here, or drop files on the window to ingest them.
         </p>
       ) : (
         <div className="space-y-2 max-h-80 overflow-y-auto">
@@ -378,7 +598,16 @@ export default function SyncPanel({ config, saveConfig, setError, setSuccess, sy
                   {entry.category && <CategoryBadge category={entry.category} />}
                 </div>
                 {entry.error && <p className="text-xs text-red-500 truncate">{entry.error}</p>}
+                {entry.note && <p className="text-xs text-gray-500 truncate">{entry.note}</p>}
               </div>
+              {entry.status === "Error" && (
+                <button
+                  onClick={() => handleRetry([entry.filename])}
+                  className="text-xs text-blue-600 hover:text-blue-800 underline"
+                >
+                  Retry
+                </button>
+              )}
               <span className="text-xs text-gray-400 whitespace-nowrap">{formatTime(entry.timestamp)}</span>
             </div>
           ))}
@@ -0,0 +1,47 @@
+import { useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { errorMessage } from "../commands";
+
+export default function UnlockPrompt({ onUnlocked, setError }) {
+  const [passphrase, setPassphrase] = useState("");
+  const [unlocking, setUnlocking] = useState(false);
+
+  const handleUnlock = async (e) => {
+    e.preventDefault();
+    setError(null);
+    setUnlocking(true);
+    try {
+      const config = await invoke("unlock_config", { passphrase });
+      setPassphrase("");
+      onUnlocked(config);
+    } catch (err) {
+      setError(errorMessage(err));
+    } finally {
+      setUnlocking(false);
+    }
+  };
+
+  return (
+    <form onSubmit={handleUnlock} className="bg-white rounded-lg border border-gray-200 p-6 space-y-4">
+      <div>
+        <h2 className="text-lg font-semibold text-gray-900">Settings are locked</h2>
+        <p className="text-sm text-gray-500">Enter the passphrase your settings were encrypted with.</p>
+      </div>
+      <input
+        type="password"
+        autoFocus
+        className="w-full px-3 py-2 border border-gray-300 rounded-lg text-sm focus:ring-2 focus:ring-primary focus:border-primary"
+        placeholder="Passphrase"
+        value={passphrase}
+        onChange={(e) => setPassphrase(e.target.value)}
+      />
+      <button
+        type="submit"
+        disabled={!passphrase || unlocking}
+        className="w-full px-4 py-2 bg-primary text-white rounded-lg text-sm font-medium hover:bg-secondary transition-colors disabled:opacity-50"
+      >
+        {unlocking ? "Unlocking..." : "Unlock"}
+      </button>
+    </form>
+  );
+}
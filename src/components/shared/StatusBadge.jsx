@@ -7,6 +7,11 @@ export const CATEGORY_COLORS = {
   unknown: { bg: "bg-gray-100", text: "text-gray-500", label: "Unknown" },
 };
 
+/** Colors for a user-defined scanner category, labelled with its name. */
+export function customCategoryColors(category) {
+  return { bg: "bg-indigo-100", text: "text-indigo-700", label: category.replace(/_/g, " ") };
+}
+
 export default function StatusBadge({ phase, watching }) {
   const labels = {
     settings: "Setup",
@@ -1,10 +1,15 @@
 import React from "react";
 import ReactDOM from "react-dom/client";
+import { getCurrentWindow } from "@tauri-apps/api/window";
 import App from "./App";
+import QuickQuery from "./components/QuickQuery";
 import "./styles.css";
 
+// The quick-query window loads the same page as the main one
+const Root = getCurrentWindow().label === "quick-query" ? QuickQuery : App;
+
 ReactDOM.createRoot(document.getElementById("root")).render(
   <React.StrictMode>
-    <App />
+    <Root />
   </React.StrictMode>
 );
@@ -0,0 +1,31 @@
+/**
+ * Large command results
+ *
+ * Commands that can return big payloads (scan results, query results) accept
+ * `handoff: true`. When the result is too large for IPC the backend writes it
+ * to a temp file and returns `{ payload_handle, size }` instead; this helper
+ * reads it back in chunks and releases it.
+ */
+import { invoke } from "@tauri-apps/api/core";
+
+const CHUNK_SIZE = 4 * 1024 * 1024;
+
+export async function invokeLarge(command, args = {}) {
+  const result = await invoke(command, { ...args, handoff: true });
+  if (!result || typeof result.payload_handle !== "string") {
+    return result;
+  }
+
+  const { payload_handle: handle, size } = result;
+  const bytes = new Uint8Array(size);
+  try {
+    for (let offset = 0; offset < size; offset += CHUNK_SIZE) {
+      const len = Math.min(CHUNK_SIZE, size - offset);
+      const chunk = await invoke("read_payload", { handle, offset, len });
+      bytes.set(new Uint8Array(chunk), offset);
+    }
+  } finally {
+    invoke("release_payload", { handle }).catch(() => {});
+  }
+  return JSON.parse(new TextDecoder().decode(bytes));
+}